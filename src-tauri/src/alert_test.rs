@@ -0,0 +1,256 @@
+//! 设置界面的“测试通知 / 测试提示音”：构造一条标为测试的通知，走真实的系统通知与提示音路径
+//! （隐私级别、模板、重要性映射、规则、静音都照常生效），只有免打扰时段与系统勿扰被显式绕过（用户正在主动测试）。
+//! 返回的报告列出平时会拦截这条通知的策略，也可用来回答“为什么这个应用没有弹出通知”。
+//! 测试通知不入库、不计未读。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::importance::{self, Importance};
+use crate::limits;
+use crate::media;
+use crate::privacy::{self, PreviewLevel, Surface};
+use crate::reminders::TOAST_SOUND;
+use crate::rules::{self, RuleAction};
+use crate::types::Notification;
+
+/// 未指定应用时测试通知使用的包名
+pub const TEST_PACKAGE: &str = "local.test";
+const MAX_SOUND_NAME_BYTES: usize = 128;
+
+/// 会拦截（或隐藏内容）的策略
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum Policy {
+    QuietHours,
+    OsFocus,
+    /// 媒体播放通知从不提醒
+    Media,
+    Rule { rule_id: String, action: RuleAction },
+    Muted,
+    /// 该重要性映射为不弹出 / 不响铃
+    Importance { importance: Importance },
+    /// 内容按隐私级别隐藏（仍会弹出）
+    Privacy { level: PreviewLevel },
+}
+
+impl Policy {
+    /// 免打扰类策略，测试时绕过
+    fn bypassable(&self) -> bool {
+        matches!(self, Policy::QuietHours | Policy::OsFocus)
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Policy::QuietHours => "quiet hours".to_string(),
+            Policy::OsFocus => "system do-not-disturb".to_string(),
+            Policy::Media => "media notification".to_string(),
+            Policy::Rule { rule_id, action } => format!("rule {} ({})", rule_id, label(action)),
+            Policy::Muted => "muted".to_string(),
+            Policy::Importance { importance } => format!("importance {}", label(importance)),
+            Policy::Privacy { level } => format!("privacy level {}", label(level)),
+        }
+    }
+}
+
+/// 枚举的序列化名称（与前端看到的一致）
+fn label<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TestAlertReport {
+    /// 已弹出系统通知
+    pub shown: bool,
+    /// 播放的提示音
+    pub sound: Option<String>,
+    /// 系统通知显示的标题与正文
+    pub title: String,
+    pub body: Option<String>,
+    /// 平时会拦截这条通知的策略（含本次绕过的）
+    pub would_be_blocked_by: Vec<Policy>,
+    /// 其中本次测试绕过的
+    pub bypassed: Vec<Policy>,
+    /// 如 "would be blocked by: quiet hours, privacy level hidden"
+    pub summary: String,
+}
+
+fn test_notification(package_name: Option<String>, now: i64) -> Notification {
+    Notification {
+        id: format!("test-{}", uuid::Uuid::new_v4()),
+        package_name: Some(package_name.unwrap_or_else(|| TEST_PACKAGE.to_string())),
+        title: Some("测试通知".to_string()),
+        text: Some("这是一条测试通知".to_string()),
+        posted_at: Some(now),
+        ..Default::default()
+    }
+}
+
+impl AppState {
+    /// 列出会影响这条通知提醒的策略，与入库流水线（ingest）和 alert_for_new 的判断一致；
+    /// 返回 (拦截弹出的策略, 拦截响铃的策略)
+    fn alert_policies(&self, n: &Notification, minute_of_day: u16, now: i64) -> (Vec<Policy>, Vec<Policy>) {
+        let settings = self.settings.read();
+        let mut toast = Vec::new();
+        if media::is_media(n) {
+            toast.push(Policy::Media);
+        }
+        if let Some(rule) = rules::evaluate(&settings.rules, n) {
+            toast.push(Policy::Rule { rule_id: rule.id.clone(), action: rule.action });
+        }
+        if self.mutes.is_muted(n, now) {
+            toast.push(Policy::Muted);
+        }
+        let behavior = settings.importance.get(n.importance);
+        if !behavior.toast {
+            toast.push(Policy::Importance { importance: n.importance });
+        }
+        let mut quiet = Vec::new();
+        if settings.respect_os_focus && self.os_focus.is_active() {
+            quiet.push(Policy::OsFocus);
+        }
+        if settings.quiet_hours.contains(minute_of_day) {
+            quiet.push(Policy::QuietHours);
+        }
+        let mut sound = toast.clone();
+        if behavior.toast && !behavior.sound {
+            sound.push(Policy::Importance { importance: n.importance });
+        }
+        // 免打扰时段内从不响铃；bypass_quiet_hours 的级别仍会弹出
+        sound.extend(quiet.iter().cloned());
+        if !behavior.bypass_quiet_hours {
+            toast.extend(quiet);
+        }
+        let level = privacy::effective_level(&settings.privacy, n.package_name.as_deref(), Surface::Toast);
+        if level != PreviewLevel::Full {
+            toast.push(Policy::Privacy { level });
+        }
+        (toast, sound)
+    }
+
+    fn test_report(&self, n: &Notification, policies: Vec<Policy>, sound: Option<&str>) -> TestAlertReport {
+        // 隐私级别只隐藏内容，不阻止弹出
+        let shown = policies.iter().all(|p| p.bypassable() || matches!(p, Policy::Privacy { .. }));
+        let sound = sound.filter(|_| shown);
+        if shown {
+            self.show_toast_with_sound(n, sound);
+        }
+        let (title, body) = self.toast_content(n);
+        let bypassed: Vec<Policy> = policies.iter().filter(|p| p.bypassable()).cloned().collect();
+        let summary = if policies.is_empty() {
+            "would be shown normally".to_string()
+        } else {
+            format!("would be blocked by: {}", policies.iter().map(Policy::describe).collect::<Vec<_>>().join(", "))
+        };
+        TestAlertReport {
+            shown,
+            sound: sound.map(str::to_string),
+            title,
+            body,
+            would_be_blocked_by: policies,
+            bypassed,
+            summary,
+        }
+    }
+
+    /// 弹出一条测试通知（可指定应用，按该应用的隐私、规则与静音设置处理）
+    pub fn send_test_toast(&self, package_name: Option<String>) -> Result<TestAlertReport, String> {
+        self.test_toast_at(package_name, importance::local_minute_of_day(), chrono::Utc::now().timestamp())
+    }
+
+    fn test_toast_at(&self, package_name: Option<String>, minute_of_day: u16, now: i64) -> Result<TestAlertReport, String> {
+        if let Some(p) = package_name.as_deref() {
+            limits::check_bytes("package_name", p.len(), limits::MAX_RULE_FIELD_BYTES)?;
+        }
+        let n = test_notification(package_name, now);
+        let (toast, sound) = self.alert_policies(&n, minute_of_day, now);
+        let plays = sound.iter().all(Policy::bypassable);
+        println!("[AlertTest] Test toast for {:?}: {:?}", n.package_name, toast);
+        Ok(self.test_report(&n, toast, plays.then_some(TOAST_SOUND)))
+    }
+
+    /// 播放测试提示音（默认为系统提示音），经系统通知路径播放
+    pub fn play_test_sound(&self, name: Option<String>) -> Result<TestAlertReport, String> {
+        self.test_sound_at(name, importance::local_minute_of_day(), chrono::Utc::now().timestamp())
+    }
+
+    fn test_sound_at(&self, name: Option<String>, minute_of_day: u16, now: i64) -> Result<TestAlertReport, String> {
+        let name = name.filter(|s| !s.trim().is_empty()).unwrap_or_else(|| TOAST_SOUND.to_string());
+        limits::check_bytes("name", name.len(), MAX_SOUND_NAME_BYTES)?;
+        let n = test_notification(None, now);
+        let (_, sound) = self.alert_policies(&n, minute_of_day, now);
+        println!("[AlertTest] Test sound {:?}: {:?}", name, sound);
+        Ok(self.test_report(&n, sound, Some(&name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mutes::MuteTarget;
+    use crate::rules::Rule;
+
+    const NOON: u16 = 12 * 60;
+    const NIGHT: u16 = 23 * 60;
+
+    #[test]
+    fn test_toast_reports_policies() {
+        let state = AppState::default();
+        state.settings.write().quiet_hours.enabled = true;
+        let report = state.test_toast_at(None, NOON, 1_000).unwrap();
+        assert!(report.shown);
+        assert_eq!(report.sound.as_deref(), Some(TOAST_SOUND));
+        assert!(report.would_be_blocked_by.is_empty());
+        assert_eq!(report.title, "测试通知");
+
+        // 免打扰时段与隐私级别：绕过前者，仍按后者隐藏内容
+        state.settings.write().privacy.per_app.insert("com.bank".into(), PreviewLevel::Hidden);
+        let report = state.test_toast_at(Some("com.bank".into()), NIGHT, 1_000).unwrap();
+        assert!(report.shown);
+        assert_eq!(report.sound.as_deref(), Some(TOAST_SOUND));
+        assert_eq!(report.bypassed, [Policy::QuietHours]);
+        assert_eq!(report.title, privacy::HIDDEN_TITLE);
+        assert_eq!(report.summary, "would be blocked by: quiet hours, privacy level hidden");
+        // 测试通知不入库
+        assert_eq!(state.counts().total, 0);
+    }
+
+    #[test]
+    fn test_rules_and_mutes_still_apply() {
+        let state = AppState::default();
+        state.settings.write().rules.push(Rule {
+            id: "no-games".into(),
+            enabled: true,
+            package: Some("com.game.*".into()),
+            channel: None,
+            pattern: None,
+            language: None,
+            action: RuleAction::Drop,
+        });
+        let report = state.test_toast_at(Some("com.game.x".into()), NOON, 1_000).unwrap();
+        assert!(!report.shown);
+        assert_eq!(report.would_be_blocked_by, [Policy::Rule { rule_id: "no-games".into(), action: RuleAction::Drop }]);
+        assert!(report.bypassed.is_empty());
+
+        state.mute(MuteTarget::Notification { package_name: "com.chat".into(), id: "test-*".into() }, None, false).unwrap();
+        let report = state.test_toast_at(Some("com.chat".into()), NOON, chrono::Utc::now().timestamp()).unwrap();
+        assert_eq!(report.would_be_blocked_by, [Policy::Muted]);
+        assert_eq!(report.summary, "would be blocked by: muted");
+    }
+
+    #[test]
+    fn test_sound_policies() {
+        let state = AppState::default();
+        let report = state.test_sound_at(Some("bell".into()), NOON, 1_000).unwrap();
+        assert_eq!(report.sound.as_deref(), Some("bell"));
+
+        state.settings.write().importance.default.sound = false;
+        state.settings.write().quiet_hours.enabled = true;
+        let report = state.test_sound_at(None, NIGHT, 1_000).unwrap();
+        assert!(!report.shown);
+        assert_eq!(report.would_be_blocked_by, [Policy::Importance { importance: Importance::Default }, Policy::QuietHours]);
+        assert_eq!(report.bypassed, [Policy::QuietHours]);
+        assert!(state.test_sound_at(Some("x".repeat(200)), NOON, 1_000).is_err());
+    }
+}
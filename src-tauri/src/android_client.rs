@@ -1,10 +1,17 @@
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
+use crate::capabilities;
+use crate::types::Event;
+use crate::clock::ClockSample;
+use crate::protocol::{self, ProtocolError, ProtocolErrorCounts};
+use crate::trace::{ConnectionTracer, Direction};
+use crate::transport::{TcpTransport, Transport, WsTransport};
+use crate::watch_only::OutboundGate;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthRequest {
     pub action: String,
@@ -12,6 +19,9 @@ pub struct AuthRequest {
     pub request_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// 桌面端支持的能力
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,36 +33,223 @@ pub struct AuthResponse {
     #[serde(rename = "requestId")]
     pub request_id: Option<String>,
     pub pending: Option<bool>,
+    /// 手机端协议版本（旧版不返回）
+    #[serde(default, rename = "protocolVersion")]
+    pub protocol_version: Option<u32>,
+    /// 手机端设备 uuid（旧版不返回）
+    #[serde(default, rename = "deviceUuid")]
+    pub device_uuid: Option<String>,
+    /// 手机端支持的能力（旧版不返回；未知标记原样保留）
+    #[serde(default)]
+    pub capabilities: Option<Vec<String>>,
+}
+
+/// 手机端支持的协议范围与能力（version 请求的响应）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceVersion {
+    #[serde(default, rename = "protocolMin")]
+    pub protocol_min: Option<u32>,
+    #[serde(default, rename = "protocolMax")]
+    pub protocol_max: Option<u32>,
+    /// 只返回当前版本的手机端
+    #[serde(default, rename = "protocolVersion")]
+    pub protocol_version: Option<u32>,
+    #[serde(default)]
+    pub capabilities: Option<Vec<String>>,
+}
+
+/// exchange_raw 收到的原始帧
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawExchange {
+    /// requestId 相同的回复
+    pub reply: Option<String>,
+    /// 窗口内收到的其他帧（按收到顺序）
+    pub others: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Pong {
+    t1: i64,
+    t2: i64,
 }
 
 pub struct AndroidSocketClient {
-    stream: Arc<Mutex<TcpStream>>,
-    #[allow(dead_code)]
+    // 直连 TCP 或经中继的 WebSocket
+    transport: Mutex<Box<dyn Transport>>,
     connection_id: String,
+    // 调试追踪（未开启时只有一次原子读）
+    tracer: Arc<ConnectionTracer>,
+    // 认证响应中的协议版本
+    protocol_version: Mutex<Option<u32>>,
+    // 认证响应中的设备 uuid；登录/授权成功后才有
+    device_uuid: Mutex<Option<String>>,
+    // 认证响应中的能力列表
+    capabilities: Mutex<Option<Vec<String>>>,
+    // 按类别累计的解码失败
+    protocol_errors: Mutex<ProtocolErrorCounts>,
+    authenticated: AtomicBool,
+    // 只读观察模式的出站闸门；加入连接池时换成各客户端共享的那个
+    gate: Arc<OutboundGate>,
 }
 
 impl AndroidSocketClient {
     /// 连接到安卓端socket服务器
-    pub fn connect(host: &str, connection_id: String) -> Result<Self, String> {
+    pub fn connect(host: &str, connection_id: String, tracer: Arc<ConnectionTracer>) -> Result<Self, String> {
         println!("[AndroidClient] Connecting to {}", host);
+        let transport = TcpTransport::connect(host)?;
+        println!("[AndroidClient] Connected to {}", host);
+        Ok(Self::with_transport(Box::new(transport), connection_id, tracer))
+    }
 
-        let stream = TcpStream::connect_timeout(
-            &host.parse().map_err(|e| format!("Invalid host: {}", e))?,
-            Duration::from_secs(10)
-        ).map_err(|e| format!("Connection failed: {}", e))?;
+    /// 经中继连接（双方无法直连时），之后的协议与直连完全相同
+    pub fn connect_via_relay(
+        relay_url: &str,
+        room_token: &str,
+        connection_id: String,
+        tracer: Arc<ConnectionTracer>,
+    ) -> Result<Self, String> {
+        println!("[AndroidClient] Connecting via relay {}", relay_url);
+        let transport = WsTransport::connect(relay_url, room_token)?;
+        println!("[AndroidClient] Joined relay room");
+        Ok(Self::with_transport(Box::new(transport), connection_id, tracer))
+    }
 
-        stream.set_read_timeout(Some(Duration::from_secs(30)))
-            .map_err(|e| format!("Failed to set read timeout: {}", e))?;
+    pub(crate) fn with_transport(transport: Box<dyn Transport>, connection_id: String, tracer: Arc<ConnectionTracer>) -> Self {
+        Self {
+            transport: Mutex::new(transport),
+            connection_id,
+            tracer,
+            protocol_version: Mutex::new(None),
+            device_uuid: Mutex::new(None),
+            capabilities: Mutex::new(None),
+            protocol_errors: Mutex::default(),
+            authenticated: AtomicBool::new(false),
+            gate: Arc::default(),
+        }
+    }
 
-        stream.set_write_timeout(Some(Duration::from_secs(10)))
-            .map_err(|e| format!("Failed to set write timeout: {}", e))?;
+    pub(crate) fn set_gate(&mut self, gate: Arc<OutboundGate>) {
+        self.gate = gate;
+    }
 
-        println!("[AndroidClient] Connected to {}", host);
+    /// 手机端协议版本（认证完成后可用；旧版手机端为 None）
+    pub fn protocol_version(&self) -> Option<u32> {
+        *self.protocol_version.lock()
+    }
 
-        Ok(Self {
-            stream: Arc::new(Mutex::new(stream)),
-            connection_id,
-        })
+    /// 是否已通过登录或授权
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated.load(Ordering::SeqCst)
+    }
+
+    pub fn device_uuid(&self) -> Option<String> {
+        self.device_uuid.lock().clone()
+    }
+
+    /// 手机端声明的能力（认证完成后可用；旧版手机端为 None）
+    pub fn capabilities(&self) -> Option<Vec<String>> {
+        self.capabilities.lock().clone()
+    }
+
+    /// 本连接累计的协议解码失败次数
+    pub fn protocol_errors(&self) -> ProtocolErrorCounts {
+        self.protocol_errors.lock().clone()
+    }
+
+    fn counted<T>(&self, result: Result<T, ProtocolError>) -> Result<T, ProtocolError> {
+        if let Err(e) = &result {
+            *self.protocol_errors.lock().entry(e.kind).or_default() += 1;
+        }
+        result
+    }
+
+    /// 解码一帧通知事件（失败计入协议错误）
+    pub fn decode_event(&self, raw: &str) -> Result<Event, ProtocolError> {
+        self.counted(protocol::decode_event(raw))
+    }
+
+    /// 认证成功：记录手机端返回的协议版本、设备 uuid 与能力列表
+    fn on_authenticated(&self, response: &AuthResponse) {
+        *self.protocol_version.lock() = response.protocol_version;
+        *self.device_uuid.lock() = response.device_uuid.clone();
+        *self.capabilities.lock() = response.capabilities.clone();
+        self.authenticated.store(true, Ordering::SeqCst);
+    }
+
+    /// 发送一条控制消息（如 set_mirroring），不等待响应；观察模式下改变手机状态的消息被拦下
+    pub fn send_action(&self, action: &serde_json::Value) -> Result<(), String> {
+        self.gate.check(&self.connection_id, action["action"].as_str())?;
+        self.send_json(action)
+    }
+
+    /// 调整读超时（低功耗模式）
+    pub fn set_read_timeout(&self, timeout: std::time::Duration) -> Result<(), String> {
+        self.transport.lock().set_read_timeout(timeout)
+    }
+
+    /// 发送 ping 并等待 pong（手机端回填收到与回复时间），用于估算时钟偏差
+    pub fn ping(&self) -> Result<ClockSample, String> {
+        let t0 = chrono::Utc::now().timestamp_millis();
+        let json = serde_json::json!({ "action": "ping", "t0": t0 }).to_string();
+        // 整个往返持有连接，避免与其他请求交错
+        let line = {
+            let mut transport = self.transport.lock();
+            transport.send_line(&json)?;
+            self.tracer.record(&self.connection_id, Direction::Send, &json);
+            transport.recv_line()?
+        };
+        let t3 = chrono::Utc::now().timestamp_millis();
+        self.tracer.record(&self.connection_id, Direction::Recv, &line);
+        let pong: Pong = self.counted(protocol::decode(&line)).map_err(|e| format!("Failed to parse pong: {}", e))?;
+        Ok(ClockSample { t0, t1: pong.t1, t2: pong.t2, t3 })
+    }
+
+    /// 实时查询手机端支持的协议范围与能力（不依赖认证时保存的信息）
+    pub fn query_version(&self) -> Result<DeviceVersion, String> {
+        let json = serde_json::json!({ "action": "version" }).to_string();
+        let line = {
+            let mut transport = self.transport.lock();
+            transport.send_line(&json)?;
+            self.tracer.record(&self.connection_id, Direction::Send, &json);
+            transport.recv_line()?
+        };
+        self.tracer.record(&self.connection_id, Direction::Recv, &line);
+        let version: DeviceVersion =
+            self.counted(protocol::decode(&line)).map_err(|e| format!("Failed to parse version: {}", e))?;
+        if version.capabilities.is_some() {
+            *self.capabilities.lock() = version.capabilities.clone();
+        }
+        Ok(version)
+    }
+
+    /// 发送一行原始 JSON 并在 window 内收取回复（调试控制台，见 raw_frame）：
+    /// 收到 requestId 相同的帧即停止；其他帧按顺序一并返回。结束后把读超时恢复为 restore
+    pub fn exchange_raw(&self, line: &str, request_id: &str, window: Duration, restore: Duration) -> Result<RawExchange, String> {
+        let frame = serde_json::from_str::<serde_json::Value>(line).unwrap_or_default();
+        self.gate.check(&self.connection_id, frame["action"].as_str())?;
+        let mut transport = self.transport.lock();
+        transport.send_line(line)?;
+        self.tracer.record(&self.connection_id, Direction::Send, line);
+        let mut exchange = RawExchange::default();
+        if window.is_zero() {
+            return Ok(exchange);
+        }
+        transport.set_read_timeout(window)?;
+        let deadline = Instant::now() + window;
+        while Instant::now() < deadline {
+            // 超时或连接错误都结束收取（连接错误由事件循环处理）
+            let Ok(reply) = transport.recv_line() else { break };
+            self.tracer.record(&self.connection_id, Direction::Recv, &reply);
+            let correlated = protocol::decode::<serde_json::Value>(&reply)
+                .is_ok_and(|v| v.get("requestId").and_then(|id| id.as_str()) == Some(request_id));
+            if correlated {
+                exchange.reply = Some(reply);
+                break;
+            }
+            exchange.others.push(reply);
+        }
+        transport.set_read_timeout(restore)?;
+        Ok(exchange)
     }
 
     /// 请求授权token（手动输入模式）
@@ -66,6 +263,7 @@ impl AndroidSocketClient {
             action: "request_token".to_string(),
             request_id: request_id.clone(),
             token: None,
+            capabilities: Some(desktop_capabilities()),
         };
 
         self.send_json(&request)?;
@@ -85,7 +283,8 @@ impl AndroidSocketClient {
                 return Err("Authorization rejected by user".to_string());
             }
 
-            if let Some(token) = auth_response.token {
+            if let Some(token) = auth_response.token.clone() {
+                self.on_authenticated(&auth_response);
                 println!("[AndroidClient] Authorization successful, token length: {}", token.len());
                 return Ok(token);
             } else {
@@ -93,7 +292,8 @@ impl AndroidSocketClient {
             }
         }
 
-        if let Some(token) = response.token {
+        if let Some(token) = response.token.clone() {
+            self.on_authenticated(&response);
             Ok(token)
         } else {
             Err(response.message.unwrap_or("Failed to get token".to_string()))
@@ -111,6 +311,7 @@ impl AndroidSocketClient {
             action: "login".to_string(),
             request_id,
             token: Some(token.to_string()),
+            capabilities: Some(desktop_capabilities()),
         };
 
         self.send_json(&request)?;
@@ -118,6 +319,7 @@ impl AndroidSocketClient {
         let response: AuthResponse = self.read_json()?;
 
         if response.success {
+            self.on_authenticated(&response);
             println!("[AndroidClient] Login successful");
             Ok(())
         } else {
@@ -130,34 +332,26 @@ impl AndroidSocketClient {
         let json = serde_json::to_string(data)
             .map_err(|e| format!("Failed to serialize: {}", e))?;
 
-        let mut stream = self.stream.lock();
-        stream.write_all(format!("{}\n", json).as_bytes())
-            .map_err(|e| format!("Failed to send: {}", e))?;
-        stream.flush()
-            .map_err(|e| format!("Failed to flush: {}", e))?;
+        self.transport.lock().send_line(&json)?;
 
-        println!("[AndroidClient] Sent: {}", json);
+        self.tracer.record(&self.connection_id, Direction::Send, &json);
         Ok(())
     }
 
     /// 读取JSON响应
     fn read_json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, String> {
-        let stream = self.stream.lock().try_clone()
-            .map_err(|e| format!("Failed to clone stream: {}", e))?;
-
-        let mut reader = BufReader::new(stream);
-        let mut line = String::new();
+        let line = self.transport.lock().recv_line()?;
 
-        reader.read_line(&mut line)
-            .map_err(|e| format!("Failed to read response: {}", e))?;
+        self.tracer.record(&self.connection_id, Direction::Recv, &line);
 
-        println!("[AndroidClient] Received: {}", line.trim());
-
-        serde_json::from_str(line.trim())
-            .map_err(|e| format!("Failed to parse JSON: {}", e))
+        self.counted(protocol::decode(&line)).map_err(|e| format!("Failed to parse JSON: {}", e))
     }
 }
 
+fn desktop_capabilities() -> Vec<String> {
+    capabilities::DESKTOP.iter().map(|c| c.to_string()).collect()
+}
+
 // 需要添加 rand crate，但为了避免添加新依赖，用时间戳替代
 mod rand {
     pub fn random<T>() -> T
@@ -171,3 +365,29 @@ mod rand {
         T::from(ts % 10000)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use tungstenite::Message;
+
+    /// 模拟中继 + 手机端：加入房间后对 login 回复成功
+    #[test]
+    fn test_login_via_relay() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let phone = std::thread::spawn(move || {
+            let mut ws = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            ws.read().unwrap(); // join_room
+            let req: AuthRequest = serde_json::from_str(ws.read().unwrap().to_text().unwrap()).unwrap();
+            let resp = serde_json::json!({ "success": req.token.as_deref() == Some("secret") });
+            ws.send(Message::Text(resp.to_string())).unwrap();
+            req.action
+        });
+
+        let client = AndroidSocketClient::connect_via_relay(&url, "room", "c1".into(), Arc::default()).unwrap();
+        client.login("secret").unwrap();
+        assert_eq!(phone.join().unwrap(), "login");
+    }
+}
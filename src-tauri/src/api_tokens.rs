@@ -0,0 +1,362 @@
+//! 本地 API 令牌：给不同的客户端（如 Stream Deck 插件只读、自己的脚本完全控制）发放带权限范围的令牌。
+//! 令牌由桌面端生成，只在创建时返回一次明文，保存的是 SHA-256 摘要。
+//! authorize 按路由所需的权限检查请求：缺少/无效令牌 401，权限不足 403。
+//! 长连接（PC 间转发，见 relay）握手时建立 ApiSession，按令牌权限过滤可接收的事件，并每隔 REVALIDATE_EVERY 重新确认令牌未被撤销，
+//! 撤销后已打开的连接会在几秒内被关闭。浏览器扩展桥接（见 bridge）按命令对应的路由调用 authorize_route。
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::commands::AppState;
+use crate::limits;
+
+pub const TOKENS_FILE: &str = "api_tokens.json";
+const TOKEN_PREFIX: &str = "dnl_";
+pub const MAX_TOKENS: usize = 50;
+const MAX_TOKEN_NAME_BYTES: usize = 128;
+/// 已打开的 WebSocket 连接重新确认令牌的间隔
+pub const REVALIDATE_EVERY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    ReadNotifications,
+    WriteActions,
+    /// PC 间转发：接收事件流并回传已读/删除（见 relay）
+    Relay,
+    /// 包含其他所有权限
+    Admin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StoredToken {
+    id: String,
+    name: String,
+    scopes: Vec<ApiScope>,
+    /// 令牌的 SHA-256（十六进制）
+    hash: String,
+    created_at: i64,
+    revoked_at: Option<i64>,
+}
+
+/// 返回给前端的令牌信息（不含摘要）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: i64,
+    pub revoked_at: Option<i64>,
+}
+
+/// 创建结果；token 明文只返回这一次
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub info: ApiTokenInfo,
+    pub token: String,
+}
+
+impl From<&StoredToken> for ApiTokenInfo {
+    fn from(t: &StoredToken) -> Self {
+        ApiTokenInfo { id: t.id.clone(), name: t.name.clone(), scopes: t.scopes.clone(), created_at: t.created_at, revoked_at: t.revoked_at }
+    }
+}
+
+/// 认证失败，status 为 HTTP 状态码（401 / 403）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiAuthError {
+    pub status: u16,
+    pub code: String,
+    pub required: Option<ApiScope>,
+}
+
+impl ApiAuthError {
+    fn unauthorized(code: &str) -> Self {
+        ApiAuthError { status: 401, code: code.to_string(), required: None }
+    }
+}
+
+impl std::fmt::Display for ApiAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ApiAuthError: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<ApiAuthError> for String {
+    fn from(e: ApiAuthError) -> Self {
+        println!("[ApiTokens] Rejected request: {} {}", e.status, e.code);
+        e.to_string()
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn generate_token() -> String {
+    format!("{}{}{}", TOKEN_PREFIX, uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+fn has_scope(scopes: &[ApiScope], required: ApiScope) -> bool {
+    scopes.contains(&ApiScope::Admin) || scopes.contains(&required)
+}
+
+/// 路由所需的权限：令牌管理需要 admin，其余读操作需要 read_notifications，修改操作需要 write_actions
+pub fn route_scope(method: &str, path: &str) -> ApiScope {
+    let path = path.split('?').next().unwrap_or(path);
+    if path.starts_with("/admin") || path.starts_with("/tokens") || path.starts_with("/settings") {
+        return ApiScope::Admin;
+    }
+    match method.to_ascii_uppercase().as_str() {
+        "GET" | "HEAD" | "OPTIONS" => ApiScope::ReadNotifications,
+        _ => ApiScope::WriteActions,
+    }
+}
+
+/// 转发给副电脑的手机端事件（见 relay）
+pub const RELAY_EVENT: &str = "relay-event";
+
+/// 长连接推送的事件所需的权限
+pub fn event_scope(event: &str) -> ApiScope {
+    match event {
+        "background-error" | "connection-trace" | "privacy-changed" | "onboarding-changed" => ApiScope::Admin,
+        RELAY_EVENT => ApiScope::Relay,
+        _ => ApiScope::ReadNotifications,
+    }
+}
+
+/// 从 Authorization 头取出 Bearer 令牌；不带 scheme 的值即令牌本身（桥接请求、转发握手直接带令牌）
+pub fn bearer(header: Option<&str>) -> Option<&str> {
+    let value = header?.trim();
+    let token = match value.split_once(' ') {
+        Some((scheme, token)) => scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())?,
+        None => value,
+    };
+    Some(token).filter(|t| !t.is_empty())
+}
+
+#[derive(Default)]
+pub struct ApiTokens {
+    /// hash -> 令牌
+    tokens: RwLock<HashMap<String, StoredToken>>,
+}
+
+impl ApiTokens {
+    /// 校验令牌并检查权限，返回令牌 id
+    pub fn authorize(&self, token: Option<&str>, required: ApiScope) -> Result<String, ApiAuthError> {
+        let token = token.ok_or_else(|| ApiAuthError::unauthorized("missing_token"))?;
+        let tokens = self.tokens.read();
+        let stored = tokens
+            .get(&hash_token(token))
+            .filter(|t| t.revoked_at.is_none())
+            .ok_or_else(|| ApiAuthError::unauthorized("invalid_token"))?;
+        if !has_scope(&stored.scopes, required) {
+            return Err(ApiAuthError { status: 403, code: "insufficient_scope".into(), required: Some(required) });
+        }
+        Ok(stored.id.clone())
+    }
+
+    /// 按请求方法与路径检查（HTTP 中间件）
+    pub fn authorize_route(&self, authorization: Option<&str>, method: &str, path: &str) -> Result<String, ApiAuthError> {
+        self.authorize(bearer(authorization), route_scope(method, path))
+    }
+
+    /// 长连接握手：按所需的权限校验令牌并建立会话
+    pub fn open_session(&self, authorization: Option<&str>, required: ApiScope, now: Instant) -> Result<ApiSession, ApiAuthError> {
+        let token_id = self.authorize(bearer(authorization), required)?;
+        let scopes = self.tokens.read().values().find(|t| t.id == token_id).map(|t| t.scopes.clone()).unwrap_or_default();
+        Ok(ApiSession { token_id, scopes, checked_at: now, closed: false })
+    }
+
+    pub(crate) fn is_active(&self, id: &str) -> bool {
+        self.tokens.read().values().any(|t| t.id == id && t.revoked_at.is_none())
+    }
+
+    fn list(&self) -> Vec<ApiTokenInfo> {
+        let mut list: Vec<ApiTokenInfo> = self.tokens.read().values().map(ApiTokenInfo::from).collect();
+        list.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        list
+    }
+
+    fn snapshot(&self) -> Vec<StoredToken> {
+        self.tokens.read().values().cloned().collect()
+    }
+}
+
+/// 一个已打开的 WebSocket 连接
+#[derive(Debug)]
+pub struct ApiSession {
+    pub token_id: String,
+    scopes: Vec<ApiScope>,
+    checked_at: Instant,
+    closed: bool,
+}
+
+impl ApiSession {
+    /// 该连接是否可以接收此事件
+    pub fn permits(&self, event: &str) -> bool {
+        !self.closed && has_scope(&self.scopes, event_scope(event))
+    }
+
+    /// 由连接的发送循环定期调用；距上次确认超过 REVALIDATE_EVERY 时重新检查，令牌已撤销则返回 false
+    pub fn still_valid(&mut self, tokens: &ApiTokens, now: Instant) -> bool {
+        if !self.closed && now.duration_since(self.checked_at) >= REVALIDATE_EVERY {
+            self.checked_at = now;
+            self.closed = !tokens.is_active(&self.token_id);
+            if self.closed {
+                println!("[ApiTokens] Closing session for revoked token {}", self.token_id);
+            }
+        }
+        !self.closed
+    }
+}
+
+impl AppState {
+    pub(crate) fn load_api_tokens(&self) {
+        let list = self.storage.load::<Vec<StoredToken>>(TOKENS_FILE).unwrap_or_default();
+        *self.api_tokens.tokens.write() = list.into_iter().map(|t| (t.hash.clone(), t)).collect();
+    }
+
+    fn save_api_tokens(&self) -> Result<(), String> {
+        self.storage.save(TOKENS_FILE, &self.api_tokens.snapshot())
+    }
+
+    pub fn create_api_token(&self, name: &str, scopes: Vec<ApiScope>) -> Result<CreatedApiToken, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(limits::InvalidArgument::new("name", "name must not be empty").into());
+        }
+        limits::check_bytes("name", name.len(), MAX_TOKEN_NAME_BYTES)?;
+        if scopes.is_empty() {
+            return Err(limits::InvalidArgument::new("scopes", "at least one scope is required").into());
+        }
+        let active = self.api_tokens.tokens.read().values().filter(|t| t.revoked_at.is_none()).count();
+        limits::check_items("api_tokens", active + 1, MAX_TOKENS)?;
+
+        let mut scopes = scopes;
+        scopes.sort_by_key(|s| *s as u8);
+        scopes.dedup();
+        let token = generate_token();
+        let stored = StoredToken {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            scopes,
+            hash: hash_token(&token),
+            created_at: chrono::Utc::now().timestamp(),
+            revoked_at: None,
+        };
+        let info = ApiTokenInfo::from(&stored);
+        self.api_tokens.tokens.write().insert(stored.hash.clone(), stored);
+        self.save_api_tokens()?;
+        println!("[ApiTokens] Created {} ({}) with {:?}", info.id, info.name, info.scopes);
+        Ok(CreatedApiToken { info, token })
+    }
+
+    pub fn list_api_tokens(&self) -> Vec<ApiTokenInfo> {
+        self.api_tokens.list()
+    }
+
+    /// 撤销令牌（保留记录以便查看）；已打开的连接在 REVALIDATE_EVERY 内关闭
+    pub fn revoke_api_token(&self, id: &str) -> Result<ApiTokenInfo, String> {
+        let info = {
+            let mut tokens = self.api_tokens.tokens.write();
+            let stored = tokens.values_mut().find(|t| t.id == id).ok_or_else(|| format!("API token not found: {}", id))?;
+            stored.revoked_at.get_or_insert(chrono::Utc::now().timestamp());
+            ApiTokenInfo::from(&*stored)
+        };
+        self.save_api_tokens()?;
+        println!("[ApiTokens] Revoked {} ({})", info.id, info.name);
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(token: &str) -> String {
+        format!("Bearer {}", token)
+    }
+
+    #[test]
+    fn test_missing_revoked_and_under_scoped_tokens() {
+        let state = AppState::default();
+        let read = state.create_api_token("Stream Deck", vec![ApiScope::ReadNotifications]).unwrap();
+        let admin = state.create_api_token("scripts", vec![ApiScope::Admin]).unwrap();
+        let tokens = &state.api_tokens;
+
+        let missing = tokens.authorize_route(None, "GET", "/notifications").unwrap_err();
+        assert_eq!((missing.status, missing.code.as_str()), (401, "missing_token"));
+        let bogus = tokens.authorize_route(Some("Bearer dnl_nope"), "GET", "/notifications").unwrap_err();
+        assert_eq!((bogus.status, bogus.code.as_str()), (401, "invalid_token"));
+        // 桥接请求直接带令牌，不带 Bearer
+        assert_eq!((bearer(Some("Basic abc")), bearer(Some("bearer  dnl_x ")), bearer(Some("dnl_x"))), (None, Some("dnl_x"), Some("dnl_x")));
+
+        let h = header(&read.token);
+        assert_eq!(tokens.authorize_route(Some(&h), "GET", "/notifications?limit=5").unwrap(), read.info.id);
+        let denied = tokens.authorize_route(Some(&h), "POST", "/notifications/1/read").unwrap_err();
+        assert_eq!((denied.status, denied.required), (403, Some(ApiScope::WriteActions)));
+        assert!(String::from(denied).starts_with("ApiAuthError: "));
+        assert_eq!(tokens.authorize_route(Some(&h), "GET", "/tokens").unwrap_err().status, 403);
+
+        let a = header(&admin.token);
+        assert!(tokens.authorize_route(Some(&a), "DELETE", "/notifications/1").is_ok());
+        assert!(tokens.authorize_route(Some(&a), "GET", "/tokens").is_ok());
+
+        state.revoke_api_token(&read.info.id).unwrap();
+        let revoked = tokens.authorize_route(Some(&h), "GET", "/notifications").unwrap_err();
+        assert_eq!((revoked.status, revoked.code.as_str()), (401, "invalid_token"));
+        assert!(state.revoke_api_token("ghost").is_err());
+    }
+
+    #[test]
+    fn test_stored_hashed_and_reloaded() {
+        let dir = crate::storage::temp_dir("api_tokens");
+        let state = AppState::default();
+        state.storage.set_base_dir(dir.clone());
+        let created = state.create_api_token("deck", vec![ApiScope::ReadNotifications, ApiScope::ReadNotifications]).unwrap();
+        assert!(created.token.starts_with(TOKEN_PREFIX));
+        assert_eq!(created.info.scopes, [ApiScope::ReadNotifications]);
+        let raw = std::fs::read_to_string(dir.join(TOKENS_FILE)).unwrap();
+        assert!(!raw.contains(&created.token));
+        assert!(raw.contains(&hash_token(&created.token)));
+
+        let restarted = AppState::default();
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_api_tokens();
+        assert_eq!(restarted.list_api_tokens(), std::slice::from_ref(&created.info));
+        assert!(restarted.api_tokens.authorize(Some(&created.token), ApiScope::ReadNotifications).is_ok());
+        assert!(state.create_api_token("  ", vec![ApiScope::Admin]).is_err());
+        assert!(state.create_api_token("none", vec![]).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_websocket_scopes_and_revocation() {
+        let state = AppState::default();
+        let read = state.create_api_token("deck", vec![ApiScope::ReadNotifications]).unwrap();
+        let write = state.create_api_token("writer", vec![ApiScope::WriteActions]).unwrap();
+        let start = Instant::now();
+
+        let open = |token: Option<&str>| state.api_tokens.open_session(token, ApiScope::ReadNotifications, start);
+        assert_eq!(open(None).unwrap_err().status, 401);
+        assert_eq!(open(Some(&header(&write.token))).unwrap_err().status, 403);
+
+        let mut session = open(Some(&header(&read.token))).unwrap();
+        assert!(session.permits("notification-added"));
+        assert!(!session.permits("connection-trace"));
+        assert!(!session.permits(RELAY_EVENT));
+
+        state.revoke_api_token(&read.info.id).unwrap();
+        // 两次确认之间不查表
+        assert!(session.still_valid(&state.api_tokens, start + Duration::from_millis(500)));
+        assert!(!session.still_valid(&state.api_tokens, start + REVALIDATE_EVERY));
+        assert!(!session.permits("notification-added"));
+    }
+}
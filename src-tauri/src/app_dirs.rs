@@ -0,0 +1,88 @@
+//! 应用目录：数据 / 日志 / 配置 / 备份，以及在系统文件管理器中打开它们。
+//! 目标是枚举而不是任意路径，只能打开这几个已知目录。
+
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+/// 旧版配置目录名（device_uuid.txt 所在位置）
+const CONFIG_DIR_NAME: &str = "notification-listener-project";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DirTarget {
+    Data,
+    Logs,
+    Config,
+    Backup,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RevealResult {
+    pub path: String,
+    /// 是否成功在文件管理器中打开（无图形环境时为 false）
+    pub opened: bool,
+}
+
+/// 配置目录（存放 device_uuid.txt）
+pub fn config_dir() -> Result<PathBuf, String> {
+    let dir = dirs::config_dir().ok_or("Failed to get config directory")?;
+    Ok(dir.join(CONFIG_DIR_NAME))
+}
+
+/// 解析目标目录，不存在时创建
+pub fn resolve(app: &AppHandle, target: DirTarget) -> Result<PathBuf, String> {
+    let path = match target {
+        DirTarget::Data => app.path().app_local_data_dir().map_err(|e| e.to_string())?,
+        DirTarget::Logs => app.path().app_log_dir().map_err(|e| e.to_string())?,
+        DirTarget::Config => config_dir()?,
+        DirTarget::Backup => app
+            .path()
+            .app_local_data_dir()
+            .map_err(|e| e.to_string())?
+            .join("backup"),
+    };
+    std::fs::create_dir_all(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Linux 下没有 DISPLAY / WAYLAND_DISPLAY 视为无图形环境
+fn has_display() -> bool {
+    if cfg!(target_os = "linux") {
+        std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some()
+    } else {
+        true
+    }
+}
+
+/// 解析目录并用系统文件管理器打开；打不开时只返回路径
+pub fn reveal(app: &AppHandle, target: DirTarget) -> Result<RevealResult, String> {
+    use tauri_plugin_opener::OpenerExt;
+
+    let path = resolve(app, target)?;
+    let path_str = path.to_string_lossy().to_string();
+    let opened = has_display()
+        && match app.opener().open_path(path_str.clone(), None::<&str>) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("[AppDirs] Failed to open {}: {}", path_str, e);
+                false
+            }
+        };
+    Ok(RevealResult { path: path_str, opened })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_is_closed_set() {
+        let t: DirTarget = serde_json::from_str("\"logs\"").unwrap();
+        assert_eq!(t, DirTarget::Logs);
+        assert!(serde_json::from_str::<DirTarget>("\"../../etc\"").is_err());
+        assert!(serde_json::from_str::<DirTarget>("\"/tmp\"").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! 应用显示名与颜色标签：包名（如 com.tencent.mm）对用户没有意义，图标也不一定取得到。
+//! 内置一份常见应用的显示名作为默认值，用户设置的覆盖项（保存在设置的 app_meta 中）优先；
+//! 删除覆盖项后回落到内置名称，都没有时各处保持原有显示（列表为包名，托盘与模板为包名末段）。
+//! 列表返回时附带解析后的 app_display_name；托盘最近通知、模板 {app} 与音量统计使用同一解析结果。
+//! 颜色只接受 #RGB / #RRGGBB（统一存为小写），显示名去掉控制字符、合并空白并截断到 MAX_DISPLAY_NAME_CHARS。
+//! 过滤规则预设导出/导入时一并包含覆盖项。
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{AppState, SETTINGS_FILE};
+
+/// 显示名最长字符数
+pub const MAX_DISPLAY_NAME_CHARS: usize = 40;
+
+/// 内置显示名（包名 -> 名称）
+const BUILTIN: &[(&str, &str)] = &[
+    ("com.tencent.mm", "微信"),
+    ("com.tencent.mobileqq", "QQ"),
+    ("com.tencent.wework", "企业微信"),
+    ("com.tencent.tim", "TIM"),
+    ("com.tencent.meeting", "腾讯会议"),
+    ("com.alibaba.android.rimet", "钉钉"),
+    ("com.ss.android.lark", "飞书"),
+    ("com.eg.android.AlipayGphone", "支付宝"),
+    ("com.taobao.taobao", "淘宝"),
+    ("com.tmall.wireless", "天猫"),
+    ("com.jingdong.app.mall", "京东"),
+    ("com.xunmeng.pinduoduo", "拼多多"),
+    ("com.sankuai.meituan", "美团"),
+    ("me.ele", "饿了么"),
+    ("com.sina.weibo", "微博"),
+    ("com.ss.android.ugc.aweme", "抖音"),
+    ("com.smile.gifmaker", "快手"),
+    ("tv.danmaku.bili", "哔哩哔哩"),
+    ("com.xingin.xhs", "小红书"),
+    ("com.zhihu.android", "知乎"),
+    ("com.netease.cloudmusic", "网易云音乐"),
+    ("com.tencent.qqmusic", "QQ音乐"),
+    ("com.sdu.didi.psnger", "滴滴出行"),
+    ("com.autonavi.minimap", "高德地图"),
+    ("com.baidu.BaiduMap", "百度地图"),
+    ("com.MobileTicket", "铁路12306"),
+    ("com.android.mms", "短信"),
+    ("com.google.android.apps.messaging", "Messages"),
+    ("com.android.phone", "电话"),
+    ("com.google.android.dialer", "Phone"),
+    ("com.android.calendar", "日历"),
+    ("com.google.android.calendar", "Google Calendar"),
+    ("com.google.android.gm", "Gmail"),
+    ("com.microsoft.office.outlook", "Outlook"),
+    ("com.microsoft.teams", "Teams"),
+    ("com.whatsapp", "WhatsApp"),
+    ("org.telegram.messenger", "Telegram"),
+    ("org.thoughtcrime.securesms", "Signal"),
+    ("com.discord", "Discord"),
+    ("com.Slack", "Slack"),
+    ("com.facebook.orca", "Messenger"),
+    ("com.instagram.android", "Instagram"),
+    ("com.twitter.android", "X"),
+    ("com.google.android.youtube", "YouTube"),
+    ("com.spotify.music", "Spotify"),
+    ("com.android.vending", "Google Play"),
+];
+
+/// 用户覆盖项；两个字段都为空时等同于没有覆盖
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AppMeta {
+    pub display_name: Option<String>,
+    /// #rgb 或 #rrggbb
+    pub color: Option<String>,
+}
+
+pub type AppMetaMap = BTreeMap<String, AppMeta>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MetaSource {
+    /// 用户设置
+    User,
+    /// 内置名称
+    Builtin,
+    /// 没有任何名称，显示包名
+    Package,
+}
+
+/// 解析后的显示信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ResolvedAppMeta {
+    pub package_name: String,
+    pub display_name: String,
+    pub color: Option<String>,
+    /// display_name 的来源
+    pub source: MetaSource,
+}
+
+fn builtin_name(package: &str) -> Option<&'static str> {
+    BUILTIN.iter().find(|(p, _)| *p == package).map(|(_, name)| *name)
+}
+
+/// 去掉控制字符、合并空白并截断；结果为空时报错
+pub fn sanitize_display_name(name: &str) -> Result<String, String> {
+    let cleaned: String = name.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    let joined = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if joined.is_empty() {
+        return Err("display_name must not be empty".to_string());
+    }
+    Ok(joined.chars().take(MAX_DISPLAY_NAME_CHARS).collect())
+}
+
+/// 校验颜色并统一为小写
+pub fn normalize_color(color: &str) -> Result<String, String> {
+    let hex = color.trim().strip_prefix('#').unwrap_or("");
+    if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("color must be #rgb or #rrggbb, got {:?}", color));
+    }
+    Ok(format!("#{}", hex.to_ascii_lowercase()))
+}
+
+fn validate_package(package: &str) -> Result<(), String> {
+    if package.is_empty() || package.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(format!("invalid package name {:?}", package));
+    }
+    Ok(())
+}
+
+impl AppMeta {
+    /// 规范化显示名与颜色，不合法时报错
+    pub fn normalized(&self) -> Result<Self, String> {
+        Ok(Self {
+            display_name: self.display_name.as_deref().map(sanitize_display_name).transpose()?,
+            color: self.color.as_deref().map(normalize_color).transpose()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.display_name.is_none() && self.color.is_none()
+    }
+}
+
+/// 校验一条覆盖项（设置与预设导入共用）
+pub fn validate_entry(package: &str, meta: &AppMeta) -> Result<(), String> {
+    validate_package(package)?;
+    let normalized = meta.normalized()?;
+    if &normalized != meta {
+        return Err(format!("app_meta for {} is not normalized", package));
+    }
+    Ok(())
+}
+
+pub fn validate(map: &AppMetaMap) -> Result<(), String> {
+    map.iter().try_for_each(|(package, meta)| validate_entry(package, meta))
+}
+
+pub fn resolve(map: &AppMetaMap, package: &str) -> ResolvedAppMeta {
+    let meta = map.get(package);
+    let (display_name, source) = match (meta.and_then(|m| m.display_name.clone()), builtin_name(package)) {
+        (Some(name), _) => (name, MetaSource::User),
+        (None, Some(name)) => (name.to_string(), MetaSource::Builtin),
+        (None, None) => (package.to_string(), MetaSource::Package),
+    };
+    ResolvedAppMeta { package_name: package.to_string(), display_name, color: meta.and_then(|m| m.color.clone()), source }
+}
+
+/// 已命名的应用返回显示名，否则 None
+pub fn display_name(map: &AppMetaMap, package: &str) -> Option<String> {
+    let resolved = resolve(map, package);
+    (resolved.source != MetaSource::Package).then_some(resolved.display_name)
+}
+
+/// 托盘与模板用的短名称：显示名，没有时取包名末段
+pub fn short_name(map: &AppMetaMap, package: &str) -> String {
+    display_name(map, package).unwrap_or_else(|| package.rsplit('.').next().unwrap_or(package).to_string())
+}
+
+impl AppState {
+    /// 设置（或在两项都为空时删除）某个应用的覆盖项，返回解析后的结果
+    pub fn set_app_meta(
+        &self,
+        package: &str,
+        display_name: Option<String>,
+        color: Option<String>,
+    ) -> Result<ResolvedAppMeta, String> {
+        validate_package(package)?;
+        let meta = AppMeta { display_name, color }.normalized()?;
+        let settings = {
+            let mut settings = self.settings.write();
+            let mut next = settings.clone();
+            if meta.is_empty() {
+                next.app_meta.remove(package);
+            } else {
+                next.app_meta.insert(package.to_string(), meta);
+                crate::limits::check_items("settings.app_meta", next.app_meta.len(), crate::limits::MAX_PER_APP_ENTRIES)?;
+            }
+            self.storage.save(SETTINGS_FILE, &next)?;
+            *settings = next.clone();
+            next
+        };
+        let resolved = resolve(&settings.app_meta, package);
+        println!("[AppMeta] {} -> {:?} ({:?})", package, resolved.display_name, resolved.source);
+        self.events.emit("app-meta-changed", self.app_meta_map());
+        self.refresh_tray_menu();
+        Ok(resolved)
+    }
+
+    /// 内置名称与用户覆盖项合并后的完整映射
+    pub fn app_meta_map(&self) -> BTreeMap<String, ResolvedAppMeta> {
+        let map = self.settings.read().app_meta.clone();
+        BUILTIN
+            .iter()
+            .map(|(package, _)| *package)
+            .chain(map.keys().map(String::as_str))
+            .map(|package| (package.to_string(), resolve(&map, package)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_and_colors() {
+        assert_eq!(sanitize_display_name("  工作\n群\t聊 ").unwrap(), "工作 群 聊");
+        assert_eq!(sanitize_display_name(&"长".repeat(100)).unwrap().chars().count(), MAX_DISPLAY_NAME_CHARS);
+        assert!(sanitize_display_name(" \u{7} ").is_err());
+        assert_eq!(normalize_color("#FFAA00").unwrap(), "#ffaa00");
+        assert_eq!(normalize_color("#0aF").unwrap(), "#0af");
+        for bad in ["red", "#12", "#12345g", "ffaa00", "#ffaa0000"] {
+            assert!(normalize_color(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_override_shadows_builtin_and_falls_back() {
+        let state = AppState::default();
+        assert_eq!(resolve(&AppMetaMap::new(), "com.tencent.mm").display_name, "微信");
+        assert_eq!(short_name(&AppMetaMap::new(), "com.corp.oa"), "oa");
+
+        let resolved = state.set_app_meta("com.tencent.mm", Some(" 家人 ".into()), Some("#00AA00".into())).unwrap();
+        assert_eq!((resolved.display_name.as_str(), resolved.source), ("家人", MetaSource::User));
+        assert_eq!(resolved.color.as_deref(), Some("#00aa00"));
+        // 只设颜色：名称仍来自内置
+        let corp = state.set_app_meta("com.corp.oa", None, Some("#123".into())).unwrap();
+        assert_eq!((corp.display_name.as_str(), corp.source), ("com.corp.oa", MetaSource::Package));
+        assert!(state.set_app_meta("com.corp.oa", None, Some("blue".into())).is_err());
+        assert!(state.set_app_meta("com corp", Some("x".into()), None).is_err());
+
+        let map = state.app_meta_map();
+        assert_eq!(map["com.tencent.mm"].display_name, "家人");
+        assert_eq!(map["com.whatsapp"].source, MetaSource::Builtin);
+        assert!(map.contains_key("com.corp.oa"));
+
+        // 删除覆盖项：回落到内置名称
+        let back = state.set_app_meta("com.tencent.mm", None, None).unwrap();
+        assert_eq!((back.display_name.as_str(), back.source, back.color), ("微信", MetaSource::Builtin, None));
+        assert!(!state.settings.read().app_meta.contains_key("com.tencent.mm"));
+        assert!(validate(&state.settings.read().app_meta).is_ok());
+    }
+}
@@ -0,0 +1,287 @@
+//! 破坏性操作审计日志：用户反馈“通知全没了”时，用来判断是删除全部、按条件删除、保留策略淘汰、
+//! 恢复出厂设置、替换导入规则，还是规则大量丢弃造成的。
+//! 每条记录操作、来源（命令 / 后台任务）、影响条数与参数（敏感内容已脱敏）。
+//! 内存中保留最近 MAX_AUDIT_ENTRIES 条；持久化为数据目录下的 audit.ndjson（只追加），
+//! 由写线程完成，记录时只做一次非阻塞发送，写入失败只打印日志，不影响被记录的操作。
+//! 文件超过两倍上限时按最近的记录重写。恢复出厂设置不清空审计日志。
+//! 入库时达到上限的逐条淘汰是常规行为，不记录；维护任务按保留策略批量淘汰时记录。
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::store::NotificationFilter;
+
+pub const AUDIT_FILE: &str = "audit.ndjson";
+/// 保留的记录条数
+pub const MAX_AUDIT_ENTRIES: usize = 1000;
+/// 写线程队列长度
+const QUEUE_CAPACITY: usize = 256;
+/// 同一规则在窗口内丢弃超过该条数时记录一次
+pub const RULE_DROP_THRESHOLD: usize = 50;
+const RULE_DROP_WINDOW_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditSource {
+    Command,
+    Background,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AuditEntry {
+    pub at: i64,
+    pub operation: String,
+    pub source: AuditSource,
+    pub affected: usize,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+/// 同一规则在当前窗口内的丢弃计数
+struct DropWindow {
+    started: i64,
+    count: usize,
+}
+
+#[derive(Default)]
+pub struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>,
+    writer: Mutex<Option<SyncSender<String>>>,
+    rule_drops: Mutex<HashMap<String, DropWindow>>,
+}
+
+/// 文本参数只保留长度
+pub fn redact_text(text: &str) -> String {
+    format!("<{} chars>", text.chars().count())
+}
+
+/// 过滤条件中的搜索词只保留长度
+pub fn redact_filter(filter: &NotificationFilter) -> serde_json::Value {
+    let mut value = serde_json::to_value(filter).unwrap_or_default();
+    if let Some(query) = filter.query.as_deref() {
+        value["query"] = redact_text(query).into();
+    }
+    value
+}
+
+/// 路径只保留文件名
+pub fn redact_path(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn read_entries(path: &Path) -> VecDeque<AuditEntry> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return VecDeque::new();
+    };
+    let mut entries: VecDeque<AuditEntry> = content.lines().filter_map(|l| serde_json::from_str(l).ok()).collect();
+    while entries.len() > MAX_AUDIT_ENTRIES {
+        entries.pop_front();
+    }
+    entries
+}
+
+fn rewrite(path: &Path, entries: &VecDeque<AuditEntry>) -> Result<(), String> {
+    let mut content = String::new();
+    for e in entries {
+        content.push_str(&serde_json::to_string(e).map_err(|e| e.to_string())?);
+        content.push('\n');
+    }
+    crate::storage::write_atomic(path, content.as_bytes())
+}
+
+/// 写线程：逐行追加，行数超过两倍上限时只保留最近的记录
+fn run_writer(path: PathBuf, rx: Receiver<String>, mut lines: usize) {
+    for line in rx {
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| writeln!(f, "{}", line))
+            .map_err(|e| e.to_string());
+        if let Err(e) = result {
+            println!("[Audit] Failed to write {}: {}", path.display(), e);
+            continue;
+        }
+        lines += 1;
+        if lines > MAX_AUDIT_ENTRIES * 2 {
+            let entries = read_entries(&path);
+            match rewrite(&path, &entries) {
+                Ok(()) => lines = entries.len(),
+                Err(e) => println!("[Audit] Failed to trim {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+impl AuditLog {
+    /// 加载已有记录并启动写线程
+    pub fn open(&self, dir: &Path) {
+        let path = dir.join(AUDIT_FILE);
+        let entries = read_entries(&path);
+        let lines = std::fs::read_to_string(&path).map(|c| c.lines().count()).unwrap_or(0);
+        *self.entries.lock() = entries;
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let spawned = std::thread::Builder::new().name("audit-log".into()).spawn(move || run_writer(path, rx, lines));
+        match spawned {
+            Ok(_) => *self.writer.lock() = Some(tx),
+            Err(e) => println!("[Audit] Failed to start writer: {}", e),
+        }
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        println!("[Audit] {} ({:?}) affected={}", entry.operation, entry.source, entry.affected);
+        if let Some(tx) = self.writer.lock().as_ref() {
+            match serde_json::to_string(&entry) {
+                Ok(line) => {
+                    if tx.try_send(line).is_err() {
+                        println!("[Audit] Writer queue full, entry kept in memory only");
+                    }
+                }
+                Err(e) => println!("[Audit] Failed to serialize entry: {}", e),
+            }
+        }
+        let mut entries = self.entries.lock();
+        if entries.len() == MAX_AUDIT_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// (内存中的记录数, 规则丢弃计数窗口数)
+    pub fn buffered(&self) -> (usize, usize) {
+        (self.entries.lock().len(), self.rule_drops.lock().len())
+    }
+
+    /// 最近的记录（新 -> 旧）
+    pub fn recent(&self, limit: usize) -> Vec<AuditEntry> {
+        self.entries.lock().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// 规则丢弃一条通知；窗口内恰好达到阈值时返回该计数（每个窗口只返回一次）
+    fn note_rule_drop(&self, rule_id: &str, now: i64) -> Option<usize> {
+        let mut drops = self.rule_drops.lock();
+        if !drops.contains_key(rule_id) {
+            // 已删除或不再命中的规则：窗口过期即移除，不随规则增删累积
+            drops.retain(|_, w| now - w.started < RULE_DROP_WINDOW_SECS);
+        }
+        let window = drops.entry(rule_id.to_string()).or_insert(DropWindow { started: now, count: 0 });
+        if now - window.started >= RULE_DROP_WINDOW_SECS {
+            *window = DropWindow { started: now, count: 0 };
+        }
+        window.count += 1;
+        (window.count == RULE_DROP_THRESHOLD).then_some(window.count)
+    }
+}
+
+impl AppState {
+    pub(crate) fn audit(&self, operation: &str, source: AuditSource, affected: usize, params: serde_json::Value) {
+        self.audit.record(AuditEntry {
+            at: chrono::Utc::now().timestamp(),
+            operation: operation.to_string(),
+            source,
+            affected,
+            params,
+        });
+    }
+
+    /// 规则丢弃较多时记录（单条丢弃不记录）
+    pub(crate) fn audit_rule_drop(&self, rule_id: &str, package: Option<&str>) {
+        let now = crate::wall_clock::mono_secs();
+        if let Some(count) = self.audit.note_rule_drop(rule_id, now) {
+            self.audit(
+                "rule_drops",
+                AuditSource::Background,
+                count,
+                serde_json::json!({ "rule_id": rule_id, "package": package, "window_secs": RULE_DROP_WINDOW_SECS }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bulk::BulkAction;
+    use crate::types::{Event, Notification};
+
+    fn fill(state: &AppState, n: usize) {
+        for i in 0..n {
+            state.ingest_event(Event {
+                event_type: "added".into(),
+                seq: 0,
+                notification: Some(Notification {
+                    id: i.to_string(),
+                    package_name: Some("com.example".into()),
+                    title: Some("t".into()),
+                    ..Default::default()
+                }),
+                id: None,
+            });
+        }
+    }
+
+    #[test]
+    fn test_destructive_operations_recorded() {
+        let state = AppState::default();
+        fill(&state, 10);
+        let filter = NotificationFilter { query: Some("secret words".into()), ..Default::default() };
+        state.apply_where(BulkAction::Delete, &filter, true);
+        state.apply_where(BulkAction::MarkRead, &filter, false);
+        assert!(state.audit.recent(10).is_empty(), "dry runs and read marks are not destructive");
+        let filter = NotificationFilter { package: Some("com.example".into()), query: Some("t".into()), ..Default::default() };
+        state.apply_where(BulkAction::Delete, &filter, false);
+        fill(&state, 3);
+        state.factory_reset().unwrap();
+
+        let log = state.audit.recent(10);
+        let ops: Vec<(&str, usize)> = log.iter().map(|e| (e.operation.as_str(), e.affected)).collect();
+        assert_eq!(ops, [("factory_reset", 3), ("delete_where", 10)]);
+        assert_eq!(log[1].params["filter"]["package"], "com.example");
+        assert_eq!(log[1].params["filter"]["query"], "<1 chars>");
+        assert_eq!(log[1].source, AuditSource::Command);
+        assert_eq!(redact_text("secret words"), "<12 chars>");
+        assert_eq!(redact_path(Path::new("/home/me/presets/work.json")), "work.json");
+    }
+
+    #[test]
+    fn test_persisted_bounded_and_survives_restart() {
+        let dir = crate::storage::temp_dir("audit");
+        let log = AuditLog::default();
+        log.open(&dir);
+        for i in 0..(MAX_AUDIT_ENTRIES * 2 + 5) {
+            log.record(AuditEntry { at: i as i64, operation: "delete_all".into(), source: AuditSource::Command, affected: i, params: serde_json::Value::Null });
+            // 写线程是异步的，队列满时只保留在内存
+            if i % 100 == 0 {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+        }
+        assert_eq!(log.recent(usize::MAX).len(), MAX_AUDIT_ENTRIES);
+        drop(log);
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        let reopened = AuditLog::default();
+        reopened.open(&dir);
+        let recent = reopened.recent(usize::MAX);
+        assert!(!recent.is_empty() && recent.len() <= MAX_AUDIT_ENTRIES);
+        assert_eq!(recent[0].affected, MAX_AUDIT_ENTRIES * 2 + 4);
+        let lines = std::fs::read_to_string(dir.join(AUDIT_FILE)).unwrap().lines().count();
+        assert!(lines <= MAX_AUDIT_ENTRIES * 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_rule_drops_recorded_once_per_window() {
+        let log = AuditLog::default();
+        let hits: Vec<usize> = (0..RULE_DROP_THRESHOLD * 2).filter_map(|i| log.note_rule_drop("spam", i as i64 / 10)).collect();
+        assert_eq!(hits, [RULE_DROP_THRESHOLD]);
+        assert_eq!(log.note_rule_drop("spam", 1000), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! 冷启动与吞吐基准：在独立的临时 AppState 上生成示例通知，测量加载、首页列表、持续入库与搜索耗时，
+//! 与宽松的预算比较，只有数量级上的退化才会超出预算（单元测试中断言，运行中的应用经 run_benchmarks 报告）。
+//! 示例通知由 demo_notifications 生成，add_dummy 也用它。
+//! run_benchmarks 只在开启 dev-tools 特性的构建中可用；不影响用户数据，只读写系统临时目录。
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{AppState, ListOptions};
+use crate::types::{Event, Notification};
+
+pub const AVAILABLE: bool = cfg!(any(test, feature = "dev-tools"));
+pub const UNAVAILABLE: &str = "Benchmarks require a build with the dev-tools feature";
+
+pub const DEFAULT_FIXTURE: usize = 50_000;
+pub const MAX_FIXTURE: usize = 200_000;
+/// 持续入库测量的事件数
+const INGEST_EVENTS: usize = 5_000;
+const SEARCH_QUERY: &str = "示例 chat";
+
+const DEMO_PACKAGES: &[&str] = &["com.demo.app", "com.demo.chat", "com.demo.mail", "com.demo.news", "com.demo.bank"];
+const DEMO_WORDS: &[&str] = &["会议", "快递", "验证码", "chat", "invoice", "reminder", "更新"];
+
+/// 预算（调试构建下也应满足，比正常值宽松一个数量级以上）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Budget {
+    pub load_ms: u64,
+    pub first_page_ms: u64,
+    pub min_ingest_per_sec: u64,
+    pub search_ms: u64,
+}
+
+pub const BUDGET: Budget = Budget { load_ms: 30_000, first_page_ms: 1_000, min_ingest_per_sec: 200, search_ms: 5_000 };
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BenchReport {
+    pub fixture_size: usize,
+    pub load_ms: u64,
+    pub first_page_ms: u64,
+    pub ingest_events: usize,
+    pub ingest_per_sec: u64,
+    pub search_ms: u64,
+    pub search_hits: usize,
+    pub budget: Budget,
+    /// 超出预算的项目
+    pub over_budget: Vec<String>,
+}
+
+/// 示例通知：id 为 `{prefix}-{序号}`，posted_at 从 start 起每条加一秒，包名与内容轮换
+pub fn demo_notifications(prefix: &str, start: i64, count: usize) -> Vec<Notification> {
+    (0..count)
+        .map(|i| Notification {
+            id: format!("{}-{}", prefix, i),
+            package_name: Some(DEMO_PACKAGES[i % DEMO_PACKAGES.len()].to_string()),
+            title: Some(format!("演示标题 {}", i + 1)),
+            text: Some(format!("这是第 {} 条示例通知（{}）", i + 1, DEMO_WORDS[i % DEMO_WORDS.len()])),
+            posted_at: Some(start + i as i64),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn ms(d: Duration) -> u64 {
+    d.as_millis() as u64
+}
+
+/// 在 dir 下写入 fixture_size 条本地通知，测量冷启动加载与随后的查询、入库
+pub fn run(dir: &Path, fixture_size: usize) -> Result<BenchReport, String> {
+    let fixture_size = fixture_size.clamp(1, MAX_FIXTURE);
+    let start = chrono::Utc::now().timestamp() - fixture_size as i64 - INGEST_EVENTS as i64;
+    let fixture: Vec<Notification> = demo_notifications("bench", start, fixture_size)
+        .into_iter()
+        .map(|n| Notification { local: true, ..n })
+        .collect();
+    let storage = crate::storage::Storage::default();
+    storage.set_base_dir(dir.to_path_buf());
+    storage.save(crate::reminders::REMINDERS_FILE, &fixture)?;
+    drop(fixture);
+
+    let state = AppState::default();
+    let started = Instant::now();
+    state.init_storage(dir.to_path_buf());
+    let load = started.elapsed();
+    // 测的是大存储上的入库，不让保留策略逐条淘汰
+    state.settings.write().retention.max_items = fixture_size + INGEST_EVENTS;
+    let loaded = state.counts().total;
+    if loaded != fixture_size {
+        return Err(format!("Loaded {} of {} fixture notifications", loaded, fixture_size));
+    }
+
+    let started = Instant::now();
+    let page = state.list_notifications(ListOptions { with_relative_time: true, limit: Some(50), ..Default::default() })?;
+    let first_page = started.elapsed();
+    if page.is_empty() {
+        return Err("First page is empty".to_string());
+    }
+
+    // 经完整入库流程（时钟校正、规则、索引、事件）
+    let events = demo_notifications("bench-live", start + fixture_size as i64, INGEST_EVENTS);
+    let started = Instant::now();
+    for n in events {
+        state.ingest_from("bench", Event { event_type: "added".into(), seq: 0, notification: Some(n), id: None });
+    }
+    let ingest = started.elapsed();
+    let ingest_per_sec = (INGEST_EVENTS as f64 / ingest.as_secs_f64().max(1e-6)) as u64;
+
+    let started = Instant::now();
+    let hits = state.search_notifications(SEARCH_QUERY, None)?.len();
+    let search = started.elapsed();
+
+    let mut report = BenchReport {
+        fixture_size,
+        load_ms: ms(load),
+        first_page_ms: ms(first_page),
+        ingest_events: INGEST_EVENTS,
+        ingest_per_sec,
+        search_ms: ms(search),
+        search_hits: hits,
+        budget: BUDGET,
+        over_budget: Vec::new(),
+    };
+    for (name, over) in [
+        ("load", report.load_ms > BUDGET.load_ms),
+        ("first_page", report.first_page_ms > BUDGET.first_page_ms),
+        ("ingest", report.ingest_per_sec < BUDGET.min_ingest_per_sec),
+        ("search", report.search_ms > BUDGET.search_ms),
+    ] {
+        if over {
+            report.over_budget.push(name.to_string());
+        }
+    }
+    println!("[Bench] {:?}", report);
+    Ok(report)
+}
+
+/// run_benchmarks 命令：使用系统临时目录，结束后删除
+pub fn run_in_temp_dir(fixture_size: Option<usize>) -> Result<BenchReport, String> {
+    if !AVAILABLE {
+        return Err(UNAVAILABLE.to_string());
+    }
+    let dir = std::env::temp_dir().join(format!("nl-bench-{}", uuid::Uuid::new_v4()));
+    let result = run(&dir, fixture_size.unwrap_or(DEFAULT_FIXTURE));
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        println!("[Bench] Failed to remove {}: {}", dir.display(), e);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_fixture_shape() {
+        let list = demo_notifications("demo", 100, 12);
+        assert_eq!(list.len(), 12);
+        assert_eq!((list[0].id.as_str(), list[0].posted_at), ("demo-0", Some(100)));
+        assert_eq!(list[6].package_name, list[1].package_name);
+        assert!(list.iter().all(|n| !n.read && !n.local));
+    }
+
+    #[test]
+    fn test_performance_budget() {
+        let report = run_in_temp_dir(None).unwrap();
+        assert_eq!(report.fixture_size, DEFAULT_FIXTURE);
+        assert!(report.search_hits > 0);
+        assert!(report.over_budget.is_empty(), "{:?}", report);
+    }
+}
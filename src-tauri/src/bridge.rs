@@ -0,0 +1,168 @@
+//! 本机桥接服务：浏览器扩展的 native messaging host 由浏览器启动，是独立的进程，
+//! 经这里转发到正在运行的实例（见 native_host）。
+//! 只监听 127.0.0.1 的随机端口，端口号写入配置目录下的 PORT_FILE；每个连接一行一个 JSON 请求/响应。
+//! 每个请求都带本地 API 令牌，按命令对应的路由检查权限（与 REST 相同：读需要 read_notifications，修改需要 write_actions）。
+//! 只开放最小的命令集：get_counts、list、mark_read、focus。
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use crate::commands::AppState;
+use crate::startup::Subsystem;
+use crate::store::NotificationFilter;
+
+pub const PORT_FILE: &str = "bridge_port";
+/// list 单页上限
+pub const MAX_LIST_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeRequest {
+    /// 原样带回响应中（扩展用来匹配请求）
+    #[serde(default)]
+    pub id: serde_json::Value,
+    pub token: Option<String>,
+    pub command: String,
+    #[serde(default)]
+    pub args: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BridgeResponse {
+    pub id: serde_json::Value,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BridgeResponse {
+    pub fn ok(id: serde_json::Value, result: impl Serialize) -> Self {
+        Self { id, ok: true, result: Some(serde_json::to_value(result).unwrap_or_default()), error: None }
+    }
+
+    pub fn err(id: serde_json::Value, error: impl Into<String>) -> Self {
+        Self { id, ok: false, result: None, error: Some(error.into()) }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ListArgs {
+    offset: usize,
+    limit: Option<usize>,
+    unread_only: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct MarkReadArgs {
+    ids: Vec<String>,
+}
+
+/// 命令对应的路由（用于权限检查）
+fn route(command: &str) -> Option<(&'static str, &'static str)> {
+    match command {
+        "get_counts" => Some(("GET", "/counts")),
+        "list" => Some(("GET", "/notifications")),
+        "mark_read" => Some(("POST", "/notifications/read")),
+        "focus" => Some(("POST", "/window/focus")),
+        _ => None,
+    }
+}
+
+fn args<T: for<'de> Deserialize<'de> + Default>(value: serde_json::Value) -> Result<T, String> {
+    if value.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(value).map_err(|e| format!("Invalid args: {}", e))
+}
+
+impl AppState {
+    /// 处理一条桥接请求；focus 由调用方提供（需要 AppHandle）
+    pub(crate) fn handle_bridge_request(&self, request: BridgeRequest, focus: &dyn Fn()) -> BridgeResponse {
+        let id = request.id;
+        let Some((method, path)) = route(&request.command) else {
+            return BridgeResponse::err(id, format!("Unknown command: {}", request.command));
+        };
+        if let Err(e) = self.api_tokens.authorize_route(request.token.as_deref(), method, path) {
+            return BridgeResponse::err(id, String::from(e));
+        }
+        if let Err(e) = self.ensure_ready(Subsystem::Store) {
+            return BridgeResponse::err(id, e);
+        }
+        let result = match request.command.as_str() {
+            "get_counts" => Ok(serde_json::to_value(self.counts()).unwrap_or_default()),
+            "list" => args::<ListArgs>(request.args).map(|a| {
+                let filter = NotificationFilter { unread: a.unread_only.then_some(true), muted: Some(false), ..Default::default() };
+                let limit = a.limit.unwrap_or(MAX_LIST_LIMIT).min(MAX_LIST_LIMIT);
+                let sort = self.resolve_sort(None);
+                let list = self.store.lock().unwrap().query_filter(sort, &filter, a.offset, Some(limit));
+                serde_json::to_value(list).unwrap_or_default()
+            }),
+            "mark_read" => args::<MarkReadArgs>(request.args)
+                .and_then(|a| self.mark_read_ids(&a.ids))
+                .map(|changed| serde_json::json!({ "changed": changed })),
+            _ => {
+                focus();
+                Ok(serde_json::Value::Null)
+            }
+        };
+        match result {
+            Ok(value) => BridgeResponse::ok(id, value),
+            Err(e) => BridgeResponse::err(id, e),
+        }
+    }
+}
+
+/// 处理一个连接：逐行读取请求并回复，直到对端关闭
+pub fn serve_connection(stream: TcpStream, handler: &dyn Fn(BridgeRequest) -> BridgeResponse) -> Result<(), String> {
+    let mut writer = stream.try_clone().map_err(|e| format!("Failed to clone stream: {}", e))?;
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|e| format!("Failed to read request: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<BridgeRequest>(&line) {
+            Ok(request) => handler(request),
+            Err(e) => BridgeResponse::err(serde_json::Value::Null, format!("Invalid request: {}", e)),
+        };
+        let json = serde_json::to_string(&response).map_err(|e| e.to_string())?;
+        writer
+            .write_all(format!("{}\n", json).as_bytes())
+            .map_err(|e| format!("Failed to send response: {}", e))?;
+    }
+    Ok(())
+}
+
+/// 接受连接，每个连接一个线程
+pub fn serve(listener: TcpListener, handler: Arc<dyn Fn(BridgeRequest) -> BridgeResponse + Send + Sync>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let handler = handler.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = serve_connection(stream, &*handler) {
+                println!("[Bridge] {}", e);
+            }
+        });
+    }
+}
+
+/// 启动桥接服务并写入端口文件（setup 时调用）
+pub fn start(app: tauri::AppHandle) -> Result<u16, String> {
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| format!("Failed to bind bridge: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let dir = crate::app_dirs::config_dir()?;
+    crate::storage::write_atomic(&dir.join(PORT_FILE), port.to_string().as_bytes())?;
+    let handler = Arc::new(move |request: BridgeRequest| {
+        let state = app.state::<AppState>();
+        state.handle_bridge_request(request, &|| crate::ensure_main_window_visible(&app))
+    });
+    std::thread::spawn(move || serve(listener, handler));
+    println!("[Bridge] Listening on 127.0.0.1:{}", port);
+    Ok(port)
+}
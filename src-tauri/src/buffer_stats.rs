@@ -0,0 +1,154 @@
+//! 长时间运行的自检：列出所有有上限的内存结构当前的大小与上限，供浸泡测试（和好奇的用户）确认曲线是平的。
+//! 计数器与序号的溢出策略：统计计数一律饱和（停在最大值，不回绕成看似重置的小数）；
+//! 变更序号与事件流序号用尽时开始新的流（见 NotificationStore::bump_seq、SubscriptionHub::publish），不回绕到旧值。
+//! 按连接的结构报告各连接中最大的那个；按时间窗口的结构同时有条数上限，突发时不会随速率增长。
+//! get_internal_buffer_stats 只在开启 dev-tools 特性的构建中可用。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+pub const AVAILABLE: bool = cfg!(any(test, feature = "dev-tools"));
+pub const UNAVAILABLE: &str = "Buffer statistics require a build with the dev-tools feature";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BufferStat {
+    pub name: String,
+    pub len: usize,
+    /// 上限；随连接数或规则数变化的结构为 None
+    pub capacity: Option<usize>,
+}
+
+fn stat(name: &str, len: usize, capacity: Option<usize>) -> BufferStat {
+    BufferStat { name: name.to_string(), len, capacity }
+}
+
+impl AppState {
+    pub fn internal_buffer_stats(&self) -> Result<Vec<BufferStat>, String> {
+        if !AVAILABLE {
+            return Err(UNAVAILABLE.to_string());
+        }
+        let (retention, webhook_cap, dismiss_threshold, batch) = {
+            let settings = self.settings.read();
+            (settings.retention.max_items, settings.webhook.max_pending, settings.phone_dismiss_burst.threshold, self.events.batcher_stats().config)
+        };
+        let (total, snoozed) = {
+            let store = self.store.lock().unwrap();
+            (store.counts().total, store.snoozed().len())
+        };
+        let (audit_entries, rule_drop_windows) = self.audit.buffered();
+        let (batch_window, batch_pending) = self.events.batcher_buffered();
+        Ok(vec![
+            // 置顶的通知不受保留上限约束
+            stat("store.notifications", total, Some(retention)),
+            stat("store.snoozed", snoozed, None),
+            stat("removal_log", self.removal_log.lock().unwrap().recent(usize::MAX).len(), Some(crate::tombstones::MAX_TOMBSTONES)),
+            stat("audit.entries", audit_entries, Some(crate::audit::MAX_AUDIT_ENTRIES)),
+            stat("audit.rule_drop_windows", rule_drop_windows, None),
+            stat("errors", self.errors.recent(usize::MAX).len(), Some(crate::error_bus::MAX_BUFFERED)),
+            stat("events.batch_window", batch_window, Some(batch.refetch_above + 1)),
+            stat("events.batch_pending", batch_pending, Some(batch.max_batch)),
+            stat("events.window_feed", self.events.window_feed().buffered(), Some(crate::window_feed::FEED_CAPACITY)),
+            stat("subscriptions.ring", self.subscriptions.max_buffered(), Some(crate::subscriptions::RING_CAPACITY)),
+            stat("trace.pending_requests", self.tracer.pending_requests(), Some(crate::trace::MAX_PENDING)),
+            stat("inspector.frames", self.tracer.inspector.max_buffered(), Some(crate::inspector::MAX_FRAMES_PER_CONNECTION)),
+            stat("link_quality.window", self.link_quality.max_window(), Some(crate::link_quality::MAX_WINDOW_FRAMES)),
+            stat("dismissals.recent", self.dismissals.max_recent(), Some(dismiss_threshold + 1)),
+            stat("rule_review", self.rule_review.len(), Some(crate::rule_review::REVIEW_CAPACITY)),
+            stat("webhook.pending", self.webhook_status().pending, Some(webhook_cap)),
+            stat("mutes", self.mutes.snapshot().len(), Some(crate::mutes::MAX_MUTES)),
+            stat("volume.packages", self.tracked_packages(), Some(crate::volume::MAX_TRACKED_PACKAGES)),
+            stat("pairing_history", self.pairing_history().len(), Some(crate::pairing_payload::MAX_HISTORY)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    use crate::error_bus::BackgroundError;
+    use crate::rules::{Rule, RuleAction};
+    use crate::trace::Direction;
+    use crate::types::{Event, Notification};
+
+    /// 浸泡测试的事件数；完整浸泡用 SOAK_EVENTS=3000000 运行
+    fn soak_events() -> usize {
+        std::env::var("SOAK_EVENTS").ok().and_then(|v| v.parse().ok()).unwrap_or(50_000)
+    }
+
+    fn event(i: usize) -> Event {
+        let seq = i as i64 + 1;
+        let id = format!("n{}", i % 3_000);
+        match i % 20 {
+            0..=1 => Event { event_type: "removed".into(), seq, notification: None, id: Some(id) },
+            2 if i % 10_000 == 2 => Event { event_type: "clear_all".into(), seq, notification: None, id: None },
+            _ => Event {
+                event_type: if i % 20 < 5 { "updated" } else { "added" }.into(),
+                seq,
+                notification: Some(Notification {
+                    id,
+                    package_name: Some(format!("com.soak.app{}", i % 80)),
+                    title: Some(format!("title {}", i % 7)),
+                    text: Some(if i % 10 == 3 { format!("promo {}", i) } else { format!("text {}", i) }),
+                    posted_at: Some(1_700_000_000 + i as i64),
+                    ..Default::default()
+                }),
+                id: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_soak_structures_stay_bounded() {
+        let state = AppState::default();
+        state.settings.write().retention.max_items = 1_000;
+        state.settings.write().rules.push(Rule {
+            id: "promo".into(),
+            enabled: true,
+            package: None,
+            channel: None,
+            pattern: Some("promo".into()),
+            language: None,
+            action: RuleAction::Drop,
+        });
+        state.tracer.start("soak", Instant::now());
+
+        let total = soak_events();
+        let checkpoints = 10;
+        let mut peak: HashMap<String, usize> = HashMap::new();
+        for i in 0..total {
+            state.tracer.record("soak", Direction::Send, &format!(r#"{{"action":"ping","requestId":"r{}"}}"#, i));
+            state.ingest_from("soak", event(i));
+            if i % 1_000 == 0 {
+                state.report_error(BackgroundError::new("soak", "code", format!("error {}", i)));
+                state.events.flush_batches();
+                state.events.take_captured();
+            }
+            if (i + 1) % (total / checkpoints) != 0 {
+                continue;
+            }
+            let checkpoint = (i + 1) / (total / checkpoints);
+            for s in state.internal_buffer_stats().unwrap() {
+                match s.capacity {
+                    Some(cap) => assert!(s.len <= cap, "{} grew to {} (cap {}) after {} events", s.name, s.len, cap, i + 1),
+                    // 没有固定上限的结构：前半程达到稳态，之后不再增长
+                    None if checkpoint > checkpoints / 2 => {
+                        assert!(s.len <= peak[&s.name], "{} still growing: {} after {} events", s.name, s.len, i + 1)
+                    }
+                    None => {
+                        let peak = peak.entry(s.name.clone()).or_default();
+                        *peak = (*peak).max(s.len);
+                    }
+                }
+            }
+        }
+        let stats: HashMap<String, usize> = state.internal_buffer_stats().unwrap().into_iter().map(|s| (s.name, s.len)).collect();
+        assert!(stats["store.notifications"] > 0 && stats["store.notifications"] <= 1_000);
+        assert_eq!(stats["rule_review"], crate::rule_review::REVIEW_CAPACITY);
+        assert!(stats["trace.pending_requests"] > 0);
+    }
+}
@@ -0,0 +1,259 @@
+//! 按过滤条件批量操作（标记已读 / 删除 / 置顶），不必经 IPC 传送大量 id。
+//! 匹配与修改在同一次持锁内完成：并发入库的通知要么整体参与本次操作，要么完全不参与。
+//! 每次操作只发送一个 notifications-bulk 事件。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{redact_filter, AuditSource};
+use crate::commands::AppState;
+use crate::limits::{check_bytes, MAX_QUERY_BYTES};
+use crate::store::NotificationFilter;
+use crate::tombstones::RemovalReason;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    MarkRead,
+    Delete,
+    Pin,
+    Unpin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BulkResult {
+    /// 受影响（dry_run 时为将受影响）的条数
+    pub affected: usize,
+    pub dry_run: bool,
+}
+
+impl AppState {
+    /// 对匹配 filter 的通知执行批量操作；dry_run 只统计不修改
+    pub fn apply_where(&self, action: BulkAction, filter: &NotificationFilter, dry_run: bool) -> BulkResult {
+        let mut store = self.store.lock().unwrap();
+        // 只统计真正会变化的条目（已读的不再标记、已置顶的不再置顶）
+        let ids: Vec<String> = store
+            .ids_where(filter)
+            .into_iter()
+            .filter(|id| {
+                let n = store.get(id).expect("ids_where returns stored ids");
+                match action {
+                    BulkAction::MarkRead => !n.read,
+                    BulkAction::Delete => true,
+                    BulkAction::Pin => !n.pinned,
+                    BulkAction::Unpin => n.pinned,
+                }
+            })
+            .collect();
+        if dry_run || ids.is_empty() {
+            return BulkResult { affected: ids.len(), dry_run };
+        }
+
+        let removed = match action {
+            BulkAction::MarkRead => {
+                store.mark_read(&ids);
+                Vec::new()
+            }
+            BulkAction::Pin | BulkAction::Unpin => {
+                store.set_pinned(&ids, action == BulkAction::Pin);
+                Vec::new()
+            }
+            BulkAction::Delete => store.remove_many(&ids),
+        };
+        drop(store);
+
+        println!("[Bulk] {:?} -> {} items", action, ids.len());
+        self.events.emit("notifications-bulk", serde_json::json!({ "action": action, "ids": ids }));
+        if removed.is_empty() {
+            self.emit_counts();
+        } else {
+            self.finish_removal(&removed, RemovalReason::UserDeletedLocal);
+            self.audit("delete_where", AuditSource::Command, removed.len(), serde_json::json!({ "filter": redact_filter(filter) }));
+        }
+        BulkResult { affected: ids.len(), dry_run }
+    }
+
+    /// 删除某个应用的全部通知，返回删除条数；包名精确匹配，没有包名的通知不会匹配
+    pub fn delete_by_package(&self, package_name: &str) -> Result<usize, String> {
+        check_bytes("package_name", package_name.len(), MAX_QUERY_BYTES)?;
+        let removed = self.store.lock().unwrap().remove_package(package_name);
+        if removed.is_empty() {
+            return Ok(0);
+        }
+        let ids: Vec<&str> = removed.iter().map(|n| n.id.as_str()).collect();
+        println!("[Bulk] delete_by_package({}) -> {} items", package_name, removed.len());
+        self.events.emit("notifications-bulk", serde_json::json!({ "action": BulkAction::Delete, "ids": ids }));
+        self.finish_removal(&removed, RemovalReason::UserDeletedLocal);
+        self.audit("delete_by_package", AuditSource::Command, removed.len(), serde_json::json!({ "package_name": package_name }));
+        Ok(removed.len())
+    }
+
+    /// 删除已读通知（含归档的，置顶的保留），给出 older_than_seconds 时只删更早的，返回删除条数
+    pub fn delete_read(&self, older_than_seconds: Option<u64>, now: i64) -> usize {
+        let cutoff = older_than_seconds.map(|secs| now.saturating_sub(i64::try_from(secs).unwrap_or(i64::MAX)));
+        let removed = self.store.lock().unwrap().remove_read(cutoff);
+        if removed.is_empty() {
+            return 0;
+        }
+        let ids: Vec<&str> = removed.iter().map(|n| n.id.as_str()).collect();
+        println!("[Bulk] delete_read(older_than={:?}) -> {} items", older_than_seconds, removed.len());
+        self.events.emit("notifications-bulk", serde_json::json!({ "action": BulkAction::Delete, "ids": ids }));
+        self.finish_removal(&removed, RemovalReason::UserDeletedLocal);
+        self.audit("delete_read", AuditSource::Command, removed.len(), serde_json::json!({ "older_than_seconds": older_than_seconds }));
+        removed.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Event, Notification};
+
+    fn add(state: &AppState, id: &str, pkg: &str, ts: i64) {
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some(pkg.into()),
+                title: Some(format!("title {}", id)),
+                posted_at: Some(ts),
+                ..Default::default()
+            }),
+            id: None,
+        });
+    }
+
+    #[test]
+    fn test_dry_run_then_apply_emits_one_event() {
+        let state = AppState::default();
+        for i in 0..10 {
+            add(&state, &format!("wx{}", i), "com.tencent.mm", i);
+        }
+        add(&state, "mail", "com.mail", 100);
+        state.events.take_captured();
+
+        let filter = NotificationFilter {
+            package: Some("com.tencent.mm".into()),
+            until: Some(6),
+            ..Default::default()
+        };
+        let preview = state.apply_where(BulkAction::Delete, &filter, true);
+        assert_eq!(preview, BulkResult { affected: 7, dry_run: true });
+        assert_eq!(state.counts().total, 11);
+        assert!(state.events.take_captured().is_empty());
+
+        let done = state.apply_where(BulkAction::Delete, &filter, false);
+        assert_eq!(done.affected, 7);
+        assert_eq!(state.counts().total, 4);
+        let events = state.events.take_captured();
+        let names: Vec<&str> = events.iter().map(|(e, _)| e.as_str()).collect();
+        assert_eq!(names, ["notifications-bulk", "counts-changed"]);
+        assert_eq!(events[0].1["ids"].as_array().unwrap().len(), 7);
+        assert_eq!(state.removal_log.lock().unwrap().recent(100).len(), 7);
+    }
+
+    #[test]
+    fn test_mark_read_and_pin_count_only_changes() {
+        let state = AppState::default();
+        for i in 0..4 {
+            add(&state, &i.to_string(), "com.demo", i);
+        }
+        state.store.lock().unwrap().mark_read(&["0".to_string()]);
+        let all = NotificationFilter::default();
+        assert_eq!(state.apply_where(BulkAction::MarkRead, &all, false).affected, 3);
+        assert_eq!(state.counts().unread, 0);
+
+        let search = NotificationFilter { query: Some("TITLE 2".into()), ..Default::default() };
+        assert_eq!(state.apply_where(BulkAction::Pin, &search, false).affected, 1);
+        assert_eq!(state.apply_where(BulkAction::Pin, &search, false).affected, 0);
+        assert!(state.store.lock().unwrap().get("2").unwrap().pinned);
+    }
+
+    #[test]
+    fn test_delete_by_package_exact_match() {
+        let state = AppState::default();
+        for i in 0..5 {
+            add(&state, &format!("game{}", i), "com.noisy.game", i);
+        }
+        add(&state, "lite", "com.noisy.game.lite", 10);
+        add(&state, "mail", "com.mail", 11);
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: "bare".into(), ..Default::default() }),
+            id: None,
+        });
+        state.store.lock().unwrap().mark_read(&["game0".to_string(), "mail".to_string()]);
+        state.events.take_captured();
+
+        assert_eq!(state.delete_by_package("com.noisy.game").unwrap(), 5);
+        let counts = state.counts();
+        assert_eq!((counts.total, counts.unread), (3, 2));
+        let store = state.store.lock().unwrap();
+        assert!(store.get("lite").is_some() && store.get("bare").is_some() && store.get("mail").unwrap().read);
+        assert!(store.integrity_issues().is_empty());
+        drop(store);
+        let names: Vec<String> = state.events.take_captured().into_iter().map(|(e, _)| e).collect();
+        assert_eq!(names, ["notifications-bulk", "counts-changed"]);
+
+        assert_eq!(state.delete_by_package("com.noisy.game").unwrap(), 0);
+        assert_eq!(state.delete_by_package("").unwrap(), 0);
+        assert_eq!(state.counts().total, 3);
+    }
+
+    #[test]
+    fn test_delete_read_keeps_unread_pinned_and_recent() {
+        let state = AppState::default();
+        for i in 0..6 {
+            add(&state, &i.to_string(), "com.app", i * 100);
+        }
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: "undated".into(), ..Default::default() }),
+            id: None,
+        });
+        let ids: Vec<String> = ["0", "1", "4", "5", "undated"].iter().map(|s| s.to_string()).collect();
+        state.store.lock().unwrap().mark_read(&ids);
+        state.store.lock().unwrap().set_pinned(&["1".to_string()], true);
+        state.events.take_captured();
+
+        // 只删 300 秒之前的：0 与没有时间的
+        assert_eq!(state.delete_read(Some(300), 600), 2);
+        {
+            let store = state.store.lock().unwrap();
+            assert!(store.get("0").is_none() && store.get("undated").is_none());
+            assert!(store.get("1").is_some() && store.get("4").is_some());
+            assert!(store.integrity_issues().is_empty());
+        }
+        assert_eq!(state.delete_read(None, 600), 2);
+        let counts = state.counts();
+        assert_eq!((counts.total, counts.unread), (3, 2));
+        assert_eq!(state.delete_read(None, 600), 0);
+        let names: Vec<String> = state.events.take_captured().into_iter().map(|(e, _)| e).collect();
+        assert_eq!(names, ["notifications-bulk", "counts-changed", "notifications-bulk", "counts-changed"]);
+    }
+
+    #[test]
+    fn test_concurrent_ingest_is_all_or_nothing() {
+        let state = std::sync::Arc::new(AppState::default());
+        let writer = {
+            let state = state.clone();
+            std::thread::spawn(move || {
+                for i in 0..500 {
+                    add(&state, &i.to_string(), "com.flood", i);
+                }
+            })
+        };
+        let filter = NotificationFilter { package: Some("com.flood".into()), ..Default::default() };
+        let mut deleted = 0;
+        while !writer.is_finished() {
+            deleted += state.apply_where(BulkAction::Delete, &filter, false).affected;
+        }
+        writer.join().unwrap();
+        deleted += state.apply_where(BulkAction::Delete, &filter, false).affected;
+        assert_eq!(deleted, 500);
+        assert_eq!(state.counts().total, 0);
+    }
+}
@@ -0,0 +1,146 @@
+//! 设备能力标记：手机端与桌面端的版本更新节奏不同，认证时双方交换能力列表（字符串数组）。
+//! 桌面端的能力随认证请求发送；手机端返回的列表保存在连接与已配对设备信息中，
+//! 未知的标记（来自更新版本的手机端）原样保存、原样返回，不做丢弃。
+//! 依赖某项能力的命令先检查标记，不支持时立即返回 UnsupportedByDevice（带缺少的能力名）。
+//! 不返回能力列表的旧版手机端视为只支持 LEGACY 中的能力。
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::android_client::AndroidSocketClient;
+use crate::commands::AppState;
+
+pub const ACTIONS: &str = "actions";
+pub const DIRECT_REPLY: &str = "direct_reply";
+pub const ICON_FETCH: &str = "icon_fetch";
+pub const DISMISSAL: &str = "dismissal";
+pub const MEDIA_CONTROL: &str = "media_control";
+pub const COMPRESSION: &str = "compression";
+pub const BATCHED_PUSH: &str = "batched_push";
+
+/// 桌面端支持的能力（认证时发送给手机端）
+pub const DESKTOP: &[&str] = &[ACTIONS, DIRECT_REPLY, ICON_FETCH, DISMISSAL, MEDIA_CONTROL, COMPRESSION, BATCHED_PUSH];
+
+/// 旧版手机端（认证响应中没有 capabilities）已有的能力
+pub const LEGACY: &[&str] = &[ACTIONS, DISMISSAL, MEDIA_CONTROL];
+
+/// 手机端声明的能力；None 表示旧版手机端未声明
+pub fn supports(capabilities: Option<&[String]>, capability: &str) -> bool {
+    match capabilities {
+        Some(list) => list.iter().any(|c| c == capability),
+        None => LEGACY.contains(&capability),
+    }
+}
+
+/// 设备不支持所需能力；以 `UnsupportedByDevice: {json}` 的形式返回给前端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsupportedByDevice {
+    pub connection_id: String,
+    pub capability: String,
+}
+
+impl std::fmt::Display for UnsupportedByDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UnsupportedByDevice: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<UnsupportedByDevice> for String {
+    fn from(e: UnsupportedByDevice) -> Self {
+        println!("[Capabilities] {} does not support {}", e.connection_id, e.capability);
+        e.to_string()
+    }
+}
+
+impl AppState {
+    /// 取已连接设备的客户端，并确认其支持 capability
+    pub(crate) fn client_with(&self, connection_id: &str, capability: &str) -> Result<Arc<AndroidSocketClient>, String> {
+        let client = self.clients.read().get(connection_id).cloned().ok_or("Device is not connected")?;
+        if !supports(client.capabilities().as_deref(), capability) {
+            return Err(UnsupportedByDevice {
+                connection_id: connection_id.to_string(),
+                capability: capability.to_string(),
+            }
+            .into());
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    use crate::media::MediaAction;
+    use crate::transport::Transport;
+
+    /// 模拟手机端：登录成功并声明给定的能力，记录收到的请求
+    struct Phone {
+        login: String,
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Transport for Phone {
+        fn send_line(&mut self, line: &str) -> Result<(), String> {
+            self.sent.lock().push(line.to_string());
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(self.login.clone())
+        }
+    }
+
+    fn connect(state: &AppState, id: &str, capabilities: Option<&[&str]>) -> Arc<Mutex<Vec<String>>> {
+        let mut login = serde_json::json!({ "success": true, "protocolVersion": 3 });
+        if let Some(caps) = capabilities {
+            login["capabilities"] = serde_json::json!(caps);
+        }
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let phone = Phone { login: login.to_string(), sent: sent.clone() };
+        let client = AndroidSocketClient::with_transport(Box::new(phone), id.into(), Arc::default());
+        client.login("t1").unwrap();
+        state.register_client(id.to_string(), client, "t1", Some("192.168.1.5:10035"));
+        sent
+    }
+
+    fn unsupported(err: String) -> UnsupportedByDevice {
+        serde_json::from_str(err.strip_prefix("UnsupportedByDevice: ").expect(&err)).unwrap()
+    }
+
+    #[test]
+    fn test_gating_per_advertised_set() {
+        let state = AppState::default();
+        let sent = connect(&state, "minimal", Some(&["actions"]));
+        connect(&state, "full", Some(DESKTOP));
+        connect(&state, "legacy", None);
+
+        // 认证请求携带桌面端能力
+        let request: serde_json::Value = serde_json::from_str(&sent.lock()[0]).unwrap();
+        assert_eq!(request["capabilities"], serde_json::json!(DESKTOP));
+
+        let e = unsupported(state.media_control("minimal", MediaAction::Next).unwrap_err());
+        assert_eq!((e.connection_id.as_str(), e.capability.as_str()), ("minimal", MEDIA_CONTROL));
+        // 支持该能力的设备继续走原有流程（这里没有正在播放的媒体）
+        assert_eq!(state.media_control("full", MediaAction::Next).unwrap_err(), "No media playing on this device");
+        assert!(state.client_with("legacy", MEDIA_CONTROL).is_ok());
+        assert!(state.client_with("legacy", DIRECT_REPLY).is_err());
+        assert!(state.client_with("full", ICON_FETCH).is_ok());
+        assert_eq!(state.client_with("gone", ACTIONS).err().unwrap(), "Device is not connected");
+    }
+
+    #[test]
+    fn test_unknown_flags_round_trip() {
+        let state = AppState::default();
+        connect(&state, "future", Some(&["actions", "holo_projection"]));
+        let client = state.clients.read()["future"].clone();
+        assert_eq!(client.capabilities().unwrap(), ["actions", "holo_projection"]);
+
+        let endpoint = state.device_endpoint("future").unwrap();
+        let json = serde_json::to_string(&endpoint).unwrap();
+        let restored: crate::endpoints::DeviceEndpoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.capabilities.as_deref().unwrap(), ["actions", "holo_projection"]);
+        assert_eq!(restored, endpoint);
+    }
+}
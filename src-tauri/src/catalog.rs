@@ -0,0 +1,346 @@
+//! 命令目录：供前端的开发者命令面板与 TypeScript 绑定生成使用，不必手工维护 invoke 名称与参数形状。
+//! 所有 invoke 命令只在 with_commands! 中登记一次，同一份列表既生成 invoke_handler（generate_handlers!），
+//! 也生成目录（catalog_entries!），新增命令时不会只注册其一。
+//! 每个命令带描述的 i18n 键（command.<name>.description），以及参数与返回值的 JSON Schema：
+//! 由 schemars 从参数与结果类型派生，参数名按前端 invoke 时使用的 camelCase，Result 命令只描述成功值。
+//! 设置环境变量 COMMAND_CATALOG_OUT 运行 `cargo test catalog` 时把目录写到该路径，供生成绑定使用。
+
+use std::sync::OnceLock;
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Metadata, ObjectValidation, Schema, SchemaObject};
+use schemars::{JsonSchema, Map};
+use serde::Serialize;
+
+/// 登记的全部命令：路径(参数: 类型, ...) -> 成功时的返回类型；
+/// State / AppHandle 等由 tauri 注入的参数不列出
+macro_rules! with_commands {
+    ($callback:ident) => {
+        $callback! {
+            commands::ping() -> crate::ping::Ping;
+            commands::greet(name: String) -> String;
+            commands::get_counts(filter: Option<crate::store::NotificationFilter>) -> crate::store::Counts;
+            commands::get_counts_versioned() -> crate::store::VersionedCounts;
+            commands::get_counts_by_package() -> Vec<crate::store::PackageCounts>;
+            commands::get_startup_snapshot() -> crate::startup::StartupSnapshot;
+            commands::list_notifications(options: Option<crate::commands::ListOptions>) -> Vec<crate::types::Notification>;
+            commands::list_notification_groups(options: Option<crate::commands::ListOptions>) -> Vec<crate::groups::NotificationGroup>;
+            commands::search_notifications(query: String, limit: Option<usize>) -> Vec<crate::search::SearchHit>;
+            commands::get_day_summary(date: String) -> crate::day_summary::DaySummary;
+            commands::mark_read(options: crate::commands::IdsOptions) -> bool;
+            commands::snooze(options: crate::commands::SnoozeOptions) -> usize;
+            commands::mark_unread(options: crate::commands::IdsOptions) -> usize;
+            commands::archive(options: crate::commands::IdsOptions) -> usize;
+            commands::unarchive(options: crate::commands::IdsOptions) -> usize;
+            commands::delete(options: crate::commands::IdOptions) -> bool;
+            commands::delete_all() -> bool;
+            commands::delete_by_package(package_name: String) -> usize;
+            commands::delete_read(older_than_seconds: Option<u64>) -> usize;
+            set_tray_tooltip(text: String) -> bool;
+            commands::add_dummy(options: Option<crate::commands::AddDummyOptions>) -> bool;
+            commands::get_settings() -> crate::settings::Settings;
+            commands::get_view_state() -> crate::settings::ViewState;
+            commands::factory_reset() -> ();
+            commands::reveal_path(target: crate::app_dirs::DirTarget) -> crate::app_dirs::RevealResult;
+            commands::get_removal_log(limit: Option<usize>) -> Vec<crate::tombstones::Tombstone>;
+            commands::set_pinned(options: crate::commands::IdsOptions, pinned: bool) -> usize;
+            commands::mark_read_where(filter: crate::store::NotificationFilter, dry_run: Option<bool>) -> crate::bulk::BulkResult;
+            commands::delete_where(filter: crate::store::NotificationFilter, dry_run: Option<bool>) -> crate::bulk::BulkResult;
+            commands::pin_where(filter: crate::store::NotificationFilter, pinned: bool, dry_run: Option<bool>) -> crate::bulk::BulkResult;
+            commands::create_local_notification(title: String, text: String, remind_at: Option<i64>) -> crate::types::Notification;
+            commands::list_local_reminders() -> Vec<crate::types::Notification>;
+            commands::cancel_local_reminder(id: String) -> bool;
+            commands::get_onboarding_state() -> crate::onboarding::OnboardingState;
+            commands::advance_onboarding(step: crate::onboarding::OnboardingStep) -> crate::onboarding::OnboardingState;
+            commands::subscribe_connection(connection_id: String, last_seq: Option<u64>, channel: crate::catalog::ChannelArg) -> usize;
+            commands::resubscribe_all(last_seq: Option<std::collections::HashMap<String, u64>>, channel: crate::catalog::ChannelArg) -> usize;
+            commands::start_connection_trace(connection_id: String) -> u64;
+            commands::stop_connection_trace() -> bool;
+            commands::get_connection_trace() -> Option<crate::trace::TraceStatus>;
+            commands::set_settings(settings: crate::settings::Settings) -> crate::settings::Settings;
+            commands::get_retention_policy() -> crate::settings::RetentionSettings;
+            commands::set_retention_policy(policy: crate::settings::RetentionSettings) -> crate::settings::RetentionSettings;
+            commands::format_timestamp(ts: i64, style: crate::time_format::TimeStyle) -> String;
+            commands::format_timestamps(timestamps: Vec<i64>, style: crate::time_format::TimeStyle) -> Vec<String>;
+            commands::get_recent_errors(limit: Option<usize>) -> Vec<crate::error_bus::ErrorReport>;
+            commands::get_maintenance_status() -> Vec<crate::maintenance::JobStatus>;
+            commands::get_background_tasks() -> Vec<crate::tasks::TaskInfo>;
+            commands::get_event_batcher_stats() -> crate::event_batch::BatcherStats;
+            commands::get_tray_update_stats() -> Vec<crate::tray_updates::TraySurfaceStats>;
+            commands::export_rules_preset(path: String) -> usize;
+            commands::import_rules_preset(path: String, mode: crate::presets::ImportMode) -> crate::presets::ImportReport;
+            commands::set_app_meta(package_name: String, display_name: Option<String>, color: Option<String>) -> crate::app_meta::ResolvedAppMeta;
+            commands::get_app_meta_map() -> std::collections::BTreeMap<String, crate::app_meta::ResolvedAppMeta>;
+            commands::get_filtered_review(limit: Option<usize>) -> Vec<crate::rule_review::FilteredEntry>;
+            commands::get_rule_stats() -> Vec<crate::rule_review::RuleStats>;
+            commands::restore_filtered(entry_id: String) -> crate::ingest::IngestOutcome;
+            commands::disable_rule(rule_id: String) -> crate::rules::Rule;
+            commands::get_internal_buffer_stats() -> Vec<crate::buffer_stats::BufferStat>;
+            commands::get_webhook_status() -> crate::webhook::WebhookStatus;
+            commands::preview_metrics_payload() -> crate::metrics::MetricsPayload;
+            commands::delete_metrics_data() -> ();
+            commands::start_event_log(path: String, mask: Option<crate::masking::MaskLevel>) -> crate::event_log::EventLogStatus;
+            commands::export_diagnostic_bundle(path: String, mask: Option<crate::masking::MaskLevel>) -> crate::diagnostics::BundleManifest;
+            commands::export_notifications(path: Option<String>, mask: Option<crate::masking::MaskLevel>) -> crate::export::ExportReport;
+            commands::migrate_to_persistent_store() -> crate::persistent_store::MigrationReport;
+            commands::get_persistence_status() -> crate::persistent_store::PersistenceStatus;
+            commands::stop_event_log() -> crate::event_log::EventLogStatus;
+            commands::get_event_log_status() -> crate::event_log::EventLogStatus;
+            commands::run_maintenance_now(job_id: String) -> crate::maintenance::JobOutcome;
+            commands::dismiss_errors(ids: Vec<String>) -> usize;
+            commands::open_notification_window(id: String) -> String;
+            commands::get_notification_preview(id: String) -> crate::privacy::Preview;
+            commands::set_hide_previews(hide: bool) -> ();
+            commands::set_watch_only(enabled: bool) -> crate::watch_only::WatchOnlyStatus;
+            commands::get_watch_only_status() -> crate::watch_only::WatchOnlyStatus;
+            commands::release_outbox() -> crate::watch_only::OutboxRelease;
+            commands::list_open_windows() -> Vec<crate::popout::PopoutWindow>;
+            // 网络相关命令
+            commands::test_connect_to_server(host: String, port: u16) -> String;
+            commands::check_port_available(port: u16) -> bool;
+            commands::find_available_port(start_port: u16) -> Option<u16>;
+            commands::identify_port_user(port: u16) -> crate::network_utils::PortUser;
+            commands::check_firewall_rule(port: u16) -> crate::firewall::FirewallRuleStatus;
+            commands::create_firewall_rule(port: u16, name: Option<String>) -> crate::firewall::FirewallRuleResult;
+            commands::get_local_ip() -> String;
+            commands::get_device_uuid() -> String;
+            commands::get_identity_info() -> crate::identity::IdentityInfo;
+            commands::get_os_type() -> String;
+            commands::get_os_version() -> String;
+            commands::get_hostname() -> String;
+            commands::start_temp_server(port: u16) -> u16;
+            commands::stop_temp_server() -> ();
+            commands::get_temp_server_status() -> Option<crate::commands::TempServerStatus>;
+            commands::get_listening_ports() -> Vec<crate::ports::ListeningPort>;
+            commands::get_pairing_history() -> Vec<crate::pairing_payload::PairingRecord>;
+            commands::connect_to_android(connection_id: String, host: String, token: Option<String>) -> String;
+            commands::connect_via_relay(connection_id: String, relay_url: String, room_token: String, token: Option<String>) -> String;
+            commands::begin_manual_setup(connection_id: Option<String>) -> crate::wizard::WizardView;
+            commands::wizard_set_target(session: String, host: String, port: u16) -> crate::wizard::WizardView;
+            commands::wizard_authenticate(session: String, token: Option<String>) -> crate::wizard::WizardView;
+            commands::wizard_finish(session: String) -> serde_json::Value;
+            commands::wizard_cancel(session: String) -> bool;
+            commands::get_media_state(connection_id: String) -> Option<crate::media::MediaState>;
+            commands::media_control(connection_id: String, action: crate::media::MediaAction) -> ();
+            commands::set_mirroring_enabled(connection_id: String, enabled: bool, backfill: Option<bool>) -> crate::mirroring::MirroringResult;
+            commands::list_connections() -> Vec<crate::commands::ConnectionInfo>;
+            commands::start_relay_source(port: u16) -> u16;
+            commands::stop_relay_source() -> bool;
+            commands::connect_to_peer(host: String, port: u16, token: String) -> crate::relay::RelayLink;
+            commands::disconnect_peer(instance: String) -> usize;
+            commands::get_relay_status() -> crate::relay::RelayStatus;
+            commands::reconnect_android(connection_id: String) -> String;
+            commands::handle_device_frame(connection_id: String, frame: crate::endpoints::ControlFrame) -> crate::endpoints::DeviceEndpoint;
+            commands::ingest_device_event(connection_id: String, frame: String) -> crate::ingest::IngestOutcome;
+            commands::upsert_notification(connection_id: String, notification: crate::types::Notification) -> crate::ingest::IngestOutcome;
+            commands::list_devices() -> Vec<crate::sync_horizon::DeviceInfo>;
+            commands::remove_device_endpoint(device_uuid: String, endpoint: String) -> crate::endpoints::DeviceEndpoint;
+            commands::set_sync_horizon(device_uuid: String, horizon: crate::sync_horizon::HorizonInput) -> crate::endpoints::DeviceEndpoint;
+            commands::backfill_history(device_uuid: String, since_ts: i64) -> crate::sync_horizon::BackfillProgress;
+            commands::preview_template(template: String, notification_id: String) -> String;
+            commands::get_notification_text(id: String) -> String;
+            commands::compact_store() -> crate::compaction::CompactionReport;
+            commands::get_link_quality(connection_id: String) -> crate::link_quality::LinkQuality;
+            commands::check_device_compatibility(device_uuid: String) -> crate::compatibility::CompatibilityReport;
+            commands::get_audit_log(limit: Option<usize>) -> Vec<crate::audit::AuditEntry>;
+            commands::get_events_since(after_seq: u64, until_seq: Option<u64>) -> Vec<crate::window_feed::BufferedEvent>;
+            commands::create_api_token(name: String, scopes: Vec<crate::api_tokens::ApiScope>) -> crate::api_tokens::CreatedApiToken;
+            commands::list_api_tokens() -> Vec<crate::api_tokens::ApiTokenInfo>;
+            commands::revoke_api_token(id: String) -> crate::api_tokens::ApiTokenInfo;
+            commands::get_volume_history(package: Option<String>, hours: u8) -> crate::volume::VolumeHistory;
+            commands::enable_frame_capture(connection_id: String, enabled: Option<bool>) -> bool;
+            commands::get_captured_frames(connection_id: String, limit: Option<usize>) -> Vec<crate::inspector::CapturedFrame>;
+            commands::clear_captured_frames(connection_id: Option<String>) -> usize;
+            commands::verify_same_network_hint() -> crate::same_network::SameNetworkHint;
+            commands::verify_store_integrity(repair: Option<bool>) -> crate::integrity::IntegrityReport;
+            commands::install_native_messaging_host(browser: crate::native_host::Browser, extension_id: String) -> crate::native_host::InstalledHost;
+            commands::get_storage_status() -> crate::storage::StorageStatus;
+            commands::mute(target: crate::mutes::MuteTarget, expires_at: Option<i64>, exempt: Option<bool>) -> crate::mutes::Mute;
+            commands::list_channels(package_name: String) -> Vec<crate::channels::ChannelInfo>;
+            commands::unmute(id: String) -> ();
+            commands::list_mutes() -> Vec<crate::mutes::MuteInfo>;
+            commands::run_benchmarks(fixture_size: Option<usize>) -> crate::bench::BenchReport;
+            commands::list_profiles() -> crate::profiles::ProfilesView;
+            commands::create_profile(name: String, settings_overrides: Option<serde_json::Value>) -> crate::profiles::ProfileInfo;
+            commands::set_profile_lock(id: String, pin: Option<String>) -> crate::profiles::ProfileInfo;
+            commands::switch_profile(id: String, pin: Option<String>) -> crate::profiles::ProfileInfo;
+            commands::send_raw_frame(connection_id: String, json: String, expect_reply: bool, timeout_ms: Option<u64>, allow_token: Option<bool>) -> crate::raw_frame::RawFrameResult;
+            commands::get_os_focus_state() -> crate::os_focus::OsFocusStatus;
+            commands::get_health() -> crate::startup::Health;
+            commands::get_command_catalog() -> serde_json::Value;
+            commands::force_take_ownership() -> crate::data_lock::DataLockStatus;
+            commands::send_test_toast(package_name: Option<String>) -> crate::alert_test::TestAlertReport;
+            commands::play_test_sound(name: Option<String>) -> crate::alert_test::TestAlertReport;
+            commands::retry_create_window() -> crate::ui_window::UiStatus;
+            commands::disconnect_android(connection_id: String) -> ();
+            commands::test_socket_server() -> String;
+            commands::test_http_pairing() -> String;
+        }
+    };
+}
+
+/// invoke_handler
+macro_rules! generate_handlers {
+    ($($($seg:ident)::+ ($($arg:ident: $ty:ty),*) -> $out:ty;)*) => {
+        tauri::generate_handler![$($($seg)::+),*]
+    };
+}
+
+macro_rules! catalog_entries {
+    ($($($seg:ident)::+ ($($arg:ident: $ty:ty),*) -> $out:ty;)*) => {{
+        let mut gen = SchemaSettings::draft07().into_generator();
+        let commands = vec![$({
+            let params = vec![$(Param { name: stringify!($arg), optional: stringify!($ty).starts_with("Option"), schema: gen.subschema_for::<$ty>() }),*];
+            let output = gen.subschema_for::<$out>();
+            CommandMeta::new(stringify!($($seg)::+), params, output)
+        }),*];
+        CommandCatalog { commands, definitions: gen.take_definitions() }
+    }};
+}
+
+/// 前端传入的 tauri Channel（invoke 时序列化为 "__CHANNEL__:<id>"）
+pub struct ChannelArg;
+
+impl JsonSchema for ChannelArg {
+    fn schema_name() -> String {
+        "Channel".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let metadata = Metadata { description: Some("tauri Channel".to_string()), ..Default::default() };
+        SchemaObject { instance_type: Some(InstanceType::String.into()), metadata: Some(Box::new(metadata)), ..Default::default() }.into()
+    }
+}
+
+struct Param {
+    name: &'static str,
+    optional: bool,
+    schema: Schema,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandMeta {
+    pub name: String,
+    pub description_key: String,
+    /// invoke 的参数对象
+    pub input: Schema,
+    pub output: Schema,
+}
+
+impl CommandMeta {
+    fn new(path: &str, params: Vec<Param>, output: Schema) -> Self {
+        let name = path.rsplit("::").next().unwrap_or(path).trim().to_string();
+        let mut object = ObjectValidation::default();
+        for param in params {
+            let key = camel_case(param.name);
+            if !param.optional {
+                object.required.insert(key.clone());
+            }
+            object.properties.insert(key, param.schema);
+        }
+        let input = SchemaObject { instance_type: Some(InstanceType::Object.into()), object: Some(Box::new(object)), ..Default::default() };
+        Self { description_key: format!("command.{}.description", name), name, input: input.into(), output }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandCatalog {
+    pub commands: Vec<CommandMeta>,
+    /// input / output 中 $ref 引用的类型
+    pub definitions: Map<String, Schema>,
+}
+
+/// tauri 默认把 snake_case 参数名转为 camelCase
+fn camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.trim_start_matches('_').chars() {
+        match c {
+            '_' => upper = true,
+            c if upper => {
+                out.extend(c.to_uppercase());
+                upper = false;
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn build() -> CommandCatalog {
+    with_commands!(catalog_entries)
+}
+
+/// get_command_catalog：启动后首次调用时生成
+pub fn catalog() -> &'static serde_json::Value {
+    static CATALOG: OnceLock<serde_json::Value> = OnceLock::new();
+    CATALOG.get_or_init(|| serde_json::to_value(build()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    macro_rules! command_names {
+        ($($($seg:ident)::+ ($($arg:ident: $ty:ty),*) -> $out:ty;)*) => {
+            vec![$(stringify!($($seg)::+).rsplit("::").next().unwrap().trim()),*]
+        };
+    }
+
+    /// 收集 schema 中所有 $ref
+    fn refs(value: &serde_json::Value, out: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(r) = map.get("$ref").and_then(|r| r.as_str()) {
+                    out.push(r.to_string());
+                }
+                map.values().for_each(|v| refs(v, out));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| refs(v, out)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_catalog_covers_every_handler() {
+        let names: Vec<&str> = with_commands!(command_names);
+        let catalog = catalog();
+        let listed: Vec<&str> = catalog["commands"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+        assert_eq!(listed, names);
+        assert_eq!(names.iter().collect::<HashSet<_>>().len(), names.len(), "duplicate command");
+        for name in crate::startup::STORE_COMMANDS {
+            assert!(names.contains(name), "unknown store command {}", name);
+        }
+        // 定义了但没有登记的命令
+        let defined = [include_str!("commands.rs"), include_str!("lib.rs")].iter().map(|s| s.matches("#[tauri::command]").count()).sum::<usize>();
+        assert_eq!(defined, names.len());
+
+        let definitions = catalog["definitions"].as_object().unwrap();
+        for command in catalog["commands"].as_array().unwrap() {
+            assert_eq!(command["input"]["type"], "object", "{}", command["name"]);
+            assert!(command["output"].is_object() || command["output"].is_boolean(), "{}", command["name"]);
+            let mut found = Vec::new();
+            refs(command, &mut found);
+            for r in found {
+                let name = r.strip_prefix("#/definitions/").unwrap();
+                assert!(definitions.contains_key(name), "{} -> {}", command["name"], r);
+            }
+        }
+        let search = catalog["commands"].as_array().unwrap().iter().find(|c| c["name"] == "search_notifications").unwrap();
+        assert_eq!(search["description_key"], "command.search_notifications.description");
+        assert_eq!(search["input"]["required"], serde_json::json!(["query"]));
+        assert!(search["input"]["properties"]["limit"].is_object());
+
+        if let Ok(path) = std::env::var("COMMAND_CATALOG_OUT") {
+            std::fs::write(path, serde_json::to_string_pretty(catalog).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_camel_case() {
+        assert_eq!(camel_case("connection_id"), "connectionId");
+        assert_eq!(camel_case("_text"), "text");
+        assert_eq!(camel_case("query"), "query");
+    }
+}
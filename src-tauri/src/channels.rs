@@ -0,0 +1,163 @@
+//! 安卓通知渠道：同一应用的通知按渠道（如“消息”“促销”“静默”）分组，手机端随通知发送 channel_id 与 channel_name。
+//! 入库时（规则过滤之前，被丢弃的也算）按包名汇总见过的渠道，写入数据目录下的 channels.json，供设置界面选择静音/规则目标。
+//! 渠道名会改、也会随系统语言变化，因此以 channel_id 为准，只保留最近一次见到的名称用于展示。
+//! 旧版安卓（8.0 之前）没有渠道，这类通知不进目录，也不会命中按渠道的静音与规则。
+//! 目录有变化时由维护任务写盘（退出时再写一次），不逐条写。
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::store::NotificationFilter;
+use crate::types::Notification;
+
+pub const CHANNELS_FILE: &str = "channels.json";
+/// 每个应用最多记录的渠道数，超出的不再记录
+pub const MAX_CHANNELS_PER_PACKAGE: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ChannelRecord {
+    name: Option<String>,
+    seen: u64,
+    last_seen_at: i64,
+}
+
+/// list_channels 的返回项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ChannelInfo {
+    pub channel_id: String,
+    /// 最近一次见到的名称
+    pub channel_name: Option<String>,
+    /// 累计收到的通知数
+    pub seen: u64,
+    /// 当前存储中属于该渠道的通知数
+    pub current: usize,
+    pub last_seen_at: i64,
+}
+
+#[derive(Default)]
+pub struct ChannelCatalog {
+    // 包名 -> channel_id -> 记录
+    packages: Mutex<BTreeMap<String, BTreeMap<String, ChannelRecord>>>,
+    dirty: AtomicBool,
+}
+
+impl ChannelCatalog {
+    pub fn observe(&self, n: &Notification, now: i64) {
+        let (Some(package), Some(channel_id)) = (&n.package_name, &n.channel_id) else {
+            return;
+        };
+        let mut packages = self.packages.lock();
+        let channels = packages.entry(package.clone()).or_default();
+        if !channels.contains_key(channel_id) && channels.len() >= MAX_CHANNELS_PER_PACKAGE {
+            return;
+        }
+        let record = channels.entry(channel_id.clone()).or_insert(ChannelRecord { name: None, seen: 0, last_seen_at: now });
+        record.seen = record.seen.saturating_add(1);
+        record.last_seen_at = now;
+        if n.channel_name.is_some() {
+            record.name = n.channel_name.clone();
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+}
+
+impl AppState {
+    pub(crate) fn load_channels(&self) {
+        *self.channels.packages.lock() = self.storage.load(CHANNELS_FILE).unwrap_or_default();
+    }
+
+    /// 目录有变化时写盘（维护任务与退出时调用）
+    pub fn persist_channels(&self) -> Result<String, String> {
+        if !self.channels.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(String::new());
+        }
+        let packages = self.channels.packages.lock().clone();
+        if let Err(e) = self.storage.save(CHANNELS_FILE, &packages) {
+            self.channels.dirty.store(true, Ordering::Relaxed);
+            return Err(e);
+        }
+        Ok(format!("{} packages", packages.len()))
+    }
+
+    /// 某个应用见过的渠道，按 channel_id 排序
+    pub fn list_channels(&self, package_name: &str) -> Vec<ChannelInfo> {
+        let Some(channels) = self.channels.packages.lock().get(package_name).cloned() else {
+            return Vec::new();
+        };
+        let filter = NotificationFilter { package: Some(package_name.to_string()), ..Default::default() };
+        let store = self.store.lock().unwrap();
+        let mut current: BTreeMap<String, usize> = BTreeMap::new();
+        for id in store.ids_where(&filter) {
+            if let Some(channel_id) = store.get(&id).and_then(|n| n.channel_id.clone()) {
+                *current.entry(channel_id).or_default() += 1;
+            }
+        }
+        channels
+            .into_iter()
+            .map(|(channel_id, r)| ChannelInfo {
+                current: current.get(&channel_id).copied().unwrap_or_default(),
+                channel_id,
+                channel_name: r.name,
+                seen: r.seen,
+                last_seen_at: r.last_seen_at,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    pub(crate) fn shop(id: &str, channel: Option<(&str, &str)>) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some("com.shop".into()),
+                title: Some(format!("title {}", id)),
+                channel_id: channel.map(|c| c.0.to_string()),
+                channel_name: channel.map(|c| c.1.to_string()),
+                ..Default::default()
+            }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_catalog_counts_and_renames() {
+        let dir = crate::storage::temp_dir("channels");
+        let state = AppState::default();
+        state.storage.set_base_dir(dir.clone());
+        state.ingest_event(shop("1", Some(("promo", "Promotions"))));
+        state.ingest_event(shop("2", Some(("promo", "Promotions"))));
+        state.ingest_event(shop("3", Some(("delivery", "Delivery updates"))));
+        // 旧版安卓：没有渠道信息
+        state.ingest_event(shop("4", None));
+        // 改名（或切换系统语言）后按 channel_id 归并，显示最新的名称
+        state.ingest_event(shop("5", Some(("promo", "优惠活动"))));
+        state.store.lock().unwrap().remove("1");
+
+        let channels = state.list_channels("com.shop");
+        assert_eq!(channels.len(), 2);
+        assert_eq!((channels[0].channel_id.as_str(), channels[0].channel_name.as_deref()), ("delivery", Some("Delivery updates")));
+        let promo = &channels[1];
+        assert_eq!((promo.channel_name.as_deref(), promo.seen, promo.current), (Some("优惠活动"), 3, 2));
+        assert!(state.list_channels("com.other").is_empty());
+
+        assert_eq!(state.persist_channels().unwrap(), "1 packages");
+        assert_eq!(state.persist_channels().unwrap(), "");
+        let restarted = AppState::default();
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_channels();
+        assert_eq!(restarted.list_channels("com.shop")[1].seen, 3);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,197 @@
+//! 按设备估算手机与桌面的时钟偏差（NTP 方式）：
+//! 每次 ping 得到 t0（桌面发出）、t1（手机收到）、t2（手机回复）、t3（桌面收到），
+//! offset = ((t1 - t0) + (t2 - t3)) / 2，取最近若干次的中位数平滑。
+//! 通知保留原始 posted_at，另附校正后的 corrected_posted_at；开启 use_corrected_time 时展示用校正值。
+
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::android_client::AndroidSocketClient;
+use crate::commands::AppState;
+use crate::types::Notification;
+
+/// 支持 ping / pong 的最低协议版本
+pub const CLOCK_SYNC_MIN_PROTOCOL: u32 = 2;
+/// 参与中位数的最近样本数
+const MAX_SAMPLES: usize = 9;
+
+/// 一次 ping 往返的四个时间点（毫秒）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockSample {
+    pub t0: i64,
+    pub t1: i64,
+    pub t2: i64,
+    pub t3: i64,
+}
+
+impl ClockSample {
+    /// 手机时钟比桌面快多少毫秒
+    pub fn offset_ms(&self) -> i64 {
+        ((self.t1 - self.t0) + (self.t2 - self.t3)) / 2
+    }
+
+    pub fn round_trip_ms(&self) -> i64 {
+        (self.t3 - self.t0) - (self.t2 - self.t1)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ClockEstimator {
+    offsets: VecDeque<i64>,
+}
+
+impl ClockEstimator {
+    pub fn add(&mut self, sample: ClockSample) {
+        if self.offsets.len() == MAX_SAMPLES {
+            self.offsets.pop_front();
+        }
+        self.offsets.push_back(sample.offset_ms());
+    }
+
+    /// 最近样本的中位数（还没有样本时为 None）
+    pub fn offset_ms(&self) -> Option<i64> {
+        if self.offsets.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<i64> = self.offsets.iter().copied().collect();
+        sorted.sort_unstable();
+        Some(sorted[sorted.len() / 2])
+    }
+}
+
+/// connection_id -> 偏差估计
+#[derive(Default)]
+pub struct ClockOffsets {
+    devices: Mutex<HashMap<String, ClockEstimator>>,
+}
+
+impl ClockOffsets {
+    pub fn record(&self, connection_id: &str, sample: ClockSample) -> Option<i64> {
+        let mut devices = self.devices.lock();
+        let estimator = devices.entry(connection_id.to_string()).or_default();
+        estimator.add(sample);
+        estimator.offset_ms()
+    }
+
+    pub fn offset_ms(&self, connection_id: &str) -> Option<i64> {
+        self.devices.lock().get(connection_id).and_then(|e| e.offset_ms())
+    }
+
+    /// 用上次运行记录的偏差作为初始值（见 handoff）
+    pub fn restore(&self, connection_id: &str, offset_ms: i64) {
+        let mut estimator = ClockEstimator::default();
+        estimator.offsets.push_back(offset_ms);
+        self.devices.lock().insert(connection_id.to_string(), estimator);
+    }
+
+    /// 丢弃所有样本（系统时间跳变后旧样本失效）
+    pub fn clear(&self) {
+        self.devices.lock().clear();
+    }
+}
+
+/// 把手机时间（秒）换算为桌面时间
+pub fn correct(ts: i64, offset_ms: i64) -> i64 {
+    ts.saturating_sub((offset_ms as f64 / 1000.0).round() as i64)
+}
+
+/// 展示用时间（updated_at 优先）：开启校正且有校正值时按偏差平移，否则用原始时间
+pub fn display_time(n: &Notification, use_corrected: bool) -> Option<i64> {
+    let raw = n.updated_at.or(n.posted_at)?;
+    match (use_corrected, n.corrected_posted_at, n.posted_at) {
+        (true, Some(corrected), Some(posted)) => Some(raw.saturating_add(corrected.saturating_sub(posted))),
+        _ => Some(raw),
+    }
+}
+
+pub(crate) fn supports_clock_sync(client: &AndroidSocketClient) -> bool {
+    client.protocol_version().is_some_and(|v| v >= CLOCK_SYNC_MIN_PROTOCOL)
+}
+
+impl AppState {
+    /// 对所有支持的连接各 ping 一次并更新偏差，返回成功的连接数（由维护任务定期调用）
+    pub fn sync_clocks(&self) -> Result<usize, String> {
+        let clients: Vec<(String, std::sync::Arc<AndroidSocketClient>)> =
+            self.clients.read().iter().map(|(id, c)| (id.clone(), c.clone())).collect();
+        let mut synced = 0;
+        let mut errors = Vec::new();
+        let now = crate::wall_clock::mono_secs();
+        // 低功耗模式下的连接按放宽后的心跳间隔
+        let due = clients.into_iter().filter(|(id, c)| supports_clock_sync(c) && self.low_power.heartbeat_due(id, now));
+        for (id, client) in due {
+            match client.ping() {
+                Ok(sample) => {
+                    let offset = self.clock_offsets.record(&id, sample);
+                    println!("[Clock] {} offset={:?}ms rtt={}ms", id, offset, sample.round_trip_ms());
+                    self.record_rtt(&id, sample.round_trip_ms(), chrono::Utc::now().timestamp());
+                    synced += 1;
+                }
+                Err(e) => errors.push(format!("{}: {}", id, e)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(synced)
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// 入库前附上校正后的时间（仅有偏差估计的连接）
+    pub(crate) fn apply_clock_offset(&self, connection_id: &str, n: &mut Notification) {
+        if let (Some(ts), Some(offset)) = (n.posted_at, self.clock_offsets.offset_ms(connection_id)) {
+            n.corrected_posted_at = Some(correct(ts, offset));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    /// 手机快 90 秒，单程延迟在 20~180ms 之间抖动，偶有一次严重不对称
+    fn synthetic(i: i64) -> ClockSample {
+        let t0 = 1_700_000_000_000 + i * 60_000;
+        let up = 20 + (i * 37) % 160;
+        let down = if i == 3 { 2_000 } else { 20 + (i * 53) % 160 };
+        let t1 = t0 + up + 90_000;
+        let t2 = t1 + 5;
+        ClockSample { t0, t1, t2, t3: t2 - 90_000 + down }
+    }
+
+    #[test]
+    fn test_offset_converges() {
+        let offsets = ClockOffsets::default();
+        let mut last = None;
+        for i in 0..20 {
+            last = offsets.record("pixel", synthetic(i));
+        }
+        let offset = last.unwrap();
+        assert!((offset - 90_000).abs() < 100, "offset {}", offset);
+        assert_eq!(offsets.offset_ms("other"), None);
+    }
+
+    #[test]
+    fn test_corrected_time_keeps_raw() {
+        let state = AppState::default();
+        for i in 0..5 {
+            state.clock_offsets.record("pixel", synthetic(i));
+        }
+        state.ingest_from(
+            "pixel",
+            Event {
+                event_type: "added".into(),
+                seq: 0,
+                notification: Some(Notification { id: "1".into(), posted_at: Some(1_000), ..Default::default() }),
+                id: None,
+            },
+        );
+        let n = state.store.lock().unwrap().get("1").cloned().unwrap();
+        assert_eq!(n.posted_at, Some(1_000));
+        assert_eq!(n.corrected_posted_at, Some(910));
+        assert_eq!(display_time(&n, false), Some(1_000));
+        assert_eq!(display_time(&n, true), Some(910));
+    }
+}
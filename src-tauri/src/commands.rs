@@ -1,145 +1,1435 @@
 //! Tauri commands 与应用状态（临时内存版，后续接入 SQLite）。
 //! 初期打开日志，稳定后再降级。
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
 use std::sync::Arc;
 use parking_lot::RwLock;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{Manager, State};
+
+use crate::app_meta::{self, ResolvedAppMeta};
+use crate::types::{Event, Notification};
+use crate::store::{Counts, NotificationFilter, NotificationStore, PackageCounts, SortMode, VersionedCounts};
+use crate::storage::{Storage, StorageStatus};
+use crate::settings::{RetentionSettings, Settings, ViewState};
+use crate::time_format::{self, TimeStyle};
+use crate::network_utils::{self, BindError, BindErrorKind, PortUser};
+use crate::temp_server::TempServer;
+use crate::pairing_payload::{PairingRecord, PAIRING_WAIT_SECS};
+use crate::ports::{ListeningPort, PortRegistry, ServerRole};
+use crate::android_client::AndroidSocketClient;
+use crate::endpoints::{ControlFrame, DeviceEndpoint, EndpointRegistry};
+use crate::error_bus::{BackgroundError, ErrorBus, ErrorReport};
+use crate::events::EventSink;
+use crate::limits;
+use crate::identity::{Identity, IdentityInfo};
+use crate::event_batch::BatcherStats;
+use crate::clock::{self, ClockOffsets};
+use crate::search::{self, SearchHit};
+use crate::bulk::{BulkAction, BulkResult};
+use crate::app_dirs::{DirTarget, RevealResult};
+use crate::popout::{PopoutRegistry, PopoutWindow, Reservation};
+use crate::event_log::{EventLog, EventLogStatus};
+use crate::masking::MaskLevel;
+use crate::derived_upgrade::DerivedUpgrade;
+use crate::channels::{ChannelCatalog, ChannelInfo};
+use crate::persistent_store::PersistentStore;
+use crate::maintenance::{JobOutcome, JobStatus, Maintenance};
+use crate::privacy::{Preview, Surface};
+use crate::presets::{ImportMode, ImportReport};
+use crate::media::{MediaAction, MediaState, MediaStates};
+use crate::mirroring::{MirroringRegistry, MirroringResult};
+use crate::onboarding::{Onboarding, OnboardingState, OnboardingStep};
+use crate::startup::{Health, Readiness, StartupSnapshot, Subsystem};
+use crate::stream_meta::SeqPersist;
+use crate::subscriptions::{StreamEvent, SubscriptionHub};
+use crate::tombstones::{RemovalLog, RemovalReason, Tombstone};
+use crate::webhook::{Webhook, WebhookStatus};
+use crate::wizard::{SetupWizard, WizardView};
+use crate::metrics::{Metrics, MetricsPayload};
+use crate::day_summary::DaySummary;
+use crate::protocol::ProtocolErrorCounts;
+use crate::compaction::{Compaction, CompactionReport};
+use crate::compatibility::CompatibilityReport;
+use crate::ingest::IngestOutcome;
+use crate::link_quality::{LinkQuality, LinkQualityTracker};
+use crate::sync_horizon::{BackfillProgress, Backfills, DeviceInfo, HorizonInput};
+use crate::tasks::{TaskInfo, TaskRegistry};
+use crate::trace::{ConnectionTracer, TraceStatus, TRACE_DURATION};
+use crate::audit::{AuditEntry, AuditLog, AuditSource};
+use crate::window_feed::BufferedEvent;
+use crate::volume::{Volume, VolumeHistory};
+use crate::inspector::CapturedFrame;
+use crate::raw_frame::RawFrameResult;
+use crate::os_focus::{OsFocus, OsFocusStatus};
+use crate::wall_clock::WallClock;
+use crate::handoff::Handoff;
+use crate::firewall::{FirewallRuleResult, FirewallRuleStatus};
+use crate::phone_dismiss::DismissTracker;
+use crate::data_lock::{DataLock, DataLockStatus};
+use crate::network_watch::NetworkWatch;
+use crate::rule_review::{FilteredEntry, RuleReview, RuleStats};
+use crate::failover::ReconnectGuard;
+use crate::groups::NotificationGroup;
+use crate::tray_updates::{TrayUpdater, TraySurfaceStats};
+use crate::watch_only::{OutboundGate, OutboxRelease, WatchOnlyStatus};
+use crate::relay::{Relay, RelayAction, RelayLink, RelayStatus};
+use crate::ping::Ping;
+use crate::alert_test::TestAlertReport;
+use crate::ui_window::{UiState, UiStatus};
+use crate::same_network::SameNetworkHint;
+use crate::low_power::{LowPower, LowPowerStatus};
+use crate::bench::BenchReport;
+use crate::profiles::{ActiveProfile, ProfileInfo, Profiles, ProfilesView};
+use crate::mutes::{Mute, MuteInfo, MuteTarget, Mutes};
+use crate::integrity::IntegrityReport;
+use crate::native_host::{Browser, InstalledHost};
+use crate::api_tokens::{ApiScope, ApiTokenInfo, ApiTokens, CreatedApiToken};
+
+#[derive(Default)]
+pub struct AppState {
+    // 通知存储（临时内存实现）：通知表 + 已读集合
+    pub(crate) store: Mutex<NotificationStore>,
+    // 临时服务器（用于扫码配对）
+    temp_server: Arc<RwLock<Option<TempServer>>>,
+    // 客户端连接池：connection_id -> AndroidSocketClient
+    pub(crate) clients: Arc<RwLock<HashMap<String, Arc<AndroidSocketClient>>>>,
+    // 应用设置
+    pub(crate) settings: RwLock<Settings>,
+    // 事件发送（setup 时挂载 AppHandle）
+    pub(crate) events: EventSink,
+    // 后台错误总线
+    pub(crate) errors: ErrorBus,
+    // 通知详情弹出窗口
+    popouts: PopoutRegistry,
+    // 本地 JSON 持久化
+    pub(crate) storage: Storage,
+    // 界面视图状态（排序方式等）
+    view_state: RwLock<ViewState>,
+    // 首次使用引导
+    onboarding: Onboarding,
+    // 单连接调试追踪（各客户端共享）
+    pub(crate) tracer: Arc<ConnectionTracer>,
+    // 最近的删除记录
+    pub(crate) removal_log: Mutex<RemovalLog>,
+    // 按连接的事件流订阅（WebView Channel）
+    pub(crate) subscriptions: SubscriptionHub,
+    // 变更序号的延迟持久化
+    pub(crate) seq_persist: SeqPersist,
+    // 后台维护任务
+    pub(crate) maintenance: Maintenance,
+    // 事件流导出（NDJSON）
+    pub(crate) event_log: EventLog,
+    // 启动时各子系统的加载状态
+    pub(crate) readiness: Readiness,
+    // 按设备暂停镜像
+    pub(crate) mirroring: Mutex<MirroringRegistry>,
+    // 已配对设备的连接信息（不扫码重连）
+    pub(crate) endpoints: Mutex<EndpointRegistry>,
+    // 配对历史（新 -> 旧，不含 token）
+    pub(crate) pairing_history: Mutex<Vec<PairingRecord>>,
+    // 按连接的正在播放媒体
+    pub(crate) media: Mutex<MediaStates>,
+    // 按设备的时钟偏差估计
+    pub(crate) clock_offsets: ClockOffsets,
+    // 入库事件的 webhook 推送
+    pub(crate) webhook: Webhook,
+    // 本应用各服务的监听端口
+    pub(crate) ports: PortRegistry,
+    // 长期运行的后台任务（退出时统一取消）
+    pub(crate) tasks: TaskRegistry,
+    // 本机身份（device_uuid 与机器 id）
+    pub(crate) identity: Identity,
+    // 手动连接向导会话
+    pub(crate) wizard: SetupWizard,
+    // 匿名使用统计（默认关闭）
+    pub(crate) metrics: Metrics,
+    // 进行中的历史补发
+    pub(crate) backfills: Backfills,
+    // 存储压缩
+    pub(crate) compaction: Compaction,
+    // 各连接的接收质量
+    pub(crate) link_quality: LinkQualityTracker,
+    // 破坏性操作审计日志
+    pub(crate) audit: AuditLog,
+    // 本地 API 令牌
+    pub(crate) api_tokens: ApiTokens,
+    // 各应用的通知量走势
+    pub(crate) volume: Volume,
+    // 夜间低功耗模式下的连接
+    pub(crate) low_power: LowPower,
+    // 按会话/通知的静音
+    pub(crate) mutes: Mutes,
+    // 数据档案（工作/个人）
+    pub(crate) profiles: Profiles,
+    // 系统勿扰状态（缓存）
+    pub(crate) os_focus: OsFocus,
+    // 主窗口是否可用（WebView 损坏时仅托盘运行）
+    pub(crate) ui: UiState,
+    // 系统时间与跳变检测
+    pub(crate) wall_clock: WallClock,
+    // 上次退出时交接的设备状态（重连时取走）
+    pub(crate) handoff: Handoff,
+    // 手机端最近的移除（批量清除判定）
+    pub(crate) dismissals: DismissTracker,
+    // 数据目录锁（同步盘上被另一台机器共用时只读）
+    pub(crate) data_lock: DataLock,
+    // 本机网络状态（断网时暂停自动重连）
+    pub(crate) network: NetworkWatch,
+    // 规则命中统计与最近被过滤的复查列表
+    pub(crate) rule_review: RuleReview,
+    // 正在重连的设备（同一设备不会同时连接多个地址）
+    pub(crate) failover: ReconnectGuard,
+    // PC 间转发（转发服务与两个方向的连接）
+    pub(crate) relay: Relay,
+    // 只读观察模式的出站闸门与待发队列（各客户端共享）
+    pub(crate) watch_only: Arc<OutboundGate>,
+    // 托盘图标/菜单/提示的合并更新
+    pub(crate) tray_updates: TrayUpdater,
+    // 数据版本与派生字段升级进度
+    pub(crate) derived_upgrade: DerivedUpgrade,
+    // 各应用见过的安卓通知渠道
+    pub(crate) channels: ChannelCatalog,
+    // 通知表的持久化后端与最近一次快照
+    pub(crate) persistent_store: PersistentStore,
+}
+
+pub(crate) const SETTINGS_FILE: &str = "settings.json";
+const VIEW_STATE_FILE: &str = "view_state.json";
+const ONBOARDING_FILE: &str = "onboarding.json";
+pub(crate) const REMOVAL_LOG_FILE: &str = "removal_log.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TempServerStatus {
+    pub running: bool,
+    pub port: u16,
+    pub waiting_for_pairing: bool,
+}
+
+impl AppState {
+    /// setup 阶段挂载 AppHandle，之后后台组件才能向前端发送事件
+    pub fn attach_app(&self, app: tauri::AppHandle) {
+        let handle = app.clone();
+        self.storage.set_error_sink(move |e| handle.state::<AppState>().report_error(e));
+        self.tracer.events.attach(app.clone());
+        self.events.attach(app);
+    }
+
+    /// 设置数据目录：读取档案列表，加载当前档案的数据
+    pub fn init_storage(&self, dir: std::path::PathBuf) {
+        self.acquire_data_lock(&dir);
+        let profile = self.init_profiles(dir);
+        self.load_profile_data(&profile);
+    }
+
+    /// 加载档案数据目录下的设置、通知与设备信息；没有对应文件的部分回到默认值
+    pub(crate) fn load_profile_data(&self, profile: &ActiveProfile) {
+        self.audit.open(&profile.dir);
+        self.storage.set_base_dir(profile.dir.clone());
+        let settings = match self.storage.load::<Settings>(SETTINGS_FILE) {
+            Some(settings) => settings,
+            // 新档案：默认设置 + 档案的设置覆盖
+            None => profile.initial_settings(),
+        };
+        match settings.validate() {
+            Ok(()) => *self.settings.write() = settings,
+            Err(e) => {
+                println!("[Storage] Ignoring invalid settings: {}", e);
+                *self.settings.write() = Settings::default();
+            }
+        }
+        if self.storage.read_timed_out(SETTINGS_FILE) {
+            self.mark_degraded(Subsystem::Settings, vec![SETTINGS_FILE.to_string()]);
+        }
+        self.mark_ready(Subsystem::Settings);
+        self.apply_importance_map();
+        self.apply_watch_only();
+        *self.view_state.write() = self.storage.load::<ViewState>(VIEW_STATE_FILE).unwrap_or_default();
+        self.onboarding.restore(self.storage.load::<OnboardingState>(ONBOARDING_FILE).unwrap_or_default());
+        *self.removal_log.lock().unwrap() = self.storage.load::<RemovalLog>(REMOVAL_LOG_FILE).unwrap_or_default();
+        self.load_stream_meta();
+        self.load_mirroring();
+        self.load_endpoints();
+        self.load_pairing_history();
+        self.load_persistent_store();
+        self.load_local_notifications();
+        self.load_data_version();
+        self.load_metrics();
+        self.load_api_tokens();
+        self.load_volume();
+        self.load_mutes();
+        self.load_channels();
+        let slow: Vec<String> = self.storage.slow_reads().into_iter().filter(|n| n != SETTINGS_FILE).collect();
+        if !slow.is_empty() {
+            self.mark_degraded(Subsystem::Store, slow);
+        }
+        self.mark_ready(Subsystem::Store);
+        self.apply_webhook_settings();
+    }
+
+    /// 推进引导状态（只前进）；变化时持久化并推送 onboarding-changed
+    pub fn advance_onboarding(&self, step: OnboardingStep) -> OnboardingState {
+        match self.onboarding.advance(step, chrono::Utc::now().timestamp()) {
+            Some(state) => {
+                println!("[Onboarding] -> {:?}", state.step);
+                self.onboarding_changed(&state);
+                state
+            }
+            None => self.onboarding.get(),
+        }
+    }
+
+    fn onboarding_changed(&self, state: &OnboardingState) {
+        if let Err(e) = self.storage.save(ONBOARDING_FILE, state) {
+            println!("[Storage] {}", e);
+        }
+        self.events.emit("onboarding-changed", state.clone());
+    }
+
+    /// 恢复出厂设置：清空通知，设置、视图状态与引导状态回到默认值
+    pub fn factory_reset(&self) -> Result<(), String> {
+        let n = self.store.lock().unwrap().clear().len();
+        self.rule_review.clear();
+        self.reset_data_version();
+        self.reset_stream();
+        self.events.emit("notifications-cleared", ());
+        self.emit_counts();
+        self.close_all_popouts();
+        let log = {
+            let mut log = self.removal_log.lock().unwrap();
+            log.clear();
+            log.clone()
+        };
+        self.storage.save(REMOVAL_LOG_FILE, &log)?;
+
+        let settings = Settings::default();
+        *self.settings.write() = settings.clone();
+        self.storage.save(SETTINGS_FILE, &settings)?;
+        self.apply_importance_map();
+        self.apply_watch_only();
+        self.delete_metrics_data();
+        let view = ViewState::default();
+        *self.view_state.write() = view.clone();
+        self.storage.save(VIEW_STATE_FILE, &view)?;
+
+        let state = self.onboarding.reset(chrono::Utc::now().timestamp());
+        self.onboarding_changed(&state);
+        // 审计日志不随出厂设置清空
+        self.audit("factory_reset", AuditSource::Command, n, serde_json::Value::Null);
+        println!("[AppState] Factory reset, cleared {} notifications", n);
+        Ok(())
+    }
+
+    /// 列表查询（list_notifications 命令与基准测试共用）
+    pub fn list_notifications(&self, mut options: ListOptions) -> Result<Vec<Notification>, String> {
+        self.ensure_ready(Subsystem::Store)?;
+        limits::check_filter(&options.filter)?;
+        options.filter.validate()?;
+        if options.filter.muted.is_none() && !options.include_muted {
+            options.filter.muted = Some(false);
+        }
+        if options.filter.archived.is_none() && !options.include_archived {
+            options.filter.archived = Some(false);
+        }
+        let sort = self.resolve_sort(options.sort);
+        let mut list = self.store.lock().unwrap().query_filter(sort, &options.filter, options.offset, options.limit);
+        {
+            let settings = self.settings.read();
+            for n in list.iter_mut() {
+                n.app_display_name = n.package_name.as_deref().and_then(|p| app_meta::display_name(&settings.app_meta, p));
+            }
+        }
+        if options.with_relative_time {
+            let (lang, use_corrected) = {
+                let settings = self.settings.read();
+                (settings.lang(), settings.use_corrected_time)
+            };
+            for n in list.iter_mut() {
+                n.relative_time = clock::display_time(n, use_corrected)
+                    .map(|ts| time_format::format_timestamp(ts, TimeStyle::Relative, lang));
+            }
+        }
+        println!("[cmd] list_notifications ({:?}) -> {} items", sort, list.len());
+        Ok(list)
+    }
+
+    pub fn search_notifications(&self, query: &str, limit: Option<usize>) -> Result<Vec<SearchHit>, String> {
+        self.ensure_ready(Subsystem::Store)?;
+        limits::check_bytes("query", query.len(), limits::MAX_QUERY_BYTES)?;
+        let terms = search::terms(query);
+        let sort = self.resolve_sort(None);
+        let list = self.store.lock().unwrap().query(sort, |n| search::matches(n, &terms), 0, limit);
+        let hits: Vec<SearchHit> = list
+            .into_iter()
+            .filter_map(|n| {
+                let matches = search::highlight(&n, &terms)?;
+                Some(SearchHit { notification: n, matches })
+            })
+            .collect();
+        println!("[cmd] search_notifications ({:?}) -> {} hits", query, hits.len());
+        Ok(hits)
+    }
+
+    /// 确定本次列表使用的排序：显式指定时记住该选择，否则沿用上次的
+    pub(crate) fn resolve_sort(&self, sort: Option<SortMode>) -> SortMode {
+        let Some(sort) = sort else {
+            return self.view_state.read().sort;
+        };
+        let snapshot = {
+            let mut view = self.view_state.write();
+            if view.sort == sort {
+                return sort;
+            }
+            view.sort = sort;
+            view.clone()
+        };
+        if let Err(e) = self.storage.save(VIEW_STATE_FILE, &snapshot) {
+            println!("[Storage] {}", e);
+        }
+        sort
+    }
+
+    /// 停止并移除配对服务器，返回之前是否在运行。
+    /// 监听循环在整个运行期间持有读锁，先通知它退出再取写锁，否则会一直等到本轮等待超时
+    pub(crate) fn stop_pairing_server(&self) -> bool {
+        if let Some(server) = self.temp_server.read().as_ref() {
+            server.stop();
+        }
+        self.temp_server.write().take().is_some()
+    }
+
+    /// 后台组件上报错误：写入缓冲区，去重/限流后推送 background-error 事件
+    pub fn report_error(&self, err: BackgroundError) {
+        println!("[ErrorBus] {}/{}: {}", err.source, err.code, err.message);
+        if let Some(report) = self.errors.report(err, std::time::Instant::now()) {
+            self.events.emit("background-error", report);
+        }
+    }
+
+    /// 通知被移除后的统一处理：记录删除原因、通知前端并关闭对应的弹出窗口
+    pub(crate) fn on_removed(&self, removed: &[Notification], reason: RemovalReason) {
+        if removed.is_empty() {
+            return;
+        }
+        for n in removed {
+            self.events.emit("notification-removed", serde_json::json!({ "id": n.id, "reason": reason }));
+        }
+        self.finish_removal(removed, reason);
+    }
+
+    /// 删除后的收尾：删除记录、本地条目持久化、计数与弹出窗口（事件由调用方发送）
+    pub(crate) fn finish_removal(&self, removed: &[Notification], reason: RemovalReason) {
+        self.record_removals(removed, reason);
+        // 转发来的通知在本机删除时回传给源电脑
+        if reason == RemovalReason::UserDeletedLocal {
+            self.relay_forward_action(RelayAction::Dismiss, removed);
+        }
+        if removed.iter().any(|n| n.local) {
+            self.save_local_notifications();
+        }
+        let ids: Vec<String> = removed.iter().map(|n| n.id.clone()).collect();
+        self.emit_counts();
+        self.close_windows(self.popouts.take_for(&ids));
+    }
+
+    /// 写入删除记录并持久化
+    pub(crate) fn record_removals(&self, removed: &[Notification], reason: RemovalReason) {
+        let now = chrono::Utc::now().timestamp();
+        let log = {
+            let mut log = self.removal_log.lock().unwrap();
+            for n in removed {
+                log.record(Tombstone::new(n, reason, now));
+            }
+            log.clone()
+        };
+        if let Err(e) = self.storage.save(REMOVAL_LOG_FILE, &log) {
+            println!("[Storage] {}", e);
+        }
+    }
+
+    /// 关闭所有弹出窗口（清空列表、退出应用时调用）
+    pub fn close_all_popouts(&self) {
+        self.close_windows(self.popouts.take_all());
+    }
+
+    fn close_windows(&self, labels: Vec<String>) {
+        let Some(app) = self.events.app() else {
+            return;
+        };
+        for label in labels {
+            if let Some(win) = app.get_webview_window(&label) {
+                println!("[Popout] Closing {}", label);
+                let _ = win.close();
+            }
+        }
+    }
+
+    pub(crate) fn has_connections(&self) -> bool {
+        !self.clients.read().is_empty()
+    }
+
+    pub(crate) fn counts(&self) -> Counts {
+        self.store.lock().unwrap().counts()
+    }
+
+    /// 推送 counts-changed（带变更序号，前端据此丢弃乱序到达的旧计数）
+    pub(crate) fn emit_counts(&self) {
+        let counts = self.store.lock().unwrap().versioned_counts();
+        self.events.emit("counts-changed", counts);
+        self.persist_seq_if_due();
+        self.refresh_tray_menu();
+        self.refresh_tray_tooltip();
+    }
+}
+
+#[tauri::command]
+pub fn get_counts(state: State<AppState>, filter: Option<NotificationFilter>) -> Result<Counts, String> {
+    // 与 list_notifications 相同的过滤条件，徽标可显示筛选后的数量
+    let counts = match filter {
+        Some(filter) => {
+            limits::check_filter(&filter)?;
+            filter.validate()?;
+            state.store.lock().unwrap().counts_filtered(&filter)
+        }
+        None => state.counts(),
+    };
+    println!("[cmd] get_counts -> unread={}, total={}", counts.unread, counts.total);
+    Ok(counts)
+}
+
+/// 按应用的计数，未读多的在前；无包名的通知归为一组。各组之和与 get_counts 一致
+#[tauri::command]
+pub fn get_counts_by_package(state: State<AppState>) -> Result<Vec<PackageCounts>, String> {
+    Ok(state.store.lock().unwrap().counts_by_package())
+}
+
+/// 计数 + 变更序号，与 counts-changed 事件携带的 seq 一致
+#[tauri::command]
+pub fn get_counts_versioned(state: State<AppState>) -> Result<VersionedCounts, String> {
+    Ok(state.store.lock().unwrap().versioned_counts())
+}
+
+/// 首屏快照（store 就绪后可用），加载页结束后用它填充主窗口
+#[tauri::command]
+pub fn get_startup_snapshot(state: State<AppState>) -> Result<StartupSnapshot, String> {
+    state.startup_snapshot()
+}
+
+/// 后端存活与版本探测（错误边界、WebView 崩溃后的重连）
+#[tauri::command]
+pub fn ping(state: State<AppState>) -> Ping {
+    state.ping()
+}
+
+/// 已过时，请使用 ping；保留一个版本供旧前端包调用
+#[tauri::command]
+pub fn greet(name: &str) -> String {
+    crate::ping::greet(name)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct ListOptions {
+    /// 为每条通知附带格式化后的相对时间，前端无需逐行调用 format_timestamp
+    pub with_relative_time: bool,
+    /// 过滤条件（包名、未读、时间范围、搜索、语言），与批量操作共用
+    #[serde(flatten)]
+    pub filter: NotificationFilter,
+    /// 排序方式；不传时沿用上次的选择
+    pub sort: Option<SortMode>,
+    /// 同时返回静音的通知（默认不返回，前端折叠为“已静音”分组；也可用 muted 过滤只取该分组）
+    pub include_muted: bool,
+    /// 同时返回归档的通知（默认不返回；也可用 archived 过滤只看归档）
+    pub include_archived: bool,
+    /// 分页：跳过条数
+    pub offset: usize,
+    /// 分页：最多返回条数
+    pub limit: Option<usize>,
+}
+
+#[tauri::command]
+pub fn list_notifications(state: State<AppState>, options: Option<ListOptions>) -> Result<Vec<Notification>, String> {
+    state.list_notifications(options.unwrap_or_default())
+}
+
+/// 按安卓通知分组返回（组内嵌套，按组内最新时间排序）；过滤与分页选项同 list_notifications，分页按组计
+#[tauri::command]
+pub fn list_notification_groups(state: State<AppState>, options: Option<ListOptions>) -> Result<Vec<NotificationGroup>, String> {
+    state.list_notification_groups(options.unwrap_or_default())
+}
+
+/// 本地某一天（YYYY-MM-DD）的每小时通知数，用于活动条与按时段跳转
+#[tauri::command]
+pub fn get_day_summary(state: State<AppState>, date: String) -> Result<DaySummary, String> {
+    state.day_summary(&date)
+}
+
+/// 搜索通知，返回每条命中及各字段的匹配区间（原始字符串的字符下标）
+#[tauri::command]
+pub fn search_notifications(
+    state: State<AppState>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    state.search_notifications(&query, limit)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct IdsOptions {
+    pub ids: Vec<String>,
+}
+
+/// 归档（同时标为已读），返回实际变化的条数
+#[tauri::command]
+pub fn archive(state: State<AppState>, options: IdsOptions) -> Result<usize, String> {
+    println!("[cmd] archive -> {} ids", options.ids.len());
+    state.set_archived_ids(&options.ids, true)
+}
+
+/// 取消归档（保持已读），返回实际变化的条数
+#[tauri::command]
+pub fn unarchive(state: State<AppState>, options: IdsOptions) -> Result<usize, String> {
+    println!("[cmd] unarchive -> {} ids", options.ids.len());
+    state.set_archived_ids(&options.ids, false)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SnoozeOptions {
+    pub ids: Vec<String>,
+    /// 到期时间（秒），到期后重新作为未读出现
+    pub until: i64,
+}
+
+/// 暂缓到指定时间（已暂缓的只更新时间），返回暂缓的条数
+#[tauri::command]
+pub fn snooze(state: State<AppState>, options: SnoozeOptions) -> Result<usize, String> {
+    println!("[cmd] snooze -> {} ids until {}", options.ids.len(), options.until);
+    state.snooze_ids(&options.ids, options.until, state.wall_clock.now())
+}
+
+#[tauri::command]
+pub fn mark_read(state: State<AppState>, options: IdsOptions) -> Result<bool, String> {
+    state.mark_read_ids(&options.ids)?;
+    println!("[cmd] mark_read -> {} ids", options.ids.len());
+    Ok(true)
+}
+
+/// 撤销已读，返回实际变化的条数
+#[tauri::command]
+pub fn mark_unread(state: State<AppState>, options: IdsOptions) -> Result<usize, String> {
+    let changed = state.mark_unread_ids(&options.ids)?;
+    println!("[cmd] mark_unread -> {} changed", changed);
+    Ok(changed)
+}
+
+// ============ 按过滤条件批量操作 ============
+
+#[tauri::command]
+pub fn mark_read_where(
+    state: State<AppState>,
+    filter: NotificationFilter,
+    dry_run: Option<bool>,
+) -> Result<BulkResult, String> {
+    limits::check_filter(&filter)?;
+    filter.validate()?;
+    let result = state.apply_where(BulkAction::MarkRead, &filter, dry_run.unwrap_or(false));
+    println!("[cmd] mark_read_where -> {:?}", result);
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn delete_where(
+    state: State<AppState>,
+    filter: NotificationFilter,
+    dry_run: Option<bool>,
+) -> Result<BulkResult, String> {
+    limits::check_filter(&filter)?;
+    filter.validate()?;
+    let result = state.apply_where(BulkAction::Delete, &filter, dry_run.unwrap_or(false));
+    println!("[cmd] delete_where -> {:?}", result);
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn pin_where(
+    state: State<AppState>,
+    filter: NotificationFilter,
+    pinned: bool,
+    dry_run: Option<bool>,
+) -> Result<BulkResult, String> {
+    limits::check_filter(&filter)?;
+    filter.validate()?;
+    let action = if pinned { BulkAction::Pin } else { BulkAction::Unpin };
+    let result = state.apply_where(action, &filter, dry_run.unwrap_or(false));
+    println!("[cmd] pin_where({}) -> {:?}", pinned, result);
+    Ok(result)
+}
+
+// ============ 本地提醒 ============
+
+#[tauri::command]
+pub fn create_local_notification(
+    state: State<AppState>,
+    title: String,
+    text: String,
+    remind_at: Option<i64>,
+) -> Result<Notification, String> {
+    println!("[cmd] create_local_notification -> remind_at={:?}", remind_at);
+    state.create_local_notification(title, text, remind_at, state.wall_clock.now())
+}
+
+#[tauri::command]
+pub fn list_local_reminders(state: State<AppState>) -> Vec<Notification> {
+    state.list_local_reminders()
+}
+
+#[tauri::command]
+pub fn cancel_local_reminder(state: State<AppState>, id: String) -> Result<bool, String> {
+    println!("[cmd] cancel_local_reminder -> {}", id);
+    state.cancel_local_reminder(&id)
+}
+
+/// 置顶/取消置顶（置顶通知不会被保留策略淘汰）
+#[tauri::command]
+pub fn set_pinned(state: State<AppState>, options: IdsOptions, pinned: bool) -> Result<usize, String> {
+    let changed = state.set_pinned_ids(&options.ids, pinned)?;
+    println!("[cmd] set_pinned({}) -> {} changed", pinned, changed);
+    Ok(changed)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct IdOptions {
+    pub id: String,
+}
+
+#[tauri::command]
+pub fn delete(state: State<AppState>, options: IdOptions) -> bool {
+    let removed = state.store.lock().unwrap().remove(&options.id);
+    if let Some(n) = removed {
+        state.on_removed(&[n], RemovalReason::UserDeletedLocal);
+    }
+    println!("[cmd] delete -> {}", options.id);
+    true
+}
+
+#[tauri::command]
+pub fn delete_all(state: State<AppState>) -> bool {
+    let mut removed = state.store.lock().unwrap().clear();
+    let n = removed.len();
+    state.rule_review.clear();
+    // 删除记录只保留最近的若干条，这里只记录最新的那部分
+    removed.sort_by_key(crate::store::sort_ts);
+    let skip = n.saturating_sub(crate::tombstones::MAX_TOMBSTONES);
+    state.record_removals(&removed[skip..], RemovalReason::UserDeletedLocal);
+    state.events.emit("notifications-cleared", serde_json::json!({ "reason": RemovalReason::UserDeletedLocal }));
+    state.emit_counts();
+    state.close_all_popouts();
+    state.audit("delete_all", AuditSource::Command, n, serde_json::Value::Null);
+    println!("[cmd] delete_all -> cleared {} items", n);
+    true
+}
+
+/// 删除某个应用的全部通知，返回删除条数
+#[tauri::command]
+pub fn delete_by_package(state: State<AppState>, package_name: String) -> Result<usize, String> {
+    println!("[cmd] delete_by_package -> {}", package_name);
+    state.delete_by_package(&package_name)
+}
+
+/// 删除已读通知（含归档的，置顶的保留）；给出 older_than_seconds 时只删更早的，返回删除条数
+#[tauri::command]
+pub fn delete_read(state: State<AppState>, older_than_seconds: Option<u64>) -> Result<usize, String> {
+    println!("[cmd] delete_read -> older_than={:?}", older_than_seconds);
+    Ok(state.delete_read(older_than_seconds, state.wall_clock.now()))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AddDummyOptions {
+    pub count: Option<u32>,
+}
+
+#[tauri::command]
+pub fn add_dummy(state: State<AppState>, options: Option<AddDummyOptions>) -> bool {
+    let count = options.and_then(|o| o.count).unwrap_or(5).clamp(1, 50);
+    let now = chrono::Utc::now().timestamp();
+    for n in crate::bench::demo_notifications(&format!("demo-{}", now), now, count as usize) {
+        let id = n.id.clone();
+        state.ingest_from("local", Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(n),
+            id: Some(id),
+        });
+    }
+    println!("[cmd] add_dummy -> {} items", count);
+    true
+}
+
+// ============ 通知详情弹出窗口 ============
+
+/// 在独立小窗口中打开通知详情；已打开则聚焦
+#[tauri::command]
+pub fn open_notification_window(
+    app: tauri::AppHandle,
+    state: State<AppState>,
+    id: String,
+) -> Result<String, String> {
+    let title = {
+        let store = state.store.lock().unwrap();
+        let n = store.get(&id).ok_or_else(|| format!("Notification not found: {}", id))?;
+        // 窗口标题也按隐私级别处理
+        let preview = crate::privacy::preview_for(&state.settings.read().privacy, n, Surface::Popout);
+        Some(preview.title).filter(|t| !t.is_empty()).unwrap_or_else(|| "Notification".to_string())
+    };
+    let cfg = state.settings.read().popout.clone();
+
+    let label = match state.popouts.reserve(&id, cfg.max_windows)? {
+        Reservation::Existing(label) => {
+            if let Some(win) = app.get_webview_window(&label) {
+                if let Ok(true) = win.is_minimized() {
+                    let _ = win.unminimize();
+                }
+                let _ = win.show();
+                let _ = win.set_focus();
+                println!("[cmd] open_notification_window -> focused {}", label);
+                return Ok(label);
+            }
+            // 窗口已不存在（登记残留），重新创建
+            state.popouts.remove_label(&label);
+            match state.popouts.reserve(&id, cfg.max_windows)? {
+                Reservation::New(label) | Reservation::Existing(label) => label,
+            }
+        }
+        Reservation::New(label) => label,
+    };
+
+    let url = format!("index.html?popout={}", crate::popout::encode_id(&id));
+    let built = tauri::WebviewWindowBuilder::new(&app, &label, tauri::WebviewUrl::App(url.into()))
+        .title(title)
+        .inner_size(cfg.width, cfg.height)
+        .always_on_top(cfg.always_on_top)
+        .skip_taskbar(false)
+        .build();
+
+    match built {
+        Ok(win) => {
+            let app_handle = app.clone();
+            let win_label = label.clone();
+            win.on_window_event(move |e| {
+                if let tauri::WindowEvent::Destroyed = e {
+                    app_handle.state::<AppState>().popouts.remove_label(&win_label);
+                }
+            });
+            println!("[cmd] open_notification_window -> created {}", label);
+            Ok(label)
+        }
+        Err(e) => {
+            state.popouts.remove_label(&label);
+            Err(format!("Failed to create window: {}", e))
+        }
+    }
+}
+
+/// 弹出窗口显示的内容（已按隐私级别处理）
+#[tauri::command]
+pub fn get_notification_preview(state: State<AppState>, id: String) -> Result<Preview, String> {
+    let store = state.store.lock().unwrap();
+    let n = store.get(&id).ok_or_else(|| format!("Notification not found: {}", id))?;
+    Ok(crate::privacy::preview_for(&state.settings.read().privacy, n, Surface::Popout))
+}
+
+/// 设置界面实时预览模板（用指定通知渲染，模板不合法时返回 InvalidTemplate）
+#[tauri::command]
+pub fn preview_template(state: State<AppState>, template: String, notification_id: String) -> Result<String, String> {
+    state.preview_template(&template, &notification_id)
+}
+
+/// 复制/导出用的通知文本（按 clipboard 模板渲染）
+#[tauri::command]
+pub fn get_notification_text(state: State<AppState>, id: String) -> Result<String, String> {
+    state.notification_text(&id)
+}
+
+/// 全局“隐藏预览”开关（托盘菜单同步更新）
+#[tauri::command]
+pub fn set_hide_previews(state: State<AppState>, hide: bool) -> Result<(), String> {
+    println!("[cmd] set_hide_previews -> {}", hide);
+    state.set_hide_previews(hide)
+}
+
+/// 只读观察模式：开启时不向手机发出任何改变其状态的操作（仍正常接收）
+#[tauri::command]
+pub fn set_watch_only(state: State<AppState>, enabled: bool) -> Result<WatchOnlyStatus, String> {
+    println!("[cmd] set_watch_only -> {}", enabled);
+    state.set_watch_only(enabled)
+}
+
+#[tauri::command]
+pub fn get_watch_only_status(state: State<AppState>) -> WatchOnlyStatus {
+    state.watch_only_status()
+}
+
+/// 观察模式关闭后确认发出期间暂存的操作
+#[tauri::command]
+pub fn release_outbox(state: State<AppState>) -> Result<OutboxRelease, String> {
+    let release = state.release_outbox()?;
+    println!("[cmd] release_outbox -> {:?}", release);
+    Ok(release)
+}
+
+#[tauri::command]
+pub fn list_open_windows(state: State<AppState>) -> Vec<PopoutWindow> {
+    state.popouts.list()
+}
+
+// ============ 设置与时间格式化命令 ============
+
+#[tauri::command]
+pub fn get_settings(state: State<AppState>) -> Result<Settings, String> {
+    state.ensure_ready(Subsystem::Settings)?;
+    Ok(state.settings.read().clone())
+}
+
+#[tauri::command]
+pub fn set_settings(state: State<AppState>, settings: Settings) -> Result<Settings, String> {
+    state.ensure_ready(Subsystem::Settings)?;
+    limits::check_settings(&settings)?;
+    settings.validate()?;
+    // 命中统计由后端累计，界面传回的可能已过时；已删除规则的统计一并去掉
+    let mut settings = settings;
+    settings.rule_hits = state.settings.read().rule_hits.clone();
+    settings.rule_hits.retain(|id, _| settings.rules.iter().any(|r| &r.id == id));
+    *state.settings.write() = settings.clone();
+    state.storage.save(SETTINGS_FILE, &settings)?;
+    state.apply_webhook_settings();
+    state.apply_importance_map();
+    state.apply_watch_only();
+    state.refresh_tray_icon();
+    state.refresh_tray_menu();
+    state.refresh_tray_tooltip();
+    if let Err(e) = state.apply_low_power() {
+        println!("[cmd] set_settings -> low-power switch failed: {}", e);
+    }
+    println!("[cmd] set_settings -> locale={}", settings.locale);
+    Ok(settings)
+}
+
+/// 当前的保留策略
+#[tauri::command]
+pub fn get_retention_policy(state: State<AppState>) -> RetentionSettings {
+    state.retention_policy()
+}
+
+/// 修改保留策略（写入设置），并按新策略立即清理一次
+#[tauri::command]
+pub fn set_retention_policy(state: State<AppState>, policy: RetentionSettings) -> Result<RetentionSettings, String> {
+    state.ensure_ready(Subsystem::Settings)?;
+    println!("[cmd] set_retention_policy -> {:?}", policy);
+    state.set_retention_policy(policy, state.wall_clock.now())
+}
+
+/// 最近的删除记录（新 -> 旧）
+#[tauri::command]
+pub fn get_removal_log(state: State<AppState>, limit: Option<usize>) -> Vec<Tombstone> {
+    state.removal_log.lock().unwrap().recent(limit.unwrap_or(50))
+}
+
+#[tauri::command]
+pub fn factory_reset(state: State<AppState>) -> Result<(), String> {
+    println!("[cmd] factory_reset");
+    state.factory_reset()
+}
+
+/// 最近的破坏性操作记录（新 -> 旧）
+#[tauri::command]
+pub fn get_audit_log(state: State<AppState>, limit: Option<usize>) -> Vec<AuditEntry> {
+    state.audit.recent(limit.unwrap_or(100))
+}
+
+/// 主窗口隐藏期间缓冲的事件，序号在 (after_seq, until_seq] 内（见 window-restored）
+#[tauri::command]
+pub fn get_events_since(state: State<AppState>, after_seq: u64, until_seq: Option<u64>) -> Result<Vec<BufferedEvent>, String> {
+    state.events.window_feed().since(after_seq, until_seq)
+}
+
+/// 最近 hours 小时（1-24）每 15 分钟的通知量；package 为空时为所有应用合计
+#[tauri::command]
+pub fn get_volume_history(state: State<AppState>, package: Option<String>, hours: u8) -> VolumeHistory {
+    state.volume_history(package.as_deref(), hours)
+}
+
+/// 诊断面板：核对存储的索引与计数，repair 为 true 时重建
+#[tauri::command]
+pub fn verify_store_integrity(state: State<AppState>, repair: Option<bool>) -> IntegrityReport {
+    let repair = repair.unwrap_or(false);
+    println!("[cmd] verify_store_integrity -> repair={}", repair);
+    state.verify_store_integrity(repair)
+}
+
+/// 诊断面板：数据目录的写入耗时、只在内存中的文件与降级的子系统
+#[tauri::command]
+pub fn get_storage_status(state: State<AppState>) -> StorageStatus {
+    state.storage_status()
+}
+
+// ============ 静音 ============
+
+/// 静音一个应用、渠道、会话或某应用的通知 id（末尾 * 前缀匹配）；expires_at 为空表示一直静音，exempt 为 true 时设为例外
+#[tauri::command]
+pub fn mute(state: State<AppState>, target: MuteTarget, expires_at: Option<i64>, exempt: Option<bool>) -> Result<Mute, String> {
+    state.mute(target, expires_at, exempt.unwrap_or(false))
+}
+
+/// 某个应用见过的安卓通知渠道与各渠道的通知数
+#[tauri::command]
+pub fn list_channels(state: State<AppState>, package_name: String) -> Vec<ChannelInfo> {
+    state.list_channels(&package_name)
+}
+
+#[tauri::command]
+pub fn unmute(state: State<AppState>, id: String) -> Result<(), String> {
+    state.unmute(&id)
+}
+
+#[tauri::command]
+pub fn list_mutes(state: State<AppState>) -> Vec<MuteInfo> {
+    state.list_mutes()
+}
+
+// ============ 数据档案 ============
+
+#[tauri::command]
+pub fn list_profiles(state: State<AppState>) -> ProfilesView {
+    state.list_profiles()
+}
+
+/// 新建档案；settings_overrides 为新档案的初始设置（叠加在默认设置上的部分字段）
+#[tauri::command]
+pub fn create_profile(state: State<AppState>, name: String, settings_overrides: Option<serde_json::Value>) -> Result<ProfileInfo, String> {
+    state.create_profile(&name, settings_overrides)
+}
+
+/// 设置或清除档案的 PIN（pin 为空时清除）
+#[tauri::command]
+pub fn set_profile_lock(state: State<AppState>, id: String, pin: Option<String>) -> Result<ProfileInfo, String> {
+    state.set_profile_lock(&id, pin.as_deref())
+}
+
+/// 切换档案（有设备连接时返回 ProfileBusy）；成功后推送 profile-switched，前端重新拉取全部数据
+#[tauri::command]
+pub async fn switch_profile(app: tauri::AppHandle, id: String, pin: Option<String>) -> Result<ProfileInfo, String> {
+    println!("[cmd] switch_profile -> {}", id);
+    tokio::task::spawn_blocking(move || app.state::<AppState>().switch_profile(&id, pin.as_deref()))
+        .await
+        .map_err(|e| format!("Profile switch panicked: {}", e))?
+}
+
+/// 开发工具：向已连接的手机发送一帧原始 JSON，返回对应的回复（见 raw_frame）
+#[tauri::command]
+pub async fn send_raw_frame(
+    app: tauri::AppHandle,
+    connection_id: String,
+    json: String,
+    expect_reply: bool,
+    timeout_ms: Option<u64>,
+    allow_token: Option<bool>,
+) -> Result<RawFrameResult, String> {
+    println!("[cmd] send_raw_frame -> {}", connection_id);
+    tokio::task::spawn_blocking(move || {
+        app.state::<AppState>().send_raw_frame(&connection_id, &json, expect_reply, timeout_ms, allow_token.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| format!("Raw frame panicked: {}", e))?
+}
+
+/// 整体健康状态（含主窗口是否可用）
+#[tauri::command]
+pub fn get_health(state: State<AppState>) -> Health {
+    state.health()
+}
+
+/// 全部命令的名称、描述键与参数/返回值的 JSON Schema（见 catalog）
+#[tauri::command]
+pub fn get_command_catalog() -> serde_json::Value {
+    crate::catalog::catalog().clone()
+}
+
+/// 弹出测试通知，返回平时会拦截它的策略
+#[tauri::command]
+pub fn send_test_toast(state: State<AppState>, package_name: Option<String>) -> Result<TestAlertReport, String> {
+    println!("[cmd] send_test_toast -> package_name={:?}", package_name);
+    state.send_test_toast(package_name)
+}
+
+/// 播放测试提示音
+#[tauri::command]
+pub fn play_test_sound(state: State<AppState>, name: Option<String>) -> Result<TestAlertReport, String> {
+    println!("[cmd] play_test_sound -> name={:?}", name);
+    state.play_test_sound(name)
+}
+
+/// 打破另一台机器的数据目录锁，退出只读模式
+#[tauri::command]
+pub fn force_take_ownership(state: State<AppState>) -> Result<DataLockStatus, String> {
+    println!("[cmd] force_take_ownership");
+    state.force_take_ownership()
+}
+
+/// 重新尝试创建主窗口（仅托盘模式下）
+#[tauri::command]
+pub async fn retry_create_window(app: tauri::AppHandle) -> Result<UiStatus, String> {
+    println!("[cmd] retry_create_window");
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let handle = app.clone();
+    app.run_on_main_thread(move || {
+        let status = handle.state::<AppState>().start_ui(|| crate::ui_window::create_main_window(&handle));
+        let _ = tx.send(status);
+    })
+    .map_err(|e| format!("Failed to reach the main thread: {}", e))?;
+    rx.await.map_err(|e| format!("Window creation did not finish: {}", e))
+}
+
+/// 系统勿扰/专注模式状态（检测不到时为 unknown）
+#[tauri::command]
+pub async fn get_os_focus_state(app: tauri::AppHandle) -> Result<OsFocusStatus, String> {
+    tokio::task::spawn_blocking(move || app.state::<AppState>().os_focus_state())
+        .await
+        .map_err(|e| format!("Focus detection panicked: {}", e))
+}
+
+/// 为浏览器扩展写入 native messaging host 清单
+#[tauri::command]
+pub fn install_native_messaging_host(browser: Browser, extension_id: String) -> Result<InstalledHost, String> {
+    println!("[cmd] install_native_messaging_host -> {:?}", browser);
+    crate::native_host::install(browser, &extension_id)
+}
+
+/// 用最近一次配对请求的来源地址检查手机与电脑是否在同一网络
+#[tauri::command]
+pub fn verify_same_network_hint(state: State<AppState>) -> SameNetworkHint {
+    state.verify_same_network_hint()
+}
+
+// ============ 协议帧检查器（开发用） ============
+
+/// 开启/关闭某个连接的帧采集；发布构建中返回错误
+#[tauri::command]
+pub fn enable_frame_capture(state: State<AppState>, connection_id: String, enabled: Option<bool>) -> Result<bool, String> {
+    state.tracer.inspector.set_enabled(&connection_id, enabled.unwrap_or(true))
+}
+
+/// 最近采集的帧（旧 -> 新）
+#[tauri::command]
+pub fn get_captured_frames(state: State<AppState>, connection_id: String, limit: Option<usize>) -> Result<Vec<CapturedFrame>, String> {
+    state.tracer.inspector.frames(&connection_id, limit.unwrap_or(100))
+}
+
+/// 清空采集结果；不指定连接时清空全部
+#[tauri::command]
+pub fn clear_captured_frames(state: State<AppState>, connection_id: Option<String>) -> Result<usize, String> {
+    state.tracer.inspector.clear(connection_id.as_deref())
+}
+
+// ============ 本地 API 令牌 ============
+
+/// 创建令牌；明文只在返回值中出现一次
+#[tauri::command]
+pub fn create_api_token(state: State<AppState>, name: String, scopes: Vec<ApiScope>) -> Result<CreatedApiToken, String> {
+    println!("[cmd] create_api_token -> {} {:?}", name, scopes);
+    state.create_api_token(&name, scopes)
+}
+
+#[tauri::command]
+pub fn list_api_tokens(state: State<AppState>) -> Vec<ApiTokenInfo> {
+    state.list_api_tokens()
+}
+
+#[tauri::command]
+pub fn revoke_api_token(state: State<AppState>, id: String) -> Result<ApiTokenInfo, String> {
+    println!("[cmd] revoke_api_token -> {}", id);
+    state.revoke_api_token(&id)
+}
+
+/// 在系统文件管理器中打开数据/日志/配置/备份目录，返回解析后的路径
+#[tauri::command]
+pub fn reveal_path(app: tauri::AppHandle, target: DirTarget) -> Result<RevealResult, String> {
+    let result = crate::app_dirs::reveal(&app, target)?;
+    println!("[cmd] reveal_path({:?}) -> {} (opened={})", target, result.path, result.opened);
+    Ok(result)
+}
+
+// ============ 首次使用引导 ============
+
+#[tauri::command]
+pub fn get_onboarding_state(state: State<AppState>) -> OnboardingState {
+    state.onboarding.get()
+}
+
+/// 前端推进引导（如看完权限说明、展示了二维码）；不允许后退
+#[tauri::command]
+pub fn advance_onboarding(state: State<AppState>, step: OnboardingStep) -> OnboardingState {
+    println!("[cmd] advance_onboarding -> {:?}", step);
+    state.advance_onboarding(step)
+}
+
+#[tauri::command]
+pub fn get_view_state(state: State<AppState>) -> ViewState {
+    state.view_state.read().clone()
+}
+
+// ============ 后台错误命令 ============
+
+#[tauri::command]
+pub fn get_recent_errors(state: State<AppState>, limit: Option<usize>) -> Vec<ErrorReport> {
+    state.errors.recent(limit.unwrap_or(50))
+}
+
+#[tauri::command]
+pub fn dismiss_errors(state: State<AppState>, ids: Vec<String>) -> Result<usize, String> {
+    limits::check_ids("ids", &ids, &state.payload_limits())?;
+    let n = state.errors.dismiss(&ids);
+    println!("[cmd] dismiss_errors -> {} removed", n);
+    Ok(n)
+}
+
+/// 正在运行的后台任务及其运行时长（诊断用）
+#[tauri::command]
+pub fn get_background_tasks(state: State<AppState>) -> Vec<TaskInfo> {
+    state.tasks.list()
+}
+
+/// 通知事件合并器的当前模式与合并/丢弃计数（诊断用，便于调整阈值）
+#[tauri::command]
+pub fn get_event_batcher_stats(state: State<AppState>) -> BatcherStats {
+    state.events.batcher_stats()
+}
+
+/// 托盘各部分的更新请求与实际应用次数
+#[tauri::command]
+pub fn get_tray_update_stats(state: State<AppState>) -> Vec<TraySurfaceStats> {
+    state.tray_updates.stats()
+}
+
+// ============ Webhook 推送 ============
+
+/// 推送状态：待发送条数、因上限丢弃的条数、最近错误
+#[tauri::command]
+pub fn get_webhook_status(state: State<AppState>) -> WebhookStatus {
+    state.webhook_status()
+}
+
+/// 使用统计将要上传的内容（与实际上传的完全一致）
+#[tauri::command]
+pub fn preview_metrics_payload(state: State<AppState>) -> MetricsPayload {
+    state.metrics_payload(chrono::Utc::now().timestamp())
+}
+
+/// 删除本地统计数据与安装 id
+#[tauri::command]
+pub fn delete_metrics_data(state: State<AppState>) {
+    state.delete_metrics_data();
+}
+
+// ============ 事件流导出 ============
 
-use crate::types::Notification;
-use crate::network_utils;
-use crate::temp_server::TempServer;
-use crate::android_client::AndroidSocketClient;
+/// 把过滤规则与单应用预览级别导出为预设文件，返回规则条数
+#[tauri::command]
+pub fn export_rules_preset(state: State<AppState>, path: String) -> Result<usize, String> {
+    println!("[cmd] export_rules_preset -> {}", path);
+    limits::check_bytes("path", path.len(), limits::MAX_PATH_BYTES)?;
+    state.ensure_ready(Subsystem::Settings)?;
+    state.export_rules_preset(std::path::Path::new(&path))
+}
 
-#[derive(Default)]
-pub struct AppState {
-    // 通知存储（临时内存实现）：id -> Notification
-    notifications: Mutex<HashMap<String, Notification>>,
-    // 已读集合
-    read_set: Mutex<HashSet<String>>,
-    // 临时服务器（用于扫码配对）
-    temp_server: Arc<RwLock<Option<TempServer>>>,
-    // 客户端连接池：connection_id -> AndroidSocketClient
-    clients: Arc<RwLock<HashMap<String, Arc<AndroidSocketClient>>>>,
+/// 导入预设（merge / replace），返回每个条目的校验结果
+#[tauri::command]
+pub fn import_rules_preset(state: State<AppState>, path: String, mode: ImportMode) -> Result<ImportReport, String> {
+    println!("[cmd] import_rules_preset -> {} ({:?})", path, mode);
+    limits::check_bytes("path", path.len(), limits::MAX_PATH_BYTES)?;
+    state.ensure_ready(Subsystem::Settings)?;
+    state.import_rules_preset(std::path::Path::new(&path), mode)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Counts {
-    pub unread: usize,
-    pub total: usize,
+/// 设置应用显示名与颜色；两项都为空时删除覆盖项，回落到内置名称或包名
+#[tauri::command]
+pub fn set_app_meta(
+    state: State<AppState>,
+    package_name: String,
+    display_name: Option<String>,
+    color: Option<String>,
+) -> Result<ResolvedAppMeta, String> {
+    println!("[cmd] set_app_meta -> {}", package_name);
+    state.ensure_ready(Subsystem::Settings)?;
+    limits::check_bytes("package_name", package_name.len(), limits::MAX_QUERY_BYTES)?;
+    state.set_app_meta(&package_name, display_name, color)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TempServerStatus {
-    pub running: bool,
-    pub port: u16,
-    pub waiting_for_pairing: bool,
+/// 内置与用户设置合并后的应用显示信息（包名 -> 显示名、颜色）
+#[tauri::command]
+pub fn get_app_meta_map(state: State<AppState>) -> Result<BTreeMap<String, ResolvedAppMeta>, String> {
+    state.ensure_ready(Subsystem::Settings)?;
+    Ok(state.app_meta_map())
 }
 
-impl AppState {
-    fn counts(&self) -> Counts {
-        let map = self.notifications.lock().unwrap();
-        let read = self.read_set.lock().unwrap();
-        let total = map.len();
-        let unread = total.saturating_sub(read.len());
-        Counts { unread, total }
-    }
+/// 最近被规则丢弃或静音的通知摘要（新 -> 旧）
+#[tauri::command]
+pub fn get_filtered_review(state: State<AppState>, limit: Option<usize>) -> Vec<FilteredEntry> {
+    state.rule_review.recent(limit.unwrap_or(50).min(crate::rule_review::REVIEW_CAPACITY))
 }
 
+/// 各规则的命中次数
 #[tauri::command]
-pub fn get_counts(state: State<AppState>) -> Counts {
-    let counts = state.counts();
-    println!("[cmd] get_counts -> unread={}, total={}", counts.unread, counts.total);
-    counts
+pub fn get_rule_stats(state: State<AppState>) -> Result<Vec<RuleStats>, String> {
+    state.ensure_ready(Subsystem::Settings)?;
+    Ok(state.rule_stats())
 }
 
+/// 把复查列表中的条目作为普通通知入库（绕过命中的规则）
 #[tauri::command]
-pub fn list_notifications(state: State<AppState>) -> Vec<Notification> {
-    let map = state.notifications.lock().unwrap();
-    let mut list: Vec<Notification> = map.values().cloned().collect();
-    // 新 -> 旧（按 updated_at/posted_at）
-    list.sort_by(|a, b| {
-        let at = a.updated_at.or(a.posted_at).unwrap_or_default();
-        let bt = b.updated_at.or(b.posted_at).unwrap_or_default();
-        bt.cmp(&at)
-    });
-    println!("[cmd] list_notifications -> {} items", list.len());
-    list
+pub fn restore_filtered(state: State<AppState>, entry_id: String) -> Result<IngestOutcome, String> {
+    println!("[cmd] restore_filtered -> {}", entry_id);
+    state.restore_filtered(&entry_id)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IdsOptions {
-    pub ids: Vec<String>,
+/// 在复查界面直接停用规则
+#[tauri::command]
+pub fn disable_rule(state: State<AppState>, rule_id: String) -> Result<crate::rules::Rule, String> {
+    println!("[cmd] disable_rule -> {}", rule_id);
+    state.ensure_ready(Subsystem::Settings)?;
+    state.disable_rule(&rule_id)
 }
 
+/// 内部有界结构的当前大小与上限（仅 dev-tools 构建）
 #[tauri::command]
-pub fn mark_read(state: State<AppState>, options: IdsOptions) -> bool {
-    let mut read = state.read_set.lock().unwrap();
-    let mut map = state.notifications.lock().unwrap();
-    for id in options.ids.iter() {
-        read.insert(id.clone());
-        if let Some(n) = map.get_mut(id) {
-            n.read = true;
-        }
+pub fn get_internal_buffer_stats(state: State<AppState>) -> Result<Vec<crate::buffer_stats::BufferStat>, String> {
+    state.internal_buffer_stats()
+}
+
+/// 开始把入库事件按行追加到 path（NDJSON）；已在记录时切换到新文件。mask 缺省为不脱敏
+#[tauri::command]
+pub fn start_event_log(state: State<AppState>, path: String, mask: Option<MaskLevel>) -> Result<EventLogStatus, String> {
+    println!("[cmd] start_event_log -> {} ({:?})", path, mask);
+    limits::check_bytes("path", path.len(), limits::MAX_PATH_BYTES)?;
+    state.start_event_log(std::path::PathBuf::from(path), mask.unwrap_or_default())
+}
+
+/// 把诊断包写到 path；mask 缺省为 bodies_hashed，返回的清单注明实际级别
+#[tauri::command]
+pub async fn export_diagnostic_bundle(
+    app: tauri::AppHandle,
+    path: String,
+    mask: Option<MaskLevel>,
+) -> Result<crate::diagnostics::BundleManifest, String> {
+    println!("[cmd] export_diagnostic_bundle -> {} ({:?})", path, mask);
+    limits::check_bytes("path", path.len(), limits::MAX_PATH_BYTES)?;
+    tokio::task::spawn_blocking(move || {
+        app.state::<AppState>().export_diagnostic_bundle(std::path::Path::new(&path), mask)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// 导出全部通知到 path（为空时写到数据目录下带时间戳的文件）；mask 缺省为 none，返回实际路径与条数
+#[tauri::command]
+pub async fn export_notifications(
+    app: tauri::AppHandle,
+    path: Option<String>,
+    mask: Option<MaskLevel>,
+) -> Result<crate::export::ExportReport, String> {
+    println!("[cmd] export_notifications -> {:?} ({:?})", path, mask);
+    if let Some(path) = &path {
+        limits::check_bytes("path", path.len(), limits::MAX_PATH_BYTES)?;
     }
-    println!("[cmd] mark_read -> {} ids", options.ids.len());
-    true
+    tokio::task::spawn_blocking(move || {
+        app.state::<AppState>().export_notifications(path.map(std::path::PathBuf::from), mask, chrono::Utc::now().timestamp())
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IdOptions {
-    pub id: String,
+#[tauri::command]
+pub fn stop_event_log(state: State<AppState>) -> EventLogStatus {
+    println!("[cmd] stop_event_log");
+    state.stop_event_log()
 }
 
+/// 当前/最近一次导出的路径、写入字节数与丢弃行数
 #[tauri::command]
-pub fn delete(state: State<AppState>, options: IdOptions) -> bool {
-    let mut map = state.notifications.lock().unwrap();
-    let mut read = state.read_set.lock().unwrap();
-    map.remove(&options.id);
-    read.remove(&options.id);
-    println!("[cmd] delete -> {}", options.id);
-    true
+pub fn get_event_log_status(state: State<AppState>) -> EventLogStatus {
+    state.event_log.status()
 }
 
+/// 把内存中的通知迁移到持久化存储（分批写入并推送 migration-progress）；失败时返回 MigrationFailed，内存中的数据不变
 #[tauri::command]
-pub fn delete_all(state: State<AppState>) -> bool {
-    let mut map = state.notifications.lock().unwrap();
-    let mut read = state.read_set.lock().unwrap();
-    let n = map.len();
-    map.clear();
-    read.clear();
-    println!("[cmd] delete_all -> cleared {} items", n);
-    true
+pub async fn migrate_to_persistent_store(app: tauri::AppHandle) -> Result<crate::persistent_store::MigrationReport, String> {
+    println!("[cmd] migrate_to_persistent_store");
+    tokio::task::spawn_blocking(move || app.state::<AppState>().migrate_to_persistent_store().map_err(String::from))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AddDummyOptions {
-    pub count: Option<u32>,
+/// 当前通知存储后端与最近一次快照
+#[tauri::command]
+pub fn get_persistence_status(state: State<AppState>) -> crate::persistent_store::PersistenceStatus {
+    state.persistence_status()
 }
 
+// ============ 后台维护 ============
+
+/// 各维护任务的间隔与最近一次运行情况
 #[tauri::command]
-pub fn add_dummy(state: State<AppState>, options: Option<AddDummyOptions>) -> bool {
-    let count = options.and_then(|o| o.count).unwrap_or(5).clamp(1, 50);
-    let mut map = state.notifications.lock().unwrap();
-    let now = chrono::Utc::now().timestamp();
-    for i in 0..count {
-        let id = format!("demo-{}-{}", now, i);
-        let n = Notification {
-            id: id.clone(),
-            package_name: Some("com.demo.app".into()),
-            title: Some(format!("演示标题 {}", i + 1)),
-            text: Some(format!("这是第 {} 条示例通知", i + 1)),
-            read: false,
-            posted_at: Some(now + i as i64),
-            updated_at: None,
-        };
-        map.insert(id, n);
-    }
-    println!("[cmd] add_dummy -> {} items", count);
-    true
+pub fn get_maintenance_status(state: State<AppState>) -> Vec<JobStatus> {
+    state.maintenance.status()
+}
+
+/// 立即运行一个维护任务（排在正在运行的任务之后）
+#[tauri::command]
+pub async fn run_maintenance_now(app: tauri::AppHandle, job_id: String) -> Result<JobOutcome, String> {
+    let job = crate::maintenance::find_job(&job_id).ok_or_else(|| format!("Unknown job: {}", job_id))?;
+    let outcome = crate::maintenance::run_job(&app, job).await;
+    println!("[cmd] run_maintenance_now({}) -> {:?}", job_id, outcome);
+    Ok(outcome)
+}
+
+/// 在后台线程中压缩存储并清理数据目录，返回压缩前后的大小；进度经 compaction-progress 推送
+#[tauri::command]
+pub async fn compact_store(app: tauri::AppHandle) -> Result<CompactionReport, String> {
+    let report = tokio::task::spawn_blocking(move || app.state::<AppState>().compact_store())
+        .await
+        .map_err(|e| format!("Compaction panicked: {}", e))??;
+    println!("[cmd] compact_store -> {} ms", report.duration_ms);
+    Ok(report)
+}
+
+/// 开发工具：在临时目录中生成示例数据，测量加载、首页、入库与搜索耗时（不影响当前数据）
+#[tauri::command]
+pub async fn run_benchmarks(fixture_size: Option<usize>) -> Result<BenchReport, String> {
+    let report = tokio::task::spawn_blocking(move || crate::bench::run_in_temp_dir(fixture_size))
+        .await
+        .map_err(|e| format!("Benchmark panicked: {}", e))??;
+    println!("[cmd] run_benchmarks -> over budget: {:?}", report.over_budget);
+    Ok(report)
+}
+
+/// 按本地时区（含夏令时）与界面语言格式化时间戳（秒）
+#[tauri::command]
+pub fn format_timestamp(state: State<AppState>, ts: i64, style: TimeStyle) -> String {
+    let lang = state.settings.read().lang();
+    time_format::format_timestamp(ts, style, lang)
+}
+
+/// 批量版本，避免前端逐条 IPC
+#[tauri::command]
+pub fn format_timestamps(state: State<AppState>, timestamps: Vec<i64>, style: TimeStyle) -> Result<Vec<String>, String> {
+    limits::check_items("timestamps", timestamps.len(), limits::MAX_TIMESTAMPS)?;
+    let lang = state.settings.read().lang();
+    Ok(timestamps
+        .into_iter()
+        .map(|ts| time_format::format_timestamp(ts, style, lang))
+        .collect())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -150,11 +1440,36 @@ pub struct ConnectToServerOptions {
 
 // ============ 网络工具命令 ============
 
+/// 检查是否已有放行本程序该端口的防火墙规则（仅 Windows）
+#[tauri::command]
+pub async fn check_firewall_rule(port: u16) -> Result<FirewallRuleStatus, String> {
+    tokio::task::spawn_blocking(move || crate::firewall::check_rule(port))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 为本程序的指定端口添加专用网络入站放行规则（仅 Windows，会请求管理员权限）
+#[tauri::command]
+pub async fn create_firewall_rule(port: u16, name: Option<String>) -> Result<FirewallRuleResult, String> {
+    println!("[cmd] create_firewall_rule -> {}", port);
+    tokio::task::spawn_blocking(move || crate::firewall::create_rule(port, name.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 pub fn check_port_available(port: u16) -> bool {
     network_utils::check_port_available(port)
 }
 
+/// 查询占用端口的进程（最多约 2 秒，查不到时返回未知）
+#[tauri::command]
+pub async fn identify_port_user(port: u16) -> Result<PortUser, String> {
+    tokio::task::spawn_blocking(move || network_utils::identify_port_user(port))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn find_available_port(start_port: u16) -> Option<u16> {
     network_utils::find_available_port(start_port)
@@ -166,32 +1481,15 @@ pub fn get_local_ip() -> Result<String, String> {
 }
 
 #[tauri::command]
-pub fn get_device_uuid() -> Result<String, String> {
-    // 从配置文件读取UUID，如果不存在则生成新的
-    use std::fs;
-    use uuid::Uuid;
-
-    let config_dir = dirs::config_dir()
-        .ok_or("Failed to get config directory")?;
-    let app_config_dir = config_dir.join("notification-listener-project");
-    let uuid_file = app_config_dir.join("device_uuid.txt");
-
-    // 创建配置目录（如果不存在）
-    if !app_config_dir.exists() {
-        fs::create_dir_all(&app_config_dir)
-            .map_err(|e| format!("Failed to create config directory: {}", e))?;
-    }
+pub fn get_device_uuid(state: State<AppState>) -> Result<String, String> {
+    // 从配置目录读取UUID，如果不存在则生成新的（启动时已按机器 id 校验过）
+    state.device_uuid()
+}
 
-    // 读取或生成UUID
-    if uuid_file.exists() {
-        fs::read_to_string(&uuid_file)
-            .map_err(|e| format!("Failed to read UUID file: {}", e))
-    } else {
-        let new_uuid = Uuid::new_v4().to_string();
-        fs::write(&uuid_file, &new_uuid)
-            .map_err(|e| format!("Failed to write UUID file: {}", e))?;
-        Ok(new_uuid)
-    }
+/// 本机身份检查结果（device_uuid 与机器 id 均为哈希，诊断用）
+#[tauri::command]
+pub fn get_identity_info(state: State<AppState>) -> IdentityInfo {
+    state.identity.info()
 }
 
 #[tauri::command]
@@ -264,23 +1562,48 @@ pub async fn test_connect_to_server(host: String, port: u16) -> Result<String, S
 }
 
 #[tauri::command]
-pub async fn start_temp_server(state: State<'_, AppState>, port: u16) -> Result<u16, String> {
+pub async fn start_temp_server(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    port: u16,
+) -> Result<u16, String> {
     println!("[cmd] start_temp_server -> port={}", port);
 
     // 先停止旧服务器
-    {
-        let mut temp_server_lock = state.temp_server.write();
-        if temp_server_lock.is_some() {
-            println!("[cmd] Stopping existing server");
-        }
-        *temp_server_lock = None; // Drop 会自动停止服务器
+    if state.stop_pairing_server() {
+        println!("[cmd] Stopped existing server");
     }
 
     // 等待端口释放
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
-    // 创建新服务器
-    let server = TempServer::new(port)?;
+    // 端口已被本应用其他服务登记时拒绝启动
+    let guard = state.ports.register(ServerRole::PairingHttp, port, "0.0.0.0").map_err(|c| {
+        let e = BindError::from(c);
+        state.events.emit("pairing-hint", e.clone());
+        e.to_string()
+    })?;
+
+    // 创建新服务器；失败时推送带分类的提示（guard 随之释放）
+    let mut server = TempServer::new(port).map_err(|e| {
+        state.events.emit("pairing-hint", e.clone());
+        e.to_string()
+    })?;
+    server.hold_port(guard);
+
+    // 绑定成功但本机局域网地址连不上：多半是防火墙
+    if let Ok(ip) = network_utils::get_local_ip() {
+        if !server.lan_self_test(&ip) {
+            let hint = BindError::new(
+                port,
+                BindErrorKind::LikelyFirewall,
+                format!("LAN self-test failed for {}:{}", ip, port),
+                None,
+            );
+            println!("[cmd] ⚠️ {}", hint);
+            state.events.emit("pairing-hint", hint);
+        }
+    }
 
     let actual_port = server.port();
     *state.temp_server.write() = Some(server);
@@ -296,36 +1619,15 @@ pub async fn start_temp_server(state: State<'_, AppState>, port: u16) -> Result<
         if let Some(server) = server_guard.as_ref() {
             println!("[cmd] Starting CONTINUOUS listener on port {}...", server.port());
 
-            // 持续监听，不退出
-            loop {
-                println!("[cmd] 🔄 Waiting for next pairing request...");
-                match server.wait_for_pairing(180) {
-                    Ok(data) => {
-                        println!("[cmd] ✅ Pairing received!");
-                        println!("[cmd] Pairing data: url={}, token_len={}", data.url, data.token.len());
-                        // TODO: 将配对数据保存到 AppState 或发送给前端
-
-                        // 继续监听下一个请求，不退出循环
-                        println!("[cmd] 🔄 Ready for next pairing...");
-                    }
-                    Err(e) => {
-                        println!("[cmd] ❌ Wait for pairing error: {}", e);
-                        // 如果是超时，继续等待；如果是服务器停止，退出
-                        if e.contains("Server stopped") {
-                            println!("[cmd] Server stopped, exiting listener loop");
-                            break;
-                        }
-                        // 超时后继续等待
-                        println!("[cmd] 🔄 Timeout, restarting listener...");
-                    }
-                }
-            }
+            // 持续监听，服务器停止后退出
+            app.state::<AppState>().serve_pairing(server, PAIRING_WAIT_SECS);
         } else {
             println!("[cmd] ❌ ERROR: Server not found in state!");
         }
     });
 
     println!("[cmd] Server is now actively listening on port {}", actual_port);
+    state.advance_onboarding(OnboardingStep::ServerStarted);
 
     // 启动后台测试任务（7秒后自动测试 HTTP 连接）
     let test_port = actual_port;
@@ -402,15 +1704,25 @@ pub async fn start_temp_server(state: State<'_, AppState>, port: u16) -> Result<
 pub async fn stop_temp_server(state: State<'_, AppState>) -> Result<(), String> {
     println!("[cmd] stop_temp_server");
 
-    let mut server_lock = state.temp_server.write();
-    if let Some(server) = server_lock.take() {
-        server.stop();
+    if state.stop_pairing_server() {
         println!("[cmd] Server stopped");
     }
 
     Ok(())
 }
 
+/// 最近的配对记录（含手机端发送的数据格式）
+#[tauri::command]
+pub fn get_pairing_history(state: State<AppState>) -> Vec<PairingRecord> {
+    state.pairing_history()
+}
+
+/// 本应用各服务登记的监听端口（诊断面板用）
+#[tauri::command]
+pub fn get_listening_ports(state: State<AppState>) -> Vec<ListeningPort> {
+    state.ports.list()
+}
+
 #[tauri::command]
 pub async fn get_temp_server_status(state: State<'_, AppState>) -> Result<Option<TempServerStatus>, String> {
     let temp_server_lock = state.temp_server.read();
@@ -439,21 +1751,150 @@ pub async fn connect_to_android(
         connection_id, host, token.is_some());
 
     // 创建客户端连接
-    let client = AndroidSocketClient::connect(&host, connection_id.clone())?;
+    let client = AndroidSocketClient::connect(&host, connection_id.clone(), state.tracer.clone())?;
+    let final_token = authorize_and_register(&state, connection_id, client, token, Some(&host))?;
+
+    println!("[cmd] connect_to_android -> success, token_len={}", final_token.len());
+    Ok(final_token)
+}
+
+/// 经中继（WebSocket）连接手机端，用于双方无法直连的网络；之后与直连行为一致
+#[tauri::command]
+pub async fn connect_via_relay(
+    state: State<'_, AppState>,
+    connection_id: String,
+    relay_url: String,
+    room_token: String,
+    token: Option<String>,
+) -> Result<String, String> {
+    println!("[cmd] connect_via_relay -> connection_id={}, relay={}, has_token={}",
+        connection_id, relay_url, token.is_some());
+
+    let client = AndroidSocketClient::connect_via_relay(&relay_url, &room_token, connection_id.clone(), state.tracer.clone())?;
+    let final_token = authorize_and_register(&state, connection_id, client, token, None)?;
+
+    println!("[cmd] connect_via_relay -> success, token_len={}", final_token.len());
+    Ok(final_token)
+}
 
-    // 如果有token，直接登录；否则请求token
+/// 有token时直接登录，否则请求token；成功后加入连接池。直连时记录地址供之后重连
+pub(crate) fn authorize_and_register(
+    state: &AppState,
+    connection_id: String,
+    client: AndroidSocketClient,
+    token: Option<String>,
+    host: Option<&str>,
+) -> Result<String, String> {
     let final_token = if let Some(t) = token {
         client.login(&t)?;
         t
     } else {
         client.request_token()?
     };
+    state.register_client(connection_id, client, &final_token, host);
+    Ok(final_token)
+}
 
-    // 保存客户端到连接池
-    state.clients.write().insert(connection_id.clone(), Arc::new(client));
+// ============ 手动连接向导 ============
 
-    println!("[cmd] connect_to_android -> success, token_len={}", final_token.len());
-    Ok(final_token)
+#[tauri::command]
+pub fn begin_manual_setup(state: State<AppState>, connection_id: Option<String>) -> WizardView {
+    state.begin_manual_setup(connection_id, chrono::Utc::now().timestamp())
+}
+
+/// 设置地址并探测可达（连接建立后由会话持有，完成前不进入连接池）
+#[tauri::command]
+pub async fn wizard_set_target(
+    state: State<'_, AppState>,
+    session: String,
+    host: String,
+    port: u16,
+) -> Result<WizardView, String> {
+    println!("[cmd] wizard_set_target -> session={}, {}:{}", session, host, port);
+    let tracer = state.tracer.clone();
+    state.wizard_set_target(&session, &host, port, chrono::Utc::now().timestamp(), |addr, connection_id| {
+        AndroidSocketClient::connect(addr, connection_id, tracer)
+    })
+}
+
+#[tauri::command]
+pub async fn wizard_authenticate(
+    state: State<'_, AppState>,
+    session: String,
+    token: Option<String>,
+) -> Result<WizardView, String> {
+    println!("[cmd] wizard_authenticate -> session={}, has_token={}", session, token.is_some());
+    state.wizard_authenticate(&session, token, chrono::Utc::now().timestamp())
+}
+
+/// 完成向导，返回最终状态与 token
+#[tauri::command]
+pub fn wizard_finish(state: State<AppState>, session: String) -> Result<serde_json::Value, String> {
+    let (view, token) = state.wizard_finish(&session, chrono::Utc::now().timestamp())?;
+    Ok(serde_json::json!({ "session": view, "token": token }))
+}
+
+#[tauri::command]
+pub fn wizard_cancel(state: State<AppState>, session: String) -> bool {
+    state.wizard_cancel(&session)
+}
+
+/// 用记录的地址与 token 重连（手机端推送过新地址/新 token 时使用新的）；
+/// 有上次退出时的交接信息时只做增量同步（见 handoff）
+#[tauri::command]
+pub async fn reconnect_android(state: State<'_, AppState>, connection_id: String) -> Result<String, String> {
+    println!("[cmd] reconnect_android -> connection_id={}", connection_id);
+    state.reconnect_device(&connection_id).map(|(token, _)| token)
+}
+
+/// 转交手机端推送的控制消息（update_endpoint / rotate_token）
+#[tauri::command]
+pub fn handle_device_frame(
+    state: State<AppState>,
+    connection_id: String,
+    frame: ControlFrame,
+) -> Result<DeviceEndpoint, String> {
+    state.handle_control_frame(&connection_id, frame)
+}
+
+/// 已配对设备（不含 token），含连接状态、能力与同步起点
+#[tauri::command]
+pub fn list_devices(state: State<AppState>) -> Vec<DeviceInfo> {
+    state.list_devices()
+}
+
+/// 移除设备的一个过时地址（device_uuid 或 connection_id）
+#[tauri::command]
+pub fn remove_device_endpoint(state: State<AppState>, device_uuid: String, endpoint: String) -> Result<DeviceEndpoint, String> {
+    println!("[cmd] remove_device_endpoint -> {} {}", device_uuid, endpoint);
+    state.remove_device_endpoint(&device_uuid, &endpoint)
+}
+
+/// 设置设备的同步起点：时间戳，或 "all"（不限制）/ "pairing"（配对时间）
+#[tauri::command]
+pub fn set_sync_horizon(state: State<AppState>, device_uuid: String, horizon: HorizonInput) -> Result<DeviceEndpoint, String> {
+    state.set_sync_horizon(&device_uuid, horizon)
+}
+
+/// 为一次同步临时降低起点，拉取 since_ts 之后的历史；进度经 backfill-progress 推送
+#[tauri::command]
+pub fn backfill_history(state: State<AppState>, device_uuid: String, since_ts: i64) -> Result<BackfillProgress, String> {
+    state.backfill_history(&device_uuid, since_ts, chrono::Utc::now().timestamp())
+}
+
+/// 转交手机端推送的通知事件帧（原始 JSON），经协议解码层校验后入库
+#[tauri::command]
+pub fn ingest_device_event(state: State<AppState>, connection_id: String, frame: String) -> Result<IngestOutcome, String> {
+    state.ingest_frame(&connection_id, &frame)
+}
+
+/// 按 (连接, 通知 id) 写入一条通知：重发的同一条原地更新，保留已读状态与 posted_at
+#[tauri::command]
+pub fn upsert_notification(state: State<AppState>, connection_id: String, notification: Notification) -> Result<IngestOutcome, String> {
+    let max_text = state.payload_limits().max_text_bytes;
+    limits::check_bytes("title", notification.title.as_deref().map_or(0, str::len), max_text)?;
+    limits::check_bytes("text", notification.text.as_deref().map_or(0, str::len), max_text)?;
+    Ok(state.upsert_notification(&connection_id, notification))
 }
 
 #[tauri::command]
@@ -464,11 +1905,207 @@ pub async fn disconnect_android(
     println!("[cmd] disconnect_android -> connection_id={}", connection_id);
 
     state.clients.write().remove(&connection_id);
+    state.subscriptions.remove(&connection_id);
+    state.low_power.forget(&connection_id);
+    state.refresh_tray_icon();
+    if state.tracer.status(std::time::Instant::now()).is_some_and(|t| t.connection_id == connection_id) {
+        state.tracer.stop(None);
+    }
 
     println!("[cmd] disconnect_android -> removed");
     Ok(())
 }
 
+// ============ 媒体播放 ============
+
+#[tauri::command]
+pub fn get_media_state(state: State<AppState>, connection_id: String) -> Option<MediaState> {
+    state.media_state(&connection_id)
+}
+
+/// 播放/暂停/上一首/下一首，转发到媒体通知上对应的按钮
+#[tauri::command]
+pub fn media_control(state: State<AppState>, connection_id: String, action: MediaAction) -> Result<(), String> {
+    println!("[cmd] media_control -> {} {:?}", connection_id, action);
+    state.media_control(&connection_id, action)
+}
+
+// ============ 镜像暂停 ============
+
+/// 暂停/恢复某个设备的通知镜像（不断开连接）；恢复时 backfill 请求补发暂停期间的通知
+#[tauri::command]
+pub fn set_mirroring_enabled(
+    state: State<AppState>,
+    connection_id: String,
+    enabled: bool,
+    backfill: Option<bool>,
+) -> Result<MirroringResult, String> {
+    println!("[cmd] set_mirroring_enabled -> {} enabled={}", connection_id, enabled);
+    state.set_mirroring_enabled(&connection_id, enabled, backfill.unwrap_or(false), chrono::Utc::now().timestamp())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConnectionInfo {
+    pub connection_id: String,
+    pub protocol_version: Option<u32>,
+    pub mirroring_enabled: bool,
+    /// 手机时钟比桌面快的毫秒数（尚未估算时为 None）
+    pub clock_offset_ms: Option<i64>,
+    /// 手机端声明的能力（旧版手机端为 None）
+    pub capabilities: Option<Vec<String>>,
+    /// 按类别累计的协议解码失败
+    pub protocol_errors: ProtocolErrorCounts,
+    /// 接收侧质量（乱序、跳号、往返抖动、帧率与评级）
+    pub link_quality: LinkQuality,
+    /// 处于夜间低功耗模式时为 Some（新通知可能延迟）
+    pub low_power: Option<LowPowerStatus>,
+    /// 本机没有可用网络，自动重连已暂停
+    pub waiting_for_network: bool,
+    /// PC 间转发连接（手机连接为 None）
+    pub relay: Option<RelayLink>,
+    /// 只读观察模式开启，改变手机状态的操作不会发出
+    pub watch_only: bool,
+}
+
+/// 当前连接（含协议版本与镜像状态）
+#[tauri::command]
+pub fn list_connections(state: State<AppState>) -> Vec<ConnectionInfo> {
+    let clients = state.clients.read();
+    let now = chrono::Utc::now().timestamp();
+    let waiting_for_network = state.network.is_parked();
+    let mut list: Vec<ConnectionInfo> = clients
+        .iter()
+        .map(|(id, client)| ConnectionInfo {
+            connection_id: id.clone(),
+            protocol_version: client.protocol_version(),
+            mirroring_enabled: !state.is_mirroring_paused(id),
+            clock_offset_ms: state.clock_offsets.offset_ms(id),
+            capabilities: client.capabilities(),
+            protocol_errors: client.protocol_errors(),
+            link_quality: state.link_quality.quality(id, now),
+            low_power: state.low_power.status(id),
+            waiting_for_network,
+            relay: None,
+            watch_only: state.watch_only.is_enabled(),
+        })
+        .collect();
+    list.extend(state.relay_connections(now, waiting_for_network));
+    list.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+    list
+}
+
+// ============ PC 间转发 ============
+
+/// 开启转发服务（监听局域网），返回实际端口；副电脑需要带 relay 权限的 API 令牌
+#[tauri::command]
+pub fn start_relay_source(app: tauri::AppHandle, port: u16) -> Result<u16, String> {
+    println!("[cmd] start_relay_source -> {}", port);
+    crate::relay::start_source(app, port)
+}
+
+#[tauri::command]
+pub fn stop_relay_source(state: State<AppState>) -> bool {
+    state.stop_relay_source()
+}
+
+/// 连接另一台电脑的转发服务，接收它转发的通知
+#[tauri::command]
+pub async fn connect_to_peer(app: tauri::AppHandle, host: String, port: u16, token: String) -> Result<RelayLink, String> {
+    println!("[cmd] connect_to_peer -> {}:{}", host, port);
+    tokio::task::spawn_blocking(move || crate::relay::connect_to_peer(app, &host, port, &token))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 断开与某台电脑的转发连接（两个方向），返回断开的条数
+#[tauri::command]
+pub fn disconnect_peer(state: State<AppState>, instance: String) -> usize {
+    state.disconnect_relay_peer(&instance)
+}
+
+#[tauri::command]
+pub fn get_relay_status(state: State<AppState>) -> RelayStatus {
+    state.relay.status()
+}
+
+/// 设备详情面板：通过现有连接实时检查协议兼容性（设备离线或无响应时返回错误）
+#[tauri::command]
+pub async fn check_device_compatibility(app: tauri::AppHandle, device_uuid: String) -> Result<CompatibilityReport, String> {
+    tokio::task::spawn_blocking(move || {
+        app.state::<AppState>().check_device_compatibility(&device_uuid, crate::compatibility::COMPAT_TIMEOUT)
+    })
+    .await
+    .map_err(|e| format!("Compatibility check panicked: {}", e))?
+}
+
+/// 单个连接的接收质量
+#[tauri::command]
+pub fn get_link_quality(state: State<AppState>, connection_id: String) -> LinkQuality {
+    state.link_quality.quality(&connection_id, chrono::Utc::now().timestamp())
+}
+
+// ============ 事件流订阅 ============
+
+/// 订阅单个连接的事件流，先补发 last_seq 之后的缓冲事件，返回补发条数
+#[tauri::command]
+pub fn subscribe_connection(
+    state: State<AppState>,
+    connection_id: String,
+    last_seq: Option<u64>,
+    channel: tauri::ipc::Channel<StreamEvent>,
+) -> Result<usize, String> {
+    let replayed = state.subscriptions.subscribe(&connection_id, last_seq.unwrap_or(0), Box::new(channel))?;
+    println!("[cmd] subscribe_connection -> {} (replayed {})", connection_id, replayed);
+    Ok(replayed)
+}
+
+/// 前端启动/重载后调用：为所有连接重新订阅，last_seq 为各连接最后确认的 seq
+#[tauri::command]
+pub fn resubscribe_all(
+    state: State<AppState>,
+    last_seq: Option<HashMap<String, u64>>,
+    channel: tauri::ipc::Channel<StreamEvent>,
+) -> Result<usize, String> {
+    let last_seq = last_seq.unwrap_or_default();
+    limits::check_items("last_seq", last_seq.len(), limits::MAX_CONNECTIONS)?;
+    let replayed = state.subscriptions.resubscribe_all(&last_seq, channel)?;
+    println!("[cmd] resubscribe_all -> replayed {}", replayed);
+    Ok(replayed)
+}
+
+// ============ 连接调试追踪 ============
+
+/// 开始追踪某个连接的收发（同时只追踪一个，10 分钟后自动停止），返回会话号
+#[tauri::command]
+pub async fn start_connection_trace(state: State<'_, AppState>, connection_id: String) -> Result<u64, String> {
+    println!("[cmd] start_connection_trace -> {}", connection_id);
+    if !state.clients.read().contains_key(&connection_id) {
+        return Err(format!("Unknown connection: {}", connection_id));
+    }
+    let session = state.tracer.start(&connection_id, std::time::Instant::now());
+    let tracer = state.tracer.clone();
+    state.tasks.spawn(format!("trace-timeout:{}", connection_id), move |token| async move {
+        tokio::select! {
+            _ = token.cancelled() => {}
+            _ = tokio::time::sleep(TRACE_DURATION) => {
+                tracer.stop(Some(session));
+            }
+        }
+    });
+    Ok(session)
+}
+
+#[tauri::command]
+pub fn stop_connection_trace(state: State<AppState>) -> bool {
+    println!("[cmd] stop_connection_trace");
+    state.tracer.stop(None)
+}
+
+#[tauri::command]
+pub fn get_connection_trace(state: State<AppState>) -> Option<TraceStatus> {
+    state.tracer.status(std::time::Instant::now())
+}
+
 // ============ Socket 测试命令（7秒后自动测试）============
 
 /// 测试 Socket 服务器（启动服务器 + 7秒后自动客户端连接测试）
@@ -682,19 +2319,17 @@ pub async fn test_http_pairing(state: State<'_, AppState>) -> Result<String, Str
     println!("{} Starting temp_server on port {}...", tag, port);
 
     // 先停止旧服务器
-    {
-        let mut temp_server_lock = state.temp_server.write();
-        if temp_server_lock.is_some() {
-            println!("{} Stopping existing server", tag);
-        }
-        *temp_server_lock = None; // Drop 会自动停止服务器
+    if state.stop_pairing_server() {
+        println!("{} Stopped existing server", tag);
     }
 
     // 等待端口释放
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
     // 启动新服务器
-    let server = crate::temp_server::TempServer::new(port)?;
+    let guard = state.ports.register(ServerRole::PairingHttp, port, "0.0.0.0").map_err(|e| e.to_string())?;
+    let mut server = crate::temp_server::TempServer::new(port).map_err(|e| e.to_string())?;
+    server.hold_port(guard);
     let actual_port = server.port();
     println!("{} Server started on port {}", tag, actual_port);
 
@@ -0,0 +1,273 @@
+//! 存储压缩：大量删除和保留策略淘汰之后，通知存储的哈希表容量不会回落，数据目录里也可能留下
+//! 崩溃时没来得及 rename 的临时文件。compact_store 在后台线程中重建存储并清理数据目录，期间照常读写。
+//!
+//! 并发写入的处理：持锁拷贝快照（记下变更序号），在锁外重建，再持锁比较序号——
+//! 未变化才替换；其间有写入则丢弃这份副本重试，重试用尽后在锁内直接重建（一定成功）。
+//! 写入始终只落在当前存储上，不会丢失。
+//!
+//! 维护调度器定期检查，空闲槽位超过阈值或存在残留临时文件时自动压缩。
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+/// 空闲槽位超过该值时自动压缩
+pub const DEAD_WEIGHT_THRESHOLD: usize = 10_000;
+/// 锁外重建的尝试次数
+const MAX_ATTEMPTS: u32 = 3;
+/// 修改时间早于该时长的临时文件视为残留（避免删掉正在写入的文件）
+const STALE_TMP_AGE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CompactionReport {
+    /// 数据目录占用（字节）
+    pub disk_bytes_before: u64,
+    pub disk_bytes_after: u64,
+    /// 存储中的空闲槽位
+    pub dead_weight_before: usize,
+    pub dead_weight_after: usize,
+    pub removed_tmp_files: usize,
+    /// 锁外重建的次数（大于 1 说明期间有写入）
+    pub attempts: u32,
+    /// 重试用尽后在锁内完成
+    pub under_lock: bool,
+    pub finished_at: i64,
+    pub duration_ms: u64,
+}
+
+impl CompactionReport {
+    fn summary(&self) -> String {
+        format!(
+            "dead weight {} -> {}, disk {} -> {} bytes",
+            self.dead_weight_before, self.dead_weight_after, self.disk_bytes_before, self.disk_bytes_after
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct Compaction {
+    running: AtomicBool,
+}
+
+/// 数据目录下文件的总大小（不含子目录）
+fn dir_bytes(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// 残留的临时文件（见 storage::write_atomic）
+fn stale_tmp_files(dir: &Path, now: SystemTime) -> Vec<std::path::PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "tmp"))
+        .filter(|p| {
+            let modified = p.metadata().and_then(|m| m.modified()).unwrap_or(now);
+            now.duration_since(modified).unwrap_or_default() >= STALE_TMP_AGE
+        })
+        .collect()
+}
+
+impl AppState {
+    fn compaction_progress(&self, stage: &str, attempt: u32) {
+        self.events.emit("compaction-progress", serde_json::json!({ "stage": stage, "attempt": attempt }));
+    }
+
+    /// 压缩存储并清理数据目录；已有压缩在进行时返回错误
+    pub fn compact_store(&self) -> Result<CompactionReport, String> {
+        self.compact_store_with(|| {})
+    }
+
+    /// between 在拷贝快照之后、替换之前调用（测试用来模拟其间的写入）
+    fn compact_store_with(&self, between: impl Fn()) -> Result<CompactionReport, String> {
+        if self.compaction.running.swap(true, Ordering::SeqCst) {
+            return Err("Compaction already running".to_string());
+        }
+        let report = self.run_compaction(between);
+        self.compaction.running.store(false, Ordering::SeqCst);
+        println!("[Compaction] {}", report.summary());
+        Ok(report)
+    }
+
+    fn run_compaction(&self, between: impl Fn()) -> CompactionReport {
+        let started = Instant::now();
+        let dir = self.storage.base_dir();
+        let mut report = CompactionReport {
+            disk_bytes_before: dir.as_deref().map(dir_bytes).unwrap_or(0),
+            dead_weight_before: self.store.lock().unwrap().dead_weight(),
+            ..Default::default()
+        };
+
+        let mut done = false;
+        while !done && report.attempts < MAX_ATTEMPTS {
+            report.attempts += 1;
+            self.compaction_progress("snapshot", report.attempts);
+            let snapshot = self.store.lock().unwrap().clone();
+            let (seq, stream_id) = (snapshot.seq(), snapshot.stream_id().to_string());
+            self.compaction_progress("rebuild", report.attempts);
+            let compacted = snapshot.compacted();
+            between();
+            let mut store = self.store.lock().unwrap();
+            if store.seq() == seq && store.stream_id() == stream_id {
+                store.adopt(compacted);
+                done = true;
+            } else {
+                println!("[Compaction] Store changed during attempt {}, retrying", report.attempts);
+            }
+        }
+        if !done {
+            self.compaction_progress("rebuild_locked", report.attempts);
+            let mut store = self.store.lock().unwrap();
+            let compacted = store.clone().compacted();
+            store.adopt(compacted);
+            report.under_lock = true;
+        }
+        report.dead_weight_after = self.store.lock().unwrap().dead_weight();
+
+        if let Some(dir) = dir.as_deref() {
+            self.compaction_progress("files", report.attempts);
+            for path in stale_tmp_files(dir, SystemTime::now()) {
+                match std::fs::remove_file(&path) {
+                    Ok(()) => report.removed_tmp_files += 1,
+                    Err(e) => println!("[Compaction] Failed to remove {}: {}", path.display(), e),
+                }
+            }
+            report.disk_bytes_after = dir_bytes(dir);
+        }
+        report.finished_at = chrono::Utc::now().timestamp();
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        self.compaction_progress("done", report.attempts);
+        report
+    }
+
+    /// 维护任务：空闲槽位超过阈值或有残留临时文件时压缩
+    pub fn compact_if_needed(&self) -> Result<String, String> {
+        let dead_weight = self.store.lock().unwrap().dead_weight();
+        let stale = self.storage.base_dir().map_or(0, |dir| stale_tmp_files(&dir, SystemTime::now()).len());
+        if dead_weight < DEAD_WEIGHT_THRESHOLD && stale == 0 {
+            return Ok(format!("skipped (dead weight {})", dead_weight));
+        }
+        Ok(self.compact_store()?.summary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Event, Notification};
+
+    fn added(id: usize) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.to_string(),
+                package_name: Some(format!("com.app{}", id % 7)),
+                posted_at: Some(id as i64),
+                ..Default::default()
+            }),
+            id: None,
+        }
+    }
+
+    fn churned() -> AppState {
+        let state = AppState::default();
+        for i in 0..2000 {
+            state.ingest_event(added(i));
+        }
+        let ids: Vec<String> = (0..1990).map(|i| i.to_string()).collect();
+        state.store.lock().unwrap().remove_many(&ids);
+        state
+    }
+
+    #[test]
+    fn test_write_during_compaction_retries() {
+        let state = churned();
+        let writes = std::sync::atomic::AtomicUsize::new(0);
+        let report = state
+            .compact_store_with(|| {
+                // 只在第一次尝试期间写入
+                if writes.fetch_add(1, Ordering::SeqCst) == 0 {
+                    state.ingest_event(added(5000));
+                }
+            })
+            .unwrap();
+        assert_eq!((report.attempts, report.under_lock), (2, false));
+        assert!(report.dead_weight_after < report.dead_weight_before);
+        assert_eq!(state.counts().total, 11);
+        assert!(state.store.lock().unwrap().get("5000").is_some());
+
+        // 每次尝试都有写入：在锁内完成
+        let next = std::sync::atomic::AtomicUsize::new(6000);
+        let report = state
+            .compact_store_with(|| {
+                state.ingest_event(added(next.fetch_add(1, Ordering::SeqCst)));
+            })
+            .unwrap();
+        assert_eq!((report.attempts, report.under_lock), (MAX_ATTEMPTS, true));
+        assert_eq!(state.counts().total, 11 + MAX_ATTEMPTS as usize);
+        let stages: Vec<String> = state
+            .events
+            .take_captured()
+            .into_iter()
+            .filter(|(e, _)| e == "compaction-progress")
+            .map(|(_, p)| p["stage"].as_str().unwrap().to_string())
+            .collect();
+        assert!(stages.contains(&"rebuild_locked".to_string()));
+        assert_eq!(stages.last().map(String::as_str), Some("done"));
+    }
+
+    #[test]
+    fn test_concurrent_ingest_loses_nothing() {
+        let state = churned();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 10_000..10_500 {
+                    state.ingest_event(added(i));
+                }
+            });
+            for _ in 0..5 {
+                state.compact_store().unwrap();
+            }
+        });
+        let store = state.store.lock().unwrap();
+        assert_eq!(store.counts().total, 510);
+        assert!((10_000..10_500).all(|i| store.get(&i.to_string()).is_some()));
+        assert_eq!(store.query(crate::store::SortMode::Newest, |_| true, 0, None).len(), 510);
+    }
+
+    #[test]
+    fn test_removes_stale_tmp_files_and_skips_when_clean() {
+        let state = AppState::default();
+        assert!(state.compact_if_needed().unwrap().starts_with("skipped"));
+
+        let dir = crate::storage::temp_dir("compaction");
+        state.storage.set_base_dir(dir.clone());
+        std::fs::write(dir.join("reminders.tmp"), vec![0u8; 100]).unwrap();
+        std::fs::write(dir.join("settings.json"), b"{}").unwrap();
+        assert!(stale_tmp_files(&dir, SystemTime::now()).is_empty(), "fresh tmp files may still be in use");
+        assert_eq!(stale_tmp_files(&dir, SystemTime::now() + STALE_TMP_AGE).len(), 1);
+
+        let report = state.compact_store().unwrap();
+        assert_eq!((report.disk_bytes_before, report.removed_tmp_files), (102, 0));
+        std::fs::remove_file(dir.join("reminders.tmp")).unwrap();
+        assert_eq!(dir_bytes(&dir), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
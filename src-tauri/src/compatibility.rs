@@ -0,0 +1,298 @@
+//! 协议兼容性检查：桌面端升级协议之前，让用户先确认手机端是否仍然可用。
+//! 通过现有连接实时询问手机端支持的协议范围与能力（不依赖认证时保存、可能已过期的信息），
+//! 与桌面端比较后给出：兼容 / 降级（列出将不可用的功能）/ 不兼容，说明文字按界面语言输出。
+//! 设备离线或在超时内没有响应时直接返回错误，不会卡住设备详情面板。
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::android_client::DeviceVersion;
+use crate::capabilities;
+use crate::clock::CLOCK_SYNC_MIN_PROTOCOL;
+use crate::commands::AppState;
+use crate::mirroring::MIRRORING_MIN_PROTOCOL;
+use crate::time_format::Lang;
+
+/// 桌面端支持的协议范围
+pub const PROTOCOL_MIN: u32 = 1;
+pub const PROTOCOL_MAX: u32 = 3;
+/// 等待手机端响应的时长
+pub const COMPAT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 依赖协议版本（而非能力标记）的功能
+const VERSIONED_FEATURES: &[(&str, u32)] = &[("clock_sync", CLOCK_SYNC_MIN_PROTOCOL), ("mirroring", MIRRORING_MIN_PROTOCOL)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Compatibility {
+    Compatible,
+    /// 可以连接，但部分功能不可用
+    Degraded,
+    Incompatible,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ProtocolRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl ProtocolRange {
+    pub const DESKTOP: ProtocolRange = ProtocolRange { min: PROTOCOL_MIN, max: PROTOCOL_MAX };
+
+    /// 手机端的范围：只返回当前版本时范围即该版本，什么都不返回的旧版视为 v1
+    pub fn of_device(v: &DeviceVersion) -> Self {
+        let max = v.protocol_max.or(v.protocol_version).unwrap_or(1);
+        let min = v.protocol_min.or(v.protocol_version).unwrap_or(max).min(max);
+        ProtocolRange { min, max }
+    }
+}
+
+/// 将不可用的功能
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LostFeature {
+    pub feature: String,
+    /// 缺少的能力（"capability:xxx"）或所需的协议版本（"protocol>=N"）
+    pub requires: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CompatibilityReport {
+    pub connection_id: String,
+    pub device_uuid: Option<String>,
+    pub status: Compatibility,
+    pub desktop: ProtocolRange,
+    pub device: ProtocolRange,
+    /// 双方都支持的最高版本；不兼容时为 None
+    pub negotiated: Option<u32>,
+    pub lost_features: Vec<LostFeature>,
+    /// 按界面语言的说明
+    pub summary: String,
+    pub checked_at: i64,
+}
+
+fn feature_label(feature: &str, lang: Lang) -> String {
+    let (zh, en) = match feature {
+        capabilities::ACTIONS => ("通知操作按钮", "Notification actions"),
+        capabilities::DIRECT_REPLY => ("直接回复", "Direct reply"),
+        capabilities::ICON_FETCH => ("应用图标", "App icons"),
+        capabilities::DISMISSAL => ("同步移除", "Dismissal sync"),
+        capabilities::MEDIA_CONTROL => ("媒体控制", "Media control"),
+        capabilities::COMPRESSION => ("传输压缩", "Compression"),
+        capabilities::BATCHED_PUSH => ("夜间批量推送", "Overnight batched push"),
+        "clock_sync" => ("时钟校正", "Clock correction"),
+        "mirroring" => ("暂停镜像", "Pausing mirroring"),
+        other => (other, other),
+    };
+    match lang {
+        Lang::Zh => zh.to_string(),
+        Lang::En => en.to_string(),
+    }
+}
+
+/// 比较双方的协议范围与能力（纯函数）
+pub fn evaluate(device: ProtocolRange, device_capabilities: Option<&[String]>, lang: Lang) -> (Compatibility, Option<u32>, Vec<LostFeature>) {
+    let high = PROTOCOL_MAX.min(device.max);
+    if high < PROTOCOL_MIN.max(device.min) {
+        return (Compatibility::Incompatible, None, Vec::new());
+    }
+    let mut lost: Vec<LostFeature> = capabilities::DESKTOP
+        .iter()
+        .filter(|c| !capabilities::supports(device_capabilities, c))
+        .map(|c| LostFeature { feature: c.to_string(), requires: format!("capability:{}", c), label: feature_label(c, lang) })
+        .collect();
+    lost.extend(VERSIONED_FEATURES.iter().filter(|(_, min)| *min > high).map(|(f, min)| LostFeature {
+        feature: f.to_string(),
+        requires: format!("protocol>={}", min),
+        label: feature_label(f, lang),
+    }));
+    let status = if lost.is_empty() { Compatibility::Compatible } else { Compatibility::Degraded };
+    (status, Some(high), lost)
+}
+
+fn summary(status: Compatibility, device: ProtocolRange, negotiated: Option<u32>, lost: &[LostFeature], lang: Lang) -> String {
+    let labels: Vec<&str> = lost.iter().map(|f| f.label.as_str()).collect();
+    let desktop = ProtocolRange::DESKTOP;
+    // 不兼容时需要更新的一方：手机端版本太旧则更新手机端，否则更新桌面端
+    let phone_outdated = device.max < desktop.min;
+    match (status, lang) {
+        (Compatibility::Compatible, Lang::Zh) => format!("与手机端兼容（协议 v{}）", negotiated.unwrap_or_default()),
+        (Compatibility::Compatible, Lang::En) => format!("Compatible with the phone (protocol v{})", negotiated.unwrap_or_default()),
+        (Compatibility::Degraded, Lang::Zh) => {
+            format!("可以连接（协议 v{}），但以下功能将不可用：{}", negotiated.unwrap_or_default(), labels.join("、"))
+        }
+        (Compatibility::Degraded, Lang::En) => format!(
+            "Can connect (protocol v{}), but these features will stop working: {}",
+            negotiated.unwrap_or_default(),
+            labels.join(", ")
+        ),
+        (Compatibility::Incompatible, Lang::Zh) => format!(
+            "不兼容：桌面端支持协议 v{}–v{}，手机端支持 v{}–v{}，请更新{}",
+            desktop.min,
+            desktop.max,
+            device.min,
+            device.max,
+            if phone_outdated { "手机端应用" } else { "桌面端" }
+        ),
+        (Compatibility::Incompatible, Lang::En) => format!(
+            "Incompatible: desktop supports protocol v{}–v{}, phone supports v{}–v{}. Please update the {}",
+            desktop.min,
+            desktop.max,
+            device.min,
+            device.max,
+            if phone_outdated { "phone app" } else { "desktop app" }
+        ),
+    }
+}
+
+impl AppState {
+    /// 通过现有连接实时检查设备与桌面端的协议兼容性（device 为 device_uuid 或 connection_id）
+    pub fn check_device_compatibility(&self, device: &str, timeout: Duration) -> Result<CompatibilityReport, String> {
+        let connection_id = match self.resolve_device(device) {
+            Ok(endpoint) => endpoint.connection_id,
+            Err(_) if self.clients.read().contains_key(device) => device.to_string(),
+            Err(e) => return Err(e),
+        };
+        let client = self
+            .clients
+            .read()
+            .get(&connection_id)
+            .cloned()
+            .ok_or_else(|| format!("Device {} is offline", device))?;
+
+        // 在独立线程中等待响应，超时后不再等待（该线程随连接读超时结束）
+        let (tx, rx) = mpsc::channel();
+        let querying = client.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(querying.query_version());
+        });
+        let version = match rx.recv_timeout(timeout) {
+            Ok(result) => result?,
+            Err(_) => return Err(format!("Device {} did not respond within {}s", device, timeout.as_secs_f32())),
+        };
+
+        // 用实时结果刷新已保存的能力列表
+        if let Some(mut endpoint) = self.device_endpoint(&connection_id) {
+            if version.capabilities.is_some() && endpoint.capabilities != version.capabilities {
+                endpoint.capabilities = version.capabilities.clone();
+                self.save_endpoint(endpoint);
+            }
+        }
+
+        let lang = self.settings.read().lang();
+        let range = ProtocolRange::of_device(&version);
+        let (status, negotiated, lost_features) = evaluate(range, version.capabilities.as_deref(), lang);
+        let report = CompatibilityReport {
+            summary: summary(status, range, negotiated, &lost_features, lang),
+            connection_id,
+            device_uuid: client.device_uuid(),
+            status,
+            desktop: ProtocolRange::DESKTOP,
+            device: range,
+            negotiated,
+            lost_features,
+            checked_at: chrono::Utc::now().timestamp(),
+        };
+        println!("[Compat] {} -> {:?} (negotiated {:?})", report.connection_id, report.status, report.negotiated);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::android_client::AndroidSocketClient;
+    use crate::transport::Transport;
+
+    /// 对 version 请求回复固定内容的手机端；delay 模拟没有响应的设备
+    struct MockPhone {
+        reply: &'static str,
+        delay: Duration,
+    }
+
+    impl Transport for MockPhone {
+        fn send_line(&mut self, line: &str) -> Result<(), String> {
+            assert_eq!(line, r#"{"action":"version"}"#);
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            std::thread::sleep(self.delay);
+            Ok(self.reply.to_string())
+        }
+    }
+
+    fn connect(state: &AppState, id: &str, reply: &'static str, delay: Duration) {
+        let client = AndroidSocketClient::with_transport(Box::new(MockPhone { reply, delay }), id.into(), Arc::default());
+        state.clients.write().insert(id.to_string(), Arc::new(client));
+    }
+
+    fn features(report: &CompatibilityReport) -> Vec<&str> {
+        report.lost_features.iter().map(|f| f.feature.as_str()).collect()
+    }
+
+    #[test]
+    fn test_older_equal_and_newer_devices() {
+        let state = AppState::default();
+        let all = r#"{"protocolMin":1,"protocolMax":3,"capabilities":["actions","direct_reply","icon_fetch","dismissal","media_control","compression","batched_push"]}"#;
+        connect(&state, "equal", all, Duration::ZERO);
+        connect(&state, "older", r#"{"protocolVersion":1}"#, Duration::ZERO);
+        connect(&state, "newer", r#"{"protocolMin":4,"protocolMax":5,"capabilities":[]}"#, Duration::ZERO);
+
+        let equal = state.check_device_compatibility("equal", COMPAT_TIMEOUT).unwrap();
+        assert_eq!((equal.status, equal.negotiated), (Compatibility::Compatible, Some(3)));
+        assert_eq!(equal.summary, "与手机端兼容（协议 v3）");
+
+        let older = state.check_device_compatibility("older", COMPAT_TIMEOUT).unwrap();
+        assert_eq!((older.status, older.negotiated), (Compatibility::Degraded, Some(1)));
+        assert_eq!(features(&older), ["direct_reply", "icon_fetch", "compression", "batched_push", "clock_sync", "mirroring"]);
+        assert!(older.summary.contains("直接回复、应用图标"));
+
+        state.settings.write().locale = "en-US".into();
+        let newer = state.check_device_compatibility("newer", COMPAT_TIMEOUT).unwrap();
+        assert_eq!((newer.status, newer.negotiated), (Compatibility::Incompatible, None));
+        assert_eq!(newer.device, ProtocolRange { min: 4, max: 5 });
+        assert!(newer.summary.ends_with("Please update the desktop app"));
+    }
+
+    #[test]
+    fn test_live_query_refreshes_stale_metadata() {
+        let state = AppState::default();
+        state.remember_endpoint("pixel", "192.168.1.5:10035", "t", Some("uuid-1".into()), Some(vec!["actions".into()]));
+        connect(&state, "pixel", r#"{"protocolVersion":3,"capabilities":["actions","direct_reply"]}"#, Duration::ZERO);
+        let report = state.check_device_compatibility("uuid-1", COMPAT_TIMEOUT).unwrap();
+        assert_eq!(report.connection_id, "pixel");
+        assert!(!features(&report).contains(&"direct_reply"));
+        let stored = state.device_endpoint("pixel").unwrap().capabilities.unwrap();
+        assert_eq!(stored, ["actions", "direct_reply"]);
+    }
+
+    #[test]
+    fn test_offline_and_unresponsive_devices() {
+        let state = AppState::default();
+        state.remember_endpoint("away", "192.168.1.6:10035", "t", Some("uuid-2".into()), None);
+        assert!(state.check_device_compatibility("uuid-2", COMPAT_TIMEOUT).unwrap_err().contains("offline"));
+        assert!(state.check_device_compatibility("ghost", COMPAT_TIMEOUT).is_err());
+
+        connect(&state, "slow", "{}", Duration::from_millis(500));
+        let started = std::time::Instant::now();
+        let err = state.check_device_compatibility("slow", Duration::from_millis(50)).unwrap_err();
+        assert!(err.contains("did not respond"), "{}", err);
+        assert!(started.elapsed() < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_evaluate_ranges() {
+        let legacy = ProtocolRange::of_device(&DeviceVersion { protocol_min: None, protocol_max: None, protocol_version: None, capabilities: None });
+        assert_eq!(legacy, ProtocolRange { min: 1, max: 1 });
+        let (status, negotiated, _) = evaluate(ProtocolRange { min: 2, max: 9 }, Some(&[]), Lang::En);
+        assert_eq!((status, negotiated), (Compatibility::Degraded, Some(PROTOCOL_MAX)));
+        let (_, _, lost) = evaluate(legacy, None, Lang::En);
+        assert_eq!(lost[0].label, "Direct reply");
+    }
+}
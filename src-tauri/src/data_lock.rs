@@ -0,0 +1,280 @@
+//! 数据目录锁：数据目录放在 OneDrive/Dropbox 等同步盘里时，两台电脑会互相覆盖对方的文件，产生冲突副本甚至损坏。
+//! 启动时在数据目录根写入 instance.lock（机器 id 哈希、主机名、pid、心跳时间），由维护任务定期刷新心跳。
+//! 发现另一台机器心跳仍新鲜的锁时以只读降级模式运行：所有数据文件写入被拒绝（内容留在内存中），
+//! 经 get_health 与 data-dir-shared 事件提示对方主机名。对方停止（或对该目录关闭同步）后心跳过期，自动接管；
+//! 用户也可调用 force_take_ownership 主动打破锁。
+//! 同一台机器上的锁按 pid 判断：进程已不存在（崩溃残留）时直接回收。
+
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+use crate::identity;
+use crate::storage::write_atomic;
+
+pub const LOCK_FILE: &str = "instance.lock";
+/// 另一台机器的锁超过这么久没有心跳视为过期（维护任务每分钟刷新一次，留出同步延迟）
+pub const FOREIGN_STALE_SECS: i64 = 5 * 60;
+
+/// 锁文件内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LockOwner {
+    pub machine_id_hash: String,
+    pub hostname: String,
+    pub pid: u32,
+    pub heartbeat_at: i64,
+}
+
+impl LockOwner {
+    /// 本进程；读不到机器 id 时以主机名代替
+    pub fn current(now: i64) -> Self {
+        let hostname = sysinfo::System::host_name().unwrap_or_else(|| "Unknown".to_string());
+        let machine_id = identity::read_machine_id().unwrap_or_else(|| hostname.clone());
+        Self { machine_id_hash: identity::hash_id(&machine_id), hostname, pid: std::process::id(), heartbeat_at: now }
+    }
+
+    fn same_machine(&self, other: &LockOwner) -> bool {
+        self.machine_id_hash == other.machine_id_hash
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockCheck {
+    /// 没有锁或锁本来就是本进程的
+    Acquired,
+    /// 回收了过期的锁（崩溃残留或对方心跳过期）
+    Reclaimed { previous: LockOwner },
+    /// 另一个进程（通常是另一台机器）仍持有
+    Held(LockOwner),
+}
+
+/// 尝试获取锁；`pid_alive` 判断本机进程是否仍在运行
+pub fn try_acquire(dir: &Path, me: &LockOwner, pid_alive: impl Fn(u32) -> bool) -> Result<LockCheck, String> {
+    let path = dir.join(LOCK_FILE);
+    let existing: Option<LockOwner> = std::fs::read(&path).ok().and_then(|b| serde_json::from_slice(&b).ok());
+    let check = match existing {
+        None => LockCheck::Acquired,
+        Some(owner) if owner.same_machine(me) && owner.pid == me.pid => LockCheck::Acquired,
+        Some(owner) if owner.same_machine(me) => {
+            if pid_alive(owner.pid) {
+                return Ok(LockCheck::Held(owner));
+            }
+            LockCheck::Reclaimed { previous: owner }
+        }
+        Some(owner) if me.heartbeat_at - owner.heartbeat_at > FOREIGN_STALE_SECS => LockCheck::Reclaimed { previous: owner },
+        Some(owner) => return Ok(LockCheck::Held(owner)),
+    };
+    write_lock(dir, me)?;
+    Ok(check)
+}
+
+fn write_lock(dir: &Path, me: &LockOwner) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(me).map_err(|e| e.to_string())?;
+    write_atomic(&dir.join(LOCK_FILE), &json)
+}
+
+fn pid_alive(pid: u32) -> bool {
+    sysinfo::System::new().refresh_process(sysinfo::Pid::from_u32(pid))
+}
+
+/// get_health 中的数据目录状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DataLockStatus {
+    /// 只读降级：数据文件不会写入
+    pub read_only: bool,
+    /// 持有锁的另一方
+    pub held_by: Option<LockOwner>,
+}
+
+#[derive(Default)]
+pub struct DataLock {
+    dir: Mutex<Option<PathBuf>>,
+    status: Mutex<DataLockStatus>,
+}
+
+impl DataLock {
+    pub fn status(&self) -> DataLockStatus {
+        self.status.lock().clone()
+    }
+}
+
+impl AppState {
+    /// 启动时（写入任何数据文件之前）获取数据目录锁
+    pub(crate) fn acquire_data_lock(&self, dir: &Path) {
+        *self.data_lock.dir.lock() = Some(dir.to_path_buf());
+        if let Err(e) = self.check_data_lock(chrono::Utc::now().timestamp()) {
+            println!("[DataLock] {}", e);
+        }
+    }
+
+    /// 维护任务：刷新心跳；锁被另一方持有时进入只读，对方的锁过期后自动接管
+    pub fn check_data_lock(&self, now: i64) -> Result<String, String> {
+        let Some(dir) = self.data_lock.dir.lock().clone() else {
+            return Ok(String::new());
+        };
+        match try_acquire(&dir, &LockOwner::current(now), pid_alive)? {
+            LockCheck::Held(owner) => {
+                let detail = format!("held by {} (pid {})", owner.hostname, owner.pid);
+                self.enter_read_only(owner);
+                Ok(detail)
+            }
+            LockCheck::Reclaimed { previous } => {
+                println!("[DataLock] Reclaimed stale lock from {} (pid {})", previous.hostname, previous.pid);
+                self.leave_read_only();
+                Ok(format!("reclaimed from {}", previous.hostname))
+            }
+            LockCheck::Acquired => {
+                self.leave_read_only();
+                Ok("owned".to_string())
+            }
+        }
+    }
+
+    fn set_storage_read_only(&self, reason: Option<String>) {
+        self.storage.set_read_only(reason.clone());
+        self.profiles.storage.set_read_only(reason);
+    }
+
+    fn enter_read_only(&self, owner: LockOwner) {
+        let reason = format!("data directory is in use by {}", owner.hostname);
+        self.set_storage_read_only(Some(reason));
+        let changed = {
+            let mut status = self.data_lock.status.lock();
+            let changed = !status.read_only || status.held_by.as_ref().map(|o| &o.machine_id_hash) != Some(&owner.machine_id_hash);
+            *status = DataLockStatus { read_only: true, held_by: Some(owner.clone()) };
+            changed
+        };
+        if changed {
+            println!("[DataLock] Data dir in use by {} (pid {}), running read-only", owner.hostname, owner.pid);
+            self.events.emit(
+                "data-dir-shared",
+                serde_json::json!({
+                    "hostname": owner.hostname,
+                    "heartbeat_at": owner.heartbeat_at,
+                    "message": format!(
+                        "The data folder is also in use by {}. Changes are not saved until you stop syncing this folder or take ownership.",
+                        owner.hostname
+                    ),
+                }),
+            );
+        }
+    }
+
+    fn leave_read_only(&self) {
+        let was_read_only = std::mem::take(&mut *self.data_lock.status.lock()).read_only;
+        if was_read_only {
+            self.set_storage_read_only(None);
+            println!("[DataLock] Lock acquired, leaving read-only mode");
+            self.events.emit("data-dir-owned", serde_json::json!({}));
+            for storage in [&self.storage, &self.profiles.storage] {
+                if let Err(e) = storage.retry_pending() {
+                    println!("[DataLock] {}", e);
+                }
+            }
+        }
+    }
+
+    /// 主动打破另一方的锁并恢复写入（对方仍在运行时其改动可能被覆盖）
+    pub fn force_take_ownership(&self) -> Result<DataLockStatus, String> {
+        let dir = self.data_lock.dir.lock().clone().ok_or("Data directory is not set")?;
+        let previous = self.data_lock.status().held_by;
+        write_lock(&dir, &LockOwner::current(chrono::Utc::now().timestamp()))?;
+        self.leave_read_only();
+        self.audit(
+            "force_take_ownership",
+            AuditSource::Command,
+            1,
+            serde_json::json!({ "previous_hostname": previous.map(|o| o.hostname) }),
+        );
+        Ok(self.data_lock.status())
+    }
+
+    /// 正常退出时删除本进程的锁
+    pub fn release_data_lock(&self) {
+        let Some(dir) = self.data_lock.dir.lock().clone() else {
+            return;
+        };
+        let path = dir.join(LOCK_FILE);
+        let owner: Option<LockOwner> = std::fs::read(&path).ok().and_then(|b| serde_json::from_slice(&b).ok());
+        if owner.is_some_and(|o| o.pid == std::process::id() && o.same_machine(&LockOwner::current(0))) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(machine: &str, pid: u32, heartbeat_at: i64) -> LockOwner {
+        LockOwner { machine_id_hash: machine.into(), hostname: format!("{}-host", machine), pid, heartbeat_at }
+    }
+
+    #[test]
+    fn test_stale_lock_reclamation() {
+        let dir = crate::storage::temp_dir("data-lock");
+        let me = owner("a", 200, 10_000);
+        assert_eq!(try_acquire(&dir, &me, |_| true), Ok(LockCheck::Acquired));
+        // 自己的锁
+        assert_eq!(try_acquire(&dir, &me, |_| true), Ok(LockCheck::Acquired));
+
+        // 同机器的崩溃残留：pid 已不存在
+        let crashed = owner("a", 100, 9_000);
+        write_lock(&dir, &crashed).unwrap();
+        assert_eq!(try_acquire(&dir, &me, |pid| pid != 100), Ok(LockCheck::Reclaimed { previous: crashed.clone() }));
+        // 同机器另一个仍在运行的进程
+        write_lock(&dir, &crashed).unwrap();
+        assert_eq!(try_acquire(&dir, &me, |_| true), Ok(LockCheck::Held(crashed)));
+
+        // 另一台机器：心跳过期才回收
+        let foreign = owner("b", 100, 10_000 - FOREIGN_STALE_SECS);
+        write_lock(&dir, &foreign).unwrap();
+        assert_eq!(try_acquire(&dir, &me, |_| false), Ok(LockCheck::Held(foreign.clone())));
+        let stale = LockOwner { heartbeat_at: foreign.heartbeat_at - 1, ..foreign };
+        write_lock(&dir, &stale).unwrap();
+        assert_eq!(try_acquire(&dir, &me, |_| false), Ok(LockCheck::Reclaimed { previous: stale }));
+        let saved: LockOwner = serde_json::from_slice(&std::fs::read(dir.join(LOCK_FILE)).unwrap()).unwrap();
+        assert_eq!(saved, me);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_foreign_lock_runs_read_only() {
+        let dir = crate::storage::temp_dir("data-lock-foreign");
+        let foreign = owner("other-machine", 4242, chrono::Utc::now().timestamp());
+        write_lock(&dir, &foreign).unwrap();
+
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        let health = state.health();
+        assert!(health.data_lock.read_only);
+        assert_eq!(health.data_lock.held_by.unwrap().hostname, "other-machine-host");
+        let events = state.events.take_captured();
+        let shared: Vec<_> = events.iter().filter(|(e, _)| e == "data-dir-shared").collect();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].1["hostname"], "other-machine-host");
+
+        // 写入被拒绝，内容留在内存中；对方的锁不被覆盖
+        let err = state.storage.save("settings.json", &*state.settings.read()).unwrap_err();
+        assert!(err.starts_with("StorageReadOnly"), "{}", err);
+        assert!(!dir.join("settings.json").exists());
+        assert_eq!(state.check_data_lock(chrono::Utc::now().timestamp()).unwrap(), "held by other-machine-host (pid 4242)");
+
+        // 主动接管：恢复写入并补写内存中的内容
+        let status = state.force_take_ownership().unwrap();
+        assert!(!status.read_only);
+        assert!(dir.join("settings.json").exists());
+        let saved: LockOwner = serde_json::from_slice(&std::fs::read(dir.join(LOCK_FILE)).unwrap()).unwrap();
+        assert_eq!(saved.pid, std::process::id());
+        assert!(state.storage.save("settings.json", &*state.settings.read()).is_ok());
+        assert_eq!(state.audit.recent(1)[0].operation, "force_take_ownership");
+
+        state.release_data_lock();
+        assert!(!dir.join(LOCK_FILE).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,151 @@
+//! 按本地日历日统计每小时的通知数，供前端渲染活动条并跳转到某个时段。
+//! 一天按实际经过的小时切分：夏令时开始的那天只有 23 段，结束的那天有 25 段（重复的钟点各占一段）。
+//! 每段的 since_ts/until_ts 可直接作为 list_notifications 的时间范围。
+
+use chrono::{NaiveDate, TimeZone, Timelike};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::limits::InvalidArgument;
+use crate::store::NotificationStore;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HourCount {
+    /// 本地钟点（0..=23）
+    pub hour: u32,
+    pub since_ts: i64,
+    /// 含端点
+    pub until_ts: i64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DaySummary {
+    pub date: String,
+    pub since_ts: i64,
+    pub until_ts: i64,
+    pub total: usize,
+    pub hours: Vec<HourCount>,
+}
+
+/// 本地日期 0 点对应的时间戳；0 点恰好被夏令时跳过时取当天最早存在的时刻
+fn day_start<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> Option<i64> {
+    (0..24).find_map(|h| tz.from_local_datetime(&date.and_hms_opt(h, 0, 0)?).earliest()).map(|t| t.timestamp())
+}
+
+pub fn day_summary<Tz: TimeZone>(store: &NotificationStore, date: &str, tz: &Tz) -> Result<DaySummary, InvalidArgument> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| InvalidArgument::new("date", format!("expected YYYY-MM-DD: {}", e)))?;
+    let next = day.succ_opt().ok_or_else(|| InvalidArgument::new("date", "date out of range"))?;
+    let (Some(start), Some(end)) = (day_start(day, tz), day_start(next, tz)) else {
+        return Err(InvalidArgument::new("date", "date out of range"));
+    };
+    let hours: Vec<HourCount> = (start..end)
+        .step_by(3600)
+        .map(|since| {
+            let until = (since + 3600).min(end) - 1;
+            HourCount {
+                hour: tz.timestamp_opt(since, 0).single().map_or(0, |t| t.hour()),
+                since_ts: since,
+                until_ts: until,
+                count: store.count_between(since, until),
+            }
+        })
+        .collect();
+    Ok(DaySummary {
+        date: day.format("%Y-%m-%d").to_string(),
+        since_ts: start,
+        until_ts: end - 1,
+        total: hours.iter().map(|h| h.count).sum(),
+        hours,
+    })
+}
+
+impl AppState {
+    /// 本地时区某一天的每小时通知数
+    pub fn day_summary(&self, date: &str) -> Result<DaySummary, String> {
+        Ok(day_summary(&self.store.lock().unwrap(), date, &chrono::Local)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::New_York;
+
+    use crate::store::{NotificationFilter, SortMode};
+    use crate::types::Notification;
+
+    fn store_at(timestamps: &[(&str, i64, Option<&str>)]) -> NotificationStore {
+        let mut store = NotificationStore::default();
+        for (id, ts, package) in timestamps {
+            let n = Notification {
+                id: id.to_string(),
+                posted_at: Some(*ts),
+                package_name: package.map(str::to_string),
+                ..Default::default()
+            };
+            store.upsert(n, false);
+        }
+        store
+    }
+
+    fn ny(y: i32, mo: u32, d: u32, h: u32) -> i64 {
+        New_York.with_ymd_and_hms(y, mo, d, h, 0, 0).earliest().unwrap().timestamp()
+    }
+
+    #[test]
+    fn test_dst_days_have_23_and_25_hours() {
+        // 2024-03-10 02:00 跳到 03:00；2024-11-03 01:00 重复一次
+        let spring_ts = ny(2024, 3, 10, 3) + 60;
+        let fall_first = ny(2024, 11, 3, 1) + 60;
+        let store = store_at(&[("a", spring_ts, None), ("b", fall_first, None), ("c", fall_first + 3600, None)]);
+
+        let spring = day_summary(&store, "2024-03-10", &New_York).unwrap();
+        assert_eq!(spring.hours.len(), 23);
+        assert!(!spring.hours.iter().any(|h| h.hour == 2));
+        assert_eq!(spring.hours.iter().find(|h| h.count == 1).unwrap().hour, 3);
+
+        let fall = day_summary(&store, "2024-11-03", &New_York).unwrap();
+        assert_eq!(fall.hours.len(), 25);
+        let ones: Vec<usize> = fall.hours.iter().filter(|h| h.hour == 1).map(|h| h.count).collect();
+        assert_eq!(ones, [1, 1]);
+        assert_eq!((fall.total, fall.until_ts - fall.since_ts + 1), (2, 25 * 3600));
+    }
+
+    #[test]
+    fn test_range_combines_with_filters_and_pagination() {
+        let evening = ny(2024, 11, 3, 18);
+        let store = store_at(&[
+            ("early", evening - 7200, Some("com.a")),
+            ("e1", evening, Some("com.a")),
+            ("e2", evening + 60, Some("com.b")),
+            ("e3", evening + 120, Some("com.a")),
+            ("late", evening + 7200, Some("com.a")),
+        ]);
+        let range = |package: Option<&str>| NotificationFilter {
+            since: Some(evening),
+            until: Some(evening + 3599),
+            package: package.map(str::to_string),
+            ..Default::default()
+        };
+        let ids = |list: Vec<Notification>| list.into_iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(store.query_filter(SortMode::Newest, &range(None), 0, None)), ["e3", "e2", "e1"]);
+        assert_eq!(ids(store.query_filter(SortMode::Oldest, &range(Some("com.a")), 1, Some(1))), ["e3"]);
+        assert_eq!(store.ids_where(&range(Some("com.a"))), ["e3", "e1"]);
+        let mut unread = range(None);
+        unread.unread = Some(true);
+        assert_eq!(ids(store.query_filter(SortMode::UnreadFirst, &unread, 0, Some(2))), ["e3", "e2"]);
+        assert_eq!(ids(store.query_filter(SortMode::AppThenTime, &range(None), 0, None)), ["e2", "e3", "e1"]);
+
+        // 空范围与颠倒的范围
+        let empty = NotificationFilter { since: Some(evening + 1), until: Some(evening + 59), ..Default::default() };
+        assert!(store.query_filter(SortMode::Newest, &empty, 0, None).is_empty());
+        let inverted: NotificationFilter =
+            serde_json::from_value(serde_json::json!({ "since_ts": evening, "until_ts": evening - 1 })).unwrap();
+        assert_eq!(inverted.validate().unwrap_err().field, "until_ts");
+        assert!(store.query_filter(SortMode::Newest, &inverted, 0, None).is_empty());
+        assert_eq!(day_summary(&store, "2024-13-01", &New_York).unwrap_err().field, "date");
+    }
+}
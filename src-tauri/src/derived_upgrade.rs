@@ -0,0 +1,248 @@
+//! 派生字段的原地升级：新增的派生字段（目前只有语言标记）只在功能上线后入库的通知上计算，
+//! 之前存下的通知（本地提醒，以及升级时仍在内存中的通知）缺少该字段，语言过滤等会表现不一致。
+//! 数据目录下的 data_version.json 记录存储已升级到的数据版本；低于 DATA_VERSION 时，
+//! 维护任务 derived_backfill 按时间索引顺序分批补算缺失的字段（批与批之间释放存储锁），
+//! 每批之后把进度（最后处理的索引键）写盘，重启后从断点继续；全部完成后写入新版本。
+//! 升级期间查询把缺失的字段当作未知：语言过滤对尚无标记的通知当场检测（见 store 的 NotificationFilter）。
+//! 会话标识由手机端提供，不需要补算。
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+use crate::language;
+use crate::settings::Settings;
+use crate::types::Notification;
+
+pub const DATA_VERSION_FILE: &str = "data_version.json";
+/// 每批处理的通知数
+pub const BATCH_SIZE: usize = 500;
+/// 一次维护任务最多处理的批数，剩余的留到下一次
+pub const MAX_BATCHES_PER_RUN: usize = 20;
+
+/// 一个数据版本引入的派生字段与补算方法（返回是否修改了通知）
+pub struct Upgrade {
+    pub version: u32,
+    pub field: &'static str,
+    pub fill: fn(&Settings, &mut Notification) -> bool,
+}
+
+/// 按版本顺序登记
+pub const UPGRADES: &[Upgrade] = &[Upgrade { version: 1, field: "language", fill: fill_language }];
+pub const DATA_VERSION: u32 = 1;
+
+/// 入库时的语言标记规则；检测关闭时新入库的通知同样没有标记，不必补算
+pub(crate) fn fill_language(settings: &Settings, n: &mut Notification) -> bool {
+    if !settings.detect_language || n.language.is_some() {
+        return false;
+    }
+    n.language = Some(language::detect_notification(n.title.as_deref(), n.text.as_deref()));
+    true
+}
+
+/// 持久化的数据版本与升级进度；没有该文件的数据目录视为版本 0（引入本机制之前）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataVersion {
+    pub version: u32,
+    /// 升级中：最后处理的索引键 (时间戳, id)
+    pub cursor: Option<(i64, String)>,
+    /// 升级中已补算的条数
+    pub upgraded: usize,
+}
+
+impl DataVersion {
+    fn current() -> Self {
+        Self { version: DATA_VERSION, ..Default::default() }
+    }
+
+    fn pending(&self) -> Vec<&'static Upgrade> {
+        UPGRADES.iter().filter(|u| u.version > self.version).collect()
+    }
+}
+
+#[derive(Default)]
+pub struct DerivedUpgrade {
+    state: Mutex<DataVersion>,
+}
+
+impl DerivedUpgrade {
+    pub fn snapshot(&self) -> DataVersion {
+        self.state.lock().clone()
+    }
+}
+
+impl AppState {
+    pub(crate) fn load_data_version(&self) {
+        *self.derived_upgrade.state.lock() = self.storage.load::<DataVersion>(DATA_VERSION_FILE).unwrap_or_default();
+    }
+
+    /// 存储已清空（恢复出厂设置）：没有需要升级的旧数据
+    pub(crate) fn reset_data_version(&self) {
+        let state = DataVersion::current();
+        *self.derived_upgrade.state.lock() = state.clone();
+        if let Err(e) = self.storage.save(DATA_VERSION_FILE, &state) {
+            println!("[Upgrade] {}", e);
+        }
+    }
+
+    /// 维护任务：补算缺失的派生字段，返回进度说明
+    pub fn run_derived_backfill(&self) -> Result<String, String> {
+        self.backfill_batches(BATCH_SIZE, MAX_BATCHES_PER_RUN)
+    }
+
+    fn backfill_batches(&self, batch_size: usize, max_batches: usize) -> Result<String, String> {
+        let mut progress = self.derived_upgrade.snapshot();
+        let pending = progress.pending();
+        if pending.is_empty() {
+            return Ok(format!("up to date (v{})", progress.version));
+        }
+        let fields: Vec<&str> = pending.iter().map(|u| u.field).collect();
+        for _ in 0..max_batches {
+            let settings = self.settings.read().clone();
+            let (changed, next) = self.store.lock().unwrap().fill_derived(progress.cursor.as_ref(), batch_size, |n| {
+                pending.iter().fold(false, |changed, u| (u.fill)(&settings, n) | changed)
+            });
+            progress.upgraded += changed;
+            progress.cursor = next;
+            if changed > 0 {
+                // 本地提醒随之写盘，进度前进后不会再回头处理
+                self.save_local_notifications();
+            }
+            if progress.cursor.is_none() {
+                let upgraded = progress.upgraded;
+                let from = progress.version;
+                progress = DataVersion::current();
+                *self.derived_upgrade.state.lock() = progress.clone();
+                self.storage.save(DATA_VERSION_FILE, &progress)?;
+                self.audit(
+                    "data_upgrade",
+                    AuditSource::Background,
+                    upgraded,
+                    serde_json::json!({ "from": from, "to": DATA_VERSION, "fields": fields }),
+                );
+                println!("[Upgrade] Data version {} -> {}, {} notifications upgraded", from, DATA_VERSION, upgraded);
+                return Ok(format!("upgraded {} to v{} ({})", upgraded, DATA_VERSION, fields.join(", ")));
+            }
+            *self.derived_upgrade.state.lock() = progress.clone();
+            self.storage.save(DATA_VERSION_FILE, &progress)?;
+            // 批与批之间让出，期间的入库与查询不被长时间阻塞
+            std::thread::yield_now();
+        }
+        Ok(format!("{} upgraded so far to v{} ({}), resuming later", progress.upgraded, DATA_VERSION, fields.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::Language;
+    use crate::store::{NotificationFilter, SortMode};
+    use crate::types::Event;
+
+    const SAMPLES: &[(&str, &str)] = &[
+        ("张三", "今晚一起吃饭吗？"),
+        ("Bob", "Are we still on for tonight?"),
+        ("Иван", "Привет, как дела?"),
+        ("田中", "こんにちは、元気ですか"),
+        ("李四", "明天上午九点开会"),
+        ("Alice", "Lunch at noon"),
+        ("王五", "快递已签收"),
+    ];
+
+    fn notification(i: usize) -> Notification {
+        let (title, text) = SAMPLES[i];
+        Notification {
+            id: i.to_string(),
+            package_name: Some("com.chat".into()),
+            title: Some(title.into()),
+            text: Some(text.into()),
+            posted_at: Some(1_000 + i as i64),
+            ..Default::default()
+        }
+    }
+
+    /// 旧版本的数据目录：本地提醒没有语言标记，也没有 data_version.json
+    fn old_store(dir: &std::path::Path) -> AppState {
+        let reminders: Vec<Notification> = (0..SAMPLES.len())
+            .map(|i| Notification { id: format!("local-{}", i), local: true, ..notification(i) })
+            .collect();
+        std::fs::write(dir.join(crate::reminders::REMINDERS_FILE), serde_json::to_vec(&reminders).unwrap()).unwrap();
+        let state = AppState::default();
+        state.storage.set_base_dir(dir.to_path_buf());
+        state.load_local_notifications();
+        state.load_data_version();
+        // 升级前入库、仍在内存中的手机通知
+        for i in 0..SAMPLES.len() {
+            state.store.lock().unwrap().upsert(notification(i), false);
+        }
+        state
+    }
+
+    fn fresh_store() -> AppState {
+        let state = AppState::default();
+        for (i, (title, text)) in SAMPLES.iter().enumerate() {
+            let n = state.create_local_notification(title.into(), text.into(), None, 1_000 + i as i64).unwrap();
+            let mut store = state.store.lock().unwrap();
+            let n = store.remove(&n.id).unwrap();
+            store.upsert(Notification { id: format!("local-{}", i), ..n }, false);
+        }
+        for i in 0..SAMPLES.len() {
+            state.ingest_event(Event { event_type: "added".into(), seq: 0, notification: Some(notification(i)), id: None });
+        }
+        state
+    }
+
+    fn by_language(state: &AppState, language: Language) -> Vec<(String, Option<Language>)> {
+        let filter = NotificationFilter { language: Some(language), ..Default::default() };
+        state.store.lock().unwrap().query_filter(SortMode::Newest, &filter, 0, None).into_iter().map(|n| (n.id, n.language)).collect()
+    }
+
+    #[test]
+    fn test_backfill_resumes_and_matches_fresh_ingest() {
+        let dir = crate::storage::temp_dir("derived-upgrade");
+        let state = old_store(&dir);
+        assert_eq!(state.derived_upgrade.snapshot().version, 0);
+
+        // 升级中：尚无标记的通知当场检测，不会被语言过滤排除
+        assert_eq!(by_language(&state, Language::Zh).len(), 6);
+        let detail = state.backfill_batches(4, 1).unwrap();
+        assert!(detail.contains("resuming"), "{}", detail);
+        let progress = state.derived_upgrade.snapshot();
+        assert_eq!((progress.version, progress.upgraded), (0, 4));
+        assert!(progress.cursor.is_some());
+        assert_eq!(by_language(&state, Language::Zh).len(), 6);
+
+        // 重启：从数据目录读回进度
+        let restarted = AppState::default();
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_data_version();
+        assert_eq!(restarted.derived_upgrade.snapshot(), progress);
+
+        let detail = state.backfill_batches(4, 10).unwrap();
+        assert_eq!(detail, "upgraded 14 to v1 (language)");
+        assert_eq!(state.run_derived_backfill().unwrap(), "up to date (v1)");
+        let saved: DataVersion = state.storage.load(DATA_VERSION_FILE).unwrap();
+        assert_eq!(saved, DataVersion::current());
+
+        let fresh = fresh_store();
+        for language in [Language::Zh, Language::Latin, Language::Cyrillic, Language::Ja, Language::Ko] {
+            assert_eq!(by_language(&state, language), by_language(&fresh, language), "{:?}", language);
+        }
+        // 本地提醒的标记已写盘
+        let saved: Vec<Notification> = state.storage.load(crate::reminders::REMINDERS_FILE).unwrap();
+        assert!(saved.iter().all(|n| n.language.is_some()));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_detection_off_only_bumps_version() {
+        let state = AppState::default();
+        state.settings.write().detect_language = false;
+        state.store.lock().unwrap().upsert(notification(0), false);
+        assert_eq!(state.run_derived_backfill().unwrap(), "upgraded 0 to v1 (language)");
+        assert_eq!(state.store.lock().unwrap().get("0").unwrap().language, None);
+        assert_eq!(state.derived_upgrade.snapshot().version, DATA_VERSION);
+    }
+}
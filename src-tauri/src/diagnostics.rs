@@ -0,0 +1,116 @@
+//! 诊断包：附在问题报告里的单个 JSON 文件，包含计数、最近的通知与删除记录、后台错误与托盘更新计数。
+//! 默认按 bodies_hashed 脱敏（见 masking），清单中注明实际使用的级别；用户可以选择 none 或 full_anonymize。
+//! 不包含设置（过滤规则的关键词可能就是敏感内容）与审计日志。
+
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::error_bus::ErrorReport;
+use crate::masking::MaskLevel;
+use crate::store::{Counts, SortMode};
+use crate::tombstones::Tombstone;
+use crate::tray_updates::TraySurfaceStats;
+use crate::types::Notification;
+
+/// 诊断包中的通知条数（最新的）
+pub const BUNDLE_NOTIFICATIONS: usize = 200;
+/// 诊断包中的后台错误条数
+pub const BUNDLE_ERRORS: usize = 50;
+pub const DEFAULT_MASK: MaskLevel = MaskLevel::BodiesHashed;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BundleManifest {
+    pub app_version: String,
+    pub git_hash: String,
+    pub created_at: i64,
+    /// 通知与删除记录的脱敏级别
+    pub mask: MaskLevel,
+    pub notifications: usize,
+    pub removals: usize,
+    pub errors: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticBundle {
+    pub manifest: BundleManifest,
+    pub counts: Counts,
+    pub notifications: Vec<Notification>,
+    pub removals: Vec<Tombstone>,
+    pub errors: Vec<ErrorReport>,
+    /// 托盘更新的请求与应用次数
+    pub tray_updates: Vec<TraySurfaceStats>,
+}
+
+impl AppState {
+    pub fn diagnostic_bundle(&self, mask: Option<MaskLevel>, now: i64) -> DiagnosticBundle {
+        let masker = self.masker(mask.unwrap_or(DEFAULT_MASK));
+        let (counts, notifications) = {
+            let store = self.store.lock().unwrap();
+            (store.counts(), store.query(SortMode::Newest, |_| true, 0, Some(BUNDLE_NOTIFICATIONS)))
+        };
+        let notifications: Vec<Notification> = notifications.iter().map(|n| masker.notification(n)).collect();
+        let removals: Vec<Tombstone> =
+            self.removal_log.lock().unwrap().recent(crate::tombstones::MAX_TOMBSTONES).iter().map(|t| masker.tombstone(t)).collect();
+        let errors = self.errors.recent(BUNDLE_ERRORS);
+        DiagnosticBundle {
+            manifest: BundleManifest {
+                app_version: crate::ping::VERSION.to_string(),
+                git_hash: crate::ping::GIT_HASH.to_string(),
+                created_at: now,
+                mask: masker.level(),
+                notifications: notifications.len(),
+                removals: removals.len(),
+                errors: errors.len(),
+            },
+            counts,
+            notifications,
+            removals,
+            errors,
+            tray_updates: self.tray_updates.stats(),
+        }
+    }
+
+    /// 写出诊断包，返回清单
+    pub fn export_diagnostic_bundle(&self, path: &Path, mask: Option<MaskLevel>) -> Result<BundleManifest, String> {
+        let bundle = self.diagnostic_bundle(mask, chrono::Utc::now().timestamp());
+        let json = serde_json::to_vec_pretty(&bundle).map_err(|e| e.to_string())?;
+        crate::storage::write_atomic(path, &json)?;
+        println!("[Diagnostics] Wrote bundle ({:?}) to {}", bundle.manifest.mask, path.display());
+        Ok(bundle.manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::masking::tests::{leaky, BODY};
+    use crate::tombstones::RemovalReason;
+
+    #[test]
+    fn test_bundle_defaults_to_hashed_bodies() {
+        let dir = crate::storage::temp_dir("diagnostics");
+        let state = AppState::default();
+        let n = leaky();
+        state.store.lock().unwrap().upsert(n.clone(), false);
+        let removed = Notification { id: "gone".into(), ..n.clone() };
+        state.on_removed(&[removed], RemovalReason::UserDeletedLocal);
+
+        let path = dir.join("bundle.json");
+        let manifest = state.export_diagnostic_bundle(&path, None).unwrap();
+        assert_eq!((manifest.mask, manifest.notifications, manifest.removals), (MaskLevel::BodiesHashed, 1, 1));
+        let bytes = String::from_utf8(std::fs::read(&path).unwrap()).unwrap();
+        assert!(!bytes.contains("4711"), "{}", bytes);
+        assert!(bytes.contains("\"bodies_hashed\""));
+
+        state.export_diagnostic_bundle(&path, Some(MaskLevel::FullAnonymize)).unwrap();
+        let bytes = std::fs::read_to_string(&path).unwrap();
+        assert!(!bytes.contains("4711") && !bytes.contains("com.secret.chat") && !bytes.contains("Alice"), "{}", bytes);
+
+        state.export_diagnostic_bundle(&path, Some(MaskLevel::None)).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains(BODY));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,315 @@
+//! 已配对设备的连接信息（地址、token、设备 uuid），用于不扫码重连。
+//! 手机端 IP/端口变化或重新生成凭据时，可通过已认证的连接推送
+//! `update_endpoint` / `rotate_token` 控制消息，更新后由下一次重连使用。
+//! 未认证、未知设备或 uuid 不一致的连接发来的更新一律拒绝并记录日志。
+//! 本机身份重新生成后（见 identity），已保存的配对需要重新扫码认证。
+//! 同一台设备可能有多个地址（家里 Wi-Fi、USB 共享网络等），配对、update_endpoint 与每次成功连接都会记入地址列表，
+//! 列表按最近成功排序，host 总是第一个；自动重连按列表顺序尝试（见 failover）。
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+const ENDPOINTS_FILE: &str = "endpoints.json";
+/// 每台设备记住的地址数
+pub const MAX_KNOWN_ENDPOINTS: usize = 8;
+
+/// 设备的一个已知地址
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct KnownEndpoint {
+    /// host:port
+    pub host: String,
+    /// 最近一次经该地址连接成功的时间
+    pub last_success: Option<i64>,
+    /// 连续连接失败次数（不持久化，重启后每个地址重新尝试）
+    #[serde(skip)]
+    pub failures: u32,
+    /// 自动重连在该时间之前跳过此地址
+    #[serde(skip)]
+    pub retry_after: Option<i64>,
+}
+
+impl KnownEndpoint {
+    fn new(host: &str, last_success: Option<i64>) -> Self {
+        Self { host: host.to_string(), last_success, failures: 0, retry_after: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceEndpoint {
+    pub connection_id: String,
+    /// host:port
+    pub host: String,
+    pub token: Option<String>,
+    pub device_uuid: Option<String>,
+    pub updated_at: i64,
+    /// 本机身份已变化，需重新配对后才能重连
+    #[serde(default)]
+    pub needs_reauth: bool,
+    /// 手机端声明的能力（旧版手机端为 None，未知标记原样保留）
+    #[serde(default)]
+    pub capabilities: Option<Vec<String>>,
+    /// 首次配对时间
+    #[serde(default)]
+    pub paired_at: Option<i64>,
+    /// 早于该时间的同步通知不入库（None 表示不限制，见 sync_horizon）
+    #[serde(default)]
+    pub sync_horizon_ts: Option<i64>,
+    /// 已知地址（按优先级，第一个与 host 相同；旧版记录加载时由 host 补齐）
+    #[serde(default)]
+    pub endpoints: Vec<KnownEndpoint>,
+}
+
+impl DeviceEndpoint {
+    /// 经 host 连接成功：移到列表最前并清除退避
+    pub fn promote(&mut self, host: &str, now: i64) {
+        self.endpoints.retain(|e| e.host != host);
+        self.endpoints.insert(0, KnownEndpoint::new(host, Some(now)));
+        self.endpoints.truncate(MAX_KNOWN_ENDPOINTS);
+        self.host = host.to_string();
+    }
+
+    /// 手机端告知的新地址：下次优先尝试，保留已有的成功记录
+    pub fn learn(&mut self, host: &str) {
+        let existing = self.endpoints.iter().position(|e| e.host == host).map(|i| self.endpoints.remove(i));
+        self.endpoints.insert(0, existing.unwrap_or_else(|| KnownEndpoint::new(host, None)));
+        self.endpoints.truncate(MAX_KNOWN_ENDPOINTS);
+        self.host = host.to_string();
+    }
+
+    /// 移除一个地址；不能移除最后一个
+    pub fn forget(&mut self, host: &str) -> Result<(), String> {
+        let pos = self.endpoints.iter().position(|e| e.host == host).ok_or_else(|| format!("Unknown endpoint {}", host))?;
+        if self.endpoints.len() == 1 {
+            return Err("Cannot remove the only endpoint of a device".to_string());
+        }
+        self.endpoints.remove(pos);
+        self.host = self.endpoints[0].host.clone();
+        Ok(())
+    }
+
+    /// 旧版记录只有 host
+    fn fill_endpoints(&mut self) {
+        if self.endpoints.is_empty() {
+            self.endpoints.push(KnownEndpoint::new(&self.host, Some(self.updated_at)));
+        }
+    }
+}
+
+/// connection_id -> 连接信息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EndpointRegistry {
+    devices: HashMap<String, DeviceEndpoint>,
+}
+
+impl EndpointRegistry {
+    pub fn get(&self, connection_id: &str) -> Option<&DeviceEndpoint> {
+        self.devices.get(connection_id)
+    }
+
+    pub fn device_count(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &DeviceEndpoint> {
+        self.devices.values()
+    }
+}
+
+/// 手机端通过已认证连接推送的控制消息
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ControlFrame {
+    UpdateEndpoint {
+        host: String,
+        port: u16,
+        #[serde(default)]
+        device_uuid: Option<String>,
+    },
+    RotateToken {
+        token: String,
+        #[serde(default)]
+        device_uuid: Option<String>,
+    },
+}
+
+impl AppState {
+    /// 直连登录成功后记录连接信息（供之后重连）；配对时间与同步起点沿用已有记录
+    pub(crate) fn remember_endpoint(&self, connection_id: &str, host: &str, token: &str, device_uuid: Option<String>, capabilities: Option<Vec<String>>) {
+        let now = chrono::Utc::now().timestamp();
+        let existing = self.device_endpoint(connection_id);
+        let mut endpoint = DeviceEndpoint {
+            connection_id: connection_id.to_string(),
+            host: host.to_string(),
+            token: Some(token.to_string()),
+            device_uuid,
+            updated_at: now,
+            needs_reauth: false,
+            capabilities,
+            paired_at: existing.as_ref().map_or(Some(now), |e| e.paired_at),
+            sync_horizon_ts: existing.as_ref().map_or(Some(now), |e| e.sync_horizon_ts),
+            endpoints: existing.map(|e| e.endpoints).unwrap_or_default(),
+        };
+        endpoint.promote(host, now);
+        self.save_endpoint(endpoint);
+    }
+
+    /// 把所有已保存的配对标记为需要重新认证，返回新标记的条数
+    pub(crate) fn mark_endpoints_need_reauth(&self) -> usize {
+        let (marked, registry) = {
+            let mut registry = self.endpoints.lock().unwrap();
+            let mut marked = 0;
+            for endpoint in registry.devices.values_mut().filter(|e| !e.needs_reauth) {
+                endpoint.needs_reauth = true;
+                marked += 1;
+            }
+            (marked, registry.clone())
+        };
+        if marked > 0 {
+            if let Err(e) = self.storage.save(ENDPOINTS_FILE, &registry) {
+                println!("[Endpoints] {}", e);
+            }
+        }
+        marked
+    }
+
+    pub(crate) fn save_endpoint(&self, mut endpoint: DeviceEndpoint) {
+        endpoint.fill_endpoints();
+        let registry = {
+            let mut registry = self.endpoints.lock().unwrap();
+            registry.devices.insert(endpoint.connection_id.clone(), endpoint);
+            registry.clone()
+        };
+        if let Err(e) = self.storage.save(ENDPOINTS_FILE, &registry) {
+            println!("[Endpoints] {}", e);
+        }
+    }
+
+    pub fn device_endpoint(&self, connection_id: &str) -> Option<DeviceEndpoint> {
+        self.endpoints.lock().unwrap().get(connection_id).cloned()
+    }
+
+    /// 处理手机端推送的端点/凭据更新；校验通过后持久化并推送 device-updated
+    pub fn handle_control_frame(&self, connection_id: &str, frame: ControlFrame) -> Result<DeviceEndpoint, String> {
+        let result = self.apply_control_frame(connection_id, frame);
+        match &result {
+            Ok(endpoint) => {
+                println!("[Endpoints] {} updated -> {}", connection_id, endpoint.host);
+                self.events.emit("device-updated", endpoint);
+            }
+            Err(e) => println!("[Endpoints] Rejected update from {}: {}", connection_id, e),
+        }
+        result
+    }
+
+    fn apply_control_frame(&self, connection_id: &str, frame: ControlFrame) -> Result<DeviceEndpoint, String> {
+        let client = self.clients.read().get(connection_id).cloned();
+        if !client.as_ref().is_some_and(|c| c.is_authenticated()) {
+            return Err("connection is not authenticated".to_string());
+        }
+        let mut endpoint = self.device_endpoint(connection_id).ok_or("unknown device")?;
+        let claimed = match &frame {
+            ControlFrame::UpdateEndpoint { device_uuid, .. } | ControlFrame::RotateToken { device_uuid, .. } => device_uuid,
+        };
+        let known = endpoint.device_uuid.clone().or(client.and_then(|c| c.device_uuid()));
+        if let (Some(known), Some(claimed)) = (&known, claimed) {
+            if known != claimed {
+                return Err(format!("device uuid mismatch ({} != {})", claimed, known));
+            }
+        }
+        match frame {
+            ControlFrame::UpdateEndpoint { host, port, .. } => {
+                if host.trim().is_empty() || port == 0 {
+                    return Err(format!("invalid endpoint {}:{}", host, port));
+                }
+                endpoint.learn(&format!("{}:{}", host, port));
+            }
+            ControlFrame::RotateToken { token, .. } => {
+                if token.is_empty() {
+                    return Err("empty token".to_string());
+                }
+                endpoint.token = Some(token);
+            }
+        }
+        endpoint.device_uuid = known;
+        endpoint.updated_at = chrono::Utc::now().timestamp();
+        self.save_endpoint(endpoint.clone());
+        Ok(endpoint)
+    }
+
+    /// 移除设备的一个过时地址（device 为 connection_id 或 device_uuid）
+    pub fn remove_device_endpoint(&self, device: &str, host: &str) -> Result<DeviceEndpoint, String> {
+        let mut endpoint = self.resolve_device(device)?;
+        endpoint.forget(host)?;
+        self.save_endpoint(endpoint.clone());
+        println!("[Endpoints] {} forgot {}", endpoint.connection_id, host);
+        self.events.emit("device-updated", &endpoint);
+        Ok(endpoint)
+    }
+
+    pub(crate) fn load_endpoints(&self) {
+        let mut registry = self.storage.load::<EndpointRegistry>(ENDPOINTS_FILE).unwrap_or_default();
+        registry.devices.values_mut().for_each(DeviceEndpoint::fill_endpoints);
+        *self.endpoints.lock().unwrap() = registry;
+    }
+
+    /// 修改已保存设备的地址列表（连接失败退避等），只改内存，不写文件
+    pub(crate) fn with_endpoint_mut<R>(&self, connection_id: &str, f: impl FnOnce(&mut DeviceEndpoint) -> R) -> Option<R> {
+        self.endpoints.lock().unwrap().devices.get_mut(connection_id).map(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::android_client::AndroidSocketClient;
+    use crate::transport::Transport;
+
+    struct LoginOk;
+
+    impl Transport for LoginOk {
+        fn send_line(&mut self, _line: &str) -> Result<(), String> {
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(r#"{"success":true,"deviceUuid":"phone-1"}"#.to_string())
+        }
+    }
+
+    fn frame(json: serde_json::Value) -> ControlFrame {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_update_endpoint_requires_authenticated_known_device() {
+        let state = AppState::default();
+        let update = || frame(serde_json::json!({ "action": "update_endpoint", "host": "192.168.1.9", "port": 10036 }));
+        let client = AndroidSocketClient::with_transport(Box::new(LoginOk), "pixel".into(), Arc::default());
+        state.clients.write().insert("pixel".into(), Arc::new(client));
+        state.remember_endpoint("pixel", "192.168.1.5:10035", "t1", None, None);
+        // 尚未登录
+        assert!(state.handle_control_frame("pixel", update()).is_err());
+
+        state.clients.read()["pixel"].login("t1").unwrap();
+        assert!(state.handle_control_frame("tablet", update()).is_err());
+        let spoofed = frame(serde_json::json!({ "action": "rotate_token", "token": "t2", "device_uuid": "other" }));
+        assert!(state.handle_control_frame("pixel", spoofed).unwrap_err().contains("mismatch"));
+
+        let endpoint = state.handle_control_frame("pixel", update()).unwrap();
+        assert_eq!(endpoint.host, "192.168.1.9:10036");
+        let hosts: Vec<&str> = endpoint.endpoints.iter().map(|e| e.host.as_str()).collect();
+        assert_eq!(hosts, ["192.168.1.9:10036", "192.168.1.5:10035"]);
+        assert!(endpoint.endpoints[0].last_success.is_none());
+        assert_eq!(endpoint.device_uuid.as_deref(), Some("phone-1"));
+        let rotated = frame(serde_json::json!({ "action": "rotate_token", "token": "t2", "device_uuid": "phone-1" }));
+        assert_eq!(state.handle_control_frame("pixel", rotated).unwrap().token.as_deref(), Some("t2"));
+        let events: Vec<String> = state.events.take_captured().into_iter().map(|(e, _)| e).collect();
+        assert_eq!(events, ["device-updated", "device-updated"]);
+    }
+}
@@ -0,0 +1,195 @@
+//! 后台错误总线：后台任务上报结构化错误，去重 + 限流后推送给前端。
+//! 缓冲区有上限，供诊断面板通过 get_recent_errors 查询。
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 缓冲区最多保留的错误条数
+pub const MAX_BUFFERED: usize = 100;
+/// 相同错误在该窗口内只记一次（累加次数）
+const DEDUP_WINDOW: Duration = Duration::from_secs(60);
+/// 限流窗口与窗口内最多推送次数
+const EMIT_WINDOW: Duration = Duration::from_secs(10);
+const MAX_EMITS_PER_WINDOW: usize = 5;
+
+/// 后台组件上报的错误
+#[derive(Debug, Clone)]
+pub struct BackgroundError {
+    pub source: String,
+    pub code: String,
+    pub message: String,
+    pub device: Option<String>,
+    pub suggested_action: Option<String>,
+}
+
+impl BackgroundError {
+    pub fn new(source: &str, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            source: source.to_string(),
+            code: code.to_string(),
+            message: message.into(),
+            device: None,
+            suggested_action: None,
+        }
+    }
+
+    /// 出错的设备连接（手机连接 id 或转发的对端实例）
+    pub fn device(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// 标记为可处理的错误，附带前端可调用的建议操作 id
+    pub fn action(mut self, action_id: &str) -> Self {
+        self.suggested_action = Some(action_id.to_string());
+        self
+    }
+}
+
+/// 缓冲区中的错误记录（同时也是 background-error 事件的载荷）
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorReport {
+    pub id: String,
+    pub source: String,
+    pub code: String,
+    pub message: String,
+    pub device: Option<String>,
+    pub resolvable: bool,
+    pub suggested_action: Option<String>,
+    pub first_at: i64,
+    pub last_at: i64,
+    pub count: u32,
+}
+
+struct Entry {
+    report: ErrorReport,
+    last_seen: Instant,
+}
+
+#[derive(Default)]
+struct Inner {
+    buffer: VecDeque<Entry>,
+    emits: VecDeque<Instant>,
+}
+
+#[derive(Default)]
+pub struct ErrorBus {
+    inner: Mutex<Inner>,
+}
+
+impl ErrorBus {
+    /// 记录一条错误；返回需要推送给前端的记录（被去重或限流时返回 None）
+    pub fn report(&self, err: BackgroundError, now: Instant) -> Option<ErrorReport> {
+        let wall = chrono::Utc::now().timestamp_millis();
+        let mut inner = self.inner.lock();
+
+        let existing = inner.buffer.iter_mut().find(|e| {
+            e.report.source == err.source
+                && e.report.code == err.code
+                && e.report.message == err.message
+                && e.report.device == err.device
+                && now.duration_since(e.last_seen) < DEDUP_WINDOW
+        });
+        if let Some(entry) = existing {
+            entry.report.count = entry.report.count.saturating_add(1);
+            entry.report.last_at = wall;
+            entry.last_seen = now;
+            return None;
+        }
+
+        let report = ErrorReport {
+            id: uuid::Uuid::new_v4().to_string(),
+            source: err.source,
+            code: err.code,
+            message: err.message,
+            device: err.device,
+            resolvable: err.suggested_action.is_some(),
+            suggested_action: err.suggested_action,
+            first_at: wall,
+            last_at: wall,
+            count: 1,
+        };
+        inner.buffer.push_back(Entry { report: report.clone(), last_seen: now });
+        while inner.buffer.len() > MAX_BUFFERED {
+            inner.buffer.pop_front();
+        }
+
+        while inner.emits.front().is_some_and(|t| now.duration_since(*t) >= EMIT_WINDOW) {
+            inner.emits.pop_front();
+        }
+        if inner.emits.len() >= MAX_EMITS_PER_WINDOW {
+            return None;
+        }
+        inner.emits.push_back(now);
+        Some(report)
+    }
+
+    /// 最近的错误（新 -> 旧）
+    pub fn recent(&self, limit: usize) -> Vec<ErrorReport> {
+        let inner = self.inner.lock();
+        inner.buffer.iter().rev().take(limit).map(|e| e.report.clone()).collect()
+    }
+
+    /// 移除指定错误，返回实际移除的条数
+    pub fn dismiss(&self, ids: &[String]) -> usize {
+        let mut inner = self.inner.lock();
+        let before = inner.buffer.len();
+        inner.buffer.retain(|e| !ids.contains(&e.report.id));
+        before - inner.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_within_window() {
+        let bus = ErrorBus::default();
+        let t0 = Instant::now();
+        let err = || BackgroundError::new("temp_server", "accept_failed", "boom");
+        assert!(bus.report(err(), t0).is_some());
+        assert!(bus.report(err(), t0 + Duration::from_secs(5)).is_none());
+        let recent = bus.recent(10);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].count, 2);
+
+        // 超过去重窗口后视为新错误
+        assert!(bus.report(err(), t0 + Duration::from_secs(5) + DEDUP_WINDOW).is_some());
+        assert_eq!(bus.recent(10).len(), 2);
+    }
+
+    #[test]
+    fn test_rate_limit_and_bounded_buffer() {
+        let bus = ErrorBus::default();
+        let t0 = Instant::now();
+        let emitted = (0..MAX_BUFFERED + 20)
+            .filter(|i| {
+                let err = BackgroundError::new("receiver", "io", format!("err {}", i));
+                bus.report(err, t0).is_some()
+            })
+            .count();
+        assert_eq!(emitted, MAX_EMITS_PER_WINDOW);
+        assert_eq!(bus.recent(usize::MAX).len(), MAX_BUFFERED);
+
+        // 窗口过后可以再次推送
+        let err = BackgroundError::new("receiver", "io", "later");
+        assert!(bus.report(err, t0 + EMIT_WINDOW).is_some());
+    }
+
+    #[test]
+    fn test_resolvable_and_dismiss() {
+        let bus = ErrorBus::default();
+        let err = BackgroundError::new("receiver", "port_in_use", "port 10035 in use").action("choose_another_port");
+        let report = bus.report(err, Instant::now()).unwrap();
+        assert!(report.resolvable);
+        assert_eq!(report.suggested_action.as_deref(), Some("choose_another_port"));
+
+        assert_eq!(bus.dismiss(&[report.id.clone(), "unknown".into()]), 1);
+        assert!(bus.recent(10).is_empty());
+    }
+}
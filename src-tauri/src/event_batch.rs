@@ -0,0 +1,285 @@
+//! 事件风暴保护：同步一次应用上千条事件时，逐条推送会让 WebView 卡住数秒。
+//! 通知新增/更新事件先经过自适应合并器：
+//! - 速率低于阈值：逐条推送（Passthrough）
+//! - 超过阈值：合并为 `notifications-batch`（每批最多 200 条），每 100ms 推送一次（Batching）
+//! - 极高速率：丢弃单条事件，只推送 `store-changed` 让前端重新拉取当前页（Refetch）
+//!
+//! 合并器本身不读时钟，时间由调用方传入，便于用假时钟测试。
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
+
+use crate::commands::AppState;
+
+/// 经过合并器的事件
+pub const BATCHED_EVENTS: &[&str] = &["notification-added", "notification-updated"];
+pub const BATCH_EVENT: &str = "notifications-batch";
+pub const STORE_CHANGED_EVENT: &str = "store-changed";
+const FLUSH_EVERY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BatchConfig {
+    /// 统计速率的滑动窗口
+    pub window_ms: u64,
+    /// 窗口内超过该条数时开始合并
+    pub batch_above: usize,
+    /// 窗口内超过该条数时改为通知前端重新拉取
+    pub refetch_above: usize,
+    /// 单批最多条数
+    pub max_batch: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self { window_ms: 1000, batch_above: 50, refetch_above: 1000, max_batch: 200 }
+    }
+}
+
+/// 按严重程度排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    #[default]
+    Passthrough,
+    Batching,
+    Refetch,
+}
+
+/// 实际要推送给前端的事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outgoing {
+    Single(String, serde_json::Value),
+    /// 每项为 `{ "event", "payload" }`，保持原始顺序
+    Batch(Vec<serde_json::Value>),
+    StoreChanged { dropped: usize },
+}
+
+impl Outgoing {
+    pub fn into_event(self) -> (String, serde_json::Value) {
+        match self {
+            Outgoing::Single(event, payload) => (event, payload),
+            Outgoing::Batch(items) => (BATCH_EVENT.to_string(), serde_json::Value::Array(items)),
+            Outgoing::StoreChanged { dropped } => {
+                (STORE_CHANGED_EVENT.to_string(), serde_json::json!({ "dropped": dropped }))
+            }
+        }
+    }
+}
+
+/// 诊断用计数
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BatcherStats {
+    pub mode: BatchMode,
+    pub config: BatchConfig,
+    /// 逐条推送的事件数
+    pub passed: u64,
+    /// 合并进批次的事件数
+    pub coalesced: u64,
+    pub batches: u64,
+    /// 因速率过高丢弃的事件数
+    pub dropped: u64,
+    pub refetches: u64,
+    /// 当前窗口内的事件数
+    pub window_rate: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct EventBatcher {
+    config: BatchConfig,
+    mode: BatchMode,
+    // 窗口内各事件的时间（毫秒）
+    recent: VecDeque<u64>,
+    pending: Vec<serde_json::Value>,
+    dropped_since_flush: usize,
+    stats: BatcherStats,
+}
+
+impl EventBatcher {
+    fn trim(&mut self, now: u64) {
+        while self.recent.front().is_some_and(|t| now.saturating_sub(*t) >= self.config.window_ms) {
+            self.recent.pop_front();
+        }
+    }
+
+    /// 送入一条事件，返回需要立即推送的事件
+    pub fn push(&mut self, now: u64, event: &str, payload: serde_json::Value) -> Vec<Outgoing> {
+        self.recent.push_back(now);
+        self.trim(now);
+        // 超过重新拉取阈值后具体速率已无区别，窗口内的时间戳不必全部保留
+        while self.recent.len() > self.config.refetch_above + 1 {
+            self.recent.pop_front();
+        }
+        let rate = self.recent.len();
+        if rate > self.config.refetch_above && self.mode != BatchMode::Refetch {
+            println!("[Events] {} events/window, switching to refetch mode", rate);
+            self.mode = BatchMode::Refetch;
+            // 已合并未推送的也由重新拉取覆盖
+            self.dropped_since_flush += self.pending.len();
+            self.stats.dropped = self.stats.dropped.saturating_add(self.pending.len() as u64);
+            self.pending.clear();
+        } else if rate > self.config.batch_above && self.mode == BatchMode::Passthrough {
+            println!("[Events] {} events/window, switching to batching mode", rate);
+            self.mode = BatchMode::Batching;
+        }
+
+        match self.mode {
+            BatchMode::Passthrough => {
+                self.stats.passed = self.stats.passed.saturating_add(1);
+                vec![Outgoing::Single(event.to_string(), payload)]
+            }
+            BatchMode::Batching => {
+                self.stats.coalesced = self.stats.coalesced.saturating_add(1);
+                self.pending.push(serde_json::json!({ "event": event, "payload": payload }));
+                if self.pending.len() >= self.config.max_batch {
+                    self.take_batch().into_iter().collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            BatchMode::Refetch => {
+                self.stats.dropped = self.stats.dropped.saturating_add(1);
+                self.dropped_since_flush += 1;
+                Vec::new()
+            }
+        }
+    }
+
+    fn take_batch(&mut self) -> Option<Outgoing> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let n = self.pending.len().min(self.config.max_batch);
+        self.stats.batches = self.stats.batches.saturating_add(1);
+        Some(Outgoing::Batch(self.pending.drain(..n).collect()))
+    }
+
+    /// 定时调用：推送积压的批次 / 重新拉取通知，速率回落后降级模式
+    pub fn tick(&mut self, now: u64) -> Vec<Outgoing> {
+        let mut out = Vec::new();
+        while let Some(batch) = self.take_batch() {
+            out.push(batch);
+        }
+        if self.dropped_since_flush > 0 {
+            self.stats.refetches = self.stats.refetches.saturating_add(1);
+            out.push(Outgoing::StoreChanged { dropped: std::mem::take(&mut self.dropped_since_flush) });
+        }
+        self.trim(now);
+        let rate = self.recent.len();
+        let mode = if rate > self.config.refetch_above {
+            BatchMode::Refetch
+        } else if rate > self.config.batch_above {
+            BatchMode::Batching
+        } else {
+            BatchMode::Passthrough
+        };
+        // 只降级；升级只在 push 时发生
+        if mode < self.mode {
+            println!("[Events] Rate back to {} events/window, {:?} -> {:?}", rate, self.mode, mode);
+            self.mode = mode;
+        }
+        out
+    }
+
+    /// (窗口内时间戳数, 积压的事件数)
+    pub fn buffered(&self) -> (usize, usize) {
+        (self.recent.len(), self.pending.len())
+    }
+
+    pub fn stats(&self) -> BatcherStats {
+        BatcherStats { mode: self.mode, config: self.config, window_rate: self.recent.len(), ..self.stats.clone() }
+    }
+}
+
+/// 后台定时推送积压的批次
+pub async fn run_flush_loop(app: AppHandle, token: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(FLUSH_EVERY) => {}
+        }
+        app.state::<AppState>().events.flush_batches();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batcher() -> EventBatcher {
+        let config = BatchConfig { window_ms: 1000, batch_above: 5, refetch_above: 20, max_batch: 4 };
+        EventBatcher { config, ..Default::default() }
+    }
+
+    fn push(b: &mut EventBatcher, now: u64, i: usize) -> Vec<Outgoing> {
+        b.push(now, "notification-added", serde_json::json!({ "id": i }))
+    }
+
+    fn batch_ids(out: &[Outgoing]) -> Vec<Vec<u64>> {
+        out.iter()
+            .filter_map(|o| match o {
+                Outgoing::Batch(items) => Some(items.iter().map(|i| i["payload"]["id"].as_u64().unwrap()).collect()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_low_rate_passes_through() {
+        let mut b = batcher();
+        for i in 0..10 {
+            // 每 300ms 一条，窗口内最多 4 条
+            let out = push(&mut b, i as u64 * 300, i);
+            assert_eq!(out, [Outgoing::Single("notification-added".into(), serde_json::json!({ "id": i }))]);
+        }
+        assert_eq!(b.stats().passed, 10);
+        assert_eq!(b.stats().mode, BatchMode::Passthrough);
+    }
+
+    #[test]
+    fn test_burst_coalesces_in_order_and_caps_batch() {
+        let mut b = batcher();
+        let mut out = Vec::new();
+        for i in 0..12 {
+            out.extend(push(&mut b, 10, i));
+        }
+        // 前 5 条逐条推送，之后合并；满 4 条立即推送一批
+        assert_eq!(out.iter().filter(|o| matches!(o, Outgoing::Single(..))).count(), 5);
+        assert_eq!(batch_ids(&out), [vec![5, 6, 7, 8]]);
+        assert_eq!(b.stats().mode, BatchMode::Batching);
+
+        let flushed = b.tick(110);
+        assert_eq!(batch_ids(&flushed), [vec![9, 10, 11]]);
+        // 窗口内仍是高速率，保持合并
+        assert_eq!(b.stats().mode, BatchMode::Batching);
+        assert!(b.tick(1100).is_empty());
+        assert_eq!(b.stats().mode, BatchMode::Passthrough);
+        let stats = b.stats();
+        assert_eq!((stats.passed, stats.coalesced, stats.batches), (5, 7, 2));
+    }
+
+    #[test]
+    fn test_extreme_rate_falls_back_to_refetch() {
+        let mut b = batcher();
+        let mut out = Vec::new();
+        for i in 0..30 {
+            out.extend(push(&mut b, 10, i));
+        }
+        assert_eq!(b.stats().mode, BatchMode::Refetch);
+        let flushed = b.tick(110);
+        // 切换前已合并但未推送的 3 条也算丢弃
+        assert_eq!(flushed, [Outgoing::StoreChanged { dropped: 13 }]);
+        assert_eq!(b.stats().dropped, 13);
+        assert!(b.tick(210).is_empty());
+
+        // 速率回落后恢复逐条推送
+        b.tick(1500);
+        assert_eq!(b.stats().mode, BatchMode::Passthrough);
+        assert!(matches!(push(&mut b, 1600, 99)[..], [Outgoing::Single(..)]));
+        assert_eq!(b.stats().refetches, 1);
+    }
+}
@@ -0,0 +1,370 @@
+//! 事件流导出：把每条入库事件（附加连接 id 与接收时间）按行追加到 NDJSON 文件，供外部分析。
+//! 写文件在独立线程中进行，入库只做一次非阻塞发送；队列满时丢弃该行并计数。
+//! 文件按大小轮转（events.ndjson -> .1 -> .2 ...，保留 keep_files 个旧文件）。
+//! 写入出错（磁盘满、无权限）时停止记录并上报后台错误，不影响入库。
+//! 开始记录时可选脱敏级别（见 masking），写入前对每条事件做变换。
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use crate::commands::AppState;
+use crate::error_bus::BackgroundError;
+use crate::masking::{MaskLevel, Masker};
+use crate::types::Event;
+
+/// 写线程队列长度
+const QUEUE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// 交给系统刷盘
+    Never,
+    /// 每行写入后 fsync
+    EveryLine,
+    /// 轮转和停止时 fsync
+    #[default]
+    OnRotate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct EventLogSettings {
+    /// 单个文件上限（MB），超过后轮转
+    pub max_mb: u64,
+    /// 保留的旧文件个数
+    pub keep_files: usize,
+    pub fsync: FsyncPolicy,
+}
+
+impl Default for EventLogSettings {
+    fn default() -> Self {
+        Self { max_mb: 10, keep_files: 5, fsync: FsyncPolicy::default() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct EventLogStatus {
+    pub active: bool,
+    pub path: Option<String>,
+    pub bytes_written: u64,
+    pub dropped_lines: u64,
+    /// 导致记录停止的错误
+    pub error: Option<String>,
+    pub mask: MaskLevel,
+}
+
+/// 写入文件的一行
+#[derive(Serialize)]
+struct LoggedEvent<'a> {
+    received_at: i64,
+    connection_id: &'a str,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Default)]
+struct Stats {
+    running: AtomicBool,
+    bytes_written: AtomicU64,
+    dropped_lines: AtomicU64,
+    error: Mutex<Option<String>>,
+    mask: MaskLevel,
+}
+
+struct Active {
+    path: PathBuf,
+    tx: SyncSender<String>,
+    stats: Arc<Stats>,
+    masker: Masker,
+}
+
+#[derive(Default)]
+pub struct EventLog {
+    active: Mutex<Option<Active>>,
+    // 最近一次记录的统计（停止后仍可查询）
+    last: Mutex<Option<(PathBuf, Arc<Stats>)>>,
+}
+
+/// 按大小轮转的追加写入器
+struct Writer {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    settings: EventLogSettings,
+}
+
+impl Writer {
+    fn open(path: &Path, settings: EventLogSettings) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self { path: path.to_path_buf(), file, size, settings })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<u64, String> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.settings.max_mb.max(1) * 1024 * 1024 {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line).map_err(|e| format!("Failed to write event log: {}", e))?;
+        if self.settings.fsync == FsyncPolicy::EveryLine {
+            self.sync()?;
+        }
+        self.size += len;
+        Ok(len)
+    }
+
+    fn sync(&self) -> Result<(), String> {
+        self.file.sync_data().map_err(|e| format!("Failed to sync event log: {}", e))
+    }
+
+    /// 当前文件改名为 .1，旧文件依次后移，超出保留数的删除
+    fn rotate(&mut self) -> Result<(), String> {
+        if self.settings.fsync != FsyncPolicy::Never {
+            self.sync()?;
+        }
+        let rotated = |i: usize| PathBuf::from(format!("{}.{}", self.path.display(), i));
+        let _ = std::fs::remove_file(rotated(self.settings.keep_files));
+        for i in (1..self.settings.keep_files).rev() {
+            let _ = std::fs::rename(rotated(i), rotated(i + 1));
+        }
+        if self.settings.keep_files > 0 {
+            std::fs::rename(&self.path, rotated(1)).map_err(|e| format!("Failed to rotate event log: {}", e))?;
+        } else {
+            std::fs::remove_file(&self.path).map_err(|e| format!("Failed to rotate event log: {}", e))?;
+        }
+        *self = Self::open(&self.path, self.settings.clone())?;
+        Ok(())
+    }
+}
+
+/// 写线程：通道关闭（停止记录）或写入出错时退出
+fn run_writer(mut writer: Writer, rx: Receiver<String>, stats: Arc<Stats>, on_error: impl Fn(String)) {
+    for line in rx {
+        match writer.write_line(&line) {
+            Ok(n) => {
+                stats.bytes_written.fetch_add(n, Ordering::Relaxed);
+            }
+            Err(e) => {
+                stats.running.store(false, Ordering::SeqCst);
+                *stats.error.lock() = Some(e.clone());
+                on_error(e);
+                return;
+            }
+        }
+    }
+    if writer.settings.fsync != FsyncPolicy::Never {
+        let _ = writer.sync();
+    }
+    stats.running.store(false, Ordering::SeqCst);
+}
+
+impl EventLog {
+    /// 记录一条事件；未开启时为空操作，队列满时丢弃
+    pub fn append(&self, connection_id: &str, event: &Event, received_at: i64) {
+        let active = self.active.lock();
+        let Some(active) = active.as_ref() else {
+            return;
+        };
+        if !active.stats.running.load(Ordering::SeqCst) {
+            return;
+        }
+        let event = &active.masker.event(event);
+        let line = match serde_json::to_string(&LoggedEvent { received_at, connection_id, event }) {
+            Ok(line) => line,
+            Err(e) => {
+                println!("[EventLog] Failed to serialize event: {}", e);
+                return;
+            }
+        };
+        match active.tx.try_send(line) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                active.stats.dropped_lines.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn status(&self) -> EventLogStatus {
+        let last = self.last.lock();
+        let Some((path, stats)) = last.as_ref() else {
+            return EventLogStatus::default();
+        };
+        let error = stats.error.lock().clone();
+        EventLogStatus {
+            active: stats.running.load(Ordering::SeqCst),
+            path: Some(path.display().to_string()),
+            bytes_written: stats.bytes_written.load(Ordering::Relaxed),
+            dropped_lines: stats.dropped_lines.load(Ordering::Relaxed),
+            error,
+            mask: stats.mask,
+        }
+    }
+
+    fn start(
+        &self,
+        path: PathBuf,
+        settings: EventLogSettings,
+        masker: Masker,
+        on_error: impl Fn(String) + Send + 'static,
+    ) -> Result<std::thread::JoinHandle<()>, String> {
+        let writer = Writer::open(&path, settings)?;
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let stats = Arc::new(Stats { mask: masker.level(), ..Default::default() });
+        stats.running.store(true, Ordering::SeqCst);
+        let thread_stats = stats.clone();
+        let handle = std::thread::Builder::new()
+            .name("event-log".into())
+            .spawn(move || run_writer(writer, rx, thread_stats, on_error))
+            .map_err(|e| format!("Failed to start event log writer: {}", e))?;
+        *self.last.lock() = Some((path.clone(), stats.clone()));
+        // 替换旧的记录：丢弃旧发送端后旧线程写完剩余内容自行退出
+        *self.active.lock() = Some(Active { path, tx, stats, masker });
+        Ok(handle)
+    }
+
+    /// 停止记录，返回停止前的文件路径（写线程写完队列中剩余的行后退出）
+    fn stop(&self) -> Option<PathBuf> {
+        let active = self.active.lock().take()?;
+        active.stats.running.store(false, Ordering::SeqCst);
+        Some(active.path)
+    }
+}
+
+impl AppState {
+    /// 开始把入库事件按 mask 脱敏后追加到 path（已在记录时切换到新文件）
+    pub fn start_event_log(&self, path: PathBuf, mask: MaskLevel) -> Result<EventLogStatus, String> {
+        let settings = self.settings.read().event_log.clone();
+        let app = self.events.app().cloned();
+        self.event_log.start(path.clone(), settings, self.masker(mask), move |e| {
+            println!("[EventLog] Stopped: {}", e);
+            if let Some(app) = &app {
+                app.state::<AppState>().report_error(
+                    BackgroundError::new("event_log", "write_failed", e).action("start_event_log"),
+                );
+            }
+        })?;
+        println!("[EventLog] Writing to {}", path.display());
+        Ok(self.event_log.status())
+    }
+
+    pub fn stop_event_log(&self) -> EventLogStatus {
+        if let Some(path) = self.event_log.stop() {
+            println!("[EventLog] Stopped writing to {}", path.display());
+        }
+        self.event_log.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Notification;
+
+    fn event(id: &str) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 1,
+            notification: Some(Notification { id: id.into(), title: Some("x".repeat(100)), ..Default::default() }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_lines_and_rotation() {
+        let dir = crate::storage::temp_dir("event-log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("events.ndjson");
+        let mut writer = Writer::open(&path, EventLogSettings { max_mb: 1, keep_files: 2, fsync: FsyncPolicy::Never }).unwrap();
+        let line = "y".repeat(300 * 1024);
+        for _ in 0..10 {
+            writer.write_line(&line).unwrap();
+        }
+        // 每个文件放 3 行，10 行 -> 当前文件 1 行 + 两个旧文件，更早的已删除
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert!(dir.join("events.ndjson.1").exists());
+        assert!(dir.join("events.ndjson.2").exists());
+        assert!(!dir.join("events.ndjson.3").exists());
+
+        let log = EventLog::default();
+        let path = dir.join("stream.ndjson");
+        let handle = log.start(path.clone(), EventLogSettings::default(), Masker::new(MaskLevel::None, ""), |_| {}).unwrap();
+        log.append("conn-1", &event("a"), 100);
+        log.append("conn-1", &event("b"), 101);
+        log.stop();
+        handle.join().unwrap();
+        log.append("conn-1", &event("c"), 102);
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["connection_id"], "conn-1");
+        assert_eq!(lines[0]["received_at"], 100);
+        assert_eq!(lines[1]["notification"]["id"], "b");
+        let status = log.status();
+        assert!(!status.active);
+        assert_eq!(status.bytes_written, std::fs::metadata(&path).unwrap().len());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_write_error_stops_log() {
+        let dir = crate::storage::temp_dir("event-log-err");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("events.ndjson");
+        let log = EventLog::default();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let sink = errors.clone();
+        // keep_files = 0 时轮转会删除当前文件；先删掉所在目录让轮转后的重新打开失败
+        let settings = EventLogSettings { max_mb: 1, keep_files: 0, fsync: FsyncPolicy::Never };
+        let handle = log.start(path, settings, Masker::new(MaskLevel::None, ""), move |e| sink.lock().push(e)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let big = Event { id: Some("z".repeat(700 * 1024)), ..event("big") };
+        log.append("c", &big, 1);
+        log.append("c", &big, 2);
+        handle.join().unwrap();
+
+        assert_eq!(errors.lock().len(), 1);
+        let status = log.status();
+        assert!(!status.active);
+        assert!(status.error.is_some());
+        log.append("c", &big, 3);
+        assert_eq!(log.status().dropped_lines, 0);
+    }
+
+    #[test]
+    fn test_masked_lines_drop_bodies() {
+        use crate::masking::tests::{leaky, BODY};
+        let dir = crate::storage::temp_dir("event-log-mask");
+        let path = dir.join("events.ndjson");
+        let log = EventLog::default();
+        let masker = Masker::new(MaskLevel::BodiesHashed, "salt");
+        let handle = log.start(path.clone(), EventLogSettings::default(), masker, |_| {}).unwrap();
+        let n = leaky();
+        log.append("conn-1", &Event { notification: Some(n), ..event("a") }, 100);
+        log.stop();
+        handle.join().unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(!written.contains(BODY) && !written.contains("4711"), "{}", written);
+        assert!(written.contains("com.secret.chat"));
+        assert_eq!(log.status().mask, MaskLevel::BodiesHashed);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,113 @@
+//! Rust -> WebView 事件发送。
+//! setup 阶段挂载 AppHandle；未挂载时（如单元测试）发送为空操作。
+//! 通知新增/更新事件经过自适应合并器（见 event_batch），高速率时合并或改为通知前端重新拉取。
+//! 主窗口隐藏时，列表类事件改为缓冲（见 window_feed）。
+
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::event_batch::{BatcherStats, EventBatcher, Outgoing, BATCHED_EVENTS};
+use crate::window_feed::WindowFeed;
+
+#[derive(Default)]
+pub struct EventSink {
+    app: OnceLock<AppHandle>,
+    batcher: parking_lot::Mutex<EventBatcher>,
+    feed: parking_lot::Mutex<WindowFeed>,
+    // 测试时记录所有发送的事件，便于断言
+    #[cfg(test)]
+    captured: parking_lot::Mutex<Vec<(String, serde_json::Value)>>,
+}
+
+impl EventSink {
+    pub fn attach(&self, app: AppHandle) {
+        let _ = self.app.set(app);
+    }
+
+    pub fn app(&self) -> Option<&AppHandle> {
+        self.app.get()
+    }
+
+    pub fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        // 隐藏期间不经过合并器，直接进入缓冲
+        if BATCHED_EVENTS.contains(&event) && self.feed.lock().is_visible() {
+            let payload = serde_json::to_value(&payload).unwrap_or_default();
+            let out = self.batcher.lock().push(now_ms(), event, payload);
+            self.send_all(out);
+        } else {
+            self.send(event, payload);
+        }
+    }
+
+    /// 推送合并器积压的批次（由后台任务定时调用）
+    pub fn flush_batches(&self) {
+        let out = self.batcher.lock().tick(now_ms());
+        self.send_all(out);
+    }
+
+    pub fn batcher_stats(&self) -> BatcherStats {
+        self.batcher.lock().stats()
+    }
+
+    /// 合并器当前持有的 (速率窗口, 待推送) 条数
+    pub fn batcher_buffered(&self) -> (usize, usize) {
+        self.batcher.lock().buffered()
+    }
+
+    pub fn window_feed(&self) -> parking_lot::MutexGuard<'_, WindowFeed> {
+        self.feed.lock()
+    }
+
+    fn send_all(&self, out: Vec<Outgoing>) {
+        for o in out {
+            let (event, payload) = o.into_event();
+            self.send(&event, payload);
+        }
+    }
+
+    fn send<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if self.feed.lock().buffer(event, || serde_json::to_value(&payload).unwrap_or_default()) {
+            return;
+        }
+
+        #[cfg(test)]
+        self.captured.lock().push((
+            event.to_string(),
+            serde_json::to_value(&payload).unwrap_or_default(),
+        ));
+
+        if let Some(app) = self.app.get() {
+            if let Err(e) = app.emit(event, payload) {
+                println!("[Events] Failed to emit {}: {}", event, e);
+            }
+        }
+    }
+
+    /// 取出并清空已记录的事件（仅测试）
+    #[cfg(test)]
+    pub fn take_captured(&self) -> Vec<(String, serde_json::Value)> {
+        std::mem::take(&mut *self.captured.lock())
+    }
+}
+
+// 合并窗口只关心时长，用单调时钟
+fn now_ms() -> u64 {
+    crate::wall_clock::mono_ms()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_without_app_is_captured() {
+        let sink = EventSink::default();
+        sink.emit("background-error", serde_json::json!({ "code": "x" }));
+        let captured = sink.take_captured();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured[0].0, "background-error");
+        assert!(sink.take_captured().is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+//! 通知导出：重置前把全部通知（含已读状态、时间与暂缓中的）写成一个格式化的 JSON 文件留档。
+//! 只在复制快照时持有存储锁，序列化与写盘在锁外逐条流式写入同目录的临时文件，完成后再 rename，不会留下写了一半的文件。
+//! 未指定路径时写到数据目录下带时间戳的文件名。默认不脱敏（自己的存档），也可按 masking 的级别导出。
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::masking::MaskLevel;
+use crate::store::SortMode;
+use crate::types::Notification;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ExportReport {
+    pub path: String,
+    pub notifications: usize,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+struct ExportFile<'a> {
+    app_version: &'a str,
+    exported_at: i64,
+    mask: MaskLevel,
+    count: usize,
+    notifications: &'a [Notification],
+}
+
+/// 默认文件名：notifications-20240101-120000.json（本地时间）
+pub fn default_file_name(now: i64) -> String {
+    let local = chrono::DateTime::from_timestamp(now, 0).unwrap_or_default().with_timezone(&chrono::Local);
+    format!("notifications-{}.json", local.format("%Y%m%d-%H%M%S"))
+}
+
+fn write_file(path: &Path, file: &ExportFile) -> Result<u64, String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Export directory is not writable: {}: {}", parent.display(), e))?;
+    }
+    let tmp = path.with_extension("tmp");
+    let out = File::create(&tmp).map_err(|e| format!("Export directory is not writable: {}: {}", tmp.display(), e))?;
+    let mut writer = BufWriter::new(out);
+    let written = serde_json::to_writer_pretty(&mut writer, file)
+        .map_err(|e| e.to_string())
+        .and_then(|()| writer.flush().map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!("Failed to write {}: {}", tmp.display(), e));
+    }
+    drop(writer);
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+    Ok(std::fs::metadata(path).map(|m| m.len()).unwrap_or_default())
+}
+
+impl AppState {
+    /// 导出全部通知（旧 -> 新，暂缓中的在后）；path 为空时写到数据目录
+    pub fn export_notifications(&self, path: Option<PathBuf>, mask: Option<MaskLevel>, now: i64) -> Result<ExportReport, String> {
+        let path = match path {
+            Some(path) => path,
+            None => self.storage.base_dir().ok_or("Data directory is not available")?.join(default_file_name(now)),
+        };
+        let mut notifications = {
+            let store = self.store.lock().unwrap();
+            let mut all = store.query(SortMode::Oldest, |_| true, 0, None);
+            all.extend(store.snoozed());
+            all
+        };
+        let masker = self.masker(mask.unwrap_or_default());
+        if masker.level() != MaskLevel::None {
+            notifications = notifications.iter().map(|n| masker.notification(n)).collect();
+        }
+        let file = ExportFile {
+            app_version: crate::ping::VERSION,
+            exported_at: now,
+            mask: masker.level(),
+            count: notifications.len(),
+            notifications: &notifications,
+        };
+        let bytes = write_file(&path, &file)?;
+        println!("[Export] Wrote {} notifications ({} bytes) to {}", notifications.len(), bytes, path.display());
+        Ok(ExportReport { path: path.display().to_string(), notifications: notifications.len(), bytes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    #[test]
+    fn test_export_thousands_round_trip() {
+        let dir = crate::storage::temp_dir("export");
+        let state = AppState::default();
+        state.settings.write().retention.max_items = 10_000;
+        for i in 0..3_000 {
+            state.ingest_event(Event {
+                event_type: "added".into(),
+                seq: 0,
+                notification: Some(Notification {
+                    id: format!("n{}", i),
+                    package_name: Some(format!("com.app{}", i % 13)),
+                    title: Some(format!("标题 {}", i)),
+                    text: Some("x".repeat(i % 200)),
+                    posted_at: Some(1_700_000_000 + i as i64),
+                    ..Default::default()
+                }),
+                id: None,
+            });
+        }
+        let read: Vec<String> = (0..1_000).map(|i| format!("n{}", i)).collect();
+        state.store.lock().unwrap().mark_read(&read);
+        let n = state.store.lock().unwrap().get("n2000").cloned().unwrap();
+        state.store.lock().unwrap().snooze(n, 1_800_000_000);
+
+        // 未设置数据目录
+        assert!(state.export_notifications(None, None, 0).is_err());
+        state.storage.set_base_dir(dir.clone());
+        let report = state.export_notifications(None, None, 1_700_000_000).unwrap();
+        assert_eq!(report.notifications, 3_000);
+        assert!(report.path.starts_with(dir.to_str().unwrap()) && report.path.ends_with(".json"));
+        let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&report.path).unwrap()).unwrap();
+        assert_eq!(json["count"], 3_000);
+        assert_eq!(report.bytes, std::fs::metadata(&report.path).unwrap().len());
+        let exported: Vec<Notification> = serde_json::from_value(json["notifications"].clone()).unwrap();
+        assert_eq!(exported.iter().filter(|n| n.read).count(), 1_000);
+        assert_eq!(exported.last().unwrap().snoozed_until, Some(1_800_000_000));
+        assert_eq!(exported[0].posted_at, Some(1_700_000_000));
+
+        // 目标目录不可写（父路径是文件）
+        let blocker = dir.join("blocker");
+        std::fs::write(&blocker, b"").unwrap();
+        let err = state.export_notifications(Some(blocker.join("out.json")), None, 0).unwrap_err();
+        assert!(err.starts_with("Export directory is not writable"), "{}", err);
+        assert!(std::fs::read_dir(&dir).unwrap().flatten().all(|e| e.path().extension().is_none_or(|x| x != "tmp")));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
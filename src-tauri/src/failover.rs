@@ -0,0 +1,249 @@
+//! 多地址设备的重连：按地址列表顺序（最近成功的在前，见 endpoints）逐个尝试，第一个连上的胜出，其余不再尝试。
+//! 连接失败的地址按指数退避（RETRY_BASE_SECS 起翻倍，最长 RETRY_MAX_SECS），退避期间自动重连跳过它；
+//! 所有地址都在退避中时本轮不尝试。手动重连忽略退避，每个地址都试一次。
+//! 同一设备同时只有一个重连在进行（维护任务与手动重连不会各连一个地址），后来的直接返回错误。
+//! 登录失败（令牌失效等）不是地址的问题，不再尝试其他地址。
+
+use std::collections::HashSet;
+
+use parking_lot::Mutex;
+
+use crate::android_client::AndroidSocketClient;
+use crate::commands::AppState;
+use crate::endpoints::DeviceEndpoint;
+
+/// 地址首次失败后的退避
+pub const RETRY_BASE_SECS: i64 = 10;
+/// 退避上限
+pub const RETRY_MAX_SECS: i64 = 600;
+
+/// 连续失败 failures 次后的退避秒数
+pub fn backoff_secs(failures: u32) -> i64 {
+    let doublings = failures.saturating_sub(1).min(16);
+    (RETRY_BASE_SECS << doublings).min(RETRY_MAX_SECS)
+}
+
+/// 尝试顺序；automatic 时跳过仍在退避中的地址
+pub fn candidates(endpoint: &DeviceEndpoint, now: i64, automatic: bool) -> Vec<String> {
+    endpoint
+        .endpoints
+        .iter()
+        .filter(|e| !automatic || e.retry_after.is_none_or(|t| t <= now))
+        .map(|e| e.host.clone())
+        .collect()
+}
+
+fn note_failure(endpoint: &mut DeviceEndpoint, host: &str, now: i64) {
+    if let Some(e) = endpoint.endpoints.iter_mut().find(|e| e.host == host) {
+        e.failures = e.failures.saturating_add(1);
+        e.retry_after = Some(now + backoff_secs(e.failures));
+    }
+}
+
+/// 正在重连的设备
+#[derive(Default)]
+pub struct ReconnectGuard {
+    active: Mutex<HashSet<String>>,
+}
+
+/// 重连结束（成功、失败或出错返回）时释放
+pub struct InFlight<'a> {
+    guard: &'a ReconnectGuard,
+    connection_id: String,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.guard.active.lock().remove(&self.connection_id);
+    }
+}
+
+impl ReconnectGuard {
+    pub fn enter(&self, connection_id: &str) -> Result<InFlight<'_>, String> {
+        if !self.active.lock().insert(connection_id.to_string()) {
+            return Err(format!("{} is already reconnecting", connection_id));
+        }
+        Ok(InFlight { guard: self, connection_id: connection_id.to_string() })
+    }
+}
+
+impl AppState {
+    /// 按顺序连接设备的已知地址，返回 (客户端, 连上的地址)；失败的地址进入退避
+    pub(crate) fn connect_failover<F>(
+        &self,
+        endpoint: &DeviceEndpoint,
+        automatic: bool,
+        now: i64,
+        mut connect: F,
+    ) -> Result<(AndroidSocketClient, String), String>
+    where
+        F: FnMut(&str) -> Result<AndroidSocketClient, String>,
+    {
+        let id = &endpoint.connection_id;
+        let hosts = candidates(endpoint, now, automatic);
+        if hosts.is_empty() {
+            return Err(format!("All endpoints of {} are backing off", id));
+        }
+        let mut errors = Vec::new();
+        for host in hosts {
+            match connect(&host) {
+                Ok(client) => {
+                    if !errors.is_empty() {
+                        println!("[Failover] {} reached via {} after {} failed", id, host, errors.len());
+                    }
+                    return Ok((client, host));
+                }
+                Err(e) => {
+                    self.with_endpoint_mut(id, |d| note_failure(d, &host, now));
+                    errors.push(format!("{}: {}", host, e));
+                }
+            }
+        }
+        Err(format!("No endpoint of {} is reachable ({})", id, errors.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::transport::Transport;
+
+    const HOME: &str = "192.168.1.5:10035";
+    const USB: &str = "192.168.42.129:10035";
+
+    struct LoginOk;
+
+    impl Transport for LoginOk {
+        fn send_line(&mut self, _line: &str) -> Result<(), String> {
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(r#"{"success":true,"deviceUuid":"phone-1"}"#.to_string())
+        }
+    }
+
+    /// 按脚本接受连接的连接器，记录尝试顺序
+    struct MockConnector {
+        accepts: Mutex<HashSet<&'static str>>,
+        attempts: Mutex<Vec<String>>,
+    }
+
+    impl MockConnector {
+        fn new(accepts: &[&'static str]) -> Self {
+            Self { accepts: Mutex::new(accepts.iter().copied().collect()), attempts: Mutex::default() }
+        }
+
+        fn connect(&self, host: &str) -> Result<AndroidSocketClient, String> {
+            self.attempts.lock().push(host.to_string());
+            if !self.accepts.lock().contains(host) {
+                return Err("Connection failed: timed out".to_string());
+            }
+            Ok(AndroidSocketClient::with_transport(Box::new(LoginOk), "pixel".into(), Arc::default()))
+        }
+
+        fn take_attempts(&self) -> Vec<String> {
+            std::mem::take(&mut *self.attempts.lock())
+        }
+    }
+
+    fn hosts(state: &AppState) -> Vec<String> {
+        state.device_endpoint("pixel").unwrap().endpoints.into_iter().map(|e| e.host).collect()
+    }
+
+    fn disconnect(state: &AppState) {
+        state.clients.write().remove("pixel");
+    }
+
+    #[test]
+    fn test_failover_promotes_working_endpoint() {
+        let state = AppState::default();
+        state.remember_endpoint("pixel", USB, "t1", None, None);
+        state.remember_endpoint("pixel", HOME, "t1", None, None);
+        assert_eq!(hosts(&state), [HOME, USB]);
+
+        // 在 USB 网络下：家里的地址失败，USB 地址胜出并移到最前
+        let mock = MockConnector::new(&[USB]);
+        state.reconnect_device_with("pixel", true, |h| mock.connect(h)).unwrap();
+        assert_eq!(mock.take_attempts(), [HOME, USB]);
+        assert_eq!(hosts(&state), [USB, HOME]);
+        let device = state.device_endpoint("pixel").unwrap();
+        assert_eq!(device.host, USB);
+        assert!(device.endpoints[0].last_success.is_some());
+        assert_eq!(device.endpoints[1].failures, 1);
+
+        // 再次重连直接用 USB 地址
+        disconnect(&state);
+        state.reconnect_device_with("pixel", true, |h| mock.connect(h)).unwrap();
+        assert_eq!(mock.take_attempts(), [USB]);
+
+        // 回到家里：自动重连跳过仍在退避中的家里地址；手动重连忽略退避
+        disconnect(&state);
+        *mock.accepts.lock() = HashSet::from([HOME]);
+        assert!(state.reconnect_device_with("pixel", true, |h| mock.connect(h)).is_err());
+        assert_eq!(mock.take_attempts(), [USB]);
+        state.reconnect_device_with("pixel", false, |h| mock.connect(h)).unwrap();
+        assert_eq!(mock.take_attempts(), [USB, HOME]);
+        assert_eq!(hosts(&state), [HOME, USB]);
+
+        // 全部退避中：本轮不尝试
+        disconnect(&state);
+        *mock.accepts.lock() = HashSet::new();
+        assert!(state.reconnect_device_with("pixel", false, |h| mock.connect(h)).is_err());
+        mock.take_attempts();
+        let err = state.reconnect_device_with("pixel", true, |h| mock.connect(h)).unwrap_err();
+        assert!(err.contains("backing off"), "{}", err);
+        assert!(mock.take_attempts().is_empty());
+    }
+
+    #[test]
+    fn test_one_reconnect_per_device() {
+        let state = AppState::default();
+        state.remember_endpoint("pixel", HOME, "t1", None, None);
+        let mock = MockConnector::new(&[HOME]);
+        let err = state
+            .reconnect_device_with("pixel", false, |_| {
+                // 第一个连接还没完成时的第二次重连
+                let nested = state.reconnect_device_with("pixel", false, |h| mock.connect(h));
+                Err(nested.unwrap_err())
+            })
+            .unwrap_err();
+        assert!(err.contains("already reconnecting"), "{}", err);
+        assert!(mock.take_attempts().is_empty());
+        // 第一次结束后可以再次重连
+        state.reconnect_device_with("pixel", false, |h| mock.connect(h)).unwrap();
+        assert_eq!(mock.take_attempts(), [HOME]);
+    }
+
+    #[test]
+    fn test_remove_endpoint_and_legacy_records() {
+        let dir = crate::storage::temp_dir("failover");
+        std::fs::write(
+            dir.join("endpoints.json"),
+            r#"{"pixel":{"connection_id":"pixel","host":"192.168.1.5:10035","token":"t1","device_uuid":"uuid-1","updated_at":100}}"#,
+        )
+        .unwrap();
+        let state = AppState::default();
+        state.storage.set_base_dir(dir.clone());
+        state.load_endpoints();
+        let device = state.device_endpoint("pixel").unwrap();
+        assert_eq!(hosts(&state), [HOME]);
+        assert_eq!(device.endpoints[0].last_success, Some(100));
+
+        assert!(state.remove_device_endpoint("uuid-1", HOME).unwrap_err().contains("only endpoint"));
+        state.remember_endpoint("pixel", USB, "t1", Some("uuid-1".into()), None);
+        let device = state.remove_device_endpoint("uuid-1", HOME).unwrap();
+        assert_eq!((device.host.as_str(), device.endpoints.len()), (USB, 1));
+        assert!(state.remove_device_endpoint("pixel", "10.0.0.1:1").is_err());
+        assert_eq!(state.list_devices()[0].endpoints.len(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        assert_eq!(backoff_secs(1), RETRY_BASE_SECS);
+        assert_eq!(backoff_secs(2), RETRY_BASE_SECS * 2);
+        assert_eq!(backoff_secs(100), RETRY_MAX_SECS);
+    }
+}
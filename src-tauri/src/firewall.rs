@@ -0,0 +1,253 @@
+//! Windows 防火墙放行助手：局域网自检失败（BindErrorKind::LikelyFirewall）后，配对界面可以一键添加入站规则。
+//! 规则只针对本程序的可执行文件与指定 TCP 端口，且只作用于专用网络配置文件；不会创建放行全部程序或全部端口的规则。
+//! 添加规则需要管理员权限：通过 PowerShell 的 Start-Process -Verb RunAs 弹出 UAC 提升 netsh，
+//! 用户拒绝提升（ERROR_CANCELLED）与其他失败分开报告。已有匹配规则时不再弹出提升。
+//! 检查规则用 Get-NetFirewall* 系列 cmdlet（输出不随系统语言变化），只看指向本程序、已启用的入站放行规则。
+//! 其他平台返回 NotSupported 错误并附带手动放行的提示。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 规则名最长字符数
+pub const MAX_RULE_NAME_CHARS: usize = 64;
+/// 用户在 UAC 对话框中选择“否”时的退出码（ERROR_CANCELLED）
+const ELEVATION_DECLINED_CODE: i32 = 1223;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallOutcome {
+    Created,
+    /// 已有匹配规则，未做修改
+    AlreadyExists,
+    /// 用户拒绝了管理员权限提升
+    ElevationDeclined,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FirewallRuleResult {
+    pub port: u16,
+    pub rule_name: String,
+    pub outcome: FirewallOutcome,
+    /// 失败时的说明
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FirewallRuleStatus {
+    pub port: u16,
+    pub exists: bool,
+    /// 匹配的规则（显示名）
+    pub rules: Vec<String>,
+}
+
+fn not_supported(port: u16) -> String {
+    format!("NotSupported: 仅 Windows 支持自动添加防火墙规则，请在系统防火墙设置中手动放行 TCP 端口 {}", port)
+}
+
+fn validate_port(port: u16) -> Result<(), String> {
+    if port == 0 {
+        return Err("port must not be 0".to_string());
+    }
+    Ok(())
+}
+
+/// 规则名：默认带端口号；只允许字母数字、空格与少量标点，避免拼进命令行时被解释
+pub fn rule_name(name: Option<&str>, port: u16) -> Result<String, String> {
+    let name = match name.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(n) => n.to_string(),
+        None => return Ok(format!("Notification Listener (TCP {})", port)),
+    };
+    if let Some(c) = name.chars().find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.' | '(' | ')'))) {
+        return Err(format!("rule name must not contain {:?}", c));
+    }
+    if name.chars().count() > MAX_RULE_NAME_CHARS {
+        return Err(format!("rule name is longer than {} characters", MAX_RULE_NAME_CHARS));
+    }
+    Ok(name)
+}
+
+/// netsh 添加规则的参数：入站、放行、TCP、指定端口、指定程序、仅专用网络
+pub fn add_rule_args(name: &str, program: &str, port: u16) -> Vec<String> {
+    vec![
+        "advfirewall".into(),
+        "firewall".into(),
+        "add".into(),
+        "rule".into(),
+        format!("name=\"{}\"", name),
+        "dir=in".into(),
+        "action=allow".into(),
+        "protocol=TCP".into(),
+        format!("localport={}", port),
+        format!("program=\"{}\"", program),
+        "profile=private".into(),
+        "enable=yes".into(),
+    ]
+}
+
+/// PowerShell 单引号字符串
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// 以管理员身份运行 netsh 的脚本；拒绝提升时以 ELEVATION_DECLINED_CODE 退出，否则透传 netsh 的退出码
+pub fn elevated_script(netsh_args: &[String]) -> String {
+    format!(
+        "try {{ $p = Start-Process -FilePath 'netsh.exe' -ArgumentList {} -Verb RunAs -WindowStyle Hidden -Wait -PassThru; exit $p.ExitCode }} \
+         catch {{ $e = $_.Exception; while ($e) {{ if ($e.NativeErrorCode -eq {code}) {{ exit {code} }}; $e = $e.InnerException }}; exit 1 }}",
+        ps_quote(&netsh_args.join(" ")),
+        code = ELEVATION_DECLINED_CODE,
+    )
+}
+
+/// 列出指向本程序、已启用的入站放行规则，每行 “显示名|本地端口|协议|配置文件”
+pub fn list_rules_script(program: &str) -> String {
+    format!(
+        "Get-NetFirewallApplicationFilter -Program {} -ErrorAction SilentlyContinue | Get-NetFirewallRule | \
+         Where-Object {{ $_.Direction -eq 'Inbound' -and $_.Action -eq 'Allow' -and $_.Enabled -eq 'True' }} | \
+         ForEach-Object {{ $f = $_ | Get-NetFirewallPortFilter; '{{0}}|{{1}}|{{2}}|{{3}}' -f $_.DisplayName, ($f.LocalPort -join ','), $f.Protocol, $_.Profile }}",
+        ps_quote(program),
+    )
+}
+
+/// 从 list_rules_script 的输出中挑出放行该 TCP 端口、且对专用网络生效的规则
+pub fn parse_matching_rules(output: &str, port: u16) -> Vec<String> {
+    let port = port.to_string();
+    output
+        .lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.trim().split('|').collect();
+            let [name, ports, protocol, profile] = cols[..] else { return None };
+            let port_ok = ports.split(',').any(|p| p.trim() == port || p.trim().eq_ignore_ascii_case("any"));
+            let protocol_ok = protocol.eq_ignore_ascii_case("tcp") || protocol.eq_ignore_ascii_case("any");
+            let profile_ok = profile.split(',').any(|p| matches!(p.trim().to_ascii_lowercase().as_str(), "private" | "any"));
+            (port_ok && protocol_ok && profile_ok).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// 由提升脚本的退出码判断结果
+pub fn classify_exit(code: Option<i32>) -> FirewallOutcome {
+    match code {
+        Some(0) => FirewallOutcome::Created,
+        Some(ELEVATION_DECLINED_CODE) => FirewallOutcome::ElevationDeclined,
+        _ => FirewallOutcome::Failed,
+    }
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn current_program() -> Result<String, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    let program = exe.to_string_lossy().into_owned();
+    // 路径会拼进 netsh 与 PowerShell 的参数，含引号时拒绝
+    if program.contains('"') || program.contains('\'') {
+        return Err(format!("Unsupported executable path {}", program));
+    }
+    Ok(program)
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn run_powershell(script: &str) -> Result<std::process::Output, String> {
+    std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-ExecutionPolicy", "Bypass", "-Command", script])
+        .output()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))
+}
+
+/// 查询是否已有放行本程序该端口的规则
+pub fn check_rule(port: u16) -> Result<FirewallRuleStatus, String> {
+    validate_port(port)?;
+    if !cfg!(windows) {
+        return Err(not_supported(port));
+    }
+    let out = run_powershell(&list_rules_script(&current_program()?))?;
+    if !out.status.success() {
+        return Err(format!("Failed to query firewall rules: {}", String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    let rules = parse_matching_rules(&String::from_utf8_lossy(&out.stdout), port);
+    Ok(FirewallRuleStatus { port, exists: !rules.is_empty(), rules })
+}
+
+/// 添加入站放行规则（会弹出 UAC）；已有匹配规则时直接返回 AlreadyExists
+pub fn create_rule(port: u16, name: Option<&str>) -> Result<FirewallRuleResult, String> {
+    validate_port(port)?;
+    let rule_name = rule_name(name, port)?;
+    if !cfg!(windows) {
+        return Err(not_supported(port));
+    }
+    let existing = check_rule(port)?;
+    if let Some(found) = existing.rules.into_iter().next() {
+        return Ok(FirewallRuleResult { port, rule_name: found, outcome: FirewallOutcome::AlreadyExists, message: None });
+    }
+    let program = current_program()?;
+    let out = run_powershell(&elevated_script(&add_rule_args(&rule_name, &program, port)))?;
+    let outcome = classify_exit(out.status.code());
+    let message = match outcome {
+        FirewallOutcome::Failed => Some(format!("netsh exited with {}", out.status)),
+        _ => None,
+    };
+    println!("[Firewall] Rule {:?} for port {} -> {:?}", rule_name, port, outcome);
+    Ok(FirewallRuleResult { port, rule_name, outcome, message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXE: &str = r"C:\Program Files\Notification Listener\app.exe";
+
+    #[test]
+    fn test_rule_is_scoped_to_program_port_and_private_profile() {
+        let args = add_rule_args("Notification Listener (TCP 10035)", EXE, 10035);
+        assert!(args.contains(&"dir=in".to_string()));
+        assert!(args.contains(&"localport=10035".to_string()));
+        assert!(args.contains(&"profile=private".to_string()));
+        assert!(args.contains(&format!("program=\"{}\"", EXE)));
+        assert!(!args.iter().any(|a| a.contains("any") || a.contains("public")));
+
+        let script = elevated_script(&args);
+        assert!(script.contains("-Verb RunAs"));
+        assert!(script.contains("exit 1223"));
+        assert_eq!(ps_quote("it's"), "'it''s'");
+    }
+
+    #[test]
+    fn test_rule_names() {
+        assert_eq!(rule_name(None, 10035).unwrap(), "Notification Listener (TCP 10035)");
+        assert_eq!(rule_name(Some("  "), 80).unwrap(), "Notification Listener (TCP 80)");
+        assert_eq!(rule_name(Some("通知接收 (配对)"), 1).unwrap(), "通知接收 (配对)");
+        for bad in ["a\"b", "x' ; rm", "a&b", "$env"] {
+            assert!(rule_name(Some(bad), 1).is_err(), "{}", bad);
+        }
+        assert!(rule_name(Some(&"a".repeat(MAX_RULE_NAME_CHARS + 1)), 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_matching_rules() {
+        let output = "Listener (TCP 10035)|10035|TCP|Private\r\n\
+                      Old rule|8080|TCP|Private\r\n\
+                      Public only|10035|TCP|Public\r\n\
+                      Udp|10035|UDP|Any\r\n\
+                      Wide|Any|Any|Domain, Private\r\n\
+                      broken line\r\n";
+        assert_eq!(parse_matching_rules(output, 10035), ["Listener (TCP 10035)", "Wide"]);
+        assert_eq!(parse_matching_rules(output, 8080), ["Old rule", "Wide"]);
+        assert!(parse_matching_rules("", 10035).is_empty());
+    }
+
+    #[test]
+    fn test_outcomes() {
+        assert_eq!(classify_exit(Some(0)), FirewallOutcome::Created);
+        assert_eq!(classify_exit(Some(ELEVATION_DECLINED_CODE)), FirewallOutcome::ElevationDeclined);
+        assert_eq!(classify_exit(Some(1)), FirewallOutcome::Failed);
+        assert_eq!(classify_exit(None), FirewallOutcome::Failed);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_not_supported_elsewhere() {
+        assert!(check_rule(10035).unwrap_err().starts_with("NotSupported"));
+        assert!(create_rule(10035, None).unwrap_err().starts_with("NotSupported"));
+        assert!(create_rule(0, None).unwrap_err().contains("port"));
+    }
+}
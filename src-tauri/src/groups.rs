@@ -0,0 +1,143 @@
+//! 按安卓通知分组（group_key）归并列表：聊天应用每条消息一条通知，另加一条汇总通知，平铺时难以阅读。
+//! 分组只在同一应用内有效；没有 group_key 的通知各自成为只有一项的分组，前端按统一结构渲染。
+//! 分组按组内最新的时间排序（新的在前），分页作用于分组；过滤条件与 list_notifications 相同。
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{AppState, ListOptions};
+use crate::store::sort_ts;
+use crate::types::Notification;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NotificationGroup {
+    /// 未分组的通知为空
+    pub group_key: Option<String>,
+    pub package_name: Option<String>,
+    /// 汇总通知（可能已被移除，或不在过滤结果中）
+    pub summary: Option<Notification>,
+    /// 组内通知（新 -> 旧，不含汇总）
+    pub children: Vec<Notification>,
+    /// 组内（含汇总）最新的时间戳，分组按此排序
+    pub latest_at: i64,
+}
+
+impl NotificationGroup {
+    fn single(n: Notification) -> Self {
+        Self { group_key: None, package_name: n.package_name.clone(), summary: None, children: vec![n], latest_at: 0 }
+    }
+}
+
+/// 归并为分组并按最新时间排序；同一时间保持输入顺序
+pub fn group_notifications(list: Vec<Notification>) -> Vec<NotificationGroup> {
+    let mut groups: Vec<NotificationGroup> = Vec::new();
+    let mut index: HashMap<(Option<String>, String), usize> = HashMap::new();
+    for n in list {
+        let Some(key) = n.group_key.clone() else {
+            groups.push(NotificationGroup::single(n));
+            continue;
+        };
+        let i = *index.entry((n.package_name.clone(), key.clone())).or_insert_with(|| {
+            groups.push(NotificationGroup {
+                group_key: Some(key),
+                package_name: n.package_name.clone(),
+                summary: None,
+                children: Vec::new(),
+                latest_at: 0,
+            });
+            groups.len() - 1
+        });
+        let group = &mut groups[i];
+        // 重复的汇总（理论上不会出现）按普通通知处理
+        if n.is_group_summary && group.summary.is_none() {
+            group.summary = Some(n);
+        } else {
+            group.children.push(n);
+        }
+    }
+    for group in groups.iter_mut() {
+        group.children.sort_by(|a, b| sort_ts(b).cmp(&sort_ts(a)).then_with(|| b.id.cmp(&a.id)));
+        group.latest_at = group.summary.iter().chain(group.children.iter()).map(sort_ts).max().unwrap_or_default();
+    }
+    groups.sort_by(|a, b| b.latest_at.cmp(&a.latest_at));
+    groups
+}
+
+impl AppState {
+    pub fn list_notification_groups(&self, mut options: ListOptions) -> Result<Vec<NotificationGroup>, String> {
+        let (offset, limit) = (options.offset, options.limit);
+        options.offset = 0;
+        options.limit = None;
+        let groups = group_notifications(self.list_notifications(options)?);
+        println!("[cmd] list_notification_groups -> {} groups", groups.len());
+        Ok(groups.into_iter().skip(offset).take(limit.unwrap_or(usize::MAX)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::startup::Subsystem;
+    use crate::types::Event;
+
+    fn chat(id: &str, package: &str, group: Option<&str>, summary: bool, ts: i64) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some(package.into()),
+                title: Some(format!("title {}", id)),
+                posted_at: Some(ts),
+                group_key: group.map(str::to_string),
+                is_group_summary: summary,
+                ..Default::default()
+            }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_groups_nest_children_and_mark_read_expands_summary() {
+        let state = AppState::default();
+        state.mark_ready(Subsystem::Store);
+        state.ingest_event(chat("m1", "com.chat", Some("family"), false, 100));
+        state.ingest_event(chat("m2", "com.chat", Some("family"), false, 300));
+        state.ingest_event(chat("sum", "com.chat", Some("family"), true, 300));
+        state.ingest_event(chat("o1", "com.other", Some("family"), false, 200));
+        state.ingest_event(chat("solo", "com.mail", None, false, 400));
+
+        let groups = state.list_notification_groups(ListOptions::default()).unwrap();
+        let shape: Vec<(Option<&str>, Option<&str>, Vec<&str>, i64)> = groups
+            .iter()
+            .map(|g| {
+                (
+                    g.package_name.as_deref(),
+                    g.summary.as_ref().map(|n| n.id.as_str()),
+                    g.children.iter().map(|n| n.id.as_str()).collect(),
+                    g.latest_at,
+                )
+            })
+            .collect();
+        // 同名 group_key 不跨应用合并；未分组的通知单独成组
+        assert_eq!(
+            shape,
+            [
+                (Some("com.mail"), None, vec!["solo"], 400),
+                (Some("com.chat"), Some("sum"), vec!["m2", "m1"], 300),
+                (Some("com.other"), None, vec!["o1"], 200),
+            ]
+        );
+        let page = state.list_notification_groups(ListOptions { offset: 1, limit: Some(1), ..Default::default() }).unwrap();
+        assert_eq!(page[0].summary.as_ref().unwrap().id, "sum");
+
+        // 标记汇总为已读：整组已读，其他应用的同名分组不受影响
+        assert_eq!(state.mark_read_ids(&["sum".to_string()]).unwrap(), 3);
+        let store = state.store.lock().unwrap();
+        assert!(["m1", "m2", "sum"].iter().all(|id| store.get(id).unwrap().read));
+        assert!(!store.get("o1").unwrap().read);
+        assert_eq!(store.counts().unread, 2);
+    }
+}
@@ -0,0 +1,461 @@
+//! 重启时的快速恢复：无法把连接本身交给新进程，但可以交接协商结果。
+//! 正常退出时为每个直连设备记下最后收到的 seq、地址、协议版本与能力、时钟偏差，加密后写入数据目录下的
+//! handoff.bin（仅所有者可读写；Windows 上数据目录本身只对当前用户开放）。
+//! 启动时读取并立即删除（只用一次）；超过 HANDOFF_MAX_AGE_SECS 的视为过期。
+//! 重连时若有对应记录（且 token 未在此期间轮换，见 take_handoff），跳过版本查询与时钟预热，
+//! 直接恢复偏差并只请求 since_seq 之后的增量；否则走冷启动：查询版本、ping 若干次估算偏差、全量同步。
+//! 密钥由 device_uuid 与机器 id 派生，不随文件保存；加密用 SHA-256 计数器模式生成密钥流，附带校验值，
+//! 解不开（换了机器、文件损坏或被改动）时按冷启动处理。
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::android_client::AndroidSocketClient;
+use crate::commands::AppState;
+use crate::identity;
+
+pub const HANDOFF_FILE: &str = "handoff.bin";
+/// 超过该时长的记录不再使用
+pub const HANDOFF_MAX_AGE_SECS: i64 = 5 * 60;
+/// 冷启动时估算时钟偏差的 ping 次数
+pub const CLOCK_WARMUP_PINGS: usize = 3;
+const BLOB_VERSION: u8 = 1;
+const NONCE_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+
+/// 单个设备的交接信息（token 只保存指纹）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceHandoff {
+    pub connection_id: String,
+    pub host: String,
+    pub token_hash: String,
+    pub protocol_version: Option<u32>,
+    pub capabilities: Option<Vec<String>>,
+    /// 最后收到的手机端 seq（手机端未编号时为 None）
+    pub last_seq: Option<i64>,
+    pub clock_offset_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HandoffBlob {
+    written_at: i64,
+    devices: Vec<DeviceHandoff>,
+}
+
+/// 启动时读到的交接信息，重连时逐个取走
+#[derive(Default)]
+pub struct Handoff {
+    devices: Mutex<HashMap<String, DeviceHandoff>>,
+}
+
+/// 重连后的同步方式（device-resumed 事件）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeReport {
+    pub connection_id: String,
+    /// 使用了交接信息
+    pub warm: bool,
+    /// 增量同步的起点；None 为全量
+    pub since_seq: Option<i64>,
+    pub clock_offset_ms: Option<i64>,
+    /// 从开始连接到发出同步请求的耗时
+    pub elapsed_ms: u64,
+}
+
+fn keystream_xor(key: &[u8; 32], nonce: &[u8], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(32).enumerate() {
+        let block = Sha256::new().chain_update(key).chain_update(nonce).chain_update((i as u64).to_le_bytes()).finalize();
+        for (b, k) in chunk.iter_mut().zip(block) {
+            *b ^= k;
+        }
+    }
+}
+
+fn tag(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+    let inner = Sha256::new().chain_update(key).chain_update(nonce).chain_update(ciphertext).finalize();
+    Sha256::new().chain_update(key).chain_update(inner).finalize().into()
+}
+
+/// 版本 | nonce | 校验值 | 密文
+pub fn seal(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let nonce = *uuid::Uuid::new_v4().as_bytes();
+    let mut ciphertext = plaintext.to_vec();
+    keystream_xor(key, &nonce, &mut ciphertext);
+    let mut out = Vec::with_capacity(1 + NONCE_LEN + TAG_LEN + ciphertext.len());
+    out.push(BLOB_VERSION);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&tag(key, &nonce, &ciphertext));
+    out.extend_from_slice(&ciphertext);
+    out
+}
+
+/// 校验失败（密钥不同、损坏或被改动）时返回 None
+pub fn open(key: &[u8; 32], data: &[u8]) -> Option<Vec<u8>> {
+    let (&version, rest) = data.split_first()?;
+    if version != BLOB_VERSION || rest.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+    let (nonce, rest) = rest.split_at(NONCE_LEN);
+    let (expected, ciphertext) = rest.split_at(TAG_LEN);
+    if tag(key, nonce, ciphertext) != expected {
+        return None;
+    }
+    let mut plaintext = ciphertext.to_vec();
+    keystream_xor(key, nonce, &mut plaintext);
+    Some(plaintext)
+}
+
+/// 原子写入且仅所有者可读写
+fn write_private(path: &Path, data: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    // 权限只在创建时生效
+    let _ = std::fs::remove_file(&tmp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp).map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+    file.write_all(data).and_then(|_| file.sync_all()).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to rename {}: {}", tmp.display(), e))
+}
+
+impl AppState {
+    /// 由 device_uuid 与机器 id 派生；没有配置目录（如单元测试未设置）时不做交接
+    fn handoff_key(&self) -> Option<[u8; 32]> {
+        let uuid = identity::load_or_create_uuid(self.identity.dir()?).ok()?;
+        let machine_id = identity::read_machine_id().unwrap_or_default();
+        Some(Sha256::new().chain_update(b"handoff:").chain_update(uuid).chain_update(b":").chain_update(machine_id).finalize().into())
+    }
+
+    /// 正常退出时调用：记录当前直连设备的协商结果，返回记录的设备数
+    pub fn write_handoff(&self, now: i64) -> usize {
+        let Some(path) = self.storage.base_dir().map(|d| d.join(HANDOFF_FILE)) else {
+            return 0;
+        };
+        let clients: Vec<(String, std::sync::Arc<AndroidSocketClient>)> =
+            self.clients.read().iter().map(|(id, c)| (id.clone(), c.clone())).collect();
+        let devices: Vec<DeviceHandoff> = clients
+            .into_iter()
+            .filter(|(_, c)| c.is_authenticated())
+            .filter_map(|(id, client)| {
+                let endpoint = self.device_endpoint(&id)?;
+                Some(DeviceHandoff {
+                    host: endpoint.host,
+                    token_hash: identity::hash_id(endpoint.token.as_deref()?),
+                    protocol_version: client.protocol_version(),
+                    capabilities: client.capabilities(),
+                    last_seq: self.link_quality.last_seq(&id),
+                    clock_offset_ms: self.clock_offsets.offset_ms(&id),
+                    connection_id: id,
+                })
+            })
+            .collect();
+        if devices.is_empty() {
+            let _ = std::fs::remove_file(&path);
+            return 0;
+        }
+        let Some(key) = self.handoff_key() else {
+            return 0;
+        };
+        let count = devices.len();
+        let result = serde_json::to_vec(&HandoffBlob { written_at: now, devices })
+            .map_err(|e| e.to_string())
+            .and_then(|json| write_private(&path, &seal(&key, &json)));
+        match result {
+            Ok(()) => {
+                println!("[Handoff] Saved state for {} devices", count);
+                count
+            }
+            Err(e) => {
+                println!("[Handoff] {}", e);
+                0
+            }
+        }
+    }
+
+    /// 启动时读取交接信息（读后即删除），返回可用的设备数
+    pub fn load_handoff(&self, now: i64) -> usize {
+        let Some(path) = self.storage.base_dir().map(|d| d.join(HANDOFF_FILE)) else {
+            return 0;
+        };
+        let Ok(data) = std::fs::read(&path) else {
+            return 0;
+        };
+        let _ = std::fs::remove_file(&path);
+        let blob = self
+            .handoff_key()
+            .and_then(|key| open(&key, &data))
+            .and_then(|json| serde_json::from_slice::<HandoffBlob>(&json).ok());
+        let Some(blob) = blob else {
+            println!("[Handoff] Ignoring unreadable handoff file");
+            return 0;
+        };
+        let age = now - blob.written_at;
+        if !(0..=HANDOFF_MAX_AGE_SECS).contains(&age) {
+            println!("[Handoff] Ignoring handoff written {}s ago", age);
+            return 0;
+        }
+        let mut devices = self.handoff.devices.lock();
+        devices.clear();
+        devices.extend(blob.devices.into_iter().map(|d| (d.connection_id.clone(), d)));
+        println!("[Handoff] Loaded state for {} devices", devices.len());
+        devices.len()
+    }
+
+    /// 取走某个设备的交接信息；token 与记录时不同（期间轮换过）则作废
+    pub(crate) fn take_handoff(&self, connection_id: &str, token: Option<&str>) -> Option<DeviceHandoff> {
+        let handoff = self.handoff.devices.lock().remove(connection_id)?;
+        if token.map(identity::hash_id).as_deref() != Some(handoff.token_hash.as_str()) {
+            println!("[Handoff] Token for {} was rotated, doing a full sync", connection_id);
+            return None;
+        }
+        Some(handoff)
+    }
+
+    /// 手动重连：用记录的地址与 token 重连并同步；登录失败时交接信息一并作废
+    pub fn reconnect_device(&self, connection_id: &str) -> Result<(String, ResumeReport), String> {
+        let tracer = self.tracer.clone();
+        self.reconnect_device_with(connection_id, false, |host| AndroidSocketClient::connect(host, connection_id.to_string(), tracer.clone()))
+    }
+
+    /// 自动重连（维护任务）：跳过退避中的地址
+    pub(crate) fn auto_reconnect_device(&self, connection_id: &str) -> Result<(String, ResumeReport), String> {
+        let tracer = self.tracer.clone();
+        self.reconnect_device_with(connection_id, true, |host| AndroidSocketClient::connect(host, connection_id.to_string(), tracer.clone()))
+    }
+
+    /// 按已知地址依次连接（见 failover），连上后登录并同步
+    pub(crate) fn reconnect_device_with<F>(&self, connection_id: &str, automatic: bool, connect: F) -> Result<(String, ResumeReport), String>
+    where
+        F: FnMut(&str) -> Result<AndroidSocketClient, String>,
+    {
+        let started = Instant::now();
+        let _in_flight = self.failover.enter(connection_id)?;
+        let endpoint = self
+            .device_endpoint(connection_id)
+            .ok_or_else(|| format!("No saved endpoint for {}", connection_id))?;
+        if endpoint.needs_reauth {
+            return Err(format!("{} needs to be paired again (device identity changed)", connection_id));
+        }
+        let handoff = self.take_handoff(connection_id, endpoint.token.as_deref());
+        let (client, host) = self.connect_failover(&endpoint, automatic, chrono::Utc::now().timestamp(), connect)?;
+        let token = crate::commands::authorize_and_register(self, connection_id.to_string(), client, endpoint.token, Some(&host))?;
+        let client = self.clients.read().get(connection_id).cloned().ok_or("Connection was closed")?;
+        let report = self.resync_device(connection_id, &client, handoff, started);
+        self.events.emit("device-resumed", &report);
+        Ok((token, report))
+    }
+
+    fn resync_device(&self, connection_id: &str, client: &AndroidSocketClient, handoff: Option<DeviceHandoff>, started: Instant) -> ResumeReport {
+        let warm = handoff.is_some();
+        let (since_seq, clock_offset_ms) = match handoff {
+            Some(h) => {
+                if let Some(offset) = h.clock_offset_ms {
+                    self.clock_offsets.restore(connection_id, offset);
+                }
+                (h.last_seq, h.clock_offset_ms)
+            }
+            None => {
+                if let Err(e) = client.query_version() {
+                    println!("[Handoff] Version query for {} failed: {}", connection_id, e);
+                }
+                let mut offset = None;
+                if crate::clock::supports_clock_sync(client) {
+                    for _ in 0..CLOCK_WARMUP_PINGS {
+                        match client.ping() {
+                            Ok(sample) => offset = self.clock_offsets.record(connection_id, sample),
+                            Err(e) => {
+                                println!("[Handoff] Ping {} failed: {}", connection_id, e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                (None, offset)
+            }
+        };
+        let mut request = serde_json::json!({ "action": "sync" });
+        if let Some(seq) = since_seq {
+            request["since_seq"] = seq.into();
+        }
+        if let Some(horizon) = self.sync_horizon(connection_id) {
+            request["horizon"] = horizon.into();
+        }
+        if let Err(e) = client.send_action(&request) {
+            println!("[Handoff] Sync request for {} failed: {}", connection_id, e);
+        }
+        let report = ResumeReport {
+            connection_id: connection_id.to_string(),
+            warm,
+            since_seq,
+            clock_offset_ms,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        };
+        println!("[Handoff] {} resumed (warm={}) in {}ms", connection_id, warm, report.elapsed_ms);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::types::Event;
+
+    /// 每个请求都要 LATENCY 才回复的手机端；记录收到的 sync 请求
+    fn mock_phone(connections: usize) -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+        const LATENCY: Duration = Duration::from_millis(40);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let syncs: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+        let recorded = syncs.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                let reader = BufReader::new(stream.try_clone().unwrap());
+                for line in reader.lines().map_while(Result::ok) {
+                    let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                    let reply = match request["action"].as_str() {
+                        Some("login") => serde_json::json!({
+                            "success": true, "protocolVersion": 2, "deviceUuid": "phone-uuid", "capabilities": ["actions"]
+                        }),
+                        Some("version") => serde_json::json!({ "protocolVersion": 2, "capabilities": ["actions"] }),
+                        Some("ping") => {
+                            let t0 = request["t0"].as_i64().unwrap();
+                            serde_json::json!({ "t1": t0 + 5_000, "t2": t0 + 5_000 })
+                        }
+                        _ => {
+                            recorded.lock().push(request);
+                            continue;
+                        }
+                    };
+                    std::thread::sleep(LATENCY);
+                    if writeln!(stream, "{}", reply).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        (addr, syncs)
+    }
+
+    fn state_with_dirs(name: &str) -> (AppState, std::path::PathBuf) {
+        let dir = crate::storage::temp_dir(name);
+        let state = AppState::default();
+        state.storage.set_base_dir(dir.clone());
+        state.identity.set_dir(dir.clone());
+        (state, dir)
+    }
+
+    #[test]
+    fn test_seal_round_trip_and_tamper() {
+        let key = [7u8; 32];
+        let sealed = seal(&key, b"{\"written_at\":1}");
+        assert_eq!(open(&key, &sealed).as_deref(), Some(&b"{\"written_at\":1}"[..]));
+        assert!(!sealed.windows(10).any(|w| w == b"written_at"), "plaintext must not leak");
+        assert_eq!(open(&[8u8; 32], &sealed), None);
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(open(&key, &tampered), None);
+        assert_eq!(open(&key, &sealed[..10]), None);
+    }
+
+    #[test]
+    fn test_restart_with_handoff_is_faster_than_cold() {
+        let (addr, syncs) = mock_phone(2);
+        let (state, dir) = state_with_dirs("handoff");
+        state.remember_endpoint("pixel", &addr, "secret-token", Some("phone-uuid".into()), None);
+
+        // 冷启动：版本查询 + 时钟预热 + 全量同步
+        let (_, cold) = state.reconnect_device("pixel").unwrap();
+        assert!(!cold.warm && cold.since_seq.is_none());
+        let offset = cold.clock_offset_ms.unwrap();
+        assert!(offset.abs_diff(5_000) < 100, "{}", offset);
+        for seq in 1..=42 {
+            state.ingest_from("pixel", Event { event_type: "removed".into(), seq, notification: None, id: Some(seq.to_string()) });
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(state.write_handoff(now), 1);
+        let path = dir.join(HANDOFF_FILE);
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("pixel"), "blob is encrypted");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // 重启：旧进程的连接断开，新进程读取交接信息
+        let endpoint = state.device_endpoint("pixel").unwrap();
+        drop(state);
+        let restarted = AppState::default();
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.identity.set_dir(dir.clone());
+        restarted.save_endpoint(endpoint);
+        assert_eq!(restarted.load_handoff(now + 5), 1);
+        assert!(!path.exists(), "handoff is single-use");
+        let (_, warm) = restarted.reconnect_device("pixel").unwrap();
+        assert!(warm.warm);
+        assert_eq!((warm.since_seq, warm.clock_offset_ms), (Some(42), Some(offset)));
+        assert_eq!(restarted.clock_offsets.offset_ms("pixel"), Some(offset));
+        assert!(
+            warm.elapsed_ms * 2 < cold.elapsed_ms,
+            "warm restart {}ms should be well under cold {}ms",
+            warm.elapsed_ms,
+            cold.elapsed_ms
+        );
+
+        std::thread::sleep(Duration::from_millis(100));
+        let syncs = syncs.lock().clone();
+        assert_eq!(syncs.len(), 2);
+        assert!(syncs[0].get("since_seq").is_none());
+        assert_eq!(syncs[1]["since_seq"], 42);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_stale_or_rotated_handoff_is_ignored() {
+        let (state, dir) = state_with_dirs("handoff-stale");
+        let key = state.handoff_key().unwrap();
+        let device = DeviceHandoff {
+            connection_id: "pixel".into(),
+            host: "127.0.0.1:1".into(),
+            token_hash: identity::hash_id("old-token"),
+            protocol_version: Some(2),
+            capabilities: None,
+            last_seq: Some(7),
+            clock_offset_ms: None,
+        };
+        let write = |written_at: i64| {
+            let json = serde_json::to_vec(&HandoffBlob { written_at, devices: vec![device.clone()] }).unwrap();
+            write_private(&dir.join(HANDOFF_FILE), &seal(&key, &json)).unwrap();
+        };
+
+        write(1_000);
+        assert_eq!(state.load_handoff(1_000 + HANDOFF_MAX_AGE_SECS + 1), 0);
+        write(1_000);
+        assert_eq!(state.load_handoff(1_000 - 60), 0, "written in the future");
+
+        write(1_000);
+        assert_eq!(state.load_handoff(1_030), 1);
+        assert_eq!(state.take_handoff("pixel", Some("rotated-token")), None);
+        assert_eq!(state.take_handoff("pixel", Some("old-token")), None, "already consumed");
+        write(1_000);
+        state.load_handoff(1_030);
+        assert_eq!(state.take_handoff("pixel", Some("old-token")).unwrap().last_seq, Some(7));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
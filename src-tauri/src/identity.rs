@@ -0,0 +1,257 @@
+//! 本机身份：device_uuid（配置目录下的 device_uuid.txt）+ 操作系统提供的机器 id。
+//! 整机镜像或在两台电脑间同步配置目录后，两台机器会共用同一个 device_uuid，手机端会把它们当成同一台设备。
+//! 启动时读取机器 id（Windows MachineGuid / Linux machine-id / macOS IOPlatformUUID），与保存的值比对：
+//! 不一致说明配置来自另一台机器，重新生成 device_uuid、推送 identity-regenerated，
+//! 并把已保存的配对标记为需要重新认证。机器 id 只保存哈希；读不到机器 id 时保持原有行为。
+//! 身份文件有意留在配置目录而不是数据目录：数据目录可以放在网络共享或移动磁盘上、由多台机器先后使用（见 data_lock），
+//! 身份必须属于本机；放进数据目录会让共用数据目录的机器来回重新生成 device_uuid。读取一律经 AppState::device_uuid。
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::commands::AppState;
+use crate::storage::write_atomic;
+
+const UUID_FILE: &str = "device_uuid.txt";
+const IDENTITY_FILE: &str = "device_identity.json";
+
+/// 与 device_uuid 一起保存的机器 id（哈希）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IdentityRecord {
+    machine_id_hash: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityStatus {
+    /// 尚未检查
+    #[default]
+    Unknown,
+    /// 首次记录机器 id（沿用已有的 device_uuid）
+    FirstSeen,
+    Unchanged,
+    /// 机器 id 与保存的不一致，已重新生成 device_uuid
+    Regenerated,
+    /// 读不到机器 id
+    Unavailable,
+}
+
+/// 诊断信息（只含哈希）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct IdentityInfo {
+    pub status: IdentityStatus,
+    pub device_uuid_hash: Option<String>,
+    pub machine_id_hash: Option<String>,
+    pub checked_at: Option<i64>,
+}
+
+#[derive(Default)]
+pub struct Identity {
+    dir: OnceLock<PathBuf>,
+    info: parking_lot::Mutex<IdentityInfo>,
+}
+
+impl Identity {
+    /// setup 阶段设置配置目录；未设置时（如单元测试）不做检查
+    pub fn set_dir(&self, dir: PathBuf) {
+        let _ = self.dir.set(dir);
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.get().map(PathBuf::as_path)
+    }
+
+    pub fn info(&self) -> IdentityInfo {
+        self.info.lock().clone()
+    }
+}
+
+pub fn hash_id(id: &str) -> String {
+    let digest = Sha256::digest(format!("notification-listener:{}", id).as_bytes());
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn normalize(id: &str) -> Option<String> {
+    let id = id.trim().trim_matches('"').to_lowercase();
+    (!id.is_empty() && id.chars().any(|c| c != '0' && c != '-')).then_some(id)
+}
+
+/// `reg query ... /v MachineGuid` 的输出
+fn parse_reg_query(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| l.contains("MachineGuid"))
+        .and_then(|l| l.split_whitespace().last())
+        .and_then(normalize)
+}
+
+/// `ioreg -rd1 -c IOPlatformExpertDevice` 的输出
+fn parse_ioreg(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|l| l.contains("\"IOPlatformUUID\""))
+        .and_then(|l| l.split('=').nth(1))
+        .and_then(normalize)
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 读取操作系统提供的机器 id；读不到返回 None
+pub fn read_machine_id() -> Option<String> {
+    if cfg!(target_os = "windows") {
+        let out = command_output("reg", &["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid"])?;
+        parse_reg_query(&out)
+    } else if cfg!(target_os = "macos") {
+        parse_ioreg(&command_output("ioreg", &["-rd1", "-c", "IOPlatformExpertDevice"])?)
+    } else {
+        ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+            .iter()
+            .find_map(|p| std::fs::read_to_string(p).ok().as_deref().and_then(normalize))
+    }
+}
+
+fn write_uuid(dir: &Path) -> Result<String, String> {
+    let uuid = uuid::Uuid::new_v4().to_string();
+    write_atomic(&dir.join(UUID_FILE), uuid.as_bytes()).map_err(|e| format!("Failed to write UUID file: {}", e))?;
+    Ok(uuid)
+}
+
+/// 读取 device_uuid，不存在时生成
+pub fn load_or_create_uuid(dir: &Path) -> Result<String, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    let path = dir.join(UUID_FILE);
+    if path.exists() {
+        let uuid = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read UUID file: {}", e))?;
+        Ok(uuid.trim().to_string())
+    } else {
+        write_uuid(dir)
+    }
+}
+
+/// 比对机器 id，必要时重新生成 device_uuid；返回状态与当前 device_uuid
+pub fn check(dir: &Path, machine_id: Option<&str>) -> Result<(IdentityStatus, String), String> {
+    let uuid = load_or_create_uuid(dir)?;
+    let Some(machine_id) = machine_id else {
+        return Ok((IdentityStatus::Unavailable, uuid));
+    };
+    let record = IdentityRecord { machine_id_hash: hash_id(machine_id) };
+    let path = dir.join(IDENTITY_FILE);
+    let saved: Option<IdentityRecord> = std::fs::read(&path).ok().and_then(|b| serde_json::from_slice(&b).ok());
+    let (status, uuid) = match saved {
+        Some(saved) if saved == record => return Ok((IdentityStatus::Unchanged, uuid)),
+        Some(_) => (IdentityStatus::Regenerated, write_uuid(dir)?),
+        None => (IdentityStatus::FirstSeen, uuid),
+    };
+    let json = serde_json::to_vec_pretty(&record).map_err(|e| e.to_string())?;
+    write_atomic(&path, &json)?;
+    Ok((status, uuid))
+}
+
+impl AppState {
+    /// 本机 device_uuid（不存在时生成）；配置目录不可用时报错
+    pub fn device_uuid(&self) -> Result<String, String> {
+        let dir = self.identity.dir().ok_or("Config directory is not available")?;
+        load_or_create_uuid(dir)
+    }
+
+    /// 启动时检查本机身份；配置来自另一台机器时重新生成 device_uuid 并要求重新配对
+    pub fn verify_identity(&self) {
+        let Some(dir) = self.identity.dir.get() else {
+            return;
+        };
+        let machine_id = read_machine_id();
+        if machine_id.is_none() {
+            println!("[Identity] Machine id unavailable, keeping device_uuid as is");
+        }
+        match check(dir, machine_id.as_deref()) {
+            Ok((status, uuid)) => self.identity_checked(status, &uuid, machine_id.as_deref()),
+            Err(e) => println!("[Identity] {}", e),
+        }
+    }
+
+    fn identity_checked(&self, status: IdentityStatus, uuid: &str, machine_id: Option<&str>) {
+        let info = IdentityInfo {
+            status,
+            device_uuid_hash: Some(hash_id(uuid)),
+            machine_id_hash: machine_id.map(hash_id),
+            checked_at: Some(chrono::Utc::now().timestamp()),
+        };
+        *self.identity.info.lock() = info.clone();
+        if status != IdentityStatus::Regenerated {
+            return;
+        }
+        let marked = self.mark_endpoints_need_reauth();
+        println!("[Identity] Machine id changed, regenerated device_uuid ({} pairings need re-auth)", marked);
+        self.events.emit(
+            "identity-regenerated",
+            serde_json::json!({
+                "reason": "machine_id_changed",
+                "message": "This configuration was copied from another computer. A new device identity was created; pair your phone again.",
+                "pairings_need_reauth": marked,
+                "device_uuid_hash": info.device_uuid_hash,
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_platform_outputs() {
+        let reg = "\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography\r\n    MachineGuid    REG_SZ    8F3A1C2E-1111-2222-3333-444455556666\r\n";
+        assert_eq!(parse_reg_query(reg).as_deref(), Some("8f3a1c2e-1111-2222-3333-444455556666"));
+        let ioreg = "  | \"IOPlatformSerialNumber\" = \"C02X\"\n  | \"IOPlatformUUID\" = \"564D2E1A-AAAA-BBBB-CCCC-DDDDEEEEFFFF\"\n";
+        assert_eq!(parse_ioreg(ioreg).as_deref(), Some("564d2e1a-aaaa-bbbb-cccc-ddddeeeeffff"));
+        // 全零占位值视为不可用
+        assert_eq!(normalize("00000000-0000-0000-0000-000000000000"), None);
+    }
+
+    #[test]
+    fn test_cloned_config_regenerates_uuid() {
+        let dir = crate::storage::temp_dir("identity");
+        let (status, original) = check(&dir, Some("machine-a")).unwrap();
+        assert_eq!(status, IdentityStatus::FirstSeen);
+        assert_eq!(check(&dir, Some("machine-a")).unwrap(), (IdentityStatus::Unchanged, original.clone()));
+        assert_eq!(check(&dir, None).unwrap(), (IdentityStatus::Unavailable, original.clone()));
+
+        let (status, cloned) = check(&dir, Some("machine-b")).unwrap();
+        assert_eq!(status, IdentityStatus::Regenerated);
+        assert_ne!(cloned, original);
+        assert_eq!(load_or_create_uuid(&dir).unwrap(), cloned);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_device_uuid_uses_identity_dir() {
+        let state = AppState::default();
+        assert!(state.device_uuid().is_err());
+        let dir = crate::storage::temp_dir("identity");
+        state.identity.set_dir(dir.clone());
+        let uuid = state.device_uuid().unwrap();
+        assert_eq!(state.device_uuid().unwrap(), uuid);
+        assert_eq!(std::fs::read_to_string(dir.join(UUID_FILE)).unwrap(), uuid);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_regeneration_marks_pairings() {
+        let state = AppState::default();
+        state.remember_endpoint("pixel", "192.168.1.5:10035", "t1", None, None);
+        state.identity_checked(IdentityStatus::Regenerated, "new-uuid", Some("machine-b"));
+        assert!(state.device_endpoint("pixel").unwrap().needs_reauth);
+        let info = state.identity.info();
+        assert_eq!(info.device_uuid_hash.as_deref(), Some(hash_id("new-uuid").as_str()));
+        let events = state.events.take_captured();
+        assert_eq!(events[0].0, "identity-regenerated");
+        assert_eq!(events[0].1["pairings_need_reauth"], 1);
+    }
+}
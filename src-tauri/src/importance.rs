@@ -0,0 +1,305 @@
+//! 按安卓通知重要性（min/low/default/high/urgent）决定桌面端行为：
+//! 是否计入未读、是否弹出系统通知、是否响铃，以及免打扰时段内是否仍然弹出。
+//! 映射表保存在设置中，可通过 set_settings 修改；存储按重要性分别维护未读计数，
+//! 修改映射后只需重新汇总各类计数，不必重新扫描通知。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::commands::AppState;
+use crate::media;
+use crate::types::Notification;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Importance {
+    Min,
+    Low,
+    #[default]
+    Default,
+    High,
+    Urgent,
+}
+
+impl Importance {
+    pub const ALL: [Importance; 5] = [
+        Importance::Min,
+        Importance::Low,
+        Importance::Default,
+        Importance::High,
+        Importance::Urgent,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// 安卓 NotificationManager.IMPORTANCE_*（1..=5）；未知值按 default 处理
+    fn from_android(level: i64) -> Self {
+        match level {
+            i64::MIN..=1 => Importance::Min,
+            2 => Importance::Low,
+            3 => Importance::Default,
+            4 => Importance::High,
+            _ => Importance::Urgent,
+        }
+    }
+}
+
+/// 手机端可能发送名称或安卓原始数值
+impl<'de> Deserialize<'de> for Importance {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Level(i64),
+            Name(String),
+        }
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Level(level) => Importance::from_android(level),
+            Raw::Name(name) => match name.trim().to_lowercase().as_str() {
+                "min" => Importance::Min,
+                "low" => Importance::Low,
+                "high" => Importance::High,
+                "urgent" | "max" => Importance::Urgent,
+                _ => Importance::Default,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ImportanceBehavior {
+    pub counts_unread: bool,
+    pub toast: bool,
+    pub sound: bool,
+    /// 免打扰时段内仍然弹出（不响铃）
+    pub bypass_quiet_hours: bool,
+}
+
+impl Default for ImportanceBehavior {
+    fn default() -> Self {
+        Self { counts_unread: true, toast: true, sound: true, bypass_quiet_hours: false }
+    }
+}
+
+/// 重要性 -> 行为
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ImportanceMap {
+    pub min: ImportanceBehavior,
+    pub low: ImportanceBehavior,
+    pub default: ImportanceBehavior,
+    pub high: ImportanceBehavior,
+    pub urgent: ImportanceBehavior,
+}
+
+impl Default for ImportanceMap {
+    fn default() -> Self {
+        let silent = ImportanceBehavior { counts_unread: false, toast: false, sound: false, bypass_quiet_hours: false };
+        Self {
+            min: silent,
+            low: silent,
+            default: ImportanceBehavior::default(),
+            high: ImportanceBehavior::default(),
+            urgent: ImportanceBehavior { bypass_quiet_hours: true, ..Default::default() },
+        }
+    }
+}
+
+impl ImportanceMap {
+    pub fn get(&self, importance: Importance) -> ImportanceBehavior {
+        match importance {
+            Importance::Min => self.min,
+            Importance::Low => self.low,
+            Importance::Default => self.default,
+            Importance::High => self.high,
+            Importance::Urgent => self.urgent,
+        }
+    }
+
+    /// 各重要性是否计入未读（按 Importance::index 排列）
+    pub fn counted(&self) -> [bool; 5] {
+        Importance::ALL.map(|i| self.get(i).counts_unread)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for importance in Importance::ALL {
+            let b = self.get(importance);
+            if (b.sound || b.bypass_quiet_hours) && !b.toast {
+                return Err(format!("importance.{:?}: sound/bypass_quiet_hours require toast", importance).to_lowercase());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 免打扰时段（本地时间，分钟数，可跨午夜）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct QuietHours {
+    pub enabled: bool,
+    pub start_minute: u16,
+    pub end_minute: u16,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self { enabled: false, start_minute: 22 * 60, end_minute: 7 * 60 }
+    }
+}
+
+impl QuietHours {
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        if !self.enabled || self.start_minute == self.end_minute {
+            return false;
+        }
+        if self.start_minute < self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start_minute || minute_of_day < self.end_minute
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.start_minute >= 24 * 60 || self.end_minute >= 24 * 60 {
+            return Err("quiet_hours minutes must be within 0..1440".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 一条新通知最终的提醒方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Alert {
+    pub toast: bool,
+    pub sound: bool,
+}
+
+/// 免打扰时段内只有 bypass_quiet_hours 的级别弹出，且不响铃
+pub fn alert_for(behavior: ImportanceBehavior, quiet: bool) -> Alert {
+    match (behavior.toast, quiet) {
+        (false, _) => Alert::default(),
+        (true, false) => Alert { toast: true, sound: behavior.sound },
+        (true, true) => Alert { toast: behavior.bypass_quiet_hours, sound: false },
+    }
+}
+
+pub(crate) fn local_minute_of_day() -> u16 {
+    use chrono::Timelike;
+    let now = chrono::Local::now();
+    (now.hour() * 60 + now.minute()) as u16
+}
+
+impl AppState {
+    /// 设置变更后同步存储的未读计数口径；计数变化时推送 counts-changed
+    pub(crate) fn apply_importance_map(&self) {
+        let counted = self.settings.read().importance.counted();
+        let changed = self.store.lock().unwrap().set_counted_importance(counted);
+        if changed {
+            self.emit_counts();
+        }
+    }
+
+    /// 新通知的提醒方式（媒体通知不提醒）
+    pub(crate) fn alert_for_new(&self, n: &Notification) -> Alert {
+        if media::is_media(n) {
+            return Alert::default();
+        }
+        // 系统勿扰与免打扰时段同样处理（见 os_focus）
+        let quiet = self.os_focus_quiet();
+        let settings = self.settings.read();
+        let quiet = quiet || settings.quiet_hours.contains(local_minute_of_day());
+        alert_for(settings.importance.get(n.importance), quiet)
+    }
+
+    pub(crate) fn dispatch_alert(&self, n: &Notification) {
+        let alert = self.alert_for_new(n);
+        if alert.toast {
+            self.show_toast(n, alert.sound);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    fn added(id: &str, importance: Importance) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some("com.example".into()),
+                title: Some("标题".into()),
+                importance,
+                ..Default::default()
+            }),
+            id: None,
+        }
+    }
+
+    /// 默认映射下各级别：(计入未读, 平时, 免打扰时段内)
+    const FIXTURES: &[(Importance, bool, Alert, Alert)] = &[
+        (Importance::Min, false, Alert { toast: false, sound: false }, Alert { toast: false, sound: false }),
+        (Importance::Low, false, Alert { toast: false, sound: false }, Alert { toast: false, sound: false }),
+        (Importance::Default, true, Alert { toast: true, sound: true }, Alert { toast: false, sound: false }),
+        (Importance::High, true, Alert { toast: true, sound: true }, Alert { toast: false, sound: false }),
+        (Importance::Urgent, true, Alert { toast: true, sound: true }, Alert { toast: true, sound: false }),
+    ];
+
+    #[test]
+    fn test_default_mapping_per_level() {
+        let map = ImportanceMap::default();
+        let state = AppState::default();
+        for (i, (importance, counts, normal, quiet)) in FIXTURES.iter().enumerate() {
+            let behavior = map.get(*importance);
+            assert_eq!(alert_for(behavior, false), *normal, "{:?}", importance);
+            assert_eq!(alert_for(behavior, true), *quiet, "{:?}", importance);
+
+            let before = state.counts().unread;
+            state.ingest_event(added(&i.to_string(), *importance));
+            assert_eq!(state.counts().unread - before, *counts as usize, "{:?}", importance);
+        }
+        assert_eq!(state.counts().total, 5);
+    }
+
+    #[test]
+    fn test_mapping_change_recounts_unread() {
+        let state = AppState::default();
+        state.ingest_event(added("low", Importance::Low));
+        state.ingest_event(added("high", Importance::High));
+        assert_eq!(state.counts().unread, 1);
+        state.events.take_captured();
+
+        state.settings.write().importance.low.counts_unread = true;
+        state.apply_importance_map();
+        assert_eq!(state.counts().unread, 2);
+        state.store.lock().unwrap().mark_read(&["low".to_string()]);
+        assert_eq!(state.counts().unread, 1);
+        state.settings.write().importance.high.counts_unread = false;
+        state.apply_importance_map();
+        assert_eq!(state.counts().unread, 0);
+        let events = state.events.take_captured();
+        assert_eq!(events.iter().filter(|(e, _)| e == "counts-changed").count(), 2);
+    }
+
+    #[test]
+    fn test_parse_and_validate() {
+        let n: Notification = serde_json::from_str(r#"{"id":"1","read":false,"importance":5}"#).unwrap();
+        assert_eq!(n.importance, Importance::Urgent);
+        let n: Notification = serde_json::from_str(r#"{"id":"1","read":false,"importance":"LOW"}"#).unwrap();
+        assert_eq!(n.importance, Importance::Low);
+        let n: Notification = serde_json::from_str(r#"{"id":"1","read":false}"#).unwrap();
+        assert_eq!(n.importance, Importance::Default);
+
+        let mut map = ImportanceMap::default();
+        map.low.sound = true;
+        assert!(map.validate().is_err());
+        let quiet = QuietHours { enabled: true, ..Default::default() };
+        assert!(quiet.contains(23 * 60) && quiet.contains(60) && !quiet.contains(12 * 60));
+    }
+}
@@ -0,0 +1,374 @@
+//! 通知入库流水线：语言检测 -> 渠道目录（见 channels） -> 规则过滤（命中计数与复查列表见 rule_review） -> 写入存储 -> 通知前端 -> 按重要性弹出系统通知。
+//! 所有来源（安卓端事件、演示数据）都经过这里，保证派生字段一致。
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::commands::AppState;
+use crate::language;
+use crate::media;
+use crate::rules::{self, RuleAction};
+use crate::store::Upsert;
+use crate::tombstones::RemovalReason;
+use crate::types::{Event, Notification};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestOutcome {
+    Stored,
+    Muted,
+    Dropped(String),
+    Removed,
+    /// 手机端划掉，本地只标为已读
+    MarkedRead,
+    /// 手机端划掉，本地保留
+    Kept,
+    Ignored,
+}
+
+impl AppState {
+    /// 处理来自指定连接的事件：先写入该连接的事件流（推送给订阅的 WebView）与导出文件，再入库
+    pub fn ingest_from(&self, connection_id: &str, event: Event) -> IngestOutcome {
+        // 已暂停镜像但手机端不支持暂停的设备：本地丢弃新增/更新
+        if matches!(event.event_type.as_str(), "added" | "updated") && self.is_mirroring_paused(connection_id) {
+            return IngestOutcome::Dropped("mirroring_paused".to_string());
+        }
+        let now = chrono::Utc::now().timestamp();
+        self.record_frame(connection_id, event.seq, now);
+        if matches!(event.event_type.as_str(), "added" | "updated")
+            && event.notification.as_ref().is_some_and(|n| self.below_sync_horizon(connection_id, n, now))
+        {
+            return IngestOutcome::Dropped("sync_horizon".to_string());
+        }
+        self.track_media(connection_id, &event.event_type, event.notification.as_ref(), event.id.as_deref());
+        self.subscriptions.publish(connection_id, event.clone());
+        self.relay_publish(connection_id, &event);
+        self.event_log.append(connection_id, &event, now);
+        self.webhook_enqueue(connection_id, &event, now);
+        if event.event_type == "added" {
+            self.record_notification_metric();
+        }
+        // 手机端的移除按批量清除设置处理（见 phone_dismiss）
+        match event.event_type.as_str() {
+            "clear_all" => return self.phone_clear_all(connection_id),
+            "removed" => {
+                return match event.id.as_deref().or(event.notification.as_ref().map(|n| n.id.as_str())) {
+                    Some(id) => {
+                        let id = self.store.lock().unwrap().scoped_id(connection_id, id);
+                        self.phone_removed(connection_id, &id)
+                    }
+                    None => IngestOutcome::Ignored,
+                };
+            }
+            _ => {}
+        }
+        let mut event = event;
+        if let Some(n) = event.notification.as_mut() {
+            self.apply_clock_offset(connection_id, n);
+            if event.event_type == "added" {
+                self.record_volume(n.package_name.as_deref(), n.posted_at, now);
+            }
+        }
+        match event.notification {
+            Some(n) if matches!(event.event_type.as_str(), "added" | "updated") => self.upsert_notification(connection_id, n),
+            _ => self.ingest_event(event),
+        }
+    }
+
+    /// 按 (连接, 通知 id) 写入：同一连接重发的 id 原地更新标题、正文与 updated_at，保留已读状态与 posted_at（见 NotificationStore::upsert）；
+    /// 另一连接的同名 id 分开存储（见 NotificationStore::upsert_from）
+    pub fn upsert_notification(&self, connection_id: &str, n: Notification) -> IngestOutcome {
+        self.ingest_filtered(n, false, None, Some(connection_id))
+    }
+
+    /// 处理一条来自手机端（或本地）的事件
+    pub fn ingest_event(&self, event: Event) -> IngestOutcome {
+        match event.event_type.as_str() {
+            "added" | "updated" => match event.notification {
+                Some(n) => self.ingest_notification(n, event.event_type == "updated"),
+                None => IngestOutcome::Ignored,
+            },
+            "removed" => {
+                let Some(id) = event.id.or(event.notification.map(|n| n.id)) else {
+                    return IngestOutcome::Ignored;
+                };
+                let removed = self.store.lock().unwrap().remove(&id);
+                if let Some(n) = removed {
+                    self.on_removed(&[n], RemovalReason::DismissedOnPhone);
+                    IngestOutcome::Removed
+                } else {
+                    IngestOutcome::Ignored
+                }
+            }
+            other => {
+                println!("[Ingest] Unknown event_type: {}", other);
+                IngestOutcome::Ignored
+            }
+        }
+    }
+
+    fn ingest_notification(&self, n: Notification, updated: bool) -> IngestOutcome {
+        self.ingest_filtered(n, updated, None, None)
+    }
+
+    /// 从复查列表恢复：跳过指定规则，其余流程与新通知相同
+    pub(crate) fn ingest_bypassing(&self, n: Notification, rule_id: &str) -> IngestOutcome {
+        self.ingest_filtered(n, false, Some(rule_id), None)
+    }
+
+    /// 给出 connection_id 时按 (连接, id) 确定存储 id，并按是否已存在决定推送 added 还是 updated
+    fn ingest_filtered(
+        &self,
+        mut n: Notification,
+        updated: bool,
+        bypass_rule: Option<&str>,
+        connection_id: Option<&str>,
+    ) -> IngestOutcome {
+        let (detect_language, mut rules, retention) = {
+            let settings = self.settings.read();
+            (settings.detect_language, settings.rules.clone(), settings.retention.clone())
+        };
+        rules.retain(|r| Some(r.id.as_str()) != bypass_rule);
+
+        n.language = if detect_language {
+            Some(language::detect_notification(n.title.as_deref(), n.text.as_deref()))
+        } else {
+            None
+        };
+        // 规则过滤之前记录渠道：被丢弃的渠道同样出现在目录中
+        self.channels.observe(&n, chrono::Utc::now().timestamp());
+
+        let mut outcome = IngestOutcome::Stored;
+        // 媒体播放通知常驻且频繁更新，不计入未读
+        let mut mark_read = media::is_media(&n);
+        if let Some(rule) = rules::evaluate(&rules, &n) {
+            self.note_rule_hit(rule, &n, chrono::Utc::now().timestamp());
+            match rule.action {
+                RuleAction::Drop => {
+                    println!("[Ingest] Dropped {} by rule {}", n.id, rule.id);
+                    self.audit_rule_drop(&rule.id, n.package_name.as_deref());
+                    return IngestOutcome::Dropped(rule.id.clone());
+                }
+                RuleAction::Mute => {
+                    mark_read = true;
+                    outcome = IngestOutcome::Muted;
+                }
+            }
+        }
+
+        // 静音的会话/通知：照常保存，但不计未读、不提醒
+        n.muted = self.mutes.is_muted(&n, chrono::Utc::now().timestamp());
+        if n.muted && outcome == IngestOutcome::Stored {
+            mark_read = true;
+            outcome = IngestOutcome::Muted;
+        }
+
+        // 已读状态由存储决定（见 NotificationStore::upsert）
+        let (stored, inserted, eviction) = {
+            let mut store = self.store.lock().unwrap();
+            let (result, id) = match connection_id {
+                Some(connection_id) => store.upsert_from(connection_id, n, mark_read),
+                None => {
+                    let id = n.id.clone();
+                    (store.upsert(n, mark_read), id)
+                }
+            };
+            let eviction = store.enforce_retention(&retention);
+            let stored = store.get(&id).cloned();
+            if let Upsert::Updated { content_changed } = result {
+                println!("[Ingest] Updated {} (content_changed={})", id, content_changed);
+            }
+            (stored, result == Upsert::Inserted, eviction)
+        };
+
+        let event = if updated || (connection_id.is_some() && !inserted) { "notification-updated" } else { "notification-added" };
+        if let Some(n) = stored {
+            // 只对新通知提醒；静音规则命中的不提醒
+            if inserted && outcome == IngestOutcome::Stored {
+                self.dispatch_alert(&n);
+            }
+            self.events.emit(event, n);
+        }
+        self.emit_counts();
+
+        for (package, count) in eviction.flooded {
+            println!("[Ingest] Package {:?} exceeded quota ({} items)", package, count);
+            self.events.emit(
+                "package-flood",
+                serde_json::json!({
+                    "package_name": package,
+                    "count": count,
+                    "quota": retention.package_quota(),
+                }),
+            );
+        }
+        if !eviction.removed.is_empty() {
+            println!("[Ingest] Retention evicted {} items", eviction.removed.len());
+            self.on_removed(&eviction.removed, RemovalReason::RetentionEvicted);
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::Language;
+    use crate::rules::Rule;
+
+    fn added(id: &str, title: &str, text: &str) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some("com.tencent.mm".into()),
+                title: Some(title.into()),
+                text: Some(text.into()),
+                ..Default::default()
+            }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_language_tag_and_rule() {
+        let state = AppState::default();
+        state.settings.write().rules.push(Rule {
+            id: "mute-latin".into(),
+            enabled: true,
+            package: None,
+            channel: None,
+            pattern: None,
+            language: Some(Language::Latin),
+            action: RuleAction::Mute,
+        });
+
+        assert_eq!(state.ingest_event(added("1", "工作群", "明天开会")), IngestOutcome::Stored);
+        assert_eq!(state.ingest_event(added("2", "Mom", "Dinner at 7?")), IngestOutcome::Muted);
+
+        let store = state.store.lock().unwrap();
+        assert_eq!(store.get("1").unwrap().language, Some(Language::Zh));
+        assert!(!store.get("1").unwrap().read);
+        assert!(store.get("2").unwrap().read);
+    }
+
+    fn updated(id: &str, title: &str, text: &str, updated_at: i64) -> Event {
+        let mut event = added(id, title, text);
+        event.event_type = "updated".into();
+        event.notification.as_mut().unwrap().updated_at = Some(updated_at);
+        event
+    }
+
+    #[test]
+    fn test_update_before_read_replaces_in_place() {
+        let state = AppState::default();
+        let mut first = added("1", "工作群", "明天开会").notification.unwrap();
+        first.posted_at = Some(1_000);
+        state.upsert_notification("pixel", first);
+        state.events.take_captured();
+
+        // 手机端重发同一 id（带着新的 posted_at）：原地更新，不重复存储，仍为未读，保留原 posted_at，不再弹出提醒
+        let mut update = updated("1", "工作群", "明天上午十点开会", 1_060).notification.unwrap();
+        update.posted_at = Some(1_060);
+        assert_eq!(state.upsert_notification("pixel", update), IngestOutcome::Stored);
+        let counts = state.counts();
+        assert_eq!((counts.unread, counts.total), (1, 1));
+        let n = state.store.lock().unwrap().get("1").cloned().unwrap();
+        assert_eq!((n.text.as_deref(), n.posted_at, n.updated_at), (Some("明天上午十点开会"), Some(1_000), Some(1_060)));
+        let events = state.events.take_captured();
+        assert!(events.iter().any(|(name, _)| name == "notification-updated"));
+        assert!(!events.iter().any(|(name, _)| name == "notification-added"));
+
+        // 读过之后内容未变的刷新不会复活为未读
+        state.store.lock().unwrap().mark_read(&["1".to_string()]);
+        state.ingest_event(updated("1", "工作群", "明天上午十点开会", 1_120));
+        assert!(state.store.lock().unwrap().get("1").unwrap().read);
+    }
+
+    #[test]
+    fn test_upsert_is_keyed_by_connection() {
+        let state = AppState::default();
+        state.upsert_notification("pixel", added("1", "工作群", "明天开会").notification.unwrap());
+        state.store.lock().unwrap().mark_read(&["1".to_string()]);
+
+        // 另一台手机的同名 id 是另一条通知：分开存储，不影响原来的已读状态
+        state.upsert_notification("tablet", added("1", "快递", "已签收").notification.unwrap());
+        state.upsert_notification("tablet", updated("1", "快递", "已签收", 1_060).notification.unwrap());
+        let counts = state.counts();
+        assert_eq!((counts.unread, counts.total), (1, 2));
+        let store = state.store.lock().unwrap();
+        let (a, b) = (store.get("1").unwrap(), store.get("tablet:1").unwrap());
+        assert_eq!((a.read, a.connection_id.as_deref(), a.text.as_deref()), (true, Some("pixel"), Some("明天开会")));
+        assert_eq!((b.read, b.connection_id.as_deref(), b.updated_at), (false, Some("tablet"), Some(1_060)));
+        drop(store);
+
+        // 手机端划掉同样按连接定位（默认标为已读）
+        let removed = Event { event_type: "removed".into(), seq: 0, notification: None, id: Some("1".into()) };
+        assert_eq!(state.ingest_from("tablet", removed), IngestOutcome::MarkedRead);
+        assert!(state.store.lock().unwrap().get("tablet:1").unwrap().read);
+        assert_eq!(state.counts().total, 2);
+    }
+
+    #[test]
+    fn test_update_for_deleted_id_stores_once() {
+        let state = AppState::default();
+        state.ingest_event(added("1", "工作群", "明天开会"));
+        state.store.lock().unwrap().remove("1");
+
+        // 手机上仍在的通知更新后重新出现（只有一条），按新通知计为未读
+        state.ingest_event(updated("1", "工作群", "明天上午十点开会", 1_060));
+        state.ingest_event(updated("1", "工作群", "明天上午十点开会", 1_120));
+        let counts = state.counts();
+        assert_eq!((counts.unread, counts.total), (1, 1));
+        assert_eq!(state.store.lock().unwrap().get("1").unwrap().updated_at, Some(1_120));
+        assert!(state.store.lock().unwrap().integrity_issues().is_empty());
+    }
+
+    #[test]
+    fn test_detection_can_be_disabled() {
+        let state = AppState::default();
+        state.settings.write().detect_language = false;
+        state.ingest_event(added("1", "工作群", "明天开会"));
+        assert_eq!(state.store.lock().unwrap().get("1").unwrap().language, None);
+    }
+
+    #[test]
+    fn test_phone_dismissal_recorded_with_reason() {
+        let state = AppState::default();
+        state.ingest_event(added("1", "工作群", "明天开会"));
+        state.events.take_captured();
+        let removed = Event {
+            event_type: "removed".into(),
+            seq: 1,
+            notification: None,
+            id: Some("1".into()),
+        };
+        assert_eq!(state.ingest_event(removed), IngestOutcome::Removed);
+
+        let events = state.events.take_captured();
+        assert_eq!(events[0].0, "notification-removed");
+        assert_eq!(events[0].1["reason"], "dismissed_on_phone");
+        let log = state.removal_log.lock().unwrap().recent(10);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].reason, RemovalReason::DismissedOnPhone);
+        assert_eq!(state.counts().total, 0);
+    }
+
+    #[test]
+    fn test_flood_warning_and_retention_eviction() {
+        let state = AppState::default();
+        state.settings.write().retention.max_items = 4;
+        for i in 0..5 {
+            state.ingest_event(added(&i.to_string(), "群", "消息"));
+        }
+        let events = state.events.take_captured();
+        let flood: Vec<_> = events.iter().filter(|(e, _)| e == "package-flood").collect();
+        assert_eq!(flood.len(), 1);
+        assert_eq!(flood[0].1["package_name"], "com.tencent.mm");
+        let removed: Vec<_> = events.iter().filter(|(e, _)| e == "notification-removed").collect();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].1["reason"], "retention_evicted");
+        assert_eq!(state.counts().total, 4);
+    }
+}
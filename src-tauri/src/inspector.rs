@@ -0,0 +1,218 @@
+//! 协议帧检查器（开发用）：按连接保留最近 MAX_FRAMES_PER_CONNECTION 帧的方向、时间、大小、
+//! 解码结果（解码失败时为原始文本与错误），并推送 frame-captured 事件供调试面板实时查看。
+//! 只在开启 dev-tools 特性的调试构建中可用（AVAILABLE 为编译期常量，发布构建中收发路径上的
+//! 检查代码会被整体去掉）；未开启任何连接时只多一次 relaxed 原子读。
+//! 令牌、密码等字段在存入之前就已替换，开发构建中也不会保存。
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use parking_lot::Mutex;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::events::EventSink;
+use crate::trace::Direction;
+
+/// 编译期决定：dev-tools 特性的调试构建（以及单元测试）才会采集
+pub const AVAILABLE: bool = cfg!(any(test, all(feature = "dev-tools", debug_assertions)));
+pub const UNAVAILABLE: &str = "Frame capture requires a debug build with the dev-tools feature";
+pub const MAX_FRAMES_PER_CONNECTION: usize = 500;
+/// 解码失败时保留的原始文本长度
+const MAX_RAW_CHARS: usize = 2048;
+const REDACTED: &str = "<redacted>";
+/// 按字段名（不区分大小写，忽略 _ 与 -）替换的字段；以 token 结尾的字段也会替换
+const SECRET_KEYS: &[&str] = &["token", "authtoken", "roomtoken", "pairingtoken", "password", "secret", "key"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CapturedFrame {
+    pub connection_id: String,
+    pub direction: Direction,
+    /// 毫秒时间戳
+    pub at: i64,
+    pub size: usize,
+    /// 解码后的 JSON（敏感字段已替换）
+    pub decoded: Option<serde_json::Value>,
+    /// 解码失败时的原始文本（已脱敏、截断）
+    pub raw: Option<String>,
+    pub decode_error: Option<String>,
+}
+
+pub(crate) fn is_secret_key(key: &str) -> bool {
+    let key = key.chars().filter(|c| *c != '_' && *c != '-').collect::<String>().to_lowercase();
+    SECRET_KEYS.contains(&key.as_str()) || key.ends_with("token")
+}
+
+fn redact_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_secret_key(key) && !v.is_null() {
+                    *v = REDACTED.into();
+                } else {
+                    redact_value(v);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+/// 无法解码的文本：替换形如 "token":"..." 的字段与长串随机字符
+fn redact_text(raw: &str) -> String {
+    static FIELD: OnceLock<Regex> = OnceLock::new();
+    static OPAQUE: OnceLock<Regex> = OnceLock::new();
+    let field = FIELD.get_or_init(|| Regex::new(r#"(?i)("[a-z_\-]*(token|password|secret|key)"\s*:\s*)"[^"]*("|$)"#).unwrap());
+    let opaque = OPAQUE.get_or_init(|| Regex::new(r"[A-Za-z0-9_\-+/=]{24,}").unwrap());
+    let text: String = raw.trim_end().chars().take(MAX_RAW_CHARS).collect();
+    let text = field.replace_all(&text, format!("${{1}}\"{}\"", REDACTED));
+    opaque.replace_all(&text, REDACTED).into_owned()
+}
+
+pub fn capture(connection_id: &str, direction: Direction, raw: &str) -> CapturedFrame {
+    let (decoded, raw_text, decode_error) = match crate::protocol::decode::<serde_json::Value>(raw) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            (Some(value), None, None)
+        }
+        Err(e) => (None, Some(redact_text(raw)), Some(e.to_string())),
+    };
+    CapturedFrame {
+        connection_id: connection_id.to_string(),
+        direction,
+        at: chrono::Utc::now().timestamp_millis(),
+        size: raw.trim_end().len(),
+        decoded,
+        raw: raw_text,
+        decode_error,
+    }
+}
+
+#[derive(Default)]
+pub struct FrameInspector {
+    // 快速路径：没有任何连接开启时直接返回
+    any_enabled: AtomicBool,
+    enabled: Mutex<HashSet<String>>,
+    frames: Mutex<HashMap<String, VecDeque<CapturedFrame>>>,
+}
+
+impl FrameInspector {
+    /// 开启/关闭某个连接的采集
+    pub fn set_enabled(&self, connection_id: &str, enabled: bool) -> Result<bool, String> {
+        if !AVAILABLE {
+            return Err(UNAVAILABLE.to_string());
+        }
+        let mut set = self.enabled.lock();
+        if enabled {
+            set.insert(connection_id.to_string());
+        } else {
+            set.remove(connection_id);
+        }
+        self.any_enabled.store(!set.is_empty(), Ordering::Relaxed);
+        println!("[Inspector] Capture for {} -> {}", connection_id, enabled);
+        Ok(enabled)
+    }
+
+    /// 最近的 limit 帧（旧 -> 新）
+    pub fn frames(&self, connection_id: &str, limit: usize) -> Result<Vec<CapturedFrame>, String> {
+        if !AVAILABLE {
+            return Err(UNAVAILABLE.to_string());
+        }
+        let frames = self.frames.lock();
+        let Some(list) = frames.get(connection_id) else {
+            return Ok(Vec::new());
+        };
+        Ok(list.iter().skip(list.len().saturating_sub(limit)).cloned().collect())
+    }
+
+    /// 各连接中采集最多的帧数
+    pub fn max_buffered(&self) -> usize {
+        self.frames.lock().values().map(|l| l.len()).max().unwrap_or(0)
+    }
+
+    /// 清空采集结果（不改变开启状态），返回清除的帧数
+    pub fn clear(&self, connection_id: Option<&str>) -> Result<usize, String> {
+        if !AVAILABLE {
+            return Err(UNAVAILABLE.to_string());
+        }
+        let mut frames = self.frames.lock();
+        let n = match connection_id {
+            Some(id) => frames.remove(id).map_or(0, |l| l.len()),
+            None => frames.drain().map(|(_, l)| l.len()).sum(),
+        };
+        Ok(n)
+    }
+
+    /// 收发路径调用（见 ConnectionTracer::record）
+    pub fn record(&self, connection_id: &str, direction: Direction, raw: &str, events: &EventSink) {
+        if !self.any_enabled.load(Ordering::Relaxed) || !self.enabled.lock().contains(connection_id) {
+            return;
+        }
+        let frame = capture(connection_id, direction, raw);
+        {
+            let mut frames = self.frames.lock();
+            let list = frames.entry(connection_id.to_string()).or_default();
+            if list.len() == MAX_FRAMES_PER_CONNECTION {
+                list.pop_front();
+            }
+            list.push_back(frame.clone());
+        }
+        events.emit("frame-captured", frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_never_stored() {
+        let sink = EventSink::default();
+        let inspector = FrameInspector::default();
+        inspector.set_enabled("c1", true).unwrap();
+        let login = r#"{"action":"login","requestId":"r1","token":"s3cr3t-token-value","nested":{"room_token":"abc"}}"#;
+        inspector.record("c1", Direction::Send, login, &sink);
+        inspector.record("c1", Direction::Recv, r#"{"success":true,"token":"zzz"#, &sink);
+
+        let frames = inspector.frames("c1", 10).unwrap();
+        assert_eq!(frames.len(), 2);
+        let sent = frames[0].decoded.as_ref().unwrap();
+        assert_eq!(sent["action"], "login");
+        assert_eq!(sent["token"], REDACTED);
+        assert_eq!(sent["nested"]["room_token"], REDACTED);
+        assert!(frames[1].decode_error.is_some());
+        assert_eq!(frames[1].raw.as_deref(), Some(r#"{"success":true,"token":"<redacted>""#));
+
+        let raw = redact_text(r#"{"authToken":"abc","x":1} trailing 0123456789abcdefABCDEF0123456789"#);
+        assert_eq!(raw, r#"{"authToken":"<redacted>","x":1} trailing <redacted>"#);
+        let captured = serde_json::to_string(&sink.take_captured()).unwrap();
+        assert!(!captured.contains("s3cr3t") && !captured.contains("\"abc\""));
+    }
+
+    #[test]
+    fn test_bounded_per_connection_and_off_by_default() {
+        let sink = EventSink::default();
+        let inspector = FrameInspector::default();
+        inspector.record("c1", Direction::Recv, "{}", &sink);
+        assert!(inspector.frames("c1", 10).unwrap().is_empty());
+
+        inspector.set_enabled("c1", true).unwrap();
+        for i in 0..MAX_FRAMES_PER_CONNECTION + 3 {
+            inspector.record("c1", Direction::Recv, &format!(r#"{{"seq":{}}}"#, i), &sink);
+            inspector.record("c2", Direction::Recv, "{}", &sink);
+        }
+        let frames = inspector.frames("c1", usize::MAX).unwrap();
+        assert_eq!(frames.len(), MAX_FRAMES_PER_CONNECTION);
+        assert_eq!(frames[0].decoded.as_ref().unwrap()["seq"], 3);
+        assert_eq!(inspector.frames("c1", 2).unwrap()[1].decoded.as_ref().unwrap()["seq"], MAX_FRAMES_PER_CONNECTION + 2);
+        assert!(inspector.frames("c2", 10).unwrap().is_empty());
+
+        assert_eq!(inspector.clear(None).unwrap(), MAX_FRAMES_PER_CONNECTION);
+        inspector.set_enabled("c1", false).unwrap();
+        inspector.record("c1", Direction::Recv, "{}", &sink);
+        assert!(inspector.frames("c1", 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+//! 存储完整性检查：已读集合、时间/包名索引、按重要性的未读计数都是增量维护的，
+//! 任何一处遗漏都会让计数悄悄出错直到重启。verify_store_integrity 以通知表为准逐项核对，
+//! 返回结构化的不一致列表；repair 为 true 时全量重建派生结构并写入审计日志。
+//! 检查在存储锁内完成（只遍历、不复制），修复在同一临界区内进行，期间的并发修改不会造成误报；
+//! 报告中的 seq 即检查时的存储序号。删除记录另行检查（同一 id 只应有一条）。
+//! 调试构建由维护任务定期检查，发布构建通过诊断面板手动调用。
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+use crate::tombstones::MAX_TOMBSTONES;
+
+/// 报告中最多列出的不一致条数（总数见 total）
+pub const MAX_REPORTED: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Structure {
+    Notifications,
+    ReadSet,
+    PinnedSet,
+    ByTime,
+    UnreadByTime,
+    ByPackage,
+    UnreadCounters,
+    Snoozed,
+    RemovalLog,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// 派生结构中缺少应有的条目
+    Missing,
+    /// 派生结构中有多余（或时间戳过期）的条目
+    Stale,
+    /// 通知上的标记与集合不一致
+    FlagMismatch,
+    CounterMismatch,
+    /// 同一 id 同时出现在两处
+    Overlap,
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Discrepancy {
+    pub structure: Structure,
+    pub kind: DiscrepancyKind,
+    pub id: Option<String>,
+    pub detail: String,
+}
+
+impl Discrepancy {
+    pub fn new(structure: Structure, kind: DiscrepancyKind, id: Option<&str>, detail: String) -> Self {
+        Self { structure, kind, id: id.map(str::to_string), detail }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct IntegrityReport {
+    /// 检查时的存储序号
+    pub seq: u64,
+    pub notifications: usize,
+    pub snoozed: usize,
+    /// 最多 MAX_REPORTED 条
+    pub discrepancies: Vec<Discrepancy>,
+    pub total: usize,
+    pub repaired: bool,
+    pub checked_at: i64,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.total == 0
+    }
+
+    /// 按结构汇总报告中列出的不一致条数
+    pub fn by_structure(&self) -> BTreeMap<Structure, usize> {
+        let mut counts = BTreeMap::new();
+        for d in &self.discrepancies {
+            *counts.entry(d.structure).or_default() += 1;
+        }
+        counts
+    }
+}
+
+impl AppState {
+    /// 核对存储的派生结构；repair 为 true 时以通知表为准重建
+    pub fn verify_store_integrity(&self, repair: bool) -> IntegrityReport {
+        let (mut issues, seq, notifications, snoozed) = {
+            let mut store = self.store.lock().unwrap();
+            let issues = store.integrity_issues();
+            if repair && !issues.is_empty() {
+                store.rebuild_derived();
+            }
+            (issues, store.seq(), store.counts().total, store.snoozed().len())
+        };
+        let log_fixed = {
+            let mut log = self.removal_log.lock().unwrap();
+            let duplicates = log.duplicate_ids();
+            issues.extend(duplicates.iter().map(|id| {
+                Discrepancy::new(Structure::RemovalLog, DiscrepancyKind::Overlap, Some(id), "duplicate tombstone".to_string())
+            }));
+            let len = log.recent(usize::MAX).len();
+            if len > MAX_TOMBSTONES {
+                issues.push(Discrepancy::new(Structure::RemovalLog, DiscrepancyKind::Invalid, None, format!("{} entries (max {})", len, MAX_TOMBSTONES)));
+            }
+            (repair && (!duplicates.is_empty() || len > MAX_TOMBSTONES)).then(|| {
+                log.dedupe();
+                log.clone()
+            })
+        };
+        if let Some(log) = log_fixed {
+            if let Err(e) = self.storage.save(crate::commands::REMOVAL_LOG_FILE, &log) {
+                println!("[Integrity] Failed to save repaired removal log: {}", e);
+            }
+        }
+
+        let total = issues.len();
+        issues.truncate(MAX_REPORTED);
+        let report = IntegrityReport {
+            seq,
+            notifications,
+            snoozed,
+            discrepancies: issues,
+            total,
+            repaired: repair && total > 0,
+            checked_at: chrono::Utc::now().timestamp(),
+        };
+        if report.is_clean() {
+            println!("[Integrity] Store consistent ({} notifications, seq {})", notifications, seq);
+            return report;
+        }
+        println!("[Integrity] {} discrepancies (repaired={}): {:?}", total, report.repaired, report.by_structure());
+        if report.repaired {
+            let params = serde_json::json!({ "seq": seq, "by_structure": report.by_structure() });
+            self.audit("store_repair", AuditSource::Command, total, params);
+            self.emit_counts();
+        }
+        report
+    }
+
+    /// 维护任务：调试构建中定期检查（不修复），发现不一致时以失败上报
+    pub fn debug_integrity_check(&self) -> Result<String, String> {
+        if !cfg!(debug_assertions) {
+            return Ok("skipped (release build)".to_string());
+        }
+        let report = self.verify_store_integrity(false);
+        if report.is_clean() {
+            return Ok(format!("{} notifications consistent", report.notifications));
+        }
+        Err(format!("{} discrepancies: {:?}", report.total, report.by_structure()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tombstones::{RemovalReason, Tombstone};
+    use crate::types::{Event, Notification};
+
+    fn added(state: &AppState, id: &str, pkg: &str, ts: i64) {
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: id.into(), package_name: Some(pkg.into()), posted_at: Some(ts), ..Default::default() }),
+            id: None,
+        });
+    }
+
+    #[test]
+    fn test_clean_after_mixed_operations() {
+        let state = AppState::default();
+        for i in 0..30 {
+            added(&state, &i.to_string(), if i % 3 == 0 { "com.a" } else { "com.b" }, 1_700_000_000 + i);
+        }
+        {
+            let mut store = state.store.lock().unwrap();
+            store.mark_read(&["1".into(), "2".into()]);
+            store.set_pinned(&["3".into()], true);
+            let n = store.get("4").unwrap().clone();
+            store.snooze(n, 100);
+            store.remove("5");
+        }
+        added(&state, "1", "com.moved", 1_700_000_500);
+        let report = state.verify_store_integrity(false);
+        assert!(report.is_clean(), "{:?}", report.discrepancies);
+        assert_eq!((report.notifications, report.snoozed), (28, 1));
+    }
+
+    #[test]
+    fn test_repair_removal_log_and_audit() {
+        let state = AppState::default();
+        added(&state, "a", "com.a", 1);
+        let y = Notification { id: "y".into(), ..Default::default() };
+        state.removal_log.lock().unwrap().record(Tombstone::new(&y, RemovalReason::TrashPurged, 2));
+        assert!(state.verify_store_integrity(false).is_clean());
+        // 模拟从文件加载出的重复记录
+        let doubled: crate::tombstones::RemovalLog = serde_json::from_value(serde_json::json!([
+            { "id": "x", "package_name": null, "title": null, "reason": "user_deleted_local", "removed_at": 1 },
+            { "id": "x", "package_name": null, "title": null, "reason": "dismissed_on_phone", "removed_at": 5 },
+        ]))
+        .unwrap();
+        *state.removal_log.lock().unwrap() = doubled;
+
+        let report = state.verify_store_integrity(false);
+        assert_eq!(report.by_structure(), BTreeMap::from([(Structure::RemovalLog, 1)]));
+        assert!(!report.repaired);
+        assert!(state.audit.recent(10).is_empty());
+
+        let report = state.verify_store_integrity(true);
+        assert!(report.repaired);
+        let log = state.removal_log.lock().unwrap().recent(10);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].reason, RemovalReason::DismissedOnPhone);
+        let audit = state.audit.recent(10);
+        assert_eq!((audit[0].operation.as_str(), audit[0].affected), ("store_repair", 1));
+        assert!(state.verify_store_integrity(false).is_clean());
+    }
+}
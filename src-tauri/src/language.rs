@@ -0,0 +1,137 @@
+//! 轻量语言检测：按 Unicode 文字区块统计主导文字（CJK / 拉丁 / 西里尔 等）。
+//! 只看前 200 个字符，代价很低，可在 ingest 时对每条通知执行。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 最多检查的字符数
+const MAX_CHARS: usize = 200;
+/// 一个汉字/假名/谚文大致相当于一个拉丁单词，计数时加权，避免中英混排时被英文字母数量压过
+const CJK_WEIGHT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Zh,
+    Ja,
+    Ko,
+    Latin,
+    Cyrillic,
+    Arabic,
+    Unknown,
+}
+
+#[derive(Default)]
+struct ScriptCounts {
+    han: usize,
+    kana: usize,
+    hangul: usize,
+    latin: usize,
+    cyrillic: usize,
+    arabic: usize,
+}
+
+fn classify(c: char, counts: &mut ScriptCounts) {
+    match c as u32 {
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0xF900..=0xFAFF | 0x20000..=0x2A6DF => counts.han += 1,
+        0x3040..=0x30FF | 0x31F0..=0x31FF => counts.kana += 1,
+        0xAC00..=0xD7AF | 0x1100..=0x11FF | 0x3130..=0x318F => counts.hangul += 1,
+        0x0400..=0x052F => counts.cyrillic += 1,
+        0x0600..=0x06FF | 0x0750..=0x077F => counts.arabic += 1,
+        _ if c.is_ascii_alphabetic() => counts.latin += 1,
+        0x00C0..=0x024F => counts.latin += 1,
+        _ => {}
+    }
+}
+
+/// 检测一段文本的主导语言（按文字区块）
+pub fn detect(text: &str) -> Language {
+    let mut counts = ScriptCounts::default();
+    for c in text.chars().take(MAX_CHARS) {
+        classify(c, &mut counts);
+    }
+
+    let cjk = (counts.han + counts.kana + counts.hangul) * CJK_WEIGHT;
+    let best = [counts.latin, counts.cyrillic, counts.arabic, cjk]
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+    if best == 0 {
+        return Language::Unknown;
+    }
+
+    if cjk == best {
+        // 出现较多假名即为日文（日文中汉字与假名混用，中文没有假名）
+        if counts.kana > 0 && counts.kana * 4 >= counts.han {
+            Language::Ja
+        } else if counts.hangul > counts.han {
+            Language::Ko
+        } else {
+            Language::Zh
+        }
+    } else if counts.latin == best {
+        Language::Latin
+    } else if counts.cyrillic == best {
+        Language::Cyrillic
+    } else {
+        Language::Arabic
+    }
+}
+
+/// 检测通知的语言（标题 + 正文）
+pub fn detect_notification(title: Option<&str>, text: Option<&str>) -> Language {
+    let combined = format!("{} {}", title.unwrap_or_default(), text.unwrap_or_default());
+    detect(&combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 小型样本集：(文本, 期望语言)
+    const FIXTURES: &[(&str, Language)] = &[
+        ("张三: 今晚一起吃饭吗？", Language::Zh),
+        ("【工作群】请大家明天上午九点准时参加周会", Language::Zh),
+        ("您的验证码是 482913，5分钟内有效", Language::Zh),
+        ("微信支付: 你向 Starbucks 付款 32.00 元", Language::Zh),
+        ("李四: 好的 OK 收到", Language::Zh),
+        ("Mom: Are you coming home for dinner tonight?", Language::Latin),
+        ("Your Uber is arriving in 3 minutes", Language::Latin),
+        ("GitHub: [repo] New pull request #42 opened", Language::Latin),
+        ("Réunion déplacée à demain, désolé", Language::Latin),
+        ("Привет! Как дела?", Language::Cyrillic),
+        ("Ваш код подтверждения: 1234", Language::Cyrillic),
+        ("明日の会議は10時からです。よろしくお願いします", Language::Ja),
+        ("おはようございます", Language::Ja),
+        ("안녕하세요, 내일 봐요", Language::Ko),
+        ("مرحبا كيف حالك", Language::Arabic),
+        ("12345 !!! 😀", Language::Unknown),
+        ("", Language::Unknown),
+    ];
+
+    #[test]
+    fn test_fixture_accuracy() {
+        let mut failures = Vec::new();
+        for (text, expected) in FIXTURES {
+            let got = detect(text);
+            if got != *expected {
+                failures.push(format!("{:?}: expected {:?}, got {:?}", text, expected, got));
+            }
+        }
+        assert!(failures.is_empty(), "misdetected:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_mixed_reports_dominant_script() {
+        // 短英文单词夹在中文中：中文占主导
+        assert_eq!(detect("今天的 meeting 改到下午三点，记得带 laptop"), Language::Zh);
+        // 长英文句子中夹一个中文名字：英文占主导
+        assert_eq!(detect("Please forward the quarterly report to 王总 before Friday afternoon"), Language::Latin);
+    }
+
+    #[test]
+    fn test_only_prefix_is_inspected() {
+        let text = format!("{}{}", "a".repeat(MAX_CHARS), "中".repeat(1000));
+        assert_eq!(detect(&text), Language::Latin);
+    }
+}
@@ -1,23 +1,108 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 mod types;
+mod api_tokens;
 mod commands;
+mod compaction;
+mod compatibility;
 mod network_utils;
 mod temp_server;
 mod simple_server;
 mod android_client;
+mod settings;
+mod time_format;
+mod events;
+mod error_bus;
+mod language;
+mod rules;
+mod ingest;
+mod popout;
+mod store;
+mod storage;
+mod onboarding;
+mod trace;
+mod tombstones;
+mod subscriptions;
+mod reminders;
+mod tray_icon;
+mod app_dirs;
+mod audit;
+mod bench;
+mod bridge;
+mod bulk;
+mod stream_meta;
+mod transport;
+mod maintenance;
+mod event_log;
+mod privacy;
+mod presets;
+mod profiles;
+mod tray_menu;
+mod startup;
+mod mirroring;
+mod tasks;
+mod clock;
+mod ports;
+mod same_network;
+mod search;
+mod webhook;
+mod window_feed;
+mod endpoints;
+mod media;
+mod native_host;
+mod pairing_payload;
+mod importance;
+mod inspector;
+mod raw_frame;
+mod os_focus;
+mod ui_window;
+mod wall_clock;
+mod handoff;
+mod phone_dismiss;
+mod normalize;
+mod data_lock;
+mod alert_test;
+mod network_watch;
+mod rule_review;
+mod buffer_stats;
+mod app_meta;
+mod failover;
+mod firewall;
+mod relay;
+mod ping;
+mod masking;
+mod diagnostics;
+mod watch_only;
+mod tray_updates;
+mod derived_upgrade;
+mod channels;
+mod export;
+mod groups;
+mod persistent_store;
+#[macro_use]
+mod catalog;
+mod integrity;
+mod event_batch;
+mod identity;
+mod wizard;
+mod limits;
+mod link_quality;
+mod low_power;
+mod mutes;
+mod metrics;
+mod capabilities;
+mod day_summary;
+mod protocol;
+mod sync_horizon;
+mod templates;
+mod volume;
 use std::time::{Instant, Duration};
 use lazy_static::lazy_static;
 use std::sync::Mutex;
-use tauri::{Manager, tray::MouseButton, image::Image, Emitter};
+use tauri::{Manager, tray::MouseButton, Emitter};
 
 lazy_static! {
     static ref LAST_CLICK: Mutex<Option<Instant>> = Mutex::new(None);
 }
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
-}
-
 #[tauri::command]
 fn set_tray_tooltip(_app: tauri::AppHandle, _text: String) -> bool {
     // TODO: 后续将托盘句柄存入全局状态，再在此更新 tooltip。
@@ -27,25 +112,73 @@ fn set_tray_tooltip(_app: tauri::AppHandle, _text: String) -> bool {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // 从这里开始计算 uptime（见 ping）
+    crate::wall_clock::mono_ms();
+
+    // 由浏览器作为 native messaging host 启动：只转发请求，不启动界面
+    if crate::native_host::is_host_launch(&std::env::args().collect::<Vec<_>>()) {
+        std::process::exit(crate::native_host::run_stdio());
+    }
+
     // 模块声明：应用自定义 types 与 commands
     // 注意：初期开启较多日志，稳定后再降级
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_dialog::init())
         // 全局状态管理：内存版，后续可替换为 SQLite 持久化
         .manage(crate::commands::AppState::default())
         .setup(|app| {
-            use tauri::{menu::MenuItemBuilder, tray::TrayIconBuilder};
+            use tauri::tray::TrayIconBuilder;
+
+            // 挂载 AppHandle，供后台任务向前端推送事件
+            app.state::<crate::commands::AppState>().attach_app(app.handle().clone());
+
+            // 主窗口：WebView 不可用时不中断启动，以仅托盘模式继续运行（见 ui_window）
+            let handle = app.handle().clone();
+            app.state::<crate::commands::AppState>().start_ui(|| crate::ui_window::create_main_window(&handle));
 
-            // 构建托盘菜单
-            let toggle = MenuItemBuilder::with_id("toggle", "显示/隐藏").build(app)?;
-            let settings = MenuItemBuilder::with_id("settings", "设置").build(app)?;
-            let quit = MenuItemBuilder::with_id("quit", "退出").build(app)?;
-            let menu = tauri::menu::MenuBuilder::new(app)
-                .items(&[&toggle, &settings, &quit])
-                .build()?;
+            // 本地数据目录：设置与通知在后台线程加载，不阻塞首屏（加载完成前命令返回 NotReady）
+            let data_dir = app.path().app_local_data_dir()
+                .map_err(|e| println!("[Storage] No app data dir: {}", e))
+                .ok();
+            // 本机身份检查（机器 id 变化时重新生成 device_uuid）在加载完成后进行
+            match crate::app_dirs::config_dir() {
+                Ok(dir) => app.state::<crate::commands::AppState>().identity.set_dir(dir),
+                Err(e) => println!("[Identity] {}", e),
+            }
+            crate::startup::spawn_loading(app.handle().clone(), data_dir);
+
+            // 后台维护调度（到期提醒、保留策略等）
+            let handle = app.handle().clone();
+            app.state::<crate::commands::AppState>()
+                .tasks
+                .spawn("maintenance", move |token| crate::maintenance::run_loop(handle, token));
+
+            // 浏览器扩展桥接（native messaging host 经它访问本实例）
+            if let Err(e) = crate::bridge::start(app.handle().clone()) {
+                println!("[Bridge] {}", e);
+            }
+
+            // 定时推送合并后的通知事件
+            let handle = app.handle().clone();
+            app.state::<crate::commands::AppState>()
+                .tasks
+                .spawn("event_batcher", move |token| crate::event_batch::run_flush_loop(handle, token));
+
+            // 合并托盘更新（托盘创建前的请求保留到创建后）
+            let handle = app.handle().clone();
+            app.state::<crate::commands::AppState>()
+                .tasks
+                .spawn("tray_updates", move |token| crate::tray_updates::run_loop(handle, token));
+
+            // 构建托盘菜单（最近通知在托盘创建后填充）
+            let hide_previews = app.state::<crate::commands::AppState>().settings.read().privacy.hide_previews;
+            let ui_available = app.state::<crate::commands::AppState>().ui.is_available();
+            let menu = crate::tray_menu::build_menu(app.handle(), &[], hide_previews, ui_available)?;
 
             // 创建托盘图标
-            let tray = TrayIconBuilder::new()
+            TrayIconBuilder::with_id(crate::tray_icon::TRAY_ID)
                 .menu(&menu)
                 .show_menu_on_left_click(false)
                 .tooltip("Notification Listener")
@@ -83,45 +216,39 @@ pub fn run() {
                 .on_menu_event(|app, event| {
                     match event.id().as_ref() {
                         "toggle" => toggle_main_window(app),
+                        crate::ui_window::UI_UNAVAILABLE_ID => crate::ui_window::show_unavailable_dialog(app),
                         "settings" => {
                             ensure_main_window_visible(app);
                             if let Some(win) = app.get_webview_window("main") {
                                 let _ = win.emit("open-settings", ());
                             }
                         }
-                        "quit" => app.exit(0),
-                        _ => {}
+                        "quit" => {
+                            app.state::<crate::commands::AppState>().close_all_popouts();
+                            app.exit(0)
+                        }
+                        crate::tray_menu::HIDE_PREVIEWS_ID => {
+                            let state = app.state::<crate::commands::AppState>();
+                            let hide = !state.settings.read().privacy.hide_previews;
+                            if let Err(e) = state.set_hide_previews(hide) {
+                                println!("[Tray] {}", e);
+                            }
+                        }
+                        id => {
+                            // 最近通知：打开主窗口并定位到该通知
+                            if let Some(nid) = id.strip_prefix(crate::tray_menu::RECENT_PREFIX) {
+                                ensure_main_window_visible(app);
+                                if let Some(win) = app.get_webview_window("main") {
+                                    let _ = win.emit("focus-notification", nid);
+                                }
+                            }
+                        }
                     }
                 })
                 .build(app)?;
-            // 生成一个简单的 32x32 RGBA 圆形图标作为托盘图标
-            let mut rgba = vec![0u8; 32 * 32 * 4];
-            let cx = 16.0f32;
-            let cy = 16.0f32;
-            let r2 = 12.0f32 * 12.0f32;
-            for y in 0..32 {
-                for x in 0..32 {
-                    let dx = x as f32 + 0.5 - cx;
-                    let dy = y as f32 + 0.5 - cy;
-                    let idx = (y * 32 + x) * 4;
-                    let inside = dx * dx + dy * dy <= r2;
-                    if inside {
-                        // 绿色圆
-                        rgba[idx] = 0x2a;      // R
-                        rgba[idx + 1] = 0xc5; // G
-                        rgba[idx + 2] = 0x74; // B
-                        rgba[idx + 3] = 0xff; // A
-                    } else {
-                        // 透明背景
-                        rgba[idx] = 0x00;
-                        rgba[idx + 1] = 0x00;
-                        rgba[idx + 2] = 0x00;
-                        rgba[idx + 3] = 0x00;
-                    }
-                }
-            }
-            let img = Image::new_owned(rgba, 32, 32);
-            let _ = tray.set_icon(Some(img));
+            // 按连接状态与系统主题绘制托盘图标
+            app.state::<crate::commands::AppState>().refresh_tray_icon();
+            app.state::<crate::commands::AppState>().refresh_tray_menu();
             // 开发模式下，自动显示主窗口，避免用户找不到托盘图标
             #[cfg(debug_assertions)]
             {
@@ -129,50 +256,38 @@ pub fn run() {
                 ensure_main_window_visible(&handle);
             }
 
-            // 拦截主窗口关闭事件：改为隐藏到托盘
-            if let Some(win) = app.get_webview_window("main") {
-                let win_handle = win.clone();
-                win.on_window_event(move |e| {
-                    if let tauri::WindowEvent::CloseRequested { api, .. } = e {
-                        api.prevent_close();
-                        let _ = win_handle.hide();
-                    }
-                });
-            }
-
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            greet,
-            crate::commands::get_counts,
-            crate::commands::list_notifications,
-            crate::commands::mark_read,
-            crate::commands::delete,
-            crate::commands::delete_all,
-            set_tray_tooltip,
-            crate::commands::add_dummy,
-            // 网络相关命令
-            crate::commands::test_connect_to_server,
-            crate::commands::check_port_available,
-            crate::commands::find_available_port,
-            crate::commands::get_local_ip,
-            crate::commands::get_device_uuid,
-            crate::commands::get_os_type,
-            crate::commands::get_os_version,
-            crate::commands::get_hostname,
-            crate::commands::start_temp_server,
-            crate::commands::stop_temp_server,
-            crate::commands::get_temp_server_status,
-            crate::commands::connect_to_android,
-            crate::commands::disconnect_android,
-            crate::commands::test_socket_server,
-            crate::commands::test_http_pairing,
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(crate::metrics::counting_commands(crate::startup::gate_store_commands(with_commands!(generate_handlers))))
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // 正常退出：停止后台任务，写入变更序号
+            if let tauri::RunEvent::Exit = event {
+                let state = app.state::<crate::commands::AppState>();
+                // 连接断开前记下协商结果，下次启动时快速恢复
+                state.write_handoff(chrono::Utc::now().timestamp());
+                let hung = tauri::async_runtime::block_on(state.tasks.shutdown(crate::tasks::SHUTDOWN_TIMEOUT));
+                if !hung.is_empty() {
+                    println!("[Tasks] Still running at exit: {:?}", hung);
+                }
+                state.shutdown_webhook();
+                if let Err(e) = state.persist_rule_hits() {
+                    println!("[RuleReview] {}", e);
+                }
+                if let Err(e) = state.persist_channels() {
+                    println!("[Channels] {}", e);
+                }
+                if let Err(e) = state.persist_notification_store() {
+                    println!("[PersistentStore] {}", e);
+                }
+                state.release_data_lock();
+                state.shutdown_stream();
+            }
+        });
 }
 
-fn ensure_main_window_visible(app: &tauri::AppHandle) {
+pub(crate) fn ensure_main_window_visible(app: &tauri::AppHandle) {
     if let Some(win) = app.get_webview_window("main") {
         // 若窗口被最小化，先恢复
         if let Ok(true) = win.is_minimized() {
@@ -180,24 +295,33 @@ fn ensure_main_window_visible(app: &tauri::AppHandle) {
         }
         let _ = win.show();
         let _ = win.set_focus();
+        app.state::<crate::commands::AppState>().set_window_visible(true);
     }
 }
 
 fn toggle_main_window(app: &tauri::AppHandle) {
+    // 仅托盘模式：没有窗口可切换，改为说明原因
+    if !app.state::<crate::commands::AppState>().ui.is_available() {
+        crate::ui_window::show_unavailable_dialog(app);
+        return;
+    }
     if let Some(win) = app.get_webview_window("main") {
         if let Ok(visible) = win.is_visible() {
             if visible {
                 let _ = win.hide();
+                app.state::<crate::commands::AppState>().set_window_visible(false);
             } else {
                 if let Ok(true) = win.is_minimized() {
                     let _ = win.unminimize();
                 }
                 let _ = win.show();
                 let _ = win.set_focus();
+                app.state::<crate::commands::AppState>().set_window_visible(true);
             }
         } else {
             let _ = win.show();
             let _ = win.set_focus();
+            app.state::<crate::commands::AppState>().set_window_visible(true);
         }
     }
 }
@@ -0,0 +1,313 @@
+//! 命令入参的大小上限：数组长度、单个字符串字节数与整体字节数。
+//! 前端 bug 曾一次传入 60 MB 的 id 数组，克隆字符串时长时间占住存储锁；
+//! 超限时在做任何工作之前返回 PayloadTooLarge（带上限与实际大小）。
+//! 所有上限集中在这里；id 数组与文本长度可在设置中调整（不超过硬上限）。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::settings::Settings;
+use crate::relay::RelayAction;
+use crate::store::NotificationFilter;
+
+/// 单次请求的 id 数量（默认 / 硬上限）
+pub const MAX_IDS: usize = 5_000;
+pub const MAX_IDS_HARD: usize = 50_000;
+/// 本地通知标题/正文字节数（默认 / 硬上限）
+pub const MAX_TEXT_BYTES: usize = 16 * 1024;
+pub const MAX_TEXT_BYTES_HARD: usize = 256 * 1024;
+/// 一次批量格式化的时间戳个数
+pub const MAX_TIMESTAMPS: usize = 5_000;
+/// 搜索词、包名等短字符串
+pub const MAX_QUERY_BYTES: usize = 1024;
+/// 文件路径、URL
+pub const MAX_PATH_BYTES: usize = 4096;
+/// 过滤规则条数与单条规则的字段字节数
+pub const MAX_RULES: usize = 500;
+pub const MAX_RULE_FIELD_BYTES: usize = 2048;
+/// 单应用预览级别条数
+pub const MAX_PER_APP_ENTRIES: usize = 2_000;
+/// 重新订阅时的连接数
+pub const MAX_CONNECTIONS: usize = 256;
+/// 导入的预设文件大小
+pub const MAX_PRESET_FILE_BYTES: u64 = 2 * 1024 * 1024;
+/// 单个请求的整体字节数
+pub const MAX_PAYLOAD_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnit {
+    Items,
+    Bytes,
+}
+
+/// 超出上限；以 `PayloadTooLarge: {json}` 的形式返回给前端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayloadTooLarge {
+    pub field: String,
+    pub limit: usize,
+    pub actual: usize,
+    pub unit: SizeUnit,
+}
+
+impl std::fmt::Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PayloadTooLarge: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<PayloadTooLarge> for String {
+    fn from(e: PayloadTooLarge) -> Self {
+        println!("[Limits] Rejected {} ({} > {} {:?})", e.field, e.actual, e.limit, e.unit);
+        e.to_string()
+    }
+}
+
+/// 参数不合法（如时间范围颠倒）；以 `InvalidArgument: {json}` 的形式返回给前端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidArgument {
+    pub field: String,
+    pub message: String,
+}
+
+impl InvalidArgument {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self { field: field.to_string(), message: message.into() }
+    }
+}
+
+impl std::fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InvalidArgument: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<InvalidArgument> for String {
+    fn from(e: InvalidArgument) -> Self {
+        println!("[Limits] Invalid {}: {}", e.field, e.message);
+        e.to_string()
+    }
+}
+
+/// 设置中可调整的上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct PayloadLimits {
+    pub max_ids: usize,
+    pub max_text_bytes: usize,
+}
+
+impl Default for PayloadLimits {
+    fn default() -> Self {
+        Self { max_ids: MAX_IDS, max_text_bytes: MAX_TEXT_BYTES }
+    }
+}
+
+impl PayloadLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_IDS_HARD).contains(&self.max_ids) {
+            return Err(format!("payload_limits.max_ids must be within 1..={}", MAX_IDS_HARD));
+        }
+        if !(1..=MAX_TEXT_BYTES_HARD).contains(&self.max_text_bytes) {
+            return Err(format!("payload_limits.max_text_bytes must be within 1..={}", MAX_TEXT_BYTES_HARD));
+        }
+        Ok(())
+    }
+}
+
+pub fn check_items(field: &str, actual: usize, limit: usize) -> Result<(), PayloadTooLarge> {
+    if actual <= limit {
+        return Ok(());
+    }
+    Err(PayloadTooLarge { field: field.to_string(), limit, actual, unit: SizeUnit::Items })
+}
+
+pub fn check_bytes(field: &str, actual: usize, limit: usize) -> Result<(), PayloadTooLarge> {
+    if actual <= limit {
+        return Ok(());
+    }
+    Err(PayloadTooLarge { field: field.to_string(), limit, actual, unit: SizeUnit::Bytes })
+}
+
+fn check_opt(field: &str, s: Option<&str>, limit: usize) -> Result<(), PayloadTooLarge> {
+    check_bytes(field, s.map_or(0, str::len), limit)
+}
+
+/// 先看长度（O(1)），再看总字节数
+pub fn check_ids(field: &str, ids: &[String], limits: &PayloadLimits) -> Result<(), PayloadTooLarge> {
+    check_items(field, ids.len(), limits.max_ids)?;
+    check_bytes(field, ids.iter().map(String::len).sum(), MAX_PAYLOAD_BYTES)
+}
+
+pub fn check_filter(filter: &NotificationFilter) -> Result<(), PayloadTooLarge> {
+    check_opt("filter.query", filter.query.as_deref(), MAX_QUERY_BYTES)?;
+    check_opt("filter.package", filter.package.as_deref(), MAX_QUERY_BYTES)
+}
+
+pub fn check_settings(settings: &Settings) -> Result<(), PayloadTooLarge> {
+    check_bytes("settings.locale", settings.locale.len(), MAX_QUERY_BYTES)?;
+    check_opt("settings.webhook.url", settings.webhook.url.as_deref(), MAX_PATH_BYTES)?;
+    check_opt("settings.metrics.endpoint", settings.metrics.endpoint.as_deref(), MAX_PATH_BYTES)?;
+    check_bytes("settings.templates", settings.templates.total_bytes(), MAX_RULE_FIELD_BYTES)?;
+    check_items("settings.rules", settings.rules.len(), MAX_RULES)?;
+    for rule in &settings.rules {
+        check_bytes("rule.id", rule.id.len(), MAX_RULE_FIELD_BYTES)?;
+        check_opt("rule.package", rule.package.as_deref(), MAX_RULE_FIELD_BYTES)?;
+        check_opt("rule.pattern", rule.pattern.as_deref(), MAX_RULE_FIELD_BYTES)?;
+    }
+    check_items("settings.app_meta", settings.app_meta.len(), MAX_PER_APP_ENTRIES)?;
+    let per_app = &settings.privacy.per_app;
+    check_items("settings.privacy.per_app", per_app.len(), MAX_PER_APP_ENTRIES)?;
+    check_bytes("settings.privacy.per_app", per_app.keys().map(String::len).sum(), MAX_PAYLOAD_BYTES)
+}
+
+impl AppState {
+    pub(crate) fn payload_limits(&self) -> PayloadLimits {
+        self.settings.read().payload_limits
+    }
+
+    /// 标记已读，返回实际变化的条数；分组汇总通知的 id 连同整组一起标记
+    pub fn mark_read_ids(&self, ids: &[String]) -> Result<usize, String> {
+        check_ids("ids", ids, &self.payload_limits())?;
+        let (changed, ids) = {
+            let mut store = self.store.lock().unwrap();
+            let ids = store.expand_group_summaries(ids);
+            (store.mark_read(&ids), ids)
+        };
+        if changed > 0 {
+            self.emit_counts();
+        }
+        self.relay_forward_ids(RelayAction::MarkRead, &ids);
+        Ok(changed)
+    }
+
+    /// 标记未读，返回实际变化的条数；静音与归档的跳过（见 NotificationStore::mark_unread）
+    pub fn mark_unread_ids(&self, ids: &[String]) -> Result<usize, String> {
+        check_ids("ids", ids, &self.payload_limits())?;
+        let changed = self.store.lock().unwrap().mark_unread(ids);
+        if changed > 0 {
+            self.emit_counts();
+        }
+        self.relay_forward_ids(RelayAction::MarkUnread, ids);
+        Ok(changed)
+    }
+
+    /// 归档/取消归档，返回实际变化的条数
+    pub fn set_archived_ids(&self, ids: &[String], archived: bool) -> Result<usize, String> {
+        check_ids("ids", ids, &self.payload_limits())?;
+        let (changed, counts_changed) = {
+            let mut store = self.store.lock().unwrap();
+            let unread = store.counts().unread;
+            let changed = store.set_archived(ids, archived);
+            (changed, store.counts().unread != unread)
+        };
+        if counts_changed {
+            self.emit_counts();
+        } else if changed > 0 {
+            self.persist_seq_if_due();
+        }
+        Ok(changed)
+    }
+
+    /// 置顶/取消置顶，返回实际变化的条数
+    pub fn set_pinned_ids(&self, ids: &[String], pinned: bool) -> Result<usize, String> {
+        check_ids("ids", ids, &self.payload_limits())?;
+        let changed = self.store.lock().unwrap().set_pinned(ids, pinned);
+        self.persist_seq_if_due();
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Rule, RuleAction};
+    use std::time::{Duration, Instant};
+
+    fn ids(n: usize) -> Vec<String> {
+        (0..n).map(|i| i.to_string()).collect()
+    }
+
+    /// 拒绝必须很快（不做实际工作）
+    fn rejected_fast<T: std::fmt::Debug>(f: impl FnOnce() -> Result<T, String>) -> PayloadTooLarge {
+        let started = Instant::now();
+        let err = f().unwrap_err();
+        assert!(started.elapsed() < Duration::from_millis(200), "slow rejection: {:?}", started.elapsed());
+        let json = err.strip_prefix("PayloadTooLarge: ").expect(&err);
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_store_commands_reject_without_mutation() {
+        let state = AppState::default();
+        state.store.lock().unwrap().upsert(crate::types::Notification { id: "1".into(), ..Default::default() }, false);
+        let seq = state.store.lock().unwrap().seq();
+
+        let mut over = ids(MAX_IDS + 1);
+        over[0] = "1".into();
+        let e = rejected_fast(|| state.mark_read_ids(&over));
+        assert_eq!((e.field.as_str(), e.limit, e.actual, e.unit), ("ids", MAX_IDS, MAX_IDS + 1, SizeUnit::Items));
+        rejected_fast(|| state.set_pinned_ids(&over, true));
+        rejected_fast(|| state.mark_unread_ids(&over));
+        rejected_fast(|| state.set_archived_ids(&over, true));
+        let e = rejected_fast(|| state.create_local_notification("x".repeat(MAX_TEXT_BYTES + 1), String::new(), None, 0));
+        assert_eq!(e.field, "title");
+        assert_eq!(state.store.lock().unwrap().seq(), seq);
+        assert!(!state.store.lock().unwrap().get("1").unwrap().read);
+
+        // 恰好在上限内照常执行
+        over.pop();
+        assert_eq!(state.mark_read_ids(&over), Ok(1));
+    }
+
+    #[test]
+    fn test_limits_adjustable_and_total_bytes_capped() {
+        let state = AppState::default();
+        state.settings.write().payload_limits.max_ids = 10;
+        assert_eq!(rejected_fast(|| state.mark_read_ids(&ids(11))).limit, 10);
+
+        let huge = vec!["x".repeat(MAX_PAYLOAD_BYTES / 4 + 1); 4];
+        let e = check_ids("ids", &huge, &PayloadLimits::default()).unwrap_err();
+        assert_eq!((e.unit, e.limit), (SizeUnit::Bytes, MAX_PAYLOAD_BYTES));
+        assert!(PayloadLimits { max_ids: MAX_IDS_HARD + 1, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_filter_and_settings_guards() {
+        let filter = NotificationFilter { query: Some("q".repeat(MAX_QUERY_BYTES + 1)), ..Default::default() };
+        assert_eq!(check_filter(&filter).unwrap_err().actual, MAX_QUERY_BYTES + 1);
+        let filter = NotificationFilter { package: Some("p".repeat(MAX_QUERY_BYTES + 1)), ..Default::default() };
+        assert_eq!(check_filter(&filter).unwrap_err().field, "filter.package");
+
+        let rule = |i: usize| Rule {
+            id: i.to_string(),
+            enabled: true,
+            package: Some("com.*".into()),
+            channel: None,
+            pattern: None,
+            language: None,
+            action: RuleAction::Mute,
+        };
+        let mut settings = Settings { rules: (0..=MAX_RULES).map(rule).collect(), ..Default::default() };
+        assert_eq!(check_settings(&settings).unwrap_err().field, "settings.rules");
+        settings.rules.truncate(1);
+        settings.rules[0].pattern = Some("a".repeat(MAX_RULE_FIELD_BYTES + 1));
+        assert_eq!(check_settings(&settings).unwrap_err().field, "rule.pattern");
+        settings.rules.clear();
+        settings.privacy.per_app = (0..=MAX_PER_APP_ENTRIES).map(|i| (i.to_string(), Default::default())).collect();
+        assert!(check_settings(&settings).is_err());
+    }
+
+    #[test]
+    fn test_oversized_preset_file_rejected_before_reading() {
+        let dir = crate::storage::temp_dir("limits-preset");
+        let path = dir.join("big.json");
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_len(MAX_PRESET_FILE_BYTES + 1).unwrap();
+        let state = AppState::default();
+        let e = rejected_fast(|| state.import_rules_preset(&path, crate::presets::ImportMode::Merge));
+        assert_eq!(e.field, "preset");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
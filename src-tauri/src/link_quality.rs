@@ -0,0 +1,289 @@
+//! 连接质量：按连接统计接收侧的乱序帧、seq 跳号（丢帧，需要重新同步）、心跳往返时间抖动与每分钟帧数，
+//! 只看最近 WINDOW_SECS 内的数据，据此给出 good / fair / poor 评级（grade 为纯函数）。
+//! 用于区分“手机端没发”与“网络丢了”：评级附在 list_connections 中；
+//! 连续 poor 超过 POOR_WARN_SECS 时推送一次 link-quality-warning（恢复后再次变差会重新提醒）。
+//! 手机端 seq 为 0 表示未编号，不参与乱序/跳号统计。
+
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+/// 统计窗口（秒）
+pub const WINDOW_SECS: i64 = 5 * 60;
+/// 连续 poor 超过该时长后提醒
+pub const POOR_WARN_SECS: i64 = 3 * 60;
+/// 窗口内帧数达到该值才按乱序/跳号占比评级（帧太少时占比没有意义）
+const MIN_FRAMES_FOR_RATIO: usize = 20;
+/// 参与抖动计算的最近往返时间个数
+const MAX_RTT_SAMPLES: usize = 10;
+/// 窗口内最多保留的帧（同步上万条时窗口按条数截断，统计仍有代表性）
+pub const MAX_WINDOW_FRAMES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkGrade {
+    #[default]
+    Good,
+    Fair,
+    Poor,
+}
+
+/// 窗口内的统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LinkStats {
+    pub frames: usize,
+    pub out_of_order: usize,
+    /// seq 跳号次数（中间的帧没有收到）
+    pub gaps: usize,
+    /// 相邻两次往返时间之差的平均值
+    pub rtt_jitter_ms: Option<i64>,
+    pub frames_per_minute: f64,
+}
+
+/// 评级：乱序+跳号占比、跳号次数与抖动中最差的一项决定
+pub fn grade(stats: &LinkStats) -> LinkGrade {
+    let lossy = if stats.frames >= MIN_FRAMES_FOR_RATIO {
+        (stats.out_of_order + stats.gaps) as f64 / stats.frames as f64
+    } else {
+        0.0
+    };
+    let jitter = stats.rtt_jitter_ms.unwrap_or(0);
+    if lossy >= 0.10 || stats.gaps >= 5 || jitter >= 300 {
+        LinkGrade::Poor
+    } else if lossy >= 0.02 || stats.gaps >= 1 || jitter >= 100 {
+        LinkGrade::Fair
+    } else {
+        LinkGrade::Good
+    }
+}
+
+/// 返回给前端的连接质量
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LinkQuality {
+    pub grade: LinkGrade,
+    #[serde(flatten)]
+    pub stats: LinkStats,
+    /// 本次连续 poor 的开始时间
+    pub poor_since: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    InOrder,
+    OutOfOrder,
+    Gap,
+}
+
+#[derive(Debug, Default)]
+pub struct LinkTracker {
+    last_seq: Option<i64>,
+    frames: VecDeque<(i64, Frame)>,
+    // 窗口内乱序/跳号帧数（随 frames 增减，避免每帧扫描整个窗口）
+    out_of_order: usize,
+    gaps: usize,
+    rtts: VecDeque<i64>,
+    poor_since: Option<i64>,
+    warned: bool,
+}
+
+impl LinkTracker {
+    fn trim(&mut self, now: i64) {
+        while self.frames.len() > MAX_WINDOW_FRAMES || self.frames.front().is_some_and(|(t, _)| now - t >= WINDOW_SECS) {
+            match self.frames.pop_front() {
+                Some((_, Frame::OutOfOrder)) => self.out_of_order -= 1,
+                Some((_, Frame::Gap)) => self.gaps -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// 记录收到的一帧
+    pub fn frame(&mut self, now: i64, seq: i64) {
+        let kind = match self.last_seq {
+            _ if seq == 0 => Frame::InOrder,
+            Some(last) if seq <= last => Frame::OutOfOrder,
+            Some(last) if seq > last + 1 => Frame::Gap,
+            _ => Frame::InOrder,
+        };
+        if seq != 0 && kind != Frame::OutOfOrder {
+            self.last_seq = Some(seq);
+        }
+        match kind {
+            Frame::OutOfOrder => self.out_of_order += 1,
+            Frame::Gap => self.gaps += 1,
+            Frame::InOrder => {}
+        }
+        self.frames.push_back((now, kind));
+        self.trim(now);
+    }
+
+    pub fn rtt(&mut self, rtt_ms: i64) {
+        if self.rtts.len() == MAX_RTT_SAMPLES {
+            self.rtts.pop_front();
+        }
+        self.rtts.push_back(rtt_ms);
+    }
+
+    pub fn stats(&mut self, now: i64) -> LinkStats {
+        self.trim(now);
+        let diffs: Vec<i64> = self.rtts.iter().zip(self.rtts.iter().skip(1)).map(|(a, b)| (a - b).abs()).collect();
+        let span_secs = self.frames.front().map_or(0, |(t, _)| now - t).max(60);
+        LinkStats {
+            frames: self.frames.len(),
+            out_of_order: self.out_of_order,
+            gaps: self.gaps,
+            rtt_jitter_ms: (!diffs.is_empty()).then(|| diffs.iter().sum::<i64>() / diffs.len() as i64),
+            frames_per_minute: self.frames.len() as f64 * 60.0 / span_secs as f64,
+        }
+    }
+
+    /// 重新评级；连续 poor 超过 POOR_WARN_SECS 且本轮尚未提醒时返回 true
+    pub fn evaluate(&mut self, now: i64) -> (LinkQuality, bool) {
+        let stats = self.stats(now);
+        let grade = grade(&stats);
+        if grade == LinkGrade::Poor {
+            let since = *self.poor_since.get_or_insert(now);
+            let warn = !self.warned && now - since >= POOR_WARN_SECS;
+            self.warned |= warn;
+            (LinkQuality { grade, stats, poor_since: Some(since) }, warn)
+        } else {
+            self.poor_since = None;
+            self.warned = false;
+            (LinkQuality { grade, stats, poor_since: None }, false)
+        }
+    }
+}
+
+/// connection_id -> 统计
+#[derive(Default)]
+pub struct LinkQualityTracker {
+    connections: Mutex<HashMap<String, LinkTracker>>,
+}
+
+impl LinkQualityTracker {
+    pub fn quality(&self, connection_id: &str, now: i64) -> LinkQuality {
+        self.connections.lock().get_mut(connection_id).map(|t| t.evaluate(now).0).unwrap_or_default()
+    }
+
+    /// 各连接中最长的帧窗口
+    pub fn max_window(&self) -> usize {
+        self.connections.lock().values().map(|t| t.frames.len()).max().unwrap_or(0)
+    }
+
+    /// 最后收到的手机端 seq
+    pub fn last_seq(&self, connection_id: &str) -> Option<i64> {
+        self.connections.lock().get(connection_id).and_then(|t| t.last_seq)
+    }
+}
+
+impl AppState {
+    /// 入库前记录收到的帧
+    pub(crate) fn record_frame(&self, connection_id: &str, seq: i64, now: i64) {
+        self.link_quality.connections.lock().entry(connection_id.to_string()).or_default().frame(now, seq);
+        self.check_link_quality(connection_id, now);
+    }
+
+    /// 时钟同步得到的往返时间
+    pub(crate) fn record_rtt(&self, connection_id: &str, rtt_ms: i64, now: i64) {
+        self.link_quality.connections.lock().entry(connection_id.to_string()).or_default().rtt(rtt_ms);
+        self.check_link_quality(connection_id, now);
+    }
+
+    fn check_link_quality(&self, connection_id: &str, now: i64) {
+        let (quality, warn) = match self.link_quality.connections.lock().get_mut(connection_id) {
+            Some(tracker) => tracker.evaluate(now),
+            None => return,
+        };
+        if !warn {
+            return;
+        }
+        println!("[Link] {} has been poor since {:?}: {:?}", connection_id, quality.poor_since, quality.stats);
+        let mut payload = serde_json::to_value(&quality).unwrap_or_default();
+        payload["connection_id"] = connection_id.into();
+        self.events.emit("link-quality-warning", payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(frames: usize, out_of_order: usize, gaps: usize, jitter: Option<i64>) -> LinkStats {
+        LinkStats { frames, out_of_order, gaps, rtt_jitter_ms: jitter, frames_per_minute: 0.0 }
+    }
+
+    #[test]
+    fn test_grade_thresholds() {
+        assert_eq!(grade(&LinkStats::default()), LinkGrade::Good);
+        assert_eq!(grade(&stats(200, 1, 0, Some(20))), LinkGrade::Good);
+        assert_eq!(grade(&stats(100, 0, 1, None)), LinkGrade::Fair);
+        assert_eq!(grade(&stats(100, 0, 0, Some(150))), LinkGrade::Fair);
+        assert_eq!(grade(&stats(50, 5, 0, None)), LinkGrade::Poor);
+        // 帧太少时不看占比
+        assert_eq!(grade(&stats(3, 1, 0, None)), LinkGrade::Good);
+        assert_eq!(grade(&stats(1000, 0, 5, None)), LinkGrade::Poor);
+        assert_eq!(grade(&stats(0, 0, 0, Some(400))), LinkGrade::Poor);
+    }
+
+    /// 稳定链路 -> 持续丢帧的链路 -> 恢复
+    #[test]
+    fn test_synthetic_history_warns_once_per_episode() {
+        let mut t = LinkTracker::default();
+        for i in 1..=100 {
+            t.frame(i, i);
+            t.rtt(40 + i % 3);
+        }
+        let (q, warn) = t.evaluate(100);
+        assert_eq!((q.grade, warn, q.stats.frames_per_minute.round()), (LinkGrade::Good, false, 61.0));
+
+        // 每 5 帧跳号一次，另有乱序重发
+        let mut seq = 100;
+        let mut warnings = 0;
+        for now in 101..=400 {
+            seq += if now % 5 == 0 { 3 } else { 1 };
+            t.frame(now, seq);
+            if now % 20 == 0 {
+                t.frame(now, seq - 4);
+            }
+            warnings += t.evaluate(now).1 as usize;
+        }
+        let (q, _) = t.evaluate(400);
+        assert_eq!(q.grade, LinkGrade::Poor);
+        assert_eq!(q.stats.out_of_order, 15);
+        assert_eq!(warnings, 1);
+
+        // 窗口滑过之后恢复，再次变差会重新提醒
+        for now in 401..=800 {
+            seq += 1;
+            t.frame(now, seq);
+        }
+        assert_eq!(t.evaluate(800).0, LinkQuality { grade: LinkGrade::Good, stats: t.stats(800), poor_since: None });
+        for i in 0..10 {
+            t.rtt(if i % 2 == 0 { 50 } else { 900 });
+        }
+        assert!(!t.evaluate(801).1);
+        assert!(t.evaluate(801 + POOR_WARN_SECS).1);
+    }
+
+    #[test]
+    fn test_state_emits_warning() {
+        let state = AppState::default();
+        for i in 0..30 {
+            state.record_frame("pixel", i * 10, 1000 + i);
+        }
+        assert!(state.events.take_captured().is_empty());
+        // 第 7 帧起已有 5 次跳号
+        state.record_rtt("pixel", 30, 1005 + POOR_WARN_SECS);
+        assert!(state.events.take_captured().is_empty());
+        state.record_rtt("pixel", 30, 1006 + POOR_WARN_SECS);
+        let events = state.events.take_captured();
+        assert_eq!(events[0].0, "link-quality-warning");
+        assert_eq!(events[0].1["connection_id"], "pixel");
+        assert_eq!(events[0].1["grade"], "poor");
+        assert_eq!(state.link_quality.quality("pixel", 1006 + POOR_WARN_SECS).stats.gaps, 28);
+    }
+}
@@ -0,0 +1,321 @@
+//! 夜间低功耗模式：长连接加心跳会让手机整夜频繁唤醒射频。
+//! 在设定时段内（沿用免打扰时段，或单独设定），桌面端发送 set_push_mode 请手机端每 interval_s 秒批量推送一次，
+//! 同时放宽心跳间隔与读超时，并在 list_connections 中标记为低功耗，界面据此说明延迟。
+//! 离开时段（或用户关闭）时恢复即时推送，并请求一次 sync 补齐。
+//! 手机端未声明 batched_push 能力时不发送控制消息，只在桌面端放宽心跳与读超时，避免误判断线。
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::android_client::AndroidSocketClient;
+use crate::capabilities::{self, BATCHED_PUSH};
+use crate::commands::AppState;
+use crate::importance::{self, QuietHours};
+use crate::settings::Settings;
+use crate::wall_clock;
+
+/// 正常模式下的心跳间隔（与 clock_sync 维护任务一致）
+pub const NORMAL_HEARTBEAT_SECS: i64 = 5 * 60;
+/// 读超时在批量间隔之外的余量
+const READ_TIMEOUT_GRACE_SECS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct LowPowerSettings {
+    pub enabled: bool,
+    /// true 时使用免打扰时段，否则使用 schedule
+    pub follow_quiet_hours: bool,
+    /// 单独设定的时段（其中的 enabled 不起作用）
+    pub schedule: QuietHours,
+    /// 手机端批量推送的间隔（秒）
+    pub interval_s: u32,
+}
+
+impl Default for LowPowerSettings {
+    fn default() -> Self {
+        Self { enabled: false, follow_quiet_hours: true, schedule: QuietHours::default(), interval_s: 300 }
+    }
+}
+
+impl LowPowerSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(60..=3600).contains(&self.interval_s) {
+            return Err("low_power.interval_s must be within 60..=3600".to_string());
+        }
+        self.schedule.validate().map_err(|e| format!("low_power.schedule: {}", e))
+    }
+}
+
+/// 当前时刻是否处于低功耗时段
+pub fn in_window(settings: &Settings, minute_of_day: u16) -> bool {
+    let low_power = &settings.low_power;
+    if !low_power.enabled {
+        return false;
+    }
+    let schedule = if low_power.follow_quiet_hours { &settings.quiet_hours } else { &low_power.schedule };
+    QuietHours { enabled: true, ..schedule.clone() }.contains(minute_of_day)
+}
+
+/// 低功耗时的心跳间隔：至少两个批量间隔，不短于正常间隔
+pub fn heartbeat_secs(interval_s: u32) -> i64 {
+    (interval_s as i64 * 2).max(NORMAL_HEARTBEAT_SECS)
+}
+
+/// 低功耗时的读超时：两个批量间隔之内没有数据不算断线
+pub fn read_timeout(interval_s: u32) -> Duration {
+    Duration::from_secs(interval_s as u64 * 2 + READ_TIMEOUT_GRACE_SECS)
+}
+
+/// list_connections 中的低功耗状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LowPowerStatus {
+    pub since: i64,
+    pub interval_s: u32,
+    /// 手机端是否已切换为批量推送（false 表示只在桌面端放宽）
+    pub remote: bool,
+    /// 新通知最多延迟的秒数
+    pub max_delay_s: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LinkPower {
+    status: LowPowerStatus,
+    // 单调时钟秒数（wall_clock::mono_secs），不受系统时间调整影响
+    last_heartbeat: i64,
+}
+
+/// 处于低功耗模式的连接
+#[derive(Default)]
+pub struct LowPower {
+    links: Mutex<HashMap<String, LinkPower>>,
+}
+
+impl LowPower {
+    pub fn status(&self, connection_id: &str) -> Option<LowPowerStatus> {
+        self.links.lock().get(connection_id).map(|l| l.status)
+    }
+
+    /// 心跳是否到期（now 为 wall_clock::mono_secs）；正常模式总是到期，低功耗时按放宽后的间隔
+    pub fn heartbeat_due(&self, connection_id: &str, now: i64) -> bool {
+        let mut links = self.links.lock();
+        let Some(link) = links.get_mut(connection_id) else {
+            return true;
+        };
+        if now - link.last_heartbeat < heartbeat_secs(link.status.interval_s) {
+            return false;
+        }
+        link.last_heartbeat = now;
+        true
+    }
+
+    /// 连接断开时丢弃
+    pub fn forget(&self, connection_id: &str) {
+        self.links.lock().remove(connection_id);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PowerModeChanged<'a> {
+    pub connection_id: &'a str,
+    /// None 表示恢复即时推送
+    pub low_power: Option<LowPowerStatus>,
+}
+
+impl AppState {
+    /// 按当前本地时间检查时段并切换（由维护任务定期调用，设置变更后也会调用）
+    pub fn apply_low_power(&self) -> Result<String, String> {
+        self.apply_low_power_at(chrono::Utc::now().timestamp(), importance::local_minute_of_day())
+    }
+
+    pub(crate) fn apply_low_power_at(&self, now: i64, minute_of_day: u16) -> Result<String, String> {
+        let (active, interval_s) = {
+            let settings = self.settings.read();
+            (in_window(&settings, minute_of_day), settings.low_power.interval_s)
+        };
+        let clients: Vec<(String, std::sync::Arc<AndroidSocketClient>)> =
+            self.clients.read().iter().map(|(id, c)| (id.clone(), c.clone())).collect();
+        let mut changed = 0;
+        let mut errors = Vec::new();
+        for (id, client) in clients {
+            let current = self.low_power.status(&id);
+            let result = match (active, current) {
+                (true, None) => self.enter_low_power(&id, &client, interval_s, now),
+                (true, Some(s)) if s.interval_s != interval_s => self.enter_low_power(&id, &client, interval_s, now),
+                (false, Some(s)) => self.exit_low_power(&id, &client, s),
+                _ => continue,
+            };
+            changed += 1;
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", id, e));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+        Ok(format!("{} ({} changed)", if active { "low-power" } else { "normal" }, changed))
+    }
+
+    fn enter_low_power(&self, connection_id: &str, client: &AndroidSocketClient, interval_s: u32, now: i64) -> Result<(), String> {
+        let remote = capabilities::supports(client.capabilities().as_deref(), BATCHED_PUSH);
+        client.set_read_timeout(read_timeout(interval_s))?;
+        if remote {
+            client.send_action(&serde_json::json!({ "action": "set_push_mode", "mode": "batched", "interval_s": interval_s }))?;
+        }
+        // 间隔调整时保留原来的开始时间（恢复时从这里补齐）
+        let since = self.low_power.status(connection_id).map_or(now, |s| s.since);
+        let status = LowPowerStatus { since, interval_s, remote, max_delay_s: if remote { interval_s } else { 0 } };
+        self.low_power.links.lock().insert(connection_id.to_string(), LinkPower { status, last_heartbeat: wall_clock::mono_secs() });
+        println!("[LowPower] {} -> batched every {}s (remote={})", connection_id, interval_s, remote);
+        self.events.emit("power-mode-changed", PowerModeChanged { connection_id, low_power: Some(status) });
+        Ok(())
+    }
+
+    fn exit_low_power(&self, connection_id: &str, client: &AndroidSocketClient, status: LowPowerStatus) -> Result<(), String> {
+        self.low_power.forget(connection_id);
+        client.set_read_timeout(crate::transport::READ_TIMEOUT)?;
+        if status.remote {
+            client.send_action(&serde_json::json!({ "action": "set_push_mode", "mode": "immediate" }))?;
+            let mut request = serde_json::json!({ "action": "sync", "since": status.since });
+            if let Some(horizon) = self.sync_horizon(connection_id) {
+                request["horizon"] = horizon.into();
+            }
+            client.send_action(&request)?;
+        }
+        println!("[LowPower] {} -> immediate (catch-up sync={})", connection_id, status.remote);
+        self.events.emit("power-mode-changed", PowerModeChanged { connection_id, low_power: None });
+        Ok(())
+    }
+
+    /// 连接（重连）成功后，处于时段内则立即切换
+    pub(crate) fn apply_low_power_on_connect(&self, connection_id: &str, client: &AndroidSocketClient) {
+        let (active, interval_s) = {
+            let settings = self.settings.read();
+            (in_window(&settings, importance::local_minute_of_day()), settings.low_power.interval_s)
+        };
+        self.low_power.forget(connection_id);
+        if !active {
+            return;
+        }
+        if let Err(e) = self.enter_low_power(connection_id, client, interval_s, chrono::Utc::now().timestamp()) {
+            println!("[LowPower] Failed to enter low-power for {}: {}", connection_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::transport::Transport;
+
+    struct Phone {
+        login: String,
+        sent: Arc<Mutex<Vec<String>>>,
+        timeouts: Arc<Mutex<Vec<Duration>>>,
+    }
+
+    impl Transport for Phone {
+        fn send_line(&mut self, line: &str) -> Result<(), String> {
+            self.sent.lock().push(line.to_string());
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(self.login.clone())
+        }
+        fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), String> {
+            self.timeouts.lock().push(timeout);
+            Ok(())
+        }
+    }
+
+    type Recorded = (Arc<Mutex<Vec<String>>>, Arc<Mutex<Vec<Duration>>>);
+
+    fn connect(state: &AppState, id: &str, capabilities: &[&str]) -> Recorded {
+        let login = serde_json::json!({ "success": true, "protocolVersion": 3, "capabilities": capabilities });
+        let (sent, timeouts) = Recorded::default();
+        let phone = Phone { login: login.to_string(), sent: sent.clone(), timeouts: timeouts.clone() };
+        let client = AndroidSocketClient::with_transport(Box::new(phone), id.into(), Arc::default());
+        client.login("t1").unwrap();
+        state.register_client(id.to_string(), client, "t1", None);
+        sent.lock().clear();
+        timeouts.lock().clear();
+        (sent, timeouts)
+    }
+
+    fn frames(sent: &Mutex<Vec<String>>) -> Vec<serde_json::Value> {
+        sent.lock().drain(..).map(|l| serde_json::from_str(&l).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_window_follows_quiet_hours_or_schedule() {
+        let mut settings = Settings::default();
+        assert!(!in_window(&settings, 23 * 60), "off by default");
+        settings.low_power.enabled = true;
+        // 沿用免打扰时段的时间（免打扰本身未开启也可以）
+        assert!(in_window(&settings, 23 * 60) && in_window(&settings, 60) && !in_window(&settings, 12 * 60));
+        settings.low_power.follow_quiet_hours = false;
+        settings.low_power.schedule = QuietHours { enabled: false, start_minute: 60, end_minute: 6 * 60 };
+        assert!(!in_window(&settings, 23 * 60) && in_window(&settings, 2 * 60));
+        assert_eq!(heartbeat_secs(300), 600);
+        assert_eq!(heartbeat_secs(60), NORMAL_HEARTBEAT_SECS);
+        settings.low_power.interval_s = 10;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_negotiated_and_fallback_modes() {
+        let state = AppState::default();
+        let (batched, batched_timeouts) = connect(&state, "new", &["actions", BATCHED_PUSH]);
+        let (legacy, legacy_timeouts) = connect(&state, "old", &["actions"]);
+        state.settings.write().low_power.enabled = true;
+        let night = 23 * 60;
+        let t0 = 1_700_000_000;
+
+        assert_eq!(state.apply_low_power_at(t0, night).unwrap(), "low-power (2 changed)");
+        assert_eq!(frames(&batched), [serde_json::json!({ "action": "set_push_mode", "mode": "batched", "interval_s": 300 })]);
+        assert!(frames(&legacy).is_empty(), "no control frame without the capability");
+        assert_eq!(*legacy_timeouts.lock(), [read_timeout(300)]);
+        assert!(state.low_power.status("new").unwrap().remote);
+        assert_eq!(state.low_power.status("old").unwrap().max_delay_s, 0);
+        assert_eq!(state.apply_low_power_at(t0 + 60, night).unwrap(), "low-power (0 changed)");
+
+        // 心跳放宽
+        let m0 = crate::wall_clock::mono_secs();
+        assert!(!state.low_power.heartbeat_due("old", m0 + NORMAL_HEARTBEAT_SECS));
+        assert!(state.low_power.heartbeat_due("old", m0 + 600));
+        assert!(state.low_power.heartbeat_due("unknown", m0));
+
+        // 早上离开时段：恢复即时推送并补齐
+        assert_eq!(state.apply_low_power_at(t0 + 8 * 3600, 8 * 60).unwrap(), "normal (2 changed)");
+        assert_eq!(
+            frames(&batched),
+            [
+                serde_json::json!({ "action": "set_push_mode", "mode": "immediate" }),
+                serde_json::json!({ "action": "sync", "since": t0 }),
+            ]
+        );
+        assert!(frames(&legacy).is_empty());
+        assert_eq!(batched_timeouts.lock().last(), Some(&crate::transport::READ_TIMEOUT));
+        assert_eq!(state.low_power.status("new"), None);
+        let events = state.events.take_captured();
+        assert_eq!(events.iter().filter(|(e, _)| e == "power-mode-changed").count(), 4);
+    }
+
+    #[test]
+    fn test_user_toggle_off_restores_immediately() {
+        let state = AppState::default();
+        let (sent, _) = connect(&state, "c1", &[BATCHED_PUSH]);
+        state.settings.write().low_power.enabled = true;
+        state.apply_low_power_at(100, 23 * 60).unwrap();
+        frames(&sent);
+        state.settings.write().low_power.enabled = false;
+        state.apply_low_power_at(200, 23 * 60).unwrap();
+        assert_eq!(frames(&sent)[0]["mode"], "immediate");
+        assert_eq!(state.low_power.status("c1"), None);
+    }
+}
@@ -0,0 +1,464 @@
+//! 后台维护调度：按各自间隔依次运行 JOBS 中登记的任务，每个任务有超时，卡住的不会拖住其他任务；
+//! 记录最近一次的运行结果，失败或超时经后台错误总线上报。
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+use crate::error_bus::BackgroundError;
+use crate::settings::RetentionSettings;
+use crate::tombstones::RemovalReason;
+
+/// 调度器检查间隔
+pub const TICK: Duration = Duration::from_secs(5);
+
+pub struct Job {
+    pub id: &'static str,
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// 成功时返回简短说明（如处理条数）
+    pub run: fn(&AppState) -> Result<String, String>,
+}
+
+/// 登记的维护任务
+pub const JOBS: &[Job] = &[
+    // 排在到期检查之前：跳变后先修正，再按新时间判断到期
+    Job {
+        id: "clock_jump",
+        interval: Duration::from_secs(15),
+        timeout: Duration::from_secs(10),
+        run: |state| state.check_clock_jump(),
+    },
+    Job {
+        id: "snooze_wakeup",
+        interval: Duration::from_secs(15),
+        timeout: Duration::from_secs(10),
+        run: |state| Ok(format!("{} woken", state.wake_due(state.wall_clock.now()).len())),
+    },
+    Job {
+        id: "retention",
+        interval: Duration::from_secs(10 * 60),
+        timeout: Duration::from_secs(30),
+        run: |state| Ok(format!("{} evicted", state.enforce_retention_now(state.wall_clock.now()))),
+    },
+    Job {
+        id: "auto_read",
+        interval: Duration::from_secs(10 * 60),
+        timeout: Duration::from_secs(30),
+        run: |state| Ok(format!("{} marked read", state.auto_read_now(state.wall_clock.now()))),
+    },
+    Job {
+        id: "seq_checkpoint",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(10),
+        run: |state| {
+            state.persist_seq_if_due();
+            Ok(String::new())
+        },
+    },
+    Job {
+        id: "clock_sync",
+        interval: Duration::from_secs(5 * 60),
+        timeout: Duration::from_secs(15),
+        run: |state| Ok(format!("{} synced", state.sync_clocks()?)),
+    },
+    Job {
+        id: "wizard_expiry",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(5),
+        run: |state| Ok(format!("{} expired", state.wizard.purge_expired(state.wall_clock.now()))),
+    },
+    Job {
+        id: "backfill_expiry",
+        interval: Duration::from_secs(15),
+        timeout: Duration::from_secs(5),
+        run: |state| Ok(format!("{} finished", state.expire_backfills(state.wall_clock.now()))),
+    },
+    Job {
+        id: "compaction",
+        interval: Duration::from_secs(60 * 60),
+        timeout: Duration::from_secs(60),
+        run: |state| state.compact_if_needed(),
+    },
+    Job {
+        id: "derived_backfill",
+        interval: Duration::from_secs(30),
+        timeout: Duration::from_secs(30),
+        run: |state| state.run_derived_backfill(),
+    },
+    Job {
+        id: "volume_checkpoint",
+        interval: Duration::from_secs(5 * 60),
+        timeout: Duration::from_secs(10),
+        run: |state| state.persist_volume(),
+    },
+    Job {
+        id: "low_power",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(15),
+        run: |state| state.apply_low_power(),
+    },
+    // 仅调试构建核对存储的派生结构
+    Job {
+        id: "store_integrity",
+        interval: Duration::from_secs(10 * 60),
+        timeout: Duration::from_secs(10),
+        run: |state| state.debug_integrity_check(),
+    },
+    Job {
+        id: "mute_expiry",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(10),
+        run: |state| Ok(format!("{} expired", state.expire_mutes(state.wall_clock.now()))),
+    },
+    Job {
+        id: "rule_hits",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(10),
+        run: |state| state.persist_rule_hits(),
+    },
+    Job {
+        id: "channel_catalog",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(10),
+        run: |state| state.persist_channels(),
+    },
+    // 持久化模式下重写通知快照（见 persistent_store）
+    Job {
+        id: "notification_store",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(30),
+        run: |state| state.persist_notification_store(),
+    },
+    // 排在重试之前：接管数据目录后立即补写
+    Job {
+        id: "data_lock",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(10),
+        run: |state| state.check_data_lock(state.wall_clock.now()),
+    },
+    // 重写只保存在内存中的数据文件（见 storage）
+    Job {
+        id: "storage_retry",
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(10),
+        run: |state| state.retry_storage_writes(),
+    },
+    // 断网检测，恢复后重连
+    Job {
+        id: "network_watch",
+        interval: Duration::from_secs(10),
+        timeout: Duration::from_secs(30),
+        run: |state| state.check_network(state.wall_clock.now()),
+    },
+    Job {
+        id: "metrics_upload",
+        interval: Duration::from_secs(60 * 60),
+        timeout: Duration::from_secs(15),
+        run: |state| state.upload_metrics_if_due(state.wall_clock.now()),
+    },
+    // 系统勿扰状态
+    Job {
+        id: "os_focus",
+        interval: crate::os_focus::CACHE_TTL,
+        timeout: Duration::from_secs(5),
+        run: |state| state.refresh_os_focus(),
+    },
+];
+
+pub fn find_job(id: &str) -> Option<&'static Job> {
+    JOBS.iter().find(|j| j.id == id)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobOutcome {
+    Ok { detail: String },
+    Failed { error: String },
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct JobStatus {
+    pub id: String,
+    pub interval_secs: u64,
+    pub last_run_at: Option<i64>,
+    pub last_duration_ms: Option<u64>,
+    pub last_outcome: Option<JobOutcome>,
+    pub next_run_at: Option<i64>,
+}
+
+#[derive(Clone)]
+struct LastRun {
+    at: i64,
+    started: Instant,
+    duration: Duration,
+    outcome: JobOutcome,
+}
+
+#[derive(Default)]
+pub struct Maintenance {
+    last: Mutex<HashMap<&'static str, LastRun>>,
+    // 任务依次运行：调度循环与手动触发共用
+    running: tokio::sync::Mutex<()>,
+}
+
+impl Maintenance {
+    /// 到期的任务（从未运行过的视为到期）
+    pub fn due(&self, now: Instant) -> Vec<&'static Job> {
+        let last = self.last.lock();
+        JOBS.iter()
+            .filter(|j| last.get(j.id).is_none_or(|r| now.duration_since(r.started) >= j.interval))
+            .collect()
+    }
+
+    fn record(&self, job: &Job, run: LastRun) {
+        self.last.lock().insert(job.id, run);
+    }
+
+    pub fn status(&self) -> Vec<JobStatus> {
+        let last = self.last.lock();
+        JOBS.iter()
+            .map(|j| {
+                let run = last.get(j.id);
+                JobStatus {
+                    id: j.id.to_string(),
+                    interval_secs: j.interval.as_secs(),
+                    last_run_at: run.map(|r| r.at),
+                    last_duration_ms: run.map(|r| r.duration.as_millis() as u64),
+                    last_outcome: run.map(|r| r.outcome.clone()),
+                    next_run_at: run.map(|r| r.at + j.interval.as_secs() as i64),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 在阻塞线程中运行，超时后不再等待（该线程自行结束）
+pub async fn run_with_timeout<F>(f: F, timeout: Duration) -> JobOutcome
+where
+    F: FnOnce() -> Result<String, String> + Send + 'static,
+{
+    match tokio::time::timeout(timeout, tokio::task::spawn_blocking(f)).await {
+        Ok(Ok(Ok(detail))) => JobOutcome::Ok { detail },
+        Ok(Ok(Err(error))) => JobOutcome::Failed { error },
+        Ok(Err(e)) => JobOutcome::Failed { error: format!("Job panicked: {}", e) },
+        Err(_) => JobOutcome::TimedOut,
+    }
+}
+
+/// 运行一个任务并记录结果；失败/超时上报错误总线
+pub async fn run_job(app: &AppHandle, job: &'static Job) -> JobOutcome {
+    let state = app.state::<AppState>();
+    let _guard = state.maintenance.running.lock().await;
+    let at = chrono::Utc::now().timestamp();
+    let started = Instant::now();
+    let handle = app.clone();
+    let outcome = run_with_timeout(move || (job.run)(&handle.state::<AppState>()), job.timeout).await;
+    let duration = started.elapsed();
+
+    match &outcome {
+        JobOutcome::Ok { .. } => {}
+        JobOutcome::Failed { error } => {
+            state.report_error(BackgroundError::new("maintenance", "job_failed", format!("{}: {}", job.id, error)));
+        }
+        JobOutcome::TimedOut => {
+            state.report_error(BackgroundError::new(
+                "maintenance",
+                "job_timeout",
+                format!("{} timed out after {:?}", job.id, job.timeout),
+            ));
+        }
+    }
+    state.maintenance.record(job, LastRun { at, started, duration, outcome: outcome.clone() });
+    outcome
+}
+
+/// 调度循环（setup 中登记为后台任务），令牌取消后在当前任务结束后退出
+pub async fn run_loop(app: AppHandle, token: CancellationToken) {
+    loop {
+        let due = app.state::<AppState>().maintenance.due(Instant::now());
+        for job in due {
+            if token.is_cancelled() {
+                return;
+            }
+            run_job(&app, job).await;
+        }
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(TICK) => {}
+        }
+    }
+}
+
+impl AppState {
+    /// 按当前设置执行一次保留策略（如调低上限后）：先清理超过 max_age_days 的已读通知，再按条数上限淘汰，返回淘汰条数
+    pub fn enforce_retention_now(&self, now: i64) -> usize {
+        let retention = self.settings.read().retention.clone();
+        let mut removed = match retention.max_age_days {
+            Some(days) => self.store.lock().unwrap().purge_read_before(now - days as i64 * 86_400),
+            None => Vec::new(),
+        };
+        removed.extend(self.store.lock().unwrap().enforce_retention(&retention).removed);
+        let n = removed.len();
+        self.on_removed(&removed, RemovalReason::RetentionEvicted);
+        if n > 0 {
+            println!("[Maintenance] Retention removed {} notifications", n);
+            self.audit("retention_purge", AuditSource::Background, n, serde_json::to_value(&retention).unwrap_or_default());
+        }
+        n
+    }
+
+    pub fn retention_policy(&self) -> RetentionSettings {
+        self.settings.read().retention.clone()
+    }
+
+    /// 修改保留策略并写入设置，随即按新策略清理一次。
+    /// 校验、写盘成功后才替换内存中的设置，整个过程持有写锁，写盘失败时设置保持不变
+    pub fn set_retention_policy(&self, retention: RetentionSettings, now: i64) -> Result<RetentionSettings, String> {
+        {
+            let mut settings = self.settings.write();
+            let mut candidate = settings.clone();
+            candidate.retention = retention.clone();
+            candidate.validate()?;
+            self.storage.save(crate::commands::SETTINGS_FILE, &candidate)?;
+            *settings = candidate;
+        }
+        self.audit("set_retention_policy", AuditSource::Command, 1, serde_json::to_value(&retention).unwrap_or_default());
+        self.enforce_retention_now(now);
+        Ok(retention)
+    }
+
+    /// 超过 auto_read_after_hours 仍未读的通知批量标记为已读（置顶与 high/urgent 的除外），返回条数。
+    /// 只推送一次 counts-changed，并以 auto_read 写入审计日志，便于用户了解未读数为何下降。
+    pub fn auto_read_now(&self, now: i64) -> usize {
+        let Some(hours) = self.settings.read().auto_read_after_hours else {
+            return 0;
+        };
+        let marked = self.store.lock().unwrap().mark_read_older_than(now - hours as i64 * 3600);
+        if marked.is_empty() {
+            return 0;
+        }
+        // 通知并未删除：记入审计日志而不是删除记录
+        self.audit("auto_read", AuditSource::Background, marked.len(), serde_json::json!({ "after_hours": hours }));
+        self.emit_counts();
+        println!("[Maintenance] Auto-read {} notifications older than {}h", marked.len(), hours);
+        marked.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::importance::Importance;
+
+    #[tokio::test]
+    async fn test_stuck_job_times_out() {
+        let outcome = run_with_timeout(
+            || {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok("late".into())
+            },
+            Duration::from_millis(20),
+        )
+        .await;
+        assert_eq!(outcome, JobOutcome::TimedOut);
+        let failed = run_with_timeout(|| Err("disk full".into()), Duration::from_secs(1)).await;
+        assert_eq!(failed, JobOutcome::Failed { error: "disk full".into() });
+    }
+
+    #[test]
+    fn test_due_follows_intervals() {
+        let m = Maintenance::default();
+        let t0 = Instant::now();
+        assert_eq!(m.due(t0).len(), JOBS.len());
+        for job in JOBS {
+            let outcome = JobOutcome::Ok { detail: String::new() };
+            m.record(job, LastRun { at: 0, started: t0, duration: Duration::ZERO, outcome });
+        }
+        assert!(m.due(t0 + Duration::from_secs(1)).is_empty());
+        let ids: Vec<&str> = m.due(t0 + Duration::from_secs(60)).iter().map(|j| j.id).collect();
+        assert_eq!(ids, ["clock_jump", "snooze_wakeup", "seq_checkpoint", "wizard_expiry", "backfill_expiry", "derived_backfill", "low_power", "mute_expiry", "rule_hits", "channel_catalog", "notification_store", "data_lock", "storage_retry", "network_watch"]);
+        assert_eq!(m.status()[2].next_run_at, Some(600));
+    }
+
+    #[test]
+    fn test_retention_job_applies_lowered_cap() {
+        let state = AppState::default();
+        for i in 0..5 {
+            state.store.lock().unwrap().upsert(
+                crate::types::Notification { id: i.to_string(), posted_at: Some(i), ..Default::default() },
+                true,
+            );
+        }
+        state.settings.write().retention.max_items = 3;
+        assert_eq!((find_job("retention").unwrap().run)(&state).unwrap(), "2 evicted");
+        assert_eq!(state.counts().total, 3);
+    }
+
+    #[test]
+    fn test_max_age_purges_only_old_read() {
+        let state = AppState::default();
+        let day = 86_400;
+        for i in 0..4 {
+            let n = crate::types::Notification { id: i.to_string(), posted_at: Some(i * day), ..Default::default() };
+            state.store.lock().unwrap().upsert(n, i != 1);
+        }
+        state.store.lock().unwrap().set_pinned(&["2".to_string()], true);
+        let policy = RetentionSettings { max_age_days: Some(1), ..state.retention_policy() };
+        assert!(state.set_retention_policy(RetentionSettings { max_age_days: Some(0), ..policy.clone() }, 0).is_err());
+        assert_eq!(state.retention_policy().max_age_days, None);
+
+        // 写盘失败：设置与存储都不变
+        let dir = crate::storage::temp_dir("retention-policy");
+        state.storage.set_base_dir(dir.clone());
+        state.storage.set_read_only(Some("locked".into()));
+        assert!(state.set_retention_policy(policy.clone(), 4 * day - 1).is_err());
+        assert_eq!(state.retention_policy().max_age_days, None);
+        assert_eq!(state.counts().total, 4);
+        state.storage.set_read_only(None);
+        let _ = std::fs::remove_dir_all(dir);
+
+        // 第 4 天：0 已读且过期被清理；1 未读、2 置顶、3 未过期保留
+        state.set_retention_policy(policy, 4 * day - 1).unwrap();
+        let store = state.store.lock().unwrap();
+        assert!(store.get("0").is_none());
+        assert!(["1", "2", "3"].iter().all(|id| store.get(id).is_some()));
+        drop(store);
+        assert_eq!(state.settings.read().retention.max_age_days, Some(1));
+        assert_eq!(state.removal_log.lock().unwrap().recent(10)[0].reason, RemovalReason::RetentionEvicted);
+    }
+
+    #[test]
+    fn test_auto_read_marks_old_unread_once() {
+        let state = AppState::default();
+        let hour = 3600;
+        for i in 0..6 {
+            let importance = if i == 1 { Importance::High } else { Importance::Default };
+            let n = crate::types::Notification { id: i.to_string(), posted_at: Some(i * hour), importance, ..Default::default() };
+            state.store.lock().unwrap().upsert(n, false);
+        }
+        state.store.lock().unwrap().set_pinned(&["0".to_string()], true);
+        assert_eq!(state.auto_read_now(10 * hour), 0);
+
+        state.settings.write().auto_read_after_hours = Some(7);
+        state.events.take_captured();
+        // 早于 3h 的 0、1、2 中，置顶的 0 与高重要性的 1 保持未读
+        assert_eq!(state.auto_read_now(10 * hour), 1);
+        assert_eq!(state.counts().unread, 5);
+        assert_eq!(state.auto_read_now(10 * hour), 0);
+        let events = state.events.take_captured();
+        assert_eq!(events.iter().filter(|(e, _)| e == "counts-changed").count(), 1);
+        assert!(state.removal_log.lock().unwrap().recent(10).is_empty());
+        let entry = &state.audit.recent(1)[0];
+        assert_eq!((entry.operation.as_str(), entry.source, entry.affected), ("auto_read", AuditSource::Background, 1));
+        assert_eq!(state.counts().total, 6);
+    }
+}
@@ -0,0 +1,207 @@
+//! 导出内容脱敏：事件流导出、诊断包与通知导出共用同一套变换，作用在 Notification / Event / Tombstone 上。
+//! none：原样输出。
+//! bodies_hashed：正文以及可能带正文内容的字段（操作按钮文字、会话标识、相对时间）替换为“长度 + 摘要”，标题与包名保留。
+//! full_anonymize：另外把标题同样替换，包名替换为稳定的化名（app-xxxxxxxx），id 替换为摘要（安卓的通知 key 含包名），
+//! 应用显示名去掉，时间截断到整点。
+//! 摘要是加盐 SHA-256 的前缀：同一安装中相同内容得到相同结果（便于对照重复通知），但不能靠常见词表反查；盐为本机 device_uuid。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::commands::AppState;
+use crate::tombstones::Tombstone;
+use crate::types::{Event, Notification};
+
+/// 摘要保留的十六进制位数
+const DIGEST_HEX: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskLevel {
+    #[default]
+    None,
+    BodiesHashed,
+    FullAnonymize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Masker {
+    level: MaskLevel,
+    salt: String,
+}
+
+fn hour(ts: i64) -> i64 {
+    ts - ts.rem_euclid(3600)
+}
+
+impl Masker {
+    pub fn new(level: MaskLevel, salt: &str) -> Self {
+        Self { level, salt: salt.to_string() }
+    }
+
+    pub fn level(&self) -> MaskLevel {
+        self.level
+    }
+
+    fn digest(&self, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update([0]);
+        hasher.update(value.as_bytes());
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect::<String>()[..DIGEST_HEX].to_string()
+    }
+
+    /// 内容替换为 “[长度 chars #摘要]”
+    pub fn hashed(&self, value: &str) -> String {
+        format!("[{} chars #{}]", value.chars().count(), self.digest(value))
+    }
+
+    /// 包名的稳定化名
+    pub fn pseudonym(&self, package: &str) -> String {
+        format!("app-{}", &self.digest(package)[..8])
+    }
+
+    fn full(&self) -> bool {
+        self.level == MaskLevel::FullAnonymize
+    }
+
+    fn mask_id(&self, id: &str) -> String {
+        if self.full() {
+            format!("id-{}", self.digest(id))
+        } else {
+            id.to_string()
+        }
+    }
+
+    fn mask_package(&self, package: Option<&String>) -> Option<String> {
+        package.map(|p| if self.full() { self.pseudonym(p) } else { p.clone() })
+    }
+
+    fn mask_title(&self, title: Option<&String>) -> Option<String> {
+        title.map(|t| if self.full() { self.hashed(t) } else { t.clone() })
+    }
+
+    fn mask_ts(&self, ts: Option<i64>) -> Option<i64> {
+        ts.map(|t| if self.full() { hour(t) } else { t })
+    }
+
+    pub fn notification(&self, n: &Notification) -> Notification {
+        if self.level == MaskLevel::None {
+            return n.clone();
+        }
+        let hash = |v: &Option<String>| v.as_deref().map(|s| self.hashed(s));
+        Notification {
+            id: self.mask_id(&n.id),
+            package_name: self.mask_package(n.package_name.as_ref()),
+            title: self.mask_title(n.title.as_ref()),
+            text: hash(&n.text),
+            posted_at: self.mask_ts(n.posted_at),
+            updated_at: self.mask_ts(n.updated_at),
+            snoozed_until: self.mask_ts(n.snoozed_until),
+            corrected_posted_at: self.mask_ts(n.corrected_posted_at),
+            actions: n.actions.iter().map(|a| self.hashed(a)).collect(),
+            conversation_key: hash(&n.conversation_key),
+            group_key: hash(&n.group_key),
+            // 聊天类应用常按联系人建渠道，渠道名里可能是人名
+            channel_id: if self.full() { hash(&n.channel_id) } else { n.channel_id.clone() },
+            channel_name: if self.full() { hash(&n.channel_name) } else { n.channel_name.clone() },
+            relative_time: None,
+            app_display_name: if self.full() { None } else { n.app_display_name.clone() },
+            ..n.clone()
+        }
+    }
+
+    pub fn event(&self, e: &Event) -> Event {
+        if self.level == MaskLevel::None {
+            return e.clone();
+        }
+        Event {
+            event_type: e.event_type.clone(),
+            seq: e.seq,
+            notification: e.notification.as_ref().map(|n| self.notification(n)),
+            id: e.id.as_deref().map(|id| self.mask_id(id)),
+        }
+    }
+
+    pub fn tombstone(&self, t: &Tombstone) -> Tombstone {
+        Tombstone {
+            id: self.mask_id(&t.id),
+            package_name: self.mask_package(t.package_name.as_ref()),
+            title: self.mask_title(t.title.as_ref()),
+            reason: t.reason,
+            removed_at: self.mask_ts(Some(t.removed_at)).unwrap_or(t.removed_at),
+        }
+    }
+}
+
+impl AppState {
+    /// 以本机 device_uuid 为盐的脱敏器（配置目录不可用时用本次运行的实例 id）
+    pub(crate) fn masker(&self, level: MaskLevel) -> Masker {
+        Masker::new(level, &self.relay_instance())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tombstones::RemovalReason;
+
+    pub(crate) const BODY: &str = "my secret body 4711";
+
+    /// 正文同时出现在操作按钮、会话标识与分组中
+    pub(crate) fn leaky() -> Notification {
+        Notification {
+            id: "0|com.secret.chat|42|null|10086".into(),
+            package_name: Some("com.secret.chat".into()),
+            title: Some("Alice Wonder".into()),
+            text: Some(BODY.into()),
+            posted_at: Some(1_700_003_723),
+            actions: vec![format!("Reply: {}", BODY), "Mark as read".into()],
+            conversation_key: Some(format!("thread/{}", BODY)),
+            group_key: Some(format!("g:com.secret.chat:{}", BODY)),
+            app_display_name: Some("SecretChat".into()),
+            ..Default::default()
+        }
+    }
+
+    fn output(masker: &Masker) -> String {
+        let n = leaky();
+        let event = Event { event_type: "added".into(), seq: 3, notification: Some(n.clone()), id: Some(n.id.clone()) };
+        let tombstone = Tombstone::new(&n, RemovalReason::UserDeletedLocal, 1_700_003_999);
+        serde_json::to_string(&(masker.notification(&n), masker.event(&event), masker.tombstone(&tombstone))).unwrap()
+    }
+
+    #[test]
+    fn test_no_body_survives_masking() {
+        let none = output(&Masker::new(MaskLevel::None, "salt"));
+        assert!(none.contains(BODY) && none.contains("com.secret.chat"));
+
+        let hashed = Masker::new(MaskLevel::BodiesHashed, "salt");
+        let out = output(&hashed);
+        assert!(!out.contains(BODY) && !out.contains("4711"), "{}", out);
+        assert!(out.contains("Alice Wonder") && out.contains("com.secret.chat"));
+        let masked = hashed.notification(&leaky());
+        assert_eq!(masked.text, Some(hashed.hashed(BODY)));
+        assert!(masked.text.unwrap().starts_with("[19 chars #"));
+        assert_eq!(masked.posted_at, Some(1_700_003_723));
+
+        let full = Masker::new(MaskLevel::FullAnonymize, "salt");
+        let out = output(&full);
+        for secret in [BODY, "4711", "Alice", "secret.chat", "SecretChat", "10086"] {
+            assert!(!out.contains(secret), "{} leaked: {}", secret, out);
+        }
+        let masked = full.notification(&leaky());
+        assert_eq!(masked.posted_at, Some(1_700_002_800));
+        assert_eq!(masked.package_name, Some(full.pseudonym("com.secret.chat")));
+    }
+
+    #[test]
+    fn test_pseudonyms_are_stable_per_salt() {
+        let a = Masker::new(MaskLevel::FullAnonymize, "install-a");
+        assert_eq!(a.pseudonym("com.tencent.mm"), a.pseudonym("com.tencent.mm"));
+        assert_ne!(a.pseudonym("com.tencent.mm"), a.pseudonym("com.tencent.mobileqq"));
+        assert_ne!(a.pseudonym("com.tencent.mm"), Masker::new(MaskLevel::FullAnonymize, "install-b").pseudonym("com.tencent.mm"));
+        assert_eq!(hour(7_199), 3_600);
+    }
+}
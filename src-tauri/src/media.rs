@@ -0,0 +1,214 @@
+//! 手机正在播放的媒体：识别媒体样式的通知（category = transport 或 MediaStyle 模板），
+//! 按连接维护 media_state（应用、标题、艺人、播放/暂停），推送 media-state-changed，
+//! 并可附加到托盘提示（“▶ 艺人 – 标题”）。媒体控制转发到通知上对应的操作按钮。
+//! 媒体通知入库时直接标记为已读，不计入未读数。
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::capabilities;
+use crate::commands::AppState;
+use crate::store::SortMode;
+use crate::templates::TemplateTarget;
+use crate::types::Notification;
+
+const APP_TOOLTIP: &str = "Notification Listener";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MediaState {
+    pub connection_id: String,
+    pub notification_id: String,
+    pub app: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub playing: bool,
+    pub updated_at: i64,
+}
+
+/// connection_id -> 媒体状态
+pub type MediaStates = HashMap<String, MediaState>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaAction {
+    Play,
+    Pause,
+    Next,
+    Prev,
+}
+
+impl MediaAction {
+    /// 通知操作按钮上可能的文字（小写比较）
+    fn labels(&self) -> &'static [&'static str] {
+        match self {
+            MediaAction::Play => &["play", "播放"],
+            MediaAction::Pause => &["pause", "暂停"],
+            MediaAction::Next => &["next", "skip to next", "下一首", "下一曲"],
+            MediaAction::Prev => &["previous", "prev", "skip to previous", "上一首", "上一曲"],
+        }
+    }
+}
+
+pub fn is_media(n: &Notification) -> bool {
+    n.category.as_deref() == Some("transport") || n.template.as_deref().is_some_and(|t| t.ends_with("MediaStyle"))
+}
+
+/// 媒体操作在通知操作按钮中的下标
+pub fn action_index(n: &Notification, action: MediaAction) -> Option<usize> {
+    n.actions
+        .iter()
+        .position(|a| action.labels().contains(&a.trim().to_lowercase().as_str()))
+}
+
+/// 从通知提取媒体状态：标题为曲名、正文为艺人；有“暂停”按钮说明正在播放
+pub fn state_from(connection_id: &str, n: &Notification, now: i64) -> MediaState {
+    MediaState {
+        connection_id: connection_id.to_string(),
+        notification_id: n.id.clone(),
+        app: n.package_name.clone(),
+        title: n.title.clone(),
+        artist: n.text.clone(),
+        playing: action_index(n, MediaAction::Pause).is_some(),
+        updated_at: now,
+    }
+}
+
+/// 托盘提示中的一行，暂停时不显示
+pub fn tooltip_line(state: &MediaState) -> Option<String> {
+    if !state.playing {
+        return None;
+    }
+    let title = state.title.as_deref().unwrap_or_default();
+    Some(match state.artist.as_deref().filter(|a| !a.is_empty()) {
+        Some(artist) => format!("▶ {} – {}", artist, title),
+        None => format!("▶ {}", title),
+    })
+}
+
+impl AppState {
+    /// 入库时更新媒体状态（新增/更新为媒体通知，或移除当前媒体通知）
+    pub(crate) fn track_media(&self, connection_id: &str, event_type: &str, n: Option<&Notification>, id: Option<&str>) {
+        let changed = {
+            let mut media = self.media.lock().unwrap();
+            match (event_type, n) {
+                ("added" | "updated", Some(n)) if is_media(n) => {
+                    let state = state_from(connection_id, n, chrono::Utc::now().timestamp());
+                    media.insert(connection_id.to_string(), state.clone());
+                    Some(serde_json::to_value(state).unwrap_or_default())
+                }
+                ("removed", _) => {
+                    let removed_id = id.or(n.map(|n| n.id.as_str()));
+                    let current = media.get(connection_id).map(|m| m.notification_id.clone());
+                    match (removed_id, current) {
+                        (Some(removed), Some(current)) if removed == current => {
+                            media.remove(connection_id);
+                            Some(serde_json::json!({ "connection_id": connection_id, "cleared": true }))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        };
+        if let Some(payload) = changed {
+            self.events.emit("media-state-changed", payload);
+            self.refresh_tray_tooltip();
+        }
+    }
+
+    pub fn media_state(&self, connection_id: &str) -> Option<MediaState> {
+        self.media.lock().unwrap().get(connection_id).cloned()
+    }
+
+    /// 把媒体控制转发到对应通知的操作按钮
+    pub fn media_control(&self, connection_id: &str, action: MediaAction) -> Result<(), String> {
+        let client = self.client_with(connection_id, capabilities::MEDIA_CONTROL)?;
+        let state = self.media_state(connection_id).ok_or("No media playing on this device")?;
+        let n = self.store.lock().unwrap().get(&state.notification_id).cloned().ok_or("Media notification is gone")?;
+        let index = action_index(&n, action).ok_or_else(|| format!("{:?} is not available", action))?;
+        client.send_action(&serde_json::json!({
+            "action": "notification_action",
+            "id": n.id,
+            "index": index,
+        }))
+    }
+
+    /// 托盘提示：应用名（断网时附加等待网络说明），设置了 tray_tooltip 模板时附加最新一条未读通知，
+    /// 开启时附加正在播放的媒体（隐藏预览时不附加）
+    pub fn tray_tooltip(&self) -> String {
+        let (show, hidden) = {
+            let settings = self.settings.read();
+            (settings.media_in_tray_tooltip, settings.privacy.hide_previews)
+        };
+        let mut lines = vec![APP_TOOLTIP.to_string()];
+        if self.network.is_parked() {
+            lines.push(crate::network_watch::WAITING_TOOLTIP.to_string());
+        }
+        if self.watch_only.is_enabled() {
+            lines.push(crate::watch_only::WATCH_ONLY_TOOLTIP.to_string());
+        }
+        let latest = self.store.lock().unwrap().query(SortMode::Newest, |n| !n.read, 0, Some(1));
+        let line = latest.first().and_then(|n| self.render_template(TemplateTarget::TrayTooltip, n));
+        lines.extend(line.filter(|l| !l.is_empty()));
+        if show && !hidden {
+            let media = self.media.lock().unwrap();
+            let mut states: Vec<&MediaState> = media.values().collect();
+            states.sort_by_key(|m| std::cmp::Reverse(m.updated_at));
+            lines.extend(states.into_iter().filter_map(tooltip_line).take(1));
+        }
+        lines.join("\n")
+    }
+
+    /// 经 tray_updates 合并
+    pub fn refresh_tray_tooltip(&self) {
+        self.tray_updates.request(crate::tray_updates::TraySurface::Tooltip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    fn player(id: &str, actions: &[&str]) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some("com.spotify.music".into()),
+                title: Some("Around the World".into()),
+                text: Some("Daft Punk".into()),
+                template: Some("android.app.Notification$MediaStyle".into()),
+                actions: actions.iter().map(|a| a.to_string()).collect(),
+                ..Default::default()
+            }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_media_state_tracked_and_not_unread() {
+        let state = AppState::default();
+        state.ingest_from("pixel", player("m1", &["Previous", "Pause", "Next"]));
+        let media = state.media_state("pixel").unwrap();
+        assert!(media.playing);
+        assert_eq!(media.artist.as_deref(), Some("Daft Punk"));
+        assert_eq!(state.counts().unread, 0);
+        assert_eq!(state.tray_tooltip(), "Notification Listener\n▶ Daft Punk – Around the World");
+
+        state.ingest_from("pixel", Event { event_type: "updated".into(), ..player("m1", &["上一首", "播放", "下一首"]) });
+        assert!(!state.media_state("pixel").unwrap().playing);
+        assert_eq!(state.tray_tooltip(), "Notification Listener");
+        let n = state.store.lock().unwrap().get("m1").cloned().unwrap();
+        assert_eq!(action_index(&n, MediaAction::Play), Some(1));
+        assert_eq!(action_index(&n, MediaAction::Pause), None);
+
+        state.ingest_from("pixel", Event { event_type: "removed".into(), seq: 0, notification: None, id: Some("m1".into()) });
+        assert!(state.media_state("pixel").is_none());
+        let events: Vec<String> = state.events.take_captured().into_iter().map(|(e, _)| e).collect();
+        assert_eq!(events.iter().filter(|e| *e == "media-state-changed").count(), 3);
+    }
+}
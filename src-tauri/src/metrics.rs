@@ -0,0 +1,364 @@
+//! 匿名使用统计：默认关闭，只有在设置中明确开启后才会累计和上传。
+//! 本地只累计三类数据：功能命令的调用次数、已配对设备数（分档）、每日通知量（分档）；
+//! 不含通知内容、包名或任何标识，唯一的 id 是开启后随机生成的安装 id。
+//! 每天向设置中的地址上传一次小 JSON，内容与 preview_metrics_payload 返回的完全一致。
+//! 服务器返回 410 或 `{"disable": true}` 时停止上传（kill switch），直到用户删除统计数据。
+
+use std::collections::BTreeMap;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime};
+
+use crate::commands::AppState;
+use crate::webhook::{http_post, parse_url};
+
+const METRICS_FILE: &str = "metrics.json";
+/// 上传间隔（秒）
+pub const UPLOAD_INTERVAL_SECS: i64 = 24 * 3600;
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// 计入统计的功能命令（只记录命令名与次数，不记录参数）
+pub const FEATURE_COMMANDS: &[&str] = &[
+    "search_notifications",
+    "mark_read_where",
+    "delete_where",
+    "pin_where",
+    "create_local_notification",
+    "open_notification_window",
+    "export_rules_preset",
+    "import_rules_preset",
+    "start_event_log",
+    "media_control",
+    "set_mirroring_enabled",
+    "start_connection_trace",
+    "begin_manual_setup",
+    "connect_via_relay",
+    "start_temp_server",
+];
+
+/// 分档上界：已配对设备数 0 / 1 / 2-3 / 4+
+const DEVICE_BUCKETS: &[u64] = &[0, 1, 3];
+/// 分档上界：每日通知量 0 / 1-10 / 11-100 / 101-1000 / 1001+
+const VOLUME_BUCKETS: &[u64] = &[0, 10, 100, 1000];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct MetricsSettings {
+    /// 用户明确开启后才统计
+    pub enabled: bool,
+    /// 上传地址（仅支持 http://），为空时只在本地累计
+    pub endpoint: Option<String>,
+}
+
+impl MetricsSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(url) = &self.endpoint {
+            parse_url(url).map_err(|e| format!("metrics.endpoint: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// 本地累计的统计数据（metrics.json）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct MetricsData {
+    install_id: Option<String>,
+    features: BTreeMap<String, u64>,
+    notifications: u64,
+    /// 当前统计周期的开始时间
+    period_start: Option<i64>,
+    last_upload_at: Option<i64>,
+    /// 服务器要求停止上传
+    stopped_by_server: bool,
+}
+
+/// 上传的内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MetricsPayload {
+    pub schema: u32,
+    pub install_id: Option<String>,
+    pub app_version: String,
+    pub os: String,
+    pub features: BTreeMap<String, u64>,
+    pub devices_paired: String,
+    pub notifications_per_day: String,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    data: Mutex<MetricsData>,
+}
+
+/// 按上界分档，如 `[0, 10, 100]` -> "0" / "1-10" / "11-100" / "101+"
+fn bucket(n: u64, bounds: &[u64]) -> String {
+    let mut lower = 0;
+    for &upper in bounds {
+        if n <= upper {
+            return if lower == upper { upper.to_string() } else { format!("{}-{}", lower, upper) };
+        }
+        lower = upper + 1;
+    }
+    format!("{}+", lower)
+}
+
+/// 服务器要求停止上传
+fn is_kill_switch(status: u16, body: &str) -> bool {
+    status == 410
+        || serde_json::from_str::<serde_json::Value>(body).is_ok_and(|v| v["disable"].as_bool() == Some(true))
+}
+
+impl AppState {
+    pub(crate) fn load_metrics(&self) {
+        *self.metrics.data.lock() = self.storage.load::<MetricsData>(METRICS_FILE).unwrap_or_default();
+    }
+
+    fn save_metrics(&self) {
+        let data = self.metrics.data.lock().clone();
+        if let Err(e) = self.storage.save(METRICS_FILE, &data) {
+            println!("[Metrics] {}", e);
+        }
+    }
+
+    /// 已开启且未被服务器停用时才累计
+    fn with_metrics(&self, now: i64, f: impl FnOnce(&mut MetricsData)) {
+        if !self.settings.read().metrics.enabled {
+            return;
+        }
+        let mut data = self.metrics.data.lock();
+        if data.stopped_by_server {
+            return;
+        }
+        data.period_start.get_or_insert(now);
+        f(&mut data);
+    }
+
+    /// 记录一次命令调用；不在 FEATURE_COMMANDS 中的命令忽略
+    pub fn record_command(&self, command: &str) {
+        if let Some(name) = FEATURE_COMMANDS.iter().find(|c| **c == command) {
+            self.with_metrics(chrono::Utc::now().timestamp(), |d| {
+                let count = d.features.entry(name.to_string()).or_default();
+                *count = count.saturating_add(1);
+            });
+        }
+    }
+
+    pub(crate) fn record_notification_metric(&self) {
+        self.with_metrics(chrono::Utc::now().timestamp(), |d| d.notifications = d.notifications.saturating_add(1));
+    }
+
+    /// 生成将要上传的内容；开启统计时才分配安装 id
+    pub fn metrics_payload(&self, now: i64) -> MetricsPayload {
+        let enabled = self.settings.read().metrics.enabled;
+        let devices = self.endpoints.lock().unwrap().device_count() as u64;
+        let mut data = self.metrics.data.lock();
+        if enabled && data.install_id.is_none() {
+            data.install_id = Some(uuid::Uuid::new_v4().to_string());
+        }
+        let days = (now - data.period_start.unwrap_or(now)).max(0) / UPLOAD_INTERVAL_SECS + 1;
+        MetricsPayload {
+            schema: SCHEMA_VERSION,
+            install_id: data.install_id.clone(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            features: data.features.clone(),
+            devices_paired: bucket(devices, DEVICE_BUCKETS),
+            notifications_per_day: bucket(data.notifications / days as u64, VOLUME_BUCKETS),
+        }
+    }
+
+    /// 清空本地统计数据与安装 id
+    pub fn delete_metrics_data(&self) {
+        *self.metrics.data.lock() = MetricsData::default();
+        self.save_metrics();
+        println!("[Metrics] Local metrics data deleted");
+    }
+
+    /// 维护任务：落盘计数，距上次上传满一天时上传
+    pub(crate) fn upload_metrics_if_due(&self, now: i64) -> Result<String, String> {
+        let settings = self.settings.read().metrics.clone();
+        if !settings.enabled {
+            return Ok("disabled".to_string());
+        }
+        let due = {
+            let data = self.metrics.data.lock();
+            if data.stopped_by_server {
+                return Ok("stopped by server".to_string());
+            }
+            let since = data.last_upload_at.or(data.period_start).unwrap_or(now);
+            now - since >= UPLOAD_INTERVAL_SECS
+        };
+        let Some(endpoint) = settings.endpoint.filter(|_| due) else {
+            self.save_metrics();
+            return Ok("not due".to_string());
+        };
+
+        let payload = self.metrics_payload(now);
+        let body = serde_json::to_string(&payload).map_err(|e| e.to_string())?;
+        let response = http_post(&endpoint, &[], &body);
+        let result = {
+            let mut data = self.metrics.data.lock();
+            match response {
+                Ok(r) if is_kill_switch(r.status, &r.body) => {
+                    println!("[Metrics] Server requested to stop uploads");
+                    data.stopped_by_server = true;
+                    data.features.clear();
+                    data.notifications = 0;
+                    Ok("stopped by server".to_string())
+                }
+                Ok(r) if (200..300).contains(&r.status) => {
+                    // 上传期间新增的计数留到下一周期
+                    for (name, n) in &payload.features {
+                        if let Some(count) = data.features.get_mut(name) {
+                            *count = count.saturating_sub(*n);
+                        }
+                    }
+                    data.features.retain(|_, n| *n > 0);
+                    data.notifications = 0;
+                    data.period_start = Some(now);
+                    data.last_upload_at = Some(now);
+                    Ok("uploaded".to_string())
+                }
+                Ok(r) => Err(format!("Metrics upload failed: HTTP {}", r.status_line)),
+                Err(e) => Err(format!("Metrics upload failed: {}", e)),
+            }
+        };
+        self.save_metrics();
+        result
+    }
+}
+
+/// 包装命令分发：调用命令前计数（只看命令名）
+pub fn counting_commands<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        if let Some(state) = invoke.message.webview_ref().try_state::<AppState>() {
+            state.record_command(invoke.message.command());
+        }
+        handler(invoke)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    use crate::types::{Event, Notification};
+
+    /// 回应固定响应，记录收到的请求体
+    fn server(response: &'static str) -> (String, std::sync::mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/metrics", listener.local_addr().unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(v) = line.to_lowercase().strip_prefix("content-length:") {
+                        len = v.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                let _ = tx.send(String::from_utf8(body).unwrap());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn opted_in(endpoint: Option<String>) -> AppState {
+        let state = AppState::default();
+        state.settings.write().metrics = MetricsSettings { enabled: true, endpoint };
+        state
+    }
+
+    fn added(id: &str) -> Event {
+        let n = Notification {
+            id: id.into(),
+            package_name: Some("com.secret.app".into()),
+            title: Some("私密标题".into()),
+            ..Default::default()
+        };
+        Event { event_type: "added".into(), seq: 0, notification: Some(n), id: None }
+    }
+
+    #[test]
+    fn test_buckets() {
+        let devices: Vec<String> = [0, 1, 2, 3, 4, 9].iter().map(|n| bucket(*n, DEVICE_BUCKETS)).collect();
+        assert_eq!(devices, ["0", "1", "2-3", "2-3", "4+", "4+"]);
+        assert_eq!(bucket(10, VOLUME_BUCKETS), "1-10");
+        assert_eq!(bucket(5000, VOLUME_BUCKETS), "1001+");
+    }
+
+    #[test]
+    fn test_off_by_default_and_no_content_in_payload() {
+        let state = AppState::default();
+        state.record_command("search_notifications");
+        state.ingest_from("pixel", added("1"));
+        let payload = state.metrics_payload(0);
+        assert!(payload.install_id.is_none() && payload.features.is_empty());
+        assert_eq!(payload.notifications_per_day, "0");
+
+        let state = opted_in(None);
+        state.remember_endpoint("pixel", "192.168.1.5:10035", "t1", None, None);
+        state.record_command("search_notifications");
+        state.record_command("get_settings");
+        for i in 0..12 {
+            state.ingest_from("pixel", added(&i.to_string()));
+        }
+        let payload = state.metrics_payload(chrono::Utc::now().timestamp());
+        assert_eq!(payload.features, BTreeMap::from([("search_notifications".to_string(), 1)]));
+        assert_eq!((payload.devices_paired.as_str(), payload.notifications_per_day.as_str()), ("1", "11-100"));
+        let json = serde_json::to_string(&payload).unwrap();
+        assert!(!json.contains("com.secret") && !json.contains("私密") && !json.contains("192.168"));
+        // 安装 id 保持不变，删除后清空
+        assert_eq!(state.metrics_payload(0).install_id, payload.install_id);
+        state.delete_metrics_data();
+        state.settings.write().metrics.enabled = false;
+        assert_eq!(state.metrics_payload(0).install_id, None);
+    }
+
+    #[test]
+    fn test_daily_upload_sends_preview() {
+        let (url, rx) = server("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}");
+        let state = opted_in(Some(url));
+        state.record_command("media_control");
+        let t0 = chrono::Utc::now().timestamp();
+        assert_eq!(state.upload_metrics_if_due(t0).unwrap(), "not due");
+
+        let now = t0 + UPLOAD_INTERVAL_SECS;
+        let preview = state.metrics_payload(now);
+        assert_eq!(state.upload_metrics_if_due(now).unwrap(), "uploaded");
+        let sent: MetricsPayload = serde_json::from_str(&rx.recv().unwrap()).unwrap();
+        assert_eq!(sent, preview);
+        assert!(state.metrics_payload(now).features.is_empty());
+        assert_eq!(state.upload_metrics_if_due(now + 60).unwrap(), "not due");
+    }
+
+    #[test]
+    fn test_kill_switch_stops_uploads() {
+        let (url, _rx) = server("HTTP/1.1 200 OK\r\nContent-Length: 16\r\n\r\n{\"disable\":true}");
+        let state = opted_in(Some(url));
+        state.record_command("media_control");
+        let now = chrono::Utc::now().timestamp() + UPLOAD_INTERVAL_SECS;
+        assert_eq!(state.upload_metrics_if_due(now).unwrap(), "stopped by server");
+        state.record_command("media_control");
+        assert!(state.metrics_payload(now).features.is_empty());
+        assert_eq!(state.upload_metrics_if_due(now + UPLOAD_INTERVAL_SECS).unwrap(), "stopped by server");
+        assert!(is_kill_switch(410, ""));
+    }
+}
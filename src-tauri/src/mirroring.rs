@@ -0,0 +1,175 @@
+//! 按设备暂停/恢复通知镜像，不断开连接。
+//! 设备以前端保存的连接 id 标识（重连时复用）。暂停时向手机端发送 set_mirroring 控制消息；
+//! 手机端协议版本过旧（不支持该消息）时在本地丢弃该连接的新增/更新事件。
+//! 暂停状态持久化到 mirroring.json，重连和重启后仍然生效。
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::android_client::AndroidSocketClient;
+use crate::commands::AppState;
+
+/// 支持 set_mirroring / sync 控制消息的最低协议版本
+pub const MIRRORING_MIN_PROTOCOL: u32 = 2;
+const MIRRORING_FILE: &str = "mirroring.json";
+
+/// 已暂停的连接：connection_id -> 暂停时间
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MirroringRegistry {
+    paused: HashMap<String, i64>,
+}
+
+impl MirroringRegistry {
+    pub fn is_paused(&self, connection_id: &str) -> bool {
+        self.paused.contains_key(connection_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MirroringResult {
+    pub connection_id: String,
+    pub enabled: bool,
+    /// 手机端是否收到控制消息（false 表示未连接或版本过旧，仅本地过滤）
+    pub remote: bool,
+    /// 请求补发的起始时间（恢复且要求补发时）
+    pub backfill_since: Option<i64>,
+}
+
+fn supports_mirroring(client: &AndroidSocketClient) -> bool {
+    client.protocol_version().is_some_and(|v| v >= MIRRORING_MIN_PROTOCOL)
+}
+
+impl AppState {
+    /// 暂停/恢复某个设备的镜像；恢复时 backfill 为 true 则请求手机端补发暂停期间的通知
+    pub fn set_mirroring_enabled(
+        &self,
+        connection_id: &str,
+        enabled: bool,
+        backfill: bool,
+        now: i64,
+    ) -> Result<MirroringResult, String> {
+        let (registry, paused_at) = {
+            let mut registry = self.mirroring.lock().unwrap();
+            let paused_at = if enabled {
+                registry.paused.remove(connection_id)
+            } else {
+                registry.paused.entry(connection_id.to_string()).or_insert(now);
+                None
+            };
+            (registry.clone(), paused_at)
+        };
+        self.storage.save(MIRRORING_FILE, &registry)?;
+
+        let client = self.clients.read().get(connection_id).cloned();
+        let mut result = MirroringResult {
+            connection_id: connection_id.to_string(),
+            enabled,
+            remote: false,
+            backfill_since: None,
+        };
+        if let Some(client) = client.filter(|c| supports_mirroring(c)) {
+            client.send_action(&serde_json::json!({ "action": "set_mirroring", "enabled": enabled }))?;
+            result.remote = true;
+            if let (true, Some(since)) = (backfill, paused_at) {
+                let mut request = serde_json::json!({ "action": "sync", "since": since });
+                if let Some(horizon) = self.sync_horizon(connection_id) {
+                    request["horizon"] = horizon.into();
+                }
+                client.send_action(&request)?;
+                result.backfill_since = Some(since);
+            }
+        }
+        println!("[Mirroring] {} -> enabled={} remote={}", connection_id, enabled, result.remote);
+        self.events.emit("mirroring-changed", &result);
+        Ok(result)
+    }
+
+    /// 本地过滤：该连接是否处于暂停状态
+    pub(crate) fn is_mirroring_paused(&self, connection_id: &str) -> bool {
+        self.mirroring.lock().unwrap().is_paused(connection_id)
+    }
+
+    /// 连接（重连）成功后重新下发暂停状态
+    pub(crate) fn apply_mirroring_on_connect(&self, connection_id: &str, client: &AndroidSocketClient) {
+        if !self.is_mirroring_paused(connection_id) || !supports_mirroring(client) {
+            return;
+        }
+        if let Err(e) = client.send_action(&serde_json::json!({ "action": "set_mirroring", "enabled": false })) {
+            println!("[Mirroring] Failed to re-apply pause for {}: {}", connection_id, e);
+        }
+    }
+
+    pub(crate) fn load_mirroring(&self) {
+        *self.mirroring.lock().unwrap() = self.storage.load::<MirroringRegistry>(MIRRORING_FILE).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use crate::transport::Transport;
+    use crate::types::{Event, Notification};
+
+    /// 记录发出的消息
+    struct FakeTransport(Arc<Mutex<Vec<String>>>);
+
+    impl Transport for FakeTransport {
+        fn send_line(&mut self, line: &str) -> Result<(), String> {
+            self.0.lock().push(line.to_string());
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(r#"{"success":true,"protocolVersion":2}"#.to_string())
+        }
+    }
+
+    fn added(id: &str) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: id.into(), ..Default::default() }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_pause_filters_locally_and_survives_restart() {
+        let dir = crate::storage::temp_dir("mirroring");
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        let r = state.set_mirroring_enabled("work", false, false, 100).unwrap();
+        assert!(!r.remote);
+        state.ingest_from("work", added("1"));
+        state.ingest_from("home", added("2"));
+        assert_eq!(state.counts().total, 1);
+
+        let restarted = AppState::default();
+        restarted.init_storage(dir.clone());
+        assert!(restarted.is_mirroring_paused("work"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_remote_pause_and_backfill() {
+        let state = AppState::default();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let client = AndroidSocketClient::with_transport(Box::new(FakeTransport(sent.clone())), "work".into(), Arc::default());
+        client.login("t").unwrap();
+        state.clients.write().insert("work".into(), Arc::new(client));
+
+        assert!(state.set_mirroring_enabled("work", false, false, 100).unwrap().remote);
+        let r = state.set_mirroring_enabled("work", true, true, 200).unwrap();
+        assert_eq!(r.backfill_since, Some(100));
+        let frames: Vec<serde_json::Value> = sent.lock()[1..].iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(frames[0], serde_json::json!({ "action": "set_mirroring", "enabled": false }));
+        assert_eq!(frames[1], serde_json::json!({ "action": "set_mirroring", "enabled": true }));
+        assert_eq!(frames[2], serde_json::json!({ "action": "sync", "since": 100 }));
+    }
+}
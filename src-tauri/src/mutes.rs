@@ -0,0 +1,375 @@
+//! 按应用、渠道、会话或单条通知静音：比屏蔽整个应用更细，比如只静音一个吵闹的群聊或购物应用的促销渠道。
+//! 目标可以是应用、应用下的安卓通知渠道（按 channel_id，见 channels）、会话（conversation_key，由手机端随通知发送，按 normalize 归一化后比较）
+//! 或某个应用下的通知 id（末尾 * 表示前缀匹配，用于 id 带序号、反复出现的通知）。可设过期时间，过期后由维护任务解除并推送 unmuted。
+//! 静音可标为例外（exempt）：多条静音都命中时以最具体的目标为准（应用 < 渠道 < 会话 < 通知），
+//! 例如静音整个应用、同时把“物流通知”渠道设为例外。
+//! 入库时命中静音的通知照常保存，但直接标为已读、不弹出，并带 muted 标记；
+//! 列表默认不返回静音的通知，由前端折叠为“已静音”分组，展开时按 muted 过滤查询。
+//! 静音会话时，会话中已有的通知一并静音，之后到达的同会话通知也会命中。
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::limits::{self, InvalidArgument};
+use crate::normalize::normalize;
+use crate::store::NotificationFilter;
+use crate::types::Notification;
+
+pub const MUTES_FILE: &str = "mutes.json";
+pub const MAX_MUTES: usize = 200;
+const MAX_KEY_BYTES: usize = 512;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MuteTarget {
+    Package { package_name: String },
+    Channel { package_name: String, channel_id: String },
+    Conversation { conversation_key: String },
+    /// id 末尾的 * 表示前缀匹配
+    Notification { package_name: String, id: String },
+}
+
+impl MuteTarget {
+    pub fn matches(&self, n: &Notification) -> bool {
+        match self {
+            MuteTarget::Package { package_name } => n.package_name.as_ref() == Some(package_name),
+            MuteTarget::Channel { package_name, channel_id } => {
+                n.package_name.as_ref() == Some(package_name) && n.channel_id.as_ref() == Some(channel_id)
+            }
+            MuteTarget::Conversation { conversation_key } => {
+                n.conversation_key.as_deref().is_some_and(|key| normalize(key) == normalize(conversation_key))
+            }
+            MuteTarget::Notification { package_name, id } => {
+                n.package_name.as_ref() == Some(package_name)
+                    && match id.strip_suffix('*') {
+                        Some(prefix) => n.id.starts_with(prefix),
+                        None => n.id == *id,
+                    }
+            }
+        }
+    }
+
+    /// 目标的具体程度，越大越具体
+    fn specificity(&self) -> u8 {
+        match self {
+            MuteTarget::Package { .. } => 0,
+            MuteTarget::Channel { .. } => 1,
+            MuteTarget::Conversation { .. } => 2,
+            MuteTarget::Notification { .. } => 3,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let (field, value) = match self {
+            MuteTarget::Package { package_name } => ("package_name", package_name),
+            MuteTarget::Channel { package_name, channel_id } => {
+                if package_name.trim().is_empty() {
+                    return Err(InvalidArgument::new("package_name", "package_name must not be empty").into());
+                }
+                ("channel_id", channel_id)
+            }
+            MuteTarget::Conversation { conversation_key } => ("conversation_key", conversation_key),
+            MuteTarget::Notification { package_name, id } => {
+                if package_name.trim().is_empty() {
+                    return Err(InvalidArgument::new("package_name", "package_name must not be empty").into());
+                }
+                ("id", id)
+            }
+        };
+        if value.trim().is_empty() || value == "*" {
+            return Err(InvalidArgument::new(field, format!("{} must not be empty", field)).into());
+        }
+        Ok(limits::check_bytes(field, value.len(), MAX_KEY_BYTES)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Mute {
+    pub id: String,
+    pub target: MuteTarget,
+    pub created_at: i64,
+    /// 过期时间（秒）；None 表示一直静音
+    pub expires_at: Option<i64>,
+    /// 例外：命中的通知不静音，覆盖不如它具体的静音
+    #[serde(default)]
+    pub exempt: bool,
+}
+
+impl Mute {
+    fn active(&self, now: i64) -> bool {
+        self.expires_at.is_none_or(|t| t > now)
+    }
+}
+
+/// list_mutes 返回：静音规则与当前命中的通知数（用于折叠分组的标题）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MuteInfo {
+    #[serde(flatten)]
+    pub mute: Mute,
+    pub members: usize,
+}
+
+#[derive(Default)]
+pub struct Mutes {
+    list: Mutex<Vec<Mute>>,
+}
+
+impl Mutes {
+    /// 命中的静音中最具体的一级决定结果；同一级里有非例外的即为静音
+    pub fn is_muted(&self, n: &Notification, now: i64) -> bool {
+        let list = self.list.lock();
+        let matched: Vec<&Mute> = list.iter().filter(|m| m.active(now) && m.target.matches(n)).collect();
+        let Some(level) = matched.iter().map(|m| m.target.specificity()).max() else {
+            return false;
+        };
+        matched.iter().any(|m| m.target.specificity() == level && !m.exempt)
+    }
+
+    pub fn snapshot(&self) -> Vec<Mute> {
+        self.list.lock().clone()
+    }
+}
+
+impl AppState {
+    pub(crate) fn load_mutes(&self) {
+        *self.mutes.list.lock() = self.storage.load::<Vec<Mute>>(MUTES_FILE).unwrap_or_default();
+    }
+
+    fn save_mutes(&self) {
+        if let Err(e) = self.storage.save(MUTES_FILE, &self.mutes.snapshot()) {
+            println!("[Mutes] {}", e);
+        }
+    }
+
+    /// 静音目标（exempt 为 true 时设为例外）；同一目标已有静音时更新过期时间与是否例外
+    pub fn mute(&self, target: MuteTarget, expires_at: Option<i64>, exempt: bool) -> Result<Mute, String> {
+        target.validate()?;
+        let now = chrono::Utc::now().timestamp();
+        if expires_at.is_some_and(|t| t <= now) {
+            return Err(InvalidArgument::new("expires_at", "expires_at must be in the future").into());
+        }
+        let mute = {
+            let mut list = self.mutes.list.lock();
+            match list.iter_mut().find(|m| m.target == target) {
+                Some(existing) => {
+                    existing.expires_at = expires_at;
+                    existing.exempt = exempt;
+                    existing.clone()
+                }
+                None => {
+                    if list.len() >= MAX_MUTES {
+                        return Err(format!("Too many mutes (max {})", MAX_MUTES));
+                    }
+                    let mute = Mute { id: uuid::Uuid::new_v4().to_string(), target, created_at: now, expires_at, exempt };
+                    list.push(mute.clone());
+                    mute
+                }
+            }
+        };
+        self.save_mutes();
+        let affected = self.refresh_muted(now);
+        println!("[Mutes] Muted {:?} ({} current items)", mute.target, affected.len());
+        self.events.emit("muted", serde_json::json!({ "mute": mute, "ids": affected }));
+        Ok(mute)
+    }
+
+    pub fn unmute(&self, id: &str) -> Result<(), String> {
+        let removed = {
+            let mut list = self.mutes.list.lock();
+            let index = list.iter().position(|m| m.id == id).ok_or_else(|| format!("Mute not found: {}", id))?;
+            list.remove(index)
+        };
+        self.save_mutes();
+        self.after_unmute(&[removed], chrono::Utc::now().timestamp());
+        Ok(())
+    }
+
+    pub fn list_mutes(&self) -> Vec<MuteInfo> {
+        let now = chrono::Utc::now().timestamp();
+        let mutes = self.mutes.snapshot();
+        let store = self.store.lock().unwrap();
+        let muted = store.ids_where(&NotificationFilter { muted: Some(true), ..Default::default() });
+        let unmuted = store.ids_where(&NotificationFilter { muted: Some(false), ..Default::default() });
+        mutes
+            .into_iter()
+            .filter(|m| m.active(now))
+            .map(|mute| {
+                // 例外统计因它而未被静音的通知
+                let ids = if mute.exempt { &unmuted } else { &muted };
+                let members = ids.iter().filter(|id| store.get(id).is_some_and(|n| mute.target.matches(n))).count();
+                MuteInfo { mute, members }
+            })
+            .collect()
+    }
+
+    /// 维护任务：解除过期的静音
+    pub fn expire_mutes(&self, now: i64) -> usize {
+        let expired: Vec<Mute> = {
+            let mut list = self.mutes.list.lock();
+            let (expired, kept) = std::mem::take(&mut *list).into_iter().partition(|m| !m.active(now));
+            *list = kept;
+            expired
+        };
+        if !expired.is_empty() {
+            self.save_mutes();
+            self.after_unmute(&expired, now);
+        }
+        expired.len()
+    }
+
+    fn after_unmute(&self, removed: &[Mute], now: i64) {
+        let affected = self.refresh_muted(now);
+        for mute in removed {
+            println!("[Mutes] Unmuted {:?}", mute.target);
+            let ids: Vec<String> = {
+                let store = self.store.lock().unwrap();
+                affected.iter().filter(|id| store.get(id).is_some_and(|n| mute.target.matches(n))).cloned().collect()
+            };
+            self.events.emit("unmuted", serde_json::json!({ "id": mute.id, "target": mute.target, "ids": ids }));
+        }
+    }
+
+    /// 按当前静音规则重新标记已有通知：新静音的标为已读；返回标记变化的 id
+    fn refresh_muted(&self, now: i64) -> Vec<String> {
+        let (changed, counts_changed) = {
+            let mut store = self.store.lock().unwrap();
+            let unread = store.counts().unread;
+            let changed = store.refresh_muted(|n| self.mutes.is_muted(n, now));
+            (changed, store.counts().unread != unread)
+        };
+        if counts_changed {
+            self.emit_counts();
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SortMode;
+    use crate::types::Event;
+
+    fn added(state: &AppState, id: &str, conversation: Option<&str>) {
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some("com.chat".into()),
+                title: Some("群聊".into()),
+                conversation_key: conversation.map(str::to_string),
+                posted_at: Some(1_700_000_000),
+                ..Default::default()
+            }),
+            id: None,
+        });
+    }
+
+    fn listed(state: &AppState, muted: Option<bool>) -> Vec<String> {
+        let filter = NotificationFilter { muted, ..Default::default() };
+        let store = state.store.lock().unwrap();
+        let mut ids: Vec<String> = store.query_filter(SortMode::Newest, &filter, 0, None).into_iter().map(|n| n.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_conversation_mute_covers_current_and_future_members() {
+        let state = AppState::default();
+        added(&state, "a1", Some("group-1"));
+        added(&state, "b1", Some("group-2"));
+        assert_eq!(state.counts().unread, 2);
+
+        let mute = state.mute(MuteTarget::Conversation { conversation_key: "group-1".into() }, None, false).unwrap();
+        assert_eq!(state.counts().unread, 1);
+        assert!(state.store.lock().unwrap().get("a1").unwrap().muted);
+
+        // 同会话的新通知：保存、已读、不计入未读
+        let outcome = state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: "a2".into(), conversation_key: Some("group-1".into()), ..Default::default() }),
+            id: None,
+        });
+        assert_eq!(outcome, crate::ingest::IngestOutcome::Muted);
+        let counts = state.counts();
+        assert_eq!((counts.unread, counts.total), (1, 3));
+        assert_eq!(listed(&state, Some(false)), ["b1"]);
+        assert_eq!(listed(&state, Some(true)), ["a1", "a2"]);
+        assert_eq!(state.list_mutes()[0].members, 2);
+
+        state.events.take_captured();
+        state.unmute(&mute.id).unwrap();
+        assert_eq!(listed(&state, Some(true)), Vec::<String>::new());
+        // 解除后保持已读
+        assert_eq!(state.counts().unread, 1);
+        let events = state.events.take_captured();
+        let (_, payload) = events.iter().find(|(e, _)| e == "unmuted").unwrap();
+        assert_eq!(payload["ids"].as_array().unwrap().len(), 2);
+        assert!(state.unmute(&mute.id).is_err());
+    }
+
+    #[test]
+    fn test_id_pattern_and_expiry() {
+        let state = AppState::default();
+        added(&state, "otp-1", None);
+        added(&state, "otp-2", None);
+        added(&state, "news", None);
+        let target = MuteTarget::Notification { package_name: "com.chat".into(), id: "otp-*".into() };
+        assert!(state.mute(target.clone(), Some(1), false).is_err());
+        assert!(state.mute(MuteTarget::Conversation { conversation_key: " ".into() }, None, false).is_err());
+
+        let now = chrono::Utc::now().timestamp();
+        let mute = state.mute(target.clone(), Some(now + 60), false).unwrap();
+        // 同一目标再次静音只更新过期时间
+        assert_eq!(state.mute(target, Some(now + 120), false).unwrap().id, mute.id);
+        assert_eq!(state.list_mutes().len(), 1);
+        assert_eq!(listed(&state, Some(true)), ["otp-1", "otp-2"]);
+
+        assert_eq!(state.expire_mutes(now + 60), 0);
+        state.events.take_captured();
+        assert_eq!(state.expire_mutes(now + 120), 1);
+        assert!(state.list_mutes().is_empty());
+        assert_eq!(listed(&state, Some(true)), Vec::<String>::new());
+        assert!(state.events.take_captured().iter().any(|(e, p)| e == "unmuted" && p["id"] == mute.id.as_str()));
+    }
+
+    #[test]
+    fn test_most_specific_mute_wins() {
+        use crate::channels::tests::shop;
+        let state = AppState::default();
+        state.ingest_event(shop("promo", Some(("promo", "Promotions"))));
+        state.ingest_event(shop("delivery", Some(("delivery", "Delivery updates"))));
+        state.ingest_event(shop("legacy", None));
+
+        // 只静音促销渠道：物流通知与没有渠道信息的通知不受影响
+        let channel = |id: &str| MuteTarget::Channel { package_name: "com.shop".into(), channel_id: id.into() };
+        let promo = state.mute(channel("promo"), None, false).unwrap();
+        assert_eq!(listed(&state, Some(true)), ["promo"]);
+        assert!(state.mute(MuteTarget::Channel { package_name: " ".into(), channel_id: "promo".into() }, None, false).is_err());
+
+        // 静音整个应用，同时把物流渠道设为例外
+        state.mute(MuteTarget::Package { package_name: "com.shop".into() }, None, false).unwrap();
+        state.mute(channel("delivery"), None, true).unwrap();
+        assert_eq!(listed(&state, Some(true)), ["legacy", "promo"]);
+        let outcome = state.ingest_event(shop("delivery-2", Some(("delivery", "物流通知"))));
+        assert_eq!(outcome, crate::ingest::IngestOutcome::Stored);
+        assert_eq!(state.ingest_event(shop("legacy-2", None)), crate::ingest::IngestOutcome::Muted);
+        let exempt = state.list_mutes().into_iter().find(|m| m.mute.exempt).unwrap();
+        assert_eq!(exempt.members, 2);
+
+        // 更具体的会话例外覆盖渠道静音
+        state.store.lock().unwrap().remove("promo");
+        let mut n = shop("promo-2", Some(("promo", "Promotions"))).notification.unwrap();
+        n.conversation_key = Some("order-42".into());
+        state.mute(MuteTarget::Conversation { conversation_key: "order-42".into() }, None, true).unwrap();
+        assert!(!state.mutes.is_muted(&n, 0));
+        state.unmute(&promo.id).unwrap();
+        n.conversation_key = None;
+        // 渠道静音解除后回到应用级静音
+        assert!(state.mutes.is_muted(&n, 0));
+    }
+}
@@ -0,0 +1,325 @@
+//! 浏览器扩展的 native messaging host：浏览器按清单启动本程序，经 stdin/stdout 通信，
+//! 每条消息前是 4 字节本机字节序的长度，之后是 UTF-8 JSON。
+//! 本进程不启动 Tauri，只把扩展的请求逐条转发给正在运行的实例（见 bridge），再把响应原样写回。
+//! 浏览器启动时不会带自定义参数：Chrome 传入扩展来源（chrome-extension://…），Firefox 传入清单路径与扩展 id，
+//! 因此除了 --native-messaging-host 也按这两种参数识别（见 is_host_launch）。
+//! stdout 只能写协议消息，本模块的日志一律写到 stderr。
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::bridge::{BridgeResponse, PORT_FILE};
+
+/// 清单中的 host 名称（只能包含小写字母、数字、点与下划线）
+pub const HOST_NAME: &str = "com.droid.notification_listener";
+pub const HOST_FLAG: &str = "--native-messaging-host";
+/// 单条消息上限（浏览器发往 host 的消息；host 发往浏览器的上限是 1 MB）
+pub const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+const BRIDGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Browser {
+    Chrome,
+    Chromium,
+    Edge,
+    Firefox,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct InstalledHost {
+    pub browser: Browser,
+    pub manifest_path: String,
+    /// 清单中登记的可执行文件
+    pub executable: String,
+}
+
+/// 本进程是否由浏览器作为 native messaging host 启动
+pub fn is_host_launch(args: &[String]) -> bool {
+    let rest = args.get(1..).unwrap_or_default();
+    rest.iter().any(|a| a == HOST_FLAG || a.starts_with("chrome-extension://"))
+        || rest.first().is_some_and(|a| a.ends_with(".json") && Path::new(a).file_stem().is_some_and(|s| s == HOST_NAME))
+}
+
+/// 读取一条消息；输入结束时返回 None
+pub fn read_message(input: &mut impl Read) -> Result<Option<serde_json::Value>, String> {
+    let mut len = [0u8; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(format!("Failed to read message length: {}", e)),
+    }
+    let len = u32::from_ne_bytes(len) as usize;
+    if len > MAX_MESSAGE_BYTES {
+        return Err(format!("Message too large ({} bytes)", len));
+    }
+    let mut body = vec![0u8; len];
+    input.read_exact(&mut body).map_err(|e| format!("Failed to read message: {}", e))?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| format!("Invalid message: {}", e))
+}
+
+pub fn write_message(output: &mut impl Write, message: &impl Serialize) -> Result<(), String> {
+    let body = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    if body.len() > MAX_MESSAGE_BYTES {
+        return Err(format!("Response too large ({} bytes)", body.len()));
+    }
+    output.write_all(&(body.len() as u32).to_ne_bytes()).map_err(|e| format!("Failed to write message: {}", e))?;
+    output.write_all(&body).map_err(|e| format!("Failed to write message: {}", e))?;
+    output.flush().map_err(|e| format!("Failed to flush: {}", e))
+}
+
+/// 到运行中实例的连接（断开后下一条消息重新连接）
+struct Upstream {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Upstream {
+    fn connect(port: u16) -> Result<Self, String> {
+        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        let stream = TcpStream::connect_timeout(&addr, BRIDGE_TIMEOUT).map_err(|e| format!("not_running: {}", e))?;
+        stream.set_read_timeout(Some(BRIDGE_TIMEOUT)).map_err(|e| e.to_string())?;
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        Ok(Self { reader, writer: stream })
+    }
+
+    fn call(&mut self, request: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let line = serde_json::to_string(request).map_err(|e| e.to_string())?;
+        self.writer.write_all(format!("{}\n", line).as_bytes()).map_err(|e| format!("Failed to forward: {}", e))?;
+        let mut response = String::new();
+        if self.reader.read_line(&mut response).map_err(|e| format!("Failed to read reply: {}", e))? == 0 {
+            return Err("Connection closed".to_string());
+        }
+        serde_json::from_str(&response).map_err(|e| format!("Invalid reply: {}", e))
+    }
+}
+
+/// 主循环：逐条转发，直到浏览器关闭 stdin。port 每次连接前读取（实例重启后端口会变）
+pub fn run(input: &mut impl Read, output: &mut impl Write, port: &dyn Fn() -> Result<u16, String>) -> Result<(), String> {
+    let mut upstream: Option<Upstream> = None;
+    while let Some(request) = read_message(input)? {
+        let id = request.get("id").cloned().unwrap_or_default();
+        if upstream.is_none() {
+            upstream = port().and_then(Upstream::connect).map_err(|e| eprintln!("[NativeHost] {}", e)).ok();
+        }
+        let reply = match upstream.as_mut().map(|u| u.call(&request)) {
+            Some(Ok(reply)) => reply,
+            Some(Err(e)) => {
+                eprintln!("[NativeHost] {}", e);
+                upstream = None;
+                serde_json::to_value(BridgeResponse::err(id, "not_running")).unwrap_or_default()
+            }
+            None => serde_json::to_value(BridgeResponse::err(id, "not_running")).unwrap_or_default(),
+        };
+        write_message(output, &reply)?;
+    }
+    Ok(())
+}
+
+/// 运行中实例写入的桥接端口
+pub fn bridge_port() -> Result<u16, String> {
+    let path = crate::app_dirs::config_dir()?.join(PORT_FILE);
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("not_running: {}", e))?;
+    text.trim().parse().map_err(|e| format!("Invalid port file: {}", e))
+}
+
+/// 作为 host 运行（run() 中在构建 Tauri 之前调用），返回进程退出码
+pub fn run_stdio() -> i32 {
+    eprintln!("[NativeHost] Started");
+    match run(&mut std::io::stdin().lock(), &mut std::io::stdout().lock(), &bridge_port) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("[NativeHost] {}", e);
+            1
+        }
+    }
+}
+
+/// 清单内容：Chrome 系按扩展来源授权，Firefox 按扩展 id 授权
+pub fn manifest(browser: Browser, executable: &Path, extension_id: &str) -> serde_json::Value {
+    let mut manifest = serde_json::json!({
+        "name": HOST_NAME,
+        "description": "Notification Listener browser bridge",
+        "path": executable.to_string_lossy(),
+        "type": "stdio",
+    });
+    match browser {
+        Browser::Firefox => manifest["allowed_extensions"] = serde_json::json!([extension_id]),
+        _ => manifest["allowed_origins"] = serde_json::json!([format!("chrome-extension://{}/", extension_id)]),
+    }
+    manifest
+}
+
+/// 各平台的清单目录；Windows 的清单放在配置目录，由注册表指向它（见 registry_key）
+pub fn manifest_dir(browser: Browser, os: &str, home: &Path, config_dir: &Path) -> PathBuf {
+    match (os, browser) {
+        ("windows", _) => config_dir.join("native-messaging").join(format!("{:?}", browser).to_lowercase()),
+        ("macos", Browser::Chrome) => home.join("Library/Application Support/Google/Chrome/NativeMessagingHosts"),
+        ("macos", Browser::Chromium) => home.join("Library/Application Support/Chromium/NativeMessagingHosts"),
+        ("macos", Browser::Edge) => home.join("Library/Application Support/Microsoft Edge/NativeMessagingHosts"),
+        ("macos", Browser::Firefox) => home.join("Library/Application Support/Mozilla/NativeMessagingHosts"),
+        (_, Browser::Chrome) => home.join(".config/google-chrome/NativeMessagingHosts"),
+        (_, Browser::Chromium) => home.join(".config/chromium/NativeMessagingHosts"),
+        (_, Browser::Edge) => home.join(".config/microsoft-edge/NativeMessagingHosts"),
+        (_, Browser::Firefox) => home.join(".mozilla/native-messaging-hosts"),
+    }
+}
+
+/// Windows 下登记清单位置的注册表键（HKCU）
+pub fn registry_key(browser: Browser) -> String {
+    let vendor = match browser {
+        Browser::Chrome => "Google\\Chrome",
+        Browser::Chromium => "Chromium",
+        Browser::Edge => "Microsoft\\Edge",
+        Browser::Firefox => "Mozilla",
+    };
+    format!("HKCU\\Software\\{}\\NativeMessagingHosts\\{}", vendor, HOST_NAME)
+}
+
+/// 写入清单（Windows 另外登记注册表）
+pub fn install(browser: Browser, extension_id: &str) -> Result<InstalledHost, String> {
+    let extension_id = extension_id.trim();
+    if extension_id.is_empty() {
+        return Err("extension_id must not be empty".to_string());
+    }
+    let executable = std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    let dir = manifest_dir(browser, std::env::consts::OS, &home, &crate::app_dirs::config_dir()?);
+    let path = dir.join(format!("{}.json", HOST_NAME));
+    let json = serde_json::to_vec_pretty(&manifest(browser, &executable, extension_id)).map_err(|e| e.to_string())?;
+    crate::storage::write_atomic(&path, &json)?;
+    if cfg!(target_os = "windows") {
+        let status = std::process::Command::new("reg")
+            .args(["add", &registry_key(browser), "/ve", "/t", "REG_SZ", "/d"])
+            .arg(&path)
+            .arg("/f")
+            .status()
+            .map_err(|e| format!("Failed to run reg: {}", e))?;
+        if !status.success() {
+            return Err(format!("Failed to register native messaging host ({})", status));
+        }
+    }
+    println!("[NativeHost] Installed {:?} manifest at {}", browser, path.display());
+    Ok(InstalledHost {
+        browser,
+        manifest_path: path.to_string_lossy().into_owned(),
+        executable: executable.to_string_lossy().into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    use crate::api_tokens::ApiScope;
+    use crate::bridge::{self, BridgeRequest};
+    use crate::commands::AppState;
+    use crate::types::Notification;
+
+    fn frames(messages: &[serde_json::Value]) -> Cursor<Vec<u8>> {
+        let mut buf = Vec::new();
+        for m in messages {
+            write_message(&mut buf, m).unwrap();
+        }
+        Cursor::new(buf)
+    }
+
+    fn replies(output: Vec<u8>) -> Vec<serde_json::Value> {
+        let mut input = Cursor::new(output);
+        std::iter::from_fn(|| read_message(&mut input).unwrap()).collect()
+    }
+
+    /// 在测试线程中启动桥接服务，返回端口与“是否已请求显示窗口”
+    fn start_bridge(state: Arc<AppState>) -> (u16, Arc<std::sync::atomic::AtomicBool>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let focused = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = focused.clone();
+        let handler = Arc::new(move |request: BridgeRequest| {
+            state.handle_bridge_request(request, &|| flag.store(true, std::sync::atomic::Ordering::SeqCst))
+        });
+        std::thread::spawn(move || bridge::serve(listener, handler));
+        (port, focused)
+    }
+
+    #[test]
+    fn test_host_over_pipes() {
+        let state = Arc::new(AppState::default());
+        state.mark_ready(crate::startup::Subsystem::Store);
+        for i in 0..3 {
+            let n = Notification { id: format!("n{}", i), title: Some(format!("t{}", i)), posted_at: Some(i), ..Default::default() };
+            state.store.lock().unwrap().upsert(n, false);
+        }
+        let read_only = state.create_api_token("badge", vec![ApiScope::ReadNotifications]).unwrap().token;
+        let full = state.create_api_token("ext", vec![ApiScope::ReadNotifications, ApiScope::WriteActions]).unwrap().token;
+        let (port, focused) = start_bridge(state.clone());
+
+        let mut input = frames(&[
+            serde_json::json!({ "id": 1, "token": read_only, "command": "get_counts" }),
+            serde_json::json!({ "id": 2, "token": read_only, "command": "list", "args": { "limit": 2 } }),
+            serde_json::json!({ "id": 3, "token": read_only, "command": "mark_read", "args": { "ids": ["n0"] } }),
+            serde_json::json!({ "id": 4, "token": full, "command": "mark_read", "args": { "ids": ["n0"] } }),
+            serde_json::json!({ "id": 5, "command": "get_counts" }),
+            serde_json::json!({ "id": 6, "token": full, "command": "focus" }),
+        ]);
+        let mut output = Vec::new();
+        run(&mut input, &mut output, &|| Ok(port)).unwrap();
+        let out = replies(output);
+
+        assert_eq!(out.len(), 6);
+        assert_eq!(out[0]["result"], serde_json::json!({ "unread": 3, "total": 3 }));
+        let ids: Vec<&str> = out[1]["result"].as_array().unwrap().iter().map(|n| n["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, ["n2", "n1"]);
+        assert_eq!(out[2]["ok"], false);
+        assert!(out[2]["error"].as_str().unwrap().contains("insufficient_scope"));
+        assert_eq!(out[3], serde_json::json!({ "id": 4, "ok": true, "result": { "changed": 1 } }));
+        assert!(out[4]["error"].as_str().unwrap().contains("missing_token"));
+        assert_eq!(out[5]["ok"], true);
+        assert!(focused.load(std::sync::atomic::Ordering::SeqCst));
+        assert_eq!(state.counts().unread, 2);
+    }
+
+    #[test]
+    fn test_not_running_and_framing_limits() {
+        let mut input = frames(&[serde_json::json!({ "id": "a", "command": "get_counts" })]);
+        let mut output = Vec::new();
+        run(&mut input, &mut output, &|| Err("not_running: no port file".into())).unwrap();
+        assert_eq!(replies(output), [serde_json::json!({ "id": "a", "ok": false, "error": "not_running" })]);
+
+        let mut oversized = Cursor::new(((MAX_MESSAGE_BYTES + 1) as u32).to_ne_bytes().to_vec());
+        assert!(read_message(&mut oversized).unwrap_err().contains("too large"));
+        assert_eq!(read_message(&mut Cursor::new(Vec::new())).unwrap(), None);
+    }
+
+    #[test]
+    fn test_launch_detection_and_manifests() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(is_host_launch(&args(&["app", HOST_FLAG])));
+        assert!(is_host_launch(&args(&["app", "chrome-extension://abcdef/", "--parent-window=0"])));
+        assert!(is_host_launch(&args(&["app", "/home/u/.mozilla/native-messaging-hosts/com.droid.notification_listener.json", "ext@example.com"])));
+        assert!(!is_host_launch(&args(&["app"])));
+        assert!(!is_host_launch(&args(&["app", "other.json"])));
+
+        let exe = Path::new("/opt/app/notification-listener");
+        let chrome = manifest(Browser::Chrome, exe, "abcdef");
+        assert_eq!(chrome["allowed_origins"], serde_json::json!(["chrome-extension://abcdef/"]));
+        assert_eq!(chrome["type"], "stdio");
+        let firefox = manifest(Browser::Firefox, exe, "ext@example.com");
+        assert_eq!(firefox["allowed_extensions"], serde_json::json!(["ext@example.com"]));
+        assert!(firefox.get("allowed_origins").is_none());
+
+        let (home, config) = (Path::new("/home/u"), Path::new("/cfg"));
+        assert_eq!(manifest_dir(Browser::Chrome, "linux", home, config), Path::new("/home/u/.config/google-chrome/NativeMessagingHosts"));
+        assert_eq!(manifest_dir(Browser::Firefox, "macos", home, config), Path::new("/home/u/Library/Application Support/Mozilla/NativeMessagingHosts"));
+        assert_eq!(manifest_dir(Browser::Edge, "windows", home, config), Path::new("/cfg/native-messaging/edge"));
+        assert_eq!(registry_key(Browser::Chrome), "HKCU\\Software\\Google\\Chrome\\NativeMessagingHosts\\com.droid.notification_listener");
+    }
+}
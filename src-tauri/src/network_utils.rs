@@ -1,4 +1,9 @@
-use std::net::TcpListener;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 /// 检查指定端口是否可用
 pub fn check_port_available(port: u16) -> bool {
@@ -26,6 +31,311 @@ pub fn get_local_ip() -> Result<String, String> {
     }
 }
 
+/// 端口绑定失败（或绑定成功但局域网不可达）的分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindErrorKind {
+    /// Unix 下 1024 以下端口需要 root 权限
+    PrivilegedPort,
+    /// 端口已被其他进程占用
+    AddrInUse,
+    /// 地址不可用（网卡已变化等）
+    AddrNotAvailable,
+    /// 系统拒绝访问（Windows 保留端口等）
+    AccessDenied,
+    /// 绑定成功但局域网自检失败，多半是防火墙
+    LikelyFirewall,
+    Other,
+}
+
+/// 结构化的端口错误，附带给用户的提示
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BindError {
+    pub port: u16,
+    pub kind: BindErrorKind,
+    pub message: String,
+    pub hint: String,
+    /// 占用端口的进程（平台支持时）
+    pub process: Option<String>,
+    /// 端口占用时的进程查询结果
+    #[serde(default)]
+    pub owner: Option<PortUser>,
+}
+
+impl std::fmt::Display for BindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}（{}）", self.message, self.hint)
+    }
+}
+
+/// 按 io::Error 类型对绑定失败分类
+pub fn classify_bind_error(port: u16, err: &io::Error) -> BindErrorKind {
+    match err.kind() {
+        io::ErrorKind::AddrInUse => BindErrorKind::AddrInUse,
+        io::ErrorKind::AddrNotAvailable => BindErrorKind::AddrNotAvailable,
+        io::ErrorKind::PermissionDenied if cfg!(unix) && port < 1024 => BindErrorKind::PrivilegedPort,
+        io::ErrorKind::PermissionDenied => BindErrorKind::AccessDenied,
+        _ => BindErrorKind::Other,
+    }
+}
+
+fn hint_for(kind: BindErrorKind, port: u16, process: Option<&str>) -> String {
+    match kind {
+        BindErrorKind::PrivilegedPort => format!("端口 {} 需要管理员权限，请使用 1024 以上的端口", port),
+        BindErrorKind::AddrInUse => match process {
+            Some(p) => format!("端口 {} 已被 {} 占用，请关闭该程序或换一个端口", port, p),
+            None => format!("端口 {} 已被占用，请换一个端口", port),
+        },
+        BindErrorKind::AddrNotAvailable => "网络地址不可用，请检查网络连接后重试".to_string(),
+        BindErrorKind::AccessDenied => format!("系统拒绝使用端口 {}，可能是保留端口，请换一个端口", port),
+        BindErrorKind::LikelyFirewall => format!("手机可能无法访问端口 {}，请检查防火墙是否放行", port),
+        BindErrorKind::Other => "启动服务失败，请换一个端口重试".to_string(),
+    }
+}
+
+impl BindError {
+    pub fn new(port: u16, kind: BindErrorKind, message: String, process: Option<String>) -> Self {
+        let hint = hint_for(kind, port, process.as_deref());
+        Self { port, kind, message, hint, process, owner: None }
+    }
+
+    /// 由绑定失败的 io::Error 构造；端口占用时尽量查出占用进程（最多约 2 秒）
+    pub fn from_io(port: u16, err: &io::Error) -> Self {
+        let kind = classify_bind_error(port, err);
+        let message = format!("Failed to bind port {}: {}", port, err);
+        if kind != BindErrorKind::AddrInUse {
+            return Self::new(port, kind, message, None);
+        }
+        let owner = identify_port_user(port);
+        let process = owner.is_known().then(|| {
+            if owner.same_app {
+                format!("本应用的另一个实例 {}", owner.label())
+            } else {
+                owner.label()
+            }
+        });
+        Self { owner: Some(owner), ..Self::new(port, kind, message, process) }
+    }
+}
+
+/// 占用端口的进程；查不到时 pid 与 name 均为 None
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PortUser {
+    pub pid: Option<u32>,
+    pub name: Option<String>,
+    /// 是本应用的另一个实例（如未退出干净的旧进程）
+    pub same_app: bool,
+}
+
+impl PortUser {
+    fn new(pid: Option<u32>, name: Option<String>) -> Self {
+        let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        let same_app = name.as_deref().is_some_and(is_this_app) && pid != Some(std::process::id());
+        Self { pid, name, same_app }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.pid.is_some() || self.name.is_some()
+    }
+
+    /// 如 "NotificationListener.exe (PID 4312)"；查不到时为 "未知进程"
+    pub fn label(&self) -> String {
+        match (&self.name, self.pid) {
+            (Some(name), Some(pid)) => format!("{} (PID {})", name, pid),
+            (Some(name), None) => name.clone(),
+            (None, Some(pid)) => format!("PID {}", pid),
+            (None, None) => "未知进程".to_string(),
+        }
+    }
+}
+
+/// 进程名与本程序的可执行文件名相同（忽略大小写与扩展名）
+fn is_this_app(name: &str) -> bool {
+    let stem = |s: &str| s.rsplit(['/', '\\']).next().unwrap_or(s).trim_end_matches(".exe").to_lowercase();
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .is_some_and(|exe| stem(&exe) == stem(name))
+}
+
+/// 端口占用查询的时间上限
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 查找监听指定端口的进程；最多等待约 2 秒，超时或查不到时返回未知
+pub fn identify_port_user(port: u16) -> PortUser {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(lookup_port_user(port));
+    });
+    match rx.recv_timeout(IDENTIFY_TIMEOUT) {
+        Ok(user) => user.unwrap_or_default(),
+        Err(_) => {
+            println!("[Network] Port {} owner lookup timed out", port);
+            PortUser::default()
+        }
+    }
+}
+
+fn lookup_port_user(port: u16) -> Option<PortUser> {
+    #[cfg(windows)]
+    {
+        let out = std::process::Command::new("netstat").args(["-ano", "-p", "TCP"]).output().ok()?;
+        let pid = parse_netstat_pid(&String::from_utf8_lossy(&out.stdout), port)?;
+        let name = std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+            .output()
+            .ok()
+            .and_then(|out| {
+                String::from_utf8_lossy(&out.stdout)
+                    .split(',')
+                    .next()
+                    .map(|s| s.trim().trim_matches('"').to_string())
+                    .filter(|s| !s.is_empty() && !s.starts_with("INFO"))
+            });
+        Some(PortUser::new(Some(pid), name))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let inodes: Vec<u64> = ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|p| std::fs::read_to_string(p).ok())
+            .flat_map(|table| parse_proc_net_tcp(&table, port))
+            .collect();
+        if inodes.is_empty() {
+            return None;
+        }
+        // 只能看到有权限读取 fd 的进程；看不到时仍知道端口被占用，但进程未知
+        let pid = find_socket_owner(&inodes)?;
+        let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok();
+        Some(PortUser::new(Some(pid), name))
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        let out = std::process::Command::new("lsof")
+            .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-Fpc"])
+            .output()
+            .ok()?;
+        parse_lsof_owner(&String::from_utf8_lossy(&out.stdout))
+    }
+}
+
+/// 解析 /proc/net/tcp(6)，返回监听指定端口的 socket inode
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_proc_net_tcp(table: &str, port: u16) -> Vec<u64> {
+    const LISTEN: &str = "0A";
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            // sl  local_address  rem_address  st  tx:rx  tr:tm  retrnsmt  uid  timeout  inode
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let local_port = cols.get(1)?.rsplit(':').next()?;
+            if u16::from_str_radix(local_port, 16).ok()? != port || *cols.get(3)? != LISTEN {
+                return None;
+            }
+            cols.get(9)?.parse().ok().filter(|inode| *inode != 0)
+        })
+        .collect()
+}
+
+/// 在 /proc/*/fd 中查找持有这些 socket 的进程
+#[cfg(target_os = "linux")]
+fn find_socket_owner(inodes: &[u64]) -> Option<u32> {
+    let targets: Vec<String> = inodes.iter().map(|i| format!("socket:[{}]", i)).collect();
+    std::fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+        let fds = std::fs::read_dir(entry.path().join("fd")).ok()?;
+        fds.flatten()
+            .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+            .any(|link| targets.iter().any(|t| link.as_os_str() == t.as_str()))
+            .then_some(pid)
+    })
+}
+
+/// 解析 `netstat -ano` 输出，找出监听指定端口的 PID
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn parse_netstat_pid(output: &str, port: u16) -> Option<u32> {
+    let suffix = format!(":{}", port);
+    output.lines().find_map(|line| {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        // 协议  本地地址  外部地址  状态  PID
+        if cols.len() < 5 || !cols[0].eq_ignore_ascii_case("TCP") || !cols[1].ends_with(&suffix) {
+            return None;
+        }
+        if cols[3] != "LISTENING" {
+            return None;
+        }
+        cols[4].parse().ok()
+    })
+}
+
+/// 解析 `lsof -Fpc` 输出（p<pid> / c<command> 各占一行）
+#[cfg_attr(any(windows, target_os = "linux"), allow(dead_code))]
+pub fn parse_lsof_owner(output: &str) -> Option<PortUser> {
+    let mut pid = None;
+    for line in output.lines() {
+        if let Some(p) = line.strip_prefix('p') {
+            pid = p.parse().ok();
+        } else if let (Some(c), Some(_)) = (line.strip_prefix('c'), pid) {
+            return Some(PortUser::new(pid, Some(c.to_string())));
+        }
+    }
+    pid.map(|p| PortUser::new(Some(p), None))
+}
+
+// ============ 子网计算 ============
+
+/// 本机网卡无法取得子网掩码时假定的前缀长度（家用/办公局域网最常见的 /24）
+pub const DEFAULT_PREFIX: u8 = 24;
+
+/// 前缀长度对应的掩码（超过 32 按 32 处理）
+pub fn prefix_mask(prefix: u8) -> u32 {
+    match prefix.min(32) {
+        0 => 0,
+        p => u32::MAX << (32 - p),
+    }
+}
+
+/// 子网的网络地址
+pub fn network_address(ip: Ipv4Addr, prefix: u8) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(ip) & prefix_mask(prefix))
+}
+
+/// 两个地址是否在同一子网（同一广播域）
+pub fn same_subnet(a: Ipv4Addr, b: Ipv4Addr, prefix: u8) -> bool {
+    network_address(a, prefix) == network_address(b, prefix)
+}
+
+/// 便于阅读的子网写法：/24 为 "192.168.50.x"，/16 为 "10.0.x.x"，其他为 "10.0.4.0/22"
+pub fn subnet_label(ip: Ipv4Addr, prefix: u8) -> String {
+    let o = network_address(ip, prefix).octets();
+    match prefix {
+        24 => format!("{}.{}.{}.x", o[0], o[1], o[2]),
+        16 => format!("{}.{}.x.x", o[0], o[1]),
+        8 => format!("{}.x.x.x", o[0]),
+        p => format!("{}.{}.{}.{}/{}", o[0], o[1], o[2], o[3], p.min(32)),
+    }
+}
+
+/// 局域网可直连的私有地址（RFC 1918）
+pub fn is_private_lan(ip: Ipv4Addr) -> bool {
+    ip.is_private()
+}
+
+/// 本机的 IPv4 网卡（名称, 地址），不含回环地址
+pub fn local_ipv4_interfaces() -> Vec<(String, Ipv4Addr)> {
+    local_ip_address::list_afinet_netifas()
+        .map(|list| {
+            list.into_iter()
+                .filter_map(|(name, ip)| match ip {
+                    IpAddr::V4(v4) if !v4.is_loopback() => Some((name, v4)),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,4 +368,95 @@ mod tests {
         assert!(ip.is_ok());
         println!("Local IP: {}", ip.unwrap());
     }
+
+    #[test]
+    fn test_classify_bind_error() {
+        let err = |kind| io::Error::new(kind, "x");
+        assert_eq!(classify_bind_error(8080, &err(io::ErrorKind::AddrInUse)), BindErrorKind::AddrInUse);
+        assert_eq!(
+            classify_bind_error(8080, &err(io::ErrorKind::AddrNotAvailable)),
+            BindErrorKind::AddrNotAvailable
+        );
+        assert_eq!(classify_bind_error(8080, &err(io::ErrorKind::PermissionDenied)), BindErrorKind::AccessDenied);
+        let low = classify_bind_error(80, &err(io::ErrorKind::PermissionDenied));
+        if cfg!(unix) {
+            assert_eq!(low, BindErrorKind::PrivilegedPort);
+        } else {
+            assert_eq!(low, BindErrorKind::AccessDenied);
+        }
+        assert_eq!(classify_bind_error(8080, &err(io::ErrorKind::Other)), BindErrorKind::Other);
+    }
+
+    #[test]
+    fn test_bind_in_use_is_classified() {
+        let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let err = TcpListener::bind(("0.0.0.0", port)).unwrap_err();
+        let e = BindError::from_io(port, &err);
+        assert_eq!(e.kind, BindErrorKind::AddrInUse);
+        assert!(e.hint.contains(&port.to_string()));
+    }
+
+    #[test]
+    fn test_parse_port_owner() {
+        let netstat = "\
+  Proto  Local Address          Foreign Address        State           PID
+  TCP    0.0.0.0:135            0.0.0.0:0              LISTENING       1020
+  TCP    0.0.0.0:10035          0.0.0.0:0              LISTENING       4242
+  TCP    192.168.1.5:10035      192.168.1.9:51000      ESTABLISHED     4242
+  TCP    0.0.0.0:100350         0.0.0.0:0              LISTENING       1
+";
+        assert_eq!(parse_netstat_pid(netstat, 10035), Some(4242));
+        assert_eq!(parse_netstat_pid(netstat, 80), None);
+
+        let label = |out: &str| parse_lsof_owner(out).map(|u| u.label());
+        assert_eq!(label("p812\ncnginx\n").as_deref(), Some("nginx (PID 812)"));
+        assert_eq!(label("p812\n").as_deref(), Some("PID 812"));
+        assert_eq!(label(""), None);
+
+        let proc_tcp = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:2733 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 91234 1 0000000000000000
+   1: 0500A8C0:2733 0900A8C0:C738 01 00000000:00000000 00:00000000 00000000  1000        0 91300 1 0000000000000000
+   2: 0100007F:0035 00000000:0000 0A 00000000:00000000 00:00000000 00000000   101        0 1800 1 0000000000000000
+";
+        assert_eq!(parse_proc_net_tcp(proc_tcp, 10035), [91234]);
+        assert!(parse_proc_net_tcp(proc_tcp, 80).is_empty());
+        assert_eq!(PortUser::default().label(), "未知进程");
+    }
+
+    #[test]
+    fn test_identify_own_listener() {
+        let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let user = identify_port_user(port);
+        // 部分环境（无 lsof / 受限的 /proc）查不到，查到时一定是本进程
+        if let Some(pid) = user.pid {
+            assert_eq!(pid, std::process::id());
+            assert!(!user.same_app);
+        }
+    }
+
+    #[test]
+    fn test_subnet_math() {
+        let ip = |s: &str| s.parse::<Ipv4Addr>().unwrap();
+        // /24
+        assert!(same_subnet(ip("192.168.50.10"), ip("192.168.50.200"), 24));
+        assert!(!same_subnet(ip("192.168.50.10"), ip("192.168.51.10"), 24));
+        assert_eq!(subnet_label(ip("192.168.50.77"), 24), "192.168.50.x");
+        // /16
+        assert!(same_subnet(ip("10.0.1.5"), ip("10.0.200.9"), 16));
+        assert!(!same_subnet(ip("10.0.1.5"), ip("10.1.1.5"), 16));
+        assert_eq!(subnet_label(ip("10.0.1.5"), 16), "10.0.x.x");
+        // 非整字节的掩码
+        assert!(same_subnet(ip("10.0.4.1"), ip("10.0.7.254"), 22));
+        assert!(!same_subnet(ip("10.0.4.1"), ip("10.0.8.1"), 22));
+        assert_eq!(subnet_label(ip("10.0.6.9"), 22), "10.0.4.0/22");
+        assert!(same_subnet(ip("192.168.1.33"), ip("192.168.1.62"), 27));
+        assert!(!same_subnet(ip("192.168.1.33"), ip("192.168.1.65"), 27));
+        assert_eq!(prefix_mask(0), 0);
+        assert_eq!(prefix_mask(32), u32::MAX);
+        assert_eq!(prefix_mask(40), u32::MAX);
+        assert!(is_private_lan(ip("172.20.0.1")) && !is_private_lan(ip("100.64.0.1")));
+    }
 }
@@ -0,0 +1,241 @@
+//! 本机断网时暂停自动重连：维护任务定期检查网卡，没有可用的非回环地址（回环与 169.254 自动地址不算）时
+//! 进入“等待网络”，自动重连一律跳过，不在断网期间对每台设备反复尝试；
+//! 网络恢复时立即对已保存、当前未连接的设备各尝试一次。
+//! 等待状态显示在连接列表（waiting_for_network）与托盘提示中，变化时推送 network-lost / network-restored。
+//! 手动重连（reconnect_android）不受影响，每次都会尝试。自动重连按设备的已知地址依次尝试（见 failover）。
+
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use parking_lot::{Mutex, RwLock};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::network_utils;
+
+/// 托盘提示中的等待网络说明
+pub const WAITING_TOOLTIP: &str = "Waiting for network";
+
+/// 本机网卡来源（测试中替换为固定列表）
+pub trait InterfaceProvider: Send + Sync {
+    /// (名称, 地址)，不含回环地址
+    fn interfaces(&self) -> Vec<(String, Ipv4Addr)>;
+}
+
+pub struct SystemInterfaces;
+
+impl InterfaceProvider for SystemInterfaces {
+    fn interfaces(&self) -> Vec<(String, Ipv4Addr)> {
+        network_utils::local_ipv4_interfaces()
+    }
+}
+
+/// 能用来连接手机的地址：非回环、非链路本地（DHCP 失败时系统自行分配的 169.254.x.x）
+pub fn is_usable(ip: Ipv4Addr) -> bool {
+    !ip.is_loopback() && !ip.is_link_local() && !ip.is_unspecified()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NetworkStatus {
+    pub online: bool,
+    /// 进入等待网络的时间（在线时为 None）
+    pub offline_since: Option<i64>,
+}
+
+pub struct NetworkWatch {
+    provider: RwLock<Arc<dyn InterfaceProvider>>,
+    offline_since: Mutex<Option<i64>>,
+}
+
+impl Default for NetworkWatch {
+    fn default() -> Self {
+        Self { provider: RwLock::new(Arc::new(SystemInterfaces)), offline_since: Mutex::new(None) }
+    }
+}
+
+/// 一次检查的状态变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Unchanged,
+    Lost,
+    Restored,
+}
+
+impl NetworkWatch {
+    #[cfg(test)]
+    pub fn set_provider(&self, provider: Arc<dyn InterfaceProvider>) {
+        *self.provider.write() = provider;
+    }
+
+    /// 自动重连是否暂停
+    pub fn is_parked(&self) -> bool {
+        self.offline_since.lock().is_some()
+    }
+
+    pub fn status(&self) -> NetworkStatus {
+        let offline_since = *self.offline_since.lock();
+        NetworkStatus { online: offline_since.is_none(), offline_since }
+    }
+
+    /// 读取网卡并更新状态
+    pub fn poll(&self, now: i64) -> Transition {
+        let provider = self.provider.read().clone();
+        let online = provider.interfaces().iter().any(|(_, ip)| is_usable(*ip));
+        let mut offline_since = self.offline_since.lock();
+        match (online, offline_since.is_some()) {
+            (false, false) => {
+                *offline_since = Some(now);
+                Transition::Lost
+            }
+            (true, true) => {
+                *offline_since = None;
+                Transition::Restored
+            }
+            _ => Transition::Unchanged,
+        }
+    }
+}
+
+impl AppState {
+    /// 维护任务：检查网络，恢复时立即重连
+    pub fn check_network(&self, now: i64) -> Result<String, String> {
+        match self.network.poll(now) {
+            Transition::Unchanged if self.network.is_parked() => Ok("waiting for network".to_string()),
+            Transition::Unchanged => Ok(String::new()),
+            Transition::Lost => {
+                println!("[Network] No usable interface, parking reconnects");
+                self.events.emit("network-lost", self.network.status());
+                self.refresh_tray_tooltip();
+                Ok("parked".to_string())
+            }
+            Transition::Restored => {
+                println!("[Network] Network is back, reconnecting now");
+                self.events.emit("network-restored", self.network.status());
+                self.refresh_tray_tooltip();
+                let (attempted, ok) = self.resume_reconnects();
+                Ok(format!("{} of {} reconnected", ok, attempted))
+            }
+        }
+    }
+
+    /// 对已保存、当前未连接的设备各自动重连一次；等待网络时全部跳过。返回 (尝试数, 成功数)
+    pub(crate) fn resume_reconnects(&self) -> (usize, usize) {
+        if self.network.is_parked() {
+            return (0, 0);
+        }
+        let ids: Vec<String> = {
+            let registry = self.endpoints.lock().unwrap();
+            let clients = self.clients.read();
+            registry
+                .devices()
+                .filter(|e| !e.needs_reauth && !clients.contains_key(&e.connection_id))
+                .map(|e| e.connection_id.clone())
+                .collect()
+        };
+        let mut ok = 0;
+        for id in &ids {
+            match self.auto_reconnect_device(id) {
+                Ok(_) => ok += 1,
+                Err(e) => println!("[Network] Reconnect {} failed: {}", id, e),
+            }
+        }
+        (ids.len(), ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 可随时切换的网卡列表
+    #[derive(Default)]
+    struct FakeInterfaces(Mutex<Vec<(String, Ipv4Addr)>>);
+
+    impl InterfaceProvider for FakeInterfaces {
+        fn interfaces(&self) -> Vec<(String, Ipv4Addr)> {
+            self.0.lock().clone()
+        }
+    }
+
+    impl FakeInterfaces {
+        fn set(&self, ips: &[Ipv4Addr]) {
+            *self.0.lock() = ips.iter().map(|ip| ("eth0".to_string(), *ip)).collect();
+        }
+    }
+
+    /// 接受连接后立即断开（登录失败），记录连接次数
+    fn counting_phone() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let count = Arc::new(AtomicUsize::new(0));
+        let seen = count.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                seen.fetch_add(1, Ordering::SeqCst);
+                drop(stream);
+            }
+        });
+        (addr, count)
+    }
+
+    fn offline_state() -> (AppState, Arc<FakeInterfaces>) {
+        let state = AppState::default();
+        let interfaces = Arc::new(FakeInterfaces::default());
+        interfaces.set(&[Ipv4Addr::new(169, 254, 3, 7)]);
+        state.network.set_provider(interfaces.clone());
+        (state, interfaces)
+    }
+
+    #[test]
+    fn test_park_and_resume() {
+        let (addr, attempts) = counting_phone();
+        let (state, interfaces) = offline_state();
+        state.remember_endpoint("pixel", &addr, "token", None, None);
+
+        assert_eq!(state.check_network(100).unwrap(), "parked");
+        assert!(state.network.is_parked());
+        assert_eq!(state.network.status(), NetworkStatus { online: false, offline_since: Some(100) });
+        assert!(state.tray_tooltip().ends_with(WAITING_TOOLTIP));
+        // 等待期间不自动重连
+        assert_eq!(state.resume_reconnects(), (0, 0));
+        assert_eq!(state.check_network(110).unwrap(), "waiting for network");
+        assert_eq!(attempts.load(Ordering::SeqCst), 0);
+
+        // 网络恢复：立即尝试一次
+        interfaces.set(&[Ipv4Addr::new(192, 168, 1, 20)]);
+        assert_eq!(state.check_network(120).unwrap(), "0 of 1 reconnected");
+        assert!(!state.network.is_parked());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(!state.tray_tooltip().contains(WAITING_TOOLTIP));
+        assert_eq!(state.check_network(130).unwrap(), "");
+        let names: Vec<String> =
+            state.events.take_captured().into_iter().map(|(name, _)| name).filter(|n| n.starts_with("network-")).collect();
+        assert_eq!(names, ["network-lost", "network-restored"]);
+    }
+
+    #[test]
+    fn test_manual_reconnect_overrides_parking() {
+        let (addr, attempts) = counting_phone();
+        let (state, _) = offline_state();
+        state.remember_endpoint("pixel", &addr, "token", None, None);
+        state.check_network(100).unwrap();
+
+        // 手动重连照常尝试（手机端断开导致登录失败），之后仍在等待网络
+        assert!(state.reconnect_device("pixel").is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(state.network.is_parked());
+        assert_eq!(state.resume_reconnects(), (0, 0));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_usable_addresses() {
+        assert!(is_usable(Ipv4Addr::new(10, 0, 0, 2)));
+        assert!(!is_usable(Ipv4Addr::LOCALHOST));
+        assert!(!is_usable(Ipv4Addr::new(169, 254, 1, 1)));
+        assert!(!is_usable(Ipv4Addr::UNSPECIFIED));
+    }
+}
@@ -0,0 +1,114 @@
+//! 文本归一化：搜索、去重（内容是否变化）、规则匹配与会话键比较共用同一套规则，
+//! 避免“看起来一样”的文本在不同环节得到不同结果。
+//! 依次做 NFC、全角 ASCII 区标点/字母折叠为半角、去掉 emoji 变体选择符、空白折叠为单个空格并去掉首尾空白。
+//! 只用于比较，展示与存储始终保留原文；输入已是归一形式时返回 Cow::Borrowed，不分配。
+
+use std::borrow::Cow;
+
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// 单个字符的折叠；None 表示丢弃
+pub fn fold_char(c: char) -> Option<char> {
+    match c {
+        // 全角 ！ 到 ～ 对应 ASCII ! 到 ~
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0),
+        // 变体选择符（❤️ 与 ❤ 视为相同）；ZWJ 保留，组合 emoji 不会被拆开
+        '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}' => None,
+        // 全角空格、NBSP、换行等
+        c if c.is_whitespace() => Some(' '),
+        c => Some(c),
+    }
+}
+
+/// 已是归一形式：不含需要折叠的字符、没有连续空白、首尾无空白
+fn is_normalized(s: &str) -> bool {
+    if s.starts_with(' ') || s.ends_with(' ') || s.contains("  ") {
+        return false;
+    }
+    s.chars().all(|c| fold_char(c) == Some(c)) && is_nfc_quick(s.chars()) == IsNormalized::Yes
+}
+
+/// 归一化后的比较形式
+pub fn normalize(s: &str) -> Cow<'_, str> {
+    if is_normalized(s) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut pending_space = false;
+    for c in s.nfc().filter_map(fold_char) {
+        if c == ' ' {
+            pending_space = !out.is_empty();
+            continue;
+        }
+        if pending_space {
+            out.push(' ');
+            pending_space = false;
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+/// 两个可选字段归一化后是否相同
+pub fn same(a: Option<&str>, b: Option<&str>) -> bool {
+    a.map(normalize) == b.map(normalize)
+}
+
+/// 逐字符折叠（规范分解 + fold_char），供需要保留原始字符下标的调用方（搜索高亮）使用；
+/// 按分解形式比较与按 NFC 比较结果一致
+pub fn fold_each(c: char, mut emit: impl FnMut(char)) {
+    unicode_normalization::char::decompose_canonical(c, |d| {
+        if let Some(d) = fold_char(d) {
+            emit(d)
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tricky_cases() {
+        let cases = [
+            // 全角冒号、括号、字母
+            ("验证码：123456", "验证码:123456"),
+            ("（ＡＢＣ）", "(ABC)"),
+            // NBSP、全角空格、换行与连续空白
+            ("a\u{00A0}b\u{3000}c\n\n d ", "a b c d"),
+            // 变体选择符
+            ("❤\u{FE0F}", "❤"),
+            ("👍\u{FE0F} ok", "👍 ok"),
+            // ZWJ 家庭 emoji 保持完整
+            ("👨\u{200D}👩\u{200D}👧", "👨\u{200D}👩\u{200D}👧"),
+            // 组合字符合成为 NFC
+            ("e\u{0301}te\u{0301}", "été"),
+            // 中文全角句号不在 ASCII 区，不折叠
+            ("好的。", "好的。"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(normalize(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_borrowed_when_unchanged() {
+        assert!(matches!(normalize("明天 10:00 开会 👨\u{200D}👩\u{200D}👧"), Cow::Borrowed(_)));
+        assert!(matches!(normalize(""), Cow::Borrowed(_)));
+        assert!(matches!(normalize("验证码：1"), Cow::Owned(_)));
+        assert!(matches!(normalize(" x"), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_same_and_fold_each() {
+        assert!(same(Some("❤\u{FE0F} 收到"), Some("❤ 收到")));
+        assert!(same(None, None));
+        assert!(!same(Some("a"), None));
+
+        let mut out = String::new();
+        for c in "é：\u{FE0F}".chars() {
+            fold_each(c, |d| out.push(d));
+        }
+        assert_eq!(out, "e\u{0301}:");
+    }
+}
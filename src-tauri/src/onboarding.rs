@@ -0,0 +1,117 @@
+//! 首次使用引导状态机（由 Rust 端维护并持久化）。
+//! 托盘、提示与 WebView 都以这里的状态为准，避免各自判断用户进行到哪一步。
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 引导步骤，按声明顺序依次推进
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    #[default]
+    FreshInstall,
+    PermissionsExplained,
+    ServerStarted,
+    QrShown,
+    FirstDevicePaired,
+    Done,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct OnboardingState {
+    pub step: OnboardingStep,
+    /// 最近一次变化的时间（秒）
+    pub updated_at: i64,
+}
+
+#[derive(Default)]
+pub struct Onboarding {
+    state: Mutex<OnboardingState>,
+}
+
+impl Onboarding {
+    pub fn get(&self) -> OnboardingState {
+        self.state.lock().clone()
+    }
+
+    /// 从持久化数据恢复
+    pub fn restore(&self, state: OnboardingState) {
+        *self.state.lock() = state;
+    }
+
+    /// 推进到指定步骤（可跳步）；只前进不后退，状态未变化时返回 None
+    pub fn advance(&self, step: OnboardingStep, now: i64) -> Option<OnboardingState> {
+        let mut state = self.state.lock();
+        if step <= state.step {
+            return None;
+        }
+        state.step = step;
+        state.updated_at = now;
+        Some(state.clone())
+    }
+
+    /// 恢复出厂设置时回到初始状态
+    pub fn reset(&self, now: i64) -> OnboardingState {
+        let mut state = self.state.lock();
+        *state = OnboardingState {
+            step: OnboardingStep::FreshInstall,
+            updated_at: now,
+        };
+        state.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_is_forward_only() {
+        let ob = Onboarding::default();
+        assert_eq!(ob.get().step, OnboardingStep::FreshInstall);
+        assert!(ob.advance(OnboardingStep::ServerStarted, 1).is_some());
+        assert!(ob.advance(OnboardingStep::PermissionsExplained, 2).is_none());
+        assert!(ob.advance(OnboardingStep::ServerStarted, 3).is_none());
+        let s = ob.advance(OnboardingStep::FirstDevicePaired, 4).unwrap();
+        assert_eq!(s, OnboardingState { step: OnboardingStep::FirstDevicePaired, updated_at: 4 });
+        assert_eq!(ob.reset(5).step, OnboardingStep::FreshInstall);
+    }
+
+    #[test]
+    fn test_step_serde_names() {
+        let json = serde_json::to_string(&OnboardingStep::FirstDevicePaired).unwrap();
+        assert_eq!(json, "\"first_device_paired\"");
+        let step: OnboardingStep = serde_json::from_str("\"qr_shown\"").unwrap();
+        assert_eq!(step, OnboardingStep::QrShown);
+    }
+
+    #[test]
+    fn test_app_state_persists_and_resets() {
+        use crate::commands::AppState;
+
+        let dir = crate::storage::temp_dir("onboarding");
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        // 只关心引导事件，丢弃加载时的 subsystem-ready
+        state.events.take_captured();
+        state.advance_onboarding(OnboardingStep::FirstDevicePaired);
+        // 重复推进不再发事件
+        state.advance_onboarding(OnboardingStep::QrShown);
+        let events = state.events.take_captured();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, "onboarding-changed");
+        assert_eq!(events[0].1["step"], "first_device_paired");
+
+        let restarted = AppState::default();
+        restarted.init_storage(dir.clone());
+        assert_eq!(restarted.advance_onboarding(OnboardingStep::QrShown).step, OnboardingStep::FirstDevicePaired);
+
+        restarted.factory_reset().unwrap();
+        let again = AppState::default();
+        again.init_storage(dir.clone());
+        assert_eq!(again.advance_onboarding(OnboardingStep::FreshInstall).step, OnboardingStep::FreshInstall);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,280 @@
+//! 系统专注/勿扰状态：Windows 的“请勿打扰”（通知总开关）、macOS 专注模式、Linux 桌面的通知勿扰。
+//! 系统正在屏蔽其他应用的通知时，本应用也不应自己弹出系统通知或响铃：开启 respect_os_focus（默认）时，
+//! 系统勿扰与免打扰时段同样处理（只有 bypass_quiet_hours 的级别仍弹出，且不响铃）。
+//! 检测都是尽力而为，通过系统自带的命令行工具读取：读不到（如 Windows 10 的专注助手、未授权的 macOS）时报告 unknown，不改变任何行为。
+//! 由维护任务每 15 秒刷新缓存，入库路径只读缓存；状态变化时推送 os-focus-changed，前端据此显示“专注助手已开启”的标记。
+//! 减少动态效果（prefers-reduced-motion）由 WebView 直接读取，不经过这里。
+
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+/// 缓存有效期（与 os_focus 维护任务的间隔一致）
+pub const CACHE_TTL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusState {
+    Active,
+    Inactive,
+    #[default]
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+pub struct OsFocusStatus {
+    pub state: FocusState,
+    /// 检测来源，如 "windows_toasts_setting" / "macos_focus" / "dbus_inhibited"；unknown 时为 None
+    pub source: Option<String>,
+    pub checked_at: i64,
+    /// 是否正因系统勿扰而不弹出（state 为 active 且开启了 respect_os_focus）
+    pub suppressing: bool,
+}
+
+#[derive(Default)]
+pub struct OsFocus {
+    current: Mutex<Option<(Instant, OsFocusStatus)>>,
+}
+
+impl OsFocus {
+    /// 缓存中的系统勿扰是否开启（不触发检测）
+    pub fn is_active(&self) -> bool {
+        self.current.lock().as_ref().is_some_and(|(_, s)| s.state == FocusState::Active)
+    }
+
+    fn fresh(&self, now: Instant) -> Option<OsFocusStatus> {
+        self.current.lock().as_ref().filter(|(at, _)| now.duration_since(*at) < CACHE_TTL).map(|(_, s)| s.clone())
+    }
+
+    /// 写入新状态，返回状态是否变化
+    fn update(&self, status: OsFocusStatus, now: Instant) -> bool {
+        let mut current = self.current.lock();
+        let changed = current.as_ref().is_none_or(|(_, s)| s.state != status.state);
+        *current = Some((now, status));
+        changed
+    }
+}
+
+/// `reg query ... /v NOC_GLOBAL_SETTING_TOASTS_ENABLED` 的输出：0x0 表示关闭了所有通知（Windows 11 的请勿打扰）
+fn parse_toasts_enabled(output: &str) -> FocusState {
+    let value = output
+        .lines()
+        .find(|l| l.contains("NOC_GLOBAL_SETTING_TOASTS_ENABLED"))
+        .and_then(|l| l.split_whitespace().last())
+        .and_then(|v| u32::from_str_radix(v.trim_start_matches("0x"), 16).ok());
+    match value {
+        Some(0) => FocusState::Active,
+        Some(_) => FocusState::Inactive,
+        None => FocusState::Unknown,
+    }
+}
+
+/// ~/Library/DoNotDisturb/DB/Assertions.json：有手动开启的专注模式时 storeAssertionRecords 非空
+fn parse_macos_assertions(json: &str) -> FocusState {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return FocusState::Unknown;
+    };
+    let Some(data) = value.get("data").and_then(|d| d.as_array()) else {
+        return FocusState::Unknown;
+    };
+    let active = data
+        .iter()
+        .filter_map(|d| d.get("storeAssertionRecords").and_then(|r| r.as_array()))
+        .any(|records| !records.is_empty());
+    if active { FocusState::Active } else { FocusState::Inactive }
+}
+
+/// 旧版 macOS：`defaults -currentHost read com.apple.notificationcenterui doNotDisturb`
+fn parse_defaults_bool(output: &str) -> FocusState {
+    match output.trim() {
+        "1" => FocusState::Active,
+        "0" => FocusState::Inactive,
+        _ => FocusState::Unknown,
+    }
+}
+
+/// freedesktop 通知服务的 Inhibited 属性：gdbus 输出如 `(<true>,)`
+fn parse_dbus_inhibited(output: &str) -> FocusState {
+    if output.contains("true") {
+        FocusState::Active
+    } else if output.contains("false") {
+        FocusState::Inactive
+    } else {
+        FocusState::Unknown
+    }
+}
+
+/// GNOME：`gsettings get org.gnome.desktop.notifications show-banners`，false 表示勿扰
+fn parse_show_banners(output: &str) -> FocusState {
+    match output.trim() {
+        "false" => FocusState::Active,
+        "true" => FocusState::Inactive,
+        _ => FocusState::Unknown,
+    }
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn windows_toasts_setting() -> FocusState {
+    command_output(
+        "reg",
+        &["query", r"HKCU\Software\Microsoft\Windows\CurrentVersion\Notifications\Settings", "/v", "NOC_GLOBAL_SETTING_TOASTS_ENABLED"],
+    )
+    .map_or(FocusState::Unknown, |out| parse_toasts_enabled(&out))
+}
+
+fn macos_focus() -> FocusState {
+    // 读取需要“完全磁盘访问权限”，读不到时退回旧版设置
+    dirs::home_dir()
+        .and_then(|home| std::fs::read_to_string(home.join("Library/DoNotDisturb/DB/Assertions.json")).ok())
+        .map_or(FocusState::Unknown, |json| parse_macos_assertions(&json))
+}
+
+fn macos_dnd_defaults() -> FocusState {
+    command_output("defaults", &["-currentHost", "read", "com.apple.notificationcenterui", "doNotDisturb"])
+        .map_or(FocusState::Unknown, |out| parse_defaults_bool(&out))
+}
+
+fn dbus_inhibited() -> FocusState {
+    command_output(
+        "gdbus",
+        &[
+            "call", "--session",
+            "--dest", "org.freedesktop.Notifications",
+            "--object-path", "/org/freedesktop/Notifications",
+            "--method", "org.freedesktop.DBus.Properties.Get",
+            "org.freedesktop.Notifications", "Inhibited",
+        ],
+    )
+    .map_or(FocusState::Unknown, |out| parse_dbus_inhibited(&out))
+}
+
+fn gnome_show_banners() -> FocusState {
+    command_output("gsettings", &["get", "org.gnome.desktop.notifications", "show-banners"])
+        .map_or(FocusState::Unknown, |out| parse_show_banners(&out))
+}
+
+type Probe = (&'static str, fn() -> FocusState);
+
+const WINDOWS_PROBES: &[Probe] = &[("windows_toasts_setting", windows_toasts_setting)];
+const MACOS_PROBES: &[Probe] = &[("macos_focus", macos_focus), ("macos_dnd_defaults", macos_dnd_defaults)];
+const LINUX_PROBES: &[Probe] = &[("dbus_inhibited", dbus_inhibited), ("gnome_show_banners", gnome_show_banners)];
+
+/// 依次尝试本平台的检测方式，返回第一个有结果的
+fn detect() -> (FocusState, Option<&'static str>) {
+    let probes = if cfg!(target_os = "windows") {
+        WINDOWS_PROBES
+    } else if cfg!(target_os = "macos") {
+        MACOS_PROBES
+    } else {
+        LINUX_PROBES
+    };
+    probes
+        .iter()
+        .map(|(source, probe)| (probe(), *source))
+        .find(|(state, _)| *state != FocusState::Unknown)
+        .map_or((FocusState::Unknown, None), |(state, source)| (state, Some(source)))
+}
+
+impl AppState {
+    /// 重新检测系统勿扰状态（维护任务调用），变化时推送 os-focus-changed
+    pub fn refresh_os_focus(&self) -> Result<String, String> {
+        let (state, source) = detect();
+        let status = self.os_focus_status(state, source);
+        Ok(format!("{:?}", self.apply_os_focus(status).state).to_lowercase())
+    }
+
+    fn os_focus_status(&self, state: FocusState, source: Option<&str>) -> OsFocusStatus {
+        OsFocusStatus {
+            state,
+            source: source.map(str::to_string),
+            checked_at: chrono::Utc::now().timestamp(),
+            suppressing: state == FocusState::Active && self.settings.read().respect_os_focus,
+        }
+    }
+
+    fn apply_os_focus(&self, status: OsFocusStatus) -> OsFocusStatus {
+        if self.os_focus.update(status.clone(), Instant::now()) {
+            println!("[OsFocus] {:?} ({})", status.state, status.source.as_deref().unwrap_or("undetected"));
+            self.events.emit("os-focus-changed", status.clone());
+        }
+        status
+    }
+
+    /// 当前系统勿扰状态；缓存过期时重新检测
+    pub fn os_focus_state(&self) -> OsFocusStatus {
+        match self.os_focus.fresh(Instant::now()) {
+            // suppressing 随设置变化，每次按当前设置重算
+            Some(status) => OsFocusStatus {
+                suppressing: status.state == FocusState::Active && self.settings.read().respect_os_focus,
+                ..status
+            },
+            None => {
+                let (state, source) = detect();
+                self.apply_os_focus(self.os_focus_status(state, source))
+            }
+        }
+    }
+
+    /// 新通知是否应按免打扰处理（系统勿扰开启且设置为遵从）
+    pub(crate) fn os_focus_quiet(&self) -> bool {
+        self.settings.read().respect_os_focus && self.os_focus.is_active()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::importance::Importance;
+    use crate::types::Notification;
+
+    #[test]
+    fn test_parse_platform_outputs() {
+        let reg = "\r\nHKEY_CURRENT_USER\\...\\Settings\r\n    NOC_GLOBAL_SETTING_TOASTS_ENABLED    REG_DWORD    0x0\r\n";
+        assert_eq!(parse_toasts_enabled(reg), FocusState::Active);
+        assert_eq!(parse_toasts_enabled(&reg.replace("0x0", "0x1")), FocusState::Inactive);
+        assert_eq!(parse_toasts_enabled("ERROR: The system was unable to find the specified registry key"), FocusState::Unknown);
+
+        let assertions = r#"{"data":[{"storeAssertionRecords":[{"assertionDetails":{"assertionDetailsModeIdentifier":"com.apple.donotdisturb.mode.default"}}]}]}"#;
+        assert_eq!(parse_macos_assertions(assertions), FocusState::Active);
+        assert_eq!(parse_macos_assertions(r#"{"data":[{}]}"#), FocusState::Inactive);
+        assert_eq!(parse_macos_assertions("not json"), FocusState::Unknown);
+        assert_eq!(parse_defaults_bool("1\n"), FocusState::Active);
+        assert_eq!(parse_defaults_bool(""), FocusState::Unknown);
+
+        assert_eq!(parse_dbus_inhibited("(<true>,)\n"), FocusState::Active);
+        assert_eq!(parse_dbus_inhibited("(<false>,)\n"), FocusState::Inactive);
+        assert_eq!(parse_show_banners("false\n"), FocusState::Active);
+        assert_eq!(parse_show_banners("true\n"), FocusState::Inactive);
+    }
+
+    #[test]
+    fn test_active_focus_quiets_alerts_unless_overridden() {
+        let state = AppState::default();
+        let n = Notification { id: "n1".into(), importance: Importance::High, ..Default::default() };
+        let urgent = Notification { importance: Importance::Urgent, ..n.clone() };
+        assert!(state.alert_for_new(&n).sound);
+
+        // unknown 不改变行为
+        state.apply_os_focus(state.os_focus_status(FocusState::Unknown, None));
+        assert!(state.alert_for_new(&n).sound);
+
+        let status = state.apply_os_focus(state.os_focus_status(FocusState::Active, Some("dbus_inhibited")));
+        assert!(status.suppressing);
+        assert!(!state.alert_for_new(&n).toast);
+        let alert = state.alert_for_new(&urgent);
+        assert!(alert.toast && !alert.sound);
+        assert!(state.os_focus_state().suppressing);
+
+        state.settings.write().respect_os_focus = false;
+        assert!(state.alert_for_new(&n).sound);
+        assert!(!state.os_focus_state().suppressing);
+    }
+}
@@ -0,0 +1,287 @@
+//! 兼容各版本安卓端发送的配对数据：
+//! - 早期版本：`{"host","port","token"}`（port 可能是字符串）
+//! - 目前版本：`{"url","token"}`
+//! - 新版本：在此基础上附带 nonce、证书指纹、设备信息与中继信息
+//!
+//! 统一整理为 PairingData（v2）；只有缺少必需字段时才拒绝，并告诉手机端缺了哪些字段。
+//! 识别出的格式记录在配对历史中。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::error_bus::BackgroundError;
+use crate::onboarding::OnboardingStep;
+use crate::protocol;
+use crate::temp_server::{PairingData, TempServer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PairingSchema {
+    /// host + port + token
+    HostPort,
+    /// url + token
+    #[default]
+    Url,
+    /// url（或 host + port）+ token，附带 nonce / 证书指纹 / 设备信息等
+    V2,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceMeta {
+    pub name: Option<String>,
+    pub model: Option<String>,
+    #[serde(alias = "androidVersion")]
+    pub android_version: Option<String>,
+}
+
+/// 所有已知字段都可缺省的原始数据
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawPairing {
+    url: Option<String>,
+    host: Option<String>,
+    port: Option<serde_json::Value>,
+    token: Option<String>,
+    nonce: Option<String>,
+    #[serde(alias = "certFingerprint")]
+    cert_fingerprint: Option<String>,
+    device: Option<DeviceMeta>,
+    #[serde(alias = "relayUrl")]
+    relay_url: Option<String>,
+    #[serde(alias = "roomSecret")]
+    room_secret: Option<String>,
+}
+
+/// 无法使用的配对数据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairingRejection {
+    pub message: String,
+    pub missing: Vec<String>,
+}
+
+impl PairingRejection {
+    /// 回给手机端的响应体
+    pub fn response(&self) -> serde_json::Value {
+        serde_json::json!({ "success": false, "message": self.message, "missing": self.missing })
+    }
+}
+
+impl std::fmt::Display for PairingRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.missing.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} (missing: {})", self.message, self.missing.join(", "))
+        }
+    }
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn parse_port(v: Option<serde_json::Value>) -> Option<u16> {
+    match v? {
+        serde_json::Value::Number(n) => n.as_u64().and_then(|p| u16::try_from(p).ok()),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+    .filter(|p| *p != 0)
+}
+
+/// 解析任意已知版本的配对数据
+pub fn parse_pairing(raw: &str) -> Result<PairingData, PairingRejection> {
+    protocol::check_frame(raw).map_err(|e| PairingRejection { message: e.to_string(), missing: Vec::new() })?;
+    let raw: RawPairing = serde_json::from_str(raw.trim()).map_err(|e| PairingRejection {
+        message: format!("Invalid pairing data: {}", e),
+        missing: Vec::new(),
+    })?;
+
+    let has_url = raw.url.is_some();
+    let mut missing = Vec::new();
+    let token = non_empty(raw.token);
+    if token.is_none() {
+        missing.push("token".to_string());
+    }
+    let url = match (non_empty(raw.url), non_empty(raw.host), parse_port(raw.port)) {
+        (Some(url), _, _) => Some(url),
+        (None, Some(host), Some(port)) => Some(format!("{}:{}", host, port)),
+        (None, Some(_), None) => {
+            missing.push("port".to_string());
+            None
+        }
+        (None, None, _) => {
+            missing.push("url".to_string());
+            None
+        }
+    };
+    let (Some(url), Some(token)) = (url, token) else {
+        return Err(PairingRejection { message: "Unusable pairing data".to_string(), missing });
+    };
+
+    let nonce = non_empty(raw.nonce);
+    let cert_fingerprint = non_empty(raw.cert_fingerprint);
+    let relay_url = non_empty(raw.relay_url);
+    let schema = if nonce.is_some() || cert_fingerprint.is_some() || raw.device.is_some() || relay_url.is_some() {
+        PairingSchema::V2
+    } else if has_url {
+        PairingSchema::Url
+    } else {
+        PairingSchema::HostPort
+    };
+    Ok(PairingData {
+        url,
+        token,
+        relay_url,
+        room_secret: non_empty(raw.room_secret),
+        nonce,
+        cert_fingerprint,
+        device: raw.device,
+        schema,
+        source_ip: None,
+    })
+}
+
+/// 配对记录（不含 token）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PairingRecord {
+    pub at: i64,
+    pub url: String,
+    pub schema: PairingSchema,
+    pub device_name: Option<String>,
+    /// 配对请求的来源地址
+    #[serde(default)]
+    pub source_ip: Option<String>,
+}
+
+/// 配对历史最多保留的条数
+pub const MAX_HISTORY: usize = 50;
+const HISTORY_FILE: &str = "pairing_history.json";
+/// 每轮等待配对请求的时长（超时后继续等待下一轮）
+pub const PAIRING_WAIT_SECS: u64 = 180;
+
+impl AppState {
+    /// 记录一次成功配对（新 -> 旧）
+    /// 持续接收配对请求，直到服务器停止；成功的记入配对历史，失败的上报错误总线
+    pub fn serve_pairing(&self, server: &TempServer, wait_secs: u64) {
+        loop {
+            println!("[Pairing] 🔄 Waiting for next pairing request...");
+            match server.wait_for_pairing(wait_secs) {
+                Ok(data) => {
+                    println!("[Pairing] ✅ Pairing received: url={}, token_len={}", data.url, data.token.len());
+                    self.record_pairing(&data);
+                    self.advance_onboarding(OnboardingStep::FirstDevicePaired);
+                }
+                Err(e) if e.contains("Server stopped") => {
+                    println!("[Pairing] Server stopped, exiting listener loop");
+                    return;
+                }
+                Err(e) if e.starts_with("Accept error") => {
+                    self.report_error(BackgroundError::new("temp_server", "accept_failed", e).action("restart_temp_server"));
+                }
+                Err(e) if e.contains("Timeout") => {}
+                Err(e) => {
+                    println!("[Pairing] ❌ {}", e);
+                    // 失败的配对请求：检查手机是否与电脑在同一网络
+                    let hint = server.last_peer().and_then(|ip| self.network_hint_for(&ip));
+                    self.report_error(BackgroundError::new("temp_server", "pairing_failed", e));
+                    if let Some(hint) = hint {
+                        self.events.emit("pairing-network-hint", hint);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn record_pairing(&self, data: &PairingData) {
+        let record = PairingRecord {
+            at: chrono::Utc::now().timestamp(),
+            url: data.url.clone(),
+            schema: data.schema,
+            device_name: data.device.as_ref().and_then(|d| d.name.clone()),
+            source_ip: data.source_ip.clone(),
+        };
+        let history = {
+            let mut history = self.pairing_history.lock().unwrap();
+            history.insert(0, record);
+            history.truncate(MAX_HISTORY);
+            history.clone()
+        };
+        if let Err(e) = self.storage.save(HISTORY_FILE, &history) {
+            println!("[Pairing] {}", e);
+        }
+        self.events.emit("pairing-recorded", history[0].clone());
+    }
+
+    pub fn pairing_history(&self) -> Vec<PairingRecord> {
+        self.pairing_history.lock().unwrap().clone()
+    }
+
+    pub(crate) fn load_pairing_history(&self) {
+        *self.pairing_history.lock().unwrap() = self.storage.load::<Vec<PairingRecord>>(HISTORY_FILE).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 期望的 (schema, url) 或缺少的字段
+    type Expected = Result<(PairingSchema, &'static str), &'static [&'static str]>;
+
+    /// 各版本安卓端的配对数据
+    const FIXTURES: &[(&str, &str, Expected)] = &[
+        ("1.0.3", r#"{"host":"192.168.1.5","port":10035,"token":"abc"}"#, Ok((PairingSchema::HostPort, "192.168.1.5:10035"))),
+        ("1.0.7", r#"{"host":"192.168.1.5","port":"10035","token":"abc"}"#, Ok((PairingSchema::HostPort, "192.168.1.5:10035"))),
+        ("1.2.0", r#"{"url":"192.168.1.5:10035","token":"abc"}"#, Ok((PairingSchema::Url, "192.168.1.5:10035"))),
+        (
+            "2.0.0",
+            r#"{"url":"192.168.1.5:10035","token":"abc","nonce":"n1","certFingerprint":"AB:CD",
+                "device":{"name":"Pixel","model":"GP4BC","androidVersion":"14"},"relayUrl":"ws://relay:8080","roomSecret":"s"}"#,
+            Ok((PairingSchema::V2, "192.168.1.5:10035")),
+        ),
+        ("2.0.0-hostport", r#"{"host":"10.0.0.2","port":10035,"token":"abc","nonce":"n1"}"#, Ok((PairingSchema::V2, "10.0.0.2:10035"))),
+        ("broken-1", r#"{"url":"192.168.1.5:10035"}"#, Err(&["token"])),
+        ("broken-2", r#"{"host":"192.168.1.5","token":"abc"}"#, Err(&["port"])),
+        ("broken-3", r#"{"port":10035,"token":""}"#, Err(&["token", "url"])),
+    ];
+
+    #[test]
+    fn test_fixture_payloads_normalize() {
+        for (version, raw, expected) in FIXTURES {
+            match (parse_pairing(raw), expected) {
+                (Ok(data), Ok((schema, url))) => {
+                    assert_eq!(data.schema, *schema, "{}", version);
+                    assert_eq!(data.url, *url, "{}", version);
+                    assert_eq!(data.token, "abc", "{}", version);
+                }
+                (Err(rejection), Err(missing)) => assert_eq!(rejection.missing, *missing, "{}", version),
+                (got, _) => panic!("{}: unexpected {:?}", version, got),
+            }
+        }
+    }
+
+    #[test]
+    fn test_v2_metadata_kept_and_rejection_response() {
+        let data = parse_pairing(FIXTURES[3].1).unwrap();
+        assert_eq!(data.cert_fingerprint.as_deref(), Some("AB:CD"));
+        assert_eq!(data.device.unwrap().android_version.as_deref(), Some("14"));
+        assert_eq!(data.relay_url.as_deref(), Some("ws://relay:8080"));
+
+        let rejection = parse_pairing("not json").unwrap_err();
+        assert!(rejection.missing.is_empty());
+        let response = parse_pairing(FIXTURES[5].1).unwrap_err().response();
+        assert_eq!(response["missing"], serde_json::json!(["token"]));
+        assert_eq!(response["success"], false);
+
+        let state = AppState::default();
+        state.record_pairing(&parse_pairing(FIXTURES[0].1).unwrap());
+        state.record_pairing(&parse_pairing(FIXTURES[3].1).unwrap());
+        let history = state.pairing_history();
+        assert_eq!(history[0].schema, PairingSchema::V2);
+        assert_eq!(history[0].device_name.as_deref(), Some("Pixel"));
+        assert_eq!(history[1].schema, PairingSchema::HostPort);
+    }
+}
@@ -0,0 +1,505 @@
+//! 通知的持久化存储：此前手机通知只在内存中（重连后由手机端重新同步），长时间运行积累的已读/置顶/暂缓状态重启即丢失。
+//! 数据目录下的 notifications/ 保存通知表的完整快照：按批写入的分段文件，加上最后写入的 manifest.json（列出本代的分段与条数）。
+//! manifest 是提交点：分段都写完之后才写入，写了一半的新一代分段不会被读取，下次保存成功后清理。
+//! 已读、置顶、归档与暂缓状态都在通知本身的字段上，恢复时原样写回（见 NotificationStore::restore_all）。
+//! 查询仍然只走内存中的存储；切换到持久化后，维护任务在存储有变化（序号前进）时重写快照，退出时再写一次。
+//! 从纯内存版本升级时由 migrate_to_persistent_store 一次性迁移：分批写入并推送 migration-progress，
+//! 逐段读回核对条数与 id 之后才写 manifest 并切换后端；任何一步失败都不改动内存中的存储，返回带进度的 MigrationFailed。
+//! 仍是内存模式且已有通知时，维护任务推送一次 migration-suggested，由前端提示用户迁移。
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+use crate::storage::write_atomic;
+use crate::store::SortMode;
+use crate::types::Notification;
+
+pub const STORE_DIR: &str = "notifications";
+pub const MANIFEST_FILE: &str = "notifications/manifest.json";
+/// 每个分段（迁移时每批）的通知数
+pub const SEGMENT_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreBackend {
+    /// 只在内存中，重启后由手机端重新同步
+    #[default]
+    Memory,
+    /// 内存 + notifications/ 下的快照
+    Persistent,
+    /// 快照有分段缺失或读不出：只恢复读得到的部分，不再写快照（避免用残缺的数据覆盖），也不能迁移
+    Degraded,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    generation: u64,
+    count: usize,
+    segments: Vec<String>,
+    saved_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MigrationReport {
+    pub notifications: usize,
+    pub batches: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStage {
+    /// 开始之前（已迁移、没有数据目录、只读等）
+    Check,
+    /// 分批写入
+    Write,
+    /// 读回核对
+    Verify,
+    /// 写入 manifest
+    Commit,
+}
+
+/// 迁移失败（内存中的存储未改动）；以 `MigrationFailed: {json}` 的形式返回给前端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationFailed {
+    pub stage: MigrationStage,
+    /// 失败前已写入的条数
+    pub written: usize,
+    pub total: usize,
+    pub message: String,
+}
+
+impl MigrationFailed {
+    fn new(stage: MigrationStage, written: usize, total: usize, message: impl Into<String>) -> Self {
+        Self { stage, written, total, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for MigrationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MigrationFailed: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<MigrationFailed> for String {
+    fn from(e: MigrationFailed) -> Self {
+        println!("[PersistentStore] Migration failed at {:?} ({}/{}): {}", e.stage, e.written, e.total, e.message);
+        e.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PersistenceStatus {
+    pub backend: StoreBackend,
+    /// 最近一次写入的快照代数与条数
+    pub generation: u64,
+    pub saved_count: usize,
+    /// 自上次写入后存储是否有变化
+    pub dirty: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Saved {
+    backend: StoreBackend,
+    generation: u64,
+    count: usize,
+    // 快照对应的存储序号
+    seq: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct PersistentStore {
+    saved: Mutex<Saved>,
+    migrating: AtomicBool,
+    suggested: AtomicBool,
+}
+
+fn segment_name(generation: u64, index: usize) -> String {
+    format!("{}/segment-{}-{:05}.json", STORE_DIR, generation, index)
+}
+
+/// 删除不属于 keep 的分段文件（失败的迁移、被新一代取代的快照）
+fn remove_segments(base: &Path, keep: &[String]) {
+    let Ok(entries) = std::fs::read_dir(base.join(STORE_DIR)) else {
+        return;
+    };
+    let keep: HashSet<&str> = keep.iter().map(|s| s.rsplit('/').next().unwrap_or(s)).collect();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with("segment-") && !keep.contains(name.as_str()) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn read_segment(base: &Path, name: &str) -> Result<Vec<Notification>, String> {
+    let data = std::fs::read(base.join(name)).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    serde_json::from_slice(&data).map_err(|e| format!("Failed to parse {}: {}", name, e))
+}
+
+impl AppState {
+    /// 启动时：有 manifest 则恢复快照并切换为持久化，否则保持内存模式；
+    /// 分段缺失、读不出或条数不符时降级为 Degraded，快照文件原样保留到下次完整加载
+    pub(crate) fn load_persistent_store(&self) {
+        *self.persistent_store.saved.lock() = Saved::default();
+        let Some(manifest) = self.storage.load::<Manifest>(MANIFEST_FILE) else {
+            return;
+        };
+        let mut restored = 0;
+        let mut missing = 0;
+        {
+            let mut store = self.store.lock().unwrap();
+            for name in &manifest.segments {
+                match self.storage.load::<Vec<Notification>>(name) {
+                    Some(list) => {
+                        restored += list.len();
+                        store.restore_all(list);
+                    }
+                    None => {
+                        missing += 1;
+                        println!("[PersistentStore] Missing segment {}", name);
+                    }
+                }
+            }
+        }
+        let backend = if missing == 0 && restored == manifest.count {
+            StoreBackend::Persistent
+        } else {
+            println!(
+                "[PersistentStore] Snapshot incomplete ({} missing segments, restored {}/{}), not overwriting it",
+                missing, restored, manifest.count
+            );
+            StoreBackend::Degraded
+        };
+        let seq = self.store.lock().unwrap().seq();
+        *self.persistent_store.saved.lock() =
+            Saved { backend, generation: manifest.generation, count: restored, seq: Some(seq) };
+        println!("[PersistentStore] Restored {} notifications (generation {})", restored, manifest.generation);
+    }
+
+    pub fn persistence_status(&self) -> PersistenceStatus {
+        let saved = self.persistent_store.saved.lock().clone();
+        let seq = self.store.lock().unwrap().seq();
+        PersistenceStatus {
+            backend: saved.backend,
+            generation: saved.generation,
+            saved_count: saved.count,
+            dirty: saved.backend != StoreBackend::Memory && saved.seq != Some(seq),
+        }
+    }
+
+    /// 列表（旧 -> 新）与暂缓区的全部通知，以及对应的存储序号
+    fn notification_snapshot(&self) -> (Vec<Notification>, u64) {
+        let store = self.store.lock().unwrap();
+        let mut all = store.query(SortMode::Oldest, |_| true, 0, None);
+        all.extend(store.snoozed());
+        (all, store.seq())
+    }
+
+    /// 维护任务：持久化模式下存储有变化时重写快照；内存模式下提示一次迁移
+    pub fn persist_notification_store(&self) -> Result<String, String> {
+        let saved = self.persistent_store.saved.lock().clone();
+        match saved.backend {
+            StoreBackend::Memory => return Ok(self.suggest_migration()),
+            StoreBackend::Degraded => return Ok(String::new()),
+            StoreBackend::Persistent => {}
+        }
+        if self.persistent_store.migrating.load(Ordering::Relaxed) {
+            return Ok(String::new());
+        }
+        let (list, seq) = self.notification_snapshot();
+        if saved.seq == Some(seq) {
+            return Ok(String::new());
+        }
+        let generation = saved.generation + 1;
+        let mut segments = Vec::new();
+        for (i, batch) in list.chunks(SEGMENT_SIZE).enumerate() {
+            let name = segment_name(generation, i);
+            self.storage.save(&name, &batch)?;
+            segments.push(name);
+        }
+        let manifest = Manifest { generation, count: list.len(), segments, saved_at: self.wall_clock.now() };
+        self.storage.save(MANIFEST_FILE, &manifest)?;
+        *self.persistent_store.saved.lock() =
+            Saved { backend: StoreBackend::Persistent, generation, count: list.len(), seq: Some(seq) };
+        if let Some(base) = self.storage.base_dir() {
+            remove_segments(&base, &manifest.segments);
+        }
+        Ok(format!("{} notifications (generation {})", list.len(), generation))
+    }
+
+    fn suggest_migration(&self) -> String {
+        let total = self.store.lock().unwrap().counts().total;
+        if total == 0 || self.persistent_store.suggested.swap(true, Ordering::Relaxed) {
+            return String::new();
+        }
+        self.events.emit("migration-suggested", serde_json::json!({ "notifications": total }));
+        format!("migration suggested ({} notifications)", total)
+    }
+
+    /// 把内存中的通知一次性迁移到持久化存储
+    pub fn migrate_to_persistent_store(&self) -> Result<MigrationReport, MigrationFailed> {
+        let base = self.storage.base_dir();
+        self.migrate_with(SEGMENT_SIZE, |name, batch| {
+            let base = base.as_ref().ok_or("Data directory is not available")?;
+            let data = serde_json::to_vec(batch).map_err(|e| e.to_string())?;
+            write_atomic(&base.join(name), &data)
+        })
+    }
+
+    fn migrate_with(
+        &self,
+        segment_size: usize,
+        write: impl FnMut(&str, &[Notification]) -> Result<(), String>,
+    ) -> Result<MigrationReport, MigrationFailed> {
+        use MigrationStage::Check;
+        match self.persistent_store.saved.lock().backend {
+            StoreBackend::Persistent => return Err(MigrationFailed::new(Check, 0, 0, "Already using the persistent store")),
+            StoreBackend::Degraded => return Err(MigrationFailed::new(Check, 0, 0, "The persisted snapshot is incomplete")),
+            StoreBackend::Memory => {}
+        }
+        let Some(base) = self.storage.base_dir() else {
+            return Err(MigrationFailed::new(Check, 0, 0, "Data directory is not available"));
+        };
+        if let Some(reason) = self.storage.read_only_reason() {
+            return Err(MigrationFailed::new(Check, 0, 0, format!("StorageReadOnly: {}", reason)));
+        }
+        if self.persistent_store.migrating.swap(true, Ordering::Relaxed) {
+            return Err(MigrationFailed::new(Check, 0, 0, "A migration is already running"));
+        }
+        let result = self.run_migration(&base, segment_size, write);
+        self.persistent_store.migrating.store(false, Ordering::Relaxed);
+        if let Ok(report) = &result {
+            self.audit("migrate_to_persistent_store", AuditSource::Command, report.notifications, serde_json::json!({ "batches": report.batches }));
+            println!("[PersistentStore] Migrated {} notifications in {} batches", report.notifications, report.batches);
+        }
+        result
+    }
+
+    fn run_migration(
+        &self,
+        base: &Path,
+        segment_size: usize,
+        mut write: impl FnMut(&str, &[Notification]) -> Result<(), String>,
+    ) -> Result<MigrationReport, MigrationFailed> {
+        let (list, seq) = self.notification_snapshot();
+        let total = list.len();
+        let batches = total.div_ceil(segment_size);
+        let generation = self.persistent_store.saved.lock().generation + 1;
+        let mut segments = Vec::with_capacity(batches);
+        let mut written = 0;
+        // 失败时删除本次写入的分段，保持数据目录原样
+        let fail = |segments: &[String], stage, written, message: String| {
+            for name in segments {
+                let _ = std::fs::remove_file(base.join(name));
+            }
+            MigrationFailed::new(stage, written, total, message)
+        };
+
+        for (i, batch) in list.chunks(segment_size).enumerate() {
+            let name = segment_name(generation, i);
+            if let Err(e) = write(&name, batch) {
+                segments.push(name);
+                return Err(fail(&segments, MigrationStage::Write, written, e));
+            }
+            segments.push(name);
+            written += batch.len();
+            self.events.emit(
+                "migration-progress",
+                serde_json::json!({ "written": written, "total": total, "batch": i + 1, "batches": batches }),
+            );
+        }
+
+        let mut read_back = HashSet::with_capacity(total);
+        let mut rows = 0;
+        for name in &segments {
+            match read_segment(base, name) {
+                Ok(batch) => {
+                    rows += batch.len();
+                    read_back.extend(batch.into_iter().map(|n| n.id));
+                }
+                Err(e) => return Err(fail(&segments, MigrationStage::Verify, written, e)),
+            }
+        }
+        if rows != total || read_back.len() != total || !list.iter().all(|n| read_back.contains(&n.id)) {
+            let message = format!("Read back {} rows ({} distinct ids), expected {}", rows, read_back.len(), total);
+            return Err(fail(&segments, MigrationStage::Verify, written, message));
+        }
+
+        let manifest = Manifest { generation, count: total, segments, saved_at: self.wall_clock.now() };
+        let data = serde_json::to_vec_pretty(&manifest).map_err(|e| fail(&manifest.segments, MigrationStage::Commit, written, e.to_string()))?;
+        if let Err(e) = write_atomic(&base.join(MANIFEST_FILE), &data) {
+            return Err(fail(&manifest.segments, MigrationStage::Commit, written, e));
+        }
+        // 快照之后的修改由下一次维护任务写入（序号已前进）
+        *self.persistent_store.saved.lock() =
+            Saved { backend: StoreBackend::Persistent, generation, count: total, seq: Some(seq) };
+        remove_segments(base, &manifest.segments);
+        Ok(MigrationReport { notifications: total, batches })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ListOptions;
+    use crate::startup::Subsystem;
+
+    const WORDS: &[&str] = &["会议", "快递", "invoice", "验证码", "dinner", "航班"];
+
+    fn seeded(count: usize) -> AppState {
+        let state = AppState::default();
+        state.mark_ready(Subsystem::Store);
+        let mut store = state.store.lock().unwrap();
+        for i in 0..count {
+            store.upsert(
+                Notification {
+                    id: format!("n{}", i),
+                    package_name: if i % 17 == 0 { None } else { Some(format!("com.app{}", i % 11)) },
+                    title: Some(format!("{} #{}", WORDS[i % WORDS.len()], i)),
+                    text: Some(format!("body {} {}", i, WORDS[(i / 7) % WORDS.len()])),
+                    posted_at: Some(1_700_000_000 + (i as i64 % 5_000)),
+                    ..Default::default()
+                },
+                i % 3 == 0,
+            );
+        }
+        let ids = |step: usize, offset: usize| (offset..count).step_by(step).map(|i| format!("n{}", i)).collect::<Vec<_>>();
+        store.set_pinned(&ids(101, 5), true);
+        store.set_archived(&ids(97, 2), true);
+        for id in ids(499, 1) {
+            let n = store.get(&id).cloned().unwrap();
+            store.snooze(n, 1_800_000_000);
+        }
+        drop(store);
+        state
+    }
+
+    fn observe(state: &AppState) -> serde_json::Value {
+        let list = state.list_notifications(ListOptions { include_archived: true, ..Default::default() }).unwrap();
+        let search = state.search_notifications("快递", Some(200)).unwrap();
+        let store = state.store.lock().unwrap();
+        serde_json::json!({
+            "list": list,
+            "counts": store.counts(),
+            "by_package": store.counts_by_package(),
+            "snoozed": store.snoozed(),
+            "search": search,
+        })
+    }
+
+    #[test]
+    fn test_migrate_20k_then_restart_is_equivalent() {
+        let dir = crate::storage::temp_dir("persistent-store");
+        let state = seeded(20_000);
+        state.storage.set_base_dir(dir.clone());
+        let before = observe(&state);
+
+        assert!(state.persist_notification_store().unwrap().starts_with("migration suggested"));
+        assert_eq!(state.persist_notification_store().unwrap(), "");
+        assert!(state.events.take_captured().iter().any(|(name, _)| name == "migration-suggested"));
+        let report = state.migrate_to_persistent_store().unwrap();
+        assert_eq!(report, MigrationReport { notifications: 20_000, batches: 20 });
+        let progress: Vec<serde_json::Value> =
+            state.events.take_captured().into_iter().filter(|(name, _)| name == "migration-progress").map(|(_, p)| p).collect();
+        assert_eq!(progress.len(), 20);
+        assert_eq!(progress[19], serde_json::json!({ "written": 20_000, "total": 20_000, "batch": 20, "batches": 20 }));
+        assert_eq!(state.persistence_status().backend, StoreBackend::Persistent);
+        assert_eq!(observe(&state), before);
+        assert!(state.migrate_to_persistent_store().is_err());
+
+        // 重启后从快照恢复
+        let restarted = AppState::default();
+        restarted.mark_ready(Subsystem::Store);
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_persistent_store();
+        assert_eq!(restarted.persistence_status().backend, StoreBackend::Persistent);
+        assert_eq!(observe(&restarted), before);
+        assert!(restarted.store.lock().unwrap().integrity_issues().is_empty());
+
+        // 之后的修改由维护任务写成新一代快照，旧分段被清理
+        restarted.mark_read_ids(&["n4".to_string()]).unwrap();
+        assert!(restarted.persistence_status().dirty);
+        assert_eq!(restarted.persist_notification_store().unwrap(), "20000 notifications (generation 2)");
+        assert_eq!(restarted.persist_notification_store().unwrap(), "");
+        let segments = std::fs::read_dir(dir.join(STORE_DIR)).unwrap().flatten().filter(|e| e.file_name().to_string_lossy().starts_with("segment-1-")).count();
+        assert_eq!(segments, 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_failed_migration_leaves_memory_store() {
+        let dir = crate::storage::temp_dir("persistent-store-fail");
+        let state = seeded(2_500);
+        state.storage.set_base_dir(dir.clone());
+        let before = observe(&state);
+
+        let mut calls = 0;
+        let err = state
+            .migrate_with(1_000, |name, batch| {
+                calls += 1;
+                if calls == 3 {
+                    return Err("disk full".into());
+                }
+                write_atomic(&dir.join(name), &serde_json::to_vec(batch).unwrap())
+            })
+            .unwrap_err();
+        assert_eq!((err.stage, err.written, err.total), (MigrationStage::Write, 2_000, 2_500));
+        assert!(String::from(err).starts_with("MigrationFailed: {"));
+        assert_eq!(state.persistence_status().backend, StoreBackend::Memory);
+        assert_eq!(observe(&state), before);
+        assert!(!dir.join(MANIFEST_FILE).exists());
+        assert_eq!(std::fs::read_dir(dir.join(STORE_DIR)).unwrap().count(), 0);
+
+        // 读回的条数不符：核对失败
+        let err = state.migrate_with(1_000, |name, batch| write_atomic(&dir.join(name), &serde_json::to_vec(&batch[1..]).unwrap())).unwrap_err();
+        assert_eq!((err.stage, err.written), (MigrationStage::Verify, 2_500));
+        assert_eq!(state.persistence_status().backend, StoreBackend::Memory);
+
+        // 没有 manifest：重启后仍是内存模式
+        let restarted = AppState::default();
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_persistent_store();
+        assert_eq!((restarted.persistence_status().backend, restarted.store.lock().unwrap().counts().total), (StoreBackend::Memory, 0));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_missing_segment_is_read_only() {
+        let dir = crate::storage::temp_dir("persistent-store-missing");
+        let state = seeded(2_500);
+        state.storage.set_base_dir(dir.clone());
+        state.migrate_to_persistent_store().unwrap();
+        let manifest = std::fs::read(dir.join(MANIFEST_FILE)).unwrap();
+        let segment = std::fs::read(dir.join(segment_name(1, 1))).unwrap();
+        std::fs::remove_file(dir.join(segment_name(1, 1))).unwrap();
+
+        // 缺一个分段：恢复其余部分，但不改写快照、不允许迁移
+        let restarted = AppState::default();
+        restarted.mark_ready(Subsystem::Store);
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_persistent_store();
+        assert_eq!(restarted.persistence_status().backend, StoreBackend::Degraded);
+        assert_eq!(restarted.notification_snapshot().0.len(), 1_500);
+        restarted.store.lock().unwrap().upsert(Notification { id: "new".into(), ..Default::default() }, false);
+        assert!(restarted.persistence_status().dirty);
+        assert_eq!(restarted.persist_notification_store().unwrap(), "");
+        assert_eq!(restarted.migrate_to_persistent_store().unwrap_err().stage, MigrationStage::Check);
+        assert_eq!(std::fs::read(dir.join(MANIFEST_FILE)).unwrap(), manifest);
+        assert!(dir.join(segment_name(1, 0)).exists());
+
+        // 分段找回后重启即回到持久化模式
+        std::fs::write(dir.join(segment_name(1, 1)), segment).unwrap();
+        let restarted = AppState::default();
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_persistent_store();
+        assert_eq!(restarted.persistence_status().backend, StoreBackend::Persistent);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,321 @@
+//! 手机端划掉通知时桌面端的处理：手机上“全部清除”会连续发来大量 removed，若照单全删，桌面端的历史也跟着没了。
+//! 同一连接在 window_ms 内的移除超过 threshold 条（或收到手机端明确的 clear_all）视为批量清除，
+//! 按 phone_dismiss_behavior 处理（默认只在本地标为已读）；突发之外的单条划掉按 single_behavior（默认删除）。
+//! 判定为突发之前已按单条删除的那几条会按批量方式恢复，结果与整批一致。
+//! 标为已读/保留的也写入删除记录（原因注明处理方式），突发开始时另写一条审计日志。
+
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+use crate::ingest::IngestOutcome;
+use crate::tombstones::RemovalReason;
+use crate::types::Notification;
+use crate::wall_clock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneDismissBehavior {
+    Remove,
+    #[default]
+    MarkRead,
+    KeepUnread,
+}
+
+impl PhoneDismissBehavior {
+    fn reason(self) -> RemovalReason {
+        match self {
+            Self::Remove => RemovalReason::DismissedOnPhone,
+            Self::MarkRead => RemovalReason::DismissedOnPhoneMarkedRead,
+            Self::KeepUnread => RemovalReason::DismissedOnPhoneKept,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DismissBurstSettings {
+    /// 突发之外的单条划掉；None 表示与 phone_dismiss_behavior 相同
+    pub single_behavior: Option<PhoneDismissBehavior>,
+    /// 窗口内移除超过该条数视为批量清除
+    pub threshold: usize,
+    pub window_ms: u64,
+}
+
+impl Default for DismissBurstSettings {
+    fn default() -> Self {
+        Self { single_behavior: Some(PhoneDismissBehavior::Remove), threshold: 5, window_ms: 3000 }
+    }
+}
+
+impl DismissBurstSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.threshold == 0 {
+            return Err("phone_dismiss_burst.threshold must be positive".to_string());
+        }
+        if !(100..=60_000).contains(&self.window_ms) {
+            return Err("phone_dismiss_burst.window_ms must be within 100..=60000".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 一次移除的判定结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dismissal {
+    Single,
+    /// onset 为刚进入突发时，此前按单条删除、需要按批量方式补处理的通知
+    Burst { onset: Vec<Notification>, started: bool },
+}
+
+#[derive(Debug, Default)]
+struct DeviceRemovals {
+    // (时间, 按单条删除时的通知)
+    recent: VecDeque<(u64, Option<Notification>)>,
+    // 突发持续到该时间（每次移除顺延一个窗口）
+    burst_until: Option<u64>,
+}
+
+impl DeviceRemovals {
+    fn observe(&mut self, now: u64, settings: &DismissBurstSettings) -> Dismissal {
+        while self.recent.front().is_some_and(|(t, _)| now.saturating_sub(*t) >= settings.window_ms) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((now, None));
+        // 只需判断是否超过阈值；突发中的移除不必全部保留
+        while self.recent.len() > settings.threshold + 1 {
+            self.recent.pop_front();
+        }
+        if self.burst_until.is_some_and(|until| now < until) {
+            self.burst_until = Some(now + settings.window_ms);
+            return Dismissal::Burst { onset: Vec::new(), started: false };
+        }
+        self.burst_until = None;
+        if self.recent.len() <= settings.threshold {
+            return Dismissal::Single;
+        }
+        self.burst_until = Some(now + settings.window_ms);
+        let onset = self.recent.iter_mut().filter_map(|(_, n)| n.take()).collect();
+        Dismissal::Burst { onset, started: true }
+    }
+
+    fn clear_all(&mut self, now: u64, window_ms: u64) -> bool {
+        let started = !self.burst_until.is_some_and(|until| now < until);
+        self.burst_until = Some(now + window_ms);
+        started
+    }
+}
+
+/// connection_id -> 最近的移除
+#[derive(Default)]
+pub struct DismissTracker {
+    devices: Mutex<HashMap<String, DeviceRemovals>>,
+}
+
+impl DismissTracker {
+    pub fn observe(&self, connection_id: &str, now: u64, settings: &DismissBurstSettings) -> Dismissal {
+        self.devices.lock().entry(connection_id.to_string()).or_default().observe(now, settings)
+    }
+
+    /// 手机端明确发来 clear_all：之后一个窗口内的移除都按批量处理；新开始时返回 true
+    pub fn clear_all(&self, connection_id: &str, now: u64, window_ms: u64) -> bool {
+        self.devices.lock().entry(connection_id.to_string()).or_default().clear_all(now, window_ms)
+    }
+
+    /// 各连接中最长的移除记录
+    pub fn max_recent(&self) -> usize {
+        self.devices.lock().values().map(|d| d.recent.len()).max().unwrap_or(0)
+    }
+
+    /// 记下最近一次按单条删除的通知（突发开始时据此补处理）
+    fn remember(&self, connection_id: &str, n: Notification) {
+        if let Some(last) = self.devices.lock().get_mut(connection_id).and_then(|d| d.recent.back_mut()) {
+            last.1 = Some(n);
+        }
+    }
+}
+
+impl AppState {
+    /// 手机端发来的 removed：判定是否属于批量清除并按设置处理
+    pub(crate) fn phone_removed(&self, connection_id: &str, id: &str) -> IngestOutcome {
+        let (burst_behavior, burst) = {
+            let settings = self.settings.read();
+            (settings.phone_dismiss_behavior, settings.phone_dismiss_burst.clone())
+        };
+        let single_behavior = burst.single_behavior.unwrap_or(burst_behavior);
+        let behavior = match self.dismissals.observe(connection_id, wall_clock::mono_ms(), &burst) {
+            Dismissal::Single => single_behavior,
+            Dismissal::Burst { onset, started } => {
+                if started {
+                    self.burst_started(connection_id, burst_behavior, "burst", onset.len() + 1);
+                }
+                self.reapply_dismissed(onset, burst_behavior);
+                burst_behavior
+            }
+        };
+        match behavior {
+            PhoneDismissBehavior::Remove => {
+                let removed = self.store.lock().unwrap().remove(id);
+                let Some(n) = removed else {
+                    return IngestOutcome::Ignored;
+                };
+                self.dismissals.remember(connection_id, n.clone());
+                self.on_removed(&[n], RemovalReason::DismissedOnPhone);
+                IngestOutcome::Removed
+            }
+            PhoneDismissBehavior::MarkRead | PhoneDismissBehavior::KeepUnread => {
+                let n = {
+                    let mut store = self.store.lock().unwrap();
+                    if behavior == PhoneDismissBehavior::MarkRead {
+                        store.mark_read(&[id.to_string()]);
+                    }
+                    store.get(id).cloned()
+                };
+                let Some(n) = n else {
+                    return IngestOutcome::Ignored;
+                };
+                self.record_removals(std::slice::from_ref(&n), behavior.reason());
+                if behavior == PhoneDismissBehavior::MarkRead {
+                    self.events.emit("notification-updated", &n);
+                    self.emit_counts();
+                    IngestOutcome::MarkedRead
+                } else {
+                    IngestOutcome::Kept
+                }
+            }
+        }
+    }
+
+    /// 手机端明确的全部清除（之后的 removed 按批量处理）
+    pub(crate) fn phone_clear_all(&self, connection_id: &str) -> IngestOutcome {
+        let (behavior, window_ms) = {
+            let settings = self.settings.read();
+            (settings.phone_dismiss_behavior, settings.phone_dismiss_burst.window_ms)
+        };
+        if self.dismissals.clear_all(connection_id, wall_clock::mono_ms(), window_ms) {
+            self.burst_started(connection_id, behavior, "clear_all", 0);
+        }
+        IngestOutcome::Ignored
+    }
+
+    fn burst_started(&self, connection_id: &str, behavior: PhoneDismissBehavior, trigger: &str, so_far: usize) {
+        println!("[Dismiss] {} cleared notifications on the phone ({}), applying {:?}", connection_id, trigger, behavior);
+        self.audit(
+            "phone_clear_all",
+            AuditSource::Background,
+            so_far,
+            serde_json::json!({ "connection_id": connection_id, "trigger": trigger, "behavior": behavior }),
+        );
+    }
+
+    /// 突发开始前已按单条删除的通知：批量方式不是删除时恢复到列表
+    fn reapply_dismissed(&self, onset: Vec<Notification>, behavior: PhoneDismissBehavior) {
+        if onset.is_empty() || behavior == PhoneDismissBehavior::Remove {
+            return;
+        }
+        let restored: Vec<Notification> = {
+            let mut store = self.store.lock().unwrap();
+            onset
+                .into_iter()
+                .filter_map(|n| {
+                    let (id, pinned) = (n.id.clone(), n.pinned);
+                    let read = n.read || behavior == PhoneDismissBehavior::MarkRead;
+                    store.upsert(n, read);
+                    if pinned {
+                        store.set_pinned(std::slice::from_ref(&id), true);
+                    }
+                    store.get(&id).cloned()
+                })
+                .collect()
+        };
+        for n in &restored {
+            self.events.emit("notification-added", n);
+        }
+        // 覆盖之前的 dismissed_on_phone 记录
+        self.record_removals(&restored, behavior.reason());
+        self.emit_counts();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    fn settings(threshold: usize) -> DismissBurstSettings {
+        DismissBurstSettings { threshold, window_ms: 1000, ..Default::default() }
+    }
+
+    fn notif(id: &str) -> Notification {
+        Notification { id: id.into(), package_name: Some("com.chat".into()), title: Some(id.into()), ..Default::default() }
+    }
+
+    #[test]
+    fn test_burst_threshold_boundaries() {
+        let s = settings(3);
+        // 正好 threshold 条不算突发
+        let mut d = DeviceRemovals::default();
+        for t in [0, 100, 200] {
+            assert_eq!(d.observe(t, &s), Dismissal::Single);
+        }
+        assert!(matches!(d.observe(300, &s), Dismissal::Burst { started: true, .. }));
+        assert!(matches!(d.observe(1200, &s), Dismissal::Burst { started: false, .. }), "continues within a window of the last");
+        assert_eq!(d.observe(2300, &s), Dismissal::Single, "ends after a quiet window");
+
+        // 第 4 条与第 1 条恰好相隔一个窗口
+        let mut d = DeviceRemovals::default();
+        for t in [0, 400, 800, 1000] {
+            assert_eq!(d.observe(t, &s), Dismissal::Single);
+        }
+        assert!(matches!(d.observe(1001, &s), Dismissal::Burst { started: true, .. }));
+
+        // clear_all 直接进入突发
+        let mut d = DeviceRemovals::default();
+        assert!(d.clear_all(0, 1000));
+        assert!(!d.clear_all(10, 1000));
+        assert!(matches!(d.observe(500, &s), Dismissal::Burst { started: false, .. }));
+    }
+
+    #[test]
+    fn test_phone_clear_all_marks_read_and_is_logged() {
+        let state = AppState::default();
+        {
+            let mut settings = state.settings.write();
+            settings.phone_dismiss_burst.threshold = 2;
+            settings.phone_dismiss_burst.window_ms = 60_000;
+        }
+        for i in 0..5 {
+            state.ingest_event(Event { event_type: "added".into(), seq: 0, notification: Some(notif(&i.to_string())), id: None });
+        }
+        let removed = |id: &str| Event { event_type: "removed".into(), seq: 0, notification: None, id: Some(id.into()) };
+
+        // 前两条按单条删除，第三条触发突发：前两条恢复为已读
+        assert_eq!(state.ingest_from("pixel", removed("0")), IngestOutcome::Removed);
+        assert_eq!(state.ingest_from("pixel", removed("1")), IngestOutcome::Removed);
+        assert_eq!(state.ingest_from("pixel", removed("2")), IngestOutcome::MarkedRead);
+        assert_eq!(state.ingest_from("pixel", removed("3")), IngestOutcome::MarkedRead);
+        let counts = state.counts();
+        assert_eq!((counts.total, counts.unread), (5, 1));
+
+        let log = state.removal_log.lock().unwrap().recent(10);
+        assert_eq!(log.len(), 4);
+        assert!(log.iter().all(|t| t.reason == RemovalReason::DismissedOnPhoneMarkedRead));
+        let audit = state.audit.recent(1);
+        assert_eq!(audit[0].operation, "phone_clear_all");
+        assert_eq!(audit[0].params["behavior"], "mark_read");
+        assert_eq!(audit[0].affected, 3);
+
+        // 另一台设备的单条划掉照常删除；选择保留时不改已读状态
+        assert_eq!(state.ingest_from("tablet", removed("4")), IngestOutcome::Removed);
+        state.settings.write().phone_dismiss_behavior = PhoneDismissBehavior::KeepUnread;
+        state.ingest_event(Event { event_type: "added".into(), seq: 0, notification: Some(notif("5")), id: None });
+        assert_eq!(state.ingest_from("tablet", Event { event_type: "clear_all".into(), ..removed("") }), IngestOutcome::Ignored);
+        assert_eq!(state.ingest_from("tablet", removed("5")), IngestOutcome::Kept);
+        assert_eq!(state.counts().unread, 1);
+        assert_eq!(state.audit.recent(1)[0].params["trigger"], "clear_all");
+    }
+}
@@ -0,0 +1,96 @@
+//! 前端的存活与版本探测：错误边界与 WebView 崩溃后的重连逻辑用 ping 确认后端还在、是哪个版本。
+//! 版本来自 Cargo 包版本，git 提交与构建配置由 build.rs 在编译时写入（取不到时为 unknown）。
+//! backend_seq / stream_id 取自通知存储的变更序号（跨重启单调，见 stream_meta）：seq 变小或 stream_id 变化说明数据被重置；
+//! boot_id 每个进程不同，变化说明后端在前端不知情时重启过。两种情况前端都应全量重新拉取。
+//! uptime_ms 用单调时钟，不受系统时间调整影响。
+//! greet 是早期脚手架命令，保留一个版本作为 ping 的过时别名，旧前端包调用时仍返回原来的问候语。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = match option_env!("GIT_HASH") {
+    Some(hash) => hash,
+    None => "unknown",
+};
+pub const BUILD_PROFILE: &str = match option_env!("BUILD_PROFILE") {
+    Some(profile) => profile,
+    None => "unknown",
+};
+
+static GREET_WARNED: AtomicBool = AtomicBool::new(false);
+static BOOT_ID: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Ping {
+    pub version: String,
+    pub git_hash: String,
+    /// debug / release
+    pub build_profile: String,
+    /// 进程启动以来的毫秒数（单调）
+    pub uptime_ms: u64,
+    /// 通知存储的变更序号
+    pub backend_seq: u64,
+    pub stream_id: String,
+    /// 本次进程的随机 id
+    pub boot_id: String,
+}
+
+/// 过时的 greet：行为与原来相同，首次调用时提示改用 ping
+pub fn greet(name: &str) -> String {
+    if !GREET_WARNED.swap(true, Ordering::Relaxed) {
+        println!("[Ping] greet is deprecated and will be removed in the next release, use ping");
+    }
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+impl AppState {
+    pub fn ping(&self) -> Ping {
+        let (backend_seq, stream_id) = {
+            let store = self.store.lock().unwrap();
+            (store.seq(), store.stream_id().to_string())
+        };
+        Ping {
+            version: VERSION.to_string(),
+            git_hash: GIT_HASH.to_string(),
+            build_profile: BUILD_PROFILE.to_string(),
+            uptime_ms: crate::wall_clock::mono_ms(),
+            backend_seq,
+            stream_id,
+            boot_id: BOOT_ID.get_or_init(|| uuid::Uuid::new_v4().to_string()).clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Notification;
+
+    #[test]
+    fn test_greet_alias_keeps_legacy_reply() {
+        assert_eq!(greet("Tauri"), "Hello, Tauri! You've been greeted from Rust!");
+        assert_eq!(greet("Tauri"), greet("Tauri"));
+        assert!(GREET_WARNED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_ping_uptime_and_seq() {
+        let state = AppState::default();
+        let first = state.ping();
+        assert_eq!(first.version, env!("CARGO_PKG_VERSION"));
+        assert!(!first.git_hash.is_empty() && !first.build_profile.is_empty());
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        state.store.lock().unwrap().upsert(Notification { id: "a".into(), ..Default::default() }, false);
+        let second = state.ping();
+        assert!(second.uptime_ms >= first.uptime_ms + 5, "{} -> {}", first.uptime_ms, second.uptime_ms);
+        assert!(second.backend_seq > first.backend_seq);
+        assert_eq!((&second.stream_id, &second.boot_id), (&first.stream_id, &first.boot_id));
+    }
+}
@@ -0,0 +1,155 @@
+//! 通知详情弹出窗口登记表：notification id -> 窗口 label。
+//! 只负责记账与数量上限，真正的窗口创建/关闭在 commands 中完成。
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PopoutWindow {
+    pub notification_id: String,
+    pub label: String,
+    pub opened_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct PopoutSettings {
+    pub width: f64,
+    pub height: f64,
+    pub always_on_top: bool,
+    /// 同时打开的弹出窗口上限
+    pub max_windows: usize,
+}
+
+impl Default for PopoutSettings {
+    fn default() -> Self {
+        Self {
+            width: 360.0,
+            height: 240.0,
+            always_on_top: false,
+            max_windows: 8,
+        }
+    }
+}
+
+/// 预留结果：已存在（需聚焦）或新登记（需创建）
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reservation {
+    Existing(String),
+    New(String),
+}
+
+#[derive(Default)]
+pub struct PopoutRegistry {
+    windows: Mutex<Vec<PopoutWindow>>,
+}
+
+/// 窗口 label 只允许字母、数字与 `-_`，其余字符替换掉
+pub fn label_for(notification_id: &str) -> String {
+    let safe: String = notification_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("notif-{}", safe)
+}
+
+/// 通知 id 作为查询参数时的百分号编码
+pub fn encode_id(id: &str) -> String {
+    let mut out = String::with_capacity(id.len());
+    for b in id.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+impl PopoutRegistry {
+    /// 为通知预留一个窗口；超过上限时返回错误
+    pub fn reserve(&self, notification_id: &str, max: usize) -> Result<Reservation, String> {
+        let mut windows = self.windows.lock();
+        if let Some(w) = windows.iter().find(|w| w.notification_id == notification_id) {
+            return Ok(Reservation::Existing(w.label.clone()));
+        }
+        if windows.len() >= max {
+            return Err(format!("Too many pop-out windows open (max {})", max));
+        }
+        // label 清洗后可能冲突，冲突时追加序号
+        let base = label_for(notification_id);
+        let mut label = base.clone();
+        let mut n = 1;
+        while windows.iter().any(|w| w.label == label) {
+            n += 1;
+            label = format!("{}-{}", base, n);
+        }
+        windows.push(PopoutWindow {
+            notification_id: notification_id.to_string(),
+            label: label.clone(),
+            opened_at: chrono::Utc::now().timestamp(),
+        });
+        Ok(Reservation::New(label))
+    }
+
+    /// 窗口关闭（或创建失败）后移除登记
+    pub fn remove_label(&self, label: &str) {
+        self.windows.lock().retain(|w| w.label != label);
+    }
+
+    /// 移除指定通知的窗口，返回需要关闭的 label
+    pub fn take_for(&self, ids: &[String]) -> Vec<String> {
+        let mut windows = self.windows.lock();
+        let (closed, kept): (Vec<_>, Vec<_>) = windows.drain(..).partition(|w| ids.contains(&w.notification_id));
+        *windows = kept;
+        closed.into_iter().map(|w| w.label).collect()
+    }
+
+    pub fn take_all(&self) -> Vec<String> {
+        self.windows.lock().drain(..).map(|w| w.label).collect()
+    }
+
+    pub fn list(&self) -> Vec<PopoutWindow> {
+        self.windows.lock().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_focuses_existing_and_caps() {
+        let reg = PopoutRegistry::default();
+        assert_eq!(reg.reserve("a", 2).unwrap(), Reservation::New("notif-a".into()));
+        assert_eq!(reg.reserve("a", 2).unwrap(), Reservation::Existing("notif-a".into()));
+        assert!(reg.reserve("b", 2).is_ok());
+        assert!(reg.reserve("c", 2).is_err());
+        reg.remove_label("notif-a");
+        assert!(reg.reserve("c", 2).is_ok());
+    }
+
+    #[test]
+    fn test_label_sanitized_and_unique() {
+        let reg = PopoutRegistry::default();
+        assert_eq!(reg.reserve("0|com.x|12", 5).unwrap(), Reservation::New("notif-0_com_x_12".into()));
+        assert_eq!(reg.reserve("0:com.x:12", 5).unwrap(), Reservation::New("notif-0_com_x_12-2".into()));
+    }
+
+    #[test]
+    fn test_encode_id() {
+        assert_eq!(encode_id("0|com.x|12"), "0%7Ccom.x%7C12");
+        assert_eq!(encode_id("中"), "%E4%B8%AD");
+    }
+
+    #[test]
+    fn test_take_for_removed_ids() {
+        let reg = PopoutRegistry::default();
+        reg.reserve("a", 5).unwrap();
+        reg.reserve("b", 5).unwrap();
+        assert_eq!(reg.take_for(&["b".to_string()]), vec!["notif-b".to_string()]);
+        assert_eq!(reg.list().len(), 1);
+        assert_eq!(reg.take_all(), vec!["notif-a".to_string()]);
+    }
+}
@@ -0,0 +1,147 @@
+//! 本应用监听端口登记：每个服务启动前按角色登记端口，已被本应用其他服务占用时拒绝启动并指明占用方，
+//! 避免手机连到错误的服务。登记由 PortGuard 持有，服务停止或启动失败时随 guard 释放。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::network_utils::{BindError, BindErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerRole {
+    /// 扫码配对用的临时 HTTP 服务（TempServer）
+    PairingHttp,
+    /// 持久 TCP 配对服务（SimpleServer）
+    SimpleServer,
+    /// PC 间转发服务（见 relay）
+    RelaySource,
+}
+
+impl ServerRole {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ServerRole::PairingHttp => "配对服务",
+            ServerRole::SimpleServer => "TCP 服务",
+            ServerRole::RelaySource => "转发服务",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ListeningPort {
+    pub port: u16,
+    pub role: ServerRole,
+    pub address: String,
+    pub since: i64,
+}
+
+/// 端口已被本应用的其他服务登记
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortConflict {
+    pub port: u16,
+    pub requested: ServerRole,
+    pub held_by: ServerRole,
+}
+
+impl std::fmt::Display for PortConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Port {} is already used by {:?} (requested by {:?})", self.port, self.held_by, self.requested)
+    }
+}
+
+impl From<PortConflict> for BindError {
+    fn from(c: PortConflict) -> Self {
+        BindError::new(c.port, BindErrorKind::AddrInUse, c.to_string(), Some(format!("本应用的{}", c.held_by.label())))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct PortRegistry {
+    entries: Arc<Mutex<HashMap<u16, ListeningPort>>>,
+}
+
+impl PortRegistry {
+    /// 登记端口；返回的 guard 被丢弃时释放
+    pub fn register(&self, role: ServerRole, port: u16, address: &str) -> Result<PortGuard, PortConflict> {
+        let mut entries = self.entries.lock();
+        if let Some(existing) = entries.get(&port) {
+            return Err(PortConflict { port, requested: role, held_by: existing.role });
+        }
+        entries.insert(
+            port,
+            ListeningPort { port, role, address: address.to_string(), since: chrono::Utc::now().timestamp() },
+        );
+        println!("[Ports] {:?} registered {}", role, port);
+        Ok(PortGuard { registry: self.clone(), port })
+    }
+
+    /// 当前登记的端口（按端口号）
+    pub fn list(&self) -> Vec<ListeningPort> {
+        let mut list: Vec<ListeningPort> = self.entries.lock().values().cloned().collect();
+        list.sort_by_key(|p| p.port);
+        list
+    }
+}
+
+pub struct PortGuard {
+    registry: PortRegistry,
+    port: u16,
+}
+
+impl Drop for PortGuard {
+    fn drop(&mut self) {
+        if let Some(entry) = self.registry.entries.lock().remove(&self.port) {
+            println!("[Ports] {:?} released {}", entry.role, self.port);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_server::SimpleServer;
+    use crate::temp_server::TempServer;
+
+    #[test]
+    fn test_conflicting_roles_are_refused() {
+        let ports = PortRegistry::default();
+        let guard = ports.register(ServerRole::PairingHttp, 10035, "0.0.0.0").unwrap();
+        let conflict = ports.register(ServerRole::SimpleServer, 10035, "0.0.0.0").err().unwrap();
+        assert_eq!(conflict.held_by, ServerRole::PairingHttp);
+        let err = BindError::from(conflict);
+        assert_eq!(err.kind, BindErrorKind::AddrInUse);
+        assert!(err.hint.contains("配对服务"));
+        assert!(ports.register(ServerRole::SimpleServer, 10036, "0.0.0.0").is_ok());
+        assert_eq!(ports.list().len(), 1);
+        drop(guard);
+        assert!(ports.list().is_empty());
+    }
+
+    #[test]
+    fn test_sequential_start_stop_releases() {
+        let ports = PortRegistry::default();
+        let port = crate::network_utils::find_available_port(20135).unwrap();
+
+        let mut server = TempServer::new(port).unwrap();
+        server.hold_port(ports.register(ServerRole::PairingHttp, port, "0.0.0.0").unwrap());
+        assert_eq!(ports.list()[0].role, ServerRole::PairingHttp);
+        let simple = SimpleServer::new(port);
+        assert!(simple.start_registered(&ports).unwrap_err().contains("PairingHttp"));
+        drop(server);
+
+        simple.start_registered(&ports).unwrap();
+        assert_eq!(ports.list()[0].role, ServerRole::SimpleServer);
+        simple.stop();
+        assert!(ports.list().is_empty());
+
+        // 绑定失败（端口被外部占用）时同样释放登记
+        let taken = crate::network_utils::find_available_port(port + 1).unwrap();
+        let _external = std::net::TcpListener::bind(("0.0.0.0", taken)).unwrap();
+        assert!(SimpleServer::new(taken).start_registered(&ports).is_err());
+        assert!(ports.list().is_empty());
+    }
+}
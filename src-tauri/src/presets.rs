@@ -0,0 +1,300 @@
+//! 过滤规则预设：把过滤规则、单应用预览级别与应用显示名/颜色覆盖项导出为带版本号的 JSON 文件，便于在同事之间共享。
+//! 不包含令牌、设备、通知等数据。导入时逐条按交互设置相同的方式校验，
+//! merge 只应用通过校验的条目；replace 任一条目不通过则整体不生效。
+//! 本项目没有单独的黑名单 / 白名单模式，屏蔽应用用 `package` + `drop` 规则表达。
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::app_meta::{self, AppMetaMap};
+use crate::audit::{redact_path, AuditSource};
+use crate::commands::{AppState, SETTINGS_FILE};
+use crate::limits;
+use crate::privacy::PreviewLevel;
+use crate::rules::{self, Rule};
+
+/// 当前预设格式版本
+pub const PRESET_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RulesPreset {
+    pub version: u32,
+    pub rules: Vec<Rule>,
+    /// 包名 -> 预览级别
+    pub privacy_overrides: HashMap<String, PreviewLevel>,
+    /// 包名 -> 显示名与颜色（旧版预设没有该字段）
+    pub app_meta: AppMetaMap,
+}
+
+impl Default for RulesPreset {
+    fn default() -> Self {
+        Self { version: PRESET_VERSION, rules: Vec::new(), privacy_overrides: HashMap::new(), app_meta: AppMetaMap::new() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// 同 id 的规则覆盖，其余追加
+    Merge,
+    /// 整体替换现有规则、单应用预览级别与应用显示名
+    Replace,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EntryResult {
+    /// "rule"、"privacy" 或 "app_meta"
+    pub kind: String,
+    /// 规则 id 或包名
+    pub key: String,
+    pub accepted: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    /// 是否写入了设置（replace 有条目被拒时为 false）
+    pub applied: bool,
+    pub entries: Vec<EntryResult>,
+}
+
+fn entry(kind: &str, key: &str, result: Result<(), String>) -> EntryResult {
+    EntryResult { kind: kind.to_string(), key: key.to_string(), accepted: result.is_ok(), error: result.err() }
+}
+
+/// 通过校验的条目
+struct Checked {
+    rules: Vec<Rule>,
+    privacy: HashMap<String, PreviewLevel>,
+    app_meta: AppMetaMap,
+}
+
+/// 逐条校验，返回通过的条目与每条结果
+fn check(preset: RulesPreset) -> (Checked, Vec<EntryResult>) {
+    let mut entries = Vec::new();
+    let mut rules = Vec::new();
+    let mut seen = HashSet::new();
+    for rule in preset.rules {
+        let result = rule.validate().and_then(|_| {
+            if seen.insert(rule.id.clone()) {
+                Ok(())
+            } else {
+                Err(format!("duplicate rule id {}", rule.id))
+            }
+        });
+        entries.push(entry("rule", &rule.id, result.clone()));
+        if result.is_ok() {
+            rules.push(rule);
+        }
+    }
+    let mut overrides: Vec<(String, PreviewLevel)> = preset.privacy_overrides.into_iter().collect();
+    overrides.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut privacy = HashMap::new();
+    for (package, level) in overrides {
+        let result = rules::validate_glob(&package);
+        entries.push(entry("privacy", &package, result.clone()));
+        if result.is_ok() {
+            privacy.insert(package, level);
+        }
+    }
+    let mut meta = AppMetaMap::new();
+    for (package, m) in preset.app_meta {
+        // 导入的显示名按交互设置相同的方式规范化（截断、去控制字符）
+        let result = m.normalized().and_then(|m| app_meta::validate_entry(&package, &m).map(|_| m));
+        entries.push(entry("app_meta", &package, result.clone().map(|_| ())));
+        if let Ok(m) = result {
+            meta.insert(package, m);
+        }
+    }
+    (Checked { rules, privacy, app_meta: meta }, entries)
+}
+
+impl AppState {
+    /// 导出当前规则与单应用预览级别，返回规则条数
+    pub fn export_rules_preset(&self, path: &Path) -> Result<usize, String> {
+        let preset = {
+            let settings = self.settings.read();
+            RulesPreset {
+                version: PRESET_VERSION,
+                rules: settings.rules.clone(),
+                privacy_overrides: settings.privacy.per_app.clone(),
+                app_meta: settings.app_meta.clone(),
+            }
+        };
+        let json = serde_json::to_string_pretty(&preset).map_err(|e| format!("Failed to serialize preset: {}", e))?;
+        crate::storage::write_atomic(path, json.as_bytes())?;
+        println!("[Presets] Exported {} rules to {}", preset.rules.len(), path.display());
+        Ok(preset.rules.len())
+    }
+
+    pub fn import_rules_preset(&self, path: &Path, mode: ImportMode) -> Result<ImportReport, String> {
+        let size = std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?.len();
+        limits::check_bytes("preset", size as usize, limits::MAX_PRESET_FILE_BYTES as usize)?;
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let preset: RulesPreset = serde_json::from_str(&raw).map_err(|e| format!("Invalid preset: {}", e))?;
+        if preset.version == 0 || preset.version > PRESET_VERSION {
+            return Err(format!("Unsupported preset version {}", preset.version));
+        }
+        limits::check_items("preset.rules", preset.rules.len(), limits::MAX_RULES)?;
+        limits::check_items("preset.app_meta", preset.app_meta.len(), limits::MAX_PER_APP_ENTRIES)?;
+        let (checked, entries) = check(preset);
+        if mode == ImportMode::Replace && entries.iter().any(|e| !e.accepted) {
+            println!("[Presets] Replace aborted, {} entries rejected", entries.iter().filter(|e| !e.accepted).count());
+            return Ok(ImportReport { mode, applied: false, entries });
+        }
+
+        let (previous, settings) = {
+            let mut settings = self.settings.write();
+            let previous = settings.rules.len();
+            let mut next = settings.clone();
+            match mode {
+                ImportMode::Replace => {
+                    next.rules = checked.rules;
+                    next.privacy.per_app = checked.privacy;
+                    next.app_meta = checked.app_meta;
+                }
+                ImportMode::Merge => {
+                    for rule in checked.rules {
+                        match next.rules.iter_mut().find(|r| r.id == rule.id) {
+                            Some(existing) => *existing = rule,
+                            None => next.rules.push(rule),
+                        }
+                    }
+                    next.privacy.per_app.extend(checked.privacy);
+                    next.app_meta.extend(checked.app_meta);
+                }
+            }
+            next.validate()?;
+            self.storage.save(SETTINGS_FILE, &next)?;
+            *settings = next.clone();
+            (previous, next)
+        };
+        if mode == ImportMode::Replace {
+            let params = serde_json::json!({ "file": redact_path(path), "rules": settings.rules.len() });
+            self.audit("import_rules_replace", AuditSource::Command, previous, params);
+        }
+        println!("[Presets] Imported ({:?}), now {} rules", mode, settings.rules.len());
+        self.events.emit("privacy-changed", &settings.privacy);
+        self.events.emit("app-meta-changed", self.app_meta_map());
+        self.refresh_tray_menu();
+        Ok(ImportReport { mode, applied: true, entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::{self, Surface};
+    use crate::rules::RuleAction;
+    use crate::types::Notification;
+
+    fn rule(id: &str, package: Option<&str>, pattern: Option<&str>, action: RuleAction) -> Rule {
+        Rule {
+            id: id.into(),
+            enabled: true,
+            package: package.map(Into::into),
+            channel: None,
+            pattern: pattern.map(Into::into),
+            language: None,
+            action,
+        }
+    }
+
+    fn fixtures() -> Vec<Notification> {
+        [("com.tencent.mm", "老板", "周报"), ("com.taobao.taobao", "双十一", "优惠券"), ("com.corp.oa", "审批", "请假")]
+            .iter()
+            .map(|(pkg, title, text)| Notification {
+                package_name: Some(pkg.to_string()),
+                title: Some(title.to_string()),
+                text: Some(text.to_string()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// 每条通知的生效结果：命中的规则动作 + 弹窗预览级别
+    fn evaluation(state: &AppState) -> Vec<(Option<RuleAction>, PreviewLevel)> {
+        let settings = state.settings.read();
+        fixtures()
+            .iter()
+            .map(|n| {
+                (
+                    rules::evaluate(&settings.rules, n).map(|r| r.action),
+                    privacy::effective_level(&settings.privacy, n.package_name.as_deref(), Surface::Toast),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_export_wipe_import_roundtrip() {
+        let dir = crate::storage::temp_dir("presets");
+        let state = AppState::default();
+        {
+            let mut settings = state.settings.write();
+            settings.rules = vec![
+                rule("block-taobao", Some("com.taobao.*"), None, RuleAction::Drop),
+                rule("mute-leave", None, Some("请假|调休"), RuleAction::Mute),
+            ];
+            settings.privacy.per_app.insert("com.tencent.mm".into(), PreviewLevel::TitleOnly);
+            settings.privacy.hide_previews = false;
+        }
+        state.set_app_meta("com.corp.oa", Some("OA 审批".into()), Some("#FF8800".into())).unwrap();
+        let before = evaluation(&state);
+        let path = dir.join("team.json");
+        assert_eq!(state.export_rules_preset(&path).unwrap(), 2);
+
+        let fresh = AppState::default();
+        assert_ne!(evaluation(&fresh), before);
+        let report = fresh.import_rules_preset(&path, ImportMode::Replace).unwrap();
+        assert!(report.applied);
+        assert!(report.entries.iter().all(|e| e.accepted));
+        assert_eq!(evaluation(&fresh), before);
+        assert_eq!(fresh.settings.read().app_meta, state.settings.read().app_meta);
+        assert_eq!(fresh.app_meta_map()["com.corp.oa"].color.as_deref(), Some("#ff8800"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_replace_is_all_or_nothing_and_merge_is_partial() {
+        let dir = crate::storage::temp_dir("presets");
+        let path = dir.join("bad.json");
+        let preset = RulesPreset {
+            rules: vec![
+                rule("ok", Some("com.corp.*"), None, RuleAction::Mute),
+                rule("broken", None, Some("(unclosed"), RuleAction::Drop),
+            ],
+            privacy_overrides: HashMap::from([("com corp".to_string(), PreviewLevel::Hidden)]),
+            app_meta: AppMetaMap::from([
+                ("com.corp.oa".to_string(), crate::app_meta::AppMeta { display_name: Some(" OA ".into()), color: None }),
+                ("com.corp.mail".to_string(), crate::app_meta::AppMeta { display_name: None, color: Some("blue".into()) }),
+            ]),
+            ..Default::default()
+        };
+        std::fs::write(&path, serde_json::to_string(&preset).unwrap()).unwrap();
+
+        let state = AppState::default();
+        state.settings.write().rules = vec![rule("keep", Some("com.keep"), None, RuleAction::Drop)];
+        let report = state.import_rules_preset(&path, ImportMode::Replace).unwrap();
+        assert!(!report.applied);
+        let rejected: Vec<&str> = report.entries.iter().filter(|e| !e.accepted).map(|e| e.key.as_str()).collect();
+        assert_eq!(rejected, ["broken", "com corp", "com.corp.mail"]);
+        assert_eq!(state.settings.read().rules.len(), 1);
+
+        let report = state.import_rules_preset(&path, ImportMode::Merge).unwrap();
+        assert!(report.applied);
+        let ids: Vec<String> = state.settings.read().rules.iter().map(|r| r.id.clone()).collect();
+        assert_eq!(ids, ["keep", "ok"]);
+        assert!(state.settings.read().privacy.per_app.is_empty());
+        assert_eq!(state.settings.read().app_meta["com.corp.oa"].display_name.as_deref(), Some("OA"));
+
+        std::fs::write(&path, r#"{"version":99}"#).unwrap();
+        assert!(state.import_rules_preset(&path, ImportMode::Merge).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
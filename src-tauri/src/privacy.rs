@@ -0,0 +1,128 @@
+//! 通知预览隐私：按应用设置预览级别（全部 / 仅应用与标题 / 仅“新通知”），
+//! 另有全局“隐藏预览”开关（托盘菜单可快速切换），屏幕共享时避免正文外露。
+//! 系统通知、详情弹出窗口、托盘最近通知都按生效级别渲染；主窗口列表只在全局开关打开时隐藏。
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::types::Notification;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewLevel {
+    /// 显示全部内容
+    #[default]
+    Full,
+    /// 只显示应用与标题
+    TitleOnly,
+    /// 只显示“新通知”
+    Hidden,
+}
+
+/// 渲染通知内容的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surface {
+    MainList,
+    Toast,
+    Popout,
+    TrayMenu,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct PrivacySettings {
+    /// 未单独设置的应用使用的级别
+    pub default_level: PreviewLevel,
+    /// 包名 -> 级别
+    pub per_app: HashMap<String, PreviewLevel>,
+    /// 全局隐藏预览（优先于一切）
+    pub hide_previews: bool,
+}
+
+/// 生效级别：全局开关 > 单应用设置 > 默认；主窗口列表不受单应用/默认设置影响
+pub fn effective_level(settings: &PrivacySettings, package: Option<&str>, surface: Surface) -> PreviewLevel {
+    if settings.hide_previews {
+        return PreviewLevel::Hidden;
+    }
+    if surface == Surface::MainList {
+        return PreviewLevel::Full;
+    }
+    package
+        .and_then(|p| settings.per_app.get(p).copied())
+        .unwrap_or(settings.default_level)
+}
+
+/// 按级别处理后的预览内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Preview {
+    pub app: Option<String>,
+    pub title: String,
+    pub body: Option<String>,
+}
+
+pub const HIDDEN_TITLE: &str = "新通知";
+
+pub fn redact(n: &Notification, level: PreviewLevel) -> Preview {
+    match level {
+        PreviewLevel::Full => Preview {
+            app: n.package_name.clone(),
+            title: n.title.clone().unwrap_or_default(),
+            body: n.text.clone(),
+        },
+        PreviewLevel::TitleOnly => Preview {
+            app: n.package_name.clone(),
+            title: n.title.clone().unwrap_or_default(),
+            body: None,
+        },
+        PreviewLevel::Hidden => Preview { app: None, title: HIDDEN_TITLE.to_string(), body: None },
+    }
+}
+
+/// 按设置与位置直接得到预览
+pub fn preview_for(settings: &PrivacySettings, n: &Notification, surface: Surface) -> Preview {
+    redact(n, effective_level(settings, n.package_name.as_deref(), surface))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_level_precedence() {
+        let mut s = PrivacySettings {
+            default_level: PreviewLevel::TitleOnly,
+            per_app: HashMap::from([("com.tencent.mm".to_string(), PreviewLevel::Hidden), ("com.mail".to_string(), PreviewLevel::Full)]),
+            hide_previews: false,
+        };
+        assert_eq!(effective_level(&s, Some("com.tencent.mm"), Surface::Toast), PreviewLevel::Hidden);
+        assert_eq!(effective_level(&s, Some("com.mail"), Surface::TrayMenu), PreviewLevel::Full);
+        assert_eq!(effective_level(&s, Some("com.other"), Surface::Popout), PreviewLevel::TitleOnly);
+        assert_eq!(effective_level(&s, None, Surface::Toast), PreviewLevel::TitleOnly);
+        assert_eq!(effective_level(&s, Some("com.tencent.mm"), Surface::MainList), PreviewLevel::Full);
+
+        s.hide_previews = true;
+        for surface in [Surface::MainList, Surface::Toast, Surface::Popout, Surface::TrayMenu] {
+            assert_eq!(effective_level(&s, Some("com.mail"), surface), PreviewLevel::Hidden);
+        }
+    }
+
+    #[test]
+    fn test_redact_levels() {
+        let n = Notification {
+            id: "1".into(),
+            package_name: Some("com.tencent.mm".into()),
+            title: Some("老板".into()),
+            text: Some("工资条".into()),
+            ..Default::default()
+        };
+        assert_eq!(redact(&n, PreviewLevel::Full).body.as_deref(), Some("工资条"));
+        let title_only = redact(&n, PreviewLevel::TitleOnly);
+        assert_eq!((title_only.title.as_str(), title_only.body), ("老板", None));
+        assert_eq!(
+            redact(&n, PreviewLevel::Hidden),
+            Preview { app: None, title: HIDDEN_TITLE.into(), body: None }
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! 多档案：工作手机与个人手机的数据分开保存（设置、规则、保留策略、已配对设备、通知各自独立）。
+//! 档案列表保存在数据目录根下的 PROFILES_FILE；每个档案有自己的数据子目录，
+//! 默认档案的子目录为空，即原有数据所在的根目录（首次启动时自动创建，不移动任何文件）。
+//! 新档案首次加载时以默认设置叠加档案的 settings_overrides 作为初始设置，之后与其他档案一样单独保存。
+//! 切换档案时先落盘旧档案（序号、通知量、待写入文件），清空内存中的通知，再加载新档案并推送 profile-switched，
+//! 前端收到后重新拉取全部数据。有设备连接时拒绝切换（ProfileBusy），避免事件写入错误的档案。
+//! 档案可以加 PIN 锁，切换到加锁的档案需要 PIN（只保存摘要）。
+
+use std::path::PathBuf;
+
+use parking_lot::{Mutex, RwLock};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::commands::AppState;
+use crate::limits::{self, InvalidArgument};
+use crate::settings::Settings;
+use crate::storage::Storage;
+use crate::store::NotificationStore;
+
+pub const PROFILES_FILE: &str = "profiles.json";
+pub const DEFAULT_PROFILE_ID: &str = "default";
+pub const MAX_PROFILES: usize = 20;
+const MAX_NAME_BYTES: usize = 64;
+const PIN_LEN: std::ops::RangeInclusive<usize> = 4..=64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Profile {
+    id: String,
+    name: String,
+    /// 相对数据目录根的子目录；默认档案为空
+    data_subdir: String,
+    /// 新档案的初始设置（叠加在默认设置上的部分字段）
+    #[serde(default)]
+    settings_overrides: serde_json::Value,
+    created_at: i64,
+    /// PIN 的 SHA-256（十六进制，以档案 id 加盐）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lock_hash: Option<String>,
+}
+
+impl Profile {
+    fn default_profile() -> Self {
+        Self {
+            id: DEFAULT_PROFILE_ID.to_string(),
+            name: "Default".to_string(),
+            data_subdir: String::new(),
+            settings_overrides: serde_json::Value::Null,
+            created_at: 0,
+            lock_hash: None,
+        }
+    }
+
+    fn info(&self, active: &str) -> ProfileInfo {
+        ProfileInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            data_subdir: self.data_subdir.clone(),
+            settings_overrides: self.settings_overrides.clone(),
+            created_at: self.created_at,
+            locked: self.lock_hash.is_some(),
+            active: self.id == active,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Registry {
+    active: String,
+    profiles: Vec<Profile>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self { active: DEFAULT_PROFILE_ID.to_string(), profiles: vec![Profile::default_profile()] }
+    }
+}
+
+impl Registry {
+    fn get(&self, id: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.id == id)
+    }
+}
+
+/// 返回给前端的档案信息（不含 PIN 摘要）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ProfileInfo {
+    pub id: String,
+    pub name: String,
+    pub data_subdir: String,
+    pub settings_overrides: serde_json::Value,
+    pub created_at: i64,
+    pub locked: bool,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ProfilesView {
+    pub active: String,
+    pub profiles: Vec<ProfileInfo>,
+}
+
+/// 当前档案的数据目录与初始设置
+#[derive(Debug, Clone)]
+pub struct ActiveProfile {
+    pub id: String,
+    pub dir: PathBuf,
+    settings_overrides: serde_json::Value,
+}
+
+impl ActiveProfile {
+    /// 默认设置叠加档案的设置覆盖；覆盖无效时使用默认设置
+    pub fn initial_settings(&self) -> Settings {
+        if self.settings_overrides.is_null() {
+            return Settings::default();
+        }
+        apply_overrides(&self.settings_overrides).unwrap_or_else(|e| {
+            println!("[Profiles] Ignoring settings overrides of {}: {}", self.id, e);
+            Settings::default()
+        })
+    }
+}
+
+fn merge(base: &mut serde_json::Value, patch: &serde_json::Value) {
+    match (base, patch) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge(base.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
+
+fn apply_overrides(overrides: &serde_json::Value) -> Result<Settings, String> {
+    if !overrides.is_object() {
+        return Err("settings_overrides must be an object".to_string());
+    }
+    let mut value = serde_json::to_value(Settings::default()).map_err(|e| e.to_string())?;
+    merge(&mut value, overrides);
+    let settings: Settings = serde_json::from_value(value).map_err(|e| format!("Invalid settings_overrides: {}", e))?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+fn pin_hash(profile_id: &str, pin: &str) -> String {
+    format!("{:x}", Sha256::digest(format!("{}:{}", profile_id, pin).as_bytes()))
+}
+
+/// 有设备连接时不能切换；以 `ProfileBusy: {json}` 的形式返回给前端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileBusy {
+    pub connected: Vec<String>,
+}
+
+impl std::fmt::Display for ProfileBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ProfileBusy: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<ProfileBusy> for String {
+    fn from(e: ProfileBusy) -> Self {
+        println!("[Profiles] Switch blocked by {} connected devices", e.connected.len());
+        e.to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct Profiles {
+    // 数据目录根；未设置时（如测试）档案只保存在内存中
+    root: RwLock<Option<PathBuf>>,
+    registry: Mutex<Registry>,
+    // 档案列表保存在根目录，与各档案的数据目录分开
+    pub(crate) storage: Storage,
+    // 同一时间只进行一次切换
+    switching: Mutex<()>,
+}
+
+impl Profiles {
+    fn active(&self, registry: &Registry) -> ActiveProfile {
+        let profile = registry.get(&registry.active).cloned().unwrap_or_else(Profile::default_profile);
+        let root = self.root.read().clone().unwrap_or_default();
+        let dir = if profile.data_subdir.is_empty() { root } else { root.join(&profile.data_subdir) };
+        ActiveProfile { id: profile.id, dir, settings_overrides: profile.settings_overrides }
+    }
+
+    fn save(&self, registry: &Registry) -> Result<(), String> {
+        self.storage.save(PROFILES_FILE, registry)
+    }
+}
+
+impl AppState {
+    /// 读取档案列表（没有时创建包含原有数据的默认档案），返回当前档案
+    pub(crate) fn init_profiles(&self, root: PathBuf) -> ActiveProfile {
+        *self.profiles.root.write() = Some(root.clone());
+        self.profiles.storage.set_base_dir(root);
+        let mut registry = self.profiles.registry.lock();
+        match self.profiles.storage.load::<Registry>(PROFILES_FILE) {
+            Some(loaded) => *registry = loaded,
+            None => {
+                println!("[Profiles] Created Default profile for existing data");
+                if let Err(e) = self.profiles.save(&registry) {
+                    println!("[Profiles] {}", e);
+                }
+            }
+        }
+        if registry.get(&registry.active).is_none() {
+            println!("[Profiles] Active profile {} missing, using default", registry.active);
+            registry.active = DEFAULT_PROFILE_ID.to_string();
+        }
+        let active = self.profiles.active(&registry);
+        println!("[Profiles] Active: {}", active.id);
+        active
+    }
+
+    pub fn list_profiles(&self) -> ProfilesView {
+        let registry = self.profiles.registry.lock();
+        ProfilesView {
+            active: registry.active.clone(),
+            profiles: registry.profiles.iter().map(|p| p.info(&registry.active)).collect(),
+        }
+    }
+
+    pub fn create_profile(&self, name: &str, settings_overrides: Option<serde_json::Value>) -> Result<ProfileInfo, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(InvalidArgument::new("name", "name must not be empty").into());
+        }
+        limits::check_bytes("name", name.len(), MAX_NAME_BYTES)?;
+        let settings_overrides = settings_overrides.unwrap_or(serde_json::Value::Null);
+        if !settings_overrides.is_null() {
+            apply_overrides(&settings_overrides).map_err(|e| String::from(InvalidArgument::new("settings_overrides", e)))?;
+        }
+        let mut registry = self.profiles.registry.lock();
+        if registry.profiles.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+            return Err(InvalidArgument::new("name", format!("A profile named {} already exists", name)).into());
+        }
+        if registry.profiles.len() >= MAX_PROFILES {
+            return Err(format!("Too many profiles (max {})", MAX_PROFILES));
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let profile = Profile {
+            data_subdir: format!("profiles/{}", id),
+            id,
+            name: name.to_string(),
+            settings_overrides,
+            created_at: chrono::Utc::now().timestamp(),
+            lock_hash: None,
+        };
+        registry.profiles.push(profile.clone());
+        self.profiles.save(&registry)?;
+        println!("[Profiles] Created {} ({})", profile.name, profile.id);
+        Ok(profile.info(&registry.active))
+    }
+
+    /// 设置或清除（pin 为 None）档案的 PIN
+    pub fn set_profile_lock(&self, id: &str, pin: Option<&str>) -> Result<ProfileInfo, String> {
+        if let Some(pin) = pin {
+            if !PIN_LEN.contains(&pin.chars().count()) {
+                return Err(InvalidArgument::new("pin", "pin must be 4-64 characters").into());
+            }
+        }
+        let mut registry = self.profiles.registry.lock();
+        let active = registry.active.clone();
+        let profile = registry.profiles.iter_mut().find(|p| p.id == id).ok_or_else(|| format!("Profile not found: {}", id))?;
+        profile.lock_hash = pin.map(|pin| pin_hash(id, pin));
+        let info = profile.info(&active);
+        self.profiles.save(&registry)?;
+        println!("[Profiles] {} locked={}", id, info.locked);
+        Ok(info)
+    }
+
+    /// 切换当前档案：落盘旧档案，清空内存中的通知，加载新档案的数据
+    pub fn switch_profile(&self, id: &str, pin: Option<&str>) -> Result<ProfileInfo, String> {
+        let _switching = self.profiles.switching.lock();
+        let target = {
+            let registry = self.profiles.registry.lock();
+            let target = registry.get(id).cloned().ok_or_else(|| format!("Profile not found: {}", id))?;
+            if registry.active == id {
+                return Ok(target.info(id));
+            }
+            target
+        };
+        if let Some(hash) = &target.lock_hash {
+            if pin.map(|pin| pin_hash(id, pin)).as_ref() != Some(hash) {
+                return Err(format!("ProfileLocked: {} requires a valid PIN", target.name));
+            }
+        }
+        let mut connected: Vec<String> = self.clients.read().keys().cloned().collect();
+        if !connected.is_empty() {
+            connected.sort();
+            return Err(ProfileBusy { connected }.into());
+        }
+        if self.profiles.root.read().is_none() {
+            return Err("No data dir".to_string());
+        }
+
+        // 落盘旧档案（清空内存中的存储之前）
+        for result in [self.persist_volume(), self.persist_rule_hits(), self.persist_channels(), self.persist_notification_store()] {
+            if let Err(e) = result {
+                println!("[Profiles] {}", e);
+            }
+        }
+        self.shutdown_stream();
+        if let Err(e) = self.storage.retry_pending() {
+            println!("[Profiles] {}", e);
+        }
+        *self.store.lock().unwrap() = NotificationStore::default();
+        self.close_all_popouts();
+
+        let active = {
+            let mut registry = self.profiles.registry.lock();
+            registry.active = id.to_string();
+            if let Err(e) = self.profiles.save(&registry) {
+                println!("[Profiles] {}", e);
+            }
+            self.profiles.active(&registry)
+        };
+        self.load_profile_data(&active);
+        println!("[Profiles] Switched to {} ({})", target.name, id);
+
+        let info = target.info(id);
+        self.events.emit("profile-switched", info.clone());
+        self.emit_counts();
+        self.refresh_tray_icon();
+        self.refresh_tray_menu();
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::android_client::AndroidSocketClient;
+    use crate::mutes::MuteTarget;
+    use crate::transport::Transport;
+    use crate::types::Notification;
+
+    struct Phone;
+
+    impl Transport for Phone {
+        fn send_line(&mut self, _line: &str) -> Result<(), String> {
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(r#"{"success":true,"protocolVersion":3}"#.to_string())
+        }
+    }
+
+    #[test]
+    fn test_default_profile_wraps_existing_data() {
+        let dir = crate::storage::temp_dir("profiles-default");
+        std::fs::write(dir.join("settings.json"), r#"{"retention":{"max_items":123}}"#).unwrap();
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+
+        let view = state.list_profiles();
+        assert_eq!(view.active, DEFAULT_PROFILE_ID);
+        assert_eq!(view.profiles.len(), 1);
+        assert_eq!((view.profiles[0].name.as_str(), view.profiles[0].data_subdir.as_str()), ("Default", ""));
+        assert_eq!(state.settings.read().retention.max_items, 123);
+        assert!(dir.join(PROFILES_FILE).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_switch_separates_data_and_respects_lock() {
+        let dir = crate::storage::temp_dir("profiles-switch");
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        state.mute(MuteTarget::Conversation { conversation_key: "family".into() }, None, false).unwrap();
+        state.store.lock().unwrap().upsert(Notification { id: "1".into(), ..Default::default() }, false);
+        state.migrate_to_persistent_store().unwrap();
+        // 迁移之后、维护任务写入之前的修改在切换时落盘
+        state.store.lock().unwrap().upsert(Notification { id: "2".into(), ..Default::default() }, false);
+
+        assert!(state.create_profile(" ", None).is_err());
+        assert!(state.create_profile("Work", Some(serde_json::json!({ "retention": { "max_items": 0 } }))).is_err());
+        let work = state.create_profile("Work", Some(serde_json::json!({ "retention": { "max_items": 100 } }))).unwrap();
+        assert!(state.create_profile("work", None).is_err());
+        state.set_profile_lock(&work.id, Some("2468")).unwrap();
+
+        assert!(state.switch_profile(&work.id, None).unwrap_err().starts_with("ProfileLocked"));
+        assert!(state.switch_profile(&work.id, Some("1111")).is_err());
+
+        // 有设备连接时拒绝切换
+        let client = AndroidSocketClient::with_transport(Box::new(Phone), "phone-1".into(), Arc::default());
+        state.clients.write().insert("phone-1".into(), Arc::new(client));
+        let busy = state.switch_profile(&work.id, Some("2468")).unwrap_err();
+        assert_eq!(busy, ProfileBusy { connected: vec!["phone-1".into()] }.to_string());
+        state.clients.write().clear();
+
+        state.events.take_captured();
+        let info = state.switch_profile(&work.id, Some("2468")).unwrap();
+        assert!(info.active && info.locked);
+        assert_eq!(state.settings.read().retention.max_items, 100);
+        assert!(state.list_mutes().is_empty());
+        assert_eq!(state.storage.base_dir(), Some(dir.join(&work.data_subdir)));
+        let events = state.events.take_captured();
+        assert!(events.iter().any(|(e, p)| e == "profile-switched" && p["id"] == work.id.as_str()));
+
+        // 切回默认档案：原有数据还在；重启后仍是默认档案
+        state.switch_profile(DEFAULT_PROFILE_ID, None).unwrap();
+        assert_eq!(state.list_mutes().len(), 1);
+        assert!(["1", "2"].iter().all(|id| state.store.lock().unwrap().get(id).is_some()));
+        assert_eq!(state.settings.read().retention.max_items, Settings::default().retention.max_items);
+        let restarted = AppState::default();
+        restarted.init_storage(dir.clone());
+        assert_eq!(restarted.list_profiles().active, DEFAULT_PROFILE_ID);
+        assert_eq!(restarted.list_profiles().profiles.len(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
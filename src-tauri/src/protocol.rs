@@ -0,0 +1,291 @@
+//! 协议解码层：局域网内任何设备都能向监听端口或已建立的连接发送数据，协议 JSON 统一经这里解码。
+//! 依次检查帧大小、嵌套深度、JSON 语法、必需字段与取值（event_type 只能是 added/updated/removed），
+//! 失败转换为带类别的 ProtocolError 并按连接计数。任意字节最多让连接降级（断开重连），不会让进程 panic。
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Read};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::error_bus::BackgroundError;
+use crate::ingest::IngestOutcome;
+use crate::types::Event;
+
+/// 单帧（一行 JSON / 一条 WebSocket 消息 / 一个 HTTP 请求体）的字节上限
+pub const MAX_FRAME_BYTES: usize = 1024 * 1024;
+/// 对象/数组的最大嵌套层数（协议消息实际不超过 4 层）
+pub const MAX_DEPTH: usize = 32;
+pub const EVENT_TYPES: &[&str] = &["added", "updated", "removed", "clear_all"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtocolErrorKind {
+    FrameTooLarge,
+    TooDeep,
+    /// 不是合法 JSON（含截断、非 UTF-8）
+    Malformed,
+    MissingField,
+    /// 字段类型或取值不对
+    InvalidValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolError {
+    pub kind: ProtocolErrorKind,
+    pub detail: String,
+}
+
+impl ProtocolError {
+    fn new(kind: ProtocolErrorKind, detail: impl Into<String>) -> Self {
+        Self { kind, detail: detail.into() }
+    }
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Protocol error ({:?}): {}", self.kind, self.detail)
+    }
+}
+
+impl From<ProtocolError> for String {
+    fn from(e: ProtocolError) -> Self {
+        e.to_string()
+    }
+}
+
+/// 按类别累计的解码失败次数
+pub type ProtocolErrorCounts = BTreeMap<ProtocolErrorKind, u64>;
+
+/// 读取一行，超过 MAX_FRAME_BYTES 时报错而不是无限增长（之后该连接应断开）
+pub fn read_frame<R: BufRead>(reader: &mut R, line: &mut String) -> std::io::Result<usize> {
+    let n = reader.by_ref().take(MAX_FRAME_BYTES as u64 + 1).read_line(line)?;
+    if n > MAX_FRAME_BYTES {
+        let e = ProtocolError::new(ProtocolErrorKind::FrameTooLarge, format!("line exceeds {} bytes", MAX_FRAME_BYTES));
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()));
+    }
+    Ok(n)
+}
+
+/// 最大嵌套层数；超过 limit 即停止扫描（字符串内的括号不计）
+fn depth(raw: &str, limit: usize) -> usize {
+    let (mut depth, mut max, mut in_string, mut escaped) = (0usize, 0usize, false, false);
+    for b in raw.bytes() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                max = max.max(depth);
+                if max > limit {
+                    break;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+/// 帧大小与嵌套深度检查（在交给 serde 之前）
+pub fn check_frame(raw: &str) -> Result<(), ProtocolError> {
+    if raw.len() > MAX_FRAME_BYTES {
+        return Err(ProtocolError::new(
+            ProtocolErrorKind::FrameTooLarge,
+            format!("{} bytes exceeds {}", raw.len(), MAX_FRAME_BYTES),
+        ));
+    }
+    if depth(raw, MAX_DEPTH) > MAX_DEPTH {
+        return Err(ProtocolError::new(ProtocolErrorKind::TooDeep, format!("nesting exceeds {} levels", MAX_DEPTH)));
+    }
+    Ok(())
+}
+
+fn classify(e: serde_json::Error) -> ProtocolError {
+    let detail = e.to_string();
+    let kind = match e.classify() {
+        serde_json::error::Category::Data if detail.starts_with("missing field") => ProtocolErrorKind::MissingField,
+        serde_json::error::Category::Data => ProtocolErrorKind::InvalidValue,
+        _ => ProtocolErrorKind::Malformed,
+    };
+    ProtocolError::new(kind, detail)
+}
+
+/// 解码一帧协议 JSON
+pub fn decode<T: DeserializeOwned>(raw: &str) -> Result<T, ProtocolError> {
+    check_frame(raw)?;
+    serde_json::from_str(raw.trim()).map_err(classify)
+}
+
+/// 解码并校验手机端推送的通知事件
+pub fn decode_event(raw: &str) -> Result<Event, ProtocolError> {
+    let event: Event = decode(raw)?;
+    if !EVENT_TYPES.contains(&event.event_type.as_str()) {
+        return Err(ProtocolError::new(
+            ProtocolErrorKind::InvalidValue,
+            format!("unknown event_type {:?}", event.event_type.chars().take(32).collect::<String>()),
+        ));
+    }
+    let notification_id = event.notification.as_ref().map(|n| n.id.as_str());
+    let valid = match event.event_type.as_str() {
+        "removed" => event.id.as_deref().or(notification_id).is_some_and(|id| !id.is_empty()),
+        "clear_all" => true,
+        _ => notification_id.is_some_and(|id| !id.is_empty()),
+    };
+    if !valid {
+        return Err(ProtocolError::new(
+            ProtocolErrorKind::MissingField,
+            format!("{} event without notification id", event.event_type),
+        ));
+    }
+    Ok(event)
+}
+
+impl AppState {
+    /// 处理连接上收到的一帧事件；解码失败计入该连接的协议错误，并带上连接上报错误总线
+    pub fn ingest_frame(&self, connection_id: &str, raw: &str) -> Result<IngestOutcome, String> {
+        let client = self.clients.read().get(connection_id).cloned();
+        let event = match &client {
+            Some(client) => client.decode_event(raw),
+            None => decode_event(raw),
+        };
+        match event {
+            Ok(event) => Ok(self.ingest_from(connection_id, event)),
+            Err(e) => {
+                println!("[Protocol] {} sent an invalid frame: {}", connection_id, e);
+                self.report_error(BackgroundError::new("ingest", "invalid_frame", e.to_string()).device(connection_id));
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    use crate::android_client::AuthResponse;
+    use crate::endpoints::ControlFrame;
+
+    const SEEDS: &[&str] = &[
+        r#"{"event_type":"added","seq":1,"notification":{"id":"n1","package_name":"com.a","title":"你好","text":"t","read":false,"posted_at":1700000000,"importance":4,"actions":["回复"]}}"#,
+        r#"{"event_type":"removed","seq":2,"id":"n1","notification":null}"#,
+        r#"{"success":true,"token":"abc","requestId":"r1","protocolVersion":3,"capabilities":["actions"]}"#,
+        r#"{"url":"192.168.1.5:10035","token":"t","nonce":"x","device":{"name":"Pixel"},"port":"10035"}"#,
+        r#"{"action":"update_endpoint","host":"192.168.1.9","port":10036}"#,
+        r#"{"t1":1,"t2":2}"#,
+    ];
+
+    /// xorshift，保证每次运行的输入相同
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n.max(1) as u64) as usize
+        }
+    }
+
+    const TOKENS: &[&str] = &[
+        "{", "}", "[", "]", "\"", "\\", ":", ",", "null", "-1", "1e999", "18446744073709551616", "-9223372036854775808",
+        "\"added\"", "\"removed\"", "\"\"", "\u{0}", "\u{FEFF}", "🦀", "\"\\ud800\"", "true",
+    ];
+
+    fn mutate(rng: &mut Rng, seed: &str) -> String {
+        let mut bytes = seed.as_bytes().to_vec();
+        for _ in 0..=rng.below(4) {
+            let at = rng.below(bytes.len() + 1);
+            match rng.below(5) {
+                0 => bytes.truncate(at),
+                1 if !bytes.is_empty() => {
+                    let i = at.min(bytes.len() - 1);
+                    bytes[i] = rng.next() as u8;
+                }
+                2 => {
+                    let token = TOKENS[rng.below(TOKENS.len())];
+                    bytes.splice(at..at, token.bytes());
+                }
+                3 => {
+                    let end = (at + rng.below(16)).min(bytes.len());
+                    let chunk = bytes[at..end].to_vec();
+                    bytes.splice(at..at, chunk);
+                }
+                _ => {
+                    let open = if rng.below(2) == 0 { b'[' } else { b'{' };
+                    bytes.splice(at..at, std::iter::repeat_n(open, rng.below(200)));
+                }
+            }
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// 所有解码入口；只要求不 panic
+    fn feed(state: &AppState, raw: &str) {
+        let _ = decode::<AuthResponse>(raw);
+        let _ = decode::<ControlFrame>(raw);
+        let _ = decode::<serde_json::Value>(raw);
+        let _ = crate::pairing_payload::parse_pairing(raw);
+        let _ = state.ingest_frame("fuzz", raw);
+    }
+
+    #[test]
+    fn test_fuzz_decoders_never_panic() {
+        let state = AppState::default();
+        state.settings.write().use_corrected_time = true;
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for i in 0..20_000 {
+            let raw = mutate(&mut rng, SEEDS[i % SEEDS.len()]);
+            feed(&state, &raw);
+        }
+        // 边界时间戳经过时钟校正与展示
+        let extreme = r#"{"event_type":"added","seq":-9223372036854775808,"notification":{"id":"x","read":true,"posted_at":-9223372036854775808,"updated_at":9223372036854775807}}"#;
+        feed(&state, extreme);
+        let n = state.store.lock().unwrap().get("x").cloned().unwrap();
+        crate::clock::display_time(&crate::types::Notification { corrected_posted_at: Some(i64::MAX), ..n }, true);
+        assert_eq!(crate::clock::correct(i64::MIN, 5_000), i64::MIN);
+    }
+
+    #[test]
+    fn test_invalid_frame_reported_with_device() {
+        let state = AppState::default();
+        assert!(state.ingest_frame("pixel", "{").is_err());
+        let report = &state.errors.recent(1)[0];
+        assert_eq!((report.source.as_str(), report.code.as_str(), report.device.as_deref()), ("ingest", "invalid_frame", Some("pixel")));
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let kind = |raw: &str| decode_event(raw).unwrap_err().kind;
+        assert_eq!(kind(&"[".repeat(100_000)), ProtocolErrorKind::TooDeep);
+        assert_eq!(kind(&format!("\"{}\"", "x".repeat(MAX_FRAME_BYTES))), ProtocolErrorKind::FrameTooLarge);
+        assert_eq!(kind(r#"{"event_type":"added""#), ProtocolErrorKind::Malformed);
+        assert_eq!(kind(r#"{"event_type":"added","notification":null,"id":null}"#), ProtocolErrorKind::MissingField);
+        assert_eq!(kind(r#"{"event_type":"exploded","seq":1,"notification":null,"id":"1"}"#), ProtocolErrorKind::InvalidValue);
+        assert_eq!(kind(r#"{"event_type":"added","seq":1,"notification":null,"id":"1"}"#), ProtocolErrorKind::MissingField);
+        assert_eq!(kind(r#"{"event_type":"added","seq":"one","notification":null,"id":"1"}"#), ProtocolErrorKind::InvalidValue);
+        // 字符串里的括号不计入深度
+        let quoted = format!(r#"{{"event_type":"removed","seq":1,"id":"{}","notification":null}}"#, "[".repeat(100));
+        assert!(decode_event(&quoted).is_ok());
+
+        let huge = format!("{}\n", "x".repeat(MAX_FRAME_BYTES + 10));
+        let mut reader = BufReader::new(huge.as_bytes());
+        let mut line = String::new();
+        assert!(read_frame(&mut reader, &mut line).is_err());
+    }
+}
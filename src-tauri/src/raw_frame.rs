@@ -0,0 +1,217 @@
+//! 原始帧控制台（开发用）：向已连接的手机发送手写的一帧 JSON 并查看回复，排查安卓端问题时不必重新编译。
+//! 与帧检查器一样只在 dev-tools 特性的调试构建中可用；发送与收到的帧同样经过检查器记录。
+//! 没有 requestId 时自动补上；整个往返持有连接（与 ping 相同），回复按 requestId 对应，
+//! 窗口内没有对应回复时把第一条其他帧作为回复（correlated 为 false）。
+//! 窗口内收到的通知事件照常入库，不会因为控制台占用连接而丢失。
+//! 含令牌、密码等字段的帧需要显式传入 allow_token；每次发送都写入审计日志（只记录 action 与大小，不记录内容）。
+
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+use crate::inspector::{self, CapturedFrame};
+use crate::limits::{self, InvalidArgument};
+use crate::trace::Direction;
+
+pub const MAX_RAW_FRAME_BYTES: usize = 64 * 1024;
+pub const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+const TIMEOUT_MS: std::ops::RangeInclusive<u64> = 100..=30_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RawFrameResult {
+    pub request_id: String,
+    /// 实际发送的帧（敏感字段已替换）
+    pub sent: CapturedFrame,
+    pub reply: Option<CapturedFrame>,
+    /// reply 是否按 requestId 对应（否则为窗口内的第一条其他帧）
+    pub correlated: bool,
+    /// 窗口内收到的其他帧
+    pub other_frames: Vec<CapturedFrame>,
+    /// 其中作为通知事件入库的帧数
+    pub ingested: usize,
+    pub elapsed_ms: u64,
+}
+
+fn contains_secret(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Object(map) => map.iter().any(|(key, v)| inspector::is_secret_key(key) || contains_secret(v)),
+        serde_json::Value::Array(items) => items.iter().any(contains_secret),
+        _ => false,
+    }
+}
+
+impl AppState {
+    pub fn send_raw_frame(
+        &self,
+        connection_id: &str,
+        json: &str,
+        expect_reply: bool,
+        timeout_ms: Option<u64>,
+        allow_token: bool,
+    ) -> Result<RawFrameResult, String> {
+        if !inspector::AVAILABLE {
+            return Err(inspector::UNAVAILABLE.to_string());
+        }
+        limits::check_bytes("json", json.len(), MAX_RAW_FRAME_BYTES)?;
+        let mut frame: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| String::from(InvalidArgument::new("json", format!("Invalid JSON: {}", e))))?;
+        let Some(object) = frame.as_object_mut() else {
+            return Err(InvalidArgument::new("json", "frame must be a JSON object").into());
+        };
+        if !allow_token && object.iter().any(|(key, v)| inspector::is_secret_key(key) || contains_secret(v)) {
+            return Err(InvalidArgument::new("json", "frame contains a token or secret field; pass allow_token to send it").into());
+        }
+        let request_id = match object.get("requestId").and_then(|v| v.as_str()) {
+            Some(id) => id.to_string(),
+            None => {
+                let id = format!("raw_{}", uuid::Uuid::new_v4().simple());
+                object.insert("requestId".into(), id.clone().into());
+                id
+            }
+        };
+        let action = object.get("action").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let line = frame.to_string();
+        let client = self.clients.read().get(connection_id).cloned().ok_or_else(|| format!("Not connected: {}", connection_id))?;
+
+        self.audit(
+            "send_raw_frame",
+            AuditSource::Command,
+            1,
+            serde_json::json!({
+                "connection_id": connection_id,
+                "action": action,
+                "bytes": line.len(),
+                "expect_reply": expect_reply,
+                "allow_token": allow_token,
+            }),
+        );
+        println!("[RawFrame] {} -> {} ({} bytes)", connection_id, if action.is_empty() { "<no action>" } else { &action }, line.len());
+
+        let window = if expect_reply {
+            Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS).clamp(*TIMEOUT_MS.start(), *TIMEOUT_MS.end()))
+        } else {
+            Duration::ZERO
+        };
+        // 低功耗模式下读超时被放宽过，结束后恢复为当前模式的值
+        let restore = self.low_power.status(connection_id).map_or(crate::transport::READ_TIMEOUT, |s| crate::low_power::read_timeout(s.interval_s));
+        let started = Instant::now();
+        let exchange = client.exchange_raw(&line, &request_id, window, restore)?;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+
+        let mut ingested = 0;
+        for raw in &exchange.others {
+            if let Ok(event) = client.decode_event(raw) {
+                self.ingest_from(connection_id, event);
+                ingested += 1;
+            }
+        }
+        let capture = |raw: &String| inspector::capture(connection_id, Direction::Recv, raw);
+        let mut other_frames: Vec<CapturedFrame> = exchange.others.iter().map(capture).collect();
+        let correlated = exchange.reply.is_some();
+        let reply = match &exchange.reply {
+            Some(raw) => Some(capture(raw)),
+            None if other_frames.is_empty() => None,
+            None => Some(other_frames.remove(0)),
+        };
+        Ok(RawFrameResult {
+            request_id,
+            sent: inspector::capture(connection_id, Direction::Send, &line),
+            reply,
+            correlated,
+            other_frames,
+            ingested,
+            elapsed_ms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use crate::android_client::AndroidSocketClient;
+    use crate::transport::Transport;
+
+    /// 收到一帧后按脚本回复；sent 记录发出的帧
+    struct Phone {
+        replies: VecDeque<String>,
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Transport for Phone {
+        fn send_line(&mut self, line: &str) -> Result<(), String> {
+            let request_id = serde_json::from_str::<serde_json::Value>(line).unwrap()["requestId"].as_str().unwrap_or_default().to_string();
+            self.replies.iter_mut().for_each(|r| *r = r.replace("$ID", &request_id));
+            self.sent.lock().push(line.to_string());
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            self.replies.pop_front().ok_or_else(|| "Read timed out".to_string())
+        }
+    }
+
+    fn connect(state: &AppState, replies: &[&str]) -> Arc<Mutex<Vec<String>>> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let phone = Phone { replies: replies.iter().map(|r| r.to_string()).collect(), sent: sent.clone() };
+        let client = AndroidSocketClient::with_transport(Box::new(phone), "phone-1".into(), Arc::default());
+        state.clients.write().insert("phone-1".into(), Arc::new(client));
+        sent
+    }
+
+    #[test]
+    fn test_correlated_reply_and_interleaved_events() {
+        let state = AppState::default();
+        let sent = connect(
+            &state,
+            &[
+                r#"{"type":"added","seq":1,"notification":{"id":"n1","title":"hi"}}"#,
+                r#"{"requestId":"$ID","success":true,"token":"abc"}"#,
+            ],
+        );
+        let result = state.send_raw_frame("phone-1", r#"{"action":"version"}"#, true, Some(500), false).unwrap();
+
+        assert!(result.request_id.starts_with("raw_"));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&sent.lock()[0]).unwrap()["requestId"], result.request_id.as_str());
+        assert!(result.correlated);
+        let reply = result.reply.unwrap().decoded.unwrap();
+        assert_eq!((reply["success"].clone(), reply["token"].clone()), (true.into(), "<redacted>".into()));
+        assert_eq!((result.other_frames.len(), result.ingested), (1, 1));
+        assert!(state.store.lock().unwrap().get("n1").is_some());
+
+        let log = state.audit.recent(10);
+        assert_eq!(log[0].operation, "send_raw_frame");
+        assert_eq!(log[0].params["action"], "version");
+    }
+
+    #[test]
+    fn test_guard_rails_and_uncorrelated_reply() {
+        let state = AppState::default();
+        assert!(state.send_raw_frame("phone-1", "{", true, None, false).unwrap_err().starts_with("InvalidArgument"));
+        assert!(state.send_raw_frame("phone-1", "[1]", true, None, false).unwrap_err().starts_with("InvalidArgument"));
+        let big = format!(r#"{{"text":"{}"}}"#, "x".repeat(MAX_RAW_FRAME_BYTES));
+        assert!(state.send_raw_frame("phone-1", &big, true, None, false).unwrap_err().starts_with("PayloadTooLarge"));
+        let login = r#"{"action":"login","requestId":"r1","auth":{"token":"abc"}}"#;
+        assert!(state.send_raw_frame("phone-1", login, true, None, false).unwrap_err().contains("allow_token"));
+        assert!(state.send_raw_frame("phone-1", login, true, None, true).unwrap_err().starts_with("Not connected"));
+        assert!(state.audit.recent(10).is_empty());
+
+        let sent = connect(&state, &[r#"{"error":"unknown action"}"#]);
+        let result = state.send_raw_frame("phone-1", login, true, None, true).unwrap();
+        assert_eq!(result.request_id, "r1");
+        assert!(!result.correlated);
+        assert_eq!(result.reply.unwrap().decoded.unwrap()["error"], "unknown action");
+        assert_eq!(result.sent.decoded.unwrap()["auth"]["token"], "<redacted>");
+        assert_eq!(sent.lock().len(), 1);
+
+        // 不等待回复
+        let result = state.send_raw_frame("phone-1", r#"{"action":"ping","t0":1}"#, false, None, false).unwrap();
+        assert!(result.reply.is_none() && result.other_frames.is_empty());
+    }
+}
@@ -0,0 +1,667 @@
+//! PC 间转发：同时有台式机和笔记本时，让正在用的那台也能收到手机通知。
+//! 主电脑（与手机配对的那台）开启转发服务后，把手机连接收到的事件原样转发给已认证的副电脑。
+//! 转发服务监听局域网，需要用户主动开启（start_relay_source，不随重启恢复），并使用带 relay 权限的专用 API 令牌（见 api_tokens）。
+//! 副电脑用 connect_to_peer 连接主电脑，事件经正常入库流程处理：连接 id 为 relay:<主电脑 id>/<设备连接>，
+//! 通知带 relayed 标记与原设备（source_device）。副电脑上的已读、撤销已读与删除回传主电脑，主电脑应用后把删除转给手机（设备支持 dismissal 时）。
+//! 防环：转发来的事件不会再被转发（副电脑同时开启转发服务也只转发自己手机的事件），主电脑拒绝来自自己的连接。
+//! 协议为每行一个 JSON 帧（RelayFrame）：副电脑先发 hello，主电脑回 welcome 或 rejected，之后主电脑发 event、副电脑发 action。
+//! 令牌撤销后，已建立的转发连接在下一次转发或回传时断开；空闲的连接由监听循环每隔 REVALIDATE_EVERY 重新确认，几秒内断开。
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::api_tokens::{ApiScope, ApiSession, RELAY_EVENT};
+use crate::capabilities;
+use crate::commands::{AppState, ConnectionInfo};
+use crate::error_bus::BackgroundError;
+use crate::network_utils::BindError;
+use crate::ports::{PortGuard, ServerRole};
+use crate::startup::StateAccess;
+use crate::tombstones::RemovalReason;
+use crate::types::{Event, Notification};
+
+/// 转发连接的 connection_id 前缀
+pub const RELAY_PREFIX: &str = "relay:";
+/// 连接与握手的超时
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// 单帧写入超时（对端卡住时不拖住入库）
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayAction {
+    MarkRead,
+    MarkUnread,
+    Dismiss,
+}
+
+/// 转发协议的帧
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RelayFrame {
+    Hello { token: String, instance: String },
+    Welcome { instance: String },
+    Rejected { error: String },
+    Event { device: String, event: Event },
+    Action { action: RelayAction, device: String, ids: Vec<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayRole {
+    /// 从另一台电脑接收（本机是副电脑）
+    Upstream,
+    /// 转发给另一台电脑（本机是主电脑）
+    Downstream,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RelayLink {
+    pub role: RelayRole,
+    /// 对端地址
+    pub peer: String,
+    /// 对端实例（device_uuid）
+    pub instance: String,
+    pub connected_at: i64,
+    /// 已转发（Downstream）或已接收（Upstream）的事件数
+    pub events: u64,
+    /// Upstream：收到过事件的设备连接
+    pub devices: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RelayStatus {
+    /// 转发服务监听的端口（未开启时为 None）
+    pub source_port: Option<u16>,
+    pub links: Vec<RelayLink>,
+}
+
+struct Link {
+    info: RelayLink,
+    writer: TcpStream,
+    // Downstream 的令牌会话，撤销后断开
+    session: Option<ApiSession>,
+}
+
+struct Source {
+    port: u16,
+    running: Arc<AtomicBool>,
+    _guard: PortGuard,
+}
+
+#[derive(Default)]
+pub struct Relay {
+    source: Mutex<Option<Source>>,
+    links: Mutex<HashMap<u64, Link>>,
+    next_id: AtomicU64,
+    // 配置目录不可用时本次运行的实例 id
+    fallback_instance: OnceLock<String>,
+}
+
+impl Relay {
+    fn add(&self, link: Link) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.links.lock().insert(id, link);
+        id
+    }
+
+    fn remove(&self, id: u64) -> Option<RelayLink> {
+        let link = self.links.lock().remove(&id)?;
+        let _ = link.writer.shutdown(Shutdown::Both);
+        Some(link.info)
+    }
+
+    fn has_role(&self, role: RelayRole) -> bool {
+        self.links.lock().values().any(|l| l.info.role == role)
+    }
+
+    pub fn status(&self) -> RelayStatus {
+        let mut links: Vec<RelayLink> = self.links.lock().values().map(|l| l.info.clone()).collect();
+        links.sort_by(|a, b| (a.role as u8, &a.instance).cmp(&(b.role as u8, &b.instance)));
+        RelayStatus { source_port: self.source.lock().as_ref().map(|s| s.port), links }
+    }
+}
+
+/// 转发来的事件（来自转发连接或已带 relayed 标记）不再转发
+pub fn is_relayed(connection_id: &str, event: &Event) -> bool {
+    connection_id.starts_with(RELAY_PREFIX) || event.notification.as_ref().is_some_and(|n| n.relayed)
+}
+
+/// 副电脑上某个设备的连接 id
+pub fn upstream_connection_id(instance: &str, device: &str) -> String {
+    format!("{}{}/{}", RELAY_PREFIX, instance, device)
+}
+
+fn write_frame(stream: &mut impl Write, frame: &RelayFrame) -> Result<(), String> {
+    let json = serde_json::to_string(frame).map_err(|e| e.to_string())?;
+    stream.write_all(format!("{}\n", json).as_bytes()).map_err(|e| format!("Failed to send relay frame: {}", e))
+}
+
+/// 读取下一帧；对端关闭时返回 None
+fn read_frame(reader: &mut impl BufRead) -> Result<Option<RelayFrame>, String> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let n = crate::protocol::read_frame(reader, &mut line).map_err(|e| format!("Failed to read relay frame: {}", e))?;
+        if n == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            return serde_json::from_str(line.trim()).map(Some).map_err(|e| format!("Invalid relay frame: {}", e));
+        }
+    }
+}
+
+impl AppState {
+    /// 本机在转发协议中的 id：device_uuid；配置目录不可用时为本次运行的随机 id
+    pub(crate) fn relay_instance(&self) -> String {
+        self.device_uuid()
+            .unwrap_or_else(|_| self.relay.fallback_instance.get_or_init(|| uuid::Uuid::new_v4().to_string()).clone())
+    }
+
+    fn emit_relay_changed(&self) {
+        self.events.emit("relay-changed", self.relay.status());
+    }
+
+    /// 把手机连接的事件转发给所有副电脑；写入失败或令牌已撤销的连接断开
+    pub(crate) fn relay_publish(&self, connection_id: &str, event: &Event) {
+        if is_relayed(connection_id, event) || !self.relay.has_role(RelayRole::Downstream) {
+            return;
+        }
+        let frame = RelayFrame::Event { device: connection_id.to_string(), event: event.clone() };
+        let dropped: Vec<u64> = {
+            let mut links = self.relay.links.lock();
+            links
+                .iter_mut()
+                .filter(|(_, l)| l.info.role == RelayRole::Downstream)
+                .filter_map(|(id, l)| {
+                    let revoked = l.session.as_ref().is_some_and(|s| !s.permits(RELAY_EVENT) || !self.api_tokens.is_active(&s.token_id));
+                    let sent = !revoked && write_frame(&mut l.writer, &frame).is_ok();
+                    if sent {
+                        l.info.events = l.info.events.saturating_add(1);
+                    }
+                    (!sent).then_some(*id)
+                })
+                .collect()
+        };
+        for id in &dropped {
+            if let Some(link) = self.relay.remove(*id) {
+                println!("[Relay] Dropped downstream {} ({})", link.instance, link.peer);
+            }
+        }
+        if !dropped.is_empty() {
+            self.emit_relay_changed();
+        }
+    }
+
+    /// 副电脑上对转发来的通知的已读/删除，按原设备回传给主电脑
+    pub(crate) fn relay_forward_action(&self, action: RelayAction, notifications: &[Notification]) {
+        let mut by_device: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for n in notifications.iter().filter(|n| n.relayed) {
+            if let Some(device) = n.source_device.as_deref() {
+                by_device.entry(device).or_default().push(n.id.clone());
+            }
+        }
+        if by_device.is_empty() {
+            return;
+        }
+        let mut links = self.relay.links.lock();
+        for (device, ids) in by_device {
+            let link = links
+                .values_mut()
+                .find(|l| l.info.role == RelayRole::Upstream && l.info.devices.iter().any(|d| d == device));
+            let Some(link) = link else {
+                println!("[Relay] No peer for {}, {:?} of {} ids not forwarded", device, action, ids.len());
+                continue;
+            };
+            let frame = RelayFrame::Action { action, device: device.to_string(), ids };
+            if let Err(e) = write_frame(&mut link.writer, &frame) {
+                println!("[Relay] Failed to forward {:?} to {}: {}", action, link.info.peer, e);
+            }
+        }
+    }
+
+    /// 按 id 回传（mark_read 等只有 id 的入口）
+    pub(crate) fn relay_forward_ids(&self, action: RelayAction, ids: &[String]) {
+        if !self.relay.has_role(RelayRole::Upstream) {
+            return;
+        }
+        let relayed: Vec<Notification> = {
+            let store = self.store.lock().unwrap();
+            ids.iter().filter_map(|id| store.get(id)).filter(|n| n.relayed).cloned().collect()
+        };
+        self.relay_forward_action(action, &relayed);
+    }
+
+    /// 主电脑应用副电脑回传的操作；删除同时转给手机
+    fn apply_relay_action(&self, action: RelayAction, device: &str, ids: &[String]) -> Result<usize, String> {
+        match action {
+            RelayAction::MarkRead => self.mark_read_ids(ids),
+            RelayAction::MarkUnread => self.mark_unread_ids(ids),
+            RelayAction::Dismiss => {
+                crate::limits::check_ids("ids", ids, &self.payload_limits())?;
+                let removed: Vec<Notification> = {
+                    let mut store = self.store.lock().unwrap();
+                    ids.iter().filter_map(|id| store.remove(id)).collect()
+                };
+                self.on_removed(&removed, RemovalReason::DismissedOnPeer);
+                // 观察模式下暂存，关闭后确认再发出
+                let sent = self.send_or_hold(device, capabilities::DISMISSAL, serde_json::json!({ "action": "dismiss", "ids": ids }));
+                if let Err(e) = sent {
+                    println!("[Relay] Dismissal not relayed to {}: {}", device, e);
+                }
+                Ok(removed.len())
+            }
+        }
+    }
+
+    /// 校验副电脑的 hello，返回令牌会话
+    fn accept_peer(&self, token: &str, instance: &str) -> Result<ApiSession, String> {
+        let session = self.api_tokens.open_session(Some(token), ApiScope::Relay, Instant::now())?;
+        if instance == self.relay_instance() {
+            return Err("Cannot relay to this instance itself".to_string());
+        }
+        Ok(session)
+    }
+
+    /// 重新确认副电脑的令牌（间隔见 REVALIDATE_EVERY），已撤销的连接断开；空闲的连接也因此在几秒内关闭
+    fn revalidate_relay_links(&self, now: Instant) {
+        let revoked: Vec<u64> = self
+            .relay
+            .links
+            .lock()
+            .iter_mut()
+            .filter_map(|(id, l)| l.session.as_mut().is_some_and(|s| !s.still_valid(&self.api_tokens, now)).then_some(*id))
+            .collect();
+        for id in &revoked {
+            if let Some(link) = self.relay.remove(*id) {
+                println!("[Relay] Closed downstream {} ({}): token revoked", link.instance, link.peer);
+            }
+        }
+        if !revoked.is_empty() {
+            self.emit_relay_changed();
+        }
+    }
+
+    /// 接收主电脑的事件直到连接关闭
+    fn consume_peer(&self, id: u64, instance: &str, reader: &mut impl BufRead) -> Result<(), String> {
+        while let Some(frame) = read_frame(reader)? {
+            let RelayFrame::Event { device, mut event } = frame else {
+                continue;
+            };
+            // 对端不会转发转发来的事件；万一收到也丢弃
+            if device.starts_with(RELAY_PREFIX) || event.notification.as_ref().is_some_and(|n| n.relayed) {
+                continue;
+            }
+            if let Some(n) = event.notification.as_mut() {
+                n.relayed = true;
+                n.source_device = Some(device.clone());
+            }
+            if let Some(link) = self.relay.links.lock().get_mut(&id) {
+                link.info.events = link.info.events.saturating_add(1);
+                if !link.info.devices.contains(&device) {
+                    link.info.devices.push(device.clone());
+                }
+            }
+            self.ingest_from(&upstream_connection_id(instance, &device), event);
+        }
+        Ok(())
+    }
+
+    /// list_connections 中的转发连接：副电脑按设备列出，主电脑按副电脑列出
+    pub(crate) fn relay_connections(&self, now: i64, waiting_for_network: bool) -> Vec<ConnectionInfo> {
+        let info = |connection_id: String, link: &RelayLink| ConnectionInfo {
+            link_quality: self.link_quality.quality(&connection_id, now),
+            mirroring_enabled: !self.is_mirroring_paused(&connection_id),
+            connection_id,
+            protocol_version: None,
+            clock_offset_ms: None,
+            capabilities: None,
+            protocol_errors: Default::default(),
+            low_power: None,
+            waiting_for_network,
+            relay: Some(link.clone()),
+            watch_only: self.watch_only.is_enabled(),
+        };
+        self.relay
+            .status()
+            .links
+            .iter()
+            .flat_map(|link| match link.role {
+                RelayRole::Upstream => link
+                    .devices
+                    .iter()
+                    .map(|d| info(upstream_connection_id(&link.instance, d), link))
+                    .collect::<Vec<_>>(),
+                RelayRole::Downstream => vec![info(format!("{}{}", RELAY_PREFIX, link.instance), link)],
+            })
+            .collect()
+    }
+
+    /// 断开与某个实例的转发连接（两个方向），返回断开的条数
+    pub fn disconnect_relay_peer(&self, instance: &str) -> usize {
+        let ids: Vec<u64> = self.relay.links.lock().iter().filter(|(_, l)| l.info.instance == instance).map(|(id, _)| *id).collect();
+        let n = ids.into_iter().filter_map(|id| self.relay.remove(id)).count();
+        if n > 0 {
+            println!("[Relay] Disconnected {} link(s) with {}", n, instance);
+            self.emit_relay_changed();
+        }
+        n
+    }
+
+    /// 关闭转发服务并断开所有副电脑
+    pub fn stop_relay_source(&self) -> bool {
+        let Some(source) = self.relay.source.lock().take() else {
+            return false;
+        };
+        source.running.store(false, Ordering::SeqCst);
+        let ids: Vec<u64> = self
+            .relay
+            .links
+            .lock()
+            .iter()
+            .filter(|(_, l)| l.info.role == RelayRole::Downstream)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.relay.remove(id);
+        }
+        println!("[Relay] Source on port {} stopped", source.port);
+        drop(source);
+        self.emit_relay_changed();
+        true
+    }
+}
+
+/// 开启转发服务（监听局域网），返回实际端口
+pub fn start_source(handle: impl StateAccess + Clone, port: u16) -> Result<u16, String> {
+    let state = handle.app_state();
+    let mut source = state.relay.source.lock();
+    if let Some(s) = source.as_ref() {
+        return Err(format!("Relay source is already running on port {}", s.port));
+    }
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| BindError::from_io(port, &e).to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let guard = state.ports.register(ServerRole::RelaySource, port, "0.0.0.0").map_err(|e| e.to_string())?;
+    listener.set_nonblocking(true).map_err(|e| format!("Failed to set nonblocking: {}", e))?;
+    let running = Arc::new(AtomicBool::new(true));
+    *source = Some(Source { port, running: running.clone(), _guard: guard });
+    drop(source);
+    println!("[Relay] Source listening on 0.0.0.0:{}", port);
+    state.emit_relay_changed();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            if !running.load(Ordering::SeqCst) {
+                break;
+            }
+            handle.app_state().revalidate_relay_links(Instant::now());
+            match stream {
+                Ok(stream) => {
+                    let handle = handle.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve_downstream(stream, handle) {
+                            println!("[Relay] Downstream closed: {}", e);
+                        }
+                    });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(100)),
+                Err(e) => println!("[Relay] Accept error: {}", e),
+            }
+        }
+        println!("[Relay] Source listener finished");
+    });
+    Ok(port)
+}
+
+/// 主电脑一侧：握手后登记为 Downstream，之后处理副电脑回传的操作
+fn serve_downstream(stream: TcpStream, handle: impl StateAccess) -> Result<(), String> {
+    let state = handle.app_state();
+    stream.set_nonblocking(false).map_err(|e| format!("Failed to set blocking: {}", e))?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT)).map_err(|e| e.to_string())?;
+    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    let mut writer = stream.try_clone().map_err(|e| format!("Failed to clone stream: {}", e))?;
+    let mut reader = BufReader::new(stream);
+
+    let accepted = match read_frame(&mut reader)? {
+        Some(RelayFrame::Hello { token, instance }) => state.accept_peer(&token, &instance).map(|t| (t, instance)),
+        _ => Err("Expected hello".to_string()),
+    };
+    let (session, instance) = match accepted {
+        Ok(accepted) => accepted,
+        Err(error) => {
+            let _ = write_frame(&mut writer, &RelayFrame::Rejected { error: error.clone() });
+            return Err(error);
+        }
+    };
+    write_frame(&mut writer, &RelayFrame::Welcome { instance: state.relay_instance() })?;
+    reader.get_ref().set_read_timeout(None).map_err(|e| e.to_string())?;
+
+    let info = RelayLink {
+        role: RelayRole::Downstream,
+        peer: peer.clone(),
+        instance: instance.clone(),
+        connected_at: chrono::Utc::now().timestamp(),
+        events: 0,
+        devices: Vec::new(),
+    };
+    let token_id = session.token_id.clone();
+    let id = state.relay.add(Link { info, writer, session: Some(session) });
+    println!("[Relay] Downstream {} connected from {}", instance, peer);
+    state.emit_relay_changed();
+
+    let result = loop {
+        match read_frame(&mut reader) {
+            Ok(Some(RelayFrame::Action { action, device, ids })) => {
+                if !state.api_tokens.is_active(&token_id) {
+                    break Err("Relay token was revoked".to_string());
+                }
+                match state.apply_relay_action(action, &device, &ids) {
+                    Ok(n) => println!("[Relay] {:?} from {}: {} of {} ids", action, instance, n, ids.len()),
+                    Err(e) => println!("[Relay] {:?} from {} failed: {}", action, instance, e),
+                }
+            }
+            Ok(Some(_)) => continue,
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+    };
+    if state.relay.remove(id).is_some() {
+        state.emit_relay_changed();
+    }
+    result
+}
+
+/// 副电脑一侧：连接主电脑的转发服务，握手后在后台接收事件
+pub fn connect_to_peer(handle: impl StateAccess + Clone, host: &str, port: u16, token: &str) -> Result<RelayLink, String> {
+    let state = handle.app_state();
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Invalid peer address {}:{}: {}", host, port, e))?
+        .next()
+        .ok_or_else(|| format!("Invalid peer address {}:{}", host, port))?;
+    let stream = TcpStream::connect_timeout(&addr, HANDSHAKE_TIMEOUT)
+        .map_err(|e| format!("Failed to connect to {}:{}: {}", host, port, e))?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT)).map_err(|e| e.to_string())?;
+    let mut writer = stream.try_clone().map_err(|e| format!("Failed to clone stream: {}", e))?;
+    let own = state.relay_instance();
+    write_frame(&mut writer, &RelayFrame::Hello { token: token.to_string(), instance: own.clone() })?;
+    let mut reader = BufReader::new(stream);
+    let instance = match read_frame(&mut reader)? {
+        Some(RelayFrame::Welcome { instance }) => instance,
+        Some(RelayFrame::Rejected { error }) => return Err(format!("Peer rejected relay: {}", error)),
+        _ => return Err("Unexpected reply from peer".to_string()),
+    };
+    if instance == own {
+        return Err("Cannot relay from this instance itself".to_string());
+    }
+    if state.relay.links.lock().values().any(|l| l.info.role == RelayRole::Upstream && l.info.instance == instance) {
+        return Err(format!("Already receiving from {}", instance));
+    }
+    reader.get_ref().set_read_timeout(None).map_err(|e| e.to_string())?;
+
+    let info = RelayLink {
+        role: RelayRole::Upstream,
+        peer: format!("{}:{}", host, port),
+        instance: instance.clone(),
+        connected_at: chrono::Utc::now().timestamp(),
+        events: 0,
+        devices: Vec::new(),
+    };
+    let id = state.relay.add(Link { info: info.clone(), writer, session: None });
+    println!("[Relay] Receiving from {} at {}", instance, info.peer);
+    state.emit_relay_changed();
+
+    std::thread::spawn(move || {
+        let state = handle.app_state();
+        if let Err(e) = state.consume_peer(id, &instance, &mut reader) {
+            println!("[Relay] Upstream {} closed: {}", instance, e);
+            state.report_error(BackgroundError::new("relay", "upstream_closed", e).device(instance.clone()));
+        }
+        if state.relay.remove(id).is_some() {
+            state.emit_relay_changed();
+        }
+    });
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn wait_for(what: &str, mut f: impl FnMut() -> bool) {
+        let started = Instant::now();
+        while !f() {
+            assert!(started.elapsed() < Duration::from_secs(5), "timed out waiting for {}", what);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn added(id: &str, title: &str) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 1,
+            notification: Some(Notification { id: id.into(), title: Some(title.into()), ..Default::default() }),
+            id: None,
+        }
+    }
+
+    fn instance() -> Arc<AppState> {
+        let state = Arc::new(AppState::default());
+        state.mark_ready(crate::startup::Subsystem::Store);
+        state
+    }
+
+    fn downstream_instances(state: &AppState) -> BTreeSet<String> {
+        state.relay.status().links.into_iter().filter(|l| l.role == RelayRole::Downstream).map(|l| l.instance).collect()
+    }
+
+    fn relay_token(state: &AppState) -> String {
+        state.create_api_token("laptop", vec![ApiScope::Relay]).unwrap().token
+    }
+
+    #[test]
+    fn test_relay_between_two_instances() {
+        let (desktop, laptop) = (instance(), instance());
+        let port = start_source(desktop.clone(), 0).unwrap();
+        let token = relay_token(&desktop);
+
+        let link = connect_to_peer(laptop.clone(), "127.0.0.1", port, &token).unwrap();
+        assert_eq!((link.role, link.instance.clone()), (RelayRole::Upstream, desktop.relay_instance()));
+        wait_for("downstream", || downstream_instances(&desktop).contains(&laptop.relay_instance()));
+
+        // 手机事件经主电脑转发，副电脑按正常流程入库并带上标记
+        desktop.ingest_from("pixel", added("n1", "hello"));
+        desktop.ingest_from("pixel", added("n2", "world"));
+        wait_for("relayed events", || laptop.store.lock().unwrap().get("n2").is_some());
+        let n1 = laptop.store.lock().unwrap().get("n1").cloned().unwrap();
+        assert!(n1.relayed);
+        assert_eq!(n1.source_device.as_deref(), Some("pixel"));
+        assert!(!desktop.store.lock().unwrap().get("n1").unwrap().relayed);
+
+        let connections = laptop.relay_connections(0, false);
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].connection_id, upstream_connection_id(&desktop.relay_instance(), "pixel"));
+        assert_eq!(connections[0].relay.as_ref().unwrap().events, 2);
+        assert_eq!(desktop.relay_connections(0, false)[0].relay.as_ref().unwrap().role, RelayRole::Downstream);
+
+        // 副电脑的已读、撤销已读与删除回传主电脑
+        laptop.mark_read_ids(&["n1".to_string()]).unwrap();
+        wait_for("mark read", || desktop.store.lock().unwrap().get("n1").is_some_and(|n| n.read));
+        laptop.mark_unread_ids(&["n1".to_string()]).unwrap();
+        wait_for("mark unread", || desktop.store.lock().unwrap().get("n1").is_some_and(|n| !n.read));
+        let removed = laptop.store.lock().unwrap().remove("n2").unwrap();
+        laptop.on_removed(&[removed], RemovalReason::UserDeletedLocal);
+        wait_for("dismiss", || desktop.store.lock().unwrap().get("n2").is_none());
+        assert_eq!(desktop.removal_log.lock().unwrap().recent(1)[0].reason, RemovalReason::DismissedOnPeer);
+
+        // 撤销令牌后下一次转发时断开
+        let token_id = desktop.list_api_tokens()[0].id.clone();
+        desktop.revoke_api_token(&token_id).unwrap();
+        desktop.ingest_from("pixel", added("n3", "late"));
+        assert!(downstream_instances(&desktop).is_empty());
+        wait_for("upstream closed", || laptop.relay.status().links.is_empty());
+
+        // 空闲的连接：没有转发也在 REVALIDATE_EVERY 之后断开
+        let token = relay_token(&desktop);
+        connect_to_peer(laptop.clone(), "127.0.0.1", port, &token).unwrap();
+        wait_for("downstream again", || !downstream_instances(&desktop).is_empty());
+        let token_id = desktop.list_api_tokens().into_iter().find(|t| t.revoked_at.is_none()).unwrap().id;
+        desktop.revoke_api_token(&token_id).unwrap();
+        wait_for("idle link closed", || downstream_instances(&desktop).is_empty());
+        wait_for("idle upstream closed", || laptop.relay.status().links.is_empty());
+        assert!(desktop.stop_relay_source());
+    }
+
+    #[test]
+    fn test_relayed_events_are_not_relayed_again() {
+        let (desktop, laptop) = (instance(), instance());
+        let desktop_port = start_source(desktop.clone(), 0).unwrap();
+        let laptop_port = start_source(laptop.clone(), 0).unwrap();
+        // 两台电脑互相接收
+        connect_to_peer(laptop.clone(), "127.0.0.1", desktop_port, &relay_token(&desktop)).unwrap();
+        connect_to_peer(desktop.clone(), "127.0.0.1", laptop_port, &relay_token(&laptop)).unwrap();
+        wait_for("both downstreams", || !downstream_instances(&desktop).is_empty() && !downstream_instances(&laptop).is_empty());
+
+        desktop.ingest_from("pixel", added("n1", "from desktop phone"));
+        wait_for("relayed", || laptop.store.lock().unwrap().get("n1").is_some());
+        // 入库前已决定不转发：笔记本没有向台式机转发任何事件
+        let laptop_out = laptop.relay.status().links.into_iter().find(|l| l.role == RelayRole::Downstream).unwrap();
+        assert_eq!(laptop_out.events, 0);
+
+        // 笔记本自己手机的事件照常转发给台式机
+        laptop.ingest_from("galaxy", added("g1", "from laptop phone"));
+        wait_for("reverse", || desktop.store.lock().unwrap().get("g1").is_some_and(|n| n.relayed));
+        assert!(!is_relayed("galaxy", &added("g1", "x")));
+        assert!(is_relayed(&upstream_connection_id("a", "pixel"), &added("g1", "x")));
+        desktop.stop_relay_source();
+        laptop.stop_relay_source();
+    }
+
+    #[test]
+    fn test_rejected_handshakes() {
+        let desktop = instance();
+        let port = start_source(desktop.clone(), 0).unwrap();
+        let err = connect_to_peer(instance(), "127.0.0.1", port, "dnl_wrong").unwrap_err();
+        assert!(err.contains("invalid_token"), "{}", err);
+        let read_only = desktop.create_api_token("deck", vec![ApiScope::ReadNotifications]).unwrap().token;
+        assert!(connect_to_peer(instance(), "127.0.0.1", port, &read_only).unwrap_err().contains("insufficient_scope"));
+        // 连接自己
+        let err = connect_to_peer(desktop.clone(), "127.0.0.1", port, &relay_token(&desktop)).unwrap_err();
+        assert!(err.contains("itself"), "{}", err);
+        assert!(start_source(desktop.clone(), port).unwrap_err().contains("already running"));
+        assert!(desktop.stop_relay_source());
+        assert!(!desktop.stop_relay_source());
+    }
+}
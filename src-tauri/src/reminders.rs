@@ -0,0 +1,293 @@
+//! 桌面端本地提醒：以 local.reminder 包名写入通知列表，可暂缓到指定时间再出现。
+//! 手机通知同样可以暂缓（snooze_ids）：暂缓期间不出现在列表与计数中，删除即取消。
+//! 到期检查由维护调度器（maintenance）驱动，到期时作为未读入库并弹出系统通知。
+//! 本地条目单独保存到 reminders.json，重启后恢复。
+
+use crate::commands::AppState;
+use crate::limits;
+use crate::privacy::Surface;
+use crate::templates::TemplateTarget;
+use crate::tombstones::RemovalReason;
+use crate::types::Notification;
+
+pub const LOCAL_PACKAGE: &str = "local.reminder";
+pub(crate) const REMINDERS_FILE: &str = "reminders.json";
+/// 系统默认提示音
+pub(crate) const TOAST_SOUND: &str = "default";
+
+impl AppState {
+    /// 创建本地通知；remind_at 在未来时先暂缓，到期后再出现
+    pub fn create_local_notification(
+        &self,
+        title: String,
+        text: String,
+        remind_at: Option<i64>,
+        now: i64,
+    ) -> Result<Notification, String> {
+        let max_text = self.payload_limits().max_text_bytes;
+        limits::check_bytes("title", title.len(), max_text)?;
+        limits::check_bytes("text", text.len(), max_text)?;
+        if title.trim().is_empty() && text.trim().is_empty() {
+            return Err("title and text must not both be empty".to_string());
+        }
+        let mut n = Notification {
+            id: format!("local-{}", uuid::Uuid::new_v4()),
+            package_name: Some(LOCAL_PACKAGE.to_string()),
+            title: Some(title),
+            text: Some(text),
+            posted_at: Some(remind_at.unwrap_or(now).max(now)),
+            local: true,
+            ..Default::default()
+        };
+        crate::derived_upgrade::fill_language(&self.settings.read(), &mut n);
+
+        let stored = {
+            let mut store = self.store.lock().unwrap();
+            match remind_at {
+                Some(at) if at > now => {
+                    store.snooze(n.clone(), at);
+                    None
+                }
+                _ => {
+                    store.upsert(n.clone(), false);
+                    store.get(&n.id).cloned()
+                }
+            }
+        };
+        match stored {
+            Some(n) => {
+                self.events.emit("notification-added", n);
+                self.emit_counts();
+            }
+            None => println!("[Reminders] {} scheduled at {:?}", n.id, remind_at),
+        }
+        self.save_local_notifications();
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .get(&n.id)
+            .cloned()
+            .or_else(|| store.snoozed().into_iter().find(|s| s.id == n.id))
+            .unwrap_or(n))
+    }
+
+    /// 所有本地条目：未到期的在前（按提醒时间），已出现的按时间新 -> 旧
+    pub fn list_local_reminders(&self) -> Vec<Notification> {
+        let store = self.store.lock().unwrap();
+        let mut list: Vec<Notification> = store.snoozed().into_iter().filter(|n| n.local).collect();
+        list.extend(store.query(crate::store::SortMode::Newest, |n| n.local, 0, None));
+        list
+    }
+
+    /// 取消（删除）一条本地条目；不是本地条目时返回错误
+    pub fn cancel_local_reminder(&self, id: &str) -> Result<bool, String> {
+        let removed = {
+            let mut store = self.store.lock().unwrap();
+            let is_local = store.get(id).map(|n| n.local)
+                .or_else(|| store.snoozed().iter().find(|n| n.id == id).map(|n| n.local));
+            match is_local {
+                None => return Ok(false),
+                Some(false) => return Err(format!("{} is not a local notification", id)),
+                Some(true) => store.remove(id),
+            }
+        };
+        if let Some(n) = removed {
+            self.on_removed(&[n], RemovalReason::UserDeletedLocal);
+        }
+        Ok(true)
+    }
+
+    /// 暂缓通知到 until（再次暂缓只更新时间），返回暂缓的条数
+    pub fn snooze_ids(&self, ids: &[String], until: i64, now: i64) -> Result<usize, String> {
+        limits::check_ids("ids", ids, &self.payload_limits())?;
+        if until <= now {
+            return Err("until must be in the future".to_string());
+        }
+        let snoozed = self.store.lock().unwrap().snooze_ids(ids, until);
+        if snoozed.is_empty() {
+            return Ok(0);
+        }
+        let ids: Vec<&str> = snoozed.iter().map(|n| n.id.as_str()).collect();
+        println!("[Reminders] Snoozed {} items until {}", ids.len(), until);
+        self.events.emit("notifications-snoozed", serde_json::json!({ "ids": ids, "until": until }));
+        self.emit_counts();
+        if snoozed.iter().any(|n| n.local) {
+            self.save_local_notifications();
+        }
+        Ok(snoozed.len())
+    }
+
+    /// 到期检查：到期的暂缓通知作为未读入库、推送事件并弹出系统通知
+    pub fn wake_due(&self, now: i64) -> Vec<Notification> {
+        let retention = self.settings.read().retention.clone();
+        // 唤醒等同于重新入库，同样受保留上限约束
+        let (woken, eviction) = {
+            let mut store = self.store.lock().unwrap();
+            let woken = store.wake_due(now);
+            let eviction = store.enforce_retention(&retention);
+            (woken, eviction)
+        };
+        if !eviction.removed.is_empty() {
+            self.on_removed(&eviction.removed, RemovalReason::RetentionEvicted);
+        }
+        for n in &woken {
+            println!("[Reminders] {} is due", n.id);
+            self.events.emit("notification-added", n.clone());
+            self.show_toast(n, false);
+        }
+        if !woken.is_empty() {
+            self.emit_counts();
+        }
+        if woken.iter().any(|n| n.local) {
+            self.save_local_notifications();
+        }
+        woken
+    }
+
+    /// 弹出系统通知（重要性提醒也走这里）
+    pub(crate) fn show_toast(&self, n: &Notification, sound: bool) {
+        self.show_toast_with_sound(n, sound.then_some(TOAST_SOUND));
+    }
+
+    /// 系统通知显示的标题与正文：按隐私级别处理，设置了模板时用模板渲染
+    pub(crate) fn toast_content(&self, n: &Notification) -> (String, Option<String>) {
+        let preview = crate::privacy::preview_for(&self.settings.read().privacy, n, Surface::Toast);
+        let title = self.render_template(TemplateTarget::ToastTitle, n).unwrap_or(preview.title);
+        let body = self.render_template(TemplateTarget::ToastBody, n).or(preview.body);
+        (title, body)
+    }
+
+    pub(crate) fn show_toast_with_sound(&self, n: &Notification, sound: Option<&str>) {
+        use tauri_plugin_notification::NotificationExt;
+        let Some(app) = self.events.app() else {
+            return;
+        };
+        let (title, body) = self.toast_content(n);
+        let mut builder = app.notification().builder().title(title).body(body.unwrap_or_default());
+        if let Some(sound) = sound {
+            builder = builder.sound(sound);
+        }
+        let result = builder.show();
+        if let Err(e) = result {
+            println!("[Reminders] Failed to show toast: {}", e);
+        }
+    }
+
+    /// 保存全部本地条目（含暂缓中的）
+    pub(crate) fn save_local_notifications(&self) {
+        let list = self.list_local_reminders();
+        if let Err(e) = self.storage.save(REMINDERS_FILE, &list) {
+            println!("[Storage] {}", e);
+        }
+    }
+
+    /// 启动时恢复本地条目
+    pub(crate) fn load_local_notifications(&self) {
+        let Some(list) = self.storage.load::<Vec<Notification>>(REMINDERS_FILE) else {
+            return;
+        };
+        let mut store = self.store.lock().unwrap();
+        for n in list.into_iter().filter(|n| n.local) {
+            let (read, pinned, id) = (n.read, n.pinned, n.id.clone());
+            match n.snoozed_until {
+                Some(at) => store.snooze(n, at),
+                None => {
+                    store.upsert(n, read);
+                    if pinned {
+                        store.set_pinned(std::slice::from_ref(&id), true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reminder_hidden_until_due_then_unread() {
+        let state = AppState::default();
+        let n = state
+            .create_local_notification("喝水".into(), "起来走走".into(), Some(1_000), 100)
+            .unwrap();
+        assert!(n.local);
+        assert_eq!(n.snoozed_until, Some(1_000));
+        assert_eq!(state.counts().total, 0);
+        assert_eq!(state.list_local_reminders().len(), 1);
+
+        assert!(state.wake_due(999).is_empty());
+        let woken = state.wake_due(1_000);
+        assert_eq!(woken.len(), 1);
+        assert!(!woken[0].read);
+        assert_eq!(state.counts().unread, 1);
+    }
+
+    fn phone(id: &str, text: &str) -> crate::types::Event {
+        crate::types::Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: id.into(), title: Some("快递".into()), text: Some(text.into()), ..Default::default() }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_snooze_phone_notifications() {
+        let state = AppState::default();
+        state.ingest_event(phone("p1", "已发货"));
+        state.ingest_event(phone("p2", "派送中"));
+        state.store.lock().unwrap().mark_read(&["p1".to_string()]);
+        state.events.take_captured();
+
+        assert!(state.snooze_ids(&["p1".to_string()], 100, 100).is_err());
+        let ids = ["p1".to_string(), "p2".to_string(), "ghost".to_string()];
+        assert_eq!(state.snooze_ids(&ids, 1_000, 100), Ok(2));
+        assert_eq!((state.counts().unread, state.counts().total), (0, 0));
+        let events = state.events.take_captured();
+        assert_eq!(events[0].0, "notifications-snoozed");
+        assert_eq!(events[0].1["ids"], serde_json::json!(["p1", "p2"]));
+
+        // 再次暂缓只更新时间；暂缓期间手机端的更新不会让它提前出现
+        assert_eq!(state.snooze_ids(&["p1".to_string()], 2_000, 100), Ok(1));
+        state.ingest_event(crate::types::Event { event_type: "updated".into(), ..phone("p1", "已到达驿站") });
+        assert_eq!(state.counts().total, 0);
+        assert!(state.store.lock().unwrap().integrity_issues().is_empty());
+
+        // 删除即取消
+        state.store.lock().unwrap().remove("p2");
+        assert!(state.wake_due(1_000).is_empty());
+        state.events.take_captured();
+        let woken = state.wake_due(2_000);
+        assert_eq!(woken.len(), 1);
+        assert_eq!((woken[0].id.as_str(), woken[0].text.as_deref(), woken[0].read), ("p1", Some("已到达驿站"), false));
+        assert_eq!(state.counts().unread, 1);
+        assert!(state.events.take_captured().iter().any(|(name, _)| name == "notification-added"));
+    }
+
+    #[test]
+    fn test_cancel_only_local_and_persisted() {
+        let dir = crate::storage::temp_dir("reminders");
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        let a = state.create_local_notification("a".into(), "".into(), None, 100).unwrap();
+        let b = state.create_local_notification("b".into(), "".into(), Some(500), 100).unwrap();
+        state.ingest_event(crate::types::Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: "phone".into(), ..Default::default() }),
+            id: None,
+        });
+        assert!(state.cancel_local_reminder("phone").is_err());
+        assert_eq!(state.cancel_local_reminder("ghost"), Ok(false));
+
+        let restarted = AppState::default();
+        restarted.init_storage(dir.clone());
+        let ids: Vec<String> = restarted.list_local_reminders().into_iter().map(|n| n.id).collect();
+        assert_eq!(ids, [b.id.clone(), a.id.clone()]);
+
+        assert_eq!(restarted.cancel_local_reminder(&b.id), Ok(true));
+        assert_eq!(restarted.list_local_reminders().len(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
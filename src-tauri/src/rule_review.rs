@@ -0,0 +1,275 @@
+//! 规则命中统计与“最近被过滤”复查列表：过滤规则写得激进时，用户需要确认没有误丢重要通知。
+//! 每条规则累计命中次数与最近命中时间，随规则一起保存在设置里（重启后仍在）；命中时只在内存中计数，
+//! 由维护任务与正常退出时落盘，避免每条通知都写一次设置文件。
+//! 被规则丢弃或静音的通知另存一份到有界复查列表（最近 REVIEW_CAPACITY 条），只展示按隐私设置处理、截断后的摘要；
+//! 列表不是通知：不计数、不参与搜索与导出，清空历史时一并清空，也不持久化。
+//! 复查列表中的条目可以恢复（绕过命中的那条规则重新入库），也可以直接停用那条规则。
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::{AppState, SETTINGS_FILE};
+use crate::ingest::IngestOutcome;
+use crate::privacy::{self, Preview, Surface};
+use crate::rules::{Rule, RuleAction};
+use crate::types::Notification;
+
+/// 复查列表保留的条数
+pub const REVIEW_CAPACITY: usize = 200;
+/// 摘要正文保留的字符数
+const SUMMARY_CHARS: usize = 120;
+
+/// 单条规则的命中统计（保存在 Settings::rule_hits）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RuleHits {
+    pub hits: u64,
+    pub last_hit_at: Option<i64>,
+}
+
+pub type RuleHitMap = BTreeMap<String, RuleHits>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RuleStats {
+    pub rule_id: String,
+    pub enabled: bool,
+    pub action: RuleAction,
+    pub hits: u64,
+    pub last_hit_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FilteredEntry {
+    pub entry_id: String,
+    pub rule_id: String,
+    pub action: RuleAction,
+    pub package_name: Option<String>,
+    /// 按隐私设置处理并截断后的摘要
+    pub summary: Preview,
+    pub filtered_at: i64,
+}
+
+struct Held {
+    entry: FilteredEntry,
+    // 恢复时重新入库用，不对外返回
+    notification: Notification,
+}
+
+#[derive(Default)]
+pub struct RuleReview {
+    entries: Mutex<VecDeque<Held>>,
+    next_id: AtomicU64,
+    hits_dirty: AtomicBool,
+}
+
+impl RuleReview {
+    fn push(&self, entry: FilteredEntry, notification: Notification) {
+        let mut entries = self.entries.lock();
+        if entries.len() >= REVIEW_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(Held { entry, notification });
+    }
+
+    /// 最近被过滤的条目（新 -> 旧）
+    pub fn recent(&self, limit: usize) -> Vec<FilteredEntry> {
+        self.entries.lock().iter().rev().take(limit).map(|h| h.entry.clone()).collect()
+    }
+
+    fn take(&self, entry_id: &str) -> Option<Held> {
+        let mut entries = self.entries.lock();
+        let pos = entries.iter().position(|h| h.entry.entry_id == entry_id)?;
+        entries.remove(pos)
+    }
+
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock();
+        let n = entries.len();
+        entries.clear();
+        n
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+}
+
+fn summarize(mut preview: Preview) -> Preview {
+    if let Some(body) = preview.body.as_mut() {
+        if body.chars().count() > SUMMARY_CHARS {
+            *body = body.chars().take(SUMMARY_CHARS).chain(std::iter::once('…')).collect();
+        }
+    }
+    preview
+}
+
+impl AppState {
+    /// 入库时规则命中：计数，并把通知摘要放入复查列表
+    pub(crate) fn note_rule_hit(&self, rule: &Rule, n: &Notification, now: i64) {
+        let summary = {
+            let mut settings = self.settings.write();
+            let hits = settings.rule_hits.entry(rule.id.clone()).or_default();
+            hits.hits += 1;
+            hits.last_hit_at = Some(now);
+            summarize(privacy::preview_for(&settings.privacy, n, Surface::Popout))
+        };
+        self.rule_review.hits_dirty.store(true, Ordering::Relaxed);
+        let entry = FilteredEntry {
+            entry_id: format!("f{}", self.rule_review.next_id.fetch_add(1, Ordering::Relaxed) + 1),
+            rule_id: rule.id.clone(),
+            action: rule.action,
+            package_name: n.package_name.clone(),
+            summary,
+            filtered_at: now,
+        };
+        self.rule_review.push(entry, n.clone());
+    }
+
+    /// 各规则（按规则顺序）的命中统计
+    pub fn rule_stats(&self) -> Vec<RuleStats> {
+        let settings = self.settings.read();
+        settings
+            .rules
+            .iter()
+            .map(|r| {
+                let hits = settings.rule_hits.get(&r.id).cloned().unwrap_or_default();
+                RuleStats { rule_id: r.id.clone(), enabled: r.enabled, action: r.action, hits: hits.hits, last_hit_at: hits.last_hit_at }
+            })
+            .collect()
+    }
+
+    /// 命中计数有变化时写入设置文件（维护任务与退出时调用）
+    pub fn persist_rule_hits(&self) -> Result<String, String> {
+        if !self.rule_review.hits_dirty.swap(false, Ordering::Relaxed) {
+            return Ok(String::new());
+        }
+        let settings = self.settings.read().clone();
+        if let Err(e) = self.storage.save(SETTINGS_FILE, &settings) {
+            self.rule_review.hits_dirty.store(true, Ordering::Relaxed);
+            return Err(e);
+        }
+        Ok(format!("{} rules", settings.rule_hits.len()))
+    }
+
+    /// 把复查列表中的条目作为普通通知重新入库，绕过当初命中的规则（其他规则照常生效）
+    pub fn restore_filtered(&self, entry_id: &str) -> Result<IngestOutcome, String> {
+        let held = self.rule_review.take(entry_id).ok_or_else(|| format!("Filtered entry {} not found", entry_id))?;
+        let outcome = self.ingest_bypassing(held.notification, &held.entry.rule_id);
+        self.audit(
+            "restore_filtered",
+            AuditSource::Command,
+            1,
+            serde_json::json!({ "rule_id": held.entry.rule_id, "package": held.entry.package_name }),
+        );
+        println!("[RuleReview] Restored {} (rule {}) -> {:?}", entry_id, held.entry.rule_id, outcome);
+        Ok(outcome)
+    }
+
+    /// 停用规则（复查界面的快捷操作），返回停用后的规则
+    pub fn disable_rule(&self, rule_id: &str) -> Result<Rule, String> {
+        let (rule, settings) = {
+            let mut settings = self.settings.write();
+            let rule = settings.rules.iter_mut().find(|r| r.id == rule_id).ok_or_else(|| format!("Rule {} not found", rule_id))?;
+            rule.enabled = false;
+            let rule = rule.clone();
+            (rule, settings.clone())
+        };
+        self.storage.save(SETTINGS_FILE, &settings)?;
+        self.events.emit("rules-changed", &settings.rules);
+        println!("[RuleReview] Disabled rule {}", rule_id);
+        Ok(rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    fn rule(id: &str, pattern: &str, action: RuleAction) -> Rule {
+        Rule { id: id.into(), enabled: true, package: None, channel: None, pattern: Some(pattern.into()), language: None, action }
+    }
+
+    fn added(id: &str, text: &str) -> Event {
+        Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: id.into(),
+                package_name: Some("com.shop".into()),
+                title: Some("Shop".into()),
+                text: Some(text.into()),
+                ..Default::default()
+            }),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_hits_and_review_buffer() {
+        let state = AppState::default();
+        state.settings.write().rules = vec![rule("promo", "sale", RuleAction::Drop), rule("quiet", "digest", RuleAction::Mute)];
+        state.ingest_event(added("a", "big sale today"));
+        state.ingest_event(added("b", "weekly digest"));
+        state.ingest_event(added("c", "order shipped"));
+
+        let stats = state.rule_stats();
+        assert_eq!((stats[0].rule_id.as_str(), stats[0].hits), ("promo", 1));
+        assert_eq!(stats[1].hits, 1);
+        let review = state.rule_review.recent(10);
+        assert_eq!(review.len(), 2);
+        assert_eq!((review[0].rule_id.as_str(), review[0].action), ("quiet", RuleAction::Mute));
+        assert_eq!(review[1].summary.body.as_deref(), Some("big sale today"));
+        // 复查列表不是通知：被丢弃的不入库
+        assert_eq!(state.counts().total, 2);
+
+        for i in 0..REVIEW_CAPACITY + 5 {
+            state.ingest_event(added(&format!("s{}", i), &format!("sale {} {}", i, "x".repeat(200))));
+        }
+        assert_eq!(state.rule_review.len(), REVIEW_CAPACITY);
+        assert_eq!(state.rule_stats()[0].hits, REVIEW_CAPACITY as u64 + 6);
+        let newest = &state.rule_review.recent(1)[0];
+        assert_eq!(newest.summary.body.as_ref().unwrap().chars().count(), SUMMARY_CHARS + 1);
+    }
+
+    #[test]
+    fn test_restore_bypasses_matched_rule() {
+        let state = AppState::default();
+        state.settings.write().rules = vec![rule("promo", "sale", RuleAction::Drop)];
+        state.ingest_event(added("a", "big sale today"));
+        let entry = state.rule_review.recent(1)[0].clone();
+
+        assert_eq!(state.restore_filtered(&entry.entry_id).unwrap(), IngestOutcome::Stored);
+        assert!(state.store.lock().unwrap().get("a").is_some());
+        assert_eq!(state.rule_review.len(), 0);
+        assert_eq!(state.audit.recent(1)[0].operation, "restore_filtered");
+        assert!(state.restore_filtered(&entry.entry_id).is_err());
+        // 规则仍然生效
+        state.ingest_event(added("b", "another sale"));
+        assert!(state.store.lock().unwrap().get("b").is_none());
+    }
+
+    #[test]
+    fn test_disable_rule_and_persisted_hits() {
+        let dir = crate::storage::temp_dir("rule_review");
+        let state = AppState::default();
+        state.storage.set_base_dir(dir.clone());
+        state.settings.write().rules = vec![rule("promo", "sale", RuleAction::Drop)];
+        state.ingest_event(added("a", "big sale today"));
+
+        assert_eq!(state.persist_rule_hits().unwrap(), "1 rules");
+        assert_eq!(state.persist_rule_hits().unwrap(), "");
+        let saved: crate::settings::Settings = state.storage.load(SETTINGS_FILE).unwrap();
+        assert_eq!(saved.rule_hits["promo"].hits, 1);
+
+        assert!(!state.disable_rule("promo").unwrap().enabled);
+        assert!(state.disable_rule("missing").is_err());
+        state.ingest_event(added("b", "another sale"));
+        assert!(state.store.lock().unwrap().get("b").is_some());
+        assert_eq!(state.rule_stats()[0].hits, 1);
+    }
+}
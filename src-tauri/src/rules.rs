@@ -0,0 +1,235 @@
+//! 通知过滤规则：按包名（支持 * 通配）、安卓通知渠道、正则关键字、语言匹配，命中后丢弃或静音。
+//! 指定了渠道的规则比只按应用的更具体，二者都命中时以渠道规则为准（见 evaluate）。
+//! 正则与标题/正文都先经 normalize 归一化再匹配，写 “验证码:” 也能命中全角的 “验证码：”。
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::language::Language;
+use crate::normalize::normalize;
+use crate::types::Notification;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// 不入库
+    Drop,
+    /// 入库但直接标记为已读
+    Mute,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Rule {
+    pub id: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 包名，支持 `*` 通配，如 `com.tencent.*`
+    pub package: Option<String>,
+    /// 安卓通知渠道 id（精确匹配，需同时指定包名）；没有渠道信息的通知不会命中
+    #[serde(default)]
+    pub channel: Option<String>,
+    /// 对标题 + 正文匹配的正则
+    pub pattern: Option<String>,
+    pub language: Option<Language>,
+    pub action: RuleAction,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Rule {
+    /// 校验规则（正则可编译、通配符合法、至少有一个匹配条件）
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("rule id must not be empty".to_string());
+        }
+        if self.package.is_none() && self.pattern.is_none() && self.language.is_none() {
+            return Err(format!("rule {} has no match condition", self.id));
+        }
+        if let Some(p) = &self.package {
+            validate_glob(p).map_err(|e| format!("rule {}: {}", self.id, e))?;
+        }
+        if let Some(channel) = &self.channel {
+            if self.package.is_none() {
+                return Err(format!("rule {}: channel requires a package", self.id));
+            }
+            if channel.trim().is_empty() {
+                return Err(format!("rule {}: channel must not be empty", self.id));
+            }
+        }
+        if let Some(p) = &self.pattern {
+            Regex::new(p).map_err(|e| format!("rule {}: invalid pattern: {}", self.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// 所有条件均满足才算命中
+    pub fn matches(&self, n: &Notification) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if let Some(glob) = &self.package {
+            match &n.package_name {
+                Some(pkg) if glob_match(glob, pkg) => {}
+                _ => return false,
+            }
+        }
+        if self.channel.is_some() && n.channel_id != self.channel {
+            return false;
+        }
+        if let Some(lang) = self.language {
+            if n.language != Some(lang) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            let Ok(re) = Regex::new(&normalize(pattern)) else {
+                return false;
+            };
+            let text = format!(
+                "{}\n{}",
+                normalize(n.title.as_deref().unwrap_or_default()),
+                normalize(n.text.as_deref().unwrap_or_default())
+            );
+            if !re.is_match(&text) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 返回命中的规则：指定了渠道的规则优先，其余按顺序取第一条
+pub fn evaluate<'a>(rules: &'a [Rule], n: &Notification) -> Option<&'a Rule> {
+    rules.iter().find(|r| r.channel.is_some() && r.matches(n)).or_else(|| rules.iter().find(|r| r.matches(n)))
+}
+
+pub fn validate_glob(glob: &str) -> Result<(), String> {
+    if glob.trim().is_empty() {
+        return Err("package pattern must not be empty".to_string());
+    }
+    if glob.chars().any(|c| c.is_whitespace()) {
+        return Err(format!("package pattern {:?} contains whitespace", glob));
+    }
+    Ok(())
+}
+
+/// 简单通配匹配：`*` 匹配任意长度字符
+pub fn glob_match(glob: &str, s: &str) -> bool {
+    let parts: Vec<&str> = glob.split('*').collect();
+    if parts.len() == 1 {
+        return glob == s;
+    }
+    let mut rest = s;
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            let Some(r) = rest.strip_prefix(part) else {
+                return false;
+            };
+            rest = r;
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else if let Some(pos) = rest.find(part) {
+            rest = &rest[pos + part.len()..];
+        } else {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("com.tencent.*", "com.tencent.mm"));
+        assert!(glob_match("*.mm", "com.tencent.mm"));
+        assert!(glob_match("com.*.mm", "com.tencent.mm"));
+        assert!(glob_match("com.tencent.mm", "com.tencent.mm"));
+        assert!(!glob_match("com.tencent.mm", "com.tencent.mobileqq"));
+        assert!(!glob_match("org.*", "com.tencent.mm"));
+    }
+
+    #[test]
+    fn test_language_rule() {
+        let rule = Rule {
+            id: "mute-zh-work".into(),
+            enabled: true,
+            package: Some("com.tencent.*".into()),
+            channel: None,
+            pattern: None,
+            language: Some(Language::Zh),
+            action: RuleAction::Mute,
+        };
+        assert!(rule.validate().is_ok());
+        let mut n = Notification {
+            package_name: Some("com.tencent.wework".into()),
+            language: Some(Language::Zh),
+            ..Default::default()
+        };
+        assert!(rule.matches(&n));
+        n.language = Some(Language::Latin);
+        assert!(!rule.matches(&n));
+    }
+
+    #[test]
+    fn test_pattern_matches_normalized_text() {
+        let rule = Rule {
+            id: "otp".into(),
+            enabled: true,
+            package: None,
+            channel: None,
+            pattern: Some(r"验证码:\d+".into()),
+            language: None,
+            action: RuleAction::Mute,
+        };
+        let n = Notification {
+            title: Some("【银行】".into()),
+            text: Some("您的验证码：123456".into()),
+            ..Default::default()
+        };
+        assert!(rule.matches(&n));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_regex() {
+        let rule = Rule {
+            id: "bad".into(),
+            enabled: true,
+            package: None,
+            channel: None,
+            pattern: Some("(unclosed".into()),
+            language: None,
+            action: RuleAction::Drop,
+        };
+        assert!(rule.validate().is_err());
+    }
+
+    #[test]
+    fn test_channel_rule_wins_over_package_rule() {
+        let rule = |id: &str, channel: Option<&str>, action| Rule {
+            id: id.into(),
+            enabled: true,
+            package: Some("com.shop".into()),
+            channel: channel.map(Into::into),
+            pattern: None,
+            language: None,
+            action,
+        };
+        let rules = vec![rule("block-shop", None, RuleAction::Drop), rule("quiet-delivery", Some("delivery"), RuleAction::Mute)];
+        assert!(rule("promo-only", Some("promo"), RuleAction::Drop).validate().is_ok());
+        assert!(Rule { package: None, ..rule("bad", Some("x"), RuleAction::Drop) }.validate().is_err());
+
+        let mut n = Notification { package_name: Some("com.shop".into()), channel_id: Some("delivery".into()), ..Default::default() };
+        assert_eq!(evaluate(&rules, &n).unwrap().id, "quiet-delivery");
+        n.channel_id = Some("promo".into());
+        assert_eq!(evaluate(&rules, &n).unwrap().id, "block-shop");
+        // 旧版安卓没有渠道：只有按应用的规则命中
+        n.channel_id = None;
+        assert_eq!(evaluate(&rules, &n).unwrap().id, "block-shop");
+    }
+}
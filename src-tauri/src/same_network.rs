@@ -0,0 +1,182 @@
+//! 同网络检查：电脑在有线 VLAN、手机在访客 Wi-Fi 时配对永远不会成功。
+//! 用手机端地址（配对请求的来源地址，或配对数据中的地址）与本机网卡、二维码中的地址比较子网，
+//! 给出：同一子网 / 不同子网但都是私有地址（可能经路由可达）/ 不可达（链路本地、公网或运营商 NAT 地址）。
+//! 本机网卡取不到子网掩码，按 DEFAULT_PREFIX 判断。
+//! 手动连接向导探测失败、二维码配对请求失败时附带该提示。
+
+use std::net::Ipv4Addr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::network_utils::{self, DEFAULT_PREFIX};
+use crate::time_format::Lang;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMatch {
+    SameSubnet,
+    /// 不同子网，但都是私有地址
+    DifferentSubnet,
+    Unroutable,
+    /// 还没有手机端地址（尚未收到任何配对请求）
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SameNetworkHint {
+    pub status: NetworkMatch,
+    pub phone_ip: Option<String>,
+    /// 二维码中的本机地址
+    pub advertised_ip: Option<String>,
+    pub phone_subnet: Option<String>,
+    pub advertised_subnet: Option<String>,
+    /// 与手机同一子网的本机网卡（不是二维码中的地址时，说明应改用该网卡的地址）
+    pub matching_interface: Option<String>,
+    pub message: String,
+}
+
+/// 配对 URL 中的主机（如 ws://192.168.1.5:10035/path -> 192.168.1.5）
+pub fn host_of(url: &str) -> Option<&str> {
+    let rest = url.split_once("://").map_or(url, |(_, r)| r);
+    let host = rest.split(['/', '?']).next()?;
+    let host = host.rsplit_once('@').map_or(host, |(_, h)| h);
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+fn message(hint: &SameNetworkHint, lang: Lang) -> String {
+    let phone = hint.phone_subnet.as_deref().unwrap_or("?");
+    let local = hint.advertised_subnet.as_deref().unwrap_or("?");
+    match (hint.status, lang) {
+        (NetworkMatch::SameSubnet, Lang::Zh) => match &hint.matching_interface {
+            Some(name) if hint.advertised_subnet.as_deref() != Some(phone) => {
+                format!("手机与本机网卡 {} 在同一网络（{}），但二维码中的地址在 {}", name, phone, local)
+            }
+            _ => format!("手机与电脑在同一网络（{}）", phone),
+        },
+        (NetworkMatch::SameSubnet, Lang::En) => match &hint.matching_interface {
+            Some(name) if hint.advertised_subnet.as_deref() != Some(phone) => {
+                format!("Your phone is on the same network as interface {} ({}), but the QR code advertises {}", name, phone, local)
+            }
+            _ => format!("Your phone is on the same network ({})", phone),
+        },
+        (NetworkMatch::DifferentSubnet, Lang::Zh) => format!("手机似乎在另一个网络（{} 与 {}）", phone, local),
+        (NetworkMatch::DifferentSubnet, Lang::En) => {
+            format!("Your phone appears to be on a different network ({} vs {})", phone, local)
+        }
+        (NetworkMatch::Unroutable, Lang::Zh) => format!("手机地址 {} 无法从局域网直连，请连接与电脑相同的 Wi-Fi", phone),
+        (NetworkMatch::Unroutable, Lang::En) => {
+            format!("Your phone's address ({}) cannot be reached over the LAN; join the same Wi-Fi as this PC", phone)
+        }
+        (NetworkMatch::Unknown, Lang::Zh) => "尚未收到手机的配对请求".to_string(),
+        (NetworkMatch::Unknown, Lang::En) => "No pairing attempt from the phone yet".to_string(),
+    }
+}
+
+/// 比较手机地址与本机地址（纯函数）
+pub fn evaluate(phone: Option<Ipv4Addr>, advertised: Option<Ipv4Addr>, interfaces: &[(String, Ipv4Addr)], lang: Lang) -> SameNetworkHint {
+    let label = |ip: Ipv4Addr| network_utils::subnet_label(ip, DEFAULT_PREFIX);
+    let mut hint = SameNetworkHint {
+        status: NetworkMatch::Unknown,
+        phone_ip: phone.map(|ip| ip.to_string()),
+        advertised_ip: advertised.map(|ip| ip.to_string()),
+        phone_subnet: phone.map(label),
+        advertised_subnet: advertised.map(label),
+        matching_interface: None,
+        message: String::new(),
+    };
+    if let Some(phone) = phone {
+        hint.matching_interface = interfaces
+            .iter()
+            .find(|(_, ip)| network_utils::same_subnet(*ip, phone, DEFAULT_PREFIX))
+            .map(|(name, _)| name.clone());
+        let advertised_matches = advertised.is_some_and(|ip| network_utils::same_subnet(ip, phone, DEFAULT_PREFIX));
+        hint.status = if advertised_matches || hint.matching_interface.is_some() {
+            NetworkMatch::SameSubnet
+        } else if network_utils::is_private_lan(phone) {
+            NetworkMatch::DifferentSubnet
+        } else {
+            NetworkMatch::Unroutable
+        };
+    }
+    hint.message = message(&hint, lang);
+    hint
+}
+
+impl AppState {
+    /// 按给定的手机地址生成提示（地址不是 IPv4 时为 None）
+    pub(crate) fn network_hint_for(&self, phone: &str) -> Option<SameNetworkHint> {
+        let phone: Ipv4Addr = phone.trim().parse().ok()?;
+        let advertised = network_utils::get_local_ip().ok().and_then(|ip| ip.parse().ok());
+        let lang = self.settings.read().lang();
+        Some(evaluate(Some(phone), advertised, &network_utils::local_ipv4_interfaces(), lang))
+    }
+
+    /// 用最近一次配对请求的来源地址（或配对数据中的地址）检查
+    pub fn verify_same_network_hint(&self) -> SameNetworkHint {
+        let phone = self.pairing_history().into_iter().find_map(|r| {
+            let ip = r.source_ip.clone().or_else(|| host_of(&r.url).map(str::to_string))?;
+            ip.parse::<Ipv4Addr>().ok()
+        });
+        match phone {
+            Some(ip) => self.network_hint_for(&ip.to_string()).expect("parsed above"),
+            None => evaluate(None, None, &[], self.settings.read().lang()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Ipv4Addr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_vlan_and_guest_wifi() {
+        let ifaces = vec![("eth0".to_string(), ip("10.0.1.20"))];
+        let hint = evaluate(Some(ip("192.168.50.33")), Some(ip("10.0.1.20")), &ifaces, Lang::En);
+        assert_eq!(hint.status, NetworkMatch::DifferentSubnet);
+        assert_eq!(hint.message, "Your phone appears to be on a different network (192.168.50.x vs 10.0.1.x)");
+
+        let same = evaluate(Some(ip("10.0.1.99")), Some(ip("10.0.1.20")), &ifaces, Lang::Zh);
+        assert_eq!((same.status, same.matching_interface.as_deref()), (NetworkMatch::SameSubnet, Some("eth0")));
+        assert_eq!(same.message, "手机与电脑在同一网络（10.0.1.x）");
+    }
+
+    #[test]
+    fn test_other_interface_and_unroutable() {
+        // 手机与第二块网卡同网，但二维码用的是另一块网卡的地址
+        let ifaces = vec![("eth0".to_string(), ip("10.0.1.20")), ("wlan0".to_string(), ip("192.168.50.2"))];
+        let hint = evaluate(Some(ip("192.168.50.33")), Some(ip("10.0.1.20")), &ifaces, Lang::En);
+        assert_eq!((hint.status, hint.matching_interface.as_deref()), (NetworkMatch::SameSubnet, Some("wlan0")));
+        assert!(hint.message.contains("QR code advertises 10.0.1.x"));
+
+        for phone in ["169.254.3.4", "100.72.1.1", "8.8.8.8"] {
+            assert_eq!(evaluate(Some(ip(phone)), Some(ip("10.0.1.20")), &ifaces, Lang::En).status, NetworkMatch::Unroutable);
+        }
+        assert_eq!(evaluate(None, None, &[], Lang::En).status, NetworkMatch::Unknown);
+    }
+
+    #[test]
+    fn test_host_of_and_history() {
+        assert_eq!(host_of("ws://192.168.1.5:10035/socket"), Some("192.168.1.5"));
+        assert_eq!(host_of("192.168.1.5:10035"), Some("192.168.1.5"));
+        assert_eq!(host_of("http://user@10.0.0.2/pair?x=1"), Some("10.0.0.2"));
+        assert_eq!(host_of("ws://"), None);
+
+        let state = AppState::default();
+        assert_eq!(state.verify_same_network_hint().status, NetworkMatch::Unknown);
+        let mut data = crate::pairing_payload::parse_pairing(r#"{"url":"ws://8.8.4.4:10035","token":"t"}"#).unwrap();
+        state.record_pairing(&data);
+        assert_eq!(state.verify_same_network_hint().phone_ip.as_deref(), Some("8.8.4.4"));
+        // 请求的来源地址优先于配对数据中的地址
+        data.source_ip = Some("1.1.1.1".into());
+        state.record_pairing(&data);
+        let hint = state.verify_same_network_hint();
+        assert_eq!((hint.phone_ip.as_deref(), hint.status), (Some("1.1.1.1"), NetworkMatch::Unroutable));
+    }
+}
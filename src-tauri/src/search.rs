@@ -0,0 +1,177 @@
+//! 通知搜索：查询按空白拆成多个词，不区分大小写（逐字符 Unicode 小写折叠），
+//! 并按 normalize 的规则折叠全角标点与变体选择符（“验证码:” 能搜到 “验证码：”），
+//! 每个词都要出现在标题 / 正文 / 包名之一中才算命中。
+//! 命中时返回各字段的匹配区间，区间为原始字符串的字符下标（Unicode 标量，左闭右开），
+//! 前端用 Array.from(text) 切片即可，不必在 JS 里重新实现匹配。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::normalize;
+use crate::types::Notification;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MatchSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Title,
+    Text,
+    PackageName,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FieldMatch {
+    pub field: SearchField,
+    pub spans: Vec<MatchSpan>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchHit {
+    pub notification: Notification,
+    pub matches: Vec<FieldMatch>,
+}
+
+/// 折叠后的文本，以及每个折叠字符对应的原始字符下标
+struct Folded {
+    chars: Vec<char>,
+    origin: Vec<usize>,
+}
+
+fn fold(s: &str) -> Folded {
+    let mut chars = Vec::with_capacity(s.len());
+    let mut origin = Vec::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        normalize::fold_each(c, |d| {
+            for lower in d.to_lowercase() {
+                chars.push(lower);
+                origin.push(i);
+            }
+        });
+    }
+    Folded { chars, origin }
+}
+
+/// 拆分并折叠查询词；空查询返回空列表
+pub fn terms(query: &str) -> Vec<Vec<char>> {
+    query.split_whitespace().map(|w| fold(w).chars).collect()
+}
+
+/// 各词在文本中的所有出现位置（原始字符下标），重叠或相邻的区间合并
+pub fn find_spans(text: &str, terms: &[Vec<char>]) -> Vec<MatchSpan> {
+    let folded = fold(text);
+    let mut spans = Vec::new();
+    for term in terms.iter().filter(|t| !t.is_empty()) {
+        if term.len() > folded.chars.len() {
+            continue;
+        }
+        for i in 0..=folded.chars.len() - term.len() {
+            if folded.chars[i..i + term.len()] == term[..] {
+                spans.push(MatchSpan { start: folded.origin[i], end: folded.origin[i + term.len() - 1] + 1 });
+            }
+        }
+    }
+    spans.sort_by_key(|s| s.start);
+    let mut merged: Vec<MatchSpan> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+fn fields(n: &Notification) -> [(SearchField, Option<&str>); 3] {
+    [
+        (SearchField::Title, n.title.as_deref()),
+        (SearchField::Text, n.text.as_deref()),
+        (SearchField::PackageName, n.package_name.as_deref()),
+    ]
+}
+
+fn contains(text: &str, term: &[char]) -> bool {
+    let folded = fold(text).chars;
+    term.is_empty() || folded.windows(term.len()).any(|w| w == term)
+}
+
+/// 每个词都出现在某个字段中
+pub fn matches(n: &Notification, terms: &[Vec<char>]) -> bool {
+    terms.iter().all(|term| fields(n).iter().any(|(_, f)| f.is_some_and(|s| contains(s, term))))
+}
+
+/// 命中时返回各字段的匹配区间；未命中返回 None
+pub fn highlight(n: &Notification, terms: &[Vec<char>]) -> Option<Vec<FieldMatch>> {
+    if !matches(n, terms) {
+        return None;
+    }
+    let found = fields(n)
+        .into_iter()
+        .filter_map(|(field, text)| {
+            let spans = find_spans(text?, terms);
+            (!spans.is_empty()).then_some(FieldMatch { field, spans })
+        })
+        .collect();
+    Some(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按区间从原始字符串中切出文字（与前端 Array.from 切片一致）
+    fn slices(text: &str, spans: &[MatchSpan]) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        spans.iter().map(|s| chars[s.start..s.end].iter().collect()).collect()
+    }
+
+    #[test]
+    fn test_spans_index_original_chars() {
+        let title = "🎉 会议 Meeting 改到明天 MEETING";
+        let spans = find_spans(title, &terms("meeting 会议"));
+        assert_eq!(slices(title, &spans), ["会议", "Meeting", "MEETING"]);
+        assert_eq!(spans[0], MatchSpan { start: 2, end: 4 });
+
+        // 折叠后长度变化的字符（İ -> i̇）仍映射回原始下标
+        let text = "İstanbul ❤️ istanbul";
+        assert_eq!(slices(text, &find_spans(text, &terms("stan"))), ["stan", "stan"]);
+    }
+
+    #[test]
+    fn test_normalized_matching() {
+        let text = "【银行】验证码：482913，５分钟内有效";
+        assert_eq!(slices(text, &find_spans(text, &terms("验证码:"))), ["验证码："]);
+        assert_eq!(slices(text, &find_spans(text, &terms("5分钟"))), ["５分钟"]);
+
+        // 变体选择符被跳过，区间仍覆盖原始字符
+        let text = "收到❤\u{FE0F}了";
+        assert_eq!(find_spans(text, &terms("❤了")), [MatchSpan { start: 2, end: 5 }]);
+        let text = "cafe\u{0301} Café";
+        assert_eq!(find_spans(text, &terms("café")), [MatchSpan { start: 0, end: 5 }, MatchSpan { start: 6, end: 10 }]);
+    }
+
+    #[test]
+    fn test_overlapping_terms_merge() {
+        let spans = find_spans("aaaa bcd", &terms("aa bc cd"));
+        assert_eq!(spans, [MatchSpan { start: 0, end: 4 }, MatchSpan { start: 5, end: 8 }]);
+    }
+
+    #[test]
+    fn test_all_terms_required_across_fields() {
+        let n = Notification {
+            title: Some("张三".into()),
+            text: Some("Lunch at 12? 🍜".into()),
+            package_name: Some("com.tencent.mm".into()),
+            ..Default::default()
+        };
+        let hit = highlight(&n, &terms("LUNCH 张三")).unwrap();
+        assert_eq!(hit.iter().map(|m| m.field).collect::<Vec<_>>(), [SearchField::Title, SearchField::Text]);
+        assert_eq!(slices(n.text.as_deref().unwrap(), &hit[1].spans), ["Lunch"]);
+        assert!(highlight(&n, &terms("lunch 李四")).is_none());
+        assert!(matches(&n, &terms("TENCENT")));
+    }
+}
@@ -0,0 +1,183 @@
+//! 应用设置（初期最小集，内存版）。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::app_meta::{self, AppMetaMap};
+use crate::event_log::EventLogSettings;
+use crate::importance::{ImportanceMap, QuietHours};
+use crate::limits::PayloadLimits;
+use crate::low_power::LowPowerSettings;
+use crate::metrics::MetricsSettings;
+use crate::phone_dismiss::{DismissBurstSettings, PhoneDismissBehavior};
+use crate::popout::PopoutSettings;
+use crate::privacy::PrivacySettings;
+use crate::rule_review::RuleHitMap;
+use crate::rules::Rule;
+use crate::store::SortMode;
+use crate::templates::TemplateSettings;
+use crate::time_format::Lang;
+use crate::webhook::WebhookSettings;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Settings {
+    /// 界面语言，如 "zh-CN" / "en-US"
+    pub locale: String,
+    /// 入库时检测通知语言（不关心语言过滤的用户可关闭）
+    pub detect_language: bool,
+    /// 过滤规则（按顺序匹配，第一条命中的生效）
+    pub rules: Vec<Rule>,
+    /// 各规则的命中统计（随规则保存，界面不编辑，见 rule_review）
+    pub rule_hits: RuleHitMap,
+    /// 通知详情弹出窗口
+    pub popout: PopoutSettings,
+    /// 保留上限与单应用配额
+    pub retention: RetentionSettings,
+    /// 高对比度托盘图标（用形状而非颜色区分状态）
+    pub high_contrast_tray: bool,
+    /// 事件流导出文件的轮转与刷盘
+    pub event_log: EventLogSettings,
+    /// 通知预览隐私级别
+    pub privacy: PrivacySettings,
+    /// 按设备时钟偏差校正后的时间展示通知
+    pub use_corrected_time: bool,
+    /// 托盘提示中显示手机正在播放的媒体
+    pub media_in_tray_tooltip: bool,
+    /// 入库事件的 webhook 推送
+    pub webhook: WebhookSettings,
+    /// 通知重要性 -> 计入未读 / 弹出 / 响铃
+    pub importance: ImportanceMap,
+    /// 免打扰时段
+    pub quiet_hours: QuietHours,
+    /// 系统勿扰/专注模式开启时按免打扰时段处理
+    pub respect_os_focus: bool,
+    /// 命令入参上限（id 数组长度、文本长度）
+    pub payload_limits: PayloadLimits,
+    /// 匿名使用统计（默认关闭）
+    pub metrics: MetricsSettings,
+    /// 超过该小时数仍未读的通知自动标记为已读（置顶与高重要性的除外），None 表示关闭
+    pub auto_read_after_hours: Option<u32>,
+    /// 系统通知、托盘提示/菜单与复制文本的模板
+    pub templates: TemplateSettings,
+    /// 夜间低功耗模式（手机端批量推送）
+    pub low_power: LowPowerSettings,
+    /// 手机端批量清除通知时桌面端的处理方式
+    pub phone_dismiss_behavior: PhoneDismissBehavior,
+    /// 批量清除的判定与单条划掉的处理
+    pub phone_dismiss_burst: DismissBurstSettings,
+    /// 应用显示名与颜色的用户覆盖项（见 app_meta）
+    pub app_meta: AppMetaMap,
+    /// 只读观察模式：不向手机发出任何改变其状态的操作（见 watch_only）
+    pub watch_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct RetentionSettings {
+    /// 最多保留的通知条数（置顶除外）
+    pub max_items: usize,
+    /// 单个应用的软配额，占 max_items 的百分比
+    pub package_quota_percent: u8,
+    /// 已读通知保留的天数（置顶除外），None 不按时间清理；未读通知只在超过 max_items 时淘汰
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            max_items: 5000,
+            package_quota_percent: 30,
+            max_age_days: None,
+        }
+    }
+}
+
+impl RetentionSettings {
+    /// 单个应用的软配额条数
+    pub fn package_quota(&self) -> usize {
+        self.max_items * self.package_quota_percent as usize / 100
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            locale: "zh-CN".to_string(),
+            detect_language: true,
+            rules: Vec::new(),
+            rule_hits: RuleHitMap::new(),
+            popout: PopoutSettings::default(),
+            retention: RetentionSettings::default(),
+            high_contrast_tray: false,
+            event_log: EventLogSettings::default(),
+            privacy: PrivacySettings::default(),
+            use_corrected_time: false,
+            media_in_tray_tooltip: true,
+            webhook: WebhookSettings::default(),
+            importance: ImportanceMap::default(),
+            quiet_hours: QuietHours::default(),
+            respect_os_focus: true,
+            payload_limits: PayloadLimits::default(),
+            metrics: MetricsSettings::default(),
+            auto_read_after_hours: None,
+            templates: TemplateSettings::default(),
+            low_power: LowPowerSettings::default(),
+            phone_dismiss_behavior: PhoneDismissBehavior::default(),
+            phone_dismiss_burst: DismissBurstSettings::default(),
+            app_meta: AppMetaMap::new(),
+            watch_only: false,
+        }
+    }
+}
+
+/// 界面视图状态（上次选择的排序等），与设置分开保存
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct ViewState {
+    pub sort: SortMode,
+}
+
+impl Settings {
+    pub fn lang(&self) -> Lang {
+        Lang::from_locale(&self.locale)
+    }
+
+    /// 校验设置是否合法
+    pub fn validate(&self) -> Result<(), String> {
+        if self.locale.trim().is_empty() {
+            return Err("locale must not be empty".to_string());
+        }
+        if self.popout.width < 100.0 || self.popout.height < 80.0 {
+            return Err("popout window size too small".to_string());
+        }
+        if self.retention.max_items == 0 {
+            return Err("retention.max_items must be positive".to_string());
+        }
+        if self.retention.package_quota_percent == 0 || self.retention.package_quota_percent > 100 {
+            return Err("retention.package_quota_percent must be within 1..=100".to_string());
+        }
+        if self.retention.max_age_days == Some(0) {
+            return Err("retention.max_age_days must be positive".to_string());
+        }
+        if self.auto_read_after_hours == Some(0) {
+            return Err("auto_read_after_hours must be positive".to_string());
+        }
+        if self.event_log.max_mb == 0 {
+            return Err("event_log.max_mb must be positive".to_string());
+        }
+        self.webhook.validate()?;
+        self.importance.validate()?;
+        self.quiet_hours.validate()?;
+        self.payload_limits.validate()?;
+        self.metrics.validate()?;
+        self.templates.validate()?;
+        self.low_power.validate()?;
+        self.phone_dismiss_burst.validate()?;
+        app_meta::validate(&self.app_meta)?;
+        for rule in &self.rules {
+            rule.validate()?;
+        }
+        Ok(())
+    }
+}
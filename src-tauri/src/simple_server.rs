@@ -1,17 +1,19 @@
 use std::sync::Arc;
 use parking_lot::Mutex;
 use std::net::{TcpListener, TcpStream};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufReader, Write};
 use std::thread;
 use std::time::Duration;
 
-// 使用现有的 PairingData 定义
-pub use crate::temp_server::PairingData;
+use crate::network_utils::BindError;
+use crate::ports::{PortGuard, PortRegistry, ServerRole};
 
 #[derive(Clone)]
 pub struct SimpleServer {
     port: u16,
     running: Arc<Mutex<bool>>,
+    // 端口登记，stop 时释放
+    port_guard: Arc<Mutex<Option<PortGuard>>>,
 }
 
 impl SimpleServer {
@@ -19,6 +21,7 @@ impl SimpleServer {
         Self {
             port,
             running: Arc::new(Mutex::new(false)),
+            port_guard: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -33,6 +36,15 @@ impl SimpleServer {
     pub fn stop(&self) {
         println!("[SimpleServer] Stopping server on port {}...", self.port);
         *self.running.lock() = false;
+        *self.port_guard.lock() = None;
+    }
+
+    /// 先登记端口再启动；冲突或绑定失败时不保留登记
+    pub fn start_registered(&self, ports: &PortRegistry) -> Result<(), String> {
+        let guard = ports.register(ServerRole::SimpleServer, self.port, "0.0.0.0").map_err(|e| e.to_string())?;
+        self.start()?;
+        *self.port_guard.lock() = Some(guard);
+        Ok(())
     }
 
     /// 启动服务器（持久化，支持多客户端）
@@ -40,7 +52,7 @@ impl SimpleServer {
         println!("[SimpleServer] Starting on port {}...", self.port);
 
         let listener = TcpListener::bind(("0.0.0.0", self.port))
-            .map_err(|e| format!("Failed to bind port {}: {}", self.port, e))?;
+            .map_err(|e| BindError::from_io(self.port, &e).to_string())?;
 
         listener.set_nonblocking(true)
             .map_err(|e| format!("Failed to set nonblocking: {}", e))?;
@@ -105,17 +117,20 @@ impl SimpleServer {
         // 1. 读取客户端发送的 PairingData
         println!("[SimpleServer] Reading pairing data from client...");
         let mut line = String::new();
-        reader.read_line(&mut line)
+        crate::protocol::read_frame(&mut reader, &mut line)
             .map_err(|e| format!("Failed to read from client: {}", e))?;
 
         println!("[SimpleServer] Received: {}", line.trim());
 
         // 解析 PairingData
-        let pairing_data: PairingData = serde_json::from_str(line.trim())
-            .map_err(|e| {
-                eprintln!("[SimpleServer] Failed to parse JSON: {}", e);
-                format!("Failed to parse pairing data: {}", e)
-            })?;
+        let pairing_data = match crate::pairing_payload::parse_pairing(&line) {
+            Ok(data) => data,
+            Err(rejection) => {
+                eprintln!("[SimpleServer] Rejected pairing data: {}", rejection);
+                let _ = stream.write_all(format!("{}\n", rejection.response()).as_bytes());
+                return Err(format!("Failed to parse pairing data: {}", rejection));
+            }
+        };
 
         println!("[SimpleServer] Pairing data parsed: url={}, token_len={}",
             pairing_data.url, pairing_data.token.len());
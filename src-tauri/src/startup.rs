@@ -0,0 +1,265 @@
+//! 启动流程：setup 中只挂载 AppHandle、建托盘，磁盘加载（设置、通知等）放到后台线程，
+//! 每个子系统加载完成后推送 subsystem-ready；主窗口在 store 就绪前显示加载页，之后用快照命令填充。
+//! 依赖未就绪子系统的命令返回 NotReady 错误；读写通知存储的命令在命令分发处统一检查（STORE_COMMANDS）。
+//! 数据目录读取超时（见 storage）时子系统仍标为就绪、以内存中的默认值运行，同时标为降级并推送 subsystem-degraded。
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime};
+
+use crate::commands::AppState;
+use crate::data_lock::DataLockStatus;
+use crate::settings::Settings;
+use crate::store::VersionedCounts;
+use crate::ui_window::UiStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    Settings,
+    Store,
+}
+
+/// 读写通知存储的命令：存储就绪之前由 gate_store_commands 以 NotReady 拒绝
+pub const STORE_COMMANDS: &[&str] = &[
+    "get_counts",
+    "get_counts_versioned",
+    "get_counts_by_package",
+    "get_startup_snapshot",
+    "list_notifications",
+    "list_notification_groups",
+    "search_notifications",
+    "get_day_summary",
+    "mark_read",
+    "snooze",
+    "mark_unread",
+    "archive",
+    "unarchive",
+    "delete",
+    "delete_all",
+    "delete_by_package",
+    "delete_read",
+    "factory_reset",
+    "set_pinned",
+    "mark_read_where",
+    "delete_where",
+    "pin_where",
+    "create_local_notification",
+    "list_local_reminders",
+    "cancel_local_reminder",
+    "restore_filtered",
+    "export_notifications",
+    "migrate_to_persistent_store",
+    "open_notification_window",
+    "get_notification_preview",
+    "get_notification_text",
+    "upsert_notification",
+    "compact_store",
+    "verify_store_integrity",
+];
+
+/// 包装命令分发：存储未就绪时直接拒绝 STORE_COMMANDS 中的命令（只看命令名）
+pub fn gate_store_commands<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let not_ready = STORE_COMMANDS
+            .contains(&invoke.message.command())
+            .then(|| invoke.message.webview_ref().try_state::<AppState>().and_then(|s| s.ensure_ready(Subsystem::Store).err()))
+            .flatten();
+        if let Some(error) = not_ready {
+            invoke.resolver.reject(error);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+#[derive(Default)]
+pub struct Readiness {
+    ready: Mutex<HashSet<Subsystem>>,
+    degraded: Mutex<HashSet<Subsystem>>,
+}
+
+impl Readiness {
+    pub fn is_ready(&self, sub: Subsystem) -> bool {
+        self.ready.lock().contains(&sub)
+    }
+
+    pub fn ready(&self) -> Vec<Subsystem> {
+        let ready = self.ready.lock();
+        [Subsystem::Settings, Subsystem::Store].into_iter().filter(|s| ready.contains(s)).collect()
+    }
+
+    pub fn degraded(&self) -> Vec<Subsystem> {
+        let degraded = self.degraded.lock();
+        [Subsystem::Settings, Subsystem::Store].into_iter().filter(|s| degraded.contains(s)).collect()
+    }
+
+    /// 新就绪时返回 true
+    fn mark(&self, sub: Subsystem) -> bool {
+        self.ready.lock().insert(sub)
+    }
+}
+
+/// 首屏快照：主窗口在 store 就绪后一次取齐
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StartupSnapshot {
+    pub ready: Vec<Subsystem>,
+    pub degraded: Vec<Subsystem>,
+    pub counts: VersionedCounts,
+    pub settings: Settings,
+}
+
+/// get_health：界面、子系统与连接的整体状态
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Health {
+    pub ui: UiStatus,
+    pub ready: Vec<Subsystem>,
+    pub degraded: Vec<Subsystem>,
+    pub connections: usize,
+    /// 数据目录被另一台机器占用时为只读
+    pub data_lock: DataLockStatus,
+}
+
+/// 后台线程访问 AppState（生产环境为 AppHandle，测试中为 Arc<AppState>）
+pub trait StateAccess: Send + 'static {
+    fn app_state(&self) -> &AppState;
+}
+
+impl<R: tauri::Runtime> StateAccess for tauri::AppHandle<R> {
+    fn app_state(&self) -> &AppState {
+        tauri::Manager::state::<AppState>(self).inner()
+    }
+}
+
+impl StateAccess for std::sync::Arc<AppState> {
+    fn app_state(&self) -> &AppState {
+        self
+    }
+}
+
+/// 在后台线程加载持久化数据；没有数据目录时直接视为就绪
+pub fn spawn_loading(handle: impl StateAccess, dir: Option<PathBuf>) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let state = handle.app_state();
+        let started = std::time::Instant::now();
+        match dir {
+            Some(dir) => state.init_storage(dir),
+            None => {
+                state.mark_ready(Subsystem::Settings);
+                state.mark_ready(Subsystem::Store);
+            }
+        }
+        println!("[Startup] Loaded in {:?}", started.elapsed());
+        state.verify_identity();
+        state.load_handoff(chrono::Utc::now().timestamp());
+        state.refresh_tray_icon();
+        state.refresh_tray_menu();
+    })
+}
+
+impl AppState {
+    pub(crate) fn mark_ready(&self, sub: Subsystem) {
+        if self.readiness.mark(sub) {
+            println!("[Startup] {:?} ready", sub);
+            self.events.emit("subsystem-ready", sub);
+        }
+    }
+
+    /// 数据读取超时：以内存中的数据继续运行
+    pub(crate) fn mark_degraded(&self, sub: Subsystem, files: Vec<String>) {
+        if self.readiness.degraded.lock().insert(sub) {
+            println!("[Startup] {:?} degraded (slow reads: {:?})", sub, files);
+            self.events.emit("subsystem-degraded", serde_json::json!({ "subsystem": sub, "files": files }));
+        }
+    }
+
+    /// 诊断信息：数据目录写入耗时、待写入与降级状态
+    pub fn storage_status(&self) -> crate::storage::StorageStatus {
+        crate::storage::StorageStatus { degraded: self.readiness.degraded(), ..self.storage.status() }
+    }
+
+    /// 维护任务：重试只保存在内存中的文件
+    pub fn retry_storage_writes(&self) -> Result<String, String> {
+        self.storage.retry_pending().map(|n| format!("{} written", n))
+    }
+
+    /// 命令入口检查：子系统未就绪时返回 NotReady 错误
+    pub fn ensure_ready(&self, sub: Subsystem) -> Result<(), String> {
+        if self.readiness.is_ready(sub) {
+            Ok(())
+        } else {
+            Err(format!("NotReady: {:?} is still loading", sub))
+        }
+    }
+
+    /// 不要求任何子系统就绪，仅托盘模式下也可调用
+    pub fn health(&self) -> Health {
+        Health {
+            ui: self.ui.status(),
+            ready: self.readiness.ready(),
+            degraded: self.readiness.degraded(),
+            connections: self.clients.read().len(),
+            data_lock: self.data_lock.status(),
+        }
+    }
+
+    pub fn startup_snapshot(&self) -> Result<StartupSnapshot, String> {
+        self.ensure_ready(Subsystem::Store)?;
+        Ok(StartupSnapshot {
+            ready: self.readiness.ready(),
+            degraded: self.readiness.degraded(),
+            counts: self.store.lock().unwrap().versioned_counts(),
+            settings: self.settings.read().clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Notification;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_setup_does_not_wait_for_large_store() {
+        let dir = crate::storage::temp_dir("startup");
+        let fixture: Vec<Notification> = (0..20_000)
+            .map(|i| Notification {
+                id: format!("local-{}", i),
+                title: Some(format!("提醒 {}", i)),
+                text: Some("x".repeat(200)),
+                posted_at: Some(i),
+                local: true,
+                ..Default::default()
+            })
+            .collect();
+        let storage = crate::storage::Storage::default();
+        storage.set_base_dir(dir.clone());
+        storage.save("reminders.json", &fixture).unwrap();
+
+        let state = Arc::new(AppState::default());
+        assert!(state.ensure_ready(Subsystem::Store).unwrap_err().starts_with("NotReady"));
+        assert!(state.startup_snapshot().is_err());
+
+        // setup 中的部分：启动后台加载立即返回
+        let started = Instant::now();
+        let loading = spawn_loading(state.clone(), Some(dir.clone()));
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        loading.join().unwrap();
+        let snapshot = state.startup_snapshot().unwrap();
+        assert_eq!(snapshot.ready, [Subsystem::Settings, Subsystem::Store]);
+        assert_eq!(snapshot.counts.total, 20_000);
+        let events: Vec<String> = state.events.take_captured().into_iter().map(|(e, _)| e).collect();
+        assert_eq!(events.iter().filter(|e| *e == "subsystem-ready").count(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,484 @@
+//! 本地持久化：应用数据目录下的 JSON 文件。
+//! 写入先写临时文件再 rename，避免写一半时崩溃导致文件损坏。
+//! 数据目录可能在网络共享或移动磁盘上，读写都有期限：
+//! 写入交给专用的写线程（按提交顺序执行），调用方最多等待 WRITE_DEADLINE；
+//! 超时或失败时内容保留在内存中（pending，按完整路径记录，同一文件只保留最新一份；切换档案后仍写回原目录），经后台错误总线上报，由维护任务重试。
+//! 写线程卡在上一次写入时，后续写入不再排队等待，直接转入 pending。
+//! 读取超过 READ_DEADLINE 时视为没有数据，记入 slow_reads；启动时据此把相应子系统标为降级（见 startup）。
+//! 读取超时的文件本次运行不再写回，避免用默认值覆盖磁盘上尚未读到的数据。
+//! 数据目录被另一台机器占用时（见 data_lock）进入只读：写入一律留在内存中，解除后由重试写回。
+//!
+//! 目录布局（文件名常量在各自模块中定义）：
+//! - 数据目录（app_local_data_dir，可随档案切换，见 profiles）：settings.json、view_state.json、通知与提醒、
+//!   endpoints.json、mutes.json 等业务数据，以及 instance.lock、handoff.bin、audit.ndjson；全部经本模块或 write_atomic 写入。
+//! - 配置目录（app_dirs::config_dir）：只放本机身份 device_uuid.txt 与 device_identity.json，不随档案切换，
+//!   也不跟着数据目录搬到共享位置（原因见 identity）。
+//! - 界面窗口状态目前没有单独持久化；视图状态（排序等）保存在数据目录的 view_state.json。
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error_bus::BackgroundError;
+
+/// 单次写入的等待期限
+pub const WRITE_DEADLINE: Duration = Duration::from_secs(2);
+/// 单次读取的等待期限
+pub const READ_DEADLINE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+struct Deadlines {
+    write: Duration,
+    read: Duration,
+}
+
+impl Default for Deadlines {
+    fn default() -> Self {
+        Self { write: WRITE_DEADLINE, read: READ_DEADLINE }
+    }
+}
+
+/// 数据目录写入耗时（写线程实测，包含调用方已超时的写入）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WriteLatency {
+    pub samples: u64,
+    pub last_ms: u64,
+    pub avg_ms: u64,
+    pub max_ms: u64,
+    pub timeouts: u64,
+    pub failures: u64,
+}
+
+/// 诊断信息：数据目录与读写状况
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StorageStatus {
+    pub data_dir: Option<String>,
+    pub write_latency: WriteLatency,
+    /// 只保存在内存中、尚未写入的文件
+    pub pending: Vec<String>,
+    /// 启动时读取超时的文件
+    pub slow_reads: Vec<String>,
+    /// 降级的子系统（由 AppState 填写）
+    pub degraded: Vec<crate::startup::Subsystem>,
+}
+
+struct Pending {
+    name: String,
+    generation: u64,
+    data: Vec<u8>,
+    /// 读取超时的文件：本次运行不写回
+    held: bool,
+}
+
+struct WriteJob {
+    name: String,
+    path: PathBuf,
+    generation: u64,
+    data: Vec<u8>,
+    reply: mpsc::Sender<Result<(), String>>,
+}
+
+/// 调用方与写线程共享的状态
+#[derive(Default)]
+struct IoState {
+    generation: AtomicU64,
+    pending: Mutex<HashMap<PathBuf, Pending>>,
+    latency: Mutex<WriteLatency>,
+    total_ms: AtomicU64,
+    /// 写线程当前写入的开始时间
+    busy_since: Mutex<Option<Instant>>,
+    // 测试时模拟慢速磁盘
+    #[cfg(test)]
+    delay: Mutex<Duration>,
+}
+
+impl IoState {
+    fn next_generation(&self) -> u64 {
+        self.generation.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// 写线程卡在一次已超过期限的写入上
+    fn stalled(&self, deadline: Duration) -> bool {
+        self.busy_since.lock().is_some_and(|t| t.elapsed() >= deadline)
+    }
+
+    /// 内容留在内存中（已有更新的版本时不覆盖）
+    fn hold(&self, name: &str, path: &Path, generation: u64, data: Vec<u8>, held: bool) {
+        let mut pending = self.pending.lock();
+        if pending.get(path).is_some_and(|p| p.generation > generation) {
+            return;
+        }
+        let held = held || pending.get(path).is_some_and(|p| p.held);
+        pending.insert(path.to_path_buf(), Pending { name: name.to_string(), generation, data, held });
+    }
+
+    fn finish(&self, job: &WriteJob, result: &Result<(), String>, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        {
+            let mut latency = self.latency.lock();
+            latency.samples += 1;
+            latency.last_ms = ms;
+            latency.max_ms = latency.max_ms.max(ms);
+            latency.avg_ms = (self.total_ms.fetch_add(ms, Ordering::Relaxed) + ms) / latency.samples;
+            if result.is_err() {
+                latency.failures += 1;
+            }
+        }
+        match result {
+            Ok(()) => {
+                let mut pending = self.pending.lock();
+                if pending.get(&job.path).is_some_and(|p| p.generation <= job.generation) {
+                    pending.remove(&job.path);
+                }
+            }
+            Err(_) => self.hold(&job.name, &job.path, job.generation, job.data.clone(), false),
+        }
+    }
+
+    #[cfg(test)]
+    fn simulate_delay(&self) {
+        let delay = *self.delay.lock();
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+
+    #[cfg(not(test))]
+    fn simulate_delay(&self) {}
+}
+
+fn writer_loop(io: Arc<IoState>, jobs: mpsc::Receiver<WriteJob>) {
+    for job in jobs {
+        let started = Instant::now();
+        *io.busy_since.lock() = Some(started);
+        io.simulate_delay();
+        let result = write_atomic(&job.path, &job.data);
+        io.finish(&job, &result, started.elapsed());
+        *io.busy_since.lock() = None;
+        let _ = job.reply.send(result);
+    }
+}
+
+type ErrorSink = Box<dyn Fn(BackgroundError) + Send + Sync>;
+
+#[derive(Default)]
+pub struct Storage {
+    // setup 时设置为 app_local_data_dir；未设置时（如测试）读写均为空操作
+    base_dir: RwLock<Option<PathBuf>>,
+    deadlines: RwLock<Deadlines>,
+    io: Arc<IoState>,
+    writer: OnceLock<Mutex<mpsc::Sender<WriteJob>>>,
+    slow_reads: Mutex<HashSet<String>>,
+    // 只读原因（数据目录被另一台机器占用）
+    read_only: RwLock<Option<String>>,
+    // 挂载 AppHandle 后指向后台错误总线
+    on_error: OnceLock<ErrorSink>,
+}
+
+impl Storage {
+    pub fn set_base_dir(&self, dir: PathBuf) {
+        println!("[Storage] Data dir: {}", dir.display());
+        *self.base_dir.write() = Some(dir);
+        // 切换档案后按新目录重新判断
+        self.slow_reads.lock().clear();
+    }
+
+    pub fn base_dir(&self) -> Option<PathBuf> {
+        self.base_dir.read().clone()
+    }
+
+    pub fn set_read_only(&self, reason: Option<String>) {
+        *self.read_only.write() = reason;
+    }
+
+    pub fn read_only_reason(&self) -> Option<String> {
+        self.read_only.read().clone()
+    }
+
+    pub fn set_error_sink(&self, sink: impl Fn(BackgroundError) + Send + Sync + 'static) {
+        let _ = self.on_error.set(Box::new(sink));
+    }
+
+    fn report(&self, code: &str, message: String) {
+        println!("[Storage] {}: {}", code, message);
+        if let Some(sink) = self.on_error.get() {
+            sink(BackgroundError::new("storage", code, message));
+        }
+    }
+
+    /// 读取 JSON 文件；文件不存在、解析失败或读取超时时返回 None
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let path = self.base_dir()?.join(name);
+        let content = self.read_with_deadline(name, path.clone())?;
+        match serde_json::from_str(&content) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("[Storage] Failed to parse {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn read_with_deadline(&self, name: &str, path: PathBuf) -> Option<String> {
+        let (tx, rx) = mpsc::channel();
+        let io = self.io.clone();
+        std::thread::spawn(move || {
+            io.simulate_delay();
+            let _ = tx.send(fs::read_to_string(&path).ok());
+        });
+        let deadline = self.deadlines.read().read;
+        match rx.recv_timeout(deadline) {
+            Ok(content) => content,
+            Err(_) => {
+                self.slow_reads.lock().insert(name.to_string());
+                self.report("read_timeout", format!("Reading {} took longer than {:?}", name, deadline));
+                None
+            }
+        }
+    }
+
+    /// 启动时读取超时的文件
+    pub fn read_timed_out(&self, name: &str) -> bool {
+        self.slow_reads.lock().contains(name)
+    }
+
+    pub fn slow_reads(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.slow_reads.lock().iter().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// 原子写入 JSON 文件；超时或失败时内容保留在内存中待重试
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<(), String> {
+        let Some(dir) = self.base_dir() else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(value)
+            .map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+        self.write(name, dir.join(name), json.into_bytes())
+    }
+
+    fn write(&self, name: &str, path: PathBuf, data: Vec<u8>) -> Result<(), String> {
+        let generation = self.io.next_generation();
+        if let Some(reason) = self.read_only.read().clone() {
+            self.io.hold(name, &path, generation, data, false);
+            return Err(format!("StorageReadOnly: {}; keeping {} in memory", reason, name));
+        }
+        if self.read_timed_out(name) {
+            self.io.hold(name, &path, generation, data, true);
+            return Err(format!("StorageDegraded: {} was not loaded; keeping changes in memory", name));
+        }
+        let deadline = self.deadlines.read().write;
+        if self.io.stalled(deadline) {
+            self.io.hold(name, &path, generation, data, false);
+            self.io.latency.lock().timeouts += 1;
+            let message = format!("Data dir is not responding; keeping {} in memory", name);
+            self.report("write_timeout", message.clone());
+            return Err(format!("StorageTimeout: {}", message));
+        }
+        let (reply, rx) = mpsc::channel();
+        let job = WriteJob { name: name.to_string(), path: path.clone(), generation, data: data.clone(), reply };
+        self.writer().lock().send(job).map_err(|_| "Storage writer stopped".to_string())?;
+        match rx.recv_timeout(deadline) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => {
+                self.report("write_failed", format!("{} (kept in memory)", e));
+                Err(e)
+            }
+            Err(_) => {
+                self.io.hold(name, &path, generation, data, false);
+                self.io.latency.lock().timeouts += 1;
+                let message = format!("Writing {} took longer than {:?}; keeping it in memory", name, deadline);
+                self.report("write_timeout", message.clone());
+                Err(format!("StorageTimeout: {}", message))
+            }
+        }
+    }
+
+    fn writer(&self) -> &Mutex<mpsc::Sender<WriteJob>> {
+        self.writer.get_or_init(|| {
+            let (tx, rx) = mpsc::channel();
+            let io = self.io.clone();
+            std::thread::Builder::new()
+                .name("storage-writer".into())
+                .spawn(move || writer_loop(io, rx))
+                .expect("failed to spawn storage writer");
+            Mutex::new(tx)
+        })
+    }
+
+    /// 重试内存中待写入的文件（维护任务调用），返回写入成功的个数
+    pub fn retry_pending(&self) -> Result<usize, String> {
+        // 只读期间不重试，解除后再写
+        if self.read_only.read().is_some() {
+            return Ok(0);
+        }
+        let due: Vec<(String, PathBuf, Vec<u8>)> = self
+            .io
+            .pending
+            .lock()
+            .iter()
+            .filter(|(_, p)| !p.held)
+            .map(|(path, p)| (p.name.clone(), path.clone(), p.data.clone()))
+            .collect();
+        let mut flushed = 0;
+        for (name, path, data) in due {
+            if self.write(&name, path, data).is_ok() {
+                flushed += 1;
+            }
+        }
+        let remaining = self.io.pending.lock().values().filter(|p| !p.held).count();
+        if remaining > 0 {
+            return Err(format!("{} files still pending", remaining));
+        }
+        Ok(flushed)
+    }
+
+    pub fn status(&self) -> StorageStatus {
+        let mut pending: Vec<String> = self.io.pending.lock().values().map(|p| p.name.clone()).collect();
+        pending.sort();
+        StorageStatus {
+            data_dir: self.base_dir().map(|d| d.display().to_string()),
+            write_latency: self.io.latency.lock().clone(),
+            pending,
+            slow_reads: self.slow_reads(),
+            degraded: Vec::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn simulate_slow_disk(&self, delay: Duration, deadline: Duration) {
+        *self.io.delay.lock() = delay;
+        *self.deadlines.write() = Deadlines { write: deadline, read: deadline };
+    }
+}
+
+/// 先写同目录下的临时文件，再 rename 覆盖目标文件
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+pub fn temp_dir(tag: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nl-test-{}-{}", tag, uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let storage = Storage::default();
+        // 未设置目录：空操作
+        assert!(storage.save("a.json", &1).is_ok());
+        assert_eq!(storage.load::<i32>("a.json"), None);
+
+        let dir = temp_dir("storage");
+        storage.set_base_dir(dir.clone());
+        storage.save("a.json", &vec![1, 2, 3]).unwrap();
+        assert_eq!(storage.load::<Vec<i32>>("a.json"), Some(vec![1, 2, 3]));
+        assert!(!dir.join("a.tmp").exists());
+
+        fs::write(dir.join("bad.json"), "{").unwrap();
+        assert_eq!(storage.load::<Vec<i32>>("bad.json"), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_sort_choice_survives_restart() {
+        use crate::commands::AppState;
+        use crate::store::SortMode;
+
+        let dir = temp_dir("view");
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        assert_eq!(state.resolve_sort(None), SortMode::Newest);
+        state.resolve_sort(Some(SortMode::UnreadFirst));
+
+        let restarted = AppState::default();
+        restarted.init_storage(dir.clone());
+        assert_eq!(restarted.resolve_sort(None), SortMode::UnreadFirst);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    fn wait_idle(storage: &Storage) {
+        let started = Instant::now();
+        while storage.io.busy_since.lock().is_some() && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_slow_writes_stay_in_memory_until_retried() {
+        let dir = temp_dir("slow-write");
+        let storage = Storage::default();
+        storage.set_base_dir(dir.clone());
+        storage.simulate_slow_disk(Duration::from_millis(400), Duration::from_millis(50));
+
+        let err = storage.save("a.json", &1).unwrap_err();
+        assert!(err.starts_with("StorageTimeout"), "{}", err);
+        // 写线程仍卡在 a.json 上：后续写入不再等待
+        let started = Instant::now();
+        assert!(storage.save("b.json", &2).unwrap_err().starts_with("StorageTimeout"));
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert_eq!(storage.status().pending, ["a.json", "b.json"]);
+
+        // a.json 最终写完即移出 pending；b.json 由重试写入
+        wait_idle(&storage);
+        storage.simulate_slow_disk(Duration::ZERO, WRITE_DEADLINE);
+        assert_eq!(storage.status().pending, ["b.json"]);
+        assert_eq!(storage.retry_pending(), Ok(1));
+        assert_eq!((storage.load::<i32>("a.json"), storage.load::<i32>("b.json")), (Some(1), Some(2)));
+
+        let status = storage.status();
+        assert!(status.pending.is_empty());
+        assert_eq!((status.write_latency.samples, status.write_latency.timeouts), (2, 2));
+        assert!(status.write_latency.max_ms >= 400);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_slow_reads_degrade_without_overwriting() {
+        use crate::commands::AppState;
+        use crate::startup::Subsystem;
+
+        let dir = temp_dir("slow-read");
+        fs::write(dir.join("settings.json"), "{}").unwrap();
+        let state = AppState::default();
+        state.storage.simulate_slow_disk(Duration::from_millis(100), Duration::from_millis(20));
+        state.init_storage(dir.clone());
+
+        // 以默认值就绪，同时标为降级
+        assert!(state.ensure_ready(Subsystem::Store).is_ok());
+        let status = state.storage_status();
+        assert_eq!(status.degraded, [Subsystem::Settings, Subsystem::Store]);
+        assert!(status.slow_reads.contains(&"settings.json".to_string()));
+        let events = state.events.take_captured();
+        assert_eq!(events.iter().filter(|(e, _)| e == "subsystem-degraded").count(), 2);
+
+        // 未读到的文件不写回
+        let err = state.storage.save("settings.json", &state.settings.read().clone()).unwrap_err();
+        assert!(err.starts_with("StorageDegraded"), "{}", err);
+        assert_eq!(state.storage.retry_pending(), Ok(0));
+        assert_eq!(fs::read_to_string(dir.join("settings.json")).unwrap(), "{}");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
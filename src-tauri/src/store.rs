@@ -0,0 +1,1636 @@
+//! 通知存储（内存版）：通知表、已读集合与按时间的索引放在同一把锁下增量维护，保证计数一致。
+//! 已读状态由桌面端决定（见 upsert）；派生结构的核对与重建见 integrity。
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::importance::{Importance, ImportanceMap};
+use crate::integrity::{Discrepancy, DiscrepancyKind, Structure};
+use crate::language::{self, Language};
+use crate::limits::InvalidArgument;
+use crate::normalize;
+use crate::search;
+use crate::settings::RetentionSettings;
+use crate::types::Notification;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Counts {
+    pub unread: usize,
+    pub total: usize,
+}
+
+/// 单个应用的计数；无包名的通知归入 package_name 为空的一组
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PackageCounts {
+    pub package_name: Option<String>,
+    pub unread: usize,
+    pub total: usize,
+}
+
+/// 计数 + 存储变更序号（同一临界区内读取，二者一定对应同一时刻）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct VersionedCounts {
+    pub unread: usize,
+    pub total: usize,
+    pub seq: u64,
+    /// 序号所属的流；变化（如恢复出厂设置后）说明 seq 重新开始，需全量同步
+    pub stream_id: String,
+}
+
+/// 列表排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+    /// 新 -> 旧
+    #[default]
+    #[serde(alias = "newest_first")]
+    Newest,
+    /// 旧 -> 新
+    #[serde(alias = "oldest_first")]
+    Oldest,
+    /// 未读在前，各组内新 -> 旧
+    UnreadFirst,
+    /// 按应用分组（通知最多的应用排最后），组内新 -> 旧
+    AppThenTime,
+    /// 按包名字母顺序分组（无包名的排最后），组内新 -> 旧
+    ByPackage,
+}
+
+/// 列表与批量操作共用的过滤条件；各条件同时满足才算匹配，未设置的条件不限制
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct NotificationFilter {
+    /// 包名（精确匹配）
+    pub package: Option<String>,
+    /// true 只要未读，false 只要已读
+    pub unread: Option<bool>,
+    /// 时间范围（含两端），按 sort_ts 比较；列表查询只遍历时间索引中的这一段
+    #[serde(alias = "since_ts")]
+    pub since: Option<i64>,
+    #[serde(alias = "until_ts")]
+    pub until: Option<i64>,
+    /// 按空白拆成多个词，每个词都出现在标题/正文/包名之一中（不区分大小写，见 search）
+    pub query: Option<String>,
+    pub language: Option<Language>,
+    /// true 只要静音的，false 排除静音的（见 mutes）
+    pub muted: Option<bool>,
+    /// true 只要归档的，false 排除归档的
+    pub archived: Option<bool>,
+}
+
+impl NotificationFilter {
+    pub fn validate(&self) -> Result<(), InvalidArgument> {
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            if until < since {
+                return Err(InvalidArgument::new("until_ts", format!("until_ts ({}) is before since_ts ({})", until, since)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, n: &Notification) -> bool {
+        if self.package.is_some() && n.package_name != self.package {
+            return false;
+        }
+        if self.unread.is_some_and(|unread| unread == n.read) {
+            return false;
+        }
+        let ts = sort_ts(n);
+        if self.since.is_some_and(|since| ts < since) || self.until.is_some_and(|until| ts > until) {
+            return false;
+        }
+        if let Some(language) = self.language {
+            // 升级前存下、尚未补算标记的通知当场检测，而不是直接排除（见 derived_upgrade）
+            let detected = n.language.unwrap_or_else(|| language::detect_notification(n.title.as_deref(), n.text.as_deref()));
+            if detected != language {
+                return false;
+            }
+        }
+        if self.muted.is_some_and(|muted| muted != n.muted) {
+            return false;
+        }
+        if self.archived.is_some_and(|archived| archived != n.archived) {
+            return false;
+        }
+        match self.query.as_deref() {
+            Some(q) => search::matches(n, &search::terms(q)),
+            None => true,
+        }
+    }
+}
+
+/// 时间索引的键：同一时间戳按 id 决定先后，保证分页稳定；列表查询直接按索引顺序遍历，不必每次排序
+type Key = (i64, String);
+
+/// 排序用时间戳：updated_at 优先，其次 posted_at
+pub fn sort_ts(n: &Notification) -> i64 {
+    n.updated_at.or(n.posted_at).unwrap_or_default()
+}
+
+fn key_of(n: &Notification) -> Key {
+    (sort_ts(n), n.id.clone())
+}
+
+/// updated_at/posted_at 早于 cutoff；没有时间的视为旧通知
+fn older_than(n: &Notification, cutoff: i64) -> bool {
+    n.updated_at.or(n.posted_at).is_none_or(|ts| ts < cutoff)
+}
+
+/// 索引中时间落在 [since, until] 内的一段；since > until 时为空
+fn in_range(set: &BTreeSet<Key>, since: Option<i64>, until: Option<i64>) -> std::collections::btree_set::Range<'_, Key> {
+    let since = since.unwrap_or(i64::MIN);
+    let until = until.unwrap_or(i64::MAX);
+    if since > until {
+        return set.range((Bound::Included((0, String::new())), Bound::Excluded((0, String::new()))));
+    }
+    let hi = match until.checked_add(1) {
+        Some(end) => Bound::Excluded((end, String::new())),
+        None => Bound::Unbounded,
+    };
+    set.range((Bound::Included((since, String::new())), hi))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upsert {
+    Inserted,
+    Updated { content_changed: bool },
+}
+
+/// 一次保留策略执行的结果
+#[derive(Debug, Default)]
+pub struct Eviction {
+    pub removed: Vec<Notification>,
+    /// 新近超出软配额的应用（每个应用只报告一次）
+    pub flooded: Vec<(Option<String>, usize)>,
+}
+
+/// 各重要性是否计入未读数，默认与默认映射表一致
+#[derive(Clone)]
+struct CountedImportance([bool; 5]);
+
+impl Default for CountedImportance {
+    fn default() -> Self {
+        Self(ImportanceMap::default().counted())
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct NotificationStore {
+    // id -> Notification
+    notifications: HashMap<String, Notification>,
+    // 已读集合（始终是 notifications 键的子集）
+    read_set: HashSet<String>,
+    // 时间索引：全部 / 未读
+    by_time: BTreeSet<Key>,
+    unread_by_time: BTreeSet<Key>,
+    // 按包名的时间索引（None 表示无包名）
+    by_package: HashMap<Option<String>, BTreeSet<Key>>,
+    // 置顶集合
+    pinned_set: HashSet<String>,
+    // 已发出过刷屏警告的应用
+    flood_warned: HashSet<Option<String>>,
+    // 暂缓中的通知：id -> Notification（snoozed_until 必有值）；不进索引也不计数，到期后作为未读重新入库
+    snoozed: HashMap<String, Notification>,
+    // 按重要性的未读计数（下标见 Importance::index）；只汇总设置中计入未读的级别
+    unread_by_importance: [usize; 5],
+    // 按包名、重要性的未读计数（没有未读的包名不保留）
+    unread_by_package: HashMap<Option<String>, [usize; 5]>,
+    // 计入未读数的重要性
+    counted_importance: CountedImportance,
+    // 变更序号：每次修改时在持锁期间递增，跨重启延续（见 stream_meta）
+    seq: u64,
+    // 序号所属的流 id
+    stream_id: String,
+}
+
+impl NotificationStore {
+    pub fn get(&self, id: &str) -> Option<&Notification> {
+        self.notifications.get(id)
+    }
+
+    /// 连接上的手机端 id 在存储中的 id（列表与暂缓区一起查）：(连接, id) 已有对应的通知时沿用，
+    /// 否则 id 空闲时直接使用，被另一连接占用时改用 "连接:id"。调用方须在同一次持锁中写入，见 upsert_from
+    pub fn scoped_id(&self, connection_id: &str, id: &str) -> String {
+        let lookup = |key: &str| self.notifications.get(key).or_else(|| self.snoozed.get(key));
+        let scoped = format!("{}:{}", connection_id, id);
+        if lookup(&scoped).is_some_and(|n| n.connection_id.as_deref() == Some(connection_id)) {
+            return scoped;
+        }
+        match lookup(id).map(|n| n.connection_id.as_deref()) {
+            // 没有连接的旧数据视为同一连接
+            None | Some(None) => id.to_string(),
+            Some(Some(owner)) if owner == connection_id => id.to_string(),
+            Some(Some(_)) => scoped,
+        }
+    }
+
+    /// 按 (连接, 手机端 id) 写入：在同一次持锁中确定存储 id 并写入，返回写入结果与存储 id
+    pub fn upsert_from(&mut self, connection_id: &str, mut n: Notification, mark_read: bool) -> (Upsert, String) {
+        n.id = self.scoped_id(connection_id, &n.id);
+        n.connection_id = Some(connection_id.to_string());
+        let id = n.id.clone();
+        (self.upsert(n, mark_read), id)
+    }
+
+    /// 写入一条来自手机端的通知。
+    /// 忽略载荷中的 `read`：新通知为未读；已存在时仅在标题/正文变化时重置为未读（按 normalize 比较，仅全角/变体选择符/空白不同不算变化）。
+    /// 已存在时保留原来的 posted_at（手机端重发更新时会带上新的时间，变化的时间记在 updated_at）。
+    /// `mark_read` 为桌面端的决定（如静音规则），为 true 时强制已读。
+    pub fn upsert(&mut self, mut n: Notification, mark_read: bool) -> Upsert {
+        // 暂缓中的通知：只更新暂缓区中的副本，到期时间、置顶与归档不变；
+        // mark_read 记在副本的 read 上，到期后照此出现（否则为未读，归档的仍为已读）
+        if let Some(old) = self.snoozed.get_mut(&n.id) {
+            let content_changed = !normalize::same(old.title.as_deref(), n.title.as_deref())
+                || !normalize::same(old.text.as_deref(), n.text.as_deref());
+            n.snoozed_until = old.snoozed_until;
+            n.pinned = old.pinned;
+            n.archived = old.archived;
+            n.posted_at = old.posted_at.or(n.posted_at);
+            n.corrected_posted_at = old.corrected_posted_at.or(n.corrected_posted_at);
+            n.read = mark_read;
+            *old = n;
+            self.bump_seq();
+            return Upsert::Updated { content_changed };
+        }
+        let result = match self.notifications.get(&n.id) {
+            None => Upsert::Inserted,
+            Some(old) => Upsert::Updated {
+                content_changed: !normalize::same(old.title.as_deref(), n.title.as_deref())
+                    || !normalize::same(old.text.as_deref(), n.text.as_deref()),
+            },
+        };
+
+        let archived = self.notifications.get(&n.id).is_some_and(|old| old.archived);
+        if let Some(old) = self.notifications.get(&n.id) {
+            n.posted_at = old.posted_at.or(n.posted_at);
+            n.corrected_posted_at = old.corrected_posted_at.or(n.corrected_posted_at);
+        }
+        let read = match result {
+            Upsert::Updated { content_changed: false } => self.read_set.contains(&n.id),
+            _ => false,
+        } || mark_read
+            || archived;
+
+        n.read = read;
+        n.archived = archived;
+        n.pinned = self.pinned_set.contains(&n.id);
+        if let Some(old) = self.notifications.remove(&n.id) {
+            self.unindex(&old);
+        }
+        if read {
+            self.read_set.insert(n.id.clone());
+        } else {
+            self.read_set.remove(&n.id);
+        }
+        self.index(&n);
+        self.notifications.insert(n.id.clone(), n);
+        self.bump_seq();
+        result
+    }
+
+    fn index(&mut self, n: &Notification) {
+        let key = key_of(n);
+        if !n.read {
+            self.unread_by_time.insert(key.clone());
+            self.unread_by_importance[n.importance.index()] += 1;
+            add_package_unread(&mut self.unread_by_package, n, true);
+        }
+        self.by_package
+            .entry(n.package_name.clone())
+            .or_default()
+            .insert(key.clone());
+        self.by_time.insert(key);
+    }
+
+    fn unindex(&mut self, n: &Notification) {
+        let key = key_of(n);
+        self.by_time.remove(&key);
+        if self.unread_by_time.remove(&key) {
+            self.unread_by_importance[n.importance.index()] -= 1;
+            add_package_unread(&mut self.unread_by_package, n, false);
+        }
+        if let Some(set) = self.by_package.get_mut(&n.package_name) {
+            set.remove(&key);
+            if set.is_empty() {
+                self.by_package.remove(&n.package_name);
+            }
+        }
+    }
+
+    /// 按排序方式遍历索引，过滤后分页返回
+    pub fn query(
+        &self,
+        sort: SortMode,
+        filter: impl Fn(&Notification) -> bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<Notification> {
+        self.query_range(sort, None, None, filter, offset, limit)
+    }
+
+    /// 按过滤条件查询；时间范围直接用于截取索引，不必遍历全部通知
+    pub fn query_filter(&self, sort: SortMode, filter: &NotificationFilter, offset: usize, limit: Option<usize>) -> Vec<Notification> {
+        self.query_range(sort, filter.since, filter.until, |n| filter.matches(n), offset, limit)
+    }
+
+    fn query_range(
+        &self,
+        sort: SortMode,
+        since: Option<i64>,
+        until: Option<i64>,
+        filter: impl Fn(&Notification) -> bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<Notification> {
+        let keys: Box<dyn Iterator<Item = &Key>> = match sort {
+            SortMode::Newest => Box::new(in_range(&self.by_time, since, until).rev()),
+            SortMode::Oldest => Box::new(in_range(&self.by_time, since, until)),
+            // 两趟：先未读索引，再从全量索引中取已读
+            SortMode::UnreadFirst => Box::new(
+                in_range(&self.unread_by_time, since, until)
+                    .rev()
+                    .chain(in_range(&self.by_time, since, until).rev().filter(|k| self.read_set.contains(&k.1))),
+            ),
+            SortMode::AppThenTime => {
+                let mut groups: Vec<(&Option<String>, &BTreeSet<Key>)> = self.by_package.iter().collect();
+                // 通知少的应用在前；数量相同按包名，无包名的排在同数量组最后
+                groups.sort_by(|a, b| {
+                    a.1.len()
+                        .cmp(&b.1.len())
+                        .then_with(|| a.0.is_none().cmp(&b.0.is_none()))
+                        .then_with(|| a.0.cmp(b.0))
+                });
+                Box::new(groups.into_iter().flat_map(move |(_, set)| in_range(set, since, until).rev()))
+            }
+            SortMode::ByPackage => {
+                let mut groups: Vec<(&Option<String>, &BTreeSet<Key>)> = self.by_package.iter().collect();
+                groups.sort_by(|a, b| a.0.is_none().cmp(&b.0.is_none()).then_with(|| a.0.cmp(b.0)));
+                Box::new(groups.into_iter().flat_map(move |(_, set)| in_range(set, since, until).rev()))
+            }
+        };
+        keys.filter_map(|k| self.notifications.get(&k.1))
+            .filter(|n| filter(n))
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// 匹配过滤条件的 id（按时间新 -> 旧）；指定包名时只遍历该包的索引
+    pub fn ids_where(&self, filter: &NotificationFilter) -> Vec<String> {
+        let keys: Box<dyn Iterator<Item = &Key>> = match &filter.package {
+            Some(_) => match self.by_package.get(&filter.package) {
+                Some(set) => Box::new(in_range(set, filter.since, filter.until).rev()),
+                None => return Vec::new(),
+            },
+            None => Box::new(in_range(&self.by_time, filter.since, filter.until).rev()),
+        };
+        keys.filter_map(|k| self.notifications.get(&k.1))
+            .filter(|n| filter.matches(n))
+            .map(|n| n.id.clone())
+            .collect()
+    }
+
+    /// 时间落在 [since, until] 内的通知条数
+    pub fn count_between(&self, since: i64, until: i64) -> usize {
+        in_range(&self.by_time, Some(since), Some(until)).count()
+    }
+
+    /// 把分组汇总通知的 id 展开为同组（同一应用、同一 group_key）的全部通知，其余 id 原样保留；结果去重
+    pub fn expand_group_summaries(&self, ids: &[String]) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut expanded = Vec::with_capacity(ids.len());
+        for id in ids {
+            if seen.insert(id.clone()) {
+                expanded.push(id.clone());
+            }
+            let Some(summary) = self.notifications.get(id).filter(|n| n.is_group_summary && n.group_key.is_some()) else {
+                continue;
+            };
+            let Some(keys) = self.by_package.get(&summary.package_name) else {
+                continue;
+            };
+            for (_, member) in keys {
+                if self.notifications.get(member).is_some_and(|n| n.group_key == summary.group_key) && seen.insert(member.clone()) {
+                    expanded.push(member.clone());
+                }
+            }
+        }
+        expanded
+    }
+
+    /// 标记已读，返回实际发生变化的条数；不存在的 id 直接跳过
+    pub fn mark_read(&mut self, ids: &[String]) -> usize {
+        let mut changed = 0;
+        for id in ids {
+            if let Some(n) = self.notifications.get_mut(id) {
+                if !n.read {
+                    n.read = true;
+                    changed += 1;
+                    self.unread_by_time.remove(&key_of(n));
+                    self.unread_by_importance[n.importance.index()] -= 1;
+                    add_package_unread(&mut self.unread_by_package, n, false);
+                }
+                self.read_set.insert(id.clone());
+            }
+        }
+        if changed > 0 {
+            self.bump_seq();
+        }
+        changed
+    }
+
+    /// 标记未读（撤销误点的已读），返回实际发生变化的条数；未读过、已归档、静音（静音的从不计为未读）或不存在的 id 直接跳过
+    pub fn mark_unread(&mut self, ids: &[String]) -> usize {
+        let mut changed = 0;
+        for id in ids {
+            if let Some(n) = self.notifications.get_mut(id) {
+                if n.archived || n.muted {
+                    continue;
+                }
+                if n.read {
+                    n.read = false;
+                    changed += 1;
+                    self.unread_by_time.insert(key_of(n));
+                    self.unread_by_importance[n.importance.index()] += 1;
+                    add_package_unread(&mut self.unread_by_package, n, true);
+                }
+                self.read_set.remove(id);
+            }
+        }
+        if changed > 0 {
+            self.bump_seq();
+        }
+        changed
+    }
+
+    /// 把时间早于 cutoff 的未读通知（置顶与高重要性的除外）标记为已读，返回被标记的通知。
+    /// 沿未读时间索引从旧到新扫描，到 cutoff 即停止；再次执行不会重复标记。
+    pub fn mark_read_older_than(&mut self, cutoff: i64) -> Vec<Notification> {
+        let ids: Vec<String> = self
+            .unread_by_time
+            .iter()
+            .take_while(|(ts, _)| *ts < cutoff)
+            .filter(|(_, id)| !self.pinned_set.contains(id))
+            .filter(|(_, id)| self.notifications.get(id).is_some_and(|n| !matches!(n.importance, Importance::High | Importance::Urgent)))
+            .map(|(_, id)| id.clone())
+            .collect();
+        self.mark_read(&ids);
+        ids.iter().filter_map(|id| self.notifications.get(id).cloned()).collect()
+    }
+
+    /// 按 is_muted 重新设置静音标记，返回标记变化的 id；新静音的通知同时标为已读
+    pub fn refresh_muted(&mut self, is_muted: impl Fn(&Notification) -> bool) -> Vec<String> {
+        let changed: Vec<(String, bool)> = self
+            .notifications
+            .values()
+            .filter_map(|n| {
+                let muted = is_muted(n);
+                (muted != n.muted).then(|| (n.id.clone(), muted))
+            })
+            .collect();
+        if changed.is_empty() {
+            return Vec::new();
+        }
+        for (id, muted) in &changed {
+            if let Some(n) = self.notifications.get_mut(id) {
+                n.muted = *muted;
+            }
+        }
+        let newly: Vec<String> = changed.iter().filter(|(_, muted)| *muted).map(|(id, _)| id.clone()).collect();
+        self.mark_read(&newly);
+        self.bump_seq();
+        changed.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// 把时间晚于 now 且满足 pred 的通知改为 now 并重建索引（系统时间向后跳变后修正），返回其 id
+    pub fn restamp_future(&mut self, now: i64, pred: impl Fn(&Notification) -> bool) -> Vec<String> {
+        let ids: Vec<String> = self
+            .by_time
+            .range((now + 1, String::new())..)
+            .filter(|(_, id)| self.notifications.get(id).is_some_and(&pred))
+            .map(|(_, id)| id.clone())
+            .collect();
+        for id in &ids {
+            let Some(mut n) = self.notifications.remove(id) else {
+                continue;
+            };
+            self.unindex(&n);
+            n.posted_at = Some(now);
+            if n.updated_at.is_some() {
+                n.updated_at = Some(now);
+            }
+            self.index(&n);
+            self.notifications.insert(id.clone(), n);
+        }
+        if !ids.is_empty() {
+            self.bump_seq();
+        }
+        ids
+    }
+
+    /// 归档（同时标为已读，手机端更新也不会重置为未读）/取消归档（保持已读），返回实际变化的条数
+    pub fn set_archived(&mut self, ids: &[String], archived: bool) -> usize {
+        if archived {
+            self.mark_read(ids);
+        }
+        let mut changed = 0;
+        for id in ids {
+            if let Some(n) = self.notifications.get_mut(id) {
+                if n.archived != archived {
+                    n.archived = archived;
+                    changed += 1;
+                }
+            }
+        }
+        if changed > 0 {
+            self.bump_seq();
+        }
+        changed
+    }
+
+    /// 设置/取消置顶，返回实际发生变化的条数
+    pub fn set_pinned(&mut self, ids: &[String], pinned: bool) -> usize {
+        let mut changed = 0;
+        for id in ids {
+            if let Some(n) = self.notifications.get_mut(id) {
+                if n.pinned != pinned {
+                    n.pinned = pinned;
+                    changed += 1;
+                }
+                if pinned {
+                    self.pinned_set.insert(id.clone());
+                } else {
+                    self.pinned_set.remove(id);
+                }
+            }
+        }
+        if changed > 0 {
+            self.bump_seq();
+        }
+        changed
+    }
+
+    /// 执行保留策略：总数超过上限时，先从超出软配额的应用中淘汰其最旧的已读通知，
+    /// 仍超出时再按全局时间从旧到新淘汰；置顶通知始终保留。
+    pub fn enforce_retention(&mut self, retention: &RetentionSettings) -> Eviction {
+        let quota = retention.package_quota();
+        let mut eviction = Eviction::default();
+
+        for (pkg, set) in &self.by_package {
+            if set.len() > quota && self.flood_warned.insert(pkg.clone()) {
+                eviction.flooded.push((pkg.clone(), set.len()));
+            }
+        }
+
+        let mut excess = self.notifications.len().saturating_sub(retention.max_items);
+        if excess == 0 {
+            return eviction;
+        }
+
+        // 第一轮：超配额应用自己的已读通知
+        let mut victims = Vec::new();
+        let mut over: Vec<(&Option<String>, &BTreeSet<Key>)> =
+            self.by_package.iter().filter(|(_, set)| set.len() > quota).collect();
+        // 超得最多的应用先淘汰
+        over.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(b.0)));
+        for (_, set) in over {
+            let allowance = (set.len() - quota).min(excess);
+            let picked: Vec<String> = set
+                .iter()
+                .filter(|k| self.read_set.contains(&k.1) && !self.pinned_set.contains(&k.1))
+                .take(allowance)
+                .map(|k| k.1.clone())
+                .collect();
+            excess -= picked.len();
+            victims.extend(picked);
+            if excess == 0 {
+                break;
+            }
+        }
+
+        // 第二轮：全局最旧的已读，第三轮：仍不够时才淘汰最旧的未读（都跳过置顶与已选中的）
+        for read_only in [true, false] {
+            if excess == 0 {
+                break;
+            }
+            let chosen: HashSet<&String> = victims.iter().collect();
+            let more: Vec<String> = self
+                .by_time
+                .iter()
+                .filter(|k| !self.pinned_set.contains(&k.1) && !chosen.contains(&k.1))
+                .filter(|k| !read_only || self.read_set.contains(&k.1))
+                .take(excess)
+                .map(|k| k.1.clone())
+                .collect();
+            excess -= more.len();
+            victims.extend(more);
+        }
+
+        eviction.removed = self.remove_many(&victims);
+        eviction
+    }
+
+    /// 暂缓一条通知直到 until：从列表中移出（如已在列表中）并保存到暂缓区
+    pub fn snooze(&mut self, mut n: Notification, until: i64) {
+        if let Some(old) = self.remove(&n.id) {
+            n.pinned = old.pinned;
+            n.archived = old.archived;
+        }
+        // 到期后为未读；静音的仍为已读
+        n.read = n.muted;
+        n.snoozed_until = Some(until);
+        self.snoozed.insert(n.id.clone(), n);
+        self.bump_seq();
+    }
+
+    /// 把列表中的通知移入暂缓区；已在暂缓区的只更新到期时间。返回被暂缓的通知，不存在的 id 跳过
+    pub fn snooze_ids(&mut self, ids: &[String], until: i64) -> Vec<Notification> {
+        let mut snoozed = Vec::new();
+        for id in ids {
+            let Some(mut n) = self.take(id) else {
+                continue;
+            };
+            n.snoozed_until = Some(until);
+            snoozed.push(n.clone());
+            self.snoozed.insert(n.id.clone(), n);
+        }
+        if !snoozed.is_empty() {
+            self.bump_seq();
+        }
+        snoozed
+    }
+
+    /// 按保存时的原样写回（已读、置顶、归档与暂缓状态取自通知本身），用于从持久化快照恢复；整批只递增一次序号
+    pub fn restore_all(&mut self, list: Vec<Notification>) {
+        for mut n in list {
+            self.take(&n.id);
+            if n.snoozed_until.is_some() {
+                self.snoozed.insert(n.id.clone(), n);
+                continue;
+            }
+            n.read |= n.archived;
+            if n.read {
+                self.read_set.insert(n.id.clone());
+            }
+            if n.pinned {
+                self.pinned_set.insert(n.id.clone());
+            }
+            self.index(&n);
+            self.notifications.insert(n.id.clone(), n);
+        }
+        self.bump_seq();
+    }
+
+    /// 取出所有到期的暂缓通知并作为未读重新入库（置顶与归档保持，归档的仍为已读），按到期时间先后返回
+    pub fn wake_due(&mut self, now: i64) -> Vec<Notification> {
+        let mut due: Vec<Notification> = self
+            .snoozed
+            .values()
+            .filter(|n| n.snoozed_until.is_some_and(|t| t <= now))
+            .cloned()
+            .collect();
+        due.sort_by(|a, b| a.snoozed_until.cmp(&b.snoozed_until).then_with(|| a.id.cmp(&b.id)));
+
+        let mut woken = Vec::with_capacity(due.len());
+        for mut n in due {
+            self.snoozed.remove(&n.id);
+            let (pinned, archived, read) = (n.pinned, n.archived, n.read);
+            n.snoozed_until = None;
+            let id = n.id.clone();
+            self.upsert(n, read);
+            if pinned {
+                self.set_pinned(std::slice::from_ref(&id), true);
+            }
+            if archived {
+                self.set_archived(std::slice::from_ref(&id), true);
+            }
+            if let Some(n) = self.get(&id) {
+                woken.push(n.clone());
+            }
+        }
+        woken
+    }
+
+    /// 暂缓中的通知（按到期时间先后）
+    pub fn snoozed(&self) -> Vec<Notification> {
+        let mut list: Vec<Notification> = self.snoozed.values().cloned().collect();
+        list.sort_by(|a, b| a.snoozed_until.cmp(&b.snoozed_until).then_with(|| a.id.cmp(&b.id)));
+        list
+    }
+
+    /// 删除一条通知（列表或暂缓区中的）
+    pub fn remove(&mut self, id: &str) -> Option<Notification> {
+        let n = self.take(id)?;
+        self.bump_seq();
+        Some(n)
+    }
+
+    /// 批量删除，整批只递增一次序号
+    pub fn remove_many(&mut self, ids: &[String]) -> Vec<Notification> {
+        let removed: Vec<Notification> = ids.iter().filter_map(|id| self.take(id)).collect();
+        if !removed.is_empty() {
+            self.bump_seq();
+        }
+        removed
+    }
+
+    /// 删除某个应用的全部通知（含暂缓区中的），包名精确匹配；整批只递增一次序号
+    pub fn remove_package(&mut self, package: &str) -> Vec<Notification> {
+        let mut ids: Vec<String> = self
+            .by_package
+            .get(&Some(package.to_string()))
+            .map(|set| set.iter().map(|k| k.1.clone()).collect())
+            .unwrap_or_default();
+        ids.extend(self.snoozed.values().filter(|n| n.package_name.as_deref() == Some(package)).map(|n| n.id.clone()));
+        self.remove_many(&ids)
+    }
+
+    /// 用户删除已读通知（delete_read）：全部已读的（含归档），置顶的保留；给出 cutoff 时只删早于它的
+    pub fn remove_read(&mut self, cutoff: Option<i64>) -> Vec<Notification> {
+        self.remove_read_where(|n| cutoff.is_none_or(|cutoff| older_than(n, cutoff)))
+    }
+
+    /// 保留策略按天数清理：早于 cutoff 的已读通知，归档的同样清理，置顶的保留
+    pub fn purge_read_before(&mut self, cutoff: i64) -> Vec<Notification> {
+        self.remove_read_where(|n| older_than(n, cutoff))
+    }
+
+    fn remove_read_where(&mut self, pred: impl Fn(&Notification) -> bool) -> Vec<Notification> {
+        let ids: Vec<String> = self
+            .read_set
+            .iter()
+            .filter(|id| !self.pinned_set.contains(*id))
+            .filter(|id| self.notifications.get(*id).is_none_or(&pred))
+            .cloned()
+            .collect();
+        self.remove_many(&ids)
+    }
+
+    fn take(&mut self, id: &str) -> Option<Notification> {
+        if let Some(n) = self.snoozed.remove(id) {
+            return Some(n);
+        }
+        self.read_set.remove(id);
+        self.pinned_set.remove(id);
+        let n = self.notifications.remove(id)?;
+        self.unindex(&n);
+        Some(n)
+    }
+
+    /// 清空，返回被清除的通知
+    pub fn clear(&mut self) -> Vec<Notification> {
+        let mut n: Vec<Notification> = self.notifications.drain().map(|(_, n)| n).collect();
+        n.extend(self.snoozed.drain().map(|(_, n)| n));
+        self.read_set.clear();
+        self.pinned_set.clear();
+        self.flood_warned.clear();
+        self.by_time.clear();
+        self.unread_by_time.clear();
+        self.unread_by_importance = [0; 5];
+        self.unread_by_package.clear();
+        self.by_package.clear();
+        self.bump_seq();
+        n
+    }
+
+    pub fn counts(&self) -> Counts {
+        let total = self.notifications.len();
+        let unread = self.counted_unread(&self.unread_by_importance);
+        Counts { unread, total }
+    }
+
+    /// 按应用的计数（未读多的在前），各组之和与 counts 一致；由索引增量维护，不遍历通知
+    pub fn counts_by_package(&self) -> Vec<PackageCounts> {
+        let mut counts: Vec<PackageCounts> = self
+            .by_package
+            .iter()
+            .map(|(package, keys)| PackageCounts {
+                package_name: package.clone(),
+                unread: self.unread_by_package.get(package).map_or(0, |c| self.counted_unread(c)),
+                total: keys.len(),
+            })
+            .collect();
+        counts.sort_by(|a, b| {
+            b.unread
+                .cmp(&a.unread)
+                .then_with(|| b.total.cmp(&a.total))
+                .then_with(|| a.package_name.is_none().cmp(&b.package_name.is_none()))
+                .then_with(|| a.package_name.cmp(&b.package_name))
+        });
+        counts
+    }
+
+    fn counted_unread(&self, by_importance: &[usize; 5]) -> usize {
+        Importance::ALL.iter().filter(|i| self.counted_importance.0[i.index()]).map(|i| by_importance[i.index()]).sum()
+    }
+
+    /// 满足过滤条件的计数（徽标显示筛选后的视图）；未读数同样只计入 counted_importance 中的重要性
+    pub fn counts_filtered(&self, filter: &NotificationFilter) -> Counts {
+        let mut counts = Counts { unread: 0, total: 0 };
+        for n in self.notifications.values().filter(|n| filter.matches(n)) {
+            counts.total += 1;
+            if !n.read && self.counted_importance.0[n.importance.index()] {
+                counts.unread += 1;
+            }
+        }
+        counts
+    }
+
+    /// 修改计入未读数的重要性，只重新汇总各类计数；返回未读数是否变化
+    pub fn set_counted_importance(&mut self, counted: [bool; 5]) -> bool {
+        if self.counted_importance.0 == counted {
+            return false;
+        }
+        let before = self.counts().unread;
+        self.counted_importance.0 = counted;
+        let changed = self.counts().unread != before;
+        if changed {
+            self.bump_seq();
+        }
+        changed
+    }
+
+    pub fn versioned_counts(&self) -> VersionedCounts {
+        let Counts { unread, total } = self.counts();
+        VersionedCounts { unread, total, seq: self.seq, stream_id: self.stream_id.clone() }
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// 推进变更序号。u64 按任何实际速率都用不完；万一用完不回绕到旧序号（客户端会误以为没有变化），
+    /// 而是开始新的流，客户端看到 stream_id 变化后全量同步
+    fn bump_seq(&mut self) {
+        match self.seq.checked_add(1) {
+            Some(seq) => self.seq = seq,
+            None => {
+                self.stream_id = uuid::Uuid::new_v4().to_string();
+                self.seq = 1;
+                println!("[Store] Change sequence exhausted, starting stream {}", self.stream_id);
+            }
+        }
+    }
+
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+
+    /// 已分配但未使用的槽位，加上已不存在应用的刷屏标记
+    pub fn dead_weight(&self) -> usize {
+        let stale_flags = self.flood_warned.iter().filter(|p| !self.by_package.contains_key(*p)).count();
+        self.notifications.capacity() - self.notifications.len()
+            + (self.read_set.capacity() - self.read_set.len())
+            + (self.pinned_set.capacity() - self.pinned_set.len())
+            + (self.snoozed.capacity() - self.snoozed.len())
+            + (self.by_package.capacity() - self.by_package.len())
+            + stale_flags
+    }
+
+    /// 从索引键 after 之后按时间顺序为至多 limit 条通知补算派生字段（fill 不得改动排序与计数用到的字段），
+    /// 返回修改的条数与下一批的起点；走到末尾时一并处理暂缓中的通知，起点为 None
+    pub fn fill_derived(
+        &mut self,
+        after: Option<&(i64, String)>,
+        limit: usize,
+        fill: impl Fn(&mut Notification) -> bool,
+    ) -> (usize, Option<(i64, String)>) {
+        let start = after.map_or(Bound::Unbounded, |k| Bound::Excluded(k.clone()));
+        let keys: Vec<Key> = self.by_time.range((start, Bound::Unbounded)).take(limit).cloned().collect();
+        let mut changed = 0;
+        for (_, id) in &keys {
+            if self.notifications.get_mut(id).is_some_and(&fill) {
+                changed += 1;
+            }
+        }
+        let next = if keys.len() < limit {
+            for n in self.snoozed.values_mut() {
+                if fill(n) {
+                    changed += 1;
+                }
+            }
+            None
+        } else {
+            keys.last().cloned()
+        };
+        if changed > 0 {
+            self.bump_seq();
+        }
+        (changed, next)
+    }
+
+    /// 按实际条数重建的副本：大量删除后哈希表容量不会回落（耗时，应在锁外对快照调用）
+    pub fn compacted(mut self) -> Self {
+        self.notifications.shrink_to_fit();
+        self.read_set.shrink_to_fit();
+        self.pinned_set.shrink_to_fit();
+        self.snoozed.shrink_to_fit();
+        self.by_package.shrink_to_fit();
+        self.unread_by_package.shrink_to_fit();
+        self
+    }
+
+    /// 用压缩后的副本替换数据；计数口径、序号等取自当前存储。
+    /// 副本必须取自当前序号（调用方检查），否则会丢失其间的修改。
+    pub fn adopt(&mut self, compacted: Self) {
+        debug_assert_eq!((compacted.seq, compacted.stream_id.as_str()), (self.seq, self.stream_id.as_str()));
+        self.notifications = compacted.notifications;
+        self.read_set = compacted.read_set;
+        self.pinned_set = compacted.pinned_set;
+        self.snoozed = compacted.snoozed;
+        self.by_time = compacted.by_time;
+        self.unread_by_time = compacted.unread_by_time;
+        self.by_package = compacted.by_package;
+        self.unread_by_importance = compacted.unread_by_importance;
+        self.unread_by_package = compacted.unread_by_package;
+        let by_package = &self.by_package;
+        self.flood_warned.retain(|p| by_package.contains_key(p));
+        self.flood_warned.shrink_to_fit();
+    }
+
+    /// 启动时恢复（或重置时开始新的）序号流
+    pub fn restore_stream(&mut self, stream_id: String, seq: u64) {
+        self.stream_id = stream_id;
+        self.seq = seq;
+    }
+
+    /// 以通知表为准核对已读/置顶集合、各时间索引与未读计数，以及暂缓区，返回发现的不一致。
+    /// 索引键必须等于 key_of(通知)，键的时间戳过期即说明该条在索引中的位置（排序）错误。
+    pub(crate) fn integrity_issues(&self) -> Vec<Discrepancy> {
+        let mut issues = Vec::new();
+        let mut by_time = BTreeSet::new();
+        let mut unread_by_time = BTreeSet::new();
+        let mut by_package: HashMap<Option<String>, BTreeSet<Key>> = HashMap::new();
+        let mut unread_by_importance = [0usize; 5];
+        let mut unread_by_package: HashMap<Option<String>, [usize; 5]> = HashMap::new();
+        for (id, n) in &self.notifications {
+            if n.id != *id {
+                issues.push(Discrepancy::new(Structure::Notifications, DiscrepancyKind::Invalid, Some(id), format!("stored under {} but id is {}", id, n.id)));
+            }
+            if n.read != self.read_set.contains(id) {
+                issues.push(Discrepancy::new(Structure::ReadSet, DiscrepancyKind::FlagMismatch, Some(id), format!("read={}", n.read)));
+            }
+            if n.pinned != self.pinned_set.contains(id) {
+                issues.push(Discrepancy::new(Structure::PinnedSet, DiscrepancyKind::FlagMismatch, Some(id), format!("pinned={}", n.pinned)));
+            }
+            if self.snoozed.contains_key(id) {
+                issues.push(Discrepancy::new(Structure::Snoozed, DiscrepancyKind::Overlap, Some(id), "also in the list".to_string()));
+            }
+            let key = (sort_ts(n), id.clone());
+            if !n.read {
+                unread_by_time.insert(key.clone());
+                unread_by_importance[n.importance.index()] += 1;
+                add_package_unread(&mut unread_by_package, n, true);
+            }
+            by_package.entry(n.package_name.clone()).or_default().insert(key.clone());
+            by_time.insert(key);
+        }
+        for (structure, set) in [(Structure::ReadSet, &self.read_set), (Structure::PinnedSet, &self.pinned_set)] {
+            for id in set.iter().filter(|id| !self.notifications.contains_key(*id)) {
+                issues.push(Discrepancy::new(structure, DiscrepancyKind::Stale, Some(id), "no such notification".to_string()));
+            }
+        }
+        diff_index(Structure::ByTime, &by_time, &self.by_time, &mut issues);
+        diff_index(Structure::UnreadByTime, &unread_by_time, &self.unread_by_time, &mut issues);
+        let empty = BTreeSet::new();
+        let packages: HashSet<&Option<String>> = by_package.keys().chain(self.by_package.keys()).collect();
+        for package in packages {
+            let actual = self.by_package.get(package);
+            if actual.is_some_and(|set| set.is_empty()) {
+                issues.push(Discrepancy::new(Structure::ByPackage, DiscrepancyKind::Stale, None, format!("empty index for {:?}", package)));
+            }
+            diff_index(Structure::ByPackage, by_package.get(package).unwrap_or(&empty), actual.unwrap_or(&empty), &mut issues);
+        }
+        for importance in Importance::ALL {
+            let (expected, actual) = (unread_by_importance[importance.index()], self.unread_by_importance[importance.index()]);
+            if expected != actual {
+                issues.push(Discrepancy::new(
+                    Structure::UnreadCounters,
+                    DiscrepancyKind::CounterMismatch,
+                    None,
+                    format!("{:?}: expected {}, found {}", importance, expected, actual).to_lowercase(),
+                ));
+            }
+        }
+        if unread_by_package != self.unread_by_package {
+            issues.push(Discrepancy::new(
+                Structure::UnreadCounters,
+                DiscrepancyKind::CounterMismatch,
+                None,
+                "per-package unread counts differ".to_string(),
+            ));
+        }
+        for (id, n) in &self.snoozed {
+            if n.snoozed_until.is_none() || n.id != *id {
+                issues.push(Discrepancy::new(Structure::Snoozed, DiscrepancyKind::Invalid, Some(id), "missing snoozed_until or mismatched id".to_string()));
+            }
+        }
+        issues
+    }
+
+    /// 以通知表为准重建全部派生结构；与列表重复的暂缓项丢弃，缺少到期时间的暂缓项立即到期
+    pub(crate) fn rebuild_derived(&mut self) {
+        for (id, n) in self.notifications.iter_mut() {
+            n.id = id.clone();
+        }
+        self.read_set = self.notifications.values().filter(|n| n.read).map(|n| n.id.clone()).collect();
+        self.pinned_set = self.notifications.values().filter(|n| n.pinned).map(|n| n.id.clone()).collect();
+        self.by_time.clear();
+        self.unread_by_time.clear();
+        self.by_package.clear();
+        self.unread_by_importance = [0; 5];
+        self.unread_by_package.clear();
+        let all: Vec<Notification> = self.notifications.values().cloned().collect();
+        for n in &all {
+            self.index(n);
+        }
+        let notifications = &self.notifications;
+        self.snoozed.retain(|id, _| !notifications.contains_key(id));
+        for (id, n) in self.snoozed.iter_mut() {
+            n.id = id.clone();
+            n.snoozed_until.get_or_insert(0);
+        }
+        self.bump_seq();
+    }
+}
+
+/// 增减某通知所属包名的未读计数，减到全为 0 时移除该包名
+fn add_package_unread(counts: &mut HashMap<Option<String>, [usize; 5]>, n: &Notification, add: bool) {
+    let entry = counts.entry(n.package_name.clone()).or_default();
+    if add {
+        entry[n.importance.index()] += 1;
+    } else {
+        entry[n.importance.index()] -= 1;
+        if entry.iter().all(|c| *c == 0) {
+            counts.remove(&n.package_name);
+        }
+    }
+}
+
+/// 比较期望与实际的索引：缺少的键与多出的键
+fn diff_index(structure: Structure, expected: &BTreeSet<Key>, actual: &BTreeSet<Key>, issues: &mut Vec<Discrepancy>) {
+    for (ts, id) in expected.difference(actual) {
+        issues.push(Discrepancy::new(structure, DiscrepancyKind::Missing, Some(id), format!("key ({}, {})", ts, id)));
+    }
+    for (ts, id) in actual.difference(expected) {
+        issues.push(Discrepancy::new(structure, DiscrepancyKind::Stale, Some(id), format!("key ({}, {})", ts, id)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn notif(id: &str, title: &str, text: &str, read: bool) -> Notification {
+        Notification {
+            id: id.into(),
+            title: Some(title.into()),
+            text: Some(text.into()),
+            read,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_incoming_never_sets_read() {
+        let mut store = NotificationStore::default();
+        assert_eq!(store.upsert(notif("1", "t", "x", true), false), Upsert::Inserted);
+        assert!(!store.get("1").unwrap().read);
+        assert_eq!(store.counts().unread, 1);
+    }
+
+    #[test]
+    fn test_update_keeps_read_unless_content_changed() {
+        let mut store = NotificationStore::default();
+        store.upsert(notif("1", "t", "x", false), false);
+        store.mark_read(&["1".to_string()]);
+
+        // 内容未变（如进度条刷新）的更新载荷带着 read=false，不应复活为未读
+        let r = store.upsert(notif("1", "t", "x", false), false);
+        assert_eq!(r, Upsert::Updated { content_changed: false });
+        assert!(store.get("1").unwrap().read);
+        assert_eq!(store.counts().unread, 0);
+
+        // 仅变体选择符或空白不同：视为未变化
+        store.upsert(notif("1", "t ", "x", false), false);
+        assert!(store.get("1").unwrap().read);
+
+        // 可见内容变化：重新未读
+        let r = store.upsert(notif("1", "t", "new text", true), false);
+        assert_eq!(r, Upsert::Updated { content_changed: true });
+        assert!(!store.get("1").unwrap().read);
+        assert_eq!(store.counts().unread, 1);
+    }
+
+    #[test]
+    fn test_mark_read_before_stale_payload_flush() {
+        // 合并中的更新载荷是在 mark_read 之前生成的快照，刷入时不能覆盖已读
+        let mut store = NotificationStore::default();
+        store.upsert(notif("1", "t", "x", false), false);
+        let stale_snapshot = store.get("1").unwrap().clone();
+        store.mark_read(&["1".to_string()]);
+        store.upsert(stale_snapshot, false);
+        assert!(store.get("1").unwrap().read);
+    }
+
+    #[test]
+    fn test_mark_read_skips_unknown_ids() {
+        let mut store = NotificationStore::default();
+        store.upsert(notif("1", "t", "x", false), false);
+        assert_eq!(store.mark_read(&["1".to_string(), "ghost".to_string()]), 1);
+        let c = store.counts();
+        assert_eq!((c.unread, c.total), (0, 1));
+        store.remove("1");
+        let c = store.counts();
+        assert_eq!((c.unread, c.total), (0, 0));
+    }
+
+    #[test]
+    fn test_mark_unread_reverses_mark_read() {
+        let mut store = NotificationStore::default();
+        store.upsert(notif("1", "t", "x", false), false);
+        store.upsert(notif("2", "t", "y", false), false);
+        store.mark_read(&["1".to_string()]);
+        let seq = store.seq();
+        // 从未读过的与不存在的 id 不算变化
+        assert_eq!(store.mark_unread(&["2".to_string(), "ghost".to_string()]), 0);
+        assert_eq!(store.seq(), seq);
+        assert_eq!(store.mark_unread(&["1".to_string(), "2".to_string()]), 1);
+        assert!(!store.get("1").unwrap().read);
+        assert_eq!(store.counts().unread, 2);
+        assert_eq!(store.query(SortMode::UnreadFirst, |n| !n.read, 0, None).len(), 2);
+        assert!(store.integrity_issues().is_empty());
+        // 同内容的更新不会把它重新变成已读
+        store.upsert(notif("1", "t", "x", false), false);
+        assert!(!store.get("1").unwrap().read);
+
+        // 静音的通知从不计为未读
+        store.upsert(Notification { muted: true, ..notif("3", "t", "z", false) }, true);
+        assert_eq!(store.mark_unread(&["3".to_string()]), 0);
+        assert!(store.get("3").unwrap().read);
+        assert_eq!(store.counts().unread, 2);
+    }
+
+    #[test]
+    fn test_archived_stays_read_and_filtered() {
+        let mut store = NotificationStore::default();
+        store.upsert(notif("1", "t", "x", false), false);
+        store.upsert(notif("2", "t", "y", false), false);
+        assert_eq!(store.set_archived(&["1".to_string(), "ghost".to_string()], true), 1);
+        assert_eq!(store.set_archived(&["1".to_string()], true), 0);
+        let c = store.counts();
+        assert_eq!((c.unread, c.total), (1, 2));
+        // 手机端改了内容也不会重新变为未读；撤销已读同样跳过
+        store.upsert(notif("1", "t", "changed", false), false);
+        assert_eq!(store.mark_unread(&["1".to_string()]), 0);
+        let n = store.get("1").unwrap();
+        assert!(n.archived && n.read);
+        assert!(store.integrity_issues().is_empty());
+
+        let active = NotificationFilter { archived: Some(false), ..Default::default() };
+        let archived = NotificationFilter { archived: Some(true), ..Default::default() };
+        assert_eq!(ids(store.query_filter(SortMode::Newest, &active, 0, None)), ["2"]);
+        assert_eq!(ids(store.query_filter(SortMode::Newest, &archived, 0, None)), ["1"]);
+
+        assert_eq!(store.set_archived(&["1".to_string()], false), 1);
+        let n = store.get("1").unwrap();
+        assert!(!n.archived && n.read);
+        assert_eq!(store.counts().unread, 1);
+    }
+
+    #[test]
+    fn test_upsert_from_keys_by_connection() {
+        let mut store = NotificationStore::default();
+        store.upsert_from("pixel", notif("1", "t", "a", false), false);
+        let n = store.get("1").cloned().unwrap();
+        store.snooze(n, 2_000);
+
+        // 暂缓区中的 "1" 仍属于 pixel：另一连接的同名 id 分开存储，pixel 重发时更新暂缓区中的副本
+        assert_eq!(store.upsert_from("tablet", notif("1", "t", "b", false), false), (Upsert::Inserted, "tablet:1".to_string()));
+        let (result, id) = store.upsert_from("pixel", notif("1", "t", "a2", false), false);
+        assert_eq!((result, id.as_str()), (Upsert::Updated { content_changed: true }, "1"));
+        assert_eq!(store.snoozed()[0].text.as_deref(), Some("a2"));
+
+        // "1" 删除之后，tablet 的更新仍落在原来的 "tablet:1"
+        store.remove("1");
+        assert_eq!(store.upsert_from("tablet", notif("1", "t", "b2", false), false).1, "tablet:1");
+        assert_eq!(store.get("tablet:1").unwrap().text.as_deref(), Some("b2"));
+        assert!(store.get("1").is_none());
+        assert!(store.integrity_issues().is_empty());
+    }
+
+    #[test]
+    fn test_snooze_keeps_archived() {
+        let mut store = NotificationStore::default();
+        store.upsert(notif("1", "t", "x", false), false);
+        store.upsert(notif("2", "t", "y", false), false);
+        store.set_archived(&["1".to_string(), "2".to_string()], true);
+        store.snooze_ids(&["1".to_string()], 100);
+        let n = store.get("2").cloned().unwrap();
+        store.snooze(n, 100);
+
+        // 暂缓期间手机端更新内容，到期后仍是归档、已读
+        store.upsert(notif("1", "t", "changed", false), false);
+        assert!(store.snoozed().iter().all(|n| n.archived));
+        let woken = store.wake_due(100);
+        assert_eq!(woken.len(), 2);
+        assert!(woken.iter().all(|n| n.archived && n.read));
+        assert_eq!(store.counts().unread, 0);
+        assert!(store.integrity_issues().is_empty());
+    }
+
+    #[test]
+    fn test_read_purges_include_archived() {
+        let mut store = NotificationStore::default();
+        for (id, ts) in [("1", 100), ("2", 100), ("3", 500), ("4", 500)] {
+            store.upsert(Notification { posted_at: Some(ts), ..notif(id, "t", id, false) }, true);
+        }
+        store.set_archived(&["1".to_string(), "3".to_string()], true);
+        store.set_pinned(&["4".to_string()], true);
+        // 按天数清理：过期的已读通知，归档的也清理
+        let mut purged = ids(store.purge_read_before(200));
+        purged.sort();
+        assert_eq!(purged, ["1", "2"]);
+        // 删除已读：全部已读的（含归档），置顶的保留
+        assert_eq!(ids(store.remove_read(None)), ["3"]);
+        assert!(store.get("4").is_some());
+        assert!(store.integrity_issues().is_empty());
+    }
+
+    fn fixture() -> NotificationStore {
+        let mut store = NotificationStore::default();
+        let items = [
+            ("a1", "com.a", 100),
+            ("a2", "com.a", 300),
+            ("a3", "com.a", 500),
+            ("b1", "com.b", 200),
+            ("b2", "com.b", 300),
+            ("c1", "com.c", 400),
+        ];
+        for (id, pkg, ts) in items {
+            let n = Notification {
+                id: id.into(),
+                package_name: Some(pkg.into()),
+                posted_at: Some(ts),
+                ..Default::default()
+            };
+            store.upsert(n, false);
+        }
+        let n = Notification { id: "x1".into(), posted_at: Some(250), ..Default::default() };
+        store.upsert(n, false);
+        store.mark_read(&["a3".to_string(), "b2".to_string()]);
+        store
+    }
+
+    fn ids(list: Vec<Notification>) -> Vec<String> {
+        list.into_iter().map(|n| n.id).collect()
+    }
+
+    #[test]
+    fn test_sort_snapshots() {
+        let store = fixture();
+        let all = |sort| ids(store.query(sort, |_| true, 0, None));
+        assert_eq!(all(SortMode::Newest), ["a3", "c1", "b2", "a2", "x1", "b1", "a1"]);
+        assert_eq!(all(SortMode::Oldest), ["a1", "b1", "x1", "a2", "b2", "c1", "a3"]);
+        assert_eq!(all(SortMode::UnreadFirst), ["c1", "a2", "x1", "b1", "a1", "a3", "b2"]);
+        assert_eq!(all(SortMode::AppThenTime), ["c1", "x1", "b2", "b1", "a3", "a2", "a1"]);
+        assert_eq!(all(SortMode::ByPackage), ["a3", "a2", "a1", "b2", "b1", "c1", "x1"]);
+        assert_eq!(serde_json::from_str::<SortMode>(r#""newest_first""#).unwrap(), SortMode::Newest);
+        assert_eq!(serde_json::from_str::<SortMode>(r#""by_package""#).unwrap(), SortMode::ByPackage);
+    }
+
+    #[test]
+    fn test_ties_break_on_id_and_paginate_stably() {
+        let store = fixture();
+        // a2 与 b2 时间戳相同，按 id 决定先后
+        let page1 = ids(store.query(SortMode::Newest, |_| true, 0, Some(3)));
+        let page2 = ids(store.query(SortMode::Newest, |_| true, 3, Some(3)));
+        assert_eq!(page1, ["a3", "c1", "b2"]);
+        assert_eq!(page2, ["a2", "x1", "b1"]);
+    }
+
+    #[test]
+    fn test_package_and_unread_filter() {
+        let store = fixture();
+        let filter = |package: Option<&str>, unread: Option<bool>| NotificationFilter {
+            package: package.map(Into::into),
+            unread,
+            ..Default::default()
+        };
+        let list = |f: &NotificationFilter| ids(store.query_filter(SortMode::Newest, f, 0, None));
+        assert_eq!(list(&filter(Some("com.a"), None)), ["a3", "a2", "a1"]);
+        assert_eq!(list(&filter(Some("com.a"), Some(true))), ["a2", "a1"]);
+        // 无包名的通知只在不按包名过滤时出现
+        assert_eq!(list(&filter(None, Some(true))), ["c1", "a2", "x1", "b1", "a1"]);
+        let c = store.counts_filtered(&filter(Some("com.b"), None));
+        assert_eq!((c.unread, c.total), (1, 2));
+        let c = store.counts_filtered(&NotificationFilter::default());
+        assert_eq!((c.unread, c.total), (store.counts().unread, store.counts().total));
+    }
+
+    #[test]
+    fn test_indices_follow_updates() {
+        let mut store = fixture();
+        // 更新时间戳后位置随之变化，已读状态变化后未读索引同步
+        let n = Notification {
+            id: "a1".into(),
+            package_name: Some("com.a".into()),
+            posted_at: Some(100),
+            updated_at: Some(900),
+            ..Default::default()
+        };
+        store.upsert(n, false);
+        store.mark_read(&["c1".to_string()]);
+        store.remove("x1");
+        assert_eq!(ids(store.query(SortMode::Newest, |_| true, 0, Some(2))), ["a1", "a3"]);
+        assert_eq!(ids(store.query(SortMode::UnreadFirst, |_| true, 0, None)), ["a1", "a2", "b1", "a3", "c1", "b2"]);
+        let unread_only = ids(store.query(SortMode::Newest, |n| !n.read, 0, None));
+        assert_eq!(unread_only, ["a1", "a2", "b1"]);
+    }
+
+    fn retention(max_items: usize, package_quota_percent: u8) -> RetentionSettings {
+        RetentionSettings { max_items, package_quota_percent, ..Default::default() }
+    }
+
+    fn add(store: &mut NotificationStore, id: &str, pkg: &str, ts: i64, read: bool) {
+        let n = Notification {
+            id: id.into(),
+            package_name: Some(pkg.into()),
+            posted_at: Some(ts),
+            ..Default::default()
+        };
+        store.upsert(n, read);
+    }
+
+    #[test]
+    fn test_flooding_app_evicts_own_read_first() {
+        let mut store = NotificationStore::default();
+        // 其他应用的旧通知
+        add(&mut store, "o1", "com.other", 1, true);
+        add(&mut store, "o2", "com.other", 2, false);
+        // 刷屏应用：6 条，其中 4 条已读
+        for i in 0..6 {
+            add(&mut store, &format!("f{}", i), "com.flood", 10 + i, i < 4);
+        }
+        // 上限 6，配额 50% = 3：超出 2 条，应淘汰 com.flood 最旧的两条已读
+        let ev = store.enforce_retention(&retention(6, 50));
+        let ids: Vec<&str> = ev.removed.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["f0", "f1"]);
+        assert_eq!(ev.flooded, [(Some("com.flood".to_string()), 6)]);
+        assert!(store.get("o1").is_some());
+
+        // 警告只发一次
+        add(&mut store, "f9", "com.flood", 30, false);
+        let ev = store.enforce_retention(&retention(6, 50));
+        assert!(ev.flooded.is_empty());
+        assert_eq!(ev.removed.len(), 1);
+    }
+
+    #[test]
+    fn test_falls_back_to_global_oldest_and_spares_pinned() {
+        let mut store = NotificationStore::default();
+        add(&mut store, "o1", "com.other", 1, false);
+        add(&mut store, "o2", "com.other", 2, false);
+        // 刷屏应用全部未读：配额轮次无可淘汰项
+        for i in 0..4 {
+            add(&mut store, &format!("f{}", i), "com.flood", 10 + i, false);
+        }
+        store.set_pinned(&["o1".to_string()], true);
+        // 上限 4：超出 2，全局最旧且未置顶的是 o2、f0
+        let ev = store.enforce_retention(&retention(4, 50));
+        let mut ids: Vec<String> = ev.removed.into_iter().map(|n| n.id).collect();
+        ids.sort();
+        assert_eq!(ids, ["f0", "o2"]);
+        assert!(store.get("o1").unwrap().pinned);
+
+        // 置顶状态不受手机端更新影响
+        add(&mut store, "o1", "com.other", 1, false);
+        assert!(store.get("o1").unwrap().pinned);
+    }
+
+    #[test]
+    fn test_quota_round_does_not_overshoot_cap() {
+        let mut store = NotificationStore::default();
+        for i in 0..8 {
+            add(&mut store, &format!("f{}", i), "com.flood", i, true);
+        }
+        add(&mut store, "o1", "com.other", 100, false);
+        // 上限 8：只需淘汰 1 条，即使 com.flood 远超配额
+        let ev = store.enforce_retention(&retention(8, 30));
+        assert_eq!(ev.removed.len(), 1);
+        assert_eq!(ev.removed[0].id, "f0");
+        assert_eq!(store.counts().total, 8);
+    }
+
+    #[test]
+    fn test_global_round_prefers_read() {
+        let mut store = NotificationStore::default();
+        add(&mut store, "u1", "com.a", 1, false);
+        add(&mut store, "u2", "com.b", 2, false);
+        add(&mut store, "r3", "com.c", 3, true);
+        add(&mut store, "u4", "com.d", 4, false);
+        // 各应用都在配额内：超出 2 条时先淘汰已读的 r3，再淘汰最旧的未读 u1
+        let ev = store.enforce_retention(&retention(2, 100));
+        let ids: Vec<&str> = ev.removed.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["r3", "u1"]);
+        assert!(store.get("u2").is_some() && store.get("u4").is_some());
+    }
+
+    #[test]
+    fn test_snoozed_hidden_until_due() {
+        let mut store = NotificationStore::default();
+        add(&mut store, "1", "com.a", 10, true);
+        store.set_pinned(&["1".to_string()], true);
+        let n = store.get("1").unwrap().clone();
+        store.snooze(n, 100);
+        store.snooze(Notification { id: "2".into(), ..Default::default() }, 50);
+        store.snooze(Notification { id: "3".into(), ..Default::default() }, 60);
+        // 暂缓期间的更新由桌面端决定为已读（如静音规则）：到期后仍为已读
+        store.upsert(Notification { id: "3".into(), text: Some("muted".into()), ..Default::default() }, true);
+        assert_eq!(store.counts().total, 0);
+        assert!(store.query(SortMode::Newest, |_| true, 0, None).is_empty());
+        assert_eq!(store.snoozed().len(), 3);
+
+        assert!(store.wake_due(49).is_empty());
+        let woken = store.wake_due(100);
+        let ids: Vec<&str> = woken.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["2", "3", "1"]);
+        // 到期后为未读，保留置顶
+        assert!(!woken[2].read && woken[2].pinned && woken[2].snoozed_until.is_none());
+        assert!(woken[1].read);
+        assert_eq!(store.counts().unread, 2);
+        assert!(store.snoozed().is_empty());
+    }
+
+    #[test]
+    fn test_counts_consistent_under_interleaving() {
+        let store = Arc::new(Mutex::new(NotificationStore::default()));
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    for i in 0..500 {
+                        let id = format!("{}", i % 50);
+                        let mut s = store.lock().unwrap();
+                        match (i + t) % 4 {
+                            0 => { s.upsert(notif(&id, "t", &format!("{}", i % 3), false), false); }
+                            1 => { s.mark_read(&[id]); }
+                            2 => { s.upsert(notif(&id, "t", "x", false), t == 0); }
+                            _ => { s.remove(&id); }
+                        }
+                        let c = s.counts();
+                        let unread = s.query(SortMode::UnreadFirst, |n| !n.read, 0, None).len();
+                        assert_eq!(c.unread, unread);
+                        assert_eq!(c.total, s.query(SortMode::Newest, |_| true, 0, None).len());
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert!(store.lock().unwrap().integrity_issues().is_empty());
+    }
+
+    #[test]
+    fn test_counts_by_package_match_counts() {
+        let mut store = NotificationStore::default();
+        let packages = [Some("com.a"), Some("com.b"), None, Some("com.c")];
+        let importances = [Importance::Min, Importance::Default, Importance::Urgent];
+        for i in 0..600 {
+            let id = format!("{}", i % 90);
+            match i % 5 {
+                0..=2 => {
+                    let n = Notification {
+                        package_name: packages[i % 4].map(String::from),
+                        importance: importances[i % 3],
+                        ..notif(&id, "t", &format!("{}", i % 7), false)
+                    };
+                    store.upsert(n, i % 11 == 0);
+                }
+                3 => { store.mark_read(&[id]); }
+                _ => { store.remove(&id); }
+            }
+            let by_package = store.counts_by_package();
+            let counts = store.counts();
+            assert_eq!(by_package.iter().map(|c| c.unread).sum::<usize>(), counts.unread);
+            assert_eq!(by_package.iter().map(|c| c.total).sum::<usize>(), counts.total);
+            assert!(by_package.windows(2).all(|w| w[0].unread >= w[1].unread));
+        }
+        // 无包名的通知只有一组
+        let by_package = store.counts_by_package();
+        assert_eq!(by_package.iter().filter(|c| c.package_name.is_none()).count(), 1);
+        for c in &by_package {
+            let mine = store.query(SortMode::Newest, |n| n.package_name == c.package_name, 0, None);
+            assert_eq!(c.total, mine.len());
+            assert_eq!(c.unread, mine.iter().filter(|n| !n.read && n.importance != Importance::Min).count());
+        }
+
+        // 修改计入未读数的重要性后同样一致
+        store.set_counted_importance([true; 5]);
+        assert_eq!(store.counts_by_package().iter().map(|c| c.unread).sum::<usize>(), store.counts().unread);
+        assert!(store.integrity_issues().is_empty());
+        store.unread_by_package.clear();
+        assert!(!store.integrity_issues().is_empty());
+        store.rebuild_derived();
+        assert!(store.integrity_issues().is_empty());
+    }
+
+    #[test]
+    fn test_versioned_counts_monotonic_under_load() {
+        let store = Arc::new(Mutex::new(NotificationStore::default()));
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    for i in 0..500 {
+                        let id = format!("{}", i % 40);
+                        let mut s = store.lock().unwrap();
+                        match (i + t) % 3 {
+                            0 => { s.upsert(notif(&id, "t", &format!("{}", i % 5), false), false); }
+                            1 => { s.mark_read(&[id]); }
+                            _ => { s.remove(&id); }
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // 采样方：seq 只增不减，同一 seq 对应的计数必然相同
+        let sampler = {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                let mut seen: HashMap<u64, VersionedCounts> = HashMap::new();
+                let mut last = 0;
+                for _ in 0..2000 {
+                    let v = store.lock().unwrap().versioned_counts();
+                    assert!(v.seq >= last);
+                    last = v.seq;
+                    if let Some(prev) = seen.insert(v.seq, v.clone()) {
+                        assert_eq!(prev, v);
+                    }
+                }
+            })
+        };
+        for h in writers {
+            h.join().unwrap();
+        }
+        sampler.join().unwrap();
+        let v = store.lock().unwrap().versioned_counts();
+        assert!(v.seq > 0);
+        assert!(store.lock().unwrap().integrity_issues().is_empty());
+    }
+
+    #[test]
+    fn test_integrity_detects_and_repairs_drift() {
+        let mut store = fixture();
+        store.mark_read(&["c1".to_string()]);
+        store.set_pinned(&["a2".to_string()], true);
+        store.snooze(Notification { id: "s1".into(), ..Default::default() }, 50);
+        assert!(store.integrity_issues().is_empty());
+
+        // 模拟增量维护的遗漏：集合、索引、计数各错一处
+        store.read_set.insert("ghost".into());
+        store.notifications.get_mut("a1").unwrap().read = true;
+        store.unread_by_importance[0] += 3;
+        store.by_package.entry(Some("com.gone".into())).or_default();
+        let a3 = store.notifications.get_mut("a3").unwrap();
+        a3.posted_at = Some(-1);
+        a3.updated_at = None;
+        store.snoozed.insert("b1".into(), store.get("b1").unwrap().clone());
+
+        let issues = store.integrity_issues();
+        let kinds = |structure| issues.iter().filter(|d| d.structure == structure).map(|d| d.kind).collect::<Vec<_>>();
+        assert!(kinds(Structure::ReadSet).contains(&DiscrepancyKind::Stale));
+        assert!(kinds(Structure::ReadSet).contains(&DiscrepancyKind::FlagMismatch));
+        assert!(kinds(Structure::UnreadCounters).contains(&DiscrepancyKind::CounterMismatch));
+        assert!(kinds(Structure::ByTime).contains(&DiscrepancyKind::Missing));
+        assert!(kinds(Structure::ByTime).contains(&DiscrepancyKind::Stale));
+        assert!(kinds(Structure::ByPackage).contains(&DiscrepancyKind::Stale));
+        assert!(kinds(Structure::Snoozed).contains(&DiscrepancyKind::Overlap));
+
+        let seq = store.seq();
+        store.rebuild_derived();
+        assert!(store.integrity_issues().is_empty());
+        assert!(store.seq() > seq);
+        assert_eq!(store.counts().unread, store.query(SortMode::Newest, |n| !n.read, 0, None).len());
+        assert_eq!(ids(store.query(SortMode::Oldest, |_| true, 0, Some(1))), ["a3"]);
+        assert_eq!(store.snoozed().iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), ["s1"]);
+    }
+
+    #[test]
+    fn test_seq_exhaustion_starts_new_stream() {
+        let mut store = NotificationStore::default();
+        store.restore_stream("old".into(), u64::MAX - 1);
+        store.upsert(notif("1", "t", "x", false), false);
+        assert_eq!((store.seq(), store.stream_id()), (u64::MAX, "old"));
+        store.upsert(notif("2", "t", "x", false), false);
+        assert_eq!(store.seq(), 1);
+        assert_ne!(store.stream_id(), "old");
+    }
+}
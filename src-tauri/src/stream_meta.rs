@@ -0,0 +1,150 @@
+//! 本地变更序号（store seq）与流 id 的持久化，保证 seq 跨重启单调递增。
+//! 不逐次写盘：序号每前进 PERSIST_EVERY / 2 写一次，正常退出时再写一次并标记 clean。
+//! 异常退出后恢复时把序号加上 PERSIST_EVERY，确保不会回退到已发出过的值。
+//! 恢复出厂设置时换新的流 id，消费方据此发现序号重置并全量同步。
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+pub const PERSIST_EVERY: u64 = 100;
+const STREAM_FILE: &str = "stream.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamMeta {
+    pub stream_id: String,
+    pub seq: u64,
+    /// 正常退出时为 true；运行中写入的都是 false
+    pub clean_shutdown: bool,
+}
+
+impl StreamMeta {
+    pub fn new_stream() -> Self {
+        Self { stream_id: uuid::Uuid::new_v4().to_string(), seq: 0, clean_shutdown: true }
+    }
+
+    /// 启动时应从哪个序号继续
+    pub fn resume_seq(&self) -> u64 {
+        if self.clean_shutdown {
+            self.seq
+        } else {
+            self.seq + PERSIST_EVERY
+        }
+    }
+}
+
+/// 上次写盘时的序号
+#[derive(Default)]
+pub struct SeqPersist {
+    last_saved: Mutex<u64>,
+}
+
+impl AppState {
+    /// 启动时恢复序号流（init_storage 中调用）
+    pub(crate) fn load_stream_meta(&self) {
+        let meta = self.storage.load::<StreamMeta>(STREAM_FILE).unwrap_or_else(StreamMeta::new_stream);
+        let seq = meta.resume_seq();
+        println!("[Stream] {} resumes at seq {} (clean={})", meta.stream_id, seq, meta.clean_shutdown);
+        self.store.lock().unwrap().restore_stream(meta.stream_id, seq);
+        self.save_stream_meta(false);
+    }
+
+    /// 变更后调用：距上次写盘已前进足够多时才写
+    pub(crate) fn persist_seq_if_due(&self) {
+        let seq = self.store.lock().unwrap().seq();
+        if seq.abs_diff(*self.seq_persist.last_saved.lock()) >= PERSIST_EVERY / 2 {
+            self.save_stream_meta(false);
+        }
+    }
+
+    /// 正常退出时写入当前序号
+    pub fn shutdown_stream(&self) {
+        self.save_stream_meta(true);
+    }
+
+    /// 开始新的序号流（恢复出厂设置）
+    pub(crate) fn reset_stream(&self) {
+        let meta = StreamMeta::new_stream();
+        println!("[Stream] New stream {}", meta.stream_id);
+        self.store.lock().unwrap().restore_stream(meta.stream_id, 0);
+        self.save_stream_meta(false);
+    }
+
+    fn save_stream_meta(&self, clean_shutdown: bool) {
+        let meta = {
+            let store = self.store.lock().unwrap();
+            StreamMeta { stream_id: store.stream_id().to_string(), seq: store.seq(), clean_shutdown }
+        };
+        let mut last_saved = self.seq_persist.last_saved.lock();
+        if let Err(e) = self.storage.save(STREAM_FILE, &meta) {
+            println!("[Storage] {}", e);
+            return;
+        }
+        *last_saved = meta.seq;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Event, Notification};
+
+    fn add(state: &AppState, id: usize) {
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: id.to_string(), ..Default::default() }),
+            id: None,
+        });
+    }
+
+    fn saved(dir: &std::path::Path) -> StreamMeta {
+        serde_json::from_str(&std::fs::read_to_string(dir.join(STREAM_FILE)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_seq_continues_across_restarts() {
+        let dir = crate::storage::temp_dir("stream");
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        let stream_id = state.store.lock().unwrap().stream_id().to_string();
+        for i in 0..10 {
+            add(&state, i);
+        }
+        // 未到写盘间隔：磁盘上仍是启动时的值
+        assert_eq!(saved(&dir).seq, 0);
+        let seq = state.store.lock().unwrap().seq();
+        state.shutdown_stream();
+
+        let clean = AppState::default();
+        clean.init_storage(dir.clone());
+        let v = clean.store.lock().unwrap().versioned_counts();
+        assert_eq!((v.seq, v.stream_id.as_str()), (seq, stream_id.as_str()));
+
+        // 模拟崩溃：未正常退出，恢复时跳过一个间隔
+        for i in 0..(PERSIST_EVERY as usize) {
+            add(&clean, 100 + i);
+        }
+        let before_crash = clean.store.lock().unwrap().seq();
+        assert!(saved(&dir).seq > seq);
+        let crashed = AppState::default();
+        crashed.init_storage(dir.clone());
+        assert!(crashed.store.lock().unwrap().seq() > before_crash);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_factory_reset_starts_new_stream() {
+        let dir = crate::storage::temp_dir("stream-reset");
+        let state = AppState::default();
+        state.init_storage(dir.clone());
+        add(&state, 1);
+        let before = state.store.lock().unwrap().versioned_counts();
+        state.factory_reset().unwrap();
+        let after = state.store.lock().unwrap().versioned_counts();
+        assert_ne!(after.stream_id, before.stream_id);
+        assert_eq!(saved(&dir).stream_id, after.stream_id);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
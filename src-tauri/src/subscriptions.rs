@@ -0,0 +1,197 @@
+//! 按连接推送事件流到 WebView（tauri Channel）。
+//! 每个连接保留一个环形缓冲；WebView 重载/崩溃后旧 Channel 失效，发送失败即注销订阅，
+//! 之后的事件只进缓冲，前端重新订阅时从最后确认的 seq 之后补发，保证不丢事件。
+
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::types::Event;
+
+/// 每个连接缓冲的事件数
+pub const RING_CAPACITY: usize = 500;
+
+/// 推送给前端的事件：seq 由这里按连接递增分配，与手机端 seq 无关
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StreamEvent {
+    pub connection_id: String,
+    pub seq: u64,
+    pub event: Event,
+}
+
+/// 订阅者（生产环境为 tauri Channel，测试中可模拟失效）
+pub trait Subscriber: Send + Sync {
+    fn send(&self, event: &StreamEvent) -> Result<(), String>;
+}
+
+impl Subscriber for tauri::ipc::Channel<StreamEvent> {
+    fn send(&self, event: &StreamEvent) -> Result<(), String> {
+        tauri::ipc::Channel::send(self, event.clone()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Default)]
+struct Stream {
+    next_seq: u64,
+    ring: VecDeque<StreamEvent>,
+    subscriber: Option<Box<dyn Subscriber>>,
+}
+
+impl Stream {
+    /// 补发 last_seq 之后的缓冲事件；失败则不挂载订阅者
+    fn attach(&mut self, last_seq: u64, subscriber: Box<dyn Subscriber>) -> Result<usize, String> {
+        let mut replayed = 0;
+        for e in self.ring.iter().filter(|e| e.seq > last_seq) {
+            subscriber.send(e)?;
+            replayed += 1;
+        }
+        self.subscriber = Some(subscriber);
+        Ok(replayed)
+    }
+}
+
+#[derive(Default)]
+pub struct SubscriptionHub {
+    streams: Mutex<HashMap<String, Stream>>,
+}
+
+impl SubscriptionHub {
+    /// 写入缓冲并推送；发送失败时注销订阅者（事件已在缓冲中，重新订阅后补发）
+    pub fn publish(&self, connection_id: &str, event: Event) -> u64 {
+        let mut streams = self.streams.lock();
+        let stream = streams.entry(connection_id.to_string()).or_default();
+        // u64 实际用不完；万一用完从 1 重新开始并丢弃缓冲，避免新事件因 seq 小于已确认值而不被补发
+        stream.next_seq = match stream.next_seq.checked_add(1) {
+            Some(seq) => seq,
+            None => {
+                stream.ring.clear();
+                1
+            }
+        };
+        let e = StreamEvent {
+            connection_id: connection_id.to_string(),
+            seq: stream.next_seq,
+            event,
+        };
+        if let Some(sub) = stream.subscriber.as_ref() {
+            if let Err(err) = sub.send(&e) {
+                println!("[Subscriptions] Channel for {} is dead ({}), deregistering", connection_id, err);
+                stream.subscriber = None;
+            }
+        }
+        stream.ring.push_back(e);
+        if stream.ring.len() > RING_CAPACITY {
+            stream.ring.pop_front();
+        }
+        stream.next_seq
+    }
+
+    /// 订阅单个连接，返回补发的事件数
+    pub fn subscribe(&self, connection_id: &str, last_seq: u64, subscriber: Box<dyn Subscriber>) -> Result<usize, String> {
+        let mut streams = self.streams.lock();
+        let stream = streams.entry(connection_id.to_string()).or_default();
+        stream.attach(last_seq, subscriber)
+    }
+
+    /// 为所有已知连接重新订阅（前端启动/重载后调用）。
+    /// last_seq 为各连接最后确认的 seq，缺省时从缓冲开头补发
+    pub fn resubscribe_all<S>(&self, last_seq: &HashMap<String, u64>, subscriber: S) -> Result<usize, String>
+    where
+        S: Subscriber + Clone + 'static,
+    {
+        let mut streams = self.streams.lock();
+        let mut replayed = 0;
+        for (id, stream) in streams.iter_mut() {
+            let from = last_seq.get(id).copied().unwrap_or(0);
+            replayed += stream.attach(from, Box::new(subscriber.clone()))?;
+        }
+        Ok(replayed)
+    }
+
+    /// 各连接中最长的缓冲
+    pub fn max_buffered(&self) -> usize {
+        self.streams.lock().values().map(|s| s.ring.len()).max().unwrap_or(0)
+    }
+
+    /// 连接断开后丢弃其缓冲
+    pub fn remove(&self, connection_id: &str) {
+        self.streams.lock().remove(connection_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// 模拟前端：记录收到的事件，可被设为失效（WebView 重载）
+    #[derive(Clone, Default)]
+    struct FakeChannel {
+        received: Arc<Mutex<Vec<(String, u64)>>>,
+        dead: Arc<AtomicBool>,
+    }
+
+    impl Subscriber for FakeChannel {
+        fn send(&self, e: &StreamEvent) -> Result<(), String> {
+            if self.dead.load(Ordering::SeqCst) {
+                return Err("channel closed".into());
+            }
+            self.received.lock().push((e.connection_id.clone(), e.seq));
+            Ok(())
+        }
+    }
+
+    fn event(n: i64) -> Event {
+        Event { event_type: "added".into(), seq: n, notification: None, id: Some(n.to_string()) }
+    }
+
+    #[test]
+    fn test_no_events_lost_after_dropped_channel() {
+        let hub = SubscriptionHub::default();
+        let old = FakeChannel::default();
+        hub.subscribe("c1", 0, Box::new(old.clone())).unwrap();
+        hub.subscribe("c2", 0, Box::new(old.clone())).unwrap();
+        for i in 1..=3 {
+            hub.publish("c1", event(i));
+        }
+        hub.publish("c2", event(1));
+
+        // WebView 重载：旧 Channel 失效，之后的发送失败并注销
+        old.dead.store(true, Ordering::SeqCst);
+        for i in 4..=6 {
+            hub.publish("c1", event(i));
+        }
+        hub.publish("c2", event(2));
+
+        // 新 WebView 用最后确认的 seq 重新订阅
+        let new = FakeChannel::default();
+        let acked = HashMap::from([("c1".to_string(), 3), ("c2".to_string(), 1)]);
+        assert_eq!(hub.resubscribe_all(&acked, new.clone()).unwrap(), 4);
+        hub.publish("c1", event(7));
+
+        let mut got = new.received.lock().clone();
+        got.sort();
+        let expected: Vec<(String, u64)> = (4..=7)
+            .map(|s| ("c1".to_string(), s))
+            .chain([("c2".to_string(), 2)])
+            .collect();
+        assert_eq!(got, expected);
+        assert_eq!(old.received.lock().len(), 4);
+    }
+
+    #[test]
+    fn test_ring_is_bounded() {
+        let hub = SubscriptionHub::default();
+        for i in 0..(RING_CAPACITY as i64 + 10) {
+            hub.publish("c1", event(i));
+        }
+        let sub = FakeChannel::default();
+        assert_eq!(hub.subscribe("c1", 0, Box::new(sub.clone())).unwrap(), RING_CAPACITY);
+        assert_eq!(sub.received.lock()[0].1, 11);
+        hub.remove("c1");
+        assert_eq!(hub.subscribe("c1", 0, Box::new(sub)).unwrap(), 0);
+    }
+}
@@ -0,0 +1,286 @@
+//! 按设备的同步起点（sync_horizon_ts）：配对后的首次同步可能一次带来几周的历史通知。
+//! 起点默认为配对时间；同步请求中附带 horizon，支持的手机端不再发送更早的通知，
+//! 不认识 horizon 的旧版手机端发来的更早通知在入库前丢弃。起点随已配对设备信息一起保存。
+//! backfill_history 为一次同步临时降低起点，按需拉取更早的历史并推送 backfill-progress；
+//! 超过 BACKFILL_IDLE_SECS 没有收到补发的通知即视为这次同步结束，起点恢复。
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::endpoints::{DeviceEndpoint, KnownEndpoint};
+use crate::types::Notification;
+
+/// 补发结束判定：多久没有收到更早的通知
+pub const BACKFILL_IDLE_SECS: i64 = 60;
+/// 每收到多少条推送一次进度
+const PROGRESS_EVERY: usize = 50;
+
+/// set_sync_horizon 的取值：时间戳，或 "all" / "pairing"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum HorizonInput {
+    At(i64),
+    Preset(HorizonPreset),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HorizonPreset {
+    /// 不限制（同步全部历史）
+    All,
+    /// 回到配对时间
+    Pairing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BackfillProgress {
+    pub connection_id: String,
+    pub since: i64,
+    pub received: usize,
+    pub done: bool,
+}
+
+struct ActiveBackfill {
+    since: i64,
+    received: usize,
+    last_activity: i64,
+}
+
+/// 进行中的补发（只在内存中，重启后不恢复）
+#[derive(Default)]
+pub struct Backfills {
+    active: parking_lot::Mutex<HashMap<String, ActiveBackfill>>,
+}
+
+/// list_devices 返回的设备信息（不含 token）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceInfo {
+    pub connection_id: String,
+    pub host: String,
+    /// 已知地址（按优先级）与最近成功时间
+    pub endpoints: Vec<KnownEndpoint>,
+    pub device_uuid: Option<String>,
+    pub connected: bool,
+    pub needs_reauth: bool,
+    pub capabilities: Option<Vec<String>>,
+    pub paired_at: Option<i64>,
+    pub sync_horizon_ts: Option<i64>,
+    /// 正在补发时的临时起点
+    pub backfill_since: Option<i64>,
+}
+
+impl AppState {
+    /// 按 connection_id 或 device_uuid 查找已配对设备
+    pub(crate) fn resolve_device(&self, device: &str) -> Result<DeviceEndpoint, String> {
+        let registry = self.endpoints.lock().unwrap();
+        registry
+            .get(device)
+            .or_else(|| registry.devices().find(|e| e.device_uuid.as_deref() == Some(device)))
+            .cloned()
+            .ok_or_else(|| format!("Unknown device {}", device))
+    }
+
+    pub fn set_sync_horizon(&self, device: &str, input: HorizonInput) -> Result<DeviceEndpoint, String> {
+        let mut endpoint = self.resolve_device(device)?;
+        endpoint.sync_horizon_ts = match input {
+            HorizonInput::At(ts) => Some(ts),
+            HorizonInput::Preset(HorizonPreset::All) => None,
+            HorizonInput::Preset(HorizonPreset::Pairing) => Some(endpoint.paired_at.unwrap_or(endpoint.updated_at)),
+        };
+        println!("[SyncHorizon] {} -> {:?}", endpoint.connection_id, endpoint.sync_horizon_ts);
+        self.save_endpoint(endpoint.clone());
+        Ok(endpoint)
+    }
+
+    /// 同步请求中附带的起点（补发期间为临时起点）
+    pub(crate) fn sync_horizon(&self, connection_id: &str) -> Option<i64> {
+        let horizon = self.device_endpoint(connection_id)?.sync_horizon_ts?;
+        let backfill = self.backfills.active.lock().get(connection_id).map(|b| b.since);
+        Some(backfill.map_or(horizon, |since| since.min(horizon)))
+    }
+
+    /// 为一次同步临时降低起点，请求手机端补发 since_ts 之后的历史
+    pub fn backfill_history(&self, device: &str, since_ts: i64, now: i64) -> Result<BackfillProgress, String> {
+        let connection_id = self.resolve_device(device)?.connection_id;
+        let client = self.clients.read().get(&connection_id).cloned().ok_or("Device is not connected")?;
+        self.backfills
+            .active
+            .lock()
+            .insert(connection_id.clone(), ActiveBackfill { since: since_ts, received: 0, last_activity: now });
+        client.send_action(&serde_json::json!({ "action": "sync", "since": since_ts, "horizon": since_ts }))?;
+        println!("[SyncHorizon] Backfill {} since {}", connection_id, since_ts);
+        let progress = BackfillProgress { connection_id, since: since_ts, received: 0, done: false };
+        self.events.emit("backfill-progress", &progress);
+        Ok(progress)
+    }
+
+    /// 入库前检查：早于起点（且不在补发范围内）的通知丢弃
+    pub(crate) fn below_sync_horizon(&self, connection_id: &str, n: &Notification, now: i64) -> bool {
+        let Some(horizon) = self.device_endpoint(connection_id).and_then(|e| e.sync_horizon_ts) else {
+            return false;
+        };
+        let Some(ts) = n.posted_at.filter(|ts| *ts < horizon) else {
+            return false;
+        };
+        let progress = {
+            let mut active = self.backfills.active.lock();
+            match active.get_mut(connection_id) {
+                Some(b) if ts >= b.since => {
+                    b.received += 1;
+                    b.last_activity = now;
+                    (b.received % PROGRESS_EVERY == 0).then(|| BackfillProgress {
+                        connection_id: connection_id.to_string(),
+                        since: b.since,
+                        received: b.received,
+                        done: false,
+                    })
+                }
+                _ => return true,
+            }
+        };
+        if let Some(progress) = progress {
+            self.events.emit("backfill-progress", &progress);
+        }
+        false
+    }
+
+    /// 结束空闲的补发，返回结束的个数（维护任务调用）
+    pub fn expire_backfills(&self, now: i64) -> usize {
+        let finished: Vec<BackfillProgress> = {
+            let mut active = self.backfills.active.lock();
+            let idle: Vec<String> = active
+                .iter()
+                .filter(|(_, b)| now - b.last_activity >= BACKFILL_IDLE_SECS)
+                .map(|(id, _)| id.clone())
+                .collect();
+            idle.into_iter()
+                .filter_map(|id| {
+                    let b = active.remove(&id)?;
+                    Some(BackfillProgress { connection_id: id, since: b.since, received: b.received, done: true })
+                })
+                .collect()
+        };
+        for progress in &finished {
+            println!("[SyncHorizon] Backfill {} done ({} items)", progress.connection_id, progress.received);
+            self.events.emit("backfill-progress", progress);
+        }
+        finished.len()
+    }
+
+    pub fn list_devices(&self) -> Vec<DeviceInfo> {
+        let endpoints: Vec<DeviceEndpoint> = self.endpoints.lock().unwrap().devices().cloned().collect();
+        let clients = self.clients.read();
+        let backfills = self.backfills.active.lock();
+        let mut list: Vec<DeviceInfo> = endpoints
+            .into_iter()
+            .map(|e| DeviceInfo {
+                connected: clients.contains_key(&e.connection_id),
+                backfill_since: backfills.get(&e.connection_id).map(|b| b.since),
+                connection_id: e.connection_id,
+                host: e.host,
+                endpoints: e.endpoints,
+                device_uuid: e.device_uuid,
+                needs_reauth: e.needs_reauth,
+                capabilities: e.capabilities,
+                paired_at: e.paired_at,
+                sync_horizon_ts: e.sync_horizon_ts,
+            })
+            .collect();
+        list.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use crate::android_client::AndroidSocketClient;
+    use crate::ingest::IngestOutcome;
+    use crate::transport::Transport;
+    use crate::types::Event;
+
+    struct Phone(Arc<Mutex<Vec<String>>>);
+
+    impl Transport for Phone {
+        fn send_line(&mut self, line: &str) -> Result<(), String> {
+            self.0.lock().push(line.to_string());
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(r#"{"success":true,"deviceUuid":"uuid-1","protocolVersion":3}"#.to_string())
+        }
+    }
+
+    fn added(id: &str, posted_at: i64) -> Event {
+        let n = Notification { id: id.into(), posted_at: Some(posted_at), ..Default::default() };
+        Event { event_type: "added".into(), seq: 0, notification: Some(n), id: None }
+    }
+
+    fn paired() -> (AppState, Arc<Mutex<Vec<String>>>, i64) {
+        let state = AppState::default();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let client = AndroidSocketClient::with_transport(Box::new(Phone(sent.clone())), "pixel".into(), Arc::default());
+        client.login("t1").unwrap();
+        state.register_client("pixel".into(), client, "t1", Some("192.168.1.5:10035"));
+        let paired_at = state.device_endpoint("pixel").unwrap().paired_at.unwrap();
+        (state, sent, paired_at)
+    }
+
+    #[test]
+    fn test_history_before_pairing_dropped() {
+        let (state, _, paired_at) = paired();
+        let dropped = IngestOutcome::Dropped("sync_horizon".into());
+        assert_eq!(state.ingest_from("pixel", added("old", paired_at - 86_400)), dropped);
+        assert_eq!(state.ingest_from("pixel", added("new", paired_at)), IngestOutcome::Stored);
+
+        // 重连不会重置起点
+        state.set_sync_horizon("uuid-1", HorizonInput::At(paired_at - 10)).unwrap();
+        state.remember_endpoint("pixel", "192.168.1.5:10035", "t1", Some("uuid-1".into()), None);
+        assert_eq!(state.device_endpoint("pixel").unwrap().sync_horizon_ts, Some(paired_at - 10));
+
+        let all: HorizonInput = serde_json::from_str("\"all\"").unwrap();
+        state.set_sync_horizon("pixel", all).unwrap();
+        assert_eq!(state.ingest_from("pixel", added("old", paired_at - 86_400)), IngestOutcome::Stored);
+        let pairing: HorizonInput = serde_json::from_str("\"pairing\"").unwrap();
+        assert_eq!(state.set_sync_horizon("pixel", pairing).unwrap().sync_horizon_ts, Some(paired_at));
+        assert!(state.set_sync_horizon("tablet", HorizonInput::At(0)).is_err());
+    }
+
+    #[test]
+    fn test_backfill_lowers_horizon_once() {
+        let (state, sent, paired_at) = paired();
+        let since = paired_at - 7 * 86_400;
+        state.backfill_history("uuid-1", since, 1000).unwrap();
+        let request: serde_json::Value = serde_json::from_str(sent.lock().last().unwrap()).unwrap();
+        assert_eq!(request, serde_json::json!({ "action": "sync", "since": since, "horizon": since }));
+        assert_eq!(state.list_devices()[0].backfill_since, Some(since));
+
+        for i in 0..PROGRESS_EVERY {
+            assert_eq!(state.ingest_from("pixel", added(&i.to_string(), since + i as i64)), IngestOutcome::Stored);
+        }
+        // 早于补发起点的仍然丢弃
+        assert!(matches!(state.ingest_from("pixel", added("older", since - 1)), IngestOutcome::Dropped(_)));
+        assert_eq!(state.expire_backfills(chrono::Utc::now().timestamp() + BACKFILL_IDLE_SECS), 1);
+        assert!(matches!(state.ingest_from("pixel", added("late", since + 1)), IngestOutcome::Dropped(_)));
+
+        let progress: Vec<serde_json::Value> = state
+            .events
+            .take_captured()
+            .into_iter()
+            .filter(|(e, _)| e == "backfill-progress")
+            .map(|(_, p)| p)
+            .collect();
+        let received: Vec<(u64, bool)> =
+            progress.iter().map(|p| (p["received"].as_u64().unwrap(), p["done"].as_bool().unwrap())).collect();
+        assert_eq!(received, [(0, false), (50, false), (50, true)]);
+        let device = &state.list_devices()[0];
+        assert_eq!((device.connected, device.backfill_since), (true, None));
+    }
+}
@@ -0,0 +1,137 @@
+//! 后台任务登记：长期运行的异步任务以名字登记并拿到根令牌的子令牌，
+//! 退出时取消根令牌并在超时内等待所有任务结束；超时未结束的按名字记录，便于定位卡住的任务。
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// 退出时等待后台任务的总时长
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+struct Entry {
+    name: String,
+    started: Instant,
+    started_at: i64,
+    handle: JoinHandle<()>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TaskInfo {
+    pub name: String,
+    pub started_at: i64,
+    pub uptime_secs: u64,
+}
+
+#[derive(Default)]
+pub struct TaskRegistry {
+    root: CancellationToken,
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, Entry>>,
+}
+
+impl TaskRegistry {
+    /// 启动并登记任务；任务应在令牌取消后尽快返回
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, f: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.into();
+        if self.root.is_cancelled() {
+            println!("[Tasks] Shutting down, not starting {}", name);
+            return;
+        }
+        let handle = tauri::async_runtime::spawn(f(self.root.child_token()));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut tasks = self.tasks.lock();
+        // 顺便清理已结束的任务
+        tasks.retain(|_, e| !e.handle.inner().is_finished());
+        tasks.insert(
+            id,
+            Entry { name, started: Instant::now(), started_at: chrono::Utc::now().timestamp(), handle },
+        );
+    }
+
+    /// 仍在运行的任务（按启动先后）
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<(u64, TaskInfo)> = self
+            .tasks
+            .lock()
+            .iter()
+            .filter(|(_, e)| !e.handle.inner().is_finished())
+            .map(|(id, e)| {
+                (*id, TaskInfo { name: e.name.clone(), started_at: e.started_at, uptime_secs: e.started.elapsed().as_secs() })
+            })
+            .collect();
+        tasks.sort_by_key(|(id, _)| *id);
+        tasks.into_iter().map(|(_, t)| t).collect()
+    }
+
+    /// 取消所有任务并等待结束，返回超时未结束的任务名
+    pub async fn shutdown(&self, timeout: Duration) -> Vec<String> {
+        self.root.cancel();
+        let deadline = tokio::time::Instant::now() + timeout;
+        let entries: Vec<Entry> = self.tasks.lock().drain().map(|(_, e)| e).collect();
+        let mut hung = Vec::new();
+        for entry in entries {
+            if tokio::time::timeout_at(deadline, entry.handle).await.is_err() {
+                println!("[Tasks] {} did not stop within {:?}", entry.name, timeout);
+                hung.push(entry.name);
+            }
+        }
+        hung.sort();
+        hung
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::AppState;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_shutdown_awaits_all_tasks() {
+        let state = AppState::default();
+        let stopped = Arc::new(AtomicUsize::new(0));
+        for name in ["heartbeat", "reaper", "scheduler"] {
+            let stopped = stopped.clone();
+            state.tasks.spawn(name, |token| async move {
+                loop {
+                    tokio::select! {
+                        _ = token.cancelled() => break,
+                        _ = tokio::time::sleep(Duration::from_millis(5)) => {}
+                    }
+                }
+                stopped.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        state.tasks.spawn("one-shot", |_| async {});
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let names: Vec<String> = state.tasks.list().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["heartbeat", "reaper", "scheduler"]);
+
+        assert!(state.tasks.shutdown(Duration::from_secs(1)).await.is_empty());
+        assert_eq!(stopped.load(Ordering::SeqCst), 3);
+        assert!(state.tasks.list().is_empty());
+        // 退出后不再接受新任务
+        state.tasks.spawn("late", |_| async {});
+        assert!(state.tasks.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hung_task_reported_by_name() {
+        let tasks = TaskRegistry::default();
+        tasks.spawn("watcher", |token| async move { token.cancelled().await });
+        tasks.spawn("stuck-receive", |_| async { tokio::time::sleep(Duration::from_secs(30)).await });
+        assert_eq!(tasks.shutdown(Duration::from_millis(50)).await, ["stuck-receive"]);
+    }
+}
@@ -1,13 +1,36 @@
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{BufReader, Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::sync::Arc;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
+use crate::network_utils::BindError;
+use crate::pairing_payload::{self, DeviceMeta, PairingSchema};
+use crate::ports::PortGuard;
+use crate::protocol;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PairingData {
     pub url: String,
     pub token: String,
+    /// 可选中继（无法直连时使用）：ws:// 地址与房间口令
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_secret: Option<String>,
+    /// 新版手机端附带的一次性随机数、证书指纹与设备信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceMeta>,
+    /// 手机端发送的数据格式（见 pairing_payload）
+    #[serde(default)]
+    pub schema: PairingSchema,
+    /// 配对请求的来源地址（由服务端填写，不来自载荷）
+    #[serde(skip)]
+    pub source_ip: Option<String>,
 }
 
 pub struct TempServer {
@@ -15,25 +38,31 @@ pub struct TempServer {
     port: u16,
     running: Arc<Mutex<bool>>,
     waiting_for_pairing: Arc<Mutex<bool>>,
+    // 端口登记，随服务一起释放
+    port_guard: Option<PortGuard>,
+    // 最近一次配对请求的来源地址（失败时用于同网络检查）
+    last_peer: Mutex<Option<String>>,
 }
 
 impl TempServer {
-    pub fn new(port: u16) -> Result<Self, String> {
+    pub fn new(port: u16) -> Result<Self, BindError> {
         println!("[TempServer] Creating server on port {}...", port);
 
         let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
             .map_err(|e| {
                 println!("[TempServer] ❌ Failed to bind port {}: {}", port, e);
-                format!("Failed to bind port {}: {}", port, e)
+                BindError::from_io(port, &e)
             })?;
 
+        // 端口 0 时由系统分配，记录实际端口
+        let port = listener.local_addr().map(|a| a.port()).unwrap_or(port);
         println!("[TempServer] ✅ Port {} bound successfully", port);
 
         listener
             .set_nonblocking(true)
             .map_err(|e| {
                 println!("[TempServer] ❌ Failed to set nonblocking: {}", e);
-                format!("Failed to set nonblocking: {}", e)
+                BindError::from_io(port, &e)
             })?;
 
         println!("[TempServer] ✅ Server created successfully");
@@ -44,6 +73,8 @@ impl TempServer {
             port,
             running: Arc::new(Mutex::new(true)),
             waiting_for_pairing: Arc::new(Mutex::new(false)),
+            port_guard: None,
+            last_peer: Mutex::new(None),
         })
     }
 
@@ -51,6 +82,38 @@ impl TempServer {
         self.port
     }
 
+    /// 持有端口登记，服务被丢弃时释放
+    pub fn hold_port(&mut self, guard: PortGuard) {
+        self.port_guard = Some(guard);
+    }
+
+    /// 局域网自检：从本机局域网 IP 连一次自己。
+    /// 需在开始监听循环之前调用，自检连接会在这里被取走，不会被当成配对请求。
+    pub fn lan_self_test(&self, ip: &str) -> bool {
+        let Some(listener) = self.listener.as_ref() else {
+            return false;
+        };
+        let Ok(addr) = format!("{}:{}", ip, self.port).parse() else {
+            return false;
+        };
+        let timeout = std::time::Duration::from_secs(1);
+        if let Err(e) = TcpStream::connect_timeout(&addr, timeout) {
+            println!("[TempServer] LAN self-test connect failed: {}", e);
+            return false;
+        }
+        let start = std::time::Instant::now();
+        while start.elapsed() < timeout {
+            match listener.accept() {
+                Ok(_) => return true,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+
     pub fn is_waiting_for_pairing(&self) -> bool {
         *self.waiting_for_pairing.lock()
     }
@@ -59,6 +122,10 @@ impl TempServer {
         *self.running.lock()
     }
 
+    pub fn last_peer(&self) -> Option<String> {
+        self.last_peer.lock().clone()
+    }
+
     /// 等待安卓端连接并接收配对数据
     /// 返回 (url, token)
     pub fn wait_for_pairing(&self, timeout_secs: u64) -> Result<PairingData, String> {
@@ -93,10 +160,14 @@ impl TempServer {
             match listener.accept() {
                 Ok((stream, addr)) => {
                     println!("[TempServer] Client connected from: {}", addr);
+                    *self.last_peer.lock() = Some(addr.ip().to_string());
                     // 将 stream 设置为阻塞模式，确保读写操作正常
                     stream.set_nonblocking(false)
                         .map_err(|e| format!("Failed to set stream blocking: {}", e))?;
-                    let result = self.handle_pairing_client(stream);
+                    let result = self.handle_pairing_client(stream).map(|mut data| {
+                        data.source_ip = Some(addr.ip().to_string());
+                        data
+                    });
                     // 配对完成（成功或失败），重置等待状态
                     *self.waiting_for_pairing.lock() = false;
                     return result;
@@ -125,7 +196,7 @@ impl TempServer {
 
         println!("[TempServer] Reading first line from client...");
         let mut first_line = String::new();
-        reader.read_line(&mut first_line)
+        protocol::read_frame(&mut reader, &mut first_line)
             .map_err(|e| {
                 eprintln!("[TempServer] Failed to read from client: {}", e);
                 format!("Failed to read from client: {}", e)
@@ -144,14 +215,16 @@ impl TempServer {
         println!("[TempServer] Received raw data (length={}): {}", first_line.len(), first_line.trim());
 
         println!("[TempServer] Parsing JSON...");
-        let pairing_data: PairingData = serde_json::from_str(first_line.trim())
-            .map_err(|e| {
-                eprintln!("[TempServer] Failed to parse JSON: {}", e);
-                eprintln!("[TempServer] Received raw data: {}", first_line.trim());
-                format!("Failed to parse pairing data: {}", e)
-            })?;
+        let pairing_data = match pairing_payload::parse_pairing(&first_line) {
+            Ok(data) => data,
+            Err(rejection) => {
+                eprintln!("[TempServer] Rejected pairing data: {}", rejection);
+                let _ = stream.write_all(format!("{}\n", rejection.response()).as_bytes());
+                return Err(format!("Failed to parse pairing data: {}", rejection));
+            }
+        };
 
-        println!("[TempServer] JSON parsed successfully");
+        println!("[TempServer] JSON parsed successfully ({:?})", pairing_data.schema);
 
         // 发送确认响应
         let response = serde_json::json!({
@@ -188,7 +261,7 @@ impl TempServer {
 
         loop {
             let mut line = String::new();
-            reader.read_line(&mut line)
+            protocol::read_frame(&mut reader, &mut line)
                 .map_err(|e| format!("Failed to read header: {}", e))?;
 
             println!("[TempServer] Header: {}", line.trim());
@@ -208,7 +281,11 @@ impl TempServer {
             headers.push(line);
         }
 
-        // 读取 body
+        // 读取 body（超过帧上限的直接拒绝，不按声明的长度分配内存）
+        if content_length > protocol::MAX_FRAME_BYTES {
+            let _ = stream.write_all(b"HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\n\r\n");
+            return Err(format!("Pairing request too large ({} bytes)", content_length));
+        }
         if content_length == 0 {
             let error_response = "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n";
             stream.write_all(error_response.as_bytes())
@@ -226,17 +303,24 @@ impl TempServer {
         let body_str = String::from_utf8_lossy(&body);
         println!("[TempServer] Request body: {}", body_str);
 
-        // 解析 JSON
-        let pairing_data: PairingData = serde_json::from_str(&body_str)
-            .map_err(|e| {
-                println!("[TempServer] JSON parse error: {}", e);
-                let error_response = "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n";
+        // 解析 JSON（兼容各版本格式）；不可用时告诉手机端缺了哪些字段
+        let pairing_data = match pairing_payload::parse_pairing(&body_str) {
+            Ok(data) => data,
+            Err(rejection) => {
+                println!("[TempServer] Rejected pairing data: {}", rejection);
+                let error_body = rejection.response().to_string();
+                let error_response = format!(
+                    "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    error_body.len(),
+                    error_body
+                );
                 let _ = stream.write_all(error_response.as_bytes());
-                format!("Invalid JSON: {}", e)
-            })?;
+                return Err(format!("Invalid pairing data: {}", rejection));
+            }
+        };
 
-        println!("[TempServer] Pairing data: url={}, token_len={}",
-            pairing_data.url, pairing_data.token.len());
+        println!("[TempServer] Pairing data: url={}, token_len={}, schema={:?}",
+            pairing_data.url, pairing_data.token.len(), pairing_data.schema);
 
         // 返回 HTTP 200 响应
         let response_json = serde_json::json!({
@@ -276,3 +360,88 @@ impl Drop for TempServer {
         println!("[TempServer] Server dropped");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::commands::AppState;
+    use crate::pairing_payload::PairingSchema;
+
+    /// 扮演手机端：按 HTTP 发送配对数据（content_length 可与 body 不一致），返回完整响应
+    fn post(port: u16, body: &str, content_length: usize) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let request = format!(
+            "POST /pair HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            port, content_length, body
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    /// 旧版手机端：直接发送一行 JSON
+    fn send_line(port: u16, line: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(format!("{}\n", line).as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_pairing_flow_end_to_end() {
+        let state = AppState::default();
+        let server = TempServer::new(0).unwrap();
+        let port = server.port();
+        assert_ne!(port, 0);
+
+        std::thread::scope(|s| {
+            let listener = s.spawn(|| state.serve_pairing(&server, 5));
+
+            let malformed = post(port, "{not json", 9);
+            assert!(malformed.starts_with("HTTP/1.1 400") && malformed.contains(r#""success":false"#), "{}", malformed);
+
+            // 无法识别的格式：告诉手机端缺了哪些字段
+            let unknown = r#"{"schemaVersion":9,"address":"10.0.0.2"}"#;
+            let rejected = post(port, unknown, unknown.len());
+            assert!(rejected.starts_with("HTTP/1.1 400") && rejected.contains(r#""missing":["token","url"]"#), "{}", rejected);
+
+            // 超过帧上限的请求不读取请求体
+            assert!(post(port, "", protocol::MAX_FRAME_BYTES + 1).starts_with("HTTP/1.1 413"));
+
+            let v2 = r#"{"url":"127.0.0.1:10035","token":"secret-1","nonce":"n-1","device":{"name":"Pixel 8","model":"GKWS6"}}"#;
+            let ok = post(port, v2, v2.len());
+            assert!(ok.starts_with("HTTP/1.1 200") && ok.contains("Pairing successful"), "{}", ok);
+
+            let legacy = send_line(port, r#"{"host":"127.0.0.1","port":"10036","token":"secret-2"}"#);
+            assert!(legacy.contains(r#""success":true"#), "{}", legacy);
+
+            // 停止后监听循环自行退出
+            server.stop();
+            listener.join().unwrap();
+        });
+        assert!(!server.is_running());
+
+        let history = state.pairing_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[0].url.as_str(), history[0].schema), ("127.0.0.1:10036", PairingSchema::HostPort));
+        assert_eq!((history[1].url.as_str(), history[1].schema), ("127.0.0.1:10035", PairingSchema::V2));
+        assert_eq!(history[1].device_name.as_deref(), Some("Pixel 8"));
+        assert!(history.iter().all(|r| r.source_ip.as_deref() == Some("127.0.0.1")));
+        assert!(!serde_json::to_string(&history).unwrap().contains("secret-"));
+
+        let events = state.events.take_captured();
+        let count = |name: &str| events.iter().filter(|(e, _)| e == name).count();
+        assert_eq!(count("pairing-recorded"), 2);
+        assert_eq!(count("pairing-network-hint"), 3);
+        assert!(count("background-error") >= 1);
+        let onboarding: Vec<_> = events.iter().filter(|(e, _)| e == "onboarding-changed").collect();
+        assert_eq!(onboarding.len(), 1);
+        assert_eq!(onboarding[0].1["step"], "first_device_paired");
+    }
+}
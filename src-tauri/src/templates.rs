@@ -0,0 +1,393 @@
+//! 通知摘要模板：系统通知、托盘提示、托盘最近通知菜单与复制文本可按设置中的模板渲染。
+//! 只支持少量占位符：{app} {title} {text} {device} {time}，可加 `:N` 按字素截断（如 {text:50}），
+//! `{{` / `}}` 表示字面量花括号。模板在 set_settings 时校验，未知占位符带位置报错。
+//! {app} 为应用显示名（见 app_meta），没有时取包名末段。内容先按隐私级别处理再渲染；缺失字段渲染为空，托盘提示与菜单不能显示换行，结果压成一行。
+//! 未设置模板的位置保持原有格式。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::app_meta;
+use crate::commands::AppState;
+use crate::privacy::{self, Surface};
+use crate::time_format::{self, TimeStyle};
+use crate::types::Notification;
+
+/// 未设置 clipboard 模板时复制的格式
+pub const DEFAULT_CLIPBOARD: &str = "{title}\n{text}";
+/// 本地创建的条目（提醒等）的 {device}
+const LOCAL_DEVICE: &str = "本机";
+
+/// 模板不合法；以 `InvalidTemplate: {json}` 的形式返回给前端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidTemplate {
+    pub field: String,
+    /// 出错位置（按字符计，从 0 开始）
+    pub position: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for InvalidTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InvalidTemplate: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<InvalidTemplate> for String {
+    fn from(e: InvalidTemplate) -> Self {
+        println!("[Templates] Invalid {} at {}: {}", e.field, e.position, e.message);
+        e.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    App,
+    Title,
+    Text,
+    Device,
+    Time,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "app" => Field::App,
+            "title" => Field::Title,
+            "text" => Field::Text,
+            "device" => Field::Device,
+            "time" => Field::Time,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field, Option<usize>),
+}
+
+/// 解析后的模板
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Segment>);
+
+/// 渲染用的字段（已按隐私级别处理）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields {
+    pub app: Option<String>,
+    pub title: Option<String>,
+    pub text: Option<String>,
+    pub device: Option<String>,
+    pub time: Option<String>,
+}
+
+impl Fields {
+    fn get(&self, field: Field) -> Option<&str> {
+        match field {
+            Field::App => self.app.as_deref(),
+            Field::Title => self.title.as_deref(),
+            Field::Text => self.text.as_deref(),
+            Field::Device => self.device.as_deref(),
+            Field::Time => self.time.as_deref(),
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(src: &str) -> Result<Self, InvalidTemplate> {
+        let err = |position: usize, message: String| InvalidTemplate { field: String::new(), position, message };
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = src.chars().enumerate().peekable();
+        while let Some((pos, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().is_some_and(|(_, c)| *c == '{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek().is_some_and(|(_, c)| *c == '}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(err(pos, "unmatched '}' (use '}}' for a literal brace)".to_string())),
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((p, '{')) => return Err(err(p, "nested '{' in placeholder".to_string())),
+                            Some((_, c)) => inner.push(c),
+                            None => return Err(err(pos, "unclosed '{'".to_string())),
+                        }
+                    }
+                    let (name, len) = match inner.split_once(':') {
+                        Some((name, len)) => (name, Some(len)),
+                        None => (inner.as_str(), None),
+                    };
+                    let field = Field::parse(name.trim())
+                        .ok_or_else(|| err(pos, format!("unknown placeholder {{{}}}", name)))?;
+                    let max = match len {
+                        None => None,
+                        Some(len) => match len.trim().parse::<usize>() {
+                            Ok(n) if n > 0 => Some(n),
+                            _ => return Err(err(pos, format!("invalid length {:?} (expected a positive number)", len))),
+                        },
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field, max));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template(segments))
+    }
+
+    /// 渲染；single_line 时换行压成空格（托盘提示/菜单）
+    pub fn render(&self, fields: &Fields, single_line: bool) -> String {
+        let mut out = String::new();
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Field(field, max) => {
+                    let value = fields.get(*field).unwrap_or_default();
+                    match max {
+                        Some(n) => out.push_str(&truncate(value, *n)),
+                        None => out.push_str(value),
+                    }
+                }
+            }
+        }
+        if single_line {
+            out = flatten(&out);
+        }
+        out.trim().to_string()
+    }
+}
+
+/// 按字素截断为最多 n 个（超出时最后一个换成 “…”），不会切开 emoji 或组合字符
+pub fn truncate(s: &str, n: usize) -> String {
+    let graphemes: Vec<&str> = s.graphemes(true).collect();
+    if graphemes.len() <= n {
+        return s.to_string();
+    }
+    graphemes[..n - 1].concat() + "…"
+}
+
+/// 换行（及其两侧空白）压成一个空格
+pub fn flatten(s: &str) -> String {
+    s.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// 可配置模板的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateTarget {
+    ToastTitle,
+    ToastBody,
+    TrayTooltip,
+    TrayMenu,
+    Clipboard,
+}
+
+impl TemplateTarget {
+    fn surface(self) -> Surface {
+        match self {
+            TemplateTarget::ToastTitle | TemplateTarget::ToastBody => Surface::Toast,
+            TemplateTarget::TrayTooltip | TemplateTarget::TrayMenu => Surface::TrayMenu,
+            TemplateTarget::Clipboard => Surface::MainList,
+        }
+    }
+
+    fn single_line(self) -> bool {
+        matches!(self, TemplateTarget::ToastTitle | TemplateTarget::TrayTooltip | TemplateTarget::TrayMenu)
+    }
+}
+
+/// 各位置的模板，None 表示使用原有格式（托盘提示为不显示最新通知）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct TemplateSettings {
+    pub toast_title: Option<String>,
+    pub toast_body: Option<String>,
+    /// 托盘提示中附加最新一条未读通知
+    pub tray_tooltip: Option<String>,
+    pub tray_menu: Option<String>,
+    /// 复制到剪贴板 / 导出文本
+    pub clipboard: Option<String>,
+}
+
+impl TemplateSettings {
+    fn entries(&self) -> [(&'static str, Option<&str>); 5] {
+        [
+            ("templates.toast_title", self.toast_title.as_deref()),
+            ("templates.toast_body", self.toast_body.as_deref()),
+            ("templates.tray_tooltip", self.tray_tooltip.as_deref()),
+            ("templates.tray_menu", self.tray_menu.as_deref()),
+            ("templates.clipboard", self.clipboard.as_deref()),
+        ]
+    }
+
+    pub fn get(&self, target: TemplateTarget) -> Option<&str> {
+        match target {
+            TemplateTarget::ToastTitle => self.toast_title.as_deref(),
+            TemplateTarget::ToastBody => self.toast_body.as_deref(),
+            TemplateTarget::TrayTooltip => self.tray_tooltip.as_deref(),
+            TemplateTarget::TrayMenu => self.tray_menu.as_deref(),
+            TemplateTarget::Clipboard => self.clipboard.as_deref(),
+        }
+    }
+
+    /// 各模板的总字节数（供入参上限检查）
+    pub fn total_bytes(&self) -> usize {
+        self.entries().iter().filter_map(|(_, t)| t.map(str::len)).sum()
+    }
+
+    pub fn validate(&self) -> Result<(), InvalidTemplate> {
+        for (field, template) in self.entries() {
+            if let Some(template) = template {
+                Template::parse(template).map_err(|e| InvalidTemplate { field: field.to_string(), ..e })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AppState {
+    /// 通知的模板字段（按该位置的隐私级别处理）
+    fn template_fields(&self, n: &Notification, surface: Surface) -> Fields {
+        let (preview, app, lang) = {
+            let settings = self.settings.read();
+            let preview = privacy::preview_for(&settings.privacy, n, surface);
+            let app = preview.app.as_deref().map(|p| app_meta::short_name(&settings.app_meta, p));
+            (preview, app, settings.lang())
+        };
+        Fields {
+            app,
+            title: Some(preview.title).filter(|t| !t.is_empty()),
+            text: preview.body.filter(|b| !b.is_empty()),
+            device: self.device_label(n),
+            time: n.posted_at.map(|ts| time_format::format_timestamp(ts, TimeStyle::Time, lang)),
+        }
+    }
+
+    /// 通知不记录来源连接：本地条目显示“本机”，只配对了一台设备时显示该设备，否则为空
+    fn device_label(&self, n: &Notification) -> Option<String> {
+        if n.local {
+            return Some(LOCAL_DEVICE.to_string());
+        }
+        let endpoints = self.endpoints.lock().unwrap();
+        let mut devices = endpoints.devices();
+        match (devices.next(), devices.next()) {
+            (Some(only), None) => Some(only.connection_id.clone()),
+            _ => None,
+        }
+    }
+
+    /// 按该位置配置的模板渲染；未配置模板（或模板已失效）时返回 None
+    pub(crate) fn render_template(&self, target: TemplateTarget, n: &Notification) -> Option<String> {
+        let template = self.settings.read().templates.get(target).map(Template::parse)?.ok()?;
+        Some(template.render(&self.template_fields(n, target.surface()), target.single_line()))
+    }
+
+    /// 设置界面实时预览：用指定通知渲染尚未保存的模板
+    pub fn preview_template(&self, template: &str, notification_id: &str) -> Result<String, String> {
+        let template = Template::parse(template).map_err(|e| InvalidTemplate { field: "template".to_string(), ..e })?;
+        let n = self
+            .store
+            .lock()
+            .unwrap()
+            .get(notification_id)
+            .cloned()
+            .ok_or_else(|| format!("Notification not found: {}", notification_id))?;
+        Ok(template.render(&self.template_fields(&n, Surface::MainList), false))
+    }
+
+    /// 复制/导出用的文本
+    pub fn notification_text(&self, id: &str) -> Result<String, String> {
+        let n = self.store.lock().unwrap().get(id).cloned().ok_or_else(|| format!("Notification not found: {}", id))?;
+        Ok(self.render_template(TemplateTarget::Clipboard, &n).unwrap_or_else(|| {
+            let template = Template::parse(DEFAULT_CLIPBOARD).expect("default template");
+            template.render(&self.template_fields(&n, Surface::MainList), false)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+
+    fn fields() -> Fields {
+        Fields {
+            app: Some("mm".into()),
+            title: Some("老板".into()),
+            text: Some("第一行\n  第二行👨‍👩‍👧‍👦很长".into()),
+            device: None,
+            time: Some("14:30".into()),
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_with_position() {
+        let e = Template::parse("{app}: {titel}").unwrap_err();
+        assert_eq!((e.position, e.message.as_str()), (7, "unknown placeholder {titel}"));
+        assert_eq!(Template::parse("标题 {title").unwrap_err().position, 3);
+        assert_eq!(Template::parse("a } b").unwrap_err().position, 2);
+        assert!(Template::parse("{text:0}").is_err());
+        assert!(Template::parse("{text:abc}").is_err());
+        assert_eq!(Template::parse("{{app}}").unwrap().render(&fields(), false), "{app}");
+
+        let settings = TemplateSettings { tray_menu: Some("{bogus}".into()), ..Default::default() };
+        let err: String = settings.validate().unwrap_err().into();
+        assert!(err.starts_with("InvalidTemplate: "));
+        assert!(err.contains("\"field\":\"templates.tray_menu\""));
+    }
+
+    #[test]
+    fn test_render_truncates_graphemes_and_flattens() {
+        let t = Template::parse("{app}: {title} — {text:6}").unwrap();
+        assert_eq!(t.render(&fields(), false), "mm: 老板 — 第一行\n …");
+        // 家庭 emoji 是一个字素，不会被切开
+        let t = Template::parse("{title} — {text:11}").unwrap();
+        assert_eq!(t.render(&fields(), true), "老板 — 第一行 第二行👨‍👩‍👧‍👦…");
+        assert_eq!(truncate("👨‍👩‍👧‍👦e\u{301}", 2), "👨‍👩‍👧‍👦e\u{301}");
+
+        // 缺失字段为空
+        let t = Template::parse("{device} {title}").unwrap();
+        assert_eq!(t.render(&Fields::default(), true), "");
+    }
+
+    #[test]
+    fn test_state_renders_with_privacy() {
+        let state = AppState::default();
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification {
+                id: "1".into(),
+                package_name: Some("com.tencent.mm".into()),
+                title: Some("老板".into()),
+                text: Some("明天开会".into()),
+                ..Default::default()
+            }),
+            id: None,
+        });
+        assert_eq!(state.notification_text("1").unwrap(), "老板\n明天开会");
+        assert_eq!(state.preview_template("{app}: {text:3}", "1").unwrap(), "微信: 明天…");
+        state.set_app_meta("com.tencent.mm", Some("家人群".into()), None).unwrap();
+        assert_eq!(state.preview_template("{app}", "1").unwrap(), "家人群");
+        assert!(state.preview_template("{nope}", "1").unwrap_err().starts_with("InvalidTemplate: "));
+        assert!(state.render_template(TemplateTarget::TrayMenu, &Notification::default()).is_none());
+
+        state.settings.write().templates.tray_menu = Some("{title}: {text}".into());
+        state.settings.write().privacy.hide_previews = true;
+        let n = state.store.lock().unwrap().get("1").cloned().unwrap();
+        assert_eq!(state.render_template(TemplateTarget::TrayMenu, &n).as_deref(), Some("新通知:"));
+    }
+}
@@ -0,0 +1,198 @@
+//! 时间戳格式化（本地化 + 时区正确）。
+//! 前端统一调用这里的实现，避免 JS 与 Rust 在夏令时、"昨天" 边界上的判断不一致。
+
+use chrono::{DateTime, Datelike, Local, TimeZone};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 格式化样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeStyle {
+    /// 相对时间："2分钟前" / "昨天 14:30"
+    #[default]
+    Relative,
+    /// 仅时间："14:30"
+    Time,
+    /// 完整日期时间："2024年3月5日 14:30"
+    Full,
+}
+
+/// 界面语言（由设置中的 locale 字符串解析）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+impl Lang {
+    pub fn from_locale(locale: &str) -> Self {
+        if locale.to_ascii_lowercase().starts_with("en") {
+            Lang::En
+        } else {
+            Lang::Zh
+        }
+    }
+}
+
+/// 使用系统本地时区（含夏令时）与当前时间格式化
+pub fn format_timestamp(ts: i64, style: TimeStyle, lang: Lang) -> String {
+    format_timestamp_at(ts, style, lang, &Local::now())
+}
+
+/// 以给定的 "now" 格式化，时区取自 `now`（便于测试固定时间与时区）
+pub fn format_timestamp_at<Tz: TimeZone>(ts: i64, style: TimeStyle, lang: Lang, now: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let tz = now.timezone();
+    let Some(t) = tz.timestamp_opt(ts, 0).earliest() else {
+        return String::new();
+    };
+
+    match style {
+        TimeStyle::Time => t.format("%H:%M").to_string(),
+        TimeStyle::Full => format_full(&t, lang),
+        TimeStyle::Relative => format_relative(&t, now, lang),
+    }
+}
+
+fn format_full<Tz: TimeZone>(t: &DateTime<Tz>, lang: Lang) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    match lang {
+        Lang::Zh => format!("{}年{}月{}日 {}", t.year(), t.month(), t.day(), t.format("%H:%M")),
+        Lang::En => t.format("%b %-d, %Y %H:%M").to_string(),
+    }
+}
+
+fn format_relative<Tz: TimeZone>(t: &DateTime<Tz>, now: &DateTime<Tz>, lang: Lang) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let secs = now.timestamp() - t.timestamp();
+
+    // 未来时间（手机时钟偏快等）：1 分钟内视为"刚刚"，否则直接显示绝对时间
+    if secs < -60 {
+        return format_full(t, lang);
+    }
+    if secs < 60 {
+        return match lang {
+            Lang::Zh => "刚刚".to_string(),
+            Lang::En => "just now".to_string(),
+        };
+    }
+    if secs < 3600 {
+        let m = secs / 60;
+        return match lang {
+            Lang::Zh => format!("{}分钟前", m),
+            Lang::En if m == 1 => "1 minute ago".to_string(),
+            Lang::En => format!("{} minutes ago", m),
+        };
+    }
+
+    // 按本地日历日比较（跨夏令时切换也按日期而不是 24 小时计算）
+    let days = (now.date_naive() - t.date_naive()).num_days();
+    let hm = t.format("%H:%M");
+    match days {
+        0 => {
+            let h = secs / 3600;
+            match lang {
+                Lang::Zh => format!("{}小时前", h),
+                Lang::En if h == 1 => "1 hour ago".to_string(),
+                Lang::En => format!("{} hours ago", h),
+            }
+        }
+        1 => match lang {
+            Lang::Zh => format!("昨天 {}", hm),
+            Lang::En => format!("Yesterday {}", hm),
+        },
+        _ if t.year() == now.year() => match lang {
+            Lang::Zh => format!("{}月{}日 {}", t.month(), t.day(), hm),
+            Lang::En => t.format("%b %-d %H:%M").to_string(),
+        },
+        _ => match lang {
+            Lang::Zh => format!("{}年{}月{}日", t.year(), t.month(), t.day()),
+            Lang::En => t.format("%b %-d, %Y").to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::New_York;
+    use chrono_tz::Asia::Shanghai;
+
+    fn ny(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<chrono_tz::Tz> {
+        New_York.with_ymd_and_hms(y, mo, d, h, mi, 0).earliest().unwrap()
+    }
+
+    #[test]
+    fn test_minutes_and_just_now() {
+        let now = Shanghai.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        let ts = now.timestamp();
+        assert_eq!(format_timestamp_at(ts - 30, TimeStyle::Relative, Lang::Zh, &now), "刚刚");
+        assert_eq!(format_timestamp_at(ts - 120, TimeStyle::Relative, Lang::Zh, &now), "2分钟前");
+        assert_eq!(format_timestamp_at(ts - 120, TimeStyle::Relative, Lang::En, &now), "2 minutes ago");
+        assert_eq!(format_timestamp_at(ts - 60, TimeStyle::Relative, Lang::En, &now), "1 minute ago");
+        assert_eq!(format_timestamp_at(ts - 3 * 3600, TimeStyle::Relative, Lang::Zh, &now), "3小时前");
+    }
+
+    #[test]
+    fn test_yesterday_boundary_uses_local_calendar_day() {
+        // 00:30 本地时间，1 小时前属于"昨天"而不是"1小时前"
+        let now = Shanghai.with_ymd_and_hms(2024, 5, 10, 0, 30, 0).unwrap();
+        let ts = Shanghai.with_ymd_and_hms(2024, 5, 9, 23, 20, 0).unwrap().timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::Zh, &now), "昨天 23:20");
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::En, &now), "Yesterday 23:20");
+
+        // UTC 下同一天，但 Asia/Shanghai 下是前一天
+        let ts = Shanghai.with_ymd_and_hms(2024, 5, 9, 14, 30, 0).unwrap().timestamp();
+        let now = Shanghai.with_ymd_and_hms(2024, 5, 10, 9, 0, 0).unwrap();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::Zh, &now), "昨天 14:30");
+    }
+
+    #[test]
+    fn test_dst_spring_forward() {
+        // 2024-03-10 02:00 纽约切换到夏令时（EST -> EDT）
+        let now = ny(2024, 3, 10, 12, 0);
+        let ts = ny(2024, 3, 9, 14, 30).timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::En, &now), "Yesterday 14:30");
+        // 01:30 EST 到 12:00 EDT 实际只过了 9.5 小时
+        let ts = ny(2024, 3, 10, 1, 30).timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::En, &now), "9 hours ago");
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Time, Lang::En, &now), "01:30");
+        let ts = ny(2024, 3, 10, 3, 15).timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Time, Lang::En, &now), "03:15");
+    }
+
+    #[test]
+    fn test_dst_fall_back() {
+        // 2024-11-03 02:00 纽约回到标准时间，当天有 25 小时
+        let now = ny(2024, 11, 3, 23, 30);
+        let ts = ny(2024, 11, 3, 0, 10).timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::En, &now), "24 hours ago");
+        let ts = ny(2024, 11, 2, 23, 50).timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::Zh, &now), "昨天 23:50");
+    }
+
+    #[test]
+    fn test_older_and_future() {
+        let now = Shanghai.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        let ts = Shanghai.with_ymd_and_hms(2024, 3, 5, 8, 5, 0).unwrap().timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::Zh, &now), "3月5日 08:05");
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::En, &now), "Mar 5 08:05");
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Full, Lang::Zh, &now), "2024年3月5日 08:05");
+
+        let ts = Shanghai.with_ymd_and_hms(2023, 12, 31, 8, 5, 0).unwrap().timestamp();
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::Zh, &now), "2023年12月31日");
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::En, &now), "Dec 31, 2023");
+
+        // 手机时钟略快：1 分钟内的未来时间视为刚刚
+        assert_eq!(format_timestamp_at(now.timestamp() + 20, TimeStyle::Relative, Lang::Zh, &now), "刚刚");
+        let ts = now.timestamp() + 7200;
+        assert_eq!(format_timestamp_at(ts, TimeStyle::Relative, Lang::Zh, &now), "2024年5月10日 14:00");
+    }
+}
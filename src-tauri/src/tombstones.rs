@@ -0,0 +1,137 @@
+//! 删除记录（墓碑）：记录每条通知消失的原因，回答“是我删的还是手机上划掉的”。
+//! 只保留最近 MAX_TOMBSTONES 条，同一 id 只保留最新一条；不参与未读计数与搜索。
+
+use std::collections::VecDeque;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::types::Notification;
+
+pub const MAX_TOMBSTONES: usize = 200;
+
+/// 通知被移除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalReason {
+    /// 用户在桌面端删除
+    UserDeletedLocal,
+    /// 手机端已划掉
+    DismissedOnPhone,
+    /// 手机端划掉，按设置只在本地标为已读（见 phone_dismiss）
+    DismissedOnPhoneMarkedRead,
+    /// 手机端划掉，按设置在本地保留原状
+    DismissedOnPhoneKept,
+    /// 超出保留上限被淘汰
+    RetentionEvicted,
+    /// 屏蔽应用后清理其历史通知
+    PackageBlockedPurge,
+    /// 回收站清空
+    TrashPurged,
+    /// 在接收转发的另一台电脑上删除（见 relay）
+    DismissedOnPeer,
+    /// 旧版本记录的自动已读（现在记入审计日志，见 auto_read_now）
+    AutoRead,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Tombstone {
+    pub id: String,
+    pub package_name: Option<String>,
+    pub title: Option<String>,
+    pub reason: RemovalReason,
+    pub removed_at: i64,
+}
+
+impl Tombstone {
+    pub fn new(n: &Notification, reason: RemovalReason, removed_at: i64) -> Self {
+        Self {
+            id: n.id.clone(),
+            package_name: n.package_name.clone(),
+            title: n.title.clone(),
+            reason,
+            removed_at,
+        }
+    }
+}
+
+/// 按时间顺序保存的墓碑（队尾最新）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct RemovalLog {
+    entries: VecDeque<Tombstone>,
+}
+
+impl RemovalLog {
+    pub fn record(&mut self, tombstone: Tombstone) {
+        self.entries.retain(|t| t.id != tombstone.id);
+        self.entries.push_back(tombstone);
+        while self.entries.len() > MAX_TOMBSTONES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// 最近的记录（新 -> 旧）
+    pub fn recent(&self, limit: usize) -> Vec<Tombstone> {
+        self.entries.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 出现多次的 id（正常情况下同一 id 只有一条）
+    pub fn duplicate_ids(&self) -> Vec<String> {
+        let mut seen = std::collections::HashSet::new();
+        self.entries.iter().filter(|t| !seen.insert(t.id.as_str())).map(|t| t.id.clone()).collect()
+    }
+
+    /// 同一 id 只保留最新一条，并截到上限；返回去掉的条数
+    pub fn dedupe(&mut self) -> usize {
+        let before = self.entries.len();
+        let mut seen = std::collections::HashSet::new();
+        let kept: VecDeque<Tombstone> = self.entries.iter().rev().filter(|t| seen.insert(t.id.clone())).cloned().collect();
+        self.entries = kept.into_iter().rev().collect();
+        while self.entries.len() > MAX_TOMBSTONES {
+            self.entries.pop_front();
+        }
+        before - self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notif(id: &str) -> Notification {
+        Notification { id: id.into(), title: Some("t".into()), ..Default::default() }
+    }
+
+    #[test]
+    fn test_same_id_replaced_and_capped() {
+        let mut log = RemovalLog::default();
+        log.record(Tombstone::new(&notif("a"), RemovalReason::DismissedOnPhone, 1));
+        log.record(Tombstone::new(&notif("b"), RemovalReason::UserDeletedLocal, 2));
+        log.record(Tombstone::new(&notif("a"), RemovalReason::UserDeletedLocal, 3));
+        let recent = log.recent(10);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].id, "a");
+        assert_eq!(recent[0].reason, RemovalReason::UserDeletedLocal);
+
+        for i in 0..MAX_TOMBSTONES + 10 {
+            log.record(Tombstone::new(&notif(&i.to_string()), RemovalReason::RetentionEvicted, i as i64));
+        }
+        assert_eq!(log.recent(usize::MAX).len(), MAX_TOMBSTONES);
+        assert_eq!(log.recent(1)[0].id, (MAX_TOMBSTONES + 9).to_string());
+    }
+
+    #[test]
+    fn test_serializes_as_list() {
+        let mut log = RemovalLog::default();
+        log.record(Tombstone::new(&notif("a"), RemovalReason::TrashPurged, 1));
+        let json = serde_json::to_value(&log).unwrap();
+        assert_eq!(json[0]["reason"], "trash_purged");
+        let back: RemovalLog = serde_json::from_value(json).unwrap();
+        assert_eq!(back.recent(1).len(), 1);
+    }
+}
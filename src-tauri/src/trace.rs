@@ -0,0 +1,246 @@
+//! 单连接调试追踪：开启后，该连接收发的每一帧以摘要形式推送 connection-trace 事件。
+//! 摘要只包含方向、action/event_type、大小、requestId 与延迟，从不包含载荷内容。
+//! 同一时间最多追踪一个连接，10 分钟后自动停止；未开启时收发路径只多一次 relaxed 原子读。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::events::EventSink;
+use crate::inspector::{self, FrameInspector};
+
+/// 追踪自动停止的时长
+pub const TRACE_DURATION: Duration = Duration::from_secs(10 * 60);
+/// 等待响应的 requestId 上限（防止只发不回时无限增长）
+pub const MAX_PENDING: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Send,
+    Recv,
+}
+
+/// 一帧的摘要（不含载荷）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameSummary {
+    pub connection_id: String,
+    pub direction: Direction,
+    pub action: Option<String>,
+    pub event_type: Option<String>,
+    pub size: usize,
+    pub request_id: Option<String>,
+    /// 收到响应时，距离发出同一 requestId 请求的毫秒数
+    pub latency_ms: Option<u64>,
+    pub at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TraceStatus {
+    pub connection_id: String,
+    pub session: u64,
+    /// 剩余秒数
+    pub remaining_secs: u64,
+}
+
+struct Target {
+    connection_id: String,
+    session: u64,
+    until: Instant,
+    pending: HashMap<String, Instant>,
+}
+
+#[derive(Default)]
+pub struct ConnectionTracer {
+    // 快速路径：未开启时直接返回
+    active: AtomicBool,
+    next_session: AtomicU64,
+    target: Mutex<Option<Target>>,
+    pub(crate) events: EventSink,
+    // 开发用的协议帧检查器（见 inspector）
+    pub(crate) inspector: FrameInspector,
+}
+
+/// 从原始帧中提取摘要字段；非 JSON 帧只记录大小
+pub fn summarize(connection_id: &str, direction: Direction, raw: &str) -> FrameSummary {
+    let value: Option<serde_json::Value> = serde_json::from_str(raw.trim()).ok();
+    let field = |name: &str| {
+        value
+            .as_ref()
+            .and_then(|v| v.get(name))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    FrameSummary {
+        connection_id: connection_id.to_string(),
+        direction,
+        action: field("action"),
+        event_type: field("event_type"),
+        size: raw.trim_end().len(),
+        request_id: field("requestId"),
+        latency_ms: None,
+        at: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+impl ConnectionTracer {
+    /// 开始追踪（替换正在进行的追踪），返回本次会话号
+    pub fn start(&self, connection_id: &str, now: Instant) -> u64 {
+        let session = self.next_session.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut target = self.target.lock();
+        if let Some(old) = target.as_ref() {
+            println!("[Trace] Replacing trace of {}", old.connection_id);
+        }
+        *target = Some(Target {
+            connection_id: connection_id.to_string(),
+            session,
+            until: now + TRACE_DURATION,
+            pending: HashMap::new(),
+        });
+        self.active.store(true, Ordering::Relaxed);
+        println!("[Trace] Tracing {} (session {})", connection_id, session);
+        session
+    }
+
+    /// 停止追踪；指定 session 时只停止该次会话（避免旧定时器停掉新追踪）
+    pub fn stop(&self, session: Option<u64>) -> bool {
+        let mut target = self.target.lock();
+        match target.as_ref() {
+            Some(t) if session.is_none_or(|s| s == t.session) => {
+                println!("[Trace] Stopped tracing {}", t.connection_id);
+                self.events.emit(
+                    "connection-trace-stopped",
+                    serde_json::json!({ "connection_id": t.connection_id, "session": t.session }),
+                );
+                *target = None;
+                self.active.store(false, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 等待响应的 requestId 数
+    pub fn pending_requests(&self) -> usize {
+        self.target.lock().as_ref().map_or(0, |t| t.pending.len())
+    }
+
+    pub fn status(&self, now: Instant) -> Option<TraceStatus> {
+        self.target.lock().as_ref().map(|t| TraceStatus {
+            connection_id: t.connection_id.clone(),
+            session: t.session,
+            remaining_secs: t.until.saturating_duration_since(now).as_secs(),
+        })
+    }
+
+    /// 收发路径调用：仅当该连接正在被追踪时生成摘要并推送
+    pub fn record(&self, connection_id: &str, direction: Direction, raw: &str) {
+        if inspector::AVAILABLE {
+            self.inspector.record(connection_id, direction, raw, &self.events);
+        }
+        if !self.active.load(Ordering::Relaxed) {
+            return;
+        }
+        self.record_at(connection_id, direction, raw, Instant::now());
+    }
+
+    fn record_at(&self, connection_id: &str, direction: Direction, raw: &str, now: Instant) {
+        let mut guard = self.target.lock();
+        let Some(target) = guard.as_mut() else {
+            return;
+        };
+        if target.connection_id != connection_id {
+            return;
+        }
+        if now >= target.until {
+            let session = target.session;
+            drop(guard);
+            self.stop(Some(session));
+            return;
+        }
+
+        let mut summary = summarize(connection_id, direction, raw);
+        if let Some(id) = summary.request_id.clone() {
+            match direction {
+                Direction::Send => {
+                    // 超过读超时仍未回复的请求不会再有响应，按时间清理；数量上限只是兜底
+                    target.pending.retain(|_, sent| now.duration_since(*sent) < crate::transport::READ_TIMEOUT);
+                    if target.pending.len() >= MAX_PENDING {
+                        target.pending.clear();
+                    }
+                    target.pending.insert(id, now);
+                }
+                Direction::Recv => {
+                    summary.latency_ms = target
+                        .pending
+                        .remove(&id)
+                        .map(|sent| now.duration_since(sent).as_millis() as u64);
+                }
+            }
+        }
+        drop(guard);
+        self.events.emit("connection-trace", summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_never_includes_payload() {
+        let raw = r#"{"action":"login","requestId":"r1","token":"secret-token"}"#;
+        let s = summarize("c1", Direction::Send, raw);
+        assert_eq!(s.action.as_deref(), Some("login"));
+        assert_eq!(s.request_id.as_deref(), Some("r1"));
+        assert_eq!(s.size, raw.len());
+        let json = serde_json::to_string(&s).unwrap();
+        assert!(!json.contains("secret-token"));
+
+        let s = summarize("c1", Direction::Recv, "not json\n");
+        assert_eq!(s.action, None);
+        assert_eq!(s.size, 8);
+    }
+
+    #[test]
+    fn test_only_traced_connection_is_recorded_with_latency() {
+        let tracer = ConnectionTracer::default();
+        tracer.record("c1", Direction::Send, r#"{"action":"x"}"#);
+        assert!(tracer.events.take_captured().is_empty());
+
+        let t0 = Instant::now();
+        tracer.start("c1", t0);
+        tracer.record_at("c2", Direction::Send, r#"{"action":"x"}"#, t0);
+        tracer.record_at("c1", Direction::Send, r#"{"action":"x","requestId":"r1"}"#, t0);
+        tracer.record_at("c1", Direction::Recv, r#"{"success":true,"requestId":"r1"}"#, t0 + Duration::from_millis(42));
+        let events = tracer.events.take_captured();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].1["latency_ms"], 42);
+
+        // 切换追踪目标后，旧连接不再记录
+        tracer.start("c2", t0);
+        tracer.record_at("c1", Direction::Send, "{}", t0);
+        assert!(tracer.events.take_captured().is_empty());
+    }
+
+    #[test]
+    fn test_auto_stop_and_stale_session() {
+        let tracer = ConnectionTracer::default();
+        let t0 = Instant::now();
+        let s1 = tracer.start("c1", t0);
+        let s2 = tracer.start("c1", t0);
+        // 旧会话的定时器不能停掉新追踪
+        assert!(!tracer.stop(Some(s1)));
+        assert_eq!(tracer.status(t0).unwrap().session, s2);
+
+        tracer.record_at("c1", Direction::Send, "{}", t0 + TRACE_DURATION);
+        let events = tracer.events.take_captured();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, "connection-trace-stopped");
+        assert!(tracer.status(t0).is_none());
+    }
+}
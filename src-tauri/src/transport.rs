@@ -0,0 +1,188 @@
+//! 与安卓端通信的传输层：每条协议消息是一行 JSON。
+//! 直连时走 TCP（按行分帧）；双方处于 Wi-Fi 客户端隔离等无法直连的网络时，
+//! 经用户自建的中继走 WebSocket（一条 Text 消息对应一行）。上层协议（认证、事件、心跳）不变。
+
+use std::io::{BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use crate::protocol;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub(crate) const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub trait Transport: Send {
+    /// 发送一条消息（不含换行）
+    fn send_line(&mut self, line: &str) -> Result<(), String>;
+    /// 接收一条消息（已去掉换行）
+    fn recv_line(&mut self) -> Result<String, String>;
+    /// 调整读超时（低功耗模式下放宽）；不支持的传输忽略
+    fn set_read_timeout(&mut self, _timeout: Duration) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub struct TcpTransport {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn connect(host: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect_timeout(
+            &host.parse().map_err(|e| format!("Invalid host: {}", e))?,
+            CONNECT_TIMEOUT,
+        ).map_err(|e| format!("Connection failed: {}", e))?;
+
+        stream.set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(|e| format!("Failed to set read timeout: {}", e))?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))
+            .map_err(|e| format!("Failed to set write timeout: {}", e))?;
+
+        let reader = stream.try_clone().map_err(|e| format!("Failed to clone stream: {}", e))?;
+        Ok(Self { reader: BufReader::new(reader), writer: stream })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send_line(&mut self, line: &str) -> Result<(), String> {
+        self.writer.write_all(format!("{}\n", line).as_bytes())
+            .map_err(|e| format!("Failed to send: {}", e))?;
+        self.writer.flush().map_err(|e| format!("Failed to flush: {}", e))
+    }
+
+    fn recv_line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        let n = protocol::read_frame(&mut self.reader, &mut line)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        if n == 0 {
+            return Err("Connection closed".to_string());
+        }
+        Ok(line.trim().to_string())
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), String> {
+        self.writer.set_read_timeout(Some(timeout))
+            .map_err(|e| format!("Failed to set read timeout: {}", e))
+    }
+}
+
+/// 加入中继房间的第一条消息；之后中继在同一房间的两端之间原样转发
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoinRoom {
+    pub action: String,
+    pub room: String,
+    pub role: String,
+}
+
+pub struct WsTransport {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+}
+
+impl WsTransport {
+    /// 连接中继（ws:// 地址）并加入 room_token 对应的房间
+    pub fn connect(relay_url: &str, room_token: &str) -> Result<Self, String> {
+        if room_token.trim().is_empty() {
+            return Err("Room token must not be empty".to_string());
+        }
+        let uri: tungstenite::http::Uri = relay_url.parse().map_err(|e| format!("Invalid relay url: {}", e))?;
+        if uri.scheme_str() != Some("ws") {
+            return Err(format!("Unsupported relay scheme (only ws://): {}", relay_url));
+        }
+        let host = uri.host().ok_or("Relay url has no host")?;
+        let addr = format!("{}:{}", host, uri.port_u16().unwrap_or(80));
+        let stream = TcpTransport::connect(&addr)?.writer;
+
+        let (socket, _) = tungstenite::client(relay_url, MaybeTlsStream::Plain(stream))
+            .map_err(|e| format!("Relay handshake failed: {}", e))?;
+        let mut transport = Self { socket };
+        let join = JoinRoom {
+            action: "join_room".to_string(),
+            room: room_token.to_string(),
+            role: "desktop".to_string(),
+        };
+        transport.send_line(&serde_json::to_string(&join).map_err(|e| e.to_string())?)?;
+        Ok(transport)
+    }
+}
+
+impl Transport for WsTransport {
+    fn send_line(&mut self, line: &str) -> Result<(), String> {
+        self.socket.send(Message::Text(line.to_string()))
+            .map_err(|e| format!("Failed to send: {}", e))
+    }
+
+    fn recv_line(&mut self) -> Result<String, String> {
+        loop {
+            let msg = self.socket.read().map_err(|e| format!("Failed to read response: {}", e))?;
+            if msg.len() > protocol::MAX_FRAME_BYTES {
+                return Err(format!("Frame too large ({} bytes)", msg.len()));
+            }
+            match msg {
+                Message::Text(text) => return Ok(text.trim().to_string()),
+                Message::Binary(bytes) => {
+                    return String::from_utf8(bytes)
+                        .map(|s| s.trim().to_string())
+                        .map_err(|e| format!("Invalid UTF-8 frame: {}", e));
+                }
+                Message::Close(_) => return Err("Connection closed".to_string()),
+                // Ping 由 tungstenite 自动回复
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+            }
+        }
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), String> {
+        match self.socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(timeout))
+                .map_err(|e| format!("Failed to set read timeout: {}", e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// 模拟中继：接受一个 WebSocket，记录加入的房间，把收到的每条消息加上前缀回发
+    fn mock_relay() -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/relay", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut ws = tungstenite::accept(stream).unwrap();
+            let join: JoinRoom = serde_json::from_str(ws.read().unwrap().to_text().unwrap()).unwrap();
+            ws.send(Message::Ping(vec![1])).unwrap();
+            while let Ok(msg) = ws.read() {
+                if let Message::Text(t) = msg {
+                    ws.send(Message::Text(format!("echo:{}", t))).unwrap();
+                }
+            }
+            join.room
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_ws_transport_roundtrip() {
+        let (url, relay) = mock_relay();
+        let mut t = WsTransport::connect(&url, "room-42").unwrap();
+        t.send_line("{\"action\":\"login\"}").unwrap();
+        assert_eq!(t.recv_line().unwrap(), "echo:{\"action\":\"login\"}");
+        drop(t);
+        assert_eq!(relay.join().unwrap(), "room-42");
+    }
+
+    #[test]
+    fn test_ws_rejects_bad_urls() {
+        assert!(WsTransport::connect("wss://example.com", "r").is_err());
+        assert!(WsTransport::connect("ws://127.0.0.1:1", " ").is_err());
+    }
+}
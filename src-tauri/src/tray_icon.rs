@@ -0,0 +1,279 @@
+//! 托盘图标绘制：按连接状态、系统主题与高对比度设置生成 RGBA 图标。
+//! 高对比度模式下用形状区分状态（实心圆 = 已连接，空心圆 = 未连接，三角 = 出错），不依赖颜色。
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use crate::commands::AppState;
+use crate::tray_updates::TraySurface;
+
+/// 托盘图标 id（构建托盘时指定，之后据此查找并更新图标）
+pub const TRAY_ID: &str = "main";
+pub const ICON_SIZE: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrayStatus {
+    Connected,
+    Disconnected,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    FilledCircle,
+    HollowCircle,
+    Triangle,
+}
+
+type Rgba = [u8; 4];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub fill: Rgba,
+    /// 描边（浅色任务栏上加深边缘，避免图标“消失”）
+    pub outline: Option<Rgba>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconSpec {
+    pub palette: Palette,
+    pub shape: Shape,
+}
+
+const GREEN: Rgba = [0x2a, 0xc5, 0x74, 0xff];
+const DARK_GREEN: Rgba = [0x13, 0x7a, 0x43, 0xff];
+const RED: Rgba = [0xe5, 0x48, 0x4d, 0xff];
+const DARK_RED: Rgba = [0xa8, 0x1e, 0x24, 0xff];
+const GRAY: Rgba = [0x9a, 0x9a, 0x9a, 0xff];
+const DARK_GRAY: Rgba = [0x5a, 0x5a, 0x5a, 0xff];
+const BLACK: Rgba = [0x00, 0x00, 0x00, 0xff];
+const WHITE: Rgba = [0xff, 0xff, 0xff, 0xff];
+
+/// 根据状态、主题与高对比度设置选择图标
+pub fn spec_for(status: TrayStatus, theme: tauri::Theme, high_contrast: bool) -> IconSpec {
+    let light = matches!(theme, tauri::Theme::Light);
+    if high_contrast {
+        let color = if light { BLACK } else { WHITE };
+        let shape = match status {
+            TrayStatus::Connected => Shape::FilledCircle,
+            TrayStatus::Disconnected => Shape::HollowCircle,
+            TrayStatus::Error => Shape::Triangle,
+        };
+        return IconSpec { palette: Palette { fill: color, outline: None }, shape };
+    }
+
+    let (fill, outline) = match status {
+        TrayStatus::Connected => (GREEN, DARK_GREEN),
+        TrayStatus::Disconnected => (GRAY, DARK_GRAY),
+        TrayStatus::Error => (RED, DARK_RED),
+    };
+    IconSpec {
+        palette: Palette { fill, outline: light.then_some(outline) },
+        // 非高对比度下出错也用三角，颜色之外再给一个形状线索
+        shape: if status == TrayStatus::Error { Shape::Triangle } else { Shape::FilledCircle },
+    }
+}
+
+/// 绘制 size x size 的 RGBA 图标
+pub fn render(spec: &IconSpec, size: u32) -> Vec<u8> {
+    let n = size as f32;
+    let c = n / 2.0;
+    let r = n * 0.375;
+    // 空心圆环宽度、描边宽度
+    let ring = (n / 8.0).max(1.5);
+    let edge = (n / 20.0).max(1.0);
+
+    let mut rgba = vec![0u8; (size * size * 4) as usize];
+    for y in 0..size {
+        for x in 0..size {
+            let px = x as f32 + 0.5;
+            let py = y as f32 + 0.5;
+            // inside: 距形状边缘的距离（正数在内部）
+            let inside = match spec.shape {
+                Shape::FilledCircle | Shape::HollowCircle => r - ((px - c).powi(2) + (py - c).powi(2)).sqrt(),
+                Shape::Triangle => triangle_depth(px, py, c, r),
+            };
+            if inside < 0.0 {
+                continue;
+            }
+            if spec.shape == Shape::HollowCircle && inside > ring {
+                continue;
+            }
+            let color = match spec.palette.outline {
+                Some(outline) if inside <= edge => outline,
+                _ => spec.palette.fill,
+            };
+            let idx = ((y * size + x) * 4) as usize;
+            rgba[idx..idx + 4].copy_from_slice(&color);
+        }
+    }
+    rgba
+}
+
+impl AppState {
+    /// 按当前连接状态、主窗口主题与设置重绘托盘图标（经 tray_updates 合并）
+    pub fn refresh_tray_icon(&self) {
+        self.tray_updates.request(TraySurface::Icon);
+    }
+
+    /// 主题已知时（如 ThemeChanged 事件）记下，之后的重绘使用它
+    pub fn refresh_tray_icon_with(&self, theme: tauri::Theme) {
+        self.tray_updates.set_theme(theme);
+        self.refresh_tray_icon();
+    }
+
+    /// 当前应显示的图标
+    pub(crate) fn tray_icon_spec(&self, app: &tauri::AppHandle) -> IconSpec {
+        let theme = self
+            .tray_updates
+            .theme()
+            .or_else(|| app.get_webview_window("main").and_then(|w| w.theme().ok()))
+            .unwrap_or(tauri::Theme::Dark);
+        let status = if self.has_connections() { TrayStatus::Connected } else { TrayStatus::Disconnected };
+        spec_for(status, theme, self.settings.read().high_contrast_tray)
+    }
+}
+
+/// 点到尖朝上的三角形三条边的最小距离（正数在内部）
+fn triangle_depth(px: f32, py: f32, c: f32, r: f32) -> f32 {
+    let top = (c, c - r);
+    let left = (c - r, c + r * 0.8);
+    let right = (c + r, c + r * 0.8);
+    let edge = |a: (f32, f32), b: (f32, f32)| {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = (dx * dx + dy * dy).sqrt();
+        // 顶点顺时针排列，内部在边的右侧
+        ((py - a.1) * dx - (px - a.0) * dy) / len
+    };
+    edge(top, right).min(edge(right, left)).min(edge(left, top))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把 RGBA 转成字符画：'.' 透明，'#' 填充色，'o' 描边色
+    fn ascii(spec: &IconSpec, size: u32) -> String {
+        let rgba = render(spec, size);
+        let mut out = String::new();
+        for y in 0..size {
+            for x in 0..size {
+                let i = ((y * size + x) * 4) as usize;
+                let px: Rgba = rgba[i..i + 4].try_into().unwrap();
+                out.push(if px[3] == 0 {
+                    '.'
+                } else if px == spec.palette.fill {
+                    '#'
+                } else {
+                    'o'
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    #[test]
+    fn test_high_contrast_shapes_snapshot() {
+        let dark = tauri::Theme::Dark;
+        let filled = ascii(&spec_for(TrayStatus::Connected, dark, true), 12);
+        assert_eq!(
+            filled,
+            "\
+............
+............
+...######...
+..########..
+..########..
+..########..
+..########..
+..########..
+..########..
+...######...
+............
+............
+"
+        );
+        let hollow = ascii(&spec_for(TrayStatus::Disconnected, dark, true), 12);
+        assert_eq!(
+            hollow,
+            "\
+............
+............
+...######...
+..##....##..
+..#......#..
+..#......#..
+..#......#..
+..#......#..
+..##....##..
+...######...
+............
+............
+"
+        );
+        let triangle = ascii(&spec_for(TrayStatus::Error, dark, true), 12);
+        assert_eq!(
+            triangle,
+            "\
+............
+............
+.....##.....
+.....##.....
+....####....
+....####....
+...######...
+...######...
+..########..
+..########..
+............
+............
+"
+        );
+    }
+
+    #[test]
+    fn test_light_theme_outline_snapshot() {
+        let spec = spec_for(TrayStatus::Connected, tauri::Theme::Light, false);
+        assert_eq!(
+            ascii(&spec, 16),
+            "\
+................
+................
+......oooo......
+....oo####oo....
+...o########o...
+...o########o...
+..o##########o..
+..o##########o..
+..o##########o..
+..o##########o..
+...o########o...
+...o########o...
+....oo####oo....
+......oooo......
+................
+................
+"
+        );
+    }
+
+    #[test]
+    fn test_palettes_follow_theme() {
+        let light = spec_for(TrayStatus::Connected, tauri::Theme::Light, false);
+        let dark = spec_for(TrayStatus::Connected, tauri::Theme::Dark, false);
+        assert_eq!(light.palette.outline, Some(DARK_GREEN));
+        assert_eq!(dark.palette.outline, None);
+        // 浅色任务栏：边缘为深色描边，中心为填充色
+        let rgba = render(&light, ICON_SIZE);
+        let center = ((16 * ICON_SIZE + 16) * 4) as usize;
+        assert_eq!(rgba[center..center + 4], GREEN);
+        let edge = ((16 * ICON_SIZE + 4) * 4) as usize;
+        assert_eq!(rgba[edge..edge + 4], DARK_GREEN);
+
+        let hc_light = spec_for(TrayStatus::Connected, tauri::Theme::Light, true);
+        assert_eq!(hc_light.palette.fill, BLACK);
+        assert_eq!(render(&dark, ICON_SIZE).len(), (ICON_SIZE * ICON_SIZE * 4) as usize);
+    }
+}
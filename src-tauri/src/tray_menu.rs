@@ -0,0 +1,146 @@
+//! 托盘菜单：（仅托盘模式下的“界面不可用”）、显示/隐藏、最近通知子菜单（按隐私级别渲染）、“隐藏预览”开关、设置、退出。
+//! 设置了 tray_menu 模板时菜单项按模板渲染。最近通知或隐私设置变化时整体重建菜单。
+
+use tauri::menu::{CheckMenuItemBuilder, Menu, MenuBuilder, MenuItemBuilder, SubmenuBuilder};
+use tauri::{AppHandle, Runtime};
+
+use crate::app_meta::{self, AppMetaMap};
+use crate::commands::AppState;
+use crate::privacy::{self, Preview, PrivacySettings, Surface};
+use crate::store::SortMode;
+use crate::templates::{self, TemplateTarget};
+use crate::tray_updates::TraySurface;
+use crate::types::Notification;
+use crate::ui_window::UI_UNAVAILABLE_ID;
+
+/// 子菜单中最近通知的条数
+pub const RECENT_ITEMS: usize = 5;
+/// 菜单项文字最长字符数
+const MAX_LABEL_CHARS: usize = 40;
+/// 最近通知菜单项 id 前缀
+pub const RECENT_PREFIX: &str = "recent:";
+pub const HIDE_PREVIEWS_ID: &str = "hide_previews";
+
+/// 菜单项文字：应用 · 标题 — 正文（超长截断）；p.app 已是显示用的短名称
+pub fn label_for(p: &Preview) -> String {
+    let mut label = match &p.app {
+        Some(app) => format!("{} · {}", app, p.title),
+        None => p.title.clone(),
+    };
+    if let Some(body) = p.body.as_deref().filter(|b| !b.is_empty()) {
+        label = format!("{} — {}", label, body);
+    }
+    if label.chars().count() > MAX_LABEL_CHARS {
+        label = label.chars().take(MAX_LABEL_CHARS - 1).collect::<String>() + "…";
+    }
+    label
+}
+
+/// 最近通知子菜单的 (id, 文字)
+pub fn recent_entries(privacy: &PrivacySettings, meta: &AppMetaMap, recent: &[Notification]) -> Vec<(String, String)> {
+    recent
+        .iter()
+        .map(|n| {
+            let mut preview = privacy::preview_for(privacy, n, Surface::TrayMenu);
+            preview.app = preview.app.map(|p| app_meta::short_name(meta, &p));
+            (format!("{}{}", RECENT_PREFIX, n.id), label_for(&preview))
+        })
+        .collect()
+}
+
+pub fn build_menu<R: Runtime>(
+    app: &AppHandle<R>,
+    recent: &[(String, String)],
+    hide_previews: bool,
+    ui_available: bool,
+) -> tauri::Result<Menu<R>> {
+    let mut submenu = SubmenuBuilder::new(app, "最近通知");
+    if recent.is_empty() {
+        submenu = submenu.item(&MenuItemBuilder::with_id("recent:none", "（无）").enabled(false).build(app)?);
+    }
+    for (id, label) in recent {
+        submenu = submenu.item(&MenuItemBuilder::with_id(id.as_str(), label).build(app)?);
+    }
+    let mut menu = MenuBuilder::new(app);
+    // 仅托盘模式：首项说明界面不可用，点击查看原因
+    if !ui_available {
+        menu = menu
+            .item(&MenuItemBuilder::with_id(UI_UNAVAILABLE_ID, "界面不可用 — 点击查看详情").build(app)?)
+            .separator();
+    }
+    menu.item(&MenuItemBuilder::with_id("toggle", "显示/隐藏").build(app)?)
+        .item(&submenu.build()?)
+        .item(&CheckMenuItemBuilder::with_id(HIDE_PREVIEWS_ID, "隐藏预览").checked(hide_previews).build(app)?)
+        .separator()
+        .item(&MenuItemBuilder::with_id("settings", "设置").build(app)?)
+        .item(&MenuItemBuilder::with_id("quit", "退出").build(app)?)
+        .build()
+}
+
+impl AppState {
+    /// 按最近通知与隐私设置重建托盘菜单（经 tray_updates 合并）
+    pub fn refresh_tray_menu(&self) {
+        self.tray_updates.request(TraySurface::Menu);
+    }
+
+    /// 菜单内容：(最近通知项, 隐藏预览, 界面可用)
+    pub(crate) fn tray_menu_state(&self) -> (Vec<(String, String)>, bool, bool) {
+        let recent = self.store.lock().unwrap().query(SortMode::Newest, |_| true, 0, Some(RECENT_ITEMS));
+        let (privacy, meta) = {
+            let settings = self.settings.read();
+            (settings.privacy.clone(), settings.app_meta.clone())
+        };
+        let mut entries = recent_entries(&privacy, &meta, &recent);
+        for ((_, label), n) in entries.iter_mut().zip(&recent) {
+            if let Some(rendered) = self.render_template(TemplateTarget::TrayMenu, n) {
+                *label = templates::truncate(&rendered, MAX_LABEL_CHARS);
+            }
+        }
+        (entries, privacy.hide_previews, self.ui.is_available())
+    }
+
+    /// 切换全局“隐藏预览”，持久化并立即重建托盘菜单
+    pub fn set_hide_previews(&self, hide: bool) -> Result<(), String> {
+        let settings = {
+            let mut settings = self.settings.write();
+            settings.privacy.hide_previews = hide;
+            settings.clone()
+        };
+        self.storage.save(crate::commands::SETTINGS_FILE, &settings)?;
+        self.events.emit("privacy-changed", &settings.privacy);
+        self.refresh_tray_menu();
+        self.refresh_tray_tooltip();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::PreviewLevel;
+
+    #[test]
+    fn test_recent_entries_follow_privacy() {
+        let n = Notification {
+            id: "1".into(),
+            package_name: Some("com.tencent.mm".into()),
+            title: Some("老板".into()),
+            text: Some("明天的会议改到下午三点，请大家提前准备好季度汇报材料，不要迟到".into()),
+            ..Default::default()
+        };
+        let mut privacy = PrivacySettings::default();
+        let meta = AppMetaMap::new();
+        let full = recent_entries(&privacy, &meta, std::slice::from_ref(&n));
+        assert_eq!(full[0].0, "recent:1");
+        assert!(full[0].1.starts_with("微信 · 老板 — 明天"));
+        assert!(full[0].1.ends_with('…'));
+        assert_eq!(full[0].1.chars().count(), MAX_LABEL_CHARS);
+
+        privacy.per_app.insert("com.tencent.mm".into(), PreviewLevel::TitleOnly);
+        assert_eq!(recent_entries(&privacy, &meta, std::slice::from_ref(&n))[0].1, "微信 · 老板");
+        let other = Notification { package_name: Some("com.corp.oa".into()), ..n.clone() };
+        assert!(recent_entries(&privacy, &meta, &[other])[0].1.starts_with("oa · 老板 — "));
+        privacy.hide_previews = true;
+        assert_eq!(recent_entries(&privacy, &meta, &[n])[0].1, privacy::HIDDEN_TITLE);
+    }
+}
@@ -0,0 +1,251 @@
+//! 托盘更新合并：计数或状态变化时托盘图标、菜单、提示都要刷新，同步一次上千条事件时逐次重绘会让 Windows 外壳明显卡顿。
+//! refresh_tray_* 只把对应部分标为待更新，后台任务每 50ms 检查一次，同一部分两次实际更新至少相隔 250ms，
+//! 更新时取当时的最新状态，因此总会收敛到最终值；渲染结果与上次应用的相同时跳过系统调用。
+//! 托盘尚未创建（启动中）时保持待更新，创建后的第一次检查补上。
+//! 合并器本身不读时钟，时间由调用方传入，便于用假时钟测试。
+
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
+
+use crate::commands::AppState;
+use crate::tray_icon::{self, ICON_SIZE, TRAY_ID};
+use crate::tray_menu;
+
+/// 同一部分两次实际更新的最小间隔
+pub const MIN_INTERVAL_MS: u64 = 250;
+const POLL_EVERY: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TraySurface {
+    Icon,
+    Menu,
+    Tooltip,
+}
+
+const SURFACES: [TraySurface; 3] = [TraySurface::Icon, TraySurface::Menu, TraySurface::Tooltip];
+
+#[derive(Debug, Default)]
+struct SurfaceState {
+    dirty: bool,
+    last_applied_at: Option<u64>,
+    last_value: Option<String>,
+    requested: u64,
+    applied: u64,
+    unchanged: u64,
+}
+
+/// 诊断用计数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TraySurfaceStats {
+    pub surface: TraySurface,
+    /// refresh 请求次数
+    pub requested: u64,
+    /// 实际的系统调用次数
+    pub applied: u64,
+    /// 渲染结果与上次相同而跳过的次数
+    pub unchanged: u64,
+    /// 仍待更新（未到间隔或托盘尚未创建）
+    pub pending: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct TrayUpdater {
+    surfaces: Mutex<[SurfaceState; 3]>,
+    // 主题变化事件带来的主题；没有时读取主窗口主题
+    theme: Mutex<Option<tauri::Theme>>,
+}
+
+impl TrayUpdater {
+    pub fn request(&self, surface: TraySurface) {
+        let mut surfaces = self.surfaces.lock();
+        let state = &mut surfaces[surface as usize];
+        state.dirty = true;
+        state.requested = state.requested.saturating_add(1);
+    }
+
+    /// 待更新且距上次实际更新已满间隔的部分
+    pub fn due(&self, now: u64) -> Vec<TraySurface> {
+        let surfaces = self.surfaces.lock();
+        SURFACES
+            .into_iter()
+            .filter(|s| {
+                let state = &surfaces[*s as usize];
+                state.dirty && state.last_applied_at.is_none_or(|at| now.saturating_sub(at) >= MIN_INTERVAL_MS)
+            })
+            .collect()
+    }
+
+    /// 记录本次渲染结果，返回是否需要实际应用（与上次应用的值不同）
+    pub fn settle(&self, surface: TraySurface, value: &str, now: u64) -> bool {
+        let mut surfaces = self.surfaces.lock();
+        let state = &mut surfaces[surface as usize];
+        state.dirty = false;
+        if state.last_value.as_deref() == Some(value) {
+            state.unchanged = state.unchanged.saturating_add(1);
+            return false;
+        }
+        state.last_value = Some(value.to_string());
+        state.last_applied_at = Some(now);
+        state.applied = state.applied.saturating_add(1);
+        true
+    }
+
+    pub fn set_theme(&self, theme: tauri::Theme) {
+        *self.theme.lock() = Some(theme);
+    }
+
+    pub fn theme(&self) -> Option<tauri::Theme> {
+        *self.theme.lock()
+    }
+
+    pub fn stats(&self) -> Vec<TraySurfaceStats> {
+        let surfaces = self.surfaces.lock();
+        SURFACES
+            .into_iter()
+            .map(|surface| {
+                let state = &surfaces[surface as usize];
+                TraySurfaceStats {
+                    surface,
+                    requested: state.requested,
+                    applied: state.applied,
+                    unchanged: state.unchanged,
+                    pending: state.dirty,
+                }
+            })
+            .collect()
+    }
+}
+
+impl AppState {
+    /// 应用到期的托盘更新；托盘尚未创建时保持待更新
+    pub fn flush_tray_updates(&self, now: u64) {
+        let due = self.tray_updates.due(now);
+        if due.is_empty() {
+            return;
+        }
+        let Some(app) = self.events.app() else {
+            return;
+        };
+        let Some(tray) = app.tray_by_id(TRAY_ID) else {
+            return;
+        };
+        for surface in due {
+            match surface {
+                TraySurface::Icon => {
+                    let spec = self.tray_icon_spec(app);
+                    if self.tray_updates.settle(surface, &format!("{:?}", spec), now) {
+                        let img = tauri::image::Image::new_owned(tray_icon::render(&spec, ICON_SIZE), ICON_SIZE, ICON_SIZE);
+                        if let Err(e) = tray.set_icon(Some(img)) {
+                            println!("[Tray] Failed to set icon: {}", e);
+                        }
+                    }
+                }
+                TraySurface::Menu => {
+                    let (entries, hide_previews, ui_available) = self.tray_menu_state();
+                    if self.tray_updates.settle(surface, &format!("{:?}", (&entries, hide_previews, ui_available)), now) {
+                        match tray_menu::build_menu(app, &entries, hide_previews, ui_available) {
+                            Ok(menu) => {
+                                if let Err(e) = tray.set_menu(Some(menu)) {
+                                    println!("[Tray] Failed to set menu: {}", e);
+                                }
+                            }
+                            Err(e) => println!("[Tray] Failed to build menu: {}", e),
+                        }
+                    }
+                }
+                TraySurface::Tooltip => {
+                    let tooltip = self.tray_tooltip();
+                    if self.tray_updates.settle(surface, &tooltip, now) {
+                        if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+                            println!("[Tray] Failed to set tooltip: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 后台定时应用合并后的托盘更新
+pub async fn run_loop(app: AppHandle, token: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(POLL_EVERY) => {}
+        }
+        app.state::<AppState>().flush_tray_updates(crate::wall_clock::mono_ms());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 模拟 2000 次计数变化（每毫秒一次）与 50ms 一次的检查，渲染值为当时的计数
+    #[test]
+    fn test_burst_is_bounded_and_converges() {
+        let updater = TrayUpdater::default();
+        let mut count = 0u64;
+        let mut now = 0u64;
+        for _ in 0..2000 {
+            count += 1;
+            now += 1;
+            updater.request(TraySurface::Tooltip);
+            updater.request(TraySurface::Menu);
+            if now % 50 == 0 {
+                for surface in updater.due(now) {
+                    updater.settle(surface, &count.to_string(), now);
+                }
+            }
+        }
+        // 突发结束后的下一次检查补上最终值
+        now += MIN_INTERVAL_MS;
+        for surface in updater.due(now) {
+            updater.settle(surface, &count.to_string(), now);
+        }
+
+        let stats = updater.stats();
+        for s in &stats[1..] {
+            assert_eq!(s.requested, 2000);
+            assert!(s.applied <= 2000 / MIN_INTERVAL_MS + 2, "{:?}", s);
+            assert!(!s.pending);
+        }
+        let surfaces = updater.surfaces.lock();
+        assert_eq!(surfaces[TraySurface::Tooltip as usize].last_value.as_deref(), Some("2000"));
+        assert_eq!(surfaces[TraySurface::Menu as usize].last_value.as_deref(), Some("2000"));
+        assert_eq!(stats[0].requested, 0);
+    }
+
+    #[test]
+    fn test_unchanged_value_skips_apply() {
+        let updater = TrayUpdater::default();
+        updater.request(TraySurface::Icon);
+        assert_eq!(updater.due(0), [TraySurface::Icon]);
+        assert!(updater.settle(TraySurface::Icon, "connected", 0));
+        updater.request(TraySurface::Icon);
+        // 间隔未满
+        assert!(updater.due(100).is_empty());
+        assert_eq!(updater.due(250), [TraySurface::Icon]);
+        assert!(!updater.settle(TraySurface::Icon, "connected", 250));
+        let stats = &updater.stats()[0];
+        assert_eq!((stats.requested, stats.applied, stats.unchanged), (2, 1, 1));
+    }
+
+    #[test]
+    fn test_requests_wait_for_tray() {
+        // 测试中没有托盘：请求保持待更新
+        let state = AppState::default();
+        state.refresh_tray_menu();
+        state.refresh_tray_tooltip();
+        state.flush_tray_updates(1000);
+        let stats = state.tray_updates.stats();
+        assert!(!stats[0].pending && stats[1].pending && stats[2].pending);
+        assert_eq!(stats[1].applied, 0);
+    }
+}
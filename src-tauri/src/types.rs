@@ -1,9 +1,13 @@
 //! 通用数据结构定义（初期最小集）。
 //! 注意：初期全部打印日志，稳定后再降级。
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::importance::Importance;
+use crate::language::Language;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct Notification {
     pub id: String,
     pub package_name: Option<String>,
@@ -12,9 +16,72 @@ pub struct Notification {
     pub read: bool,
     pub posted_at: Option<i64>,
     pub updated_at: Option<i64>,
+    /// 入库时检测到的主导语言
+    #[serde(default)]
+    pub language: Option<Language>,
+    /// 置顶（由桌面端决定，保留策略不会淘汰置顶通知）
+    #[serde(default)]
+    pub pinned: bool,
+    /// 桌面端本地创建（提醒等），不属于任何手机，不参与回写手机的操作
+    #[serde(default)]
+    pub local: bool,
+    /// 暂缓显示直到该时间（秒）；到期前不出现在列表与计数中
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<i64>,
+    /// 安卓通知类别（如 "transport" 表示媒体播放）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// 通知样式模板（如 "android.app.Notification$MediaStyle"）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// 操作按钮文字（按手机端顺序，下标用于回传点击）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<String>,
+    /// 安卓通知重要性，决定是否计入未读、弹出与响铃
+    #[serde(default)]
+    pub importance: Importance,
+    /// 按设备时钟偏差校正后的 posted_at（posted_at 保持手机原值）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrected_posted_at: Option<i64>,
+    /// 会话标识（手机端提供，如聊天的 shortcut id），同一会话的通知共享
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_key: Option<String>,
+    /// 安卓通知分组（同一应用内有效，如聊天应用的每条消息与其汇总通知共享）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_key: Option<String>,
+    /// 分组的汇总通知（标为已读时同组的通知一并标记）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_group_summary: bool,
+    /// 安卓通知渠道 id（8.0 起，手机端提供）；同一应用内唯一，不随语言变化
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    /// 渠道显示名（可能被应用改名或随系统语言变化，仅用于展示）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_name: Option<String>,
+    /// 命中静音（见 mutes），由桌面端在入库时设置
+    #[serde(default)]
+    pub muted: bool,
+    /// 已归档：列表默认不返回，不计入未读；仍受保留策略清理（与其他已读通知相同），导出时包含
+    #[serde(default)]
+    pub archived: bool,
+    /// 列表返回时附带的相对时间文本（仅用于展示，不持久化语义）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_time: Option<String>,
+    /// 列表返回时附带的应用显示名（见 app_meta，仅用于展示）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_display_name: Option<String>,
+    /// 经另一台电脑转发而来（见 relay）；转发来的通知不会再被转发
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub relayed: bool,
+    /// 转发来的通知在源电脑上所属的设备连接
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_device: Option<String>,
+    /// 送来这条通知的连接（入库时由桌面端设置）；手机端的 id 只在一台设备内唯一，与 id 一起确定一条通知
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Event {
     pub event_type: String, // added | updated | removed
     pub seq: i64,
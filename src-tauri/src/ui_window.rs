@@ -0,0 +1,220 @@
+//! 主窗口的创建与降级：WebView 不可用（如 Linux 上 WebKitGTK 损坏）时，窗口创建失败不再导致启动失败，
+//! 应用以仅托盘模式继续运行，系统通知是唯一的界面；命令、服务器与连接照常工作。
+//! 主窗口不由配置自动创建（tauri.conf.json 中 create: false），而是在 setup 中按同一份配置创建并捕获错误。
+//! 失败原因经 get_health 与托盘菜单的“界面不可用”项（点击弹出系统对话框）展示，retry_create_window 可稍后重试。
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime, WebviewWindow};
+
+use crate::commands::AppState;
+
+pub const MAIN_WINDOW: &str = "main";
+/// 托盘菜单中“界面不可用”项
+pub const UI_UNAVAILABLE_ID: &str = "ui_unavailable";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UiStatus {
+    pub available: bool,
+    /// 最近一次创建失败的原因
+    pub error: Option<String>,
+    pub failed_at: Option<i64>,
+    /// 已尝试创建的次数（含启动时）
+    pub attempts: u32,
+}
+
+impl Default for UiStatus {
+    fn default() -> Self {
+        Self { available: true, error: None, failed_at: None, attempts: 0 }
+    }
+}
+
+#[derive(Default)]
+pub struct UiState {
+    status: Mutex<UiStatus>,
+}
+
+impl UiState {
+    pub fn status(&self) -> UiStatus {
+        self.status.lock().clone()
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.status.lock().available
+    }
+
+    /// 记录一次创建结果，返回可用性是否变化
+    fn record(&self, result: &Result<(), String>, now: i64) -> bool {
+        let mut status = self.status.lock();
+        status.attempts += 1;
+        let was_available = status.available;
+        match result {
+            Ok(()) => {
+                status.available = true;
+                status.error = None;
+                status.failed_at = None;
+            }
+            Err(e) => {
+                status.available = false;
+                status.error = Some(e.clone());
+                status.failed_at = Some(now);
+            }
+        }
+        status.available != was_available
+    }
+}
+
+/// 按配置创建主窗口（已存在时直接返回）
+pub fn create_main_window<R: Runtime>(app: &AppHandle<R>) -> Result<(), String> {
+    if app.get_webview_window(MAIN_WINDOW).is_some() {
+        return Ok(());
+    }
+    let config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == MAIN_WINDOW)
+        .ok_or("Main window is missing from the configuration")?;
+    let win = tauri::WebviewWindowBuilder::from_config(app, config)
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Failed to create main window: {}", e))?;
+    install_handlers(&win);
+    Ok(())
+}
+
+/// 拦截主窗口关闭事件（改为隐藏到托盘），并跟踪前台与系统主题
+fn install_handlers<R: Runtime>(win: &WebviewWindow<R>) {
+    let win_handle = win.clone();
+    win.on_window_event(move |e| match e {
+        tauri::WindowEvent::CloseRequested { api, .. } => {
+            api.prevent_close();
+            let _ = win_handle.hide();
+            win_handle.state::<AppState>().set_window_visible(false);
+        }
+        // 兜底：窗口经其他途径（如任务栏）回到前台
+        tauri::WindowEvent::Focused(true) => {
+            win_handle.state::<AppState>().set_window_visible(true);
+        }
+        // 系统主题切换：重绘托盘图标
+        tauri::WindowEvent::ThemeChanged(theme) => {
+            win_handle.state::<AppState>().refresh_tray_icon_with(*theme);
+        }
+        _ => {}
+    });
+}
+
+/// 弹出系统对话框说明界面为何不可用，可选择重试
+pub fn show_unavailable_dialog(app: &AppHandle) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+    let status = app.state::<AppState>().ui.status();
+    if status.available {
+        crate::ensure_main_window_visible(app);
+        return;
+    }
+    let message = format!(
+        "主窗口无法创建，应用正以仅托盘模式运行（通知镜像与连接不受影响）。\n\n{}",
+        status.error.unwrap_or_default()
+    );
+    let handle = app.clone();
+    app.dialog()
+        .message(message)
+        .title("界面不可用")
+        .kind(MessageDialogKind::Error)
+        .buttons(MessageDialogButtons::OkCancelCustom("重试".into(), "关闭".into()))
+        .show(move |retry| {
+            if retry {
+                // 在主线程创建窗口
+                let main = handle.clone();
+                let _ = main.run_on_main_thread(move || {
+                    handle.state::<AppState>().start_ui(|| create_main_window(&handle));
+                });
+            }
+        });
+}
+
+impl AppState {
+    /// 创建主窗口并记录结果；失败时继续以仅托盘模式运行
+    pub fn start_ui(&self, create: impl FnOnce() -> Result<(), String>) -> UiStatus {
+        let result = create();
+        let changed = self.ui.record(&result, chrono::Utc::now().timestamp());
+        let status = self.ui.status();
+        match &result {
+            Ok(()) if changed => println!("[Ui] Main window restored after {} attempts", status.attempts),
+            Ok(()) => {}
+            Err(e) => {
+                println!("[Ui] ⚠️ {} — continuing in tray-only mode", e);
+                if changed {
+                    self.notify_ui_unavailable();
+                }
+            }
+        }
+        if changed {
+            self.events.emit("ui-status-changed", status.clone());
+            self.refresh_tray_menu();
+        }
+        status
+    }
+
+    /// 仅托盘模式下唯一能告知用户的途径：一条系统通知
+    fn notify_ui_unavailable(&self) {
+        use tauri_plugin_notification::NotificationExt;
+        let Some(app) = self.events.app() else {
+            return;
+        };
+        let result = app
+            .notification()
+            .builder()
+            .title("界面不可用")
+            .body("主窗口无法创建，已切换为仅托盘模式。点击托盘菜单中的“界面不可用”查看详情。")
+            .show();
+        if let Err(e) = result {
+            println!("[Ui] Failed to show toast: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::startup::{self, Subsystem};
+    use crate::types::Event;
+
+    #[test]
+    fn test_window_failure_keeps_setup_running() {
+        let state = Arc::new(AppState::default());
+        let status = state.start_ui(|| Err("Failed to create main window: WebKitGTK renderer crashed".into()));
+        assert!(!status.available);
+        assert_eq!(status.attempts, 1);
+
+        // 其余启动步骤照常完成，命令可用
+        startup::spawn_loading(state.clone(), None).join().unwrap();
+        assert!(state.readiness.is_ready(Subsystem::Store));
+        state.ingest_from(
+            "phone-1",
+            Event {
+                event_type: "added".into(),
+                seq: 1,
+                notification: Some(crate::types::Notification { id: "n1".into(), ..Default::default() }),
+                id: None,
+            },
+        );
+        assert_eq!(state.counts().total, 1);
+
+        let health = state.health();
+        assert!(!health.ui.available);
+        assert!(health.ui.error.unwrap().contains("WebKitGTK"));
+        let events = state.events.take_captured();
+        assert_eq!(events.iter().filter(|(e, _)| e == "ui-status-changed").count(), 1);
+
+        // 再次失败不重复推送；重试成功后恢复
+        assert!(!state.start_ui(|| Err("still broken".into())).available);
+        assert!(state.events.take_captured().iter().all(|(e, _)| e != "ui-status-changed"));
+        let status = state.start_ui(|| Ok(()));
+        assert_eq!((status.available, status.error, status.attempts), (true, None, 3));
+        assert_eq!(state.events.take_captured().iter().filter(|(e, _)| e == "ui-status-changed").count(), 1);
+    }
+}
@@ -0,0 +1,244 @@
+//! 各应用最近 24 小时的通知量（用于应用列表旁的走势图）。
+//! 每个应用一个固定长度的环形数组，每 15 分钟一个桶；桶的滚动由桌面端收到事件的时间驱动，
+//! 通知按 posted_at 计入对应的桶：同步回放的旧通知落在它原来的桶里（超出 24 小时的不计），
+//! 不会堆到当前桶；晚于收到时间的 posted_at 按收到时间计。
+//! 单独跟踪的应用最多 MAX_TRACKED_PACKAGES 个，之后新出现的应用计入 "other"；
+//! 滚动后 24 小时内没有通知的应用不再占位。
+//! 由维护任务定期保存，重启后不会清空。
+
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+
+pub const VOLUME_FILE: &str = "volume_history.json";
+pub const BUCKET_SECS: i64 = 15 * 60;
+/// 24 小时
+pub const BUCKETS: usize = 96;
+pub const MAX_TRACKED_PACKAGES: usize = 50;
+/// 未单独跟踪的应用合计
+pub const OTHER_PACKAGE: &str = "other";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct VolumeBucket {
+    /// 桶的开始时间（秒）
+    pub start: i64,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct VolumeHistory {
+    /// None 表示所有应用合计
+    pub package: Option<String>,
+    /// 应用显示名（见 app_meta）
+    pub display_name: Option<String>,
+    /// false 表示该应用没有单独跟踪（计入 other 或最近没有通知）
+    pub tracked: bool,
+    pub bucket_secs: i64,
+    /// 旧 -> 新，最后一个是当前桶
+    pub buckets: Vec<VolumeBucket>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeSeries {
+    /// 当前桶的编号（开始时间 / BUCKET_SECS）
+    head: i64,
+    /// package -> 按桶编号取模存放的计数
+    series: HashMap<String, Vec<u32>>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+fn slot(bucket: i64) -> usize {
+    bucket.rem_euclid(BUCKETS as i64) as usize
+}
+
+impl VolumeSeries {
+    /// 滚动到 now 所在的桶，清空滑出窗口的桶，并移除整个窗口内都为 0 的应用
+    fn advance(&mut self, now: i64) {
+        let bucket = now.div_euclid(BUCKET_SECS);
+        if bucket <= self.head {
+            return;
+        }
+        let steps = (bucket - self.head).min(BUCKETS as i64);
+        for counts in self.series.values_mut() {
+            for b in (bucket - steps + 1)..=bucket {
+                counts[slot(b)] = 0;
+            }
+        }
+        self.series.retain(|_, counts| counts.iter().any(|c| *c > 0));
+        self.head = bucket;
+        self.dirty = true;
+    }
+
+    /// 计入一条通知；posted_at 缺失时按收到时间计
+    pub fn record(&mut self, package: &str, posted_at: Option<i64>, received_at: i64) {
+        self.advance(received_at);
+        let bucket = posted_at.unwrap_or(received_at).min(received_at).div_euclid(BUCKET_SECS);
+        if bucket <= self.head - BUCKETS as i64 {
+            return;
+        }
+        let key = if self.series.contains_key(package) || self.tracked_count() < MAX_TRACKED_PACKAGES {
+            package
+        } else {
+            OTHER_PACKAGE
+        };
+        let counts = self.series.entry(key.to_string()).or_insert_with(|| vec![0; BUCKETS]);
+        counts[slot(bucket)] = counts[slot(bucket)].saturating_add(1);
+        self.dirty = true;
+    }
+
+    /// 单独跟踪的应用数（不含 other）
+    fn tracked_count(&self) -> usize {
+        self.series.keys().filter(|k| *k != OTHER_PACKAGE).count()
+    }
+
+    /// 最近 hours 小时（1-24）的桶；package 为 None 时为合计
+    pub fn history(&mut self, package: Option<&str>, hours: u8, now: i64) -> VolumeHistory {
+        self.advance(now);
+        let n = (hours.clamp(1, 24) as usize * 3600 / BUCKET_SECS as usize).min(BUCKETS);
+        let series: Vec<&Vec<u32>> = match package {
+            Some(p) => self.series.get(p).into_iter().collect(),
+            None => self.series.values().collect(),
+        };
+        let buckets = (0..n as i64)
+            .rev()
+            .map(|back| {
+                let b = self.head - back;
+                VolumeBucket { start: b * BUCKET_SECS, count: series.iter().map(|s| s[slot(b)]).sum() }
+            })
+            .collect();
+        VolumeHistory {
+            package: package.map(str::to_string),
+            display_name: None,
+            tracked: package.is_none() || !series.is_empty(),
+            bucket_secs: BUCKET_SECS,
+            buckets,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Volume {
+    series: Mutex<VolumeSeries>,
+}
+
+impl AppState {
+    pub(crate) fn record_volume(&self, package: Option<&str>, posted_at: Option<i64>, received_at: i64) {
+        self.volume.series.lock().record(package.unwrap_or("unknown"), posted_at, received_at);
+    }
+
+    /// 正在统计的应用数
+    pub(crate) fn tracked_packages(&self) -> usize {
+        self.volume.series.lock().series.len()
+    }
+
+    pub fn volume_history(&self, package: Option<&str>, hours: u8) -> VolumeHistory {
+        let mut history = self.volume.series.lock().history(package, hours, chrono::Utc::now().timestamp());
+        history.display_name = package.and_then(|p| crate::app_meta::display_name(&self.settings.read().app_meta, p));
+        history
+    }
+
+    pub(crate) fn load_volume(&self) {
+        *self.volume.series.lock() = self.storage.load::<VolumeSeries>(VOLUME_FILE).unwrap_or_default();
+    }
+
+    /// 维护任务：有变化时保存
+    pub fn persist_volume(&self) -> Result<String, String> {
+        let snapshot = {
+            let mut series = self.volume.series.lock();
+            if !series.dirty {
+                return Ok("unchanged".to_string());
+            }
+            series.dirty = false;
+            series.clone()
+        };
+        self.storage.save(VOLUME_FILE, &snapshot)?;
+        Ok(format!("{} packages", snapshot.series.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0: i64 = 1_700_000_200;
+
+    fn counts(h: &VolumeHistory) -> Vec<u32> {
+        h.buckets.iter().map(|b| b.count).collect()
+    }
+
+    #[test]
+    fn test_rollover_with_fake_clock() {
+        let mut v = VolumeSeries::default();
+        v.record("com.chat", None, T0);
+        v.record("com.chat", Some(T0 - 10), T0 + 5);
+        v.record("com.chat", None, T0 + BUCKET_SECS);
+        let h = v.history(Some("com.chat"), 1, T0 + BUCKET_SECS);
+        assert_eq!(counts(&h), [0, 0, 2, 1]);
+        assert_eq!(h.buckets[3].start, (T0 + BUCKET_SECS).div_euclid(BUCKET_SECS) * BUCKET_SECS);
+
+        // 一小时后：原有计数向前移动，当前桶为 0
+        let h = v.history(Some("com.chat"), 2, T0 + 5 * BUCKET_SECS);
+        assert_eq!(counts(&h), [0, 0, 2, 1, 0, 0, 0, 0]);
+        // 24 小时后全部滑出，应用不再占位
+        let h = v.history(Some("com.chat"), 24, T0 + 25 * 3600);
+        assert!(!h.tracked);
+        assert_eq!(h.buckets.len(), BUCKETS);
+        assert!(v.series.is_empty());
+    }
+
+    #[test]
+    fn test_replayed_history_does_not_spike_current_bucket() {
+        let mut v = VolumeSeries::default();
+        let now = T0 + 10 * 3600;
+        // 同步回放：两小时前与两天前的通知，以及时钟超前的通知
+        v.record("com.mail", Some(now - 2 * 3600), now);
+        v.record("com.mail", Some(now - 48 * 3600), now);
+        v.record("com.mail", Some(now + 3600), now);
+        let h = v.history(Some("com.mail"), 3, now);
+        assert_eq!(h.buckets.last().unwrap().count, 1);
+        assert_eq!(counts(&h).iter().sum::<u32>(), 2);
+        assert_eq!(h.buckets[h.buckets.len() - 9].count, 1);
+    }
+
+    #[test]
+    fn test_other_bucket_overflow_and_total() {
+        let mut v = VolumeSeries::default();
+        for i in 0..MAX_TRACKED_PACKAGES + 5 {
+            v.record(&format!("com.app{}", i), None, T0);
+        }
+        v.record("com.app0", None, T0);
+        assert_eq!(v.tracked_count(), MAX_TRACKED_PACKAGES);
+        assert_eq!(v.history(Some(OTHER_PACKAGE), 1, T0).buckets.last().unwrap().count, 5);
+        assert!(!v.history(Some("com.app52"), 1, T0).tracked);
+        assert_eq!(v.history(Some("com.app0"), 1, T0).buckets.last().unwrap().count, 2);
+        assert_eq!(v.history(None, 1, T0).buckets.last().unwrap().count, MAX_TRACKED_PACKAGES as u32 + 6);
+
+        // 旧应用滑出后腾出位置
+        v.record("com.late", None, T0 + 25 * 3600);
+        assert!(v.history(Some("com.late"), 1, T0 + 25 * 3600).tracked);
+        assert_eq!(v.series.len(), 1);
+    }
+
+    #[test]
+    fn test_persisted_across_restart() {
+        let dir = crate::storage::temp_dir("volume");
+        let state = AppState::default();
+        state.storage.set_base_dir(dir.clone());
+        let now = chrono::Utc::now().timestamp();
+        state.record_volume(Some("com.chat"), Some(now), now);
+        assert_eq!(state.persist_volume().unwrap(), "1 packages");
+        assert_eq!(state.persist_volume().unwrap(), "unchanged");
+
+        let restarted = AppState::default();
+        restarted.storage.set_base_dir(dir.clone());
+        restarted.load_volume();
+        let h = restarted.volume_history(Some("com.chat"), 24);
+        assert_eq!(counts(&h).iter().sum::<u32>(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
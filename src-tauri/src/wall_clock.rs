@@ -0,0 +1,230 @@
+//! 时间来源与系统时间跳变检测。
+//! 纯时长的逻辑（事件合并的速率窗口、窗口隐藏时长、接收质量统计、低功耗心跳、限流与退避）用单调时钟，
+//! 不受系统时间调整影响；mono_ms / mono_secs 是进程启动以来的时长，只能相互比较，不能当作时间展示。
+//! 需要真实时间的（入库时间、提醒到期、时段判断）仍用系统时间，由维护任务定期比较两种时钟的流逝：
+//! 差值超过 JUMP_THRESHOLD 视为跳变（如 NTP 校正了偏差数小时的时钟），此时立即重新判断提醒到期与低功耗时段，
+//! 丢弃按旧时间测得的设备时钟偏差样本，把被错误时间标到“未来”的本地条目拉回当前时间，
+//! 写入审计日志并推送 clock-jumped（前端据此重算相对时间）。
+//! 时间来源可注入（Clock），测试中用手动时钟模拟跳变。
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+
+/// 两种时钟流逝之差超过该值视为跳变（小于它的属于正常校时）
+pub const JUMP_THRESHOLD: Duration = Duration::from_secs(120);
+
+pub trait Clock: Send + Sync {
+    /// 系统时间（毫秒）
+    fn wall_ms(&self) -> i64;
+    fn monotonic(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn wall_ms(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+static PROCESS_START: OnceLock<Instant> = OnceLock::new();
+
+/// 进程启动以来的毫秒数（单调）
+pub fn mono_ms() -> u64 {
+    PROCESS_START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// 进程启动以来的秒数（单调）
+pub fn mono_secs() -> i64 {
+    (mono_ms() / 1000) as i64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockJump {
+    /// 正数为向前跳（系统时间比流逝的时长走得多）
+    pub delta_secs: i64,
+    pub detected_at: i64,
+}
+
+pub struct WallClock {
+    clock: Box<dyn Clock>,
+    // 上次观察时的 (系统时间毫秒, 单调时钟)
+    anchor: Mutex<Option<(i64, Instant)>>,
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl WallClock {
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        Self { clock: Box::new(clock), anchor: Mutex::new(None) }
+    }
+
+    /// 当前系统时间（秒）
+    pub fn now(&self) -> i64 {
+        self.clock.wall_ms().div_euclid(1000)
+    }
+
+    /// 与上次观察比较两种时钟的流逝，超过阈值时返回跳变；每次观察都重新锚定
+    pub fn observe(&self) -> Option<ClockJump> {
+        let (wall, mono) = (self.clock.wall_ms(), self.clock.monotonic());
+        let previous = self.anchor.lock().replace((wall, mono));
+        let (last_wall, last_mono) = previous?;
+        let elapsed_ms = mono.saturating_duration_since(last_mono).as_millis() as i64;
+        let delta_ms = (wall - last_wall) - elapsed_ms;
+        (delta_ms.unsigned_abs() > JUMP_THRESHOLD.as_millis() as u64)
+            .then(|| ClockJump { delta_secs: delta_ms / 1000, detected_at: wall.div_euclid(1000) })
+    }
+}
+
+impl AppState {
+    /// 维护任务：检测系统时间跳变并重新评估依赖真实时间的状态
+    pub fn check_clock_jump(&self) -> Result<String, String> {
+        let Some(jump) = self.wall_clock.observe() else {
+            return Ok(String::new());
+        };
+        let now = jump.detected_at;
+        println!("[Clock] System time jumped {:+}s", jump.delta_secs);
+        self.audit("clock_jump", AuditSource::Background, 0, serde_json::json!({ "delta_secs": jump.delta_secs }));
+
+        // 偏差样本是按旧的系统时间测得的
+        self.clock_offsets.clear();
+        let restamped = self.restamp_future_local(now);
+        let woken = self.wake_due(now).len();
+        if let Err(e) = self.apply_low_power() {
+            println!("[Clock] {}", e);
+        }
+        self.events.emit("clock-jumped", jump);
+        Ok(format!("{:+}s, {} restamped, {} woken", jump.delta_secs, restamped, woken))
+    }
+
+    /// 时间晚于 now 的本地条目（桌面端按跳变前的时间标记）改为 now，返回条数
+    fn restamp_future_local(&self, now: i64) -> usize {
+        let restamped = self.store.lock().unwrap().restamp_future(now, |n| n.local);
+        if !restamped.is_empty() {
+            self.save_local_notifications();
+            self.emit_counts();
+        }
+        restamped.len()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::Arc;
+
+    use crate::clock::ClockSample;
+
+    /// 手动拨动的时钟：wall 与 mono 分别前进
+    #[derive(Clone)]
+    pub(crate) struct ManualClock {
+        wall_ms: Arc<AtomicI64>,
+        mono_ms: Arc<AtomicI64>,
+        base: Instant,
+    }
+
+    impl ManualClock {
+        pub(crate) fn new(wall_secs: i64) -> Self {
+            Self { wall_ms: Arc::new(AtomicI64::new(wall_secs * 1000)), mono_ms: Arc::default(), base: Instant::now() }
+        }
+
+        /// 正常流逝：两种时钟一起前进
+        pub(crate) fn advance(&self, secs: i64) {
+            self.wall_ms.fetch_add(secs * 1000, Ordering::SeqCst);
+            self.mono_ms.fetch_add(secs * 1000, Ordering::SeqCst);
+        }
+
+        /// 只拨动系统时间
+        pub(crate) fn jump(&self, secs: i64) {
+            self.wall_ms.fetch_add(secs * 1000, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn wall_ms(&self) -> i64 {
+            self.wall_ms.load(Ordering::SeqCst)
+        }
+
+        fn monotonic(&self) -> Instant {
+            self.base + Duration::from_millis(self.mono_ms.load(Ordering::SeqCst) as u64)
+        }
+    }
+
+    #[test]
+    fn test_detects_jumps_beyond_threshold() {
+        let clock = ManualClock::new(1_700_000_000);
+        let wall = WallClock::with_clock(clock.clone());
+        assert_eq!(wall.observe(), None, "first observation only anchors");
+        clock.advance(3600);
+        assert_eq!(wall.observe(), None);
+        // 正常校时的小幅调整
+        clock.advance(15);
+        clock.jump(-30);
+        assert_eq!(wall.observe(), None);
+
+        clock.advance(15);
+        clock.jump(3 * 3600);
+        let jump = wall.observe().unwrap();
+        assert_eq!(jump.delta_secs, 3 * 3600);
+        assert_eq!(jump.detected_at, wall.now());
+        clock.advance(15);
+        clock.jump(-2 * 3600);
+        assert_eq!(wall.observe().unwrap().delta_secs, -2 * 3600);
+        clock.advance(15);
+        assert_eq!(wall.observe(), None, "re-anchored after each jump");
+    }
+
+    #[test]
+    fn test_jump_reevaluates_time_dependent_state() {
+        let clock = ManualClock::new(1_700_000_000);
+        let mut state = AppState::default();
+        state.wall_clock = WallClock::with_clock(clock.clone());
+        let t0 = state.wall_clock.now();
+        assert_eq!(state.check_clock_jump().unwrap(), "");
+
+        // 时钟快了 3 小时时创建的本地条目，以及 1 小时后的提醒
+        clock.jump(3 * 3600);
+        let ahead = state.wall_clock.now();
+        state.check_clock_jump().unwrap();
+        let note = state.create_local_notification("便签".into(), "x".into(), None, ahead).unwrap();
+        state.create_local_notification("提醒".into(), "y".into(), Some(ahead + 3600), ahead).unwrap();
+        state.clock_offsets.record("phone-1", ClockSample { t0: 0, t1: 5_000, t2: 5_000, t3: 0 });
+        state.events.take_captured();
+
+        // NTP 校正回来：便签拉回当前时间，提醒按真实时间仍未到期
+        clock.advance(15);
+        clock.jump(-3 * 3600);
+        assert!(state.check_clock_jump().unwrap().starts_with("-10800s, 1 restamped, 0 woken"));
+        let now = state.wall_clock.now();
+        assert_eq!(now, t0 + 15);
+        let restamped = state.list_local_reminders().into_iter().find(|n| n.id == note.id).unwrap();
+        assert_eq!(restamped.posted_at, Some(now));
+        assert_eq!(state.clock_offsets.offset_ms("phone-1"), None);
+        let log = state.audit.recent(10);
+        assert_eq!((log[0].operation.as_str(), log[0].params["delta_secs"].as_i64()), ("clock_jump", Some(-3 * 3600)));
+        let events = state.events.take_captured();
+        let jumped = events.iter().find(|(e, _)| e == "clock-jumped").unwrap();
+        assert_eq!(jumped.1["delta_secs"], -3 * 3600);
+
+        // 向前跳过提醒时间：立即到期
+        clock.advance(15);
+        clock.jump(8 * 3600);
+        assert!(state.check_clock_jump().unwrap().ends_with("1 woken"));
+        assert_eq!(state.counts().total, 2);
+    }
+}
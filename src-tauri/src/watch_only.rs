@@ -0,0 +1,280 @@
+//! 只读观察模式：排查问题时确保桌面端不改动手机上的任何东西（不同步删除、不触发操作按钮、不切换镜像/推送方式），同时照常接收。
+//! 闸门设在 AndroidSocketClient 发出控制帧的唯一出口（send_action / exchange_raw）：开启时只放行不改变手机状态的帧
+//! （ping / version / sync 与认证），其余在写入连接之前返回 WatchOnlyMode 错误。用放行名单而非拦截名单，以后新增的操作默认被拦下。
+//! 后台发出、没有人可以重试的操作（如副电脑转来的删除）开启期间暂存在待发队列中而不是丢弃；
+//! 关闭观察模式不会自动发出，需要再调用 release_outbox 确认。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditSource;
+use crate::commands::AppState;
+
+/// 观察模式下仍可发送的帧（不改变手机状态）
+pub const ALLOWED_ACTIONS: &[&str] = &["ping", "version", "sync", "login", "request_token"];
+pub const WATCH_ONLY_TOOLTIP: &str = "Watch-only: not changing anything on the phone";
+
+pub fn is_allowed(action: Option<&str>) -> bool {
+    action.is_some_and(|a| ALLOWED_ACTIONS.contains(&a))
+}
+
+/// 帧被观察模式拦下；以 `WatchOnlyMode: {json}` 的形式返回给前端
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchOnlyBlocked {
+    pub connection_id: String,
+    /// 帧的 action（没有时为空）
+    pub action: String,
+}
+
+impl std::fmt::Display for WatchOnlyBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WatchOnlyMode: {}", serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+impl From<WatchOnlyBlocked> for String {
+    fn from(e: WatchOnlyBlocked) -> Self {
+        println!("[WatchOnly] Blocked {:?} to {}", e.action, e.connection_id);
+        e.to_string()
+    }
+}
+
+/// 暂存的待发帧
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HeldFrame {
+    pub connection_id: String,
+    pub frame: serde_json::Value,
+    pub held_at: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WatchOnlyStatus {
+    pub enabled: bool,
+    /// 待发队列中的帧数
+    pub held: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OutboxRelease {
+    pub sent: usize,
+    /// 设备未连接或发送失败，仍留在队列中
+    pub kept: usize,
+}
+
+/// 各客户端共享的出站闸门（开关与设置中的 watch_only 同步）
+#[derive(Debug, Default)]
+pub struct OutboundGate {
+    enabled: AtomicBool,
+    held: Mutex<Vec<HeldFrame>>,
+}
+
+impl OutboundGate {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// 发往 connection_id 的帧是否可以写入连接
+    pub fn check(&self, connection_id: &str, action: Option<&str>) -> Result<(), WatchOnlyBlocked> {
+        if !self.is_enabled() || is_allowed(action) {
+            return Ok(());
+        }
+        Err(WatchOnlyBlocked { connection_id: connection_id.to_string(), action: action.unwrap_or_default().to_string() })
+    }
+
+    fn hold(&self, connection_id: &str, frame: serde_json::Value, now: i64) {
+        self.held.lock().push(HeldFrame { connection_id: connection_id.to_string(), frame, held_at: now });
+    }
+
+    pub fn held(&self) -> usize {
+        self.held.lock().len()
+    }
+}
+
+impl AppState {
+    pub fn watch_only_status(&self) -> WatchOnlyStatus {
+        WatchOnlyStatus { enabled: self.watch_only.is_enabled(), held: self.watch_only.held() }
+    }
+
+    /// 设置变化（加载、整体保存、重置）后同步闸门
+    pub(crate) fn apply_watch_only(&self) {
+        self.watch_only.set_enabled(self.settings.read().watch_only);
+    }
+
+    pub fn set_watch_only(&self, enabled: bool) -> Result<WatchOnlyStatus, String> {
+        let settings = {
+            let mut settings = self.settings.write();
+            settings.watch_only = enabled;
+            settings.clone()
+        };
+        self.storage.save(crate::commands::SETTINGS_FILE, &settings)?;
+        self.apply_watch_only();
+        let status = self.watch_only_status();
+        self.audit("set_watch_only", AuditSource::Command, 1, serde_json::json!({ "enabled": enabled, "held": status.held }));
+        println!("[WatchOnly] enabled={} held={}", enabled, status.held);
+        self.events.emit("watch-only-changed", &status);
+        self.refresh_tray_tooltip();
+        Ok(status)
+    }
+
+    /// 后台发往手机的操作：观察模式下暂存，否则直接发送
+    pub(crate) fn send_or_hold(&self, connection_id: &str, capability: &str, frame: serde_json::Value) -> Result<(), String> {
+        let client = self.client_with(connection_id, capability)?;
+        if self.watch_only.check(connection_id, frame["action"].as_str()).is_err() {
+            println!("[WatchOnly] Holding {} for {}", frame["action"], connection_id);
+            self.watch_only.hold(connection_id, frame, chrono::Utc::now().timestamp());
+            self.events.emit("watch-only-changed", self.watch_only_status());
+            return Ok(());
+        }
+        client.send_action(&frame)
+    }
+
+    /// 观察模式关闭后确认发出暂存的帧；设备不在线或发送失败的留在队列中
+    pub fn release_outbox(&self) -> Result<OutboxRelease, String> {
+        if self.watch_only.is_enabled() {
+            return Err("WatchOnlyMode: turn off watch-only mode before releasing held frames".to_string());
+        }
+        let frames = std::mem::take(&mut *self.watch_only.held.lock());
+        let mut kept = Vec::new();
+        let mut release = OutboxRelease::default();
+        for held in frames {
+            let client = self.clients.read().get(&held.connection_id).cloned();
+            match client.ok_or_else(|| "Device is not connected".to_string()).and_then(|c| c.send_action(&held.frame)) {
+                Ok(()) => release.sent += 1,
+                Err(e) => {
+                    println!("[WatchOnly] Keeping held frame for {}: {}", held.connection_id, e);
+                    kept.push(held);
+                }
+            }
+        }
+        release.kept = kept.len();
+        // 发送期间可能又有新的暂存帧，保持原顺序放在前面
+        self.watch_only.held.lock().splice(0..0, kept);
+        self.audit("release_outbox", AuditSource::Command, release.sent, serde_json::json!({ "kept": release.kept }));
+        self.events.emit("watch-only-changed", self.watch_only_status());
+        Ok(release)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::android_client::AndroidSocketClient;
+    use crate::media::MediaAction;
+    use crate::transport::Transport;
+    use crate::types::{Event, Notification};
+    use std::sync::Arc;
+
+    /// 手机端在桌面端发出的全部操作（含以后可能出现的），每一种都必须被拦下
+    const MUTATING: &[&str] = &[
+        "dismiss",
+        "mark_read",
+        "notification_action",
+        "invoke_action",
+        "reply",
+        "set_mirroring",
+        "set_push_mode",
+        "rotate_token",
+        "update_endpoint",
+        "media_control",
+    ];
+
+    struct Recording(Arc<Mutex<Vec<String>>>);
+
+    impl Transport for Recording {
+        fn send_line(&mut self, line: &str) -> Result<(), String> {
+            self.0.lock().push(line.to_string());
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            Ok(r#"{"success":true,"protocolVersion":2,"capabilities":["actions","dismissal","media_control"]}"#.to_string())
+        }
+    }
+
+    fn connected(state: &AppState) -> Arc<Mutex<Vec<String>>> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let client = AndroidSocketClient::with_transport(Box::new(Recording(sent.clone())), "phone".into(), Arc::default());
+        client.login("t").unwrap();
+        state.register_client("phone".into(), client, "t", None);
+        sent.lock().clear();
+        sent
+    }
+
+    fn actions(sent: &Mutex<Vec<String>>) -> Vec<String> {
+        sent.lock()
+            .iter()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap()["action"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_gate_blocks_every_mutating_action() {
+        let state = AppState::default();
+        let sent = connected(&state);
+        state.set_watch_only(true).unwrap();
+        let client = state.clients.read().get("phone").cloned().unwrap();
+
+        for action in MUTATING {
+            let err = client.send_action(&serde_json::json!({ "action": action, "ids": ["1"] })).unwrap_err();
+            assert!(err.starts_with("WatchOnlyMode: "), "{}: {}", action, err);
+            let err = client.exchange_raw(&format!(r#"{{"action":"{}"}}"#, action), "r", Default::default(), Default::default());
+            assert!(err.unwrap_err().starts_with("WatchOnlyMode: "));
+        }
+        assert!(client.exchange_raw(r#"{"requestId":"r"}"#, "r", Default::default(), Default::default()).is_err());
+        // 经由各功能入口发出的操作同样被拦下
+        assert!(state.set_mirroring_enabled("phone", false, false, 100).unwrap_err().starts_with("WatchOnlyMode: "));
+        assert!(state.send_raw_frame("phone", r#"{"action":"dismiss","ids":["1"]}"#, false, None, false).is_err());
+        state.ingest_from("phone", Event {
+            event_type: "added".into(),
+            seq: 1,
+            notification: Some(Notification {
+                id: "m".into(),
+                template: Some("android.app.Notification$MediaStyle".into()),
+                actions: vec!["Previous".into(), "Pause".into(), "Next".into()],
+                ..Default::default()
+            }),
+            id: None,
+        });
+        assert!(state.media_control("phone", MediaAction::Next).unwrap_err().starts_with("WatchOnlyMode: "));
+        assert!(sent.lock().is_empty(), "{:?}", sent.lock());
+
+        // 只读的帧照常发出
+        client.send_action(&serde_json::json!({ "action": "sync", "since": 0 })).unwrap();
+        client.login("t").unwrap();
+        assert_eq!(actions(&sent), ["sync", "login"]);
+        assert!(state.tray_tooltip().contains(WATCH_ONLY_TOOLTIP));
+        assert!(state.settings.read().watch_only);
+    }
+
+    #[test]
+    fn test_held_frames_wait_for_release() {
+        let state = AppState::default();
+        let sent = connected(&state);
+        state.set_watch_only(true).unwrap();
+
+        state.send_or_hold("phone", crate::capabilities::DISMISSAL, serde_json::json!({ "action": "dismiss", "ids": ["1"] })).unwrap();
+        state.send_or_hold("phone", crate::capabilities::DISMISSAL, serde_json::json!({ "action": "dismiss", "ids": ["2"] })).unwrap();
+        assert_eq!(state.watch_only_status(), WatchOnlyStatus { enabled: true, held: 2 });
+        assert!(state.release_outbox().unwrap_err().starts_with("WatchOnlyMode"));
+
+        // 关闭后仍需确认才发出
+        assert_eq!(state.set_watch_only(false).unwrap().held, 2);
+        assert!(sent.lock().is_empty());
+        assert_eq!(state.release_outbox().unwrap(), OutboxRelease { sent: 2, kept: 0 });
+        assert_eq!(actions(&sent), ["dismiss", "dismiss"]);
+
+        // 设备不在线时留在队列中
+        state.set_watch_only(true).unwrap();
+        state.send_or_hold("phone", crate::capabilities::DISMISSAL, serde_json::json!({ "action": "dismiss", "ids": ["3"] })).unwrap();
+        state.set_watch_only(false).unwrap();
+        state.clients.write().clear();
+        assert_eq!(state.release_outbox().unwrap(), OutboxRelease { sent: 0, kept: 1 });
+        assert_eq!(state.watch_only_status().held, 1);
+    }
+}
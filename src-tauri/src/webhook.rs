@@ -0,0 +1,465 @@
+//! Webhook 推送：每条入库事件 POST 到设置中的 http:// 地址。
+//! 待发送的事件先写入数据目录下的 spool 文件再投递，重启后继续发送（至少一次）；
+//! 每条带 idempotency_key（请求头与正文中各一份），接收方据此去重。
+//! spool 有条数与时长上限，超出时丢弃最旧的并计数。投递成功的确认延迟落盘，正常退出时写入。
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::types::Event;
+
+pub const SPOOL_FILE: &str = "webhook_spool.json";
+/// 投递确认的落盘间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WebhookSettings {
+    /// 推送地址（仅支持 http://），为空时不推送
+    pub url: Option<String>,
+    /// spool 最多保留的待发送条数
+    pub max_pending: usize,
+    /// 待发送事件最长保留时间（秒）
+    pub max_age_secs: i64,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self { url: None, max_pending: 1000, max_age_secs: 24 * 3600 }
+    }
+}
+
+impl WebhookSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(url) = &self.url {
+            parse_url(url)?;
+        }
+        if self.max_pending == 0 {
+            return Err("webhook.max_pending must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    pub idempotency_key: String,
+    pub created_at: i64,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SpoolFile {
+    pending: VecDeque<Delivery>,
+    dropped_oldest: u64,
+    dropped_expired: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookStatus {
+    pub active: bool,
+    pub url: Option<String>,
+    pub pending: usize,
+    pub delivered: u64,
+    pub dropped_oldest: u64,
+    pub dropped_expired: u64,
+    pub last_error: Option<String>,
+}
+
+struct Spool {
+    file: SpoolFile,
+    path: Option<PathBuf>,
+    settings: WebhookSettings,
+    delivered: u64,
+    last_error: Option<String>,
+    // 有未落盘的投递确认
+    dirty: bool,
+    last_flush: Instant,
+}
+
+impl Spool {
+    fn load(path: Option<PathBuf>, settings: WebhookSettings) -> Self {
+        let file = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        Self { file, path, settings, delivered: 0, last_error: None, dirty: false, last_flush: Instant::now() }
+    }
+
+    fn flush(&mut self) {
+        self.dirty = false;
+        self.last_flush = Instant::now();
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string(&self.file)
+            .map_err(|e| format!("Failed to serialize webhook spool: {}", e))
+            .and_then(|json| crate::storage::write_atomic(path, json.as_bytes()));
+        if let Err(e) = result {
+            println!("[Webhook] {}", e);
+        }
+    }
+
+    /// 丢弃超时的事件
+    fn prune(&mut self, now: i64) {
+        let before = self.file.pending.len();
+        let max_age = self.settings.max_age_secs;
+        self.file.pending.retain(|d| now - d.created_at <= max_age);
+        let expired = (before - self.file.pending.len()) as u64;
+        if expired > 0 {
+            self.file.dropped_expired = self.file.dropped_expired.saturating_add(expired);
+            self.dirty = true;
+        }
+    }
+
+    fn push(&mut self, delivery: Delivery) {
+        while self.file.pending.len() >= self.settings.max_pending {
+            self.file.pending.pop_front();
+            self.file.dropped_oldest = self.file.dropped_oldest.saturating_add(1);
+        }
+        self.file.pending.push_back(delivery);
+        // 新事件立即落盘，保证至少一次
+        self.flush();
+    }
+
+    fn ack(&mut self, key: &str) {
+        if let Some(pos) = self.file.pending.iter().position(|d| d.idempotency_key == key) {
+            self.file.pending.remove(pos);
+            self.delivered = self.delivered.saturating_add(1);
+            self.dirty = true;
+        }
+        if self.dirty && (self.file.pending.is_empty() || self.last_flush.elapsed() >= FLUSH_INTERVAL) {
+            self.flush();
+        }
+    }
+}
+
+struct Shared {
+    url: String,
+    spool: Mutex<Spool>,
+    wake: Condvar,
+    stop: AtomicBool,
+}
+
+impl Shared {
+    /// 等待新事件或停止；返回 false 表示已停止
+    fn wait(&self, timeout: Duration) -> bool {
+        let mut spool = self.spool.lock();
+        if !self.stop.load(Ordering::SeqCst) {
+            self.wake.wait_for(&mut spool, timeout);
+        }
+        !self.stop.load(Ordering::SeqCst)
+    }
+}
+
+pub struct WebhookDispatcher {
+    shared: Arc<Shared>,
+    worker: Option<std::thread::JoinHandle<()>>,
+}
+
+impl WebhookDispatcher {
+    /// 加载 spool 并开始投递（spool_path 为 None 时只在内存中排队）
+    pub fn start(url: String, spool_path: Option<PathBuf>, settings: WebhookSettings) -> Result<Self, String> {
+        parse_url(&url)?;
+        let mut spool = Spool::load(spool_path, settings);
+        spool.prune(chrono::Utc::now().timestamp());
+        if !spool.file.pending.is_empty() {
+            println!("[Webhook] Resuming {} pending deliveries", spool.file.pending.len());
+        }
+        let shared = Arc::new(Shared { url, spool: Mutex::new(spool), wake: Condvar::new(), stop: AtomicBool::new(false) });
+        let thread_shared = shared.clone();
+        let worker = std::thread::Builder::new()
+            .name("webhook".into())
+            .spawn(move || run_worker(&thread_shared))
+            .map_err(|e| format!("Failed to start webhook dispatcher: {}", e))?;
+        Ok(Self { shared, worker: Some(worker) })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.shared.url
+    }
+
+    pub fn enqueue(&self, payload: serde_json::Value, now: i64) {
+        let delivery = Delivery { idempotency_key: uuid::Uuid::new_v4().to_string(), created_at: now, payload };
+        self.shared.spool.lock().push(delivery);
+        self.shared.wake.notify_one();
+    }
+
+    pub fn status(&self) -> WebhookStatus {
+        let spool = self.shared.spool.lock();
+        WebhookStatus {
+            active: !self.shared.stop.load(Ordering::SeqCst),
+            url: Some(self.shared.url.clone()),
+            pending: spool.file.pending.len(),
+            delivered: spool.delivered,
+            dropped_oldest: spool.file.dropped_oldest,
+            dropped_expired: spool.file.dropped_expired,
+            last_error: spool.last_error.clone(),
+        }
+    }
+
+    /// 正常退出：等当前请求结束，把 spool 写入磁盘
+    pub fn shutdown(mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        self.shared.wake.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        self.shared.spool.lock().flush();
+    }
+}
+
+impl Drop for WebhookDispatcher {
+    // 未经 shutdown 直接丢弃（相当于进程被杀）：不落盘，未确认的事件下次启动时重发
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        self.shared.wake.notify_all();
+    }
+}
+
+fn run_worker(shared: &Shared) {
+    let mut backoff = Duration::from_secs(1);
+    while !shared.stop.load(Ordering::SeqCst) {
+        let next = {
+            let mut spool = shared.spool.lock();
+            spool.prune(chrono::Utc::now().timestamp());
+            let next = spool.file.pending.front().cloned();
+            if next.is_none() && spool.dirty {
+                spool.flush();
+            }
+            next
+        };
+        let Some(delivery) = next else {
+            shared.wait(FLUSH_INTERVAL);
+            continue;
+        };
+        match post(&shared.url, &delivery) {
+            Ok(()) => {
+                backoff = Duration::from_secs(1);
+                let mut spool = shared.spool.lock();
+                spool.last_error = None;
+                spool.ack(&delivery.idempotency_key);
+            }
+            Err(e) => {
+                println!("[Webhook] Delivery failed, retrying in {:?}: {}", backoff, e);
+                shared.spool.lock().last_error = Some(e);
+                if !shared.wait(backoff) {
+                    break;
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+    println!("[Webhook] Dispatcher stopped");
+}
+
+/// 解析 http://host[:port][/path]，返回 (host:port, path)
+pub(crate) fn parse_url(url: &str) -> Result<(String, String), String> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| format!("Unsupported webhook url {:?}, only http:// is supported", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        return Err(format!("Invalid webhook url {:?}", url));
+    }
+    let authority = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    Ok((authority, path.to_string()))
+}
+
+fn post(url: &str, delivery: &Delivery) -> Result<(), String> {
+    let body = serde_json::json!({
+        "idempotency_key": delivery.idempotency_key,
+        "created_at": delivery.created_at,
+        "event": delivery.payload,
+    })
+    .to_string();
+    let response = http_post(url, &[("Idempotency-Key", &delivery.idempotency_key)], &body)?;
+    if (200..300).contains(&response.status) {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status_line))
+    }
+}
+
+pub(crate) struct HttpResponse {
+    pub status: u16,
+    pub status_line: String,
+    pub body: String,
+}
+
+/// 最简 HTTP/1.1 POST（JSON，仅 http://），读取状态行与响应体
+pub(crate) fn http_post(url: &str, headers: &[(&str, &str)], body: &str) -> Result<HttpResponse, String> {
+    let (authority, path) = parse_url(url)?;
+    let mut stream = TcpStream::connect(&authority).map_err(|e| format!("Connect error: {}", e))?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).map_err(|e| format!("Set timeout error: {}", e))?;
+    let extra: String = headers.iter().map(|(k, v)| format!("{}: {}\r\n", k, v)).collect();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        authority,
+        extra,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).map_err(|e| format!("Write error: {}", e))?;
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).map_err(|e| format!("Read error: {}", e))?;
+    let status: u16 = status_line.split_whitespace().nth(1).and_then(|s| s.parse().ok()).unwrap_or(0);
+    // 响应体尽力读取（Connection: close，读到对端关闭或超时为止）
+    let mut rest = String::new();
+    let _ = std::io::Read::read_to_string(&mut reader, &mut rest);
+    let body = rest.split_once("\r\n\r\n").map(|(_, b)| b.to_string()).unwrap_or_default();
+    Ok(HttpResponse { status, status_line: status_line.trim().to_string(), body })
+}
+
+/// AppState 中的 webhook 推送（未配置地址时为空）
+#[derive(Default)]
+pub struct Webhook {
+    dispatcher: Mutex<Option<WebhookDispatcher>>,
+}
+
+impl AppState {
+    /// 按设置启动/重启/停止推送（设置加载后与修改后调用）
+    pub fn apply_webhook_settings(&self) {
+        let settings = self.settings.read().webhook.clone();
+        let mut dispatcher = self.webhook.dispatcher.lock();
+        if dispatcher.as_ref().map(|d| d.url()) == settings.url.as_deref() {
+            return;
+        }
+        if let Some(old) = dispatcher.take() {
+            old.shutdown();
+        }
+        let Some(url) = settings.url.clone() else {
+            return;
+        };
+        let spool = self.storage.base_dir().map(|d| d.join(SPOOL_FILE));
+        match WebhookDispatcher::start(url.clone(), spool, settings) {
+            Ok(d) => {
+                println!("[Webhook] Delivering to {}", url);
+                *dispatcher = Some(d);
+            }
+            Err(e) => println!("[Webhook] {}", e),
+        }
+    }
+
+    pub(crate) fn webhook_enqueue(&self, connection_id: &str, event: &Event, now: i64) {
+        if let Some(d) = self.webhook.dispatcher.lock().as_ref() {
+            d.enqueue(serde_json::json!({ "connection_id": connection_id, "event": event }), now);
+        }
+    }
+
+    pub fn webhook_status(&self) -> WebhookStatus {
+        self.webhook.dispatcher.lock().as_ref().map(|d| d.status()).unwrap_or_default()
+    }
+
+    /// 退出时停止投递并把 spool 写入磁盘
+    pub fn shutdown_webhook(&self) {
+        if let Some(d) = self.webhook.dispatcher.lock().take() {
+            d.shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    /// 模拟接收方：记录收到的 idempotency_key（每个请求稍作延迟）
+    fn mock_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(v) = line.to_lowercase().strip_prefix("content-length:") {
+                        len = v.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+                log.lock().push(body["idempotency_key"].as_str().unwrap().to_string());
+                stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn wait_until(f: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !f() {
+            assert!(start.elapsed() < Duration::from_secs(10), "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_spool_survives_dispatcher_kill() {
+        let dir = crate::storage::temp_dir("webhook");
+        let spool = dir.join(SPOOL_FILE);
+        let (url, received) = mock_server();
+        let now = chrono::Utc::now().timestamp();
+
+        let first = WebhookDispatcher::start(url.clone(), Some(spool.clone()), WebhookSettings::default()).unwrap();
+        for i in 0..30 {
+            first.enqueue(serde_json::json!({ "n": i }), now);
+        }
+        wait_until(|| received.lock().len() >= 3);
+        drop(first);
+
+        let second = WebhookDispatcher::start(url, Some(spool.clone()), WebhookSettings::default()).unwrap();
+        wait_until(|| second.status().pending == 0);
+        second.shutdown();
+
+        let keys = received.lock().clone();
+        assert_eq!(keys.iter().collect::<HashSet<_>>().len(), 30);
+        let on_disk: SpoolFile = serde_json::from_str(&std::fs::read_to_string(spool).unwrap()).unwrap();
+        assert!(on_disk.pending.is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_spool_caps_size_and_age() {
+        let mut spool = Spool::load(None, WebhookSettings { url: None, max_pending: 3, max_age_secs: 60 });
+        for i in 0..5 {
+            spool.push(Delivery { idempotency_key: i.to_string(), created_at: i * 30, payload: serde_json::Value::Null });
+        }
+        assert_eq!(spool.file.dropped_oldest, 2);
+        spool.prune(150);
+        let keys: Vec<&str> = spool.file.pending.iter().map(|d| d.idempotency_key.as_str()).collect();
+        assert_eq!(keys, ["3", "4"]);
+        assert_eq!(spool.file.dropped_expired, 1);
+        assert!(parse_url("https://example.com").is_err());
+        assert_eq!(parse_url("http://example.com").unwrap(), ("example.com:80".to_string(), "/".to_string()));
+    }
+}
@@ -0,0 +1,240 @@
+//! 主窗口隐藏时的事件降级：窗口隐藏到托盘后，WebView 可能被系统挂起，逐条推送既浪费，
+//! 在 Windows 上还可能丢事件，重新打开时列表是旧的。
+//! 隐藏期间，逐条的列表类事件（HIDDEN_BUFFERED_EVENTS）不再推送，而是编号后存入环形缓冲；
+//! 计数、托盘与系统通知照常工作。窗口重新显示时推送一次 `window-restored`，
+//! 带上需要补齐的序号范围 (after_seq, until_seq]，前端用 get_events_since 取回后按序应用。
+//! 显示之后的事件直接推送，序号都大于 until_seq，不会与补齐的事件重复。
+//! 缓冲溢出时 complete 为 false，前端应重新拉取整个列表。
+
+use std::collections::VecDeque;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::AppState;
+use crate::event_batch::{BATCH_EVENT, STORE_CHANGED_EVENT};
+
+/// 隐藏期间改为缓冲的事件
+pub const HIDDEN_BUFFERED_EVENTS: &[&str] = &[
+    "notification-added",
+    "notification-updated",
+    "notification-removed",
+    "notifications-bulk",
+    "notifications-snoozed",
+    "notifications-cleared",
+    BATCH_EVENT,
+    STORE_CHANGED_EVENT,
+    "media-state-changed",
+    "connection-trace",
+];
+pub const RESTORED_EVENT: &str = "window-restored";
+/// 缓冲上限，超出时丢弃最早的事件
+pub const FEED_CAPACITY: usize = 5000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BufferedEvent {
+    pub seq: u64,
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+/// window-restored 的内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowRestored {
+    /// 需要补齐 (after_seq, until_seq] 范围内的事件
+    pub after_seq: u64,
+    pub until_seq: u64,
+    pub missed: u64,
+    /// false 表示缓冲溢出，部分事件已丢弃，应重新拉取列表
+    pub complete: bool,
+    pub hidden_secs: i64,
+}
+
+#[derive(Debug)]
+struct HiddenSpan {
+    after_seq: u64,
+    since: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct WindowFeed {
+    /// 最近一次缓冲的事件序号
+    last_seq: u64,
+    ring: VecDeque<BufferedEvent>,
+    /// 主窗口启动时可见（见 tauri.conf.json）
+    hidden: Option<HiddenSpan>,
+}
+
+impl WindowFeed {
+    pub fn is_visible(&self) -> bool {
+        self.hidden.is_none()
+    }
+
+    /// 窗口隐藏；已隐藏时返回 false
+    pub fn hide(&mut self, now: i64) -> bool {
+        if self.hidden.is_some() {
+            return false;
+        }
+        self.hidden = Some(HiddenSpan { after_seq: self.last_seq, since: now });
+        true
+    }
+
+    /// 隐藏期间缓冲该事件时返回 true（调用方不再推送）
+    pub fn buffer(&mut self, event: &str, payload: impl FnOnce() -> serde_json::Value) -> bool {
+        if self.hidden.is_none() || !HIDDEN_BUFFERED_EVENTS.contains(&event) {
+            return false;
+        }
+        self.last_seq += 1;
+        if self.ring.len() == FEED_CAPACITY {
+            self.ring.pop_front();
+        }
+        self.ring.push_back(BufferedEvent { seq: self.last_seq, event: event.to_string(), payload: payload() });
+        true
+    }
+
+    /// 缓冲的事件数
+    pub fn buffered(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// 窗口显示；之前处于隐藏状态时返回需要补齐的范围
+    pub fn show(&mut self, now: i64) -> Option<WindowRestored> {
+        let span = self.hidden.take()?;
+        let first_kept = self.ring.front().map_or(self.last_seq + 1, |e| e.seq);
+        Some(WindowRestored {
+            after_seq: span.after_seq,
+            until_seq: self.last_seq,
+            missed: self.last_seq - span.after_seq,
+            complete: first_kept <= span.after_seq + 1,
+            hidden_secs: now - span.since,
+        })
+    }
+
+    /// 序号在 (after_seq, until_seq] 内的缓冲事件；范围起点已被丢弃时返回错误
+    pub fn since(&self, after_seq: u64, until_seq: Option<u64>) -> Result<Vec<BufferedEvent>, String> {
+        let until = until_seq.unwrap_or(self.last_seq);
+        if let Some(first) = self.ring.front() {
+            if first.seq > after_seq + 1 && until > after_seq {
+                return Err(format!("Events after seq {} are no longer buffered (oldest is {})", after_seq, first.seq));
+            }
+        }
+        Ok(self.ring.iter().filter(|e| e.seq > after_seq && e.seq <= until).cloned().collect())
+    }
+}
+
+impl AppState {
+    /// 主窗口显示/隐藏（由窗口事件与托盘操作调用）
+    pub fn set_window_visible(&self, visible: bool) {
+        // 只用于计算隐藏时长
+        let now = crate::wall_clock::mono_secs();
+        if !visible {
+            if self.events.window_feed().hide(now) {
+                println!("[Window] Hidden, buffering list events");
+            }
+            return;
+        }
+        let restored = self.events.window_feed().show(now);
+        if let Some(restored) = restored {
+            println!(
+                "[Window] Restored after {}s, {} events to catch up (complete={})",
+                restored.hidden_secs, restored.missed, restored.complete
+            );
+            self.events.emit(RESTORED_EVENT, restored);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Event, Notification};
+
+    fn added(state: &AppState, id: &str) {
+        state.ingest_event(Event {
+            event_type: "added".into(),
+            seq: 0,
+            notification: Some(Notification { id: id.into(), title: Some(id.into()), ..Default::default() }),
+            id: None,
+        });
+    }
+
+    fn names(events: &[(String, serde_json::Value)]) -> Vec<&str> {
+        events.iter().map(|(e, _)| e.as_str()).collect()
+    }
+
+    #[test]
+    fn test_visibility_state_machine() {
+        let mut feed = WindowFeed::default();
+        assert!(feed.is_visible());
+        assert!(!feed.buffer("notification-added", || serde_json::Value::Null));
+        assert_eq!(feed.show(0), None, "showing a visible window restores nothing");
+
+        assert!(feed.hide(100));
+        assert!(!feed.hide(150));
+        assert!(feed.buffer("notification-added", || 1.into()));
+        assert!(!feed.buffer("counts-changed", || 2.into()), "counts keep flowing while hidden");
+        assert!(feed.buffer("notification-removed", || 3.into()));
+        let restored = feed.show(400).unwrap();
+        assert_eq!(restored, WindowRestored { after_seq: 0, until_seq: 2, missed: 2, complete: true, hidden_secs: 300 });
+        assert_eq!(feed.show(500), None);
+
+        // 隐藏期间没有事件
+        feed.hide(600);
+        let restored = feed.show(601).unwrap();
+        assert_eq!((restored.after_seq, restored.until_seq, restored.missed), (2, 2, 0));
+        assert!(feed.since(restored.after_seq, Some(restored.until_seq)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_overflow_marks_incomplete() {
+        let mut feed = WindowFeed::default();
+        feed.hide(0);
+        for i in 0..FEED_CAPACITY + 10 {
+            feed.buffer("notification-added", || i.into());
+        }
+        let restored = feed.show(1).unwrap();
+        assert!(!restored.complete);
+        assert!(feed.since(restored.after_seq, Some(restored.until_seq)).is_err());
+        // 溢出之后的范围仍可取回
+        let tail = feed.since(restored.until_seq - 5, None).unwrap();
+        assert_eq!(tail.len(), 5);
+    }
+
+    /// 多次隐藏/显示：每条事件要么实时推送，要么在补齐范围内，且只出现一次
+    #[test]
+    fn test_catch_up_handoff_across_cycles() {
+        let state = AppState::default();
+        added(&state, "live-1");
+        assert!(names(&state.events.take_captured()).contains(&"notification-added"));
+        state.set_window_visible(false);
+        added(&state, "hidden-1");
+        added(&state, "hidden-2");
+        let while_hidden = state.events.take_captured();
+        assert!(names(&while_hidden).contains(&"counts-changed"));
+        assert!(!names(&while_hidden).contains(&"notification-added"));
+
+        state.set_window_visible(true);
+        added(&state, "live-2");
+        state.set_window_visible(false);
+        added(&state, "hidden-3");
+        state.set_window_visible(true);
+
+        let captured = state.events.take_captured();
+        let mut seen: Vec<String> = Vec::new();
+        for (event, payload) in &captured {
+            match event.as_str() {
+                "notification-added" => seen.push(payload["id"].as_str().unwrap().to_string()),
+                RESTORED_EVENT => {
+                    let r: WindowRestored = serde_json::from_value(payload.clone()).unwrap();
+                    assert!(r.complete);
+                    let missed = state.events.window_feed().since(r.after_seq, Some(r.until_seq)).unwrap();
+                    assert_eq!(missed.len() as u64, r.missed);
+                    seen.extend(missed.iter().map(|e| e.payload["id"].as_str().unwrap().to_string()));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(seen, ["hidden-1", "hidden-2", "live-2", "hidden-3"]);
+        assert_eq!(names(&captured).iter().filter(|e| **e == RESTORED_EVENT).count(), 2);
+    }
+}
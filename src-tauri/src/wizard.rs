@@ -0,0 +1,328 @@
+//! 手动连接向导：输入地址 -> 探测可达 -> 登录或请求授权 -> 完成。
+//! 向导会话在后端维护，未完成前连接只属于会话，不进入连接池；
+//! 取消或过期（10 分钟）时会话连同已建立的连接一起丢弃，不会留下半成品连接。
+//! 前端只根据返回的 WizardView 渲染状态，认证过程推送 wizard-progress。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::android_client::AndroidSocketClient;
+use crate::commands::AppState;
+use crate::same_network::SameNetworkHint;
+
+/// 会话有效期（秒）
+pub const SESSION_TTL_SECS: i64 = 10 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WizardStep {
+    /// 等待输入地址
+    Target,
+    /// 已连上，等待认证
+    Authenticate,
+    /// 认证进行中
+    Authenticating,
+    /// 认证成功，等待完成
+    Ready,
+}
+
+/// 返回给前端的会话状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WizardView {
+    pub session_id: String,
+    pub connection_id: String,
+    pub step: WizardStep,
+    pub host: Option<String>,
+    /// 上一步失败的原因（可修改后重试）
+    pub error: Option<String>,
+    /// 探测失败时的同网络检查结果
+    pub network_hint: Option<SameNetworkHint>,
+    pub expires_at: i64,
+}
+
+struct WizardSession {
+    connection_id: String,
+    step: WizardStep,
+    host: Option<String>,
+    error: Option<String>,
+    network_hint: Option<SameNetworkHint>,
+    client: Option<AndroidSocketClient>,
+    token: Option<String>,
+    expires_at: i64,
+}
+
+impl WizardSession {
+    fn view(&self, session_id: &str) -> WizardView {
+        WizardView {
+            session_id: session_id.to_string(),
+            connection_id: self.connection_id.clone(),
+            step: self.step,
+            host: self.host.clone(),
+            error: self.error.clone(),
+            network_hint: self.network_hint.clone(),
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SetupWizard {
+    sessions: parking_lot::Mutex<HashMap<String, WizardSession>>,
+}
+
+impl SetupWizard {
+    /// 丢弃过期会话（连同其连接），返回丢弃的个数
+    pub fn purge_expired(&self, now: i64) -> usize {
+        let mut sessions = self.sessions.lock();
+        let before = sessions.len();
+        sessions.retain(|id, s| {
+            let alive = s.expires_at > now;
+            if !alive {
+                println!("[Wizard] Session {} expired", id);
+            }
+            alive
+        });
+        before - sessions.len()
+    }
+
+    /// 对未过期的会话执行 f
+    fn with<T>(&self, session_id: &str, now: i64, f: impl FnOnce(&mut WizardSession) -> Result<T, String>) -> Result<T, String> {
+        self.purge_expired(now);
+        let mut sessions = self.sessions.lock();
+        let session = sessions.get_mut(session_id).ok_or("Setup session not found or expired")?;
+        f(session)
+    }
+}
+
+fn progress(state: &AppState, session_id: &str, stage: &str, error: Option<&str>) {
+    state.events.emit(
+        "wizard-progress",
+        serde_json::json!({ "session_id": session_id, "stage": stage, "error": error }),
+    );
+}
+
+impl AppState {
+    pub fn begin_manual_setup(&self, connection_id: Option<String>, now: i64) -> WizardView {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let session = WizardSession {
+            connection_id: connection_id.unwrap_or_else(|| format!("manual-{}", &session_id[..8])),
+            step: WizardStep::Target,
+            host: None,
+            error: None,
+            network_hint: None,
+            client: None,
+            token: None,
+            expires_at: now + SESSION_TTL_SECS,
+        };
+        let view = session.view(&session_id);
+        self.wizard.purge_expired(now);
+        self.wizard.sessions.lock().insert(session_id.clone(), session);
+        println!("[Wizard] Began session {} for {}", session_id, view.connection_id);
+        view
+    }
+
+    /// 设置目标地址并探测可达；可在认证前重复设置（旧连接被替换）
+    pub fn wizard_set_target(
+        &self,
+        session_id: &str,
+        host: &str,
+        port: u16,
+        now: i64,
+        connect: impl FnOnce(&str, String) -> Result<AndroidSocketClient, String>,
+    ) -> Result<WizardView, String> {
+        let host = host.trim();
+        if host.is_empty() || port == 0 {
+            return Err(format!("Invalid address {}:{}", host, port));
+        }
+        let addr = format!("{}:{}", host, port);
+        let connection_id = self.wizard.with(session_id, now, |s| match s.step {
+            WizardStep::Target | WizardStep::Authenticate => Ok(s.connection_id.clone()),
+            step => Err(format!("Cannot change target in step {:?}", step)),
+        })?;
+        // 探测期间不持锁
+        let probe = connect(&addr, connection_id);
+        let network_hint = probe.as_ref().err().and_then(|_| self.network_hint_for(host));
+        self.wizard.with(session_id, now, |s| {
+            s.host = Some(addr.clone());
+            match probe {
+                Ok(client) => {
+                    s.client = Some(client);
+                    s.step = WizardStep::Authenticate;
+                    s.error = None;
+                    s.network_hint = None;
+                }
+                Err(e) => {
+                    println!("[Wizard] {} unreachable: {}", addr, e);
+                    s.client = None;
+                    s.step = WizardStep::Target;
+                    s.error = Some(e);
+                    s.network_hint = network_hint;
+                }
+            }
+            Ok(s.view(session_id))
+        })
+    }
+
+    /// 有 token 时登录，否则请求手机端授权；失败后可重试
+    pub fn wizard_authenticate(&self, session_id: &str, token: Option<String>, now: i64) -> Result<WizardView, String> {
+        let client = self.wizard.with(session_id, now, |s| {
+            if s.step != WizardStep::Authenticate {
+                return Err(format!("Cannot authenticate in step {:?}", s.step));
+            }
+            s.step = WizardStep::Authenticating;
+            s.client.take().ok_or_else(|| "No connection for this session".to_string())
+        })?;
+
+        progress(self, session_id, if token.is_some() { "logging_in" } else { "requesting_token" }, None);
+        let result = match token {
+            Some(t) => client.login(&t).map(|_| t),
+            None => client.request_token(),
+        };
+        match &result {
+            Ok(_) => progress(self, session_id, "authenticated", None),
+            Err(e) => progress(self, session_id, "failed", Some(e)),
+        }
+
+        // 认证期间被取消或过期：连接随 client 一起丢弃
+        self.wizard.with(session_id, now, |s| {
+            s.client = Some(client);
+            match result {
+                Ok(token) => {
+                    s.token = Some(token);
+                    s.step = WizardStep::Ready;
+                    s.error = None;
+                }
+                Err(e) => {
+                    s.step = WizardStep::Authenticate;
+                    s.error = Some(e);
+                }
+            }
+            Ok(s.view(session_id))
+        })
+    }
+
+    /// 完成：记录连接信息并把连接加入连接池，返回 token
+    pub fn wizard_finish(&self, session_id: &str, now: i64) -> Result<(WizardView, String), String> {
+        self.wizard.purge_expired(now);
+        let session = {
+            let mut sessions = self.wizard.sessions.lock();
+            match sessions.get(session_id).map(|s| s.step) {
+                None => return Err("Setup session not found or expired".to_string()),
+                Some(WizardStep::Ready) => sessions.remove(session_id).unwrap(),
+                Some(step) => return Err(format!("Cannot finish in step {:?}", step)),
+            }
+        };
+        let view = session.view(session_id);
+        let (Some(client), Some(token)) = (session.client, session.token) else {
+            return Err("Session has no authenticated connection".to_string());
+        };
+        self.register_client(session.connection_id, client, &token, session.host.as_deref());
+        println!("[Wizard] Session {} finished", session_id);
+        Ok((view, token))
+    }
+
+    /// 取消并丢弃会话（连同已建立的连接）
+    pub fn wizard_cancel(&self, session_id: &str) -> bool {
+        let removed = self.wizard.sessions.lock().remove(session_id).is_some();
+        if removed {
+            println!("[Wizard] Session {} cancelled", session_id);
+        }
+        removed
+    }
+
+    pub(crate) fn register_client(&self, connection_id: String, mut client: AndroidSocketClient, token: &str, host: Option<&str>) {
+        // 此后发出的所有控制消息（包括下面重新下发的镜像/低功耗状态）都经过观察模式闸门
+        client.set_gate(self.watch_only.clone());
+        if let Some(host) = host {
+            self.remember_endpoint(&connection_id, host, token, client.device_uuid(), client.capabilities());
+        }
+
+        // 之前暂停过镜像的设备，重连后重新下发暂停
+        self.apply_mirroring_on_connect(&connection_id, &client);
+        // 处于低功耗时段时立即切换
+        self.apply_low_power_on_connect(&connection_id, &client);
+
+        // 保存客户端到连接池
+        self.clients.write().insert(connection_id, Arc::new(client));
+        self.refresh_tray_icon();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Transport;
+
+    /// 按顺序返回预设响应的手机端
+    struct Scripted(Vec<&'static str>);
+
+    impl Transport for Scripted {
+        fn send_line(&mut self, _line: &str) -> Result<(), String> {
+            Ok(())
+        }
+        fn recv_line(&mut self) -> Result<String, String> {
+            if self.0.is_empty() {
+                return Err("closed".to_string());
+            }
+            Ok(self.0.remove(0).to_string())
+        }
+    }
+
+    fn phone(script: Vec<&'static str>) -> impl FnOnce(&str, String) -> Result<AndroidSocketClient, String> {
+        move |_, id| Ok(AndroidSocketClient::with_transport(Box::new(Scripted(script)), id, Arc::default()))
+    }
+
+    #[test]
+    fn test_wizard_happy_path_with_retry() {
+        let state = AppState::default();
+        let view = state.begin_manual_setup(Some("pixel".into()), 1000);
+        let id = view.session_id.clone();
+        assert!(state.wizard_authenticate(&id, None, 1000).is_err());
+
+        let failed = state.wizard_set_target(&id, "10.0.0.9", 10035, 1000, |_, _| Err("refused".to_string())).unwrap();
+        assert_eq!((failed.step, failed.error.as_deref()), (WizardStep::Target, Some("refused")));
+
+        let script = vec![r#"{"success":false,"message":"bad token"}"#, r#"{"success":true}"#];
+        let view = state.wizard_set_target(&id, "192.168.1.5", 10035, 1001, phone(script)).unwrap();
+        assert_eq!(view.step, WizardStep::Authenticate);
+        let view = state.wizard_authenticate(&id, Some("wrong".into()), 1002).unwrap();
+        assert_eq!(view.step, WizardStep::Authenticate);
+        assert!(view.error.is_some());
+        // 未完成前不进入连接池
+        assert!(state.clients.read().is_empty());
+
+        let view = state.wizard_authenticate(&id, Some("t1".into()), 1003).unwrap();
+        assert_eq!(view.step, WizardStep::Ready);
+        let (_, token) = state.wizard_finish(&id, 1004).unwrap();
+        assert_eq!(token, "t1");
+        assert!(state.clients.read().contains_key("pixel"));
+        assert_eq!(state.device_endpoint("pixel").unwrap().host, "192.168.1.5:10035");
+        assert!(state.wizard_finish(&id, 1005).is_err());
+
+        let stages: Vec<String> = state
+            .events
+            .take_captured()
+            .into_iter()
+            .filter(|(e, _)| e == "wizard-progress")
+            .map(|(_, p)| p["stage"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(stages, ["logging_in", "failed", "logging_in", "authenticated"]);
+    }
+
+    #[test]
+    fn test_cancel_and_expiry_drop_connection() {
+        let state = AppState::default();
+        let id = state.begin_manual_setup(None, 0).session_id;
+        state.wizard_set_target(&id, "192.168.1.5", 10035, 0, phone(vec![r#"{"success":true}"#])).unwrap();
+        assert!(state.wizard_cancel(&id));
+        assert!(state.wizard_authenticate(&id, Some("t".into()), 1).is_err());
+
+        let id = state.begin_manual_setup(None, 0).session_id;
+        assert!(state.wizard_set_target(&id, "192.168.1.5", 10035, SESSION_TTL_SECS, phone(vec![])).is_err());
+        assert_eq!(state.wizard.purge_expired(SESSION_TTL_SECS), 0);
+        assert!(state.clients.read().is_empty());
+    }
+}
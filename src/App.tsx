@@ -1,4 +1,4 @@
-import { useEffect, useMemo, useState } from "react";
+import { useEffect, useMemo, useRef, useState } from "react";
 import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { invoke } from "@tauri-apps/api/core";
@@ -22,11 +22,20 @@ type Counts = {
   total: number;
 };
 
+// 带存储变更序号的计数（get_counts_versioned / counts-changed 事件）
+type VersionedCounts = Counts & { seq: number; stream_id: string };
+
 function App() {
   const [counts, setCounts] = useState<Counts>({ unread: 0, total: 0 });
+  // 已应用的最新计数序号，乱序到达的旧计数直接丢弃
+  const countsSeqRef = useRef(-1);
+  // 序号所属的流；流变化（恢复出厂设置）时序号重新开始
+  const streamIdRef = useRef("");
   const [items, setItems] = useState<Notification[]>([]);
   const [selected, setSelected] = useState<Record<string, boolean>>({});
   const [loading, setLoading] = useState(false);
+  // 后端 store 加载完成前显示加载页
+  const [storeReady, setStoreReady] = useState(false);
   const [error, setError] = useState<string | null>(null);
 
   // 添加连接管理相关状态
@@ -49,15 +58,27 @@ function App() {
     }
   };
 
+  function applyCounts(c: VersionedCounts) {
+    if (c.stream_id !== streamIdRef.current) {
+      streamIdRef.current = c.stream_id;
+      countsSeqRef.current = -1;
+    }
+    if (c.seq < countsSeqRef.current) {
+      return;
+    }
+    countsSeqRef.current = c.seq;
+    setCounts({ unread: c.unread, total: c.total });
+  }
+
   async function refreshAll() {
     setLoading(true);
     setError(null);
     try {
       const [c, list] = await Promise.all([
-        invoke<Counts>("get_counts"),
+        invoke<VersionedCounts>("get_counts_versioned"),
         invoke<Notification[]>("list_notifications"),
       ]);
-      setCounts(c);
+      applyCounts(c);
       setItems(list);
       try {
         await invoke("set_tray_tooltip", { text: `未读 ${c.unread} / 总数 ${c.total}` });
@@ -73,8 +94,28 @@ function App() {
     }
   }
 
+  // store 就绪后用快照填充主窗口；未就绪时等待 subsystem-ready
+  async function hydrate() {
+    try {
+      const snap = await invoke<{ counts: VersionedCounts }>("get_startup_snapshot");
+      applyCounts(snap.counts);
+      setStoreReady(true);
+      await refreshAll();
+    } catch (e) {
+      if (!String(e).startsWith("NotReady")) {
+        setError(String(e));
+      }
+    }
+  }
+
   useEffect(() => {
-    refreshAll();
+    hydrate();
+
+    const unlistenReady = listen<string>("subsystem-ready", (event) => {
+      if (event.payload === "store") {
+        hydrate();
+      }
+    });
 
     // 监听后端发来的"打开设置"事件
     const unlistenPromise = listen("open-settings", () => {
@@ -82,8 +123,14 @@ function App() {
       setShowSettings(true);
     });
 
+    const unlistenCounts = listen<VersionedCounts>("counts-changed", (event) => {
+      applyCounts(event.payload);
+    });
+
     return () => {
+      unlistenReady.then((un) => un());
       unlistenPromise.then((un) => un());
+      unlistenCounts.then((un) => un());
     };
   }, []);
 
@@ -133,6 +180,16 @@ function App() {
     }
   }
 
+  // 后端仍在加载本地数据
+  if (!storeReady) {
+    return (
+      <main className="container">
+        <p style={{ textAlign: "center", marginTop: "30vh", color: "#888" }}>正在加载…</p>
+        {error && <p style={{ color: "#e5484d", textAlign: "center" }}>{error}</p>}
+      </main>
+    );
+  }
+
   // 如果显示设置页面，渲染连接管理界面
   if (showSettings) {
     return (
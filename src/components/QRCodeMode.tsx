@@ -1,5 +1,6 @@
 import React, { useState, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import QRCode from 'qrcode';
 import { Connection } from '../types/connection';
 import { ConnectionStorage } from '../types/connectionStorage';
@@ -29,6 +30,15 @@ type QRCodeModeState =
   | { type: 'pairing'; port: number; qrcodeUrl: string }
   | { type: 'error'; message: string };
 
+// 后端 pairing-hint 事件（端口绑定失败 / 局域网自检失败）
+interface PairingHint {
+  port: number;
+  kind: string;
+  message: string;
+  hint: string;
+  process: string | null;
+}
+
 const STORAGE_KEY_PORT = 'qrcode_server_port';
 
 const QRCodeMode: React.FC<QRCodeModeProps> = ({ onConnectionAdded }) => {
@@ -39,6 +49,18 @@ const QRCodeMode: React.FC<QRCodeModeProps> = ({ onConnectionAdded }) => {
   const [state, setState] = useState<QRCodeModeState>({ type: 'idle' });
   const canvasRef = useRef<HTMLCanvasElement>(null);
   const autoStartInProgressRef = useRef<boolean>(false); // 防止重复自动启动
+  const [hint, setHint] = useState<PairingHint | null>(null);
+
+  // 监听后端的配对提示
+  useEffect(() => {
+    const unlistenPromise = listen<PairingHint>('pairing-hint', (event) => {
+      console.log('[QRCodeMode] pairing-hint:', event.payload);
+      setHint(event.payload);
+    });
+    return () => {
+      unlistenPromise.then((un) => un());
+    };
+  }, []);
 
   // 组件挂载时查询后端状态
   useEffect(() => {
@@ -293,6 +315,19 @@ const QRCodeMode: React.FC<QRCodeModeProps> = ({ onConnectionAdded }) => {
           {state.type === 'pairing' ? '正在配对中...' : '等待安卓设备扫码连接...'}
         </div>
 
+        {hint && hint.port === state.port && (
+          <div style={{
+            padding: '10px',
+            backgroundColor: '#fff8e1',
+            border: '1px solid #ffe082',
+            borderRadius: '4px',
+            color: '#8a6d00',
+            marginBottom: '15px'
+          }}>
+            {hint.hint}
+          </div>
+        )}
+
         <div style={{ fontSize: '13px', color: '#888', lineHeight: '1.6' }}>
           请在安卓设备上扫描二维码<br />
           扫码后设备将自动连接